@@ -45,7 +45,26 @@ fn main() -> anyhow::Result<()> {
                     wast_processor,
                 )?;
                 test_directory_module(spectests, "tests/wast/spec/proposals/simd", wast_processor)?;
-                // test_directory_module(spectests, "tests/wast/spec/proposals/bulk-memory-operations", wast_processor)?;
+                test_directory_module(
+                    spectests,
+                    "tests/wast/spec/proposals/threads",
+                    wast_processor,
+                )?;
+                test_directory_module(
+                    spectests,
+                    "tests/wast/spec/proposals/tail-call",
+                    wast_processor,
+                )?;
+                test_directory_module(
+                    spectests,
+                    "tests/wast/spec/proposals/bulk-memory-operations",
+                    wast_processor,
+                )?;
+                test_directory_module(
+                    spectests,
+                    "tests/wast/spec/proposals/reference-types",
+                    wast_processor,
+                )?;
                 Ok(())
             })?;
             with_test_module(&mut spectests, "wasmer", |spectests| {
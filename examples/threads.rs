@@ -0,0 +1,108 @@
+//! With the threads proposal, several instances running on different
+//! host threads can share a memory, and block on it with
+//! `memory.atomic.wait` until another thread calls
+//! `memory.atomic.notify`.
+//!
+//! This example spawns a guest thread with `Module::spawn_thread`,
+//! which waits on a shared memory until the main thread wakes it up.
+//!
+//! You can run the example directly by executing in Wasmer root:
+//!
+//! ```shell
+//! cargo run --example threads --release --features "cranelift"
+//! ```
+//!
+//! Ready?
+
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread;
+use wasmer::{
+    imports, wat2wasm, Features, Instance, Memory, MemoryType, Module, RuntimeError, Store,
+};
+use wasmer_compiler_cranelift::Cranelift;
+use wasmer_engine_jit::JIT;
+
+fn main() -> anyhow::Result<()> {
+    // Let's declare the Wasm module with the text representation.
+    let wasm_bytes = wat2wasm(
+        br#"
+(module
+  (import "env" "memory" (memory 1 1 shared))
+
+  ;; Blocks while the flag at address 0 is 0.
+  (func (export "wait") (result i32)
+    (memory.atomic.wait32 (i32.const 0) (i32.const 0) (i64.const -1)))
+
+  ;; Wakes the threads waiting on the flag.
+  (func (export "wake") (result i32)
+    (memory.atomic.notify (i32.const 0) (i32.const 1))))
+"#,
+    )?;
+
+    // Shared memories are part of the threads proposal.
+    let mut features = Features::new();
+    features.threads(true);
+    let engine = JIT::new(Cranelift::default()).features(features);
+    let store = Store::new(&engine.engine());
+
+    println!("Compiling module...");
+    let module = Module::new(&store, wasm_bytes)?;
+
+    // A shared memory must declare a maximum size.
+    let memory = Memory::new(&store, MemoryType::new(1, Some(1), true))?;
+
+    // Each guest thread gets its own instance, importing the same memory.
+    let done = Arc::new(AtomicBool::new(false));
+    let waiter = {
+        let memory = memory.clone();
+        let done = done.clone();
+        module.spawn_thread(
+            move || {
+                imports! {
+                    "env" => {
+                        "memory" => memory,
+                    },
+                }
+            },
+            move |instance| {
+                let result = (|| {
+                    let wait = instance
+                        .exports
+                        .get_native_function::<(), i32>("wait")
+                        .map_err(|error| RuntimeError::new(error.to_string()))?;
+
+                    println!("Waiting...");
+                    wait.call()
+                })();
+                done.store(true, Ordering::SeqCst);
+                result
+            },
+        )
+    };
+
+    let import_object = imports! {
+        "env" => {
+            "memory" => memory.clone(),
+        },
+    };
+    let instance = Instance::new(&module, &import_object)?;
+    let wake = instance.exports.get_native_function::<(), i32>("wake")?;
+
+    // Set the flag, then wake the waiter. The flag is checked atomically
+    // with the start of the wait, so the waiter either sees it set and
+    // doesn't block, or is blocked and gets woken by a notification.
+    let flag = unsafe { &*(memory.data_ptr() as *const AtomicU32) };
+    flag.store(1, Ordering::SeqCst);
+    while !done.load(Ordering::SeqCst) {
+        println!("Woke {} thread(s)", wake.call()?);
+        thread::yield_now();
+    }
+
+    let result = waiter.join()?;
+    // 0 means the waiter was woken, and 1 that the flag was already set.
+    println!("Waiter returned {}", result);
+    assert!(result == 0 || result == 1);
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use crate::externals::Tag;
+use crate::types::Val;
+use crate::RuntimeError;
+use std::error::Error;
+use std::fmt;
+use wasmer_vm::{Exception as RuntimeException, Trap};
+
+/// A WebAssembly exception, from the exception handling proposal.
+///
+/// An exception is created from a [`Tag`], and carries values of the types
+/// of the tag. An exception thrown by Wasm code and not caught is returned
+/// to the host as a [`RuntimeError`], from which it can be recovered with
+/// [`Exception::from_runtime_error`].
+///
+/// Host functions can throw an exception to their Wasm caller by returning
+/// it as their error, or a [`RuntimeError`] created from it.
+#[derive(Clone)]
+pub struct Exception {
+    exception: RuntimeException,
+}
+
+impl Exception {
+    /// Create a new `Exception` of the given [`Tag`], carrying `values`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Exception, Store, Tag, TagType, Type, Value};
+    /// # let store = Store::default();
+    /// #
+    /// let t = Tag::new(&store, TagType::new(vec![Type::I32]));
+    /// let e = Exception::new(&t, &[Value::I32(42)]).unwrap();
+    ///
+    /// assert!(e.is(&t));
+    /// assert_eq!(e.payload(), vec![Value::I32(42)]);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the values don't match the parameters of the
+    /// tag type.
+    pub fn new(tag: &Tag, values: &[Val]) -> Result<Self, RuntimeError> {
+        let params = tag.ty().params();
+        if values.len() != params.len()
+            || values
+                .iter()
+                .zip(params.iter())
+                .any(|(v, ty)| v.ty() != *ty)
+        {
+            return Err(RuntimeError::new(format!(
+                "exception values don't match the tag type {}",
+                tag.ty()
+            )));
+        }
+        let payload = values
+            .iter()
+            .map(|value| {
+                let mut raw = 0u128;
+                unsafe {
+                    value.write_value_to(&mut raw as *mut u128 as *mut i128);
+                }
+                raw
+            })
+            .collect();
+        Ok(Self {
+            exception: RuntimeException::new(tag.vm_tag().clone(), payload),
+        })
+    }
+
+    /// Returns the exception that caused `error`, if it was thrown and not
+    /// caught.
+    pub fn from_runtime_error(error: &RuntimeError) -> Option<Self> {
+        error.exception().map(|exception| Self {
+            exception: exception.clone(),
+        })
+    }
+
+    /// Returns whether the exception was created from `tag`.
+    pub fn is(&self, tag: &Tag) -> bool {
+        self.exception.is(tag.vm_tag())
+    }
+
+    /// Returns the values carried by the exception.
+    pub fn payload(&self) -> Vec<Val> {
+        self.exception
+            .tag()
+            .ty()
+            .params()
+            .iter()
+            .zip(self.exception.payload().iter())
+            .map(|(ty, raw)| unsafe {
+                Val::read_value_from(raw as *const u128 as *const i128, *ty)
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for Exception {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Exception")
+            .field("ty", self.exception.tag().ty())
+            .field("payload", &self.payload())
+            .finish()
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.exception)
+    }
+}
+
+impl Error for Exception {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        // The VM finds the exceptions thrown by host functions through the
+        // source chain of their errors.
+        Some(&self.exception)
+    }
+}
+
+impl From<Exception> for RuntimeError {
+    fn from(exception: Exception) -> Self {
+        Self::from_trap(Trap::User(Box::new(exception)))
+    }
+}
@@ -1,7 +1,7 @@
 use crate::exports::{ExportError, Exportable};
 use crate::externals::Extern;
 use crate::store::Store;
-use crate::types::Val;
+use crate::types::{Val, ValFuncRef};
 use crate::FunctionType;
 use crate::NativeFunc;
 use crate::RuntimeError;
@@ -153,6 +153,7 @@ impl Function {
             VMDynamicFunctionContext::from_context(DynamicFunctionWithoutEnv {
                 func: Arc::new(func),
                 function_type: ty.clone(),
+                store: store.clone(),
             });
         // We don't yet have the address with the Wasm ABI signature.
         // The engine linker will replace the address with one pointing to a
@@ -260,6 +261,7 @@ impl Function {
                 env: Box::new(env),
                 func: Arc::new(func),
                 function_type: ty.clone(),
+                store: store.clone(),
             });
 
         let import_init_function_ptr: for<'a> fn(&'a mut _, &'a _) -> Result<(), _> =
@@ -524,18 +526,20 @@ impl Function {
                 )));
             }
             unsafe {
-                arg.write_value_to(slot);
+                arg.write_value_with_store(&self.store, slot);
             }
         }
 
         // Call the trampoline.
         if let Err(error) = unsafe {
-            wasmer_call_trampoline(
-                self.exported.vm_function.vmctx,
-                func.trampoline,
-                self.exported.vm_function.address,
-                values_vec.as_mut_ptr() as *mut u8,
-            )
+            self.store.call_on_guest_stack(|| {
+                wasmer_call_trampoline(
+                    self.exported.vm_function.vmctx,
+                    func.trampoline,
+                    self.exported.vm_function.address,
+                    values_vec.as_mut_ptr() as *mut u8,
+                )
+            })
         } {
             return Err(RuntimeError::from_trap(error));
         }
@@ -544,7 +548,7 @@ impl Function {
         for (index, &value_type) in signature.results().iter().enumerate() {
             unsafe {
                 let ptr = values_vec.as_ptr().add(index);
-                results[index] = Val::read_value_from(ptr, value_type);
+                results[index] = Val::read_value_with_store(&self.store, ptr, value_type);
             }
         }
 
@@ -810,6 +814,7 @@ impl fmt::Debug for Function {
 pub(crate) trait VMDynamicFunction: Send + Sync {
     fn call(&self, args: &[Val]) -> Result<Vec<Val>, RuntimeError>;
     fn function_type(&self) -> &FunctionType;
+    fn store(&self) -> &Store;
 }
 
 #[derive(Clone)]
@@ -817,6 +822,7 @@ pub(crate) struct DynamicFunctionWithoutEnv {
     #[allow(clippy::type_complexity)]
     func: Arc<dyn Fn(&[Val]) -> Result<Vec<Val>, RuntimeError> + 'static + Send + Sync>,
     function_type: FunctionType,
+    store: Store,
 }
 
 impl VMDynamicFunction for DynamicFunctionWithoutEnv {
@@ -826,6 +832,9 @@ impl VMDynamicFunction for DynamicFunctionWithoutEnv {
     fn function_type(&self) -> &FunctionType {
         &self.function_type
     }
+    fn store(&self) -> &Store {
+        &self.store
+    }
 }
 
 pub(crate) struct DynamicFunctionWithEnv<Env>
@@ -836,6 +845,7 @@ where
     #[allow(clippy::type_complexity)]
     func: Arc<dyn Fn(&Env, &[Val]) -> Result<Vec<Val>, RuntimeError> + 'static + Send + Sync>,
     env: Box<Env>,
+    store: Store,
 }
 
 impl<Env: Sized + Clone + 'static + Send + Sync> Clone for DynamicFunctionWithEnv<Env> {
@@ -844,6 +854,7 @@ impl<Env: Sized + Clone + 'static + Send + Sync> Clone for DynamicFunctionWithEn
             env: self.env.clone(),
             function_type: self.function_type.clone(),
             func: self.func.clone(),
+            store: self.store.clone(),
         }
     }
 }
@@ -858,6 +869,9 @@ where
     fn function_type(&self) -> &FunctionType {
         &self.function_type
     }
+    fn store(&self) -> &Store {
+        &self.store
+    }
 }
 
 trait VMDynamicFunctionCall<T: VMDynamicFunction> {
@@ -891,7 +905,11 @@ impl<T: VMDynamicFunction> VMDynamicFunctionCall<T> for VMDynamicFunctionContext
             let func_ty = self.ctx.function_type();
             let mut args = Vec::with_capacity(func_ty.params().len());
             for (i, ty) in func_ty.params().iter().enumerate() {
-                args.push(Val::read_value_from(values_vec.add(i), *ty));
+                args.push(Val::read_value_with_store(
+                    self.ctx.store(),
+                    values_vec.add(i),
+                    *ty,
+                ));
             }
             let returns = self.ctx.call(&args)?;
 
@@ -906,7 +924,7 @@ impl<T: VMDynamicFunction> VMDynamicFunctionCall<T> for VMDynamicFunctionContext
                 )));
             }
             for (i, ret) in returns.iter().enumerate() {
-                ret.write_value_to(values_vec.add(i));
+                ret.write_value_with_store(self.ctx.store(), values_vec.add(i));
             }
             Ok(())
         }));
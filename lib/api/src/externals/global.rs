@@ -1,7 +1,7 @@
 use crate::exports::{ExportError, Exportable};
 use crate::externals::Extern;
 use crate::store::{Store, StoreObject};
-use crate::types::Val;
+use crate::types::{Val, ValFuncRef, ValType};
 use crate::GlobalType;
 use crate::Mutability;
 use crate::RuntimeError;
@@ -67,9 +67,14 @@ impl Global {
             ty: val.ty(),
         });
         unsafe {
-            global
-                .set_unchecked(val.clone())
-                .map_err(|e| RuntimeError::new(format!("create global for {:?}: {}", val, e)))?;
+            match val {
+                Val::FuncRef(_) | Val::ExternRef(_) => {
+                    global.set_raw_unchecked(raw_value(store, &val))
+                }
+                _ => global.set_unchecked(val.clone()).map_err(|e| {
+                    RuntimeError::new(format!("create global for {:?}: {}", val, e))
+                })?,
+            }
         };
 
         Ok(Self {
@@ -125,7 +130,15 @@ impl Global {
     /// assert_eq!(g.get(), Value::I32(1));
     /// ```
     pub fn get(&self) -> Val {
-        self.global.get()
+        match self.ty().ty {
+            ty @ ValType::FuncRef | ty @ ValType::ExternRef => {
+                let raw = self.global.get_raw();
+                unsafe {
+                    Val::read_value_with_store(&self.store, &raw as *const u128 as *const i128, ty)
+                }
+            }
+            _ => self.global.get(),
+        }
     }
 
     /// Sets a custom value [`Val`] to the runtime Global.
@@ -174,9 +187,13 @@ impl Global {
             return Err(RuntimeError::new("cross-`Store` values are not supported"));
         }
         unsafe {
-            self.global
-                .set(val)
-                .map_err(|e| RuntimeError::new(format!("{}", e)))?;
+            match val {
+                Val::FuncRef(_) | Val::ExternRef(_) if val.ty() == self.ty().ty => {
+                    self.global.set_raw(raw_value(&self.store, &val))
+                }
+                _ => self.global.set(val),
+            }
+            .map_err(|e| RuntimeError::new(format!("{}", e)))?;
         }
         Ok(())
     }
@@ -233,3 +250,10 @@ impl<'a> Exportable<'a> for Global {
         }
     }
 }
+
+/// Returns the raw value compiled code sees for the reference `val`.
+fn raw_value(store: &Store, val: &Val) -> u128 {
+    let mut raw = 0u128;
+    unsafe { val.write_value_with_store(store, &mut raw as *mut u128 as *mut i128) };
+    raw
+}
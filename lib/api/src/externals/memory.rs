@@ -2,7 +2,6 @@ use crate::exports::{ExportError, Exportable};
 use crate::externals::Extern;
 use crate::store::Store;
 use crate::{MemoryType, MemoryView};
-use std::convert::TryInto;
 use std::slice;
 use std::sync::Arc;
 use wasmer_engine::{Export, ExportMemory};
@@ -111,7 +110,7 @@ impl Memory {
     pub unsafe fn data_unchecked_mut(&self) -> &mut [u8] {
         let definition = self.memory.vmmemory();
         let def = definition.as_ref();
-        slice::from_raw_parts_mut(def.base, def.current_length.try_into().unwrap())
+        slice::from_raw_parts_mut(def.base, def.current_length)
     }
 
     /// Returns the pointer to the raw bytes of the `Memory`.
@@ -125,7 +124,7 @@ impl Memory {
     pub fn data_size(&self) -> u64 {
         let definition = self.memory.vmmemory();
         let def = unsafe { definition.as_ref() };
-        def.current_length.into()
+        def.current_length as u64
     }
 
     /// Returns the size (in [`Pages`]) of the `Memory`.
@@ -217,7 +216,7 @@ impl Memory {
 
         let length = self.size().bytes().0 / std::mem::size_of::<T>();
 
-        unsafe { MemoryView::new(base as _, length as u32) }
+        unsafe { MemoryView::new(base as _, length) }
     }
 
     pub(crate) fn from_vm_export(store: &Store, wasmer_export: ExportMemory) -> Self {
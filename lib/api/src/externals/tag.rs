@@ -0,0 +1,108 @@
+use crate::exports::{ExportError, Exportable};
+use crate::externals::Extern;
+use crate::store::Store;
+use crate::TagType;
+use std::fmt;
+use std::sync::Arc;
+use wasmer_engine::{Export, ExportTag};
+use wasmer_vm::{Tag as RuntimeTag, VMExportTag};
+
+/// A WebAssembly exception `tag`, from the exception handling proposal.
+///
+/// A tag identifies the exceptions created from it, and gives the types of
+/// the values they carry. Two tags of the same type are still different
+/// tags: a `catch` clause only catches the exceptions of its own tag.
+#[derive(Clone)]
+pub struct Tag {
+    store: Store,
+    tag: Arc<RuntimeTag>,
+}
+
+impl Tag {
+    /// Create a new `Tag` for exceptions carrying values of the types of
+    /// the provided [`TagType`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Store, Tag, TagType, Type};
+    /// # let store = Store::default();
+    /// #
+    /// let t = Tag::new(&store, TagType::new(vec![Type::I32]));
+    ///
+    /// assert_eq!(t.ty().params(), &[Type::I32]);
+    /// ```
+    pub fn new(store: &Store, ty: TagType) -> Self {
+        Self {
+            store: store.clone(),
+            tag: Arc::new(RuntimeTag::new(ty)),
+        }
+    }
+
+    /// Returns the [`TagType`] of the `Tag`.
+    pub fn ty(&self) -> &TagType {
+        self.tag.ty()
+    }
+
+    /// Returns the [`Store`] where the `Tag` belongs.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    pub(crate) fn from_vm_export(store: &Store, wasmer_export: ExportTag) -> Self {
+        Self {
+            store: store.clone(),
+            tag: wasmer_export.vm_tag.from,
+        }
+    }
+
+    pub(crate) fn vm_tag(&self) -> &Arc<RuntimeTag> {
+        &self.tag
+    }
+
+    /// Returns whether or not these two tags are the same tag.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use wasmer::{Store, Tag, TagType, Type};
+    /// # let store = Store::default();
+    /// #
+    /// let t = Tag::new(&store, TagType::new(vec![Type::I32]));
+    /// let u = Tag::new(&store, TagType::new(vec![Type::I32]));
+    ///
+    /// assert!(t.same(&t));
+    /// assert!(!t.same(&u));
+    /// ```
+    pub fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.tag, &other.tag)
+    }
+}
+
+impl fmt::Debug for Tag {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Tag")
+            .field("ty", &self.ty())
+            .finish()
+    }
+}
+
+impl<'a> Exportable<'a> for Tag {
+    fn to_export(&self) -> Export {
+        ExportTag {
+            vm_tag: VMExportTag {
+                from: self.tag.clone(),
+                instance_ref: None,
+            },
+        }
+        .into()
+    }
+
+    fn get_self_from_extern(_extern: &'a Extern) -> Result<&'a Self, ExportError> {
+        match _extern {
+            Extern::Tag(tag) => Ok(tag),
+            _ => Err(ExportError::IncompatibleType),
+        }
+    }
+}
@@ -1,13 +1,14 @@
-use crate::exports::Exports;
+use crate::exports::{Exportable, Exports};
 use crate::externals::Extern;
 use crate::module::Module;
 use crate::store::Store;
 use crate::{HostEnvInitError, LinkError, RuntimeError};
+use std::cell::RefCell;
 use std::fmt;
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
-use wasmer_engine::Resolver;
-use wasmer_vm::{InstanceHandle, VMContext};
+use wasmer_engine::{Export, Resolver};
+use wasmer_vm::{InstanceHandle, InstanceInitializer, InstantiationArg, VMContext};
 
 /// A WebAssembly Instance is a stateful, executable
 /// instance of a WebAssembly [`Module`].
@@ -113,7 +114,22 @@ impl Instance {
     ///  * Runtime errors that happen when running the module `start` function.
     pub fn new(module: &Module, resolver: &dyn Resolver) -> Result<Self, InstantiationError> {
         let store = module.store();
-        let handle = module.instantiate(resolver)?;
+        let instantiates = module
+            .info()
+            .instances
+            .iter()
+            .any(|instance| matches!(instance, InstanceInitializer::Instantiate { .. }));
+        let handle = if !instantiates {
+            module.instantiate(resolver, Box::new(()))?
+        } else {
+            let resolver = LinkingResolver::new(module, resolver);
+            // A nested module failing to instantiate leaves its import
+            // unresolved, so its own error is the one reported.
+            resolver
+                .instantiate_all()
+                .and_then(|nested| module.instantiate(&resolver, Box::new(nested)))
+                .map_err(|error| resolver.error.into_inner().unwrap_or(error))?
+        };
         let exports = module
             .exports()
             .map(|export| {
@@ -165,6 +181,124 @@ impl Instance {
     }
 }
 
+/// Resolves the imports of a module using the module linking proposal.
+///
+/// The imports aliased from the instances the module creates are resolved
+/// by instantiating its nested modules, the others by `resolver`.
+struct LinkingResolver<'a> {
+    module: &'a Module,
+    resolver: &'a dyn Resolver,
+    instances: RefCell<Vec<Option<Instance>>>,
+    error: RefCell<Option<InstantiationError>>,
+}
+
+impl<'a> LinkingResolver<'a> {
+    fn new(module: &'a Module, resolver: &'a dyn Resolver) -> Self {
+        Self {
+            module,
+            resolver,
+            instances: RefCell::new(vec![None; module.info().instances.len()]),
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Instantiates the nested modules, in instance index order, before
+    /// the module itself.
+    ///
+    /// The instances are returned so the module keeps them alive: the
+    /// items it imports from them don't.
+    fn instantiate_all(&self) -> Result<Vec<Instance>, InstantiationError> {
+        let mut nested = Vec::new();
+        for (index, instance) in self.module.info().instances.iter().enumerate() {
+            if let InstanceInitializer::Instantiate { module, args } = instance {
+                match self.instance(index, *module, args) {
+                    Some(instance) => nested.push(instance),
+                    // The failure of the nested module is recorded by `instance`.
+                    None => {
+                        return Err(self.error.borrow_mut().take().expect("instantiation error"))
+                    }
+                }
+            }
+        }
+        Ok(nested)
+    }
+
+    /// Resolves the import at `index` of the module.
+    fn import(&self, index: u32) -> Option<Export> {
+        let (module, field, _) = self.module.info().imports.get_index(index as usize)?.0;
+        self.resolve(index, module, field)
+    }
+
+    /// Resolves the export `field` of the instance at `index`, for the
+    /// import at `import_index`.
+    fn instance_export(&self, index: usize, import_index: u32, field: &str) -> Option<Export> {
+        match &self.module.info().instances[index] {
+            InstanceInitializer::Import(namespace) => {
+                self.resolver.resolve(import_index, namespace, field)
+            }
+            InstanceInitializer::Instantiate { module, args } => {
+                let instance = self.instance(index, *module, args)?;
+                let export = instance.exports.get_extern(field)?;
+                Some(export.to_export())
+            }
+        }
+    }
+
+    /// Returns the instance at `index`, instantiating the nested module
+    /// at `module` with `args` the first time.
+    fn instance(
+        &self,
+        index: usize,
+        module: usize,
+        args: &[(String, InstantiationArg)],
+    ) -> Option<Instance> {
+        if let Some(instance) = &self.instances.borrow()[index] {
+            return Some(instance.clone());
+        }
+        let resolver = ArgsResolver { parent: self, args };
+        match Instance::new(self.module.submodule(module), &resolver) {
+            Ok(instance) => {
+                self.instances.borrow_mut()[index] = Some(instance.clone());
+                Some(instance)
+            }
+            Err(error) => {
+                self.error.borrow_mut().get_or_insert(error);
+                None
+            }
+        }
+    }
+}
+
+impl Resolver for LinkingResolver<'_> {
+    fn resolve(&self, index: u32, module: &str, field: &str) -> Option<Export> {
+        match self.module.info().instance_exports.get(&index) {
+            Some((instance, export)) => self.instance_export(*instance, index, export),
+            None => self.resolver.resolve(index, module, field),
+        }
+    }
+}
+
+/// Resolves the imports of a nested module from the items its parent
+/// instantiates it with.
+struct ArgsResolver<'a> {
+    parent: &'a LinkingResolver<'a>,
+    args: &'a [(String, InstantiationArg)],
+}
+
+impl Resolver for ArgsResolver<'_> {
+    fn resolve(&self, index: u32, module: &str, field: &str) -> Option<Export> {
+        let (_, arg) = self.args.iter().find(|(name, _)| name == module)?;
+        match arg {
+            // Items are imported with a single-level name.
+            InstantiationArg::Import(import) if field.is_empty() => self.parent.import(*import),
+            InstantiationArg::Import(_) => None,
+            InstantiationArg::Instance(instance) => {
+                self.parent.instance_export(*instance, index, field)
+            }
+        }
+    }
+}
+
 impl fmt::Debug for Instance {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         f.debug_struct("Instance")
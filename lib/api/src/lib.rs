@@ -254,14 +254,18 @@
 //! [wasmer-wasi]: https://docs.rs/wasmer-wasi/*/wasmer_wasi/
 
 mod env;
+mod exception;
 mod exports;
 mod externals;
 mod import_object;
 mod instance;
 mod module;
 mod native;
+#[cfg(unix)]
+mod profiler;
 mod ptr;
 mod store;
+mod thread;
 mod tunables;
 mod types;
 mod utils;
@@ -284,35 +288,39 @@ pub mod internals {
 }
 
 pub use crate::env::{HostEnvInitError, LazyInit, WasmerEnv};
+pub use crate::exception::Exception;
 pub use crate::exports::{ExportError, Exportable, Exports, ExportsIterator};
 pub use crate::externals::{
-    Extern, FromToNativeWasmType, Function, Global, HostFunction, Memory, Table, WasmTypeList,
+    Extern, FromToNativeWasmType, Function, Global, HostFunction, Memory, Table, Tag, WasmTypeList,
 };
 pub use crate::import_object::{ImportObject, ImportObjectIterator, LikeNamespace};
 pub use crate::instance::{Instance, InstantiationError};
 pub use crate::module::Module;
 pub use crate::native::NativeFunc;
-pub use crate::ptr::{Array, Item, WasmPtr};
+#[cfg(unix)]
+pub use crate::profiler::{Profile, Profiler, ProfilerError};
+pub use crate::ptr::{Array, Item, WasmPtr, WasmPtr64};
 pub use crate::store::{Store, StoreObject};
+pub use crate::thread::{GuestThread, ThreadError};
 pub use crate::tunables::BaseTunables;
 pub use crate::types::{
-    ExportType, ExternRef, ExternType, FunctionType, GlobalType, HostInfo, HostRef, ImportType,
-    MemoryType, Mutability, TableType, Val, ValType,
+    ExportType, ExternRef, ExternType, FunctionType, GlobalType, ImportType, MemoryType,
+    Mutability, TableType, TagType, Val, ValType,
 };
 pub use crate::types::{Val as Value, ValType as Type};
 pub use crate::utils::is_wasm;
 pub use target_lexicon::{Architecture, CallingConvention, OperatingSystem, Triple, HOST};
 #[cfg(feature = "compiler")]
 pub use wasmer_compiler::{
-    wasmparser, CompilerConfig, FunctionMiddleware, MiddlewareError, MiddlewareReaderState,
-    ModuleMiddleware,
+    wasmparser, CompilerConfig, FunctionBodyData, FunctionMiddleware, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware,
 };
 pub use wasmer_compiler::{
     CompileError, CpuFeature, Features, ParseCpuFeatureError, Target, WasmError, WasmResult,
 };
 pub use wasmer_engine::{
     ChainableNamedResolver, DeserializeError, Engine, Export, FrameInfo, LinkError, NamedResolver,
-    NamedResolverChain, Resolver, RuntimeError, SerializeError, Tunables,
+    NamedResolverChain, Resolver, RuntimeError, SerializeError, SourceFrame, Tunables,
 };
 pub use wasmer_types::{
     Atomically, Bytes, ExportIndex, GlobalInit, LocalFunctionIndex, MemoryView, Pages, ValueType,
@@ -326,6 +334,7 @@ pub mod vm {
 
     pub use wasmer_vm::{
         Memory, MemoryError, MemoryStyle, Table, TableStyle, VMMemoryDefinition, VMTableDefinition,
+        Waiters,
     };
 }
 
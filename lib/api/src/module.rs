@@ -1,16 +1,21 @@
 use crate::store::Store;
+use crate::thread::GuestThread;
 use crate::types::{ExportType, ImportType};
-use crate::InstantiationError;
+use crate::{Instance, InstantiationError, RuntimeError};
+use std::any::Any;
 use std::fmt;
 use std::io;
 use std::path::Path;
 use std::sync::Arc;
+use std::thread;
 use thiserror::Error;
 use wasmer_compiler::CompileError;
 #[cfg(feature = "wat")]
 use wasmer_compiler::WasmError;
-use wasmer_engine::{Artifact, DeserializeError, Resolver, SerializeError};
-use wasmer_vm::{ExportsIterator, ImportsIterator, InstanceHandle, ModuleInfo};
+use wasmer_engine::{
+    Artifact, DeserializeError, Resolver, SerializeError, SourceFrame, FRAME_INFO,
+};
+use wasmer_vm::{ExportsIterator, ImportsIterator, InstanceHandle, ModuleInfo, Submodule};
 
 #[derive(Error, Debug)]
 pub enum IoCompileError {
@@ -34,6 +39,7 @@ pub enum IoCompileError {
 pub struct Module {
     store: Store,
     artifact: Arc<dyn Artifact>,
+    submodules: Arc<[Module]>,
 }
 
 impl Module {
@@ -159,7 +165,10 @@ impl Module {
 
     fn compile(store: &Store, binary: &[u8]) -> Result<Self, CompileError> {
         let artifact = store.engine().compile(binary, store.tunables())?;
-        Ok(Self::from_artifact(store, artifact))
+        Self::from_artifact(store, artifact).map_err(|error| match error {
+            DeserializeError::Compiler(error) => error,
+            error => CompileError::Codegen(format!("failed to load a nested module: {}", error)),
+        })
     }
 
     /// Serializes a module into a binary representation that the `Engine`
@@ -223,7 +232,7 @@ impl Module {
     /// ```
     pub unsafe fn deserialize(store: &Store, bytes: &[u8]) -> Result<Self, DeserializeError> {
         let artifact = store.engine().deserialize(bytes)?;
-        Ok(Self::from_artifact(store, artifact))
+        Self::from_artifact(store, artifact)
     }
 
     /// Deserializes a a serialized Module located in a `Path` into a `Module`.
@@ -248,24 +257,48 @@ impl Module {
         path: impl AsRef<Path>,
     ) -> Result<Self, DeserializeError> {
         let artifact = store.engine().deserialize_from_file(path.as_ref())?;
-        Ok(Self::from_artifact(store, artifact))
+        Self::from_artifact(store, artifact)
     }
 
-    fn from_artifact(store: &Store, artifact: Arc<dyn Artifact>) -> Self {
-        Self {
+    fn from_artifact(store: &Store, artifact: Arc<dyn Artifact>) -> Result<Self, DeserializeError> {
+        // The nested modules of the module linking proposal are kept
+        // serialized in the artifact by the engines that can deserialize
+        // them, and as Wasm binaries otherwise.
+        let submodules = artifact
+            .module_ref()
+            .submodules
+            .iter()
+            .map(|submodule| match submodule {
+                Submodule::Binary(binary) => {
+                    Self::compile(store, binary).map_err(DeserializeError::Compiler)
+                }
+                // SAFETY: the module was serialized by the engine of its
+                // parent.
+                Submodule::Serialized(bytes) => unsafe { Self::deserialize(store, bytes) },
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self {
             store: store.clone(),
             artifact,
-        }
+            submodules: submodules.into(),
+        })
+    }
+
+    /// Returns the nested module at `index`, as in the module linking
+    /// proposal.
+    pub(crate) fn submodule(&self, index: usize) -> &Self {
+        &self.submodules[index]
     }
 
     pub(crate) fn instantiate(
         &self,
         resolver: &dyn Resolver,
+        host_state: Box<dyn Any>,
     ) -> Result<InstanceHandle, InstantiationError> {
         unsafe {
             let instance_handle =
                 self.artifact
-                    .instantiate(self.store.tunables(), resolver, Box::new(()))?;
+                    .instantiate(self.store.tunables(), resolver, host_state)?;
 
             // After the instance handle is created, we need to initialize
             // the data, call the start function and so. However, if any
@@ -401,6 +434,42 @@ impl Module {
         &self.store
     }
 
+    /// Spawns a guest thread: a host thread running its own instance of
+    /// this module.
+    ///
+    /// `imports` runs on the new thread and returns the imports of the
+    /// instance, usually built from clones of the shared [`Memory`] the
+    /// threads communicate through. `entry` then runs with the instance,
+    /// and its result is returned by [`GuestThread::join`].
+    ///
+    /// [`Memory`]: crate::Memory
+    pub fn spawn_thread<I, R, F, T>(&self, imports: I, entry: F) -> GuestThread<T>
+    where
+        I: FnOnce() -> R + Send + 'static,
+        R: Resolver,
+        F: FnOnce(&Instance) -> Result<T, RuntimeError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let module = self.clone();
+        GuestThread::new(thread::spawn(move || {
+            let instance = Instance::new(&module, &imports())?;
+            Ok(entry(&instance)?)
+        }))
+    }
+
+    /// Returns the source frames at the given offset of the module,
+    /// innermost first, as described by the DWARF in its custom sections.
+    ///
+    /// The list is empty if the module has no DWARF, or if nothing in
+    /// its source maps to this offset.
+    pub fn source_frames(&self, module_offset: usize) -> Vec<SourceFrame> {
+        self.artifact.register_frame_info();
+        FRAME_INFO
+            .read()
+            .unwrap()
+            .lookup_source_frames(self.artifact.module_ref(), module_offset)
+    }
+
     /// The ABI of the ModuleInfo is very unstable, we refactor it very often.
     /// This function is public because in some cases it can be useful to get some
     /// extra information from the module.
@@ -136,12 +136,14 @@ macro_rules! impl_native_traits {
                             rets_list.as_mut()
                         };
                         unsafe {
-                            wasmer_vm::wasmer_call_trampoline(
-                                self.vmctx(),
-                                trampoline,
-                                self.address(),
-                                args_rets.as_mut_ptr() as *mut u8,
-                            )
+                            self.store.call_on_guest_stack(|| {
+                                wasmer_vm::wasmer_call_trampoline(
+                                    self.vmctx(),
+                                    trampoline,
+                                    self.address(),
+                                    args_rets.as_mut_ptr() as *mut u8,
+                                )
+                            })
                         }?;
                         let num_rets = rets_list.len();
                         if !using_rets_array && num_rets > 0 {
@@ -0,0 +1,538 @@
+//! A sampling profiler for the WebAssembly code running in the process.
+//!
+//! A `SIGPROF` timer interrupts the running thread at a fixed interval of
+//! CPU time. The signal handler only records the native return addresses
+//! on the stack, by following the frame pointers of the interrupted
+//! thread; they are mapped to WebAssembly functions with the registered
+//! frame information once the profiler is stopped.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::mem;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicPtr, AtomicUsize, Ordering};
+use std::time::Duration;
+use thiserror::Error;
+use wasmer_engine::FRAME_INFO;
+
+/// The maximum number of frames recorded per sample by
+/// [`Profiler::start`].
+const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// The number of samples [`Profiler::start`] keeps; later samples are
+/// dropped.
+const DEFAULT_MAX_SAMPLES: usize = 16 * 1024;
+
+/// The sample buffer of the running profiler, null if none is running.
+///
+/// Each sample takes `SAMPLE_SIZE` words: the number of frames, then the
+/// frames, innermost first.
+static SAMPLES: AtomicPtr<usize> = AtomicPtr::new(ptr::null_mut());
+static SAMPLE_SIZE: AtomicUsize = AtomicUsize::new(0);
+static MAX_SAMPLES: AtomicUsize = AtomicUsize::new(0);
+static NEXT_SAMPLE: AtomicUsize = AtomicUsize::new(0);
+static RUNNING: AtomicBool = AtomicBool::new(false);
+/// Whether a thread is taking a sample.
+static SAMPLING: AtomicBool = AtomicBool::new(false);
+/// The pipe the stack is read through, see [`read_frame`].
+static PIPE_READ: AtomicI32 = AtomicI32::new(-1);
+static PIPE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    // Not bound by the `libc` crate on Linux.
+    fn setitimer(
+        which: libc::c_int,
+        new_value: *const libc::itimerval,
+        old_value: *mut libc::itimerval,
+    ) -> libc::c_int;
+}
+#[cfg(not(target_os = "linux"))]
+use libc::setitimer;
+
+/// An error while starting the profiler.
+#[derive(Error, Debug)]
+pub enum ProfilerError {
+    /// Only one profiler can run at a time in a process.
+    #[error("a profiler is already running")]
+    AlreadyRunning,
+    /// The timer or its signal handler couldn't be set up.
+    #[error("failed to set up the profiling timer: {0}")]
+    Timer(#[source] io::Error),
+}
+
+/// A running sampling profiler.
+///
+/// Samples are taken in whatever thread is running when the timer fires,
+/// so every instance running in the process is profiled. Stop it with
+/// [`Profiler::stop`] to get the [`Profile`].
+///
+/// Stacks are walked with the frame pointers, which the compilers keep
+/// in the code they generate. Host frames compiled without them end the
+/// walk, and the WebAssembly frames above them are not recorded.
+///
+/// # Example
+///
+/// ```ignore
+/// let profiler = Profiler::start(Duration::from_millis(1))?;
+/// run.call(&[])?;
+/// profiler.stop().write_folded(&mut std::io::stdout())?;
+/// ```
+pub struct Profiler {
+    samples: Box<[usize]>,
+    sample_size: usize,
+    max_samples: usize,
+    interval: Duration,
+    pipe: [libc::c_int; 2],
+    /// The handler replaced by the profiler's, once it's installed.
+    previous_handler: Option<libc::sigaction>,
+    armed: bool,
+}
+
+impl Profiler {
+    /// Start sampling every `interval` of CPU time consumed by the
+    /// process, keeping up to 16384 samples of up to 64 frames.
+    pub fn start(interval: Duration) -> Result<Self, ProfilerError> {
+        Self::start_with_capacity(interval, DEFAULT_MAX_SAMPLES, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Start sampling every `interval` of CPU time consumed by the
+    /// process, keeping up to `max_samples` samples of up to `max_depth`
+    /// frames.
+    ///
+    /// The signal handler can't allocate, so the buffer, of
+    /// `max_samples * (max_depth + 1)` words, is allocated up front.
+    pub fn start_with_capacity(
+        interval: Duration,
+        max_samples: usize,
+        max_depth: usize,
+    ) -> Result<Self, ProfilerError> {
+        if RUNNING.swap(true, Ordering::AcqRel) {
+            return Err(ProfilerError::AlreadyRunning);
+        }
+        let mut pipe = [-1; 2];
+        if unsafe { libc::pipe(pipe.as_mut_ptr()) } != 0 {
+            RUNNING.store(false, Ordering::Release);
+            return Err(ProfilerError::Timer(io::Error::last_os_error()));
+        }
+        let sample_size = max_depth + 1;
+        let mut profiler = Self {
+            samples: vec![0; max_samples * sample_size].into_boxed_slice(),
+            sample_size,
+            max_samples,
+            interval,
+            pipe,
+            previous_handler: None,
+            armed: true,
+        };
+        PIPE_READ.store(pipe[0], Ordering::SeqCst);
+        PIPE_WRITE.store(pipe[1], Ordering::SeqCst);
+        SAMPLE_SIZE.store(sample_size, Ordering::SeqCst);
+        MAX_SAMPLES.store(max_samples, Ordering::SeqCst);
+        NEXT_SAMPLE.store(0, Ordering::SeqCst);
+        SAMPLES.store(profiler.samples.as_mut_ptr(), Ordering::SeqCst);
+
+        unsafe {
+            let mut handler: libc::sigaction = mem::zeroed();
+            handler.sa_flags = libc::SA_SIGINFO | libc::SA_RESTART;
+            handler.sa_sigaction = sample as usize;
+            libc::sigemptyset(&mut handler.sa_mask);
+            let mut previous_handler: libc::sigaction = mem::zeroed();
+            if libc::sigaction(libc::SIGPROF, &handler, &mut previous_handler) != 0 {
+                return Err(ProfilerError::Timer(io::Error::last_os_error()));
+            }
+            // Only a handler the profiler replaced is restored.
+            profiler.previous_handler = Some(previous_handler);
+        }
+
+        if let Err(error) = set_timer(interval) {
+            // Dropping the profiler restores the handler.
+            return Err(ProfilerError::Timer(error));
+        }
+        Ok(profiler)
+    }
+
+    /// Stop sampling and aggregate the samples.
+    pub fn stop(mut self) -> Profile {
+        self.disarm();
+        let count = NEXT_SAMPLE.load(Ordering::SeqCst).min(self.max_samples);
+        let raw_samples = self
+            .samples
+            .chunks(self.sample_size)
+            .take(count)
+            .map(|sample| sample[1..=sample[0]].to_vec())
+            .collect::<Vec<_>>();
+        Profile::resolve(raw_samples, self.interval)
+    }
+
+    fn disarm(&mut self) {
+        if !self.armed {
+            return;
+        }
+        self.armed = false;
+        if let Some(previous_handler) = self.previous_handler.take() {
+            let _ = set_timer(Duration::from_secs(0));
+            unsafe {
+                libc::sigaction(libc::SIGPROF, &previous_handler, ptr::null_mut());
+            }
+        }
+        SAMPLES.store(ptr::null_mut(), Ordering::SeqCst);
+        // Another thread may still be in the handler.
+        while SAMPLING.load(Ordering::Acquire) {
+            std::hint::spin_loop();
+        }
+        unsafe {
+            libc::close(self.pipe[0]);
+            libc::close(self.pipe[1]);
+        }
+        RUNNING.store(false, Ordering::Release);
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        self.disarm();
+    }
+}
+
+fn set_timer(interval: Duration) -> io::Result<()> {
+    let interval = libc::timeval {
+        tv_sec: interval.as_secs() as libc::time_t,
+        tv_usec: interval.subsec_micros() as libc::suseconds_t,
+    };
+    let timer = libc::itimerval {
+        it_interval: interval,
+        it_value: interval,
+    };
+    if unsafe { setitimer(libc::ITIMER_PROF, &timer, ptr::null_mut()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The `SIGPROF` handler.
+///
+/// It must only call async-signal-safe functions: it neither allocates
+/// nor takes locks, and walks the stack into a slot of the preallocated
+/// sample buffer.
+extern "C" fn sample(
+    _signum: libc::c_int,
+    _siginfo: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    let samples = SAMPLES.load(Ordering::SeqCst);
+    if samples.is_null() {
+        return;
+    }
+    // The stack is read through a single pipe, so a thread interrupted
+    // while another one is sampling is skipped.
+    if SAMPLING.swap(true, Ordering::Acquire) {
+        return;
+    }
+    let index = NEXT_SAMPLE.fetch_add(1, Ordering::SeqCst);
+    if index < MAX_SAMPLES.load(Ordering::SeqCst) {
+        let size = SAMPLE_SIZE.load(Ordering::SeqCst);
+        unsafe {
+            // Failed reads set `errno`, which the interrupted code may be
+            // about to check.
+            let errno = *errno_location();
+            let sample = slice::from_raw_parts_mut(samples.add(index * size), size);
+            sample[0] = walk_frames(context, &mut sample[1..]);
+            *errno_location() = errno;
+        }
+    }
+    SAMPLING.store(false, Ordering::Release);
+}
+
+unsafe fn errno_location() -> *mut libc::c_int {
+    cfg_if::cfg_if! {
+        if #[cfg(any(target_os = "linux", target_os = "android"))] {
+            libc::__errno_location()
+        } else {
+            libc::__error()
+        }
+    }
+}
+
+/// Records the interrupted program counter, then the return address of
+/// each frame, in `frames`. Returns the number of addresses recorded.
+unsafe fn walk_frames(context: *mut libc::c_void, frames: &mut [usize]) -> usize {
+    let (pc, mut fp) = registers(context);
+    if frames.is_empty() || pc == 0 {
+        return 0;
+    }
+    frames[0] = pc;
+    let mut depth = 1;
+    while depth < frames.len() && fp != 0 && fp % mem::align_of::<usize>() == 0 {
+        // A frame starts with the frame pointer of the caller, followed
+        // by the return address, on both x86-64 and AArch64.
+        let (caller_fp, return_address) = match read_frame(fp) {
+            Some(frame) => frame,
+            None => break,
+        };
+        if return_address == 0 {
+            break;
+        }
+        frames[depth] = return_address;
+        depth += 1;
+        // The stack grows down, so the frames of callers are above.
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
+    depth
+}
+
+/// Reads the two words at `fp`.
+///
+/// Code compiled without frame pointers may use the register for
+/// anything, so the memory is read through a pipe: `write` fails with
+/// `EFAULT` instead of faulting when it isn't readable.
+unsafe fn read_frame(fp: usize) -> Option<(usize, usize)> {
+    let mut frame = [0usize; 2];
+    let size = mem::size_of_val(&frame);
+    let written = libc::write(
+        PIPE_WRITE.load(Ordering::Relaxed),
+        fp as *const libc::c_void,
+        size,
+    );
+    if written != size as isize {
+        return None;
+    }
+    let read = libc::read(
+        PIPE_READ.load(Ordering::Relaxed),
+        frame.as_mut_ptr() as *mut libc::c_void,
+        size,
+    );
+    if read != size as isize {
+        return None;
+    }
+    Some((frame[0], frame[1]))
+}
+
+/// The program counter and frame pointer of the interrupted thread, or
+/// zeroes on platforms where they aren't known.
+unsafe fn registers(context: *mut libc::c_void) -> (usize, usize) {
+    cfg_if::cfg_if! {
+        if #[cfg(all(target_os = "linux", target_arch = "x86_64"))] {
+            let cx = &*(context as *const libc::ucontext_t);
+            (
+                cx.uc_mcontext.gregs[libc::REG_RIP as usize] as usize,
+                cx.uc_mcontext.gregs[libc::REG_RBP as usize] as usize,
+            )
+        } else if #[cfg(all(target_os = "linux", target_arch = "aarch64"))] {
+            let cx = &*(context as *const libc::ucontext_t);
+            (cx.uc_mcontext.pc as usize, cx.uc_mcontext.regs[29] as usize)
+        } else if #[cfg(all(target_os = "macos", target_arch = "x86_64"))] {
+            let cx = &*(context as *const libc::ucontext_t);
+            let state = &(*cx.uc_mcontext).__ss;
+            (state.__rip as usize, state.__rbp as usize)
+        } else {
+            let _ = context;
+            (0, 0)
+        }
+    }
+}
+
+/// The samples taken by a [`Profiler`], aggregated by WebAssembly call
+/// stack.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    /// The number of samples for each stack, outermost frame first.
+    stacks: BTreeMap<Vec<String>, usize>,
+    total_samples: usize,
+    /// The CPU time between samples.
+    interval: Duration,
+}
+
+impl Profile {
+    fn resolve(raw_samples: Vec<Vec<usize>>, interval: Duration) -> Self {
+        // Return addresses point after the call instruction; look up
+        // the call itself. The first address is the interrupted one.
+        let pcs = |sample: &[usize]| {
+            sample
+                .iter()
+                .enumerate()
+                .map(|(depth, pc)| if depth == 0 { *pc } else { pc - 1 })
+                .collect::<Vec<_>>()
+        };
+
+        {
+            let mut info = FRAME_INFO.write().unwrap();
+            for sample in &raw_samples {
+                for pc in pcs(sample) {
+                    if info.should_process_frame(pc).unwrap_or(false) {
+                        info.maybe_process_frame(pc);
+                    }
+                }
+            }
+        }
+
+        let info = FRAME_INFO.read().unwrap();
+        let mut profile = Self {
+            stacks: BTreeMap::new(),
+            total_samples: raw_samples.len(),
+            interval,
+        };
+        for sample in &raw_samples {
+            let mut stack = pcs(sample)
+                .into_iter()
+                .filter_map(|pc| info.lookup_frame_info(pc))
+                .map(|frame| match frame.function_name() {
+                    Some(name) => name.to_string(),
+                    None => format!("{}[{}]", frame.module_name(), frame.func_index()),
+                })
+                .collect::<Vec<_>>();
+            // Samples taken outside of WebAssembly code are not reported.
+            if stack.is_empty() {
+                continue;
+            }
+            stack.reverse();
+            *profile.stacks.entry(stack).or_insert(0) += 1;
+        }
+        profile
+    }
+
+    /// The number of samples taken, including those outside of
+    /// WebAssembly code.
+    pub fn total_samples(&self) -> usize {
+        self.total_samples
+    }
+
+    /// The number of samples for each WebAssembly call stack, outermost
+    /// frame first.
+    pub fn stacks(&self) -> impl Iterator<Item = (&[String], usize)> {
+        self.stacks
+            .iter()
+            .map(|(stack, count)| (stack.as_slice(), *count))
+    }
+
+    /// The number of samples each WebAssembly function was running in,
+    /// not counting the functions it called.
+    pub fn self_samples(&self) -> BTreeMap<&str, usize> {
+        let mut functions = BTreeMap::new();
+        for (stack, count) in &self.stacks {
+            if let Some(function) = stack.last() {
+                *functions.entry(function.as_str()).or_insert(0) += count;
+            }
+        }
+        functions
+    }
+
+    /// Write the profile in the folded stacks format, read by tools like
+    /// `flamegraph.pl` and `inferno`.
+    pub fn write_folded<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (stack, count) in &self.stacks {
+            writeln!(out, "{} {}", stack.join(";"), count)?;
+        }
+        Ok(())
+    }
+
+    /// Write the profile in the pprof format, read by `go tool pprof`.
+    ///
+    /// The protocol buffer is written uncompressed, which pprof reads as
+    /// well as gzipped ones. Each sample counts once and for the CPU time
+    /// between samples.
+    pub fn write_pprof<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut strings = vec![String::new()];
+        let mut string = |value: &str| match strings.iter().position(|s| s == value) {
+            Some(index) => index as u64,
+            None => {
+                strings.push(value.to_string());
+                strings.len() as u64 - 1
+            }
+        };
+        let mut profile = Vec::new();
+        let interval = self.interval.as_nanos() as u64;
+
+        // Profile.sample_type: the sample count and the CPU time.
+        let mut value_type = |profile: &mut Vec<u8>, field, ty, unit| {
+            let mut message = Vec::new();
+            proto::uint(&mut message, 1, string(ty));
+            proto::uint(&mut message, 2, string(unit));
+            proto::bytes(profile, field, &message);
+        };
+        value_type(&mut profile, 1, "samples", "count");
+        value_type(&mut profile, 1, "cpu", "nanoseconds");
+        // Profile.period_type
+        value_type(&mut profile, 11, "cpu", "nanoseconds");
+
+        // Each function has a location of the same id, starting at 1.
+        let mut functions: BTreeMap<&str, u64> = BTreeMap::new();
+        for (stack, count) in &self.stacks {
+            let locations = stack
+                .iter()
+                .rev()
+                .map(|function| {
+                    let id = functions.len() as u64 + 1;
+                    *functions.entry(function).or_insert(id)
+                })
+                .collect::<Vec<_>>();
+            // Profile.sample: the locations, innermost first, and the
+            // values.
+            let mut sample = Vec::new();
+            proto::packed(&mut sample, 1, &locations);
+            proto::packed(&mut sample, 2, &[*count as u64, *count as u64 * interval]);
+            proto::bytes(&mut profile, 2, &sample);
+        }
+        for (function, id) in functions {
+            // Profile.location, with a single line in the function.
+            let mut line = Vec::new();
+            proto::uint(&mut line, 1, id);
+            let mut location = Vec::new();
+            proto::uint(&mut location, 1, id);
+            proto::bytes(&mut location, 4, &line);
+            proto::bytes(&mut profile, 4, &location);
+            // Profile.function
+            let mut message = Vec::new();
+            proto::uint(&mut message, 1, id);
+            proto::uint(&mut message, 2, string(function));
+            proto::bytes(&mut profile, 5, &message);
+        }
+        // Profile.period
+        proto::uint(&mut profile, 12, interval);
+        // Profile.string_table, which must start with the empty string.
+        for value in &strings {
+            proto::bytes(&mut profile, 6, value.as_bytes());
+        }
+        out.write_all(&profile)
+    }
+}
+
+/// The subset of the protocol buffer encoding the pprof format needs.
+mod proto {
+    const VARINT: u64 = 0;
+    const LENGTH_DELIMITED: u64 = 2;
+
+    fn varint(out: &mut Vec<u8>, mut value: u64) {
+        while value >= 0x80 {
+            out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+
+    /// An integer field.
+    pub(super) fn uint(out: &mut Vec<u8>, field: u64, value: u64) {
+        varint(out, field << 3 | VARINT);
+        varint(out, value);
+    }
+
+    /// A string, bytes or message field.
+    pub(super) fn bytes(out: &mut Vec<u8>, field: u64, value: &[u8]) {
+        varint(out, field << 3 | LENGTH_DELIMITED);
+        varint(out, value.len() as u64);
+        out.extend_from_slice(value);
+    }
+
+    /// A packed repeated integer field.
+    pub(super) fn packed(out: &mut Vec<u8>, field: u64, values: &[u64]) {
+        let mut encoded = Vec::new();
+        for value in values {
+            varint(&mut encoded, *value);
+        }
+        bytes(out, field, &encoded);
+    }
+}
@@ -7,6 +7,7 @@
 //! related bugs when implementing an ABI.
 
 use crate::{externals::Memory, FromToNativeWasmType};
+use std::convert::TryFrom;
 use std::{cell::Cell, fmt, marker::PhantomData, mem};
 use wasmer_types::ValueType;
 
@@ -304,6 +305,210 @@ impl<T: Copy, Ty> fmt::Debug for WasmPtr<T, Ty> {
     }
 }
 
+/// A zero-cost type that represents a pointer to something in a 64-bit Wasm
+/// linear memory.
+///
+/// This is the counterpart of [`WasmPtr`] for memories of the [memory64]
+/// proposal: it is passed as an `i64` and has the same methods, with `u64`
+/// offsets, indices and lengths.
+///
+/// [memory64]: https://github.com/WebAssembly/memory64
+#[repr(transparent)]
+pub struct WasmPtr64<T: Copy, Ty = Item> {
+    offset: u64,
+    _phantom: PhantomData<(T, Ty)>,
+}
+
+/// Returns `offset` as a host index if the `len` bytes starting there are
+/// within `memory`.
+fn checked_offset(memory: &Memory, offset: u64, len: u64) -> Option<usize> {
+    if offset.checked_add(len)? > memory.data_size() {
+        return None;
+    }
+    usize::try_from(offset).ok()
+}
+
+/// Methods relevant to all types of `WasmPtr64`.
+impl<T: Copy, Ty> WasmPtr64<T, Ty> {
+    /// Create a new `WasmPtr64` at the given offset.
+    #[inline]
+    pub fn new(offset: u64) -> Self {
+        Self {
+            offset,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Get the offset into Wasm linear memory for this `WasmPtr64`.
+    #[inline]
+    pub fn offset(self) -> u64 {
+        self.offset
+    }
+}
+
+/// Methods for `WasmPtr64`s to data that can be dereferenced, see
+/// [`WasmPtr<T, Item>`](WasmPtr).
+impl<T: Copy + ValueType> WasmPtr64<T, Item> {
+    /// Dereference the `WasmPtr64` getting access to a `&Cell<T>` allowing for
+    /// reading and mutating of the inner value.
+    ///
+    /// This method is unsound if used with unsynchronized shared memory.
+    #[inline]
+    pub fn deref<'a>(self, memory: &'a Memory) -> Option<&'a Cell<T>> {
+        unsafe { self.deref_mut(memory) }.map(|cell| &*cell)
+    }
+
+    /// Mutably dereference this `WasmPtr64` getting a `&mut Cell<T>` allowing
+    /// for direct access to a `&mut T`.
+    ///
+    /// # Safety
+    /// - This method does not do any aliasing checks: it's possible to create
+    ///  `&mut T` that point to the same memory. You should ensure that you have
+    ///   exclusive access to Wasm linear memory before calling this method.
+    #[inline]
+    pub unsafe fn deref_mut<'a>(self, memory: &'a Memory) -> Option<&'a mut Cell<T>> {
+        if mem::size_of::<T>() == 0 {
+            return None;
+        }
+        let offset = checked_offset(memory, self.offset, mem::size_of::<T>() as u64)?;
+        let cell_ptr = align_pointer(memory.data_ptr().add(offset) as usize, mem::align_of::<T>())
+            as *mut Cell<T>;
+        Some(&mut *cell_ptr)
+    }
+}
+
+/// Methods for `WasmPtr64`s to arrays of data that can be dereferenced, see
+/// [`WasmPtr<T, Array>`](WasmPtr).
+impl<T: Copy + ValueType> WasmPtr64<T, Array> {
+    /// Dereference the `WasmPtr64` getting access to a `&[Cell<T>]` allowing
+    /// for reading and mutating of the inner values.
+    ///
+    /// This method is unsound if used with unsynchronized shared memory.
+    #[inline]
+    pub fn deref(self, memory: &Memory, index: u64, length: u64) -> Option<&[Cell<T>]> {
+        unsafe { self.deref_mut(memory, index, length) }.map(|cells| &*cells)
+    }
+
+    /// Mutably dereference this `WasmPtr64` getting a `&mut [Cell<T>]`
+    /// allowing for direct access to a `&mut [T]`.
+    ///
+    /// # Safety
+    /// - This method does not do any aliasing checks: it's possible to create
+    ///  `&mut T` that point to the same memory. You should ensure that you have
+    ///   exclusive access to Wasm linear memory before calling this method.
+    #[inline]
+    pub unsafe fn deref_mut(
+        self,
+        memory: &Memory,
+        index: u64,
+        length: u64,
+    ) -> Option<&mut [Cell<T>]> {
+        // gets the size of the item in the array with padding added such that
+        // for any index, we will always result an aligned memory access
+        let item_size = mem::size_of::<T>() + (mem::size_of::<T>() % mem::align_of::<T>());
+        let slice_full_len = index.checked_add(length)?;
+
+        if self.offset >= memory.data_size() || mem::size_of::<T>() == 0 {
+            return None;
+        }
+        let offset = checked_offset(
+            memory,
+            self.offset,
+            (item_size as u64).checked_mul(slice_full_len)?,
+        )?;
+
+        let cell_ptr = align_pointer(memory.data_ptr().add(offset) as usize, mem::align_of::<T>())
+            as *mut Cell<T>;
+        let cell_ptrs = &mut std::slice::from_raw_parts_mut(cell_ptr, slice_full_len as usize)
+            [index as usize..];
+        Some(cell_ptrs)
+    }
+
+    /// Get a UTF-8 string from the `WasmPtr64` with the given length.
+    ///
+    /// # Safety
+    /// This method has the same safety invariants as
+    /// [`WasmPtr::get_utf8_str`].
+    pub unsafe fn get_utf8_str<'a>(self, memory: &'a Memory, str_len: u64) -> Option<&'a str> {
+        if self.offset >= memory.data_size() {
+            return None;
+        }
+        let offset = checked_offset(memory, self.offset, str_len)?;
+        let ptr = memory.data_ptr().add(offset) as *const u8;
+        let slice: &[u8] = std::slice::from_raw_parts(ptr, str_len as usize);
+        std::str::from_utf8(slice).ok()
+    }
+
+    /// Get a UTF-8 `String` from the `WasmPtr64` with the given length.
+    pub fn get_utf8_string(self, memory: &Memory, str_len: u64) -> Option<String> {
+        unsafe { self.get_utf8_str(memory, str_len) }.map(|s| s.to_owned())
+    }
+
+    /// Get a UTF-8 string from the `WasmPtr64`, where the string is
+    /// nul-terminated.
+    ///
+    /// # Safety
+    /// This method has the same safety invariants as
+    /// [`WasmPtr::get_utf8_str`].
+    pub unsafe fn get_utf8_str_with_nul<'a>(self, memory: &'a Memory) -> Option<&'a str> {
+        let offset = usize::try_from(self.offset).ok()?;
+        memory
+            .view::<u8>()
+            .get(offset..)?
+            .iter()
+            .map(|cell| cell.get())
+            .position(|byte| byte == 0)
+            .and_then(|length| self.get_utf8_str(memory, length as u64))
+    }
+
+    /// Get a UTF-8 `String` from the `WasmPtr64`, where the string is
+    /// nul-terminated.
+    pub fn get_utf8_string_with_nul(self, memory: &Memory) -> Option<String> {
+        unsafe { self.get_utf8_str_with_nul(memory) }.map(|s| s.to_owned())
+    }
+}
+
+unsafe impl<T: Copy, Ty> FromToNativeWasmType for WasmPtr64<T, Ty> {
+    type Native = i64;
+
+    fn to_native(self) -> Self::Native {
+        self.offset as i64
+    }
+    fn from_native(n: Self::Native) -> Self {
+        Self {
+            offset: n as u64,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+unsafe impl<T: Copy, Ty> ValueType for WasmPtr64<T, Ty> {}
+
+impl<T: Copy, Ty> Clone for WasmPtr64<T, Ty> {
+    fn clone(&self) -> Self {
+        Self {
+            offset: self.offset,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: Copy, Ty> Copy for WasmPtr64<T, Ty> {}
+
+impl<T: Copy, Ty> PartialEq for WasmPtr64<T, Ty> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+
+impl<T: Copy, Ty> Eq for WasmPtr64<T, Ty> {}
+
+impl<T: Copy, Ty> fmt::Debug for WasmPtr64<T, Ty> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WasmPtr64({:#x})", self.offset)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -394,4 +599,35 @@ mod test {
             assert!(unsafe { oob_end_array_ptr.deref_mut(&memory, 1, 0).is_none() });
         }
     }
+
+    /// Ensure that `WasmPtr64` catches out of bounds accesses, including ones
+    /// whose end address overflows.
+    #[test]
+    fn wasm_ptr64_memory_bounds_checks_hold() {
+        let store = Store::default();
+        let memory_descriptor = MemoryType::new64(1, Some(1), false);
+        let memory = Memory::new(&store, memory_descriptor).unwrap();
+
+        let last_valid_address_for_u32 = memory.data_size() - 4;
+        let end_wasm_ptr: WasmPtr64<u32> = WasmPtr64::new(last_valid_address_for_u32);
+        assert!(end_wasm_ptr.deref(&memory).is_some());
+        assert!(unsafe { end_wasm_ptr.deref_mut(&memory).is_some() });
+
+        let oob_wasm_ptr: WasmPtr64<u32> = WasmPtr64::new(last_valid_address_for_u32 + 1);
+        assert!(oob_wasm_ptr.deref(&memory).is_none());
+        let wrapping_wasm_ptr: WasmPtr64<u32> = WasmPtr64::new(u64::max_value() - 1);
+        assert!(wrapping_wasm_ptr.deref(&memory).is_none());
+
+        let end_wasm_ptr_array: WasmPtr64<u32, Array> = WasmPtr64::new(last_valid_address_for_u32);
+        assert!(end_wasm_ptr_array.deref(&memory, 0, 1).is_some());
+        let invalid_idx_len_combos: [(u64, u64); 3] =
+            [(0, 2), (1, 1), (u64::max_value(), u64::max_value())];
+        for &(idx, len) in invalid_idx_len_combos.iter() {
+            assert!(end_wasm_ptr_array.deref(&memory, idx, len).is_none());
+        }
+        assert!(end_wasm_ptr_array.get_utf8_string(&memory, 5).is_none());
+        assert!(end_wasm_ptr_array
+            .get_utf8_string(&memory, u64::max_value())
+            .is_none());
+    }
 }
@@ -1,9 +1,13 @@
 use crate::tunables::BaseTunables;
+use crate::{ExternRef, RuntimeError};
+use std::collections::HashMap;
 use std::fmt;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 #[cfg(all(feature = "compiler", feature = "engine"))]
 use wasmer_compiler::CompilerConfig;
 use wasmer_engine::{Engine, Tunables};
+use wasmer_vm::{Trap, VMCallerCheckedAnyfunc, VMExternRef, VMSharedSignatureIndex};
 
 /// The store represents all global state that can be manipulated by
 /// WebAssembly programs. It consists of the runtime representation
@@ -19,8 +23,26 @@ use wasmer_engine::{Engine, Tunables};
 pub struct Store {
     engine: Arc<dyn Engine + Send + Sync>,
     tunables: Arc<dyn Tunables + Send + Sync>,
+    /// The size of the stack guest code runs on, 0 to run it on the
+    /// stack of the calling thread.
+    guest_stack_size: Arc<AtomicUsize>,
+    refs: Arc<Mutex<StoreRefs>>,
 }
 
+/// The references the host passed to WebAssembly through a store.
+///
+/// Compiled code only sees their raw values, so the store keeps them
+/// alive for as long as it lives.
+#[derive(Default)]
+struct StoreRefs {
+    func_refs: HashMap<(usize, VMSharedSignatureIndex, usize), Box<VMCallerCheckedAnyfunc>>,
+    extern_refs: HashMap<VMExternRef, ExternRef>,
+}
+
+/// This is correct because the `VMCallerCheckedAnyfunc`s only point to
+/// functions and their environments, which are not tied to a thread.
+unsafe impl Send for StoreRefs {}
+
 impl Store {
     /// Creates a new `Store` with a specific [`Engine`].
     pub fn new<E>(engine: &E) -> Self
@@ -30,6 +52,8 @@ impl Store {
         Self {
             engine: engine.cloned(),
             tunables: Arc::new(BaseTunables::for_target(engine.target())),
+            guest_stack_size: Arc::new(AtomicUsize::new(0)),
+            refs: Default::default(),
         }
     }
 
@@ -41,6 +65,8 @@ impl Store {
         Self {
             engine: engine.cloned(),
             tunables: Arc::new(tunables),
+            guest_stack_size: Arc::new(AtomicUsize::new(0)),
+            refs: Default::default(),
         }
     }
 
@@ -54,6 +80,107 @@ impl Store {
         &self.engine
     }
 
+    /// Runs the WebAssembly code called through this store, and its
+    /// clones, on a dedicated stack of at least `size` bytes instead of
+    /// the stack of the calling thread. `None` runs it on the calling
+    /// thread again.
+    ///
+    /// This makes the recursion depth the guest can reach independent
+    /// of the thread calling it. Each thread allocates its own stack on
+    /// its first call, with a guard page below it, and overflowing it
+    /// traps with `TrapCode::StackOverflow`.
+    ///
+    /// Only supported on Unix; elsewhere setting a size returns an
+    /// error.
+    pub fn set_guest_stack_size(&self, size: Option<usize>) -> Result<(), RuntimeError> {
+        if cfg!(not(unix)) && size.is_some() {
+            return Err(RuntimeError::new(
+                "guest stacks are not supported on this platform",
+            ));
+        }
+        self.guest_stack_size
+            .store(size.unwrap_or(0), Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Returns the size of the stack the WebAssembly code runs on, or
+    /// `None` if it runs on the stack of the calling thread.
+    ///
+    /// See [`Store::set_guest_stack_size`].
+    pub fn guest_stack_size(&self) -> Option<usize> {
+        match self.guest_stack_size.load(Ordering::Relaxed) {
+            0 => None,
+            size => Some(size),
+        }
+    }
+
+    /// Calls into WebAssembly with `call`, on the guest stack if one
+    /// is configured.
+    ///
+    /// # Safety
+    ///
+    /// See [`wasmer_vm::on_guest_stack`].
+    pub(crate) unsafe fn call_on_guest_stack<F>(&self, call: F) -> Result<(), Trap>
+    where
+        F: FnOnce() -> Result<(), Trap>,
+    {
+        #[cfg(unix)]
+        {
+            if let Some(size) = self.guest_stack_size() {
+                return wasmer_vm::on_guest_stack(size, call)?;
+            }
+        }
+        call()
+    }
+
+    /// Returns the raw value compiled code sees for the function
+    /// reference `anyfunc`: a pointer to an equal `VMCallerCheckedAnyfunc`
+    /// that lives as long as the store, or null for a null reference.
+    pub(crate) fn func_ref(
+        &self,
+        anyfunc: VMCallerCheckedAnyfunc,
+    ) -> *const VMCallerCheckedAnyfunc {
+        if anyfunc.func_ptr.is_null() {
+            return std::ptr::null();
+        }
+        let key = (
+            anyfunc.func_ptr as usize,
+            anyfunc.type_index,
+            unsafe { anyfunc.vmctx.host_env } as usize,
+        );
+        let mut refs = self.refs.lock().unwrap();
+        &**refs
+            .func_refs
+            .entry(key)
+            .or_insert_with(|| Box::new(anyfunc))
+    }
+
+    /// Returns the raw value compiled code sees for `extern_ref`, keeping
+    /// the referenced data alive as long as the store.
+    pub(crate) fn extern_ref_to_raw(&self, extern_ref: &ExternRef) -> VMExternRef {
+        let raw = VMExternRef(extern_ref.address());
+        if !raw.is_null() {
+            let mut refs = self.refs.lock().unwrap();
+            refs.extern_refs
+                .entry(raw)
+                .or_insert_with(|| extern_ref.clone());
+        }
+        raw
+    }
+
+    /// Returns the `ExternRef` that `raw` is the raw value of in this
+    /// store.
+    pub(crate) fn extern_ref_from_raw(&self, raw: VMExternRef) -> ExternRef {
+        if raw.is_null() {
+            return ExternRef::Null;
+        }
+        let refs = self.refs.lock().unwrap();
+        refs.extern_refs
+            .get(&raw)
+            .cloned()
+            .expect("externref not found in store")
+    }
+
     /// Checks whether two stores are identical. A store is considered
     /// equal to another store if both have the same engine. The
     /// tunables are excluded from the logic.
@@ -111,6 +238,8 @@ impl Default for Store {
         Store {
             engine: Arc::new(engine),
             tunables: Arc::new(tunables),
+            guest_stack_size: Arc::new(AtomicUsize::new(0)),
+            refs: Default::default(),
         }
     }
 }
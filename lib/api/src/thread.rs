@@ -0,0 +1,46 @@
+use crate::{InstantiationError, RuntimeError};
+use std::panic;
+use std::thread;
+use thiserror::Error;
+
+/// An error while running a guest thread.
+#[derive(Error, Debug)]
+pub enum ThreadError {
+    /// The instance of the thread couldn't be created.
+    #[error(transparent)]
+    Instantiation(#[from] InstantiationError),
+
+    /// The entry point of the thread failed.
+    #[error(transparent)]
+    Runtime(#[from] RuntimeError),
+}
+
+/// A guest thread: a host thread running its own instance of a module,
+/// usually importing a shared [`Memory`] from the thread that spawned it.
+///
+/// Guest threads are spawned with [`Module::spawn_thread`].
+///
+/// [`Memory`]: crate::Memory
+/// [`Module::spawn_thread`]: crate::Module::spawn_thread
+#[derive(Debug)]
+pub struct GuestThread<T> {
+    handle: thread::JoinHandle<Result<T, ThreadError>>,
+}
+
+impl<T> GuestThread<T> {
+    pub(crate) fn new(handle: thread::JoinHandle<Result<T, ThreadError>>) -> Self {
+        Self { handle }
+    }
+
+    /// Waits for the thread to finish, and returns the result of its entry
+    /// point.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of the thread if it panicked.
+    pub fn join(self) -> Result<T, ThreadError> {
+        self.handle
+            .join()
+            .unwrap_or_else(|payload| panic::resume_unwind(payload))
+    }
+}
@@ -75,8 +75,11 @@ impl Tunables for BaseTunables {
         // tunables make it static.
         //
         // If the module doesn't declare an explicit maximum treat it as 4GiB.
+        //
+        // 64-bit memories are always dynamic: their addresses can't be
+        // covered by a guard region, so every access needs a bounds check.
         let maximum = memory.maximum.unwrap_or_else(Pages::max_value);
-        if maximum <= self.static_memory_bound {
+        if !memory.memory64 && maximum <= self.static_memory_bound {
             MemoryStyle::Static {
                 // Bound can be larger than the maximum for performance reasons
                 bound: self.static_memory_bound,
@@ -190,5 +193,13 @@ mod tests {
             }
             s => panic!("Unexpected memory style: {:?}", s),
         }
+
+        // Small maximum, 64-bit
+        let requested = MemoryType::new64(3, Some(16), true);
+        let style = tunables.memory_style(&requested);
+        match style {
+            MemoryStyle::Dynamic { offset_guard_size } => assert_eq!(offset_guard_size, 256),
+            s => panic!("Unexpected memory style: {:?}", s),
+        }
     }
 }
@@ -4,9 +4,10 @@ use crate::RuntimeError;
 use std::ptr;
 use wasmer_types::Value;
 pub use wasmer_types::{
-    ExportType, ExternRef, ExternType, FunctionType, GlobalType, HostInfo, HostRef, ImportType,
-    MemoryType, Mutability, TableType, Type as ValType,
+    ExportType, ExternRef, ExternType, FunctionType, GlobalType, ImportType, MemoryType,
+    Mutability, TableType, TagType, Type as ValType,
 };
+use wasmer_vm::{TableElement, VMCallerCheckedAnyfunc};
 
 /// WebAssembly computations manipulate values of basic value types:
 /// * Integers (32 or 64 bit width)
@@ -19,9 +20,9 @@ pub type Val = Value<Function>;
 impl StoreObject for Val {
     fn comes_from_same_store(&self, store: &Store) -> bool {
         match self {
-            Self::FuncRef(f) => Store::same(store, f.store()),
-            Self::ExternRef(ExternRef::Ref(_)) | Self::ExternRef(ExternRef::Other(_)) => false,
-            Self::ExternRef(ExternRef::Null) => true,
+            Self::FuncRef(Some(f)) => Store::same(store, f.store()),
+            Self::FuncRef(None) => true,
+            Self::ExternRef(_) => true,
             Self::I32(_) | Self::I64(_) | Self::F32(_) | Self::F64(_) | Self::V128(_) => true,
         }
     }
@@ -29,12 +30,13 @@ impl StoreObject for Val {
 
 impl From<Function> for Val {
     fn from(val: Function) -> Self {
-        Self::FuncRef(val)
+        Self::FuncRef(Some(val))
     }
 }
 
 /// It provides useful functions for converting back and forth
-/// from [`Val`] into `FuncRef`.
+/// from [`Val`] into `FuncRef`, and into the values compiled code
+/// sees for references.
 pub trait ValFuncRef {
     fn into_checked_anyfunc(
         &self,
@@ -42,6 +44,23 @@ pub trait ValFuncRef {
     ) -> Result<wasmer_vm::VMCallerCheckedAnyfunc, RuntimeError>;
 
     fn from_checked_anyfunc(item: wasmer_vm::VMCallerCheckedAnyfunc, store: &Store) -> Self;
+
+    fn to_table_element(&self, store: &Store, ty: ValType) -> Result<TableElement, RuntimeError>;
+
+    fn from_table_element(item: TableElement, store: &Store) -> Self;
+
+    /// Writes the value to `p` the way compiled code passes it around.
+    ///
+    /// # Safety
+    /// `p` must be valid for writing a value of the type of `self`.
+    unsafe fn write_value_with_store(&self, store: &Store, p: *mut i128);
+
+    /// Reads a value of type `ty` that compiled code wrote to `p`.
+    ///
+    /// # Safety
+    /// `p` must be valid for reading a value of type `ty`, coming from
+    /// `store`.
+    unsafe fn read_value_with_store(store: &Store, p: *const i128, ty: ValType) -> Self;
 }
 
 impl ValFuncRef for Val {
@@ -53,21 +72,21 @@ impl ValFuncRef for Val {
             return Err(RuntimeError::new("cross-`Store` values are not supported"));
         }
         Ok(match self {
-            Self::ExternRef(ExternRef::Null) => wasmer_vm::VMCallerCheckedAnyfunc {
+            Self::FuncRef(None) => wasmer_vm::VMCallerCheckedAnyfunc {
                 func_ptr: ptr::null(),
                 type_index: wasmer_vm::VMSharedSignatureIndex::default(),
                 vmctx: wasmer_vm::VMFunctionEnvironment {
                     host_env: ptr::null_mut(),
                 },
             },
-            Self::FuncRef(f) => f.checked_anyfunc(),
+            Self::FuncRef(Some(f)) => f.checked_anyfunc(),
             _ => return Err(RuntimeError::new("val is not funcref")),
         })
     }
 
     fn from_checked_anyfunc(item: wasmer_vm::VMCallerCheckedAnyfunc, store: &Store) -> Self {
         if item.type_index == wasmer_vm::VMSharedSignatureIndex::default() {
-            return Self::ExternRef(ExternRef::Null);
+            return Self::FuncRef(None);
         }
         let signature = store
             .engine()
@@ -89,6 +108,55 @@ impl ValFuncRef for Val {
             },
         };
         let f = Function::from_vm_export(store, export);
-        Self::FuncRef(f)
+        Self::FuncRef(Some(f))
+    }
+
+    fn to_table_element(&self, store: &Store, ty: ValType) -> Result<TableElement, RuntimeError> {
+        Ok(match (self, ty) {
+            (Self::ExternRef(extern_ref), ValType::ExternRef) => {
+                TableElement::ExternRef(store.extern_ref_to_raw(extern_ref))
+            }
+            (_, ValType::FuncRef) => TableElement::FuncRef(self.into_checked_anyfunc(store)?),
+            _ => return Err(RuntimeError::new("val is not externref")),
+        })
+    }
+
+    fn from_table_element(item: TableElement, store: &Store) -> Self {
+        match item {
+            TableElement::FuncRef(anyfunc) => Self::from_checked_anyfunc(anyfunc, store),
+            TableElement::ExternRef(raw) => Self::ExternRef(store.extern_ref_from_raw(raw)),
+        }
+    }
+
+    unsafe fn write_value_with_store(&self, store: &Store, p: *mut i128) {
+        match self {
+            Self::FuncRef(f) => ptr::write(
+                p as *mut *const VMCallerCheckedAnyfunc,
+                f.as_ref()
+                    .map_or(ptr::null(), |f| store.func_ref(f.checked_anyfunc())),
+            ),
+            Self::ExternRef(extern_ref) => ptr::write(
+                p as *mut wasmer_vm::VMExternRef,
+                store.extern_ref_to_raw(extern_ref),
+            ),
+            _ => self.write_value_to(p),
+        }
+    }
+
+    unsafe fn read_value_with_store(store: &Store, p: *const i128, ty: ValType) -> Self {
+        match ty {
+            ValType::FuncRef => {
+                let func_ref = ptr::read(p as *const *const VMCallerCheckedAnyfunc);
+                if func_ref.is_null() {
+                    Self::FuncRef(None)
+                } else {
+                    Self::from_checked_anyfunc((*func_ref).clone(), store)
+                }
+            }
+            ValType::ExternRef => Self::ExternRef(
+                store.extern_ref_from_raw(ptr::read(p as *const wasmer_vm::VMExternRef)),
+            ),
+            _ => Self::read_value_from(p, ty),
+        }
     }
 }
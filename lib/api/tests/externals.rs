@@ -67,7 +67,7 @@ fn table_new() -> Result<()> {
         maximum: None,
     };
     let f = Function::new_native(&store, || {});
-    let table = Table::new(&store, table_type, Value::FuncRef(f))?;
+    let table = Table::new(&store, table_type, Value::FuncRef(Some(f)))?;
     assert_eq!(*table.ty(), table_type);
 
     // Anyrefs not yet supported
@@ -92,7 +92,7 @@ fn table_get() -> Result<()> {
         maximum: Some(1),
     };
     let f = Function::new_native(&store, |num: i32| num + 1);
-    let table = Table::new(&store, table_type, Value::FuncRef(f.clone()))?;
+    let table = Table::new(&store, table_type, Value::FuncRef(Some(f.clone())))?;
     assert_eq!(*table.ty(), table_type);
     let _elem = table.get(0).unwrap();
     // assert_eq!(elem.funcref().unwrap(), f);
@@ -115,13 +115,13 @@ fn table_grow() -> Result<()> {
         maximum: Some(10),
     };
     let f = Function::new_native(&store, |num: i32| num + 1);
-    let table = Table::new(&store, table_type, Value::FuncRef(f.clone()))?;
+    let table = Table::new(&store, table_type, Value::FuncRef(Some(f.clone())))?;
     // Growing to a bigger maximum should return None
-    let old_len = table.grow(12, Value::FuncRef(f.clone()));
+    let old_len = table.grow(12, Value::FuncRef(Some(f.clone())));
     assert!(old_len.is_err());
 
     // Growing to a bigger maximum should return None
-    let old_len = table.grow(5, Value::FuncRef(f.clone()))?;
+    let old_len = table.grow(5, Value::FuncRef(Some(f.clone())))?;
     assert_eq!(old_len, 0);
 
     Ok(())
@@ -141,6 +141,7 @@ fn memory_new() -> Result<()> {
         shared: false,
         minimum: Pages(0),
         maximum: Some(Pages(10)),
+        memory64: false,
     };
     let memory = Memory::new(&store, memory_type)?;
     assert_eq!(memory.size(), Pages(0));
@@ -177,6 +178,33 @@ fn memory_grow() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn memory_shared() -> Result<()> {
+    let store = Store::default();
+
+    let desc = MemoryType::new(Pages(1), Some(Pages(16)), true);
+    let memory = Memory::new(&store, desc)?;
+    let base = memory.data_ptr();
+
+    // Shared memories never move when they grow.
+    assert_eq!(memory.grow(Pages(15))?, Pages(1));
+    assert_eq!(memory.data_ptr(), base);
+
+    // Shared memories can be used from other threads.
+    let other = memory.clone();
+    std::thread::spawn(move || other.view::<u8>()[0].set(42))
+        .join()
+        .unwrap();
+    assert_eq!(memory.view::<u8>()[0].get(), 42);
+
+    let bad_desc = MemoryType::new(Pages(1), None, true);
+    let bad_result = Memory::new(&store, bad_desc);
+
+    assert!(matches!(bad_result, Err(MemoryError::InvalidMemory { .. })));
+
+    Ok(())
+}
+
 #[test]
 fn function_new() -> Result<()> {
     let store = Store::default();
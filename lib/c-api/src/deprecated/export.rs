@@ -143,7 +143,11 @@ pub unsafe extern "C" fn wasmer_export_descriptors(
     let module = &*(module as *const Module);
 
     let named_export_descriptors: Box<NamedExportDescriptors> = Box::new(NamedExportDescriptors(
-        module.exports().into_iter().map(|e| e.into()).collect(),
+        module
+            .exports()
+            .filter(|e| !matches!(e.ty(), ExternType::Tag(_)))
+            .map(|e| e.into())
+            .collect(),
     ));
     *export_descriptors =
         Box::into_raw(named_export_descriptors) as *mut wasmer_export_descriptors_t;
@@ -568,6 +572,7 @@ impl From<&ExternType> for wasmer_import_export_kind {
             ExternType::Global(_) => wasmer_import_export_kind::WASM_GLOBAL,
             ExternType::Table(_) => wasmer_import_export_kind::WASM_TABLE,
             ExternType::Function(_) => wasmer_import_export_kind::WASM_FUNCTION,
+            ExternType::Tag(_) => unreachable!("tags are filtered out of the deprecated C API"),
         }
     }
 }
@@ -19,7 +19,7 @@ use std::sync::{Arc, Mutex};
 // use std::convert::TryFrom,
 use std::collections::HashMap;
 use wasmer::{
-    ChainableNamedResolver, Exports, Extern, Function, FunctionType, Global, ImportObject,
+    ChainableNamedResolver, Exports, Extern, ExternType, Function, FunctionType, Global, ImportObject,
     ImportObjectIterator, ImportType, Memory, Module, NamedResolver, RuntimeError, Table, Val,
     ValType, WasmerEnv,
 };
@@ -44,7 +44,7 @@ pub(crate) struct CAPIImportObject {
     /// required because the old Wasmer API allowed accessing these from the Instance
     /// but the new/current API does not. So we store them here to pass them to the Instance
     /// to allow functions to access this data for backwards compatibilty.
-    pub(crate) imported_memories: Vec<*mut Memory>,
+    pub(crate) imported_memories: Vec<(String, String, *mut Memory)>,
     /// List of pointers to `LegacyEnv`s used to patch imported functions to be able to
     /// pass a the "vmctx" as the first argument.
     /// Needed here because of extending import objects.
@@ -191,6 +191,12 @@ pub unsafe extern "C" fn wasmer_import_object_get_import(
                 let writer = import_export_value_out.func as *mut Global;
                 *writer = global.clone();
             }
+            Extern::Tag(_) => {
+                update_last_error(CApiError {
+                    msg: format!("Found tag, expected {}", tag.to_str()),
+                });
+                return wasmer_result_t::WASMER_ERROR;
+            }
         }
 
         import_out.value = *import_export_value;
@@ -454,7 +460,11 @@ pub unsafe extern "C" fn wasmer_import_object_extend(
         let export = match import.tag {
             wasmer_import_export_kind::WASM_MEMORY => {
                 let mem = import.value.memory as *mut Memory;
-                import_object.imported_memories.push(mem);
+                import_object.imported_memories.push((
+                    module_name.to_string(),
+                    import_name.to_string(),
+                    mem,
+                ));
                 Extern::Memory((&*mem).clone())
             }
             wasmer_import_export_kind::WASM_FUNCTION => {
@@ -520,7 +530,10 @@ pub unsafe extern "C" fn wasmer_import_descriptors(
     } else {
         return;
     };
-    let descriptors = module.imports().collect::<Vec<ImportType>>();
+    let descriptors = module
+        .imports()
+        .filter(|import| !matches!(import.ty(), ExternType::Tag(_)))
+        .collect::<Vec<ImportType>>();
 
     let named_import_descriptors: Box<NamedImportDescriptors> =
         Box::new(NamedImportDescriptors(descriptors));
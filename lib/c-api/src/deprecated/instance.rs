@@ -15,9 +15,9 @@ use std::ffi::CStr;
 use std::ptr::NonNull;
 use std::slice;
 use wasmer::{
-    Exports, Extern, Function, Global, ImportObject, Instance, Memory, Module, Table, Val,
+    Exports, Extern, ExternType, Function, Global, ImportObject, Instance, Memory, Module, Table, Val,
 };
-use wasmer_types::{entity::*, ExportIndex, MemoryIndex};
+use wasmer_types::{entity::*, ExportIndex, ImportIndex, MemoryIndex};
 
 /// Opaque pointer to an Instance type plus metadata.
 ///
@@ -35,7 +35,9 @@ pub(crate) struct CAPIInstance {
     /// required because the old Wasmer API allowed accessing these from the Instance
     /// but the new/current API does not. So we store them here to allow functions to
     /// access this data for backwards compatibilty.
-    pub(crate) imported_memories: Vec<*mut Memory>,
+    ///
+    /// Each memory is stored along with its module and import names.
+    pub(crate) imported_memories: Vec<(String, String, *mut Memory)>,
     /// The Instance/Ctx wide data.  Previously the Ctx Instance were separate, but
     /// given the internal API changes this is no longer true: this is a single global
     /// void pointer like the old Wasmer API used to have.
@@ -180,7 +182,7 @@ pub unsafe extern "C" fn wasmer_instantiate(
             wasmer_import_export_kind::WASM_MEMORY => {
                 let mem = import.value.memory as *mut Memory;
                 // TODO: review ownership here, probably need to clone Memory for imported_meomries
-                imported_memories.push(mem);
+                imported_memories.push((module_name.to_string(), import_name.to_string(), mem));
                 Extern::Memory((&*mem).clone())
             }
             wasmer_import_export_kind::WASM_FUNCTION => {
@@ -469,6 +471,7 @@ pub unsafe extern "C" fn wasmer_instance_exports(
         .instance
         .module()
         .exports()
+        .filter(|export_type| !matches!(export_type.ty(), ExternType::Tag(_)))
         .map(|export_type| NamedExport {
             export_type,
             instance,
@@ -525,8 +528,6 @@ pub unsafe extern "C" fn wasmer_instance_context_data_set(
 
 /// Gets the `memory_idx`th memory of the instance.
 ///
-/// Note that the index is always `0` until multiple memories are supported.
-///
 /// This function is mostly used inside host functions (aka imported
 /// functions) to read the instance memory.
 ///
@@ -548,20 +549,43 @@ pub unsafe extern "C" fn wasmer_instance_context_data_set(
 #[no_mangle]
 pub unsafe extern "C" fn wasmer_instance_context_memory(
     ctx: *const wasmer_instance_context_t,
-    _memory_idx: u32,
+    memory_idx: u32,
 ) -> Option<&'static wasmer_memory_t> {
     let instance = &*(ctx as *const CAPIInstance);
     let module = instance.instance.module();
-    let memory_index = MemoryIndex::new(0);
+    let memory_index = MemoryIndex::from_u32(memory_idx);
+    if memory_index.index() >= module.info().memories.len() {
+        update_last_error(CApiError {
+            msg: format!("Memory index `{}` is out of bounds", memory_idx),
+        });
+        return None;
+    }
     if module.info().is_imported_memory(memory_index) {
-        if let Some(memory) = instance.imported_memories.first() {
+        let memory = module
+            .info()
+            .imports
+            .iter()
+            .find_map(
+                |((module_name, import_name, _), import_index)| match import_index {
+                    ImportIndex::Memory(idx) if *idx == memory_index => {
+                        Some((module_name, import_name))
+                    }
+                    _ => None,
+                },
+            )
+            .and_then(|(module_name, import_name)| {
+                instance
+                    .imported_memories
+                    .iter()
+                    .find(|(m, n, _)| m == module_name && n == import_name)
+            });
+        if let Some((_, _, memory)) = memory {
             let memory: &Memory = &**memory;
             return Some(&*(Box::into_raw(Box::new(memory.clone())) as *const wasmer_memory_t));
         } else {
             update_last_error(CApiError {
-                msg:
-                    "Internal error: memory is imported but the list of imported memories is empty"
-                        .to_string(),
+                msg: "Internal error: memory is imported but it is not in the list of imported memories"
+                    .to_string(),
             });
             return None;
         }
@@ -122,7 +122,7 @@ pub unsafe extern "C" fn wasmer_module_instantiate(
         let export = match import.tag {
             wasmer_import_export_kind::WASM_MEMORY => {
                 let mem = import.value.memory as *mut Memory;
-                imported_memories.push(mem);
+                imported_memories.push((module_name.to_string(), import_name.to_string(), mem));
                 Extern::Memory((&*mem).clone())
             }
             wasmer_import_export_kind::WASM_FUNCTION => {
@@ -2,10 +2,11 @@ use super::externals::{wasm_extern_t, wasm_extern_vec_t};
 use super::module::wasm_module_t;
 use super::store::wasm_store_t;
 use super::trap::wasm_trap_t;
+use crate::error::{update_last_error, CApiError};
 use crate::ordered_resolver::OrderedResolver;
 use std::mem;
 use std::sync::Arc;
-use wasmer::{Extern, Instance, InstantiationError};
+use wasmer::{Extern, ExternType, Instance, InstantiationError};
 
 /// Opaque type representing a WebAssembly instance.
 #[allow(non_camel_case_types)]
@@ -47,6 +48,21 @@ pub unsafe extern "C" fn wasm_instance_new(
     let imports = imports?;
 
     let wasm_module = &module.inner;
+    if let Some(import) = wasm_module
+        .imports()
+        .find(|import| matches!(import.ty(), ExternType::Tag(_)))
+    {
+        update_last_error(CApiError {
+            msg: format!(
+                "the tag `{}` `{}` can't be imported through the Wasm C API",
+                import.module(),
+                import.name()
+            ),
+        });
+
+        return None;
+    }
+
     let module_imports = wasm_module.imports();
     let module_import_count = module_imports.len();
     let resolver: OrderedResolver = imports
@@ -184,6 +200,8 @@ pub unsafe extern "C" fn wasm_instance_exports(
     let mut extern_vec = instance
         .exports
         .iter()
+        // The Wasm C API has no tags.
+        .filter(|(_, r#extern)| !matches!(r#extern, Extern::Tag(_)))
         .map(|(name, r#extern)| {
             let function = if let Extern::Function { .. } = r#extern {
                 instance.exports.get_function(&name).ok().cloned()
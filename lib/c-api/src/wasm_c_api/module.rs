@@ -6,7 +6,7 @@ use super::types::{
 use crate::error::{update_last_error, CApiError};
 use std::ptr::NonNull;
 use std::sync::Arc;
-use wasmer::Module;
+use wasmer::{ExternType, Module};
 
 /// Opaque type representing a WebAssembly module.
 #[allow(non_camel_case_types)]
@@ -235,9 +235,11 @@ pub unsafe extern "C" fn wasm_module_exports(
     // own
     out: &mut wasm_exporttype_vec_t,
 ) {
+    // The Wasm C API has no tags.
     let exports = module
         .inner
         .exports()
+        .filter(|export| !matches!(export.ty(), ExternType::Tag(_)))
         .map(Into::into)
         .map(Box::new)
         .collect::<Vec<Box<wasm_exporttype_t>>>();
@@ -376,9 +378,12 @@ pub unsafe extern "C" fn wasm_module_imports(
     // own
     out: &mut wasm_importtype_vec_t,
 ) {
+    // The Wasm C API has no tags: modules importing them can't be
+    // instantiated by `wasm_instance_new`.
     let imports = module
         .inner
         .imports()
+        .filter(|import| !matches!(import.ty(), ExternType::Tag(_)))
         .map(Into::into)
         .map(Box::new)
         .collect::<Vec<Box<wasm_importtype_t>>>();
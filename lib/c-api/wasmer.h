@@ -984,8 +984,6 @@ const wasmer_instance_context_t *wasmer_instance_context_get(wasmer_instance_t *
 /**
  * Gets the `memory_idx`th memory of the instance.
  *
- * Note that the index is always `0` until multiple memories are supported.
- *
  * This function is mostly used inside host functions (aka imported
  * functions) to read the instance memory.
  *
@@ -0,0 +1,662 @@
+//! A compile cache at the granularity of functions.
+//!
+//! Wrapping a compiler configuration in a [`FunctionCache`] makes its
+//! compiler reuse the functions it compiled before from a directory, so
+//! recompiling a module after a small edit only compiles the functions
+//! that changed.
+
+use crate::hash::Hash;
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{Debug, Write};
+use std::fs::{self, create_dir_all};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use wasmer_compiler::wasmparser::{self, Operator, TypeOrFuncType};
+use wasmer_compiler::{
+    Compilation, CompileError, CompileModuleInfo, CompiledFunction, Compiler, CompilerConfig,
+    CustomSection, CustomSectionProtection, Dwarf, Features, FunctionBodyData, ModuleMiddleware,
+    ModuleTranslationState, Relocation, RelocationTarget, SectionBody, SectionIndex, SourceLoc,
+    SymbolRegistry, Target,
+};
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{FunctionIndex, GlobalIndex, LocalFunctionIndex, SignatureIndex, TagIndex};
+use wasmer_vm::{ModuleInfo, VMOffsets};
+
+/// A compiler configuration whose compiler caches the compiled functions
+/// in a directory.
+///
+/// A function is reused when its body, its signature, the declarations
+/// it refers to, the layout of the instances of its module, the target
+/// and the configuration are the same as when it was cached. The
+/// configuration is told apart by its `Debug` representation, which
+/// includes its middlewares: a middleware whose output depends on
+/// something its `Debug` implementation leaves out must not be used with
+/// the cache.
+///
+/// The functions are compiled one by one with
+/// [`Compiler::compile_function`], so the custom sections and unwind
+/// information of each function can be cached along with it.
+///
+/// # Usage
+///
+/// ```ignore
+/// let config = FunctionCache::new(Singlepass::default(), "some/directory")?;
+/// let store = Store::new(&JIT::new(config).engine());
+/// ```
+pub struct FunctionCache {
+    config: Box<dyn DebugCompilerConfig>,
+    path: PathBuf,
+}
+
+/// A compiler configuration that can be told apart from the others.
+trait DebugCompilerConfig: CompilerConfig + Debug {
+    fn into_compiler_config(self: Box<Self>) -> Box<dyn CompilerConfig>;
+}
+
+impl<T: CompilerConfig + Debug + 'static> DebugCompilerConfig for T {
+    fn into_compiler_config(self: Box<Self>) -> Box<dyn CompilerConfig> {
+        self
+    }
+}
+
+impl FunctionCache {
+    /// Wrap `config`, caching the functions in the directory at `path`.
+    pub fn new<C, P>(config: C, path: P) -> io::Result<Self>
+    where
+        C: CompilerConfig + Debug + 'static,
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        create_dir_all(&path)?;
+        Ok(Self {
+            config: Box::new(config),
+            path,
+        })
+    }
+}
+
+impl CompilerConfig for FunctionCache {
+    fn enable_pic(&mut self) {
+        self.config.enable_pic();
+    }
+
+    fn enable_verifier(&mut self) {
+        self.config.enable_verifier();
+    }
+
+    fn compiler(self: Box<Self>) -> Box<dyn Compiler> {
+        let Self { config, path } = *self;
+        let config_key = format!("{}\0{:?}", env!("CARGO_PKG_VERSION"), config);
+        Box::new(FunctionCacheCompiler {
+            compiler: config.into_compiler_config().compiler(),
+            config_key,
+            path,
+        })
+    }
+
+    fn default_features_for_target(&self, target: &Target) -> Features {
+        self.config.default_features_for_target(target)
+    }
+
+    fn push_middleware(&mut self, middleware: Arc<dyn ModuleMiddleware>) {
+        self.config.push_middleware(middleware);
+    }
+}
+
+/// A compiled function, as stored in the cache.
+#[derive(Serialize, Deserialize)]
+struct CachedFunction {
+    /// The offset of the function body in the module it was compiled from.
+    module_offset: usize,
+    function: CompiledFunction,
+    custom_sections: PrimaryMap<SectionIndex, CustomSection>,
+    /// The custom section holding the unwind information of the
+    /// function, if any.
+    eh_frame: Option<SectionIndex>,
+}
+
+impl CachedFunction {
+    fn new(module_offset: usize, compilation: &Compilation) -> Self {
+        Self {
+            module_offset,
+            function: compilation.get(LocalFunctionIndex::new(0)).clone(),
+            custom_sections: compilation.get_custom_sections(),
+            eh_frame: compilation.get_debug().map(|debug| debug.eh_frame),
+        }
+    }
+
+    /// Move the source locations of the function to the function body at
+    /// `module_offset`.
+    fn rebase(&mut self, module_offset: usize) {
+        let cached_offset = self.module_offset;
+        let rebase = |srcloc: &mut SourceLoc| {
+            if !srcloc.is_default() {
+                let offset = srcloc.bits() as usize + module_offset - cached_offset;
+                *srcloc = SourceLoc::new(offset as u32);
+            }
+        };
+        let address_map = &mut self.function.frame_info.address_map;
+        rebase(&mut address_map.start_srcloc);
+        rebase(&mut address_map.end_srcloc);
+        for instruction in address_map.instructions.iter_mut() {
+            rebase(&mut instruction.srcloc);
+        }
+        self.module_offset = module_offset;
+    }
+}
+
+/// The compiler of a [`FunctionCache`].
+struct FunctionCacheCompiler {
+    compiler: Box<dyn Compiler>,
+    config_key: String,
+    path: PathBuf,
+}
+
+impl FunctionCacheCompiler {
+    /// What the compiled code of every function of the module depends
+    /// on, besides the function itself and the declarations it refers to.
+    fn layout_key(&self, target: &Target, module: &CompileModuleInfo) -> String {
+        let info = &module.module;
+        let pointer_size = target
+            .triple()
+            .pointer_width()
+            .map(|width| width.bytes())
+            .unwrap_or(8);
+        // Compilers describe the locals of the modules with DWARF.
+        let has_dwarf = info.custom_sections.contains_key(".debug_info");
+        format!(
+            "{}\0{:?}\0{:?}\0{:?}\0{:?}\0{:?}\0{:?}\0{:?}\0{:?}\0{}",
+            self.config_key,
+            target.triple(),
+            target.cpu_features(),
+            module.features,
+            VMOffsets::new(pointer_size, info),
+            info.memories,
+            info.tables,
+            module.memory_styles,
+            module.table_styles,
+            has_dwarf,
+        )
+    }
+
+    /// The path the function `index` is cached at, unless it can't be
+    /// told apart from other functions.
+    fn function_path(
+        &self,
+        layout_key: &str,
+        module: &ModuleInfo,
+        function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData>,
+        index: LocalFunctionIndex,
+    ) -> Option<PathBuf> {
+        let body = &function_bodies[index];
+        let mut key = layout_key.to_string();
+        let signature = module.functions[module.func_index(index)];
+        write!(key, "\0{:?}", module.signatures[signature]).ok()?;
+        write_references(&mut key, module, function_bodies, body)?;
+        let mut key = key.into_bytes();
+        key.push(0);
+        key.extend_from_slice(body.data);
+        Some(self.path.join(Hash::generate(&key).to_string()))
+    }
+
+    /// Load the function `index` from the cache, or compile and cache it.
+    fn function(
+        &self,
+        target: &Target,
+        module: &CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        layout_key: &str,
+        function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData>,
+        index: LocalFunctionIndex,
+    ) -> Result<CachedFunction, CompileError> {
+        let body = &function_bodies[index];
+        let path = self.function_path(layout_key, &module.module, function_bodies, index);
+        if let Some(mut cached) = path.as_deref().and_then(Self::load) {
+            cached.rebase(body.module_offset);
+            return Ok(cached);
+        }
+
+        let mut module = CompileModuleInfo {
+            module: module.module.clone(),
+            features: module.features.clone(),
+            memory_styles: module.memory_styles.clone(),
+            table_styles: module.table_styles.clone(),
+        };
+        let compilation =
+            self.compiler
+                .compile_function(target, &mut module, module_translation, index, body)?;
+        let cached = CachedFunction::new(body.module_offset, &compilation);
+        if let Some(path) = path {
+            // The cache is best-effort: a function that can't be stored
+            // is compiled again next time.
+            let _ = Self::store(&path, &cached);
+        }
+        Ok(cached)
+    }
+
+    fn load(path: &Path) -> Option<CachedFunction> {
+        let bytes = fs::read(path).ok()?;
+        bincode::deserialize(&bytes).ok()
+    }
+
+    fn store(path: &Path, cached: &CachedFunction) -> io::Result<()> {
+        let bytes = bincode::serialize(cached)
+            .map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+        // Write to a temporary file first, so concurrent compilations
+        // never read a partially written function.
+        let temporary = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&temporary, bytes)?;
+        fs::rename(&temporary, path)
+    }
+}
+
+/// Write the declarations the function `body` refers to, or `None` if
+/// it can't be read.
+///
+/// The bodies of the local functions it refers to are written too, since
+/// middlewares may inline them.
+fn write_references(
+    key: &mut String,
+    module: &ModuleInfo,
+    function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData>,
+    body: &FunctionBodyData,
+) -> Option<()> {
+    let mut functions = BTreeSet::new();
+    let mut signatures = BTreeSet::new();
+    let mut globals = BTreeSet::new();
+    let mut tags = BTreeSet::new();
+    let mut reader = wasmparser::FunctionBody::new(0, body.data)
+        .get_operators_reader()
+        .ok()?;
+    while !reader.eof() {
+        match reader.read().ok()? {
+            Operator::Call { function_index }
+            | Operator::ReturnCall { function_index }
+            | Operator::RefFunc { function_index } => {
+                functions.insert(FunctionIndex::from_u32(function_index));
+            }
+            Operator::CallIndirect { index, .. } | Operator::ReturnCallIndirect { index, .. } => {
+                signatures.insert(SignatureIndex::from_u32(index));
+            }
+            Operator::Block { ty }
+            | Operator::Loop { ty }
+            | Operator::If { ty }
+            | Operator::Try { ty } => {
+                if let TypeOrFuncType::FuncType(index) = ty {
+                    signatures.insert(SignatureIndex::from_u32(index));
+                }
+            }
+            Operator::GlobalGet { global_index } | Operator::GlobalSet { global_index } => {
+                globals.insert(GlobalIndex::from_u32(global_index));
+            }
+            Operator::Throw { index } | Operator::Catch { index } => {
+                tags.insert(TagIndex::from_u32(index));
+            }
+            _ => {}
+        }
+    }
+
+    for index in functions {
+        let signature = module.functions.get(index)?;
+        write!(key, "\0f{}:{:?}", index.index(), signature).ok()?;
+        if let Some(local_index) = module.local_func_index(index) {
+            let body = function_bodies.get(local_index)?;
+            write!(key, ":{}", Hash::generate(body.data).to_string()).ok()?;
+        }
+        signatures.insert(*signature);
+    }
+    for index in tags {
+        let signature = module.tags.get(index)?;
+        write!(key, "\0e{}:{:?}", index.index(), signature).ok()?;
+        signatures.insert(*signature);
+    }
+    for index in signatures {
+        let signature = module.signatures.get(index)?;
+        write!(key, "\0t{}:{:?}", index.index(), signature).ok()?;
+    }
+    for index in globals {
+        let global = module.globals.get(index)?;
+        write!(key, "\0g{}:{:?}", index.index(), global).ok()?;
+    }
+    Some(())
+}
+
+/// The custom sections of a module, gathered from the compilations of
+/// its functions.
+#[derive(Default)]
+struct CustomSections {
+    sections: PrimaryMap<SectionIndex, CustomSection>,
+    /// The sections without relocations, by contents, so the ones
+    /// compiled for several functions are only kept once.
+    shared: HashMap<(bool, Vec<u8>), SectionIndex>,
+    /// The section the unwind information of all the compilations is
+    /// gathered in.
+    eh_frame_index: Option<SectionIndex>,
+    eh_frame: EhFrame,
+}
+
+impl CustomSections {
+    /// Gather the `sections` of the function `index`, or of the module
+    /// if `None`, returning their new indices.
+    ///
+    /// Returns `None` if the unwind information in `eh_frame` can't be
+    /// read.
+    fn add(
+        &mut self,
+        index: Option<LocalFunctionIndex>,
+        sections: PrimaryMap<SectionIndex, CustomSection>,
+        eh_frame: Option<SectionIndex>,
+    ) -> Option<PrimaryMap<SectionIndex, SectionIndex>> {
+        let mut indices = PrimaryMap::with_capacity(sections.len());
+        let mut relocated = vec![];
+        let mut unwind_info = None;
+        for (section_index, section) in sections {
+            let new_index = if Some(section_index) == eh_frame {
+                unwind_info = Some(section);
+                self.eh_frame_index()
+            } else if section.relocations.is_empty() {
+                self.share(section)
+            } else {
+                let new_index = self.sections.push(section);
+                relocated.push(new_index);
+                new_index
+            };
+            indices.push(new_index);
+        }
+
+        for new_index in relocated {
+            for relocation in self.sections[new_index].relocations.iter_mut() {
+                map_relocation(relocation, index, &indices);
+            }
+        }
+        if let Some(section) = unwind_info {
+            self.eh_frame.append(&section, |relocation| {
+                map_relocation(relocation, index, &indices)
+            })?;
+        }
+        Some(indices)
+    }
+
+    fn share(&mut self, section: CustomSection) -> SectionIndex {
+        let executable = section.protection == CustomSectionProtection::ReadExecute;
+        let key = (executable, section.bytes.as_slice().to_vec());
+        let sections = &mut self.sections;
+        *self
+            .shared
+            .entry(key)
+            .or_insert_with(|| sections.push(section))
+    }
+
+    fn eh_frame_index(&mut self) -> SectionIndex {
+        let sections = &mut self.sections;
+        *self.eh_frame_index.get_or_insert_with(|| {
+            sections.push(CustomSection {
+                protection: CustomSectionProtection::Read,
+                bytes: SectionBody::new_with_vec(vec![]),
+                relocations: vec![],
+            })
+        })
+    }
+
+    /// The gathered sections, and where the unwind information is.
+    fn finish(mut self) -> (PrimaryMap<SectionIndex, CustomSection>, Option<Dwarf>) {
+        let dwarf = match (self.eh_frame_index, self.eh_frame.finish()) {
+            (Some(index), Some(section)) => {
+                self.sections[index] = section;
+                Some(Dwarf::new(index))
+            }
+            _ => None,
+        };
+        (self.sections, dwarf)
+    }
+}
+
+/// Point `relocation`, compiled for the function `index`, to where its
+/// target ended up in the module.
+///
+/// The relocations of the sections of a function to local functions
+/// refer to the function itself, wherever it was when it was cached.
+fn map_relocation(
+    relocation: &mut Relocation,
+    index: Option<LocalFunctionIndex>,
+    sections: &PrimaryMap<SectionIndex, SectionIndex>,
+) {
+    match &mut relocation.reloc_target {
+        RelocationTarget::CustomSection(section) => *section = sections[*section],
+        RelocationTarget::LocalFunc(function) | RelocationTarget::JumpTable(function, _) => {
+            if let Some(index) = index {
+                *function = index;
+            }
+        }
+        RelocationTarget::LibCall(_) => {}
+    }
+}
+
+/// An `.eh_frame` section gathered from the ones of several
+/// compilations, sharing their identical CIEs.
+#[derive(Default)]
+struct EhFrame {
+    bytes: Vec<u8>,
+    relocations: Vec<Relocation>,
+    /// The offsets of the CIEs without relocations, by contents.
+    cies: HashMap<Vec<u8>, usize>,
+}
+
+impl EhFrame {
+    /// Append the entries of `section` up to its terminator, or return
+    /// `None` if it can't be read.
+    fn append(
+        &mut self,
+        section: &CustomSection,
+        mut map_relocation: impl FnMut(&mut Relocation),
+    ) -> Option<()> {
+        let data = section.bytes.as_slice();
+        let read_u32 = |offset: usize| -> Option<usize> {
+            let bytes = data.get(offset..offset.checked_add(4)?)?;
+            Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+        };
+        // The new offsets of the CIEs of `section`, by their offsets.
+        let mut cies = HashMap::new();
+        let mut offset = 0;
+        while offset < data.len() {
+            let length = read_u32(offset)?;
+            // 64-bit entries are never emitted for the code of a module.
+            if length == 0 || length == 0xffff_ffff {
+                break;
+            }
+            let end = offset.checked_add(4 + length)?;
+            let entry = data.get(offset..end)?;
+            let relocations = section
+                .relocations
+                .iter()
+                .filter(|relocation| (offset..end).contains(&(relocation.offset as usize)))
+                .collect::<Vec<_>>();
+            let cie_pointer = read_u32(offset + 4)?;
+
+            let new_offset = self.bytes.len();
+            if cie_pointer == 0 {
+                if relocations.is_empty() {
+                    if let Some(&shared) = self.cies.get(entry) {
+                        cies.insert(offset, shared);
+                        offset = end;
+                        continue;
+                    }
+                    self.cies.insert(entry.to_vec(), new_offset);
+                }
+                cies.insert(offset, new_offset);
+                self.bytes.extend_from_slice(entry);
+            } else {
+                // The CIE pointer is relative to the pointer itself.
+                let cie = (offset + 4).checked_sub(cie_pointer)?;
+                let new_cie = *cies.get(&cie)?;
+                let new_cie_pointer = (new_offset + 4 - new_cie) as u32;
+                self.bytes.extend_from_slice(entry);
+                self.bytes[new_offset + 4..new_offset + 8]
+                    .copy_from_slice(&new_cie_pointer.to_le_bytes());
+            }
+            for relocation in relocations {
+                let mut relocation = relocation.clone();
+                relocation.offset = (relocation.offset as usize - offset + new_offset) as u32;
+                map_relocation(&mut relocation);
+                self.relocations.push(relocation);
+            }
+            offset = end;
+        }
+        Some(())
+    }
+
+    /// The gathered section, if any entry was added.
+    fn finish(self) -> Option<CustomSection> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let mut bytes = self.bytes;
+        // The table ends with an entry of length 0.
+        bytes.extend_from_slice(&[0, 0, 0, 0]);
+        Some(CustomSection {
+            protection: CustomSectionProtection::Read,
+            bytes: SectionBody::new_with_vec(bytes),
+            relocations: self.relocations,
+        })
+    }
+}
+
+/// Gather the compilation of a module from the one of its skeleton and
+/// the ones of its functions, or return `None` if their unwind
+/// information can't be read.
+fn gather(
+    skeleton: &Compilation,
+    cached_functions: Vec<(LocalFunctionIndex, CachedFunction)>,
+) -> Option<Compilation> {
+    let mut custom_sections = CustomSections::default();
+    custom_sections.add(
+        None,
+        skeleton.get_custom_sections(),
+        skeleton.get_debug().map(|debug| debug.eh_frame),
+    )?;
+    let mut functions = PrimaryMap::with_capacity(cached_functions.len());
+    for (index, cached) in cached_functions {
+        let sections = custom_sections.add(Some(index), cached.custom_sections, cached.eh_frame)?;
+        let mut function = cached.function;
+        for relocation in function.relocations.iter_mut() {
+            match &mut relocation.reloc_target {
+                RelocationTarget::CustomSection(section) => *section = sections[*section],
+                RelocationTarget::JumpTable(function, _) => *function = index,
+                RelocationTarget::LocalFunc(_) | RelocationTarget::LibCall(_) => {}
+            }
+        }
+        functions.push(function);
+    }
+
+    let (custom_sections, debug) = custom_sections.finish();
+    Some(Compilation::new(
+        functions,
+        custom_sections,
+        skeleton.get_function_call_trampolines(),
+        skeleton.get_dynamic_function_trampolines(),
+        debug,
+    ))
+}
+
+impl Compiler for FunctionCacheCompiler {
+    fn validate_module<'data>(
+        &self,
+        features: &Features,
+        data: &'data [u8],
+    ) -> Result<(), CompileError> {
+        self.compiler.validate_module(features, data)
+    }
+
+    fn transform_module_info(&self, module: &mut ModuleInfo) {
+        self.compiler.transform_module_info(module)
+    }
+
+    fn analyze_function_bodies(
+        &self,
+        module: &ModuleInfo,
+        function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) {
+        self.compiler
+            .analyze_function_bodies(module, function_body_inputs)
+    }
+
+    fn compile_module<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module mut CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+    ) -> Result<Compilation, CompileError> {
+        // Compiling no function gives the trampolines, and the custom
+        // sections of the module.
+        let skeleton =
+            self.compiler
+                .compile_module(target, module, module_translation, PrimaryMap::new())?;
+
+        let layout_key = self.layout_key(target, module);
+        let cached_functions = function_body_inputs
+            .keys()
+            .collect::<Vec<_>>()
+            .par_iter()
+            .map(|&index| {
+                let cached = self.function(
+                    target,
+                    module,
+                    module_translation,
+                    &layout_key,
+                    &function_body_inputs,
+                    index,
+                )?;
+                Ok((index, cached))
+            })
+            .collect::<Result<Vec<_>, CompileError>>()?;
+
+        match gather(&skeleton, cached_functions) {
+            Some(compilation) => Ok(compilation),
+            // Unwind information that can't be gathered is left to the
+            // compiler.
+            None => self.compiler.compile_module(
+                target,
+                module,
+                module_translation,
+                function_body_inputs,
+            ),
+        }
+    }
+
+    fn compile_function<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module mut CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'data>,
+    ) -> Result<Compilation, CompileError> {
+        self.compiler
+            .compile_function(target, module, module_translation, index, input)
+    }
+
+    fn experimental_native_compile_module<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module mut CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
+        symbol_registry: &dyn SymbolRegistry,
+        wasmer_metadata: &[u8],
+    ) -> Option<Result<Vec<u8>, CompileError>> {
+        // Object files are produced for the whole module at once, so
+        // they are never cached.
+        self.compiler.experimental_native_compile_module(
+            target,
+            module,
+            module_translation,
+            function_body_inputs,
+            symbol_registry,
+            wasmer_metadata,
+        )
+    }
+}
@@ -21,10 +21,14 @@
 
 mod cache;
 mod filesystem;
+#[cfg(feature = "function-cache")]
+mod function_cache;
 mod hash;
 
 pub use crate::cache::Cache;
 pub use crate::filesystem::FileSystemCache;
+#[cfg(feature = "function-cache")]
+pub use crate::function_cache::FunctionCache;
 pub use crate::hash::Hash;
 
 // We re-export those for convinience of users
@@ -6,7 +6,7 @@ use crate::commands::Compile;
 use crate::commands::CreateExe;
 #[cfg(feature = "wast")]
 use crate::commands::Wast;
-use crate::commands::{Cache, Config, Inspect, Run, SelfUpdate, Validate};
+use crate::commands::{Cache, Config, Inspect, Repl, Run, SelfUpdate, Validate};
 use crate::error::PrettyError;
 use anyhow::Result;
 
@@ -62,6 +62,10 @@ enum WasmerCLIOptions {
     #[structopt(name = "inspect")]
     Inspect(Inspect),
 
+    /// Explore a WebAssembly module interactively
+    #[structopt(name = "repl")]
+    Repl(Repl),
+
     /// Run spec testsuite
     #[cfg(feature = "wast")]
     #[structopt(name = "wast")]
@@ -81,6 +85,7 @@ impl WasmerCLIOptions {
             Self::CreateExe(create_exe) => create_exe.execute(),
             Self::Config(config) => config.execute(),
             Self::Inspect(inspect) => inspect.execute(),
+            Self::Repl(repl) => repl.execute(),
             #[cfg(feature = "wast")]
             Self::Wast(wast) => wast.execute(),
         }
@@ -100,7 +105,7 @@ pub fn wasmer_main() {
     let args = std::env::args().collect::<Vec<_>>();
     let command = args.get(1);
     let options = match command.unwrap_or(&"".to_string()).as_ref() {
-        "cache" | "compile" | "config" | "create-exe" | "help" | "inspect" | "run"
+        "cache" | "compile" | "config" | "create-exe" | "help" | "inspect" | "repl" | "run"
         | "self-update" | "validate" | "wast" => WasmerCLIOptions::from_args(),
         _ => {
             WasmerCLIOptions::from_iter_safe(args.iter()).unwrap_or_else(|e| {
@@ -6,6 +6,7 @@ mod config;
 #[cfg(all(feature = "object-file", feature = "compiler"))]
 mod create_exe;
 mod inspect;
+mod repl;
 mod run;
 mod self_update;
 mod validate;
@@ -18,4 +19,4 @@ pub use compile::*;
 pub use create_exe::*;
 #[cfg(feature = "wast")]
 pub use wast::*;
-pub use {cache::*, config::*, inspect::*, run::*, self_update::*, validate::*};
+pub use {cache::*, config::*, inspect::*, repl::*, run::*, self_update::*, validate::*};
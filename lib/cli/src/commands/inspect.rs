@@ -1,23 +1,101 @@
 use crate::store::StoreOptions;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Error, Result};
 use bytesize::ByteSize;
+use serde::Serialize;
 use std::path::PathBuf;
+use std::str::FromStr;
 use structopt::StructOpt;
 use wasmer::*;
 
+mod details;
+
+use details::ModuleDetails;
+
 #[derive(Debug, StructOpt)]
-/// The options for the `wasmer validate` subcommand
+/// The options for the `wasmer inspect` subcommand
 pub struct Inspect {
-    /// File to validate as WebAssembly
+    /// File to inspect as WebAssembly
     #[structopt(name = "FILE", parse(from_os_str))]
     path: PathBuf,
 
+    /// Output format: `text` or `json`
+    #[structopt(long = "format", default_value = "text")]
+    format: InspectFormat,
+
     #[structopt(flatten)]
     store: StoreOptions,
 }
 
+/// The output format of the `wasmer inspect` subcommand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InspectFormat {
+    /// Human readable text
+    Text,
+    /// JSON, for consumption by other tools
+    Json,
+}
+
+impl FromStr for InspectFormat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            format => bail!("The `{}` format is not supported.", format),
+        }
+    }
+}
+
+/// An import or export of the module, as shown in the output.
+#[derive(Debug, Serialize)]
+struct ExternDetails {
+    module: Option<String>,
+    name: String,
+    #[serde(rename = "type")]
+    ty: String,
+}
+
+/// The imports or exports of the module, grouped by kind.
+#[derive(Debug, Default, Serialize)]
+struct ExternsDetails {
+    functions: Vec<ExternDetails>,
+    memories: Vec<ExternDetails>,
+    tables: Vec<ExternDetails>,
+    globals: Vec<ExternDetails>,
+    tags: Vec<ExternDetails>,
+}
+
+impl ExternsDetails {
+    fn push(&mut self, module: Option<&str>, name: &str, ty: &ExternType) {
+        let (list, ty) = match ty {
+            ExternType::Function(ty) => (&mut self.functions, ty.to_string()),
+            ExternType::Memory(ty) => (&mut self.memories, ty.to_string()),
+            ExternType::Table(ty) => (&mut self.tables, ty.to_string()),
+            ExternType::Global(ty) => (&mut self.globals, ty.to_string()),
+            ExternType::Tag(ty) => (&mut self.tags, ty.to_string()),
+        };
+        list.push(ExternDetails {
+            module: module.map(ToString::to_string),
+            name: name.to_string(),
+            ty,
+        });
+    }
+}
+
+/// The full report printed by `wasmer inspect`.
+#[derive(Debug, Serialize)]
+struct InspectReport {
+    #[serde(rename = "type")]
+    ty: &'static str,
+    size: usize,
+    imports: ExternsDetails,
+    exports: ExternsDetails,
+    #[serde(flatten)]
+    details: ModuleDetails,
+}
+
 impl Inspect {
-    /// Runs logic for the `validate` subcommand
+    /// Runs logic for the `inspect` subcommand
     pub fn execute(&self) -> Result<()> {
         self.inner_execute()
             .context(format!("failed to inspect `{}`", self.path.display()))
@@ -26,49 +104,153 @@ impl Inspect {
         let (store, _engine_type, _compiler_type) = self.store.get_store()?;
         let module_contents = std::fs::read(&self.path)?;
         let module = Module::new(&store, &module_contents)?;
-        println!(
-            "Type: {}",
-            if !is_wasm(&module_contents) {
-                "wat"
-            } else {
-                "wasm"
-            }
-        );
-        println!("Size: {}", ByteSize(module_contents.len() as _));
-        println!("Imports:");
-        println!("  Functions:");
-        for f in module.imports().functions() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
-        }
-        println!("  Memories:");
-        for f in module.imports().memories() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        let is_wat = !is_wasm(&module_contents);
+        #[cfg(feature = "wat")]
+        let details = ModuleDetails::parse(&wat2wasm(&module_contents)?)?;
+        #[cfg(not(feature = "wat"))]
+        let details = ModuleDetails::parse(&module_contents)?;
+
+        let mut imports = ExternsDetails::default();
+        for import in module.imports() {
+            imports.push(Some(import.module()), import.name(), import.ty());
         }
-        println!("  Tables:");
-        for f in module.imports().tables() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+        let mut exports = ExternsDetails::default();
+        for export in module.exports() {
+            exports.push(None, export.name(), export.ty());
         }
-        println!("  Globals:");
-        for f in module.imports().globals() {
-            println!("    \"{}\".\"{}\": {}", f.module(), f.name(), f.ty());
+
+        let report = InspectReport {
+            ty: if is_wat { "wat" } else { "wasm" },
+            size: module_contents.len(),
+            imports,
+            exports,
+            details,
+        };
+        match self.format {
+            InspectFormat::Text => print_text(&report),
+            InspectFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
         }
-        println!("Exports:");
-        println!("  Functions:");
-        for f in module.exports().functions() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+        Ok(())
+    }
+}
+
+fn print_externs(externs: &ExternsDetails) {
+    let kinds = [
+        ("Functions", &externs.functions),
+        ("Memories", &externs.memories),
+        ("Tables", &externs.tables),
+        ("Globals", &externs.globals),
+        ("Tags", &externs.tags),
+    ];
+    for (kind, list) in kinds.iter() {
+        println!("  {}:", kind);
+        for e in list.iter() {
+            match &e.module {
+                Some(module) => println!("    \"{}\".\"{}\": {}", module, e.name, e.ty),
+                None => println!("    \"{}\": {}", e.name, e.ty),
+            }
         }
-        println!("  Memories:");
-        for f in module.exports().memories() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+}
+
+fn print_text(report: &InspectReport) {
+    let details = &report.details;
+    println!("Type: {}", report.ty);
+    println!("Size: {}", ByteSize(report.size as _));
+    println!("Imports:");
+    print_externs(&report.imports);
+    println!("Exports:");
+    print_externs(&report.exports);
+    if let Some(start) = details.start_function {
+        println!("Start function: {}", start);
+    }
+    println!("Memories:");
+    for (index, memory) in details.memories.iter().enumerate() {
+        println!(
+            "  {}: {}min: {}, max: {}{}{}",
+            index,
+            if memory.imported { "(imported) " } else { "" },
+            memory.limits.minimum,
+            memory
+                .limits
+                .maximum
+                .map_or_else(|| "none".to_string(), |max| max.to_string()),
+            if memory.shared { ", shared" } else { "" },
+            if memory.memory64 { ", 64-bit" } else { "" },
+        );
+    }
+    println!("Tables:");
+    for (index, table) in details.tables.iter().enumerate() {
+        println!(
+            "  {}: {}{} min: {}, max: {}",
+            index,
+            if table.imported { "(imported) " } else { "" },
+            table.element_type,
+            table.limits.minimum,
+            table
+                .limits
+                .maximum
+                .map_or_else(|| "none".to_string(), |max| max.to_string()),
+        );
+    }
+    println!("Data segments:");
+    for (index, segment) in details.data_segments.iter().enumerate() {
+        match segment.memory_index {
+            Some(memory) => println!(
+                "  {}: {} (memory {})",
+                index,
+                ByteSize(segment.size as _),
+                memory
+            ),
+            None => println!("  {}: {} (passive)", index, ByteSize(segment.size as _)),
         }
-        println!("  Tables:");
-        for f in module.exports().tables() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+    println!("Element segments:");
+    for (index, segment) in details.element_segments.iter().enumerate() {
+        match segment.table_index {
+            Some(table) => println!("  {}: {} elements (table {})", index, segment.size, table),
+            None => println!("  {}: {} elements ({})", index, segment.size, segment.kind),
         }
-        println!("  Globals:");
-        for f in module.exports().globals() {
-            println!("    \"{}\": {}", f.name(), f.ty());
+    }
+    println!("Custom sections:");
+    for section in details.custom_sections.iter() {
+        println!("  \"{}\": {}", section.name, ByteSize(section.size as _));
+    }
+    if let Some(names) = &details.name_section {
+        println!("Name section:");
+        if let Some(module) = &names.module {
+            println!("  Module: \"{}\"", module);
         }
-        Ok(())
+        println!("  Function names: {}", names.functions.len());
+        println!(
+            "  Functions with local names: {}",
+            names.functions_with_local_names
+        );
+    }
+    println!("Function bodies:");
+    for function in details.functions.iter() {
+        println!(
+            "  {}{}: {}, {} locals",
+            function.index,
+            function
+                .name
+                .as_ref()
+                .map_or_else(String::new, |name| format!(" \"{}\"", name)),
+            ByteSize(function.size as _),
+            function.locals
+        );
     }
+    println!(
+        "Features used: {}",
+        if details.features.is_empty() {
+            "none".to_string()
+        } else {
+            details
+                .features
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    );
 }
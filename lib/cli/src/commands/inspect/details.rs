@@ -0,0 +1,695 @@
+//! Gathers the low-level details of a WebAssembly module that are not
+//! exposed through `wasmer::Module` (custom sections, segments, function
+//! bodies, ...) by walking the binary with `wasmparser`.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use wasmparser::{
+    Data, DataKind, Element, ElementKind, ImportSectionEntryType, MemoryType, Name, Operator,
+    Parser, Payload, TypeDef, TypeOrFuncType,
+};
+
+/// The limits of a memory or a table.
+#[derive(Debug, Serialize)]
+pub struct Limits {
+    /// The initial size (in pages for memories, elements for tables).
+    pub minimum: u64,
+    /// The maximum size, if any.
+    pub maximum: Option<u64>,
+}
+
+/// A memory, either imported or defined in the module.
+#[derive(Debug, Serialize)]
+pub struct MemoryDetails {
+    /// Whether the memory is imported.
+    pub imported: bool,
+    /// The memory limits, in Wasm pages.
+    pub limits: Limits,
+    /// Whether the memory is shared.
+    pub shared: bool,
+    /// Whether the memory uses 64-bit indexes.
+    pub memory64: bool,
+}
+
+/// A table, either imported or defined in the module.
+#[derive(Debug, Serialize)]
+pub struct TableDetails {
+    /// Whether the table is imported.
+    pub imported: bool,
+    /// The element type of the table.
+    pub element_type: String,
+    /// The table limits, in elements.
+    pub limits: Limits,
+}
+
+/// A custom section.
+#[derive(Debug, Serialize)]
+pub struct CustomSectionDetails {
+    /// The name of the custom section.
+    pub name: String,
+    /// The size of the section payload, in bytes.
+    pub size: usize,
+}
+
+/// The contents of the `name` custom section.
+#[derive(Debug, Default, Serialize)]
+pub struct NameSectionDetails {
+    /// The module name, if any.
+    pub module: Option<String>,
+    /// The named functions, by function index.
+    pub functions: Vec<(u32, String)>,
+    /// The number of functions with named locals.
+    pub functions_with_local_names: u32,
+}
+
+/// A data segment.
+#[derive(Debug, Serialize)]
+pub struct DataSegmentDetails {
+    /// Whether the segment is passive.
+    pub passive: bool,
+    /// The memory the segment is copied into, for active segments.
+    pub memory_index: Option<u32>,
+    /// The size of the segment, in bytes.
+    pub size: usize,
+}
+
+/// An element segment.
+#[derive(Debug, Serialize)]
+pub struct ElementSegmentDetails {
+    /// The segment kind: `active`, `passive` or `declared`.
+    pub kind: &'static str,
+    /// The table the segment is copied into, for active segments.
+    pub table_index: Option<u32>,
+    /// The number of elements in the segment.
+    pub size: u32,
+}
+
+/// A function body defined in the module.
+#[derive(Debug, Serialize)]
+pub struct FunctionBodyDetails {
+    /// The function index, counting imported functions.
+    pub index: u32,
+    /// The function name, taken from the name section, if any.
+    pub name: Option<String>,
+    /// The size of the body, in bytes.
+    pub size: usize,
+    /// The number of locals declared in the body (parameters excluded).
+    pub locals: u32,
+}
+
+/// All the details gathered from a WebAssembly binary.
+#[derive(Debug, Default, Serialize)]
+pub struct ModuleDetails {
+    /// The custom sections, in order of appearance.
+    pub custom_sections: Vec<CustomSectionDetails>,
+    /// The `name` section contents, if present.
+    pub name_section: Option<NameSectionDetails>,
+    /// The start function index, if any.
+    pub start_function: Option<u32>,
+    /// The memories (imported first).
+    pub memories: Vec<MemoryDetails>,
+    /// The tables (imported first).
+    pub tables: Vec<TableDetails>,
+    /// The data segments.
+    pub data_segments: Vec<DataSegmentDetails>,
+    /// The element segments.
+    pub element_segments: Vec<ElementSegmentDetails>,
+    /// The defined function bodies.
+    pub functions: Vec<FunctionBodyDetails>,
+    /// The WebAssembly proposals the module actually makes use of.
+    pub features: BTreeSet<&'static str>,
+}
+
+impl ModuleDetails {
+    /// Walks the given WebAssembly binary and gathers its details.
+    pub fn parse(wasm: &[u8]) -> Result<Self> {
+        let mut details = Self::default();
+        let mut imported_functions = 0;
+
+        for payload in Parser::new(0).parse_all(wasm) {
+            match payload? {
+                Payload::TypeSection(types) => {
+                    for ty in types {
+                        if let TypeDef::Func(func_type) = ty? {
+                            if func_type.returns.len() > 1 {
+                                details.features.insert("multi-value");
+                            }
+                        }
+                    }
+                }
+                Payload::ImportSection(imports) => {
+                    for import in imports {
+                        match import?.ty {
+                            ImportSectionEntryType::Function(_) => imported_functions += 1,
+                            ImportSectionEntryType::Memory(memory) => {
+                                details.add_memory(true, memory)
+                            }
+                            ImportSectionEntryType::Table(table) => details.add_table(true, table),
+                            ImportSectionEntryType::Global(global) => {
+                                details.add_value_type(global.content_type)
+                            }
+                            ImportSectionEntryType::Module(_)
+                            | ImportSectionEntryType::Instance(_) => {
+                                details.features.insert("module-linking");
+                            }
+                            ImportSectionEntryType::Event(_) => {
+                                details.features.insert("exceptions");
+                            }
+                        }
+                    }
+                }
+                Payload::TableSection(tables) => {
+                    for table in tables {
+                        details.add_table(false, table?);
+                    }
+                }
+                Payload::MemorySection(memories) => {
+                    for memory in memories {
+                        details.add_memory(false, memory?);
+                    }
+                }
+                Payload::GlobalSection(globals) => {
+                    for global in globals {
+                        details.add_value_type(global?.ty.content_type);
+                    }
+                }
+                Payload::StartSection { func, .. } => details.start_function = Some(func),
+                Payload::ElementSection(elements) => {
+                    for element in elements {
+                        let Element { kind, items, ty } = element?;
+                        details.add_value_type(ty);
+                        let (kind, table_index) = match kind {
+                            ElementKind::Active { table_index, .. } => {
+                                ("active", Some(table_index))
+                            }
+                            ElementKind::Passive => {
+                                details.features.insert("bulk-memory");
+                                ("passive", None)
+                            }
+                            ElementKind::Declared => {
+                                details.features.insert("reference-types");
+                                ("declared", None)
+                            }
+                        };
+                        details.element_segments.push(ElementSegmentDetails {
+                            kind,
+                            table_index,
+                            size: items.get_items_reader()?.get_count(),
+                        });
+                    }
+                }
+                Payload::DataCountSection { .. } => {
+                    details.features.insert("bulk-memory");
+                }
+                Payload::DataSection(data) => {
+                    for entry in data {
+                        let Data { kind, data } = entry?;
+                        let (passive, memory_index) = match kind {
+                            DataKind::Active { memory_index, .. } => (false, Some(memory_index)),
+                            DataKind::Passive => {
+                                details.features.insert("bulk-memory");
+                                (true, None)
+                            }
+                        };
+                        details.data_segments.push(DataSegmentDetails {
+                            passive,
+                            memory_index,
+                            size: data.len(),
+                        });
+                    }
+                }
+                Payload::CodeSectionEntry(body) => {
+                    let mut locals = 0;
+                    let mut locals_reader = body.get_locals_reader()?;
+                    for _ in 0..locals_reader.get_count() {
+                        let (count, ty) = locals_reader.read()?;
+                        details.add_value_type(ty);
+                        locals += count;
+                    }
+                    let mut operators = body.get_operators_reader()?;
+                    while !operators.eof() {
+                        details.add_operator(&operators.read()?);
+                    }
+                    let index = imported_functions + details.functions.len() as u32;
+                    details.functions.push(FunctionBodyDetails {
+                        index,
+                        name: None,
+                        size: body.get_binary_reader().bytes_remaining(),
+                        locals,
+                    });
+                }
+                Payload::CustomSection {
+                    name,
+                    data,
+                    data_offset,
+                } => {
+                    details.custom_sections.push(CustomSectionDetails {
+                        name: name.to_string(),
+                        size: data.len(),
+                    });
+                    if name == "name" {
+                        details.name_section = Some(parse_name_section(data, data_offset)?);
+                    }
+                }
+                Payload::InstanceSection(_)
+                | Payload::AliasSection(_)
+                | Payload::ModuleSectionStart { .. }
+                | Payload::ModuleSectionEntry { .. } => {
+                    details.features.insert("module-linking");
+                }
+                Payload::EventSection(_) => {
+                    details.features.insert("exceptions");
+                }
+                _ => {}
+            }
+        }
+
+        if details.memories.len() > 1 {
+            details.features.insert("multi-memory");
+        }
+        if details.tables.len() > 1 {
+            details.features.insert("reference-types");
+        }
+        if let Some(name_section) = &details.name_section {
+            for function in details.functions.iter_mut() {
+                function.name = name_section
+                    .functions
+                    .iter()
+                    .find(|(index, _)| *index == function.index)
+                    .map(|(_, name)| name.clone());
+            }
+        }
+
+        Ok(details)
+    }
+
+    fn add_memory(&mut self, imported: bool, memory: MemoryType) {
+        let details = match memory {
+            MemoryType::M32 { limits, shared } => MemoryDetails {
+                imported,
+                limits: Limits {
+                    minimum: limits.initial.into(),
+                    maximum: limits.maximum.map(Into::into),
+                },
+                shared,
+                memory64: false,
+            },
+            MemoryType::M64 { limits, shared } => {
+                self.features.insert("memory64");
+                MemoryDetails {
+                    imported,
+                    limits: Limits {
+                        minimum: limits.initial,
+                        maximum: limits.maximum,
+                    },
+                    shared,
+                    memory64: true,
+                }
+            }
+        };
+        if details.shared {
+            self.features.insert("threads");
+        }
+        self.memories.push(details);
+    }
+
+    fn add_table(&mut self, imported: bool, table: wasmparser::TableType) {
+        self.add_value_type(table.element_type);
+        self.tables.push(TableDetails {
+            imported,
+            element_type: format!("{:?}", table.element_type),
+            limits: Limits {
+                minimum: table.limits.initial.into(),
+                maximum: table.limits.maximum.map(Into::into),
+            },
+        });
+    }
+
+    fn add_value_type(&mut self, ty: wasmparser::Type) {
+        match ty {
+            wasmparser::Type::V128 => {
+                self.features.insert("simd");
+            }
+            wasmparser::Type::ExternRef => {
+                self.features.insert("reference-types");
+            }
+            _ => {}
+        }
+    }
+
+    fn add_operator(&mut self, operator: &Operator) {
+        let feature = match operator {
+            Operator::Block { ty } | Operator::Loop { ty } | Operator::If { ty } => match ty {
+                TypeOrFuncType::FuncType(_) => Some("multi-value"),
+                TypeOrFuncType::Type(ty) => {
+                    self.add_value_type(*ty);
+                    None
+                }
+            },
+            Operator::MemoryInit { .. }
+            | Operator::DataDrop { .. }
+            | Operator::MemoryCopy { .. }
+            | Operator::MemoryFill { .. }
+            | Operator::TableInit { .. }
+            | Operator::ElemDrop { .. }
+            | Operator::TableCopy { .. } => Some("bulk-memory"),
+            Operator::RefNull { .. }
+            | Operator::RefIsNull { .. }
+            | Operator::RefFunc { .. }
+            | Operator::TypedSelect { .. }
+            | Operator::TableGet { .. }
+            | Operator::TableSet { .. }
+            | Operator::TableGrow { .. }
+            | Operator::TableSize { .. }
+            | Operator::TableFill { .. } => Some("reference-types"),
+            Operator::ReturnCall { .. } | Operator::ReturnCallIndirect { .. } => Some("tail-call"),
+            Operator::Try { .. }
+            | Operator::Catch { .. }
+            | Operator::Throw { .. }
+            | Operator::Rethrow { .. }
+            | Operator::Unwind => Some("exceptions"),
+            Operator::MemoryAtomicNotify { .. }
+            | Operator::MemoryAtomicWait32 { .. }
+            | Operator::MemoryAtomicWait64 { .. }
+            | Operator::AtomicFence { .. }
+            | Operator::I32AtomicLoad { .. }
+            | Operator::I64AtomicLoad { .. }
+            | Operator::I32AtomicLoad8U { .. }
+            | Operator::I32AtomicLoad16U { .. }
+            | Operator::I64AtomicLoad8U { .. }
+            | Operator::I64AtomicLoad16U { .. }
+            | Operator::I64AtomicLoad32U { .. }
+            | Operator::I32AtomicStore { .. }
+            | Operator::I64AtomicStore { .. }
+            | Operator::I32AtomicStore8 { .. }
+            | Operator::I32AtomicStore16 { .. }
+            | Operator::I64AtomicStore8 { .. }
+            | Operator::I64AtomicStore16 { .. }
+            | Operator::I64AtomicStore32 { .. }
+            | Operator::I32AtomicRmwAdd { .. }
+            | Operator::I64AtomicRmwAdd { .. }
+            | Operator::I32AtomicRmw8AddU { .. }
+            | Operator::I32AtomicRmw16AddU { .. }
+            | Operator::I64AtomicRmw8AddU { .. }
+            | Operator::I64AtomicRmw16AddU { .. }
+            | Operator::I64AtomicRmw32AddU { .. }
+            | Operator::I32AtomicRmwSub { .. }
+            | Operator::I64AtomicRmwSub { .. }
+            | Operator::I32AtomicRmw8SubU { .. }
+            | Operator::I32AtomicRmw16SubU { .. }
+            | Operator::I64AtomicRmw8SubU { .. }
+            | Operator::I64AtomicRmw16SubU { .. }
+            | Operator::I64AtomicRmw32SubU { .. }
+            | Operator::I32AtomicRmwAnd { .. }
+            | Operator::I64AtomicRmwAnd { .. }
+            | Operator::I32AtomicRmw8AndU { .. }
+            | Operator::I32AtomicRmw16AndU { .. }
+            | Operator::I64AtomicRmw8AndU { .. }
+            | Operator::I64AtomicRmw16AndU { .. }
+            | Operator::I64AtomicRmw32AndU { .. }
+            | Operator::I32AtomicRmwOr { .. }
+            | Operator::I64AtomicRmwOr { .. }
+            | Operator::I32AtomicRmw8OrU { .. }
+            | Operator::I32AtomicRmw16OrU { .. }
+            | Operator::I64AtomicRmw8OrU { .. }
+            | Operator::I64AtomicRmw16OrU { .. }
+            | Operator::I64AtomicRmw32OrU { .. }
+            | Operator::I32AtomicRmwXor { .. }
+            | Operator::I64AtomicRmwXor { .. }
+            | Operator::I32AtomicRmw8XorU { .. }
+            | Operator::I32AtomicRmw16XorU { .. }
+            | Operator::I64AtomicRmw8XorU { .. }
+            | Operator::I64AtomicRmw16XorU { .. }
+            | Operator::I64AtomicRmw32XorU { .. }
+            | Operator::I32AtomicRmwXchg { .. }
+            | Operator::I64AtomicRmwXchg { .. }
+            | Operator::I32AtomicRmw8XchgU { .. }
+            | Operator::I32AtomicRmw16XchgU { .. }
+            | Operator::I64AtomicRmw8XchgU { .. }
+            | Operator::I64AtomicRmw16XchgU { .. }
+            | Operator::I64AtomicRmw32XchgU { .. }
+            | Operator::I32AtomicRmwCmpxchg { .. }
+            | Operator::I64AtomicRmwCmpxchg { .. }
+            | Operator::I32AtomicRmw8CmpxchgU { .. }
+            | Operator::I32AtomicRmw16CmpxchgU { .. }
+            | Operator::I64AtomicRmw8CmpxchgU { .. }
+            | Operator::I64AtomicRmw16CmpxchgU { .. }
+            | Operator::I64AtomicRmw32CmpxchgU { .. } => Some("threads"),
+            Operator::V128Load { .. }
+            | Operator::V128Store { .. }
+            | Operator::V128Const { .. }
+            | Operator::I8x16Splat
+            | Operator::I8x16ExtractLaneS { .. }
+            | Operator::I8x16ExtractLaneU { .. }
+            | Operator::I8x16ReplaceLane { .. }
+            | Operator::I16x8Splat
+            | Operator::I16x8ExtractLaneS { .. }
+            | Operator::I16x8ExtractLaneU { .. }
+            | Operator::I16x8ReplaceLane { .. }
+            | Operator::I32x4Splat
+            | Operator::I32x4ExtractLane { .. }
+            | Operator::I32x4ReplaceLane { .. }
+            | Operator::I64x2Splat
+            | Operator::I64x2ExtractLane { .. }
+            | Operator::I64x2ReplaceLane { .. }
+            | Operator::F32x4Splat
+            | Operator::F32x4ExtractLane { .. }
+            | Operator::F32x4ReplaceLane { .. }
+            | Operator::F64x2Splat
+            | Operator::F64x2ExtractLane { .. }
+            | Operator::F64x2ReplaceLane { .. }
+            | Operator::I8x16Eq
+            | Operator::I8x16Ne
+            | Operator::I8x16LtS
+            | Operator::I8x16LtU
+            | Operator::I8x16GtS
+            | Operator::I8x16GtU
+            | Operator::I8x16LeS
+            | Operator::I8x16LeU
+            | Operator::I8x16GeS
+            | Operator::I8x16GeU
+            | Operator::I16x8Eq
+            | Operator::I16x8Ne
+            | Operator::I16x8LtS
+            | Operator::I16x8LtU
+            | Operator::I16x8GtS
+            | Operator::I16x8GtU
+            | Operator::I16x8LeS
+            | Operator::I16x8LeU
+            | Operator::I16x8GeS
+            | Operator::I16x8GeU
+            | Operator::I32x4Eq
+            | Operator::I32x4Ne
+            | Operator::I32x4LtS
+            | Operator::I32x4LtU
+            | Operator::I32x4GtS
+            | Operator::I32x4GtU
+            | Operator::I32x4LeS
+            | Operator::I32x4LeU
+            | Operator::I32x4GeS
+            | Operator::I32x4GeU
+            | Operator::I64x2Eq
+            | Operator::I64x2Ne
+            | Operator::F32x4Eq
+            | Operator::F32x4Ne
+            | Operator::F32x4Lt
+            | Operator::F32x4Gt
+            | Operator::F32x4Le
+            | Operator::F32x4Ge
+            | Operator::F64x2Eq
+            | Operator::F64x2Ne
+            | Operator::F64x2Lt
+            | Operator::F64x2Gt
+            | Operator::F64x2Le
+            | Operator::F64x2Ge
+            | Operator::V128Not
+            | Operator::V128And
+            | Operator::V128AndNot
+            | Operator::V128Or
+            | Operator::V128Xor
+            | Operator::V128Bitselect
+            | Operator::V128AnyTrue
+            | Operator::I8x16Abs
+            | Operator::I8x16Neg
+            | Operator::I8x16AllTrue
+            | Operator::I8x16Bitmask
+            | Operator::I8x16Shl
+            | Operator::I8x16ShrS
+            | Operator::I8x16ShrU
+            | Operator::I8x16Add
+            | Operator::I8x16AddSatS
+            | Operator::I8x16AddSatU
+            | Operator::I8x16Sub
+            | Operator::I8x16SubSatS
+            | Operator::I8x16SubSatU
+            | Operator::I8x16MinS
+            | Operator::I8x16MinU
+            | Operator::I8x16MaxS
+            | Operator::I8x16MaxU
+            | Operator::I16x8Abs
+            | Operator::I16x8Neg
+            | Operator::I16x8AllTrue
+            | Operator::I16x8Bitmask
+            | Operator::I16x8Shl
+            | Operator::I16x8ShrS
+            | Operator::I16x8ShrU
+            | Operator::I16x8Add
+            | Operator::I16x8AddSatS
+            | Operator::I16x8AddSatU
+            | Operator::I16x8Sub
+            | Operator::I16x8SubSatS
+            | Operator::I16x8SubSatU
+            | Operator::I16x8Mul
+            | Operator::I16x8MinS
+            | Operator::I16x8MinU
+            | Operator::I16x8MaxS
+            | Operator::I16x8MaxU
+            | Operator::I32x4Abs
+            | Operator::I32x4Neg
+            | Operator::I32x4AllTrue
+            | Operator::I32x4Bitmask
+            | Operator::I32x4Shl
+            | Operator::I32x4ShrS
+            | Operator::I32x4ShrU
+            | Operator::I32x4Add
+            | Operator::I32x4Sub
+            | Operator::I32x4Mul
+            | Operator::I32x4MinS
+            | Operator::I32x4MinU
+            | Operator::I32x4MaxS
+            | Operator::I32x4MaxU
+            | Operator::I32x4DotI16x8S
+            | Operator::I64x2Neg
+            | Operator::I64x2AllTrue
+            | Operator::I64x2Bitmask
+            | Operator::I64x2Shl
+            | Operator::I64x2ShrS
+            | Operator::I64x2ShrU
+            | Operator::I64x2Add
+            | Operator::I64x2Sub
+            | Operator::I64x2Mul
+            | Operator::F32x4Ceil
+            | Operator::F32x4Floor
+            | Operator::F32x4Trunc
+            | Operator::F32x4Nearest
+            | Operator::F64x2Ceil
+            | Operator::F64x2Floor
+            | Operator::F64x2Trunc
+            | Operator::F64x2Nearest
+            | Operator::F32x4Abs
+            | Operator::F32x4Neg
+            | Operator::F32x4Sqrt
+            | Operator::F32x4Add
+            | Operator::F32x4Sub
+            | Operator::F32x4Mul
+            | Operator::F32x4Div
+            | Operator::F32x4Min
+            | Operator::F32x4Max
+            | Operator::F32x4PMin
+            | Operator::F32x4PMax
+            | Operator::F64x2Abs
+            | Operator::F64x2Neg
+            | Operator::F64x2Sqrt
+            | Operator::F64x2Add
+            | Operator::F64x2Sub
+            | Operator::F64x2Mul
+            | Operator::F64x2Div
+            | Operator::F64x2Min
+            | Operator::F64x2Max
+            | Operator::F64x2PMin
+            | Operator::F64x2PMax
+            | Operator::I32x4TruncSatF32x4S
+            | Operator::I32x4TruncSatF32x4U
+            | Operator::F32x4ConvertI32x4S
+            | Operator::F32x4ConvertI32x4U
+            | Operator::I8x16Swizzle
+            | Operator::I8x16Shuffle { .. }
+            | Operator::V128Load8Splat { .. }
+            | Operator::V128Load16Splat { .. }
+            | Operator::V128Load32Splat { .. }
+            | Operator::V128Load32Zero { .. }
+            | Operator::V128Load64Splat { .. }
+            | Operator::V128Load64Zero { .. }
+            | Operator::I8x16NarrowI16x8S
+            | Operator::I8x16NarrowI16x8U
+            | Operator::I16x8NarrowI32x4S
+            | Operator::I16x8NarrowI32x4U
+            | Operator::I16x8WidenLowI8x16S
+            | Operator::I16x8WidenHighI8x16S
+            | Operator::I16x8WidenLowI8x16U
+            | Operator::I16x8WidenHighI8x16U
+            | Operator::I32x4WidenLowI16x8S
+            | Operator::I32x4WidenHighI16x8S
+            | Operator::I32x4WidenLowI16x8U
+            | Operator::I32x4WidenHighI16x8U
+            | Operator::I64x2WidenLowI32x4S
+            | Operator::I64x2WidenHighI32x4S
+            | Operator::I64x2WidenLowI32x4U
+            | Operator::I64x2WidenHighI32x4U
+            | Operator::I16x8ExtMulLowI8x16S
+            | Operator::I16x8ExtMulHighI8x16S
+            | Operator::I16x8ExtMulLowI8x16U
+            | Operator::I16x8ExtMulHighI8x16U
+            | Operator::I32x4ExtMulLowI16x8S
+            | Operator::I32x4ExtMulHighI16x8S
+            | Operator::I32x4ExtMulLowI16x8U
+            | Operator::I32x4ExtMulHighI16x8U
+            | Operator::I64x2ExtMulLowI32x4S
+            | Operator::I64x2ExtMulHighI32x4S
+            | Operator::I64x2ExtMulLowI32x4U
+            | Operator::I64x2ExtMulHighI32x4U
+            | Operator::V128Load8x8S { .. }
+            | Operator::V128Load8x8U { .. }
+            | Operator::V128Load16x4S { .. }
+            | Operator::V128Load16x4U { .. }
+            | Operator::V128Load32x2S { .. }
+            | Operator::V128Load32x2U { .. }
+            | Operator::V128Load8Lane { .. }
+            | Operator::V128Load16Lane { .. }
+            | Operator::V128Load32Lane { .. }
+            | Operator::V128Load64Lane { .. }
+            | Operator::V128Store8Lane { .. }
+            | Operator::V128Store16Lane { .. }
+            | Operator::V128Store32Lane { .. }
+            | Operator::V128Store64Lane { .. }
+            | Operator::I8x16RoundingAverageU
+            | Operator::I16x8RoundingAverageU
+            | Operator::I16x8Q15MulrSatS
+            | Operator::F32x4DemoteF64x2Zero
+            | Operator::F64x2PromoteLowF32x4
+            | Operator::F64x2ConvertLowI32x4S
+            | Operator::F64x2ConvertLowI32x4U
+            | Operator::I32x4TruncSatF64x2SZero
+            | Operator::I32x4TruncSatF64x2UZero => Some("simd"),
+            _ => None,
+        };
+        if let Some(feature) = feature {
+            self.features.insert(feature);
+        }
+    }
+}
+
+fn parse_name_section(data: &[u8], data_offset: usize) -> Result<NameSectionDetails> {
+    let mut details = NameSectionDetails::default();
+    let mut names = wasmparser::NameSectionReader::new(data, data_offset)?;
+    while !names.eof() {
+        match names.read()? {
+            Name::Module(module) => details.module = Some(module.get_name()?.to_string()),
+            Name::Function(functions) => {
+                let mut map = functions.get_map()?;
+                for _ in 0..map.get_count() {
+                    let naming = map.read()?;
+                    details
+                        .functions
+                        .push((naming.index, naming.name.to_string()));
+                }
+            }
+            Name::Local(locals) => {
+                details.functions_with_local_names +=
+                    locals.get_function_local_reader()?.get_count();
+            }
+        }
+    }
+    Ok(details)
+}
@@ -0,0 +1,256 @@
+use crate::store::StoreOptions;
+use crate::utils::parse_value;
+use anyhow::{Context, Result};
+use colored::*;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use structopt::StructOpt;
+use wasmer::*;
+
+#[cfg(feature = "wasi")]
+use super::run::Wasi;
+
+const HELP: &str = "Commands:
+  exports                          List the exports of the instance
+  call <function> [args...]        Call an exported function
+  get <global>                     Read an exported global
+  set <global> <value>             Write an exported global
+  hexdump <memory> <offset> <len>  Dump a range of an exported memory
+  poke <memory> <offset> <byte>... Write bytes into an exported memory
+  grow <memory> <pages>            Grow an exported memory
+  reset                            Instantiate the module again
+  help                             Show this message
+  quit                             Leave the REPL";
+
+#[derive(Debug, StructOpt)]
+/// The options for the `wasmer repl` subcommand
+pub struct Repl {
+    /// File to explore
+    #[structopt(name = "FILE", parse(from_os_str))]
+    path: PathBuf,
+
+    #[structopt(flatten)]
+    store: StoreOptions,
+
+    #[cfg(feature = "wasi")]
+    #[structopt(flatten)]
+    wasi: Wasi,
+}
+
+impl Repl {
+    /// Runs logic for the `repl` subcommand
+    pub fn execute(&self) -> Result<()> {
+        self.inner_execute()
+            .context(format!("failed to explore `{}`", self.path.display()))
+    }
+
+    fn inner_execute(&self) -> Result<()> {
+        let (store, _engine_type, _compiler_type) = self.store.get_store()?;
+        let module_contents = std::fs::read(&self.path)?;
+        let mut module = Module::new(&store, &module_contents)?;
+        module.set_name(&self.path.file_name().unwrap_or_default().to_string_lossy());
+        let mut instance = self.instantiate(&module)?;
+
+        let interactive = atty::is(atty::Stream::Stdin);
+        if interactive {
+            eprintln!("Type `help` to list the available commands.");
+        }
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        loop {
+            if interactive {
+                print!("wasmer> ");
+                io::stdout().flush()?;
+            }
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => break,
+            };
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let (command, args) = match words.split_first() {
+                Some((command, args)) => (*command, args),
+                None => continue,
+            };
+            let result = match command {
+                "quit" | "exit" => break,
+                "help" => {
+                    println!("{}", HELP);
+                    Ok(())
+                }
+                "reset" => self.instantiate(&module).map(|new_instance| {
+                    instance = new_instance;
+                }),
+                command => run_command(&instance, command, args),
+            };
+            if let Err(error) = result {
+                eprintln!("{}: {:#}", "error".red().bold(), error);
+            }
+        }
+        Ok(())
+    }
+
+    fn instantiate(&self, module: &Module) -> Result<Instance> {
+        #[cfg(feature = "wasi")]
+        {
+            if Wasi::has_wasi_imports(module) {
+                let program_name = self
+                    .path
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                return self
+                    .wasi
+                    .instantiate(module, program_name, vec![], ImportObject::new());
+            }
+        }
+        Ok(Instance::new(module, &imports! {})?)
+    }
+}
+
+fn run_command(instance: &Instance, command: &str, args: &[&str]) -> Result<()> {
+    match (command, args) {
+        ("exports", []) => {
+            for (name, export) in instance.exports.iter() {
+                println!("{}", format_export(name, &export.ty()));
+            }
+        }
+        ("call", [name, args @ ..]) => {
+            let function = instance.exports.get_function(name)?;
+            let params = function.ty().params();
+            if params.len() != args.len() {
+                bail!(
+                    "Function expected {} arguments, but received {}",
+                    params.len(),
+                    args.len()
+                );
+            }
+            let args = args
+                .iter()
+                .zip(params.iter())
+                .map(|(arg, ty)| parse_value(arg, ty))
+                .collect::<Result<Vec<_>>>()?;
+            let results = function.call(&args)?;
+            println!(
+                "{}",
+                results
+                    .iter()
+                    .map(|val| val.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            );
+        }
+        ("get", [name]) => {
+            let global = instance.exports.get_global(name)?;
+            println!("{}", global.get().to_string());
+        }
+        ("set", [name, value]) => {
+            let global = instance.exports.get_global(name)?;
+            global.set(parse_value(value, &global.ty().ty)?)?;
+        }
+        ("hexdump", [name, offset, length]) => {
+            let memory = instance.exports.get_memory(name)?;
+            let offset = parse_number(offset)?;
+            let length = parse_number(length)?;
+            let view = memory.view::<u8>();
+            let range = memory_range(view.len(), offset, length)?;
+            let bytes = view[range]
+                .iter()
+                .map(|cell| cell.get())
+                .collect::<Vec<_>>();
+            print_hexdump(offset, &bytes);
+        }
+        ("poke", [name, offset, bytes @ ..]) if !bytes.is_empty() => {
+            let memory = instance.exports.get_memory(name)?;
+            let offset = parse_number(offset)?;
+            let bytes = bytes
+                .iter()
+                .map(|byte| match parse_number(byte)? {
+                    byte if byte <= 0xff => Ok(byte as u8),
+                    _ => bail!("`{}` doesn't fit into a byte", byte),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let view = memory.view::<u8>();
+            let range = memory_range(view.len(), offset, bytes.len())?;
+            for (cell, byte) in view[range].iter().zip(bytes) {
+                cell.set(byte);
+            }
+        }
+        ("grow", [name, pages]) => {
+            let memory = instance.exports.get_memory(name)?;
+            let pages = parse_number(pages)?;
+            if pages > u32::MAX as usize {
+                bail!("Can't grow the memory by {} pages", pages);
+            }
+            let previous = memory.grow(pages as u32)?;
+            println!("{} -> {} pages", previous.0, memory.size().0);
+        }
+        ("exports", _)
+        | ("call", _)
+        | ("get", _)
+        | ("set", _)
+        | ("hexdump", _)
+        | ("poke", _)
+        | ("grow", _) => bail!(
+            "Wrong arguments for `{}`, type `help` for the usage",
+            command
+        ),
+        _ => bail!(
+            "Unknown command `{}`, type `help` to list the available commands",
+            command
+        ),
+    }
+    Ok(())
+}
+
+fn format_export(name: &str, ty: &ExternType) -> String {
+    match ty {
+        ExternType::Function(ty) => format!("function \"{}\": {}", name, ty),
+        ExternType::Global(ty) => format!("global \"{}\": {}", name, ty),
+        ExternType::Memory(ty) => format!("memory \"{}\": {}", name, ty),
+        ExternType::Table(ty) => format!("table \"{}\": {}", name, ty),
+        ExternType::Tag(ty) => format!("tag \"{}\": {}", name, ty),
+    }
+}
+
+/// Parses a decimal or `0x`-prefixed hexadecimal number.
+fn parse_number(number: &str) -> Result<usize> {
+    let parsed = match number.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16),
+        None => number.parse(),
+    };
+    parsed.map_err(|_| anyhow!("`{}` is not a valid number", number))
+}
+
+/// Checks that `offset..offset + length` is within a memory of the given size.
+fn memory_range(size: usize, offset: usize, length: usize) -> Result<std::ops::Range<usize>> {
+    match offset.checked_add(length) {
+        Some(end) if end <= size => Ok(offset..end),
+        _ => bail!(
+            "The range {:#x}..{:#x} is out of the memory bounds ({:#x} bytes)",
+            offset,
+            offset.saturating_add(length),
+            size
+        ),
+    }
+}
+
+fn print_hexdump(offset: usize, bytes: &[u8]) {
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let hex = chunk
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii = chunk
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect::<String>();
+        println!("{:08x}  {:<47}  |{}|", offset + line * 16, hex, ascii);
+    }
+}
@@ -1,12 +1,18 @@
 use crate::common::get_cache_dir;
+use crate::error::PrettyError;
+use crate::limits::{LimitError, LimitOptions};
 #[cfg(feature = "debug")]
 use crate::logging;
 use crate::store::{CompilerType, EngineType, StoreOptions};
 use crate::suggestions::suggest_function_exports;
+#[cfg(feature = "compiler")]
+use crate::trace::{TraceOptions, Tracer};
+use crate::utils::{parse_named_module, parse_timeout, parse_value};
 use crate::warning;
 use anyhow::{anyhow, Context, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use wasmer::*;
 #[cfg(feature = "cache")]
 use wasmer_cache::{Cache, FileSystemCache, Hash};
@@ -17,7 +23,7 @@ use structopt::StructOpt;
 mod wasi;
 
 #[cfg(feature = "wasi")]
-use wasi::Wasi;
+pub(crate) use wasi::Wasi;
 
 #[derive(Debug, StructOpt, Clone)]
 /// The options for the `wasmer run` subcommand
@@ -34,6 +40,17 @@ pub struct Run {
     #[structopt(long = "invoke", short = "i")]
     invoke: Option<String>,
 
+    /// Instantiate a module before the main one, and expose its exports
+    /// to the modules importing from `NAME`
+    #[structopt(
+        long = "module",
+        name = "NAME=PATH",
+        multiple = true,
+        number_of_values = 1,
+        parse(try_from_str = parse_named_module)
+    )]
+    modules: Vec<(String, PathBuf)>,
+
     /// The command name is a string that will override the first argument passed
     /// to the wasm program. This is used in wapm to provide nicer output in
     /// help commands and error messages of the running wasm program
@@ -49,6 +66,17 @@ pub struct Run {
     #[structopt(flatten)]
     store: StoreOptions,
 
+    #[structopt(flatten)]
+    limits: LimitOptions,
+
+    #[cfg(feature = "compiler")]
+    #[structopt(flatten)]
+    trace: TraceOptions,
+
+    /// Maximum wall-clock time the guest can run for, in seconds.
+    #[structopt(long = "timeout", name = "SECONDS", parse(try_from_str = parse_timeout))]
+    timeout: Option<Duration>,
+
     // TODO: refactor WASI structure to allow shared options with Emscripten
     #[cfg(feature = "wasi")]
     #[structopt(flatten)]
@@ -91,11 +119,28 @@ impl Run {
 
     fn inner_execute(&self) -> Result<()> {
         let module = self.get_module()?;
+        if let Some(timeout) = self.timeout {
+            Self::start_watchdog(timeout);
+        }
+        let imports = self.link_modules(module.store())?;
+        let result = self.execute_module(&module, &imports);
+        #[cfg(feature = "compiler")]
+        {
+            if let (Err(error), Some(tracer)) = (&result, &imports.tracer) {
+                tracer.trap(error);
+            }
+        }
+        result
+    }
+
+    fn execute_module(&self, module: &Module, imports: &Imports) -> Result<()> {
+        let linked = imports.for_instance(module.store());
         // Do we want to invoke a function?
         if let Some(ref invoke) = self.invoke {
-            let imports = imports! {};
-            let instance = Instance::new(&module, &imports)?;
-            let result = self.invoke_function(&instance, &invoke, &self.args)?;
+            let instance = self.instantiate(module, &linked)?;
+            let result = self
+                .invoke_function(&instance, &invoke, &self.args)
+                .map_err(|e| self.limits.check_error(Some(&instance), e))?;
             println!(
                 "{}",
                 result
@@ -113,19 +158,19 @@ impl Run {
                 EmscriptenGlobals,
             };
             // TODO: refactor this
-            if is_emscripten_module(&module) {
-                let mut emscripten_globals = EmscriptenGlobals::new(module.store(), &module)
-                    .map_err(|e| anyhow!("{}", e))?;
+            if is_emscripten_module(module) {
+                let mut emscripten_globals =
+                    EmscriptenGlobals::new(module.store(), module).map_err(|e| anyhow!("{}", e))?;
                 let mut em_env = EmEnv::new(&emscripten_globals.data, Default::default());
                 let import_object =
                     generate_emscripten_env(module.store(), &mut emscripten_globals, &mut em_env);
-                let mut instance = match Instance::new(&module, &import_object) {
+                let mut instance = match Instance::new(module, &import_object.chain_back(&linked)) {
                     Ok(instance) => instance,
                     Err(e) => {
-                        let err: Result<(), _> = Err(e);
+                        let err: Result<(), _> = Err(self.limits.check_error(None, e.into()));
                         #[cfg(feature = "wasi")]
                         {
-                            if Wasi::has_wasi_imports(&module) {
+                            if Wasi::has_wasi_imports(module) {
                                 return err.with_context(|| "This module has both Emscripten and WASI imports. Wasmer does not currently support Emscripten modules using WASI imports.");
                             }
                         }
@@ -144,7 +189,8 @@ impl Run {
                     },
                     self.args.iter().map(|arg| arg.as_str()).collect(),
                     None, //run.em_entrypoint.clone(),
-                )?;
+                )
+                .map_err(|e| self.limits.check_error(Some(&instance), e.into()))?;
                 return Ok(());
             }
         }
@@ -152,7 +198,12 @@ impl Run {
         // If WASI is enabled, try to execute it with it
         #[cfg(feature = "wasi")]
         {
-            let wasi_version = Wasi::get_version(&module);
+            // Modules importing from linked modules can't be detected in strict mode
+            let wasi_version = if self.modules.is_empty() {
+                Wasi::get_version(module)
+            } else {
+                Wasi::get_version_non_strict(module)
+            };
             if wasi_version.is_some() {
                 let program_name = self
                     .command_name
@@ -163,29 +214,168 @@ impl Run {
                             .map(|f| f.to_string_lossy().to_string())
                     })
                     .unwrap_or_default();
-                return self
+                let instance = self
                     .wasi
-                    .execute(module, program_name, self.args.clone())
+                    .instantiate(module, program_name, self.args.clone(), &linked)
+                    .map_err(|e| self.limits.check_error(None, e))
+                    .with_context(|| "WASI instantiation failed")?;
+                return Wasi::execute(&instance)
+                    .map_err(|e| self.limits.check_error(Some(&instance), e))
                     .with_context(|| "WASI execution failed");
             }
         }
 
-        // Try to instantiate the wasm file, with no provided imports
-        let imports = imports! {};
-        let instance = Instance::new(&module, &imports)?;
+        // Try to instantiate the wasm file, with only the linked modules as imports
+        let instance = self.instantiate(module, &linked)?;
         let start: Function = self.try_find_function(&instance, "_start", &[])?;
-        start.call(&[])?;
+        start
+            .call(&[])
+            .map_err(|e| self.limits.check_error(Some(&instance), e.into()))?;
 
         Ok(())
     }
 
+    /// Whether the options change the generated code, in which case
+    /// previously cached artifacts can't be reused, and each module needs
+    /// its own store.
+    fn affects_compilation(&self) -> bool {
+        #[cfg(feature = "compiler")]
+        return self.limits.affects_compilation() || self.trace.is_enabled();
+        #[cfg(not(feature = "compiler"))]
+        return self.limits.affects_compilation();
+    }
+
+    /// Gets the store for the host target, enforcing the resource limits
+    /// and tracing the guest as requested.
+    fn get_store(&self) -> Result<(Store, EngineType, CompilerType)> {
+        #[cfg(all(feature = "compiler", feature = "engine"))]
+        return self.store.get_store_with_limits(&self.limits, &self.trace);
+        #[cfg(not(all(feature = "compiler", feature = "engine")))]
+        return self.store.get_store_with_limits(&self.limits);
+    }
+
+    /// Instantiates the module, reporting the resource limits that
+    /// prevented it, if any.
+    fn instantiate(&self, module: &Module, resolver: &dyn Resolver) -> Result<Instance> {
+        Instance::new(module, resolver).map_err(|e| self.limits.check_error(None, e.into()))
+    }
+
+    /// Instantiates the modules passed with `--module` in dependency order,
+    /// and registers the exports of each one as a namespace named after it.
+    fn link_modules(&self, store: &Store) -> Result<Imports> {
+        let mut imports = Imports {
+            linked: ImportObject::new(),
+            #[cfg(feature = "compiler")]
+            tracer: self.trace.start()?,
+        };
+        let mut pending: Vec<(&str, Module)> = Vec::with_capacity(self.modules.len());
+        for (name, path) in self.modules.iter() {
+            if pending.iter().any(|(other, _)| other == name) {
+                bail!("The module name `{}` is used more than once", name);
+            }
+            // Middlewares can only transform a single module, so each linked
+            // module gets its own store when compiling with them
+            let own_store;
+            let store = if self.affects_compilation() {
+                own_store = self.get_store()?.0;
+                &own_store
+            } else {
+                store
+            };
+            let module = self
+                .load_linked_module(store, name, path)
+                .with_context(|| {
+                    format!("failed to load module `{}` from `{}`", name, path.display())
+                })?;
+            pending.push((name.as_str(), module));
+        }
+
+        while !pending.is_empty() {
+            // A module is ready once none of the modules it imports from is pending
+            let ready = pending.iter().position(|(_, module)| {
+                module
+                    .imports()
+                    .all(|import| !pending.iter().any(|(name, _)| *name == import.module()))
+            });
+            let (name, module) = match ready {
+                Some(index) => pending.remove(index),
+                None => bail!(
+                    "The modules {} import from each other in a cycle",
+                    pending
+                        .iter()
+                        .map(|(name, _)| format!("`{}`", name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            };
+            let instance = self
+                .instantiate_linked_module(name, &module, &imports.for_instance(module.store()))
+                .with_context(|| format!("failed to instantiate module `{}`", name))?;
+            imports.linked.register(name, instance.exports.clone());
+        }
+        Ok(imports)
+    }
+
+    fn load_linked_module(&self, store: &Store, name: &str, path: &Path) -> Result<Module> {
+        let contents = std::fs::read(path)?;
+        #[cfg(feature = "native")]
+        {
+            if wasmer_engine_native::NativeArtifact::is_deserializable(&contents) {
+                return Ok(unsafe { Module::deserialize(store, &contents)? });
+            }
+        }
+        #[cfg(feature = "jit")]
+        {
+            if wasmer_engine_jit::JITArtifact::is_deserializable(&contents) {
+                return Ok(unsafe { Module::deserialize(store, &contents)? });
+            }
+        }
+        let mut module = Module::new(store, &contents)?;
+        module.set_name(name);
+        Ok(module)
+    }
+
+    fn instantiate_linked_module<R: NamedResolver>(
+        &self,
+        name: &str,
+        module: &Module,
+        linked: &R,
+    ) -> Result<Instance> {
+        #[cfg(feature = "wasi")]
+        let instance = if Wasi::has_wasi_imports(module) {
+            self.wasi
+                .instantiate(module, name.to_string(), vec![], linked)
+                .map_err(|e| self.limits.check_error(None, e))?
+        } else {
+            self.instantiate(module, linked)?
+        };
+        #[cfg(not(feature = "wasi"))]
+        let instance = self.instantiate(module, linked)?;
+
+        // Reactor modules expect `_initialize` to be called before any other export
+        if let Ok(initialize) = instance.exports.get_function("_initialize") {
+            initialize
+                .call(&[])
+                .map_err(|e| self.limits.check_error(Some(&instance), e.into()))?;
+        }
+        Ok(instance)
+    }
+
+    /// Spawns a thread terminating the process once the timeout expires.
+    fn start_watchdog(timeout: Duration) {
+        std::thread::spawn(move || {
+            std::thread::sleep(timeout);
+            PrettyError::report::<()>(Err(LimitError::Timeout(timeout).into()));
+        });
+    }
+
     fn get_module(&self) -> Result<Module> {
         let contents = std::fs::read(self.path.clone())?;
         #[cfg(feature = "native")]
         {
             if wasmer_engine_native::NativeArtifact::is_deserializable(&contents) {
                 let engine = wasmer_engine_native::Native::headless().engine();
-                let store = Store::new(&engine);
+                let store = self.limits.new_store(&engine);
                 let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
                 return Ok(module);
             }
@@ -194,18 +384,19 @@ impl Run {
         {
             if wasmer_engine_jit::JITArtifact::is_deserializable(&contents) {
                 let engine = wasmer_engine_jit::JIT::headless().engine();
-                let store = Store::new(&engine);
+                let store = self.limits.new_store(&engine);
                 let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
                 return Ok(module);
             }
         }
-        let (store, engine_type, compiler_type) = self.store.get_store()?;
+        let (store, engine_type, compiler_type) = self.get_store()?;
         #[cfg(feature = "cache")]
-        let module_result: Result<Module> = if !self.disable_cache && contents.len() > 0x1000 {
-            self.get_module_from_cache(&store, &contents, &engine_type, &compiler_type)
-        } else {
-            Module::new(&store, &contents).map_err(|e| e.into())
-        };
+        let module_result: Result<Module> =
+            if !self.disable_cache && contents.len() > 0x1000 && !self.affects_compilation() {
+                self.get_module_from_cache(&store, &contents, &engine_type, &compiler_type)
+            } else {
+                Module::new(&store, &contents).map_err(|e| e.into())
+            };
         #[cfg(not(feature = "cache"))]
         let module_result = Module::new(&store, &contents);
 
@@ -358,34 +549,37 @@ impl Run {
         let invoke_args = args
             .iter()
             .zip(func_ty.params().iter())
-            .map(|(arg, param_type)| match param_type {
-                ValType::I32 => {
-                    Ok(Val::I32(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a i32", arg)
-                    })?))
-                }
-                ValType::I64 => {
-                    Ok(Val::I64(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a i64", arg)
-                    })?))
-                }
-                ValType::F32 => {
-                    Ok(Val::F32(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a f32", arg)
-                    })?))
-                }
-                ValType::F64 => {
-                    Ok(Val::F64(arg.parse().map_err(|_| {
-                        anyhow!("Can't convert `{}` into a f64", arg)
-                    })?))
-                }
-                _ => Err(anyhow!(
-                    "Don't know how to convert {} into {:?}",
-                    arg,
-                    param_type
-                )),
-            })
+            .map(|(arg, param_type)| parse_value(arg, param_type))
             .collect::<Result<Vec<_>>>()?;
         Ok(func.call(&invoke_args)?)
     }
 }
+
+/// The imports available to the instances created by `wasmer run`.
+struct Imports {
+    /// The exports of the modules passed with `--module`, by name.
+    linked: ImportObject,
+    /// The tracer receiving the events of the instrumented modules.
+    #[cfg(feature = "compiler")]
+    tracer: Option<Tracer>,
+}
+
+impl Imports {
+    /// Returns the imports of a new instance created in `store`.
+    ///
+    /// The tracing hooks are created for every instance, as they need
+    /// to know which instance is calling them.
+    fn for_instance(&self, store: &Store) -> NamedResolverChain<ImportObject, &ImportObject> {
+        #[allow(unused_mut)]
+        let mut hooks = ImportObject::new();
+        #[cfg(feature = "compiler")]
+        {
+            if let Some(tracer) = &self.tracer {
+                tracer.register_hooks(store, &mut hooks);
+            }
+        }
+        #[cfg(not(feature = "compiler"))]
+        let _ = store;
+        hooks.chain_back(&self.linked)
+    }
+}
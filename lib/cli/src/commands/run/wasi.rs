@@ -1,7 +1,7 @@
 use crate::utils::{parse_envvar, parse_mapdir};
 use anyhow::{Context, Result};
 use std::path::PathBuf;
-use wasmer::{Instance, Module};
+use wasmer::{ChainableNamedResolver, Instance, Module, NamedResolver};
 use wasmer_wasi::{get_wasi_version, WasiError, WasiState, WasiVersion};
 
 use structopt::StructOpt;
@@ -36,6 +36,12 @@ impl Wasi {
         get_wasi_version(&module, true)
     }
 
+    /// Gets the WASI version (if any) for the provided module, allowing
+    /// it to have non-WASI imports as well.
+    pub fn get_version_non_strict(module: &Module) -> Option<WasiVersion> {
+        get_wasi_version(&module, false)
+    }
+
     /// Checks if a given module has any WASI imports at all.
     pub fn has_wasi_imports(module: &Module) -> bool {
         // Get the wasi version in non-strict mode, so no other imports
@@ -43,8 +49,16 @@ impl Wasi {
         get_wasi_version(&module, false).is_some()
     }
 
-    /// Helper function for executing Wasi from the `Run` command.
-    pub fn execute(&self, module: Module, program_name: String, args: Vec<String>) -> Result<()> {
+    /// Helper function for instantiating a module with WASI from the `Run` command.
+    ///
+    /// Imports that are not provided by WASI are looked up in `linked`.
+    pub fn instantiate<R: NamedResolver>(
+        &self,
+        module: &Module,
+        program_name: String,
+        args: Vec<String>,
+        linked: R,
+    ) -> Result<Instance> {
         let args = args.iter().cloned().map(|arg| arg.into_bytes());
 
         let mut wasi_state_builder = WasiState::new(program_name);
@@ -63,9 +77,12 @@ impl Wasi {
         }
 
         let mut wasi_env = wasi_state_builder.finalize()?;
-        let import_object = wasi_env.import_object(&module)?;
-        let instance = Instance::new(&module, &import_object)?;
+        let import_object = wasi_env.import_object(module)?;
+        Ok(Instance::new(module, &import_object.chain_back(linked))?)
+    }
 
+    /// Helper function for executing Wasi from the `Run` command.
+    pub fn execute(instance: &Instance) -> Result<()> {
         let start = instance.exports.get_function("_start")?;
         let result = start.call(&[]);
 
@@ -29,6 +29,10 @@ pub struct WasmFeatures {
     #[structopt(long = "enable-bulk-memory")]
     pub bulk_memory: bool,
 
+    /// Enable support for the exception handling proposal.
+    #[structopt(long = "enable-exceptions")]
+    pub exceptions: bool,
+
     /// Enable support for all pre-standard proposals.
     #[structopt(long = "enable-all")]
     pub all: bool,
@@ -1,5 +1,6 @@
 //! Implements `PretyError` to print pretty errors in the CLI (when they happen)
 
+use crate::limits::LimitError;
 use anyhow::{Chain, Error};
 use colored::*;
 use std::fmt::{self, Debug, Write};
@@ -25,8 +26,11 @@ impl PrettyError {
         std::process::exit(match result {
             Ok(_t) => 0,
             Err(error) => {
+                let exit_code = error
+                    .downcast_ref::<LimitError>()
+                    .map_or(1, LimitError::exit_code);
                 eprintln!("{:?}", PrettyError { error });
-                1
+                exit_code
             }
         });
     }
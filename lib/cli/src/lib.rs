@@ -22,10 +22,13 @@ pub mod common;
 pub mod error;
 pub mod c_gen;
 pub mod cli;
+pub mod limits;
 #[cfg(feature = "debug")]
 pub mod logging;
 pub mod store;
 pub mod suggestions;
+#[cfg(feature = "compiler")]
+pub mod trace;
 pub mod utils;
 
 /// Version number of this crate.
@@ -0,0 +1,377 @@
+//! Resource limits that can be imposed on guests from the command line.
+
+use std::fmt;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use structopt::StructOpt;
+use wasmer::vm::{
+    self, MemoryError, MemoryStyle, TableStyle, VMMemoryDefinition, VMTableDefinition,
+};
+use wasmer::{BaseTunables, Engine, Instance, MemoryType, Pages, Store, TableType, Tunables};
+use wasmer_vm::{TableElement, Trap};
+
+/// The exit code used when a memory limit has been hit.
+pub const MEMORY_LIMIT_EXIT_CODE: i32 = 120;
+/// The exit code used when a table limit has been hit.
+pub const TABLE_LIMIT_EXIT_CODE: i32 = 121;
+/// The exit code used when the fuel has been exhausted.
+pub const FUEL_EXHAUSTED_EXIT_CODE: i32 = 122;
+/// The exit code used when the execution timed out.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+
+#[derive(Debug, Clone, StructOpt)]
+/// The resource limits options
+pub struct LimitOptions {
+    /// Maximum size of each memory, in Wasm pages (64 KiB each).
+    #[structopt(long = "max-memory", name = "PAGES")]
+    max_memory: Option<u32>,
+
+    /// Maximum number of elements of each table.
+    #[structopt(long = "max-table-elements", name = "ELEMENTS")]
+    max_table_elements: Option<u32>,
+
+    /// Maximum number of operators the guest can execute.
+    #[cfg(feature = "compiler")]
+    #[structopt(long = "fuel", name = "POINTS")]
+    fuel: Option<u64>,
+
+    /// Records which limits have been hit while running the guest.
+    #[structopt(skip)]
+    hits: Arc<LimitHits>,
+}
+
+/// Records which limits have been hit, shared between the tunables
+/// and the memories and tables they create.
+#[derive(Debug, Default)]
+struct LimitHits {
+    memory: AtomicBool,
+    table: AtomicBool,
+}
+
+/// The error reported when a guest hits one of its resource limits.
+#[derive(Debug)]
+pub enum LimitError {
+    /// A memory couldn't be created or grown within `--max-memory`.
+    Memory(Pages),
+    /// A table couldn't be created or grown within `--max-table-elements`.
+    Table(u32),
+    /// The guest exhausted its `--fuel`.
+    Fuel(u64),
+    /// The guest didn't finish within `--timeout`.
+    Timeout(Duration),
+}
+
+impl LimitError {
+    /// The exit code the process should terminate with.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Memory(_) => MEMORY_LIMIT_EXIT_CODE,
+            Self::Table(_) => TABLE_LIMIT_EXIT_CODE,
+            Self::Fuel(_) => FUEL_EXHAUSTED_EXIT_CODE,
+            Self::Timeout(_) => TIMEOUT_EXIT_CODE,
+        }
+    }
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Memory(pages) => write!(
+                f,
+                "the guest exceeded the memory limit of {} pages ({} bytes)",
+                pages.0,
+                pages.bytes().0
+            ),
+            Self::Table(elements) => write!(
+                f,
+                "the guest exceeded the table limit of {} elements",
+                elements
+            ),
+            Self::Fuel(points) => write!(f, "the guest exhausted its fuel of {} points", points),
+            Self::Timeout(duration) => write!(
+                f,
+                "the guest didn't finish within {:.3}s",
+                duration.as_secs_f64()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl LimitOptions {
+    /// Creates a store for the given engine, enforcing the memory and
+    /// table limits.
+    pub fn new_store(&self, engine: &(dyn Engine + Send + Sync)) -> Store {
+        let base = BaseTunables::for_target(engine.target());
+        Store::new_with_tunables(engine, self.tunables(base))
+    }
+
+    /// Wraps the given tunables so they enforce the memory and table limits.
+    pub fn tunables<T: Tunables>(&self, base: T) -> LimitingTunables<T> {
+        LimitingTunables {
+            max_memory: self.max_memory.map(Pages),
+            max_table_elements: self.max_table_elements,
+            hits: self.hits.clone(),
+            base,
+        }
+    }
+
+    /// Whether the limits change the generated code, in which case
+    /// previously cached artifacts can't be reused.
+    pub fn affects_compilation(&self) -> bool {
+        #[cfg(feature = "compiler")]
+        return self.fuel.is_some();
+        #[cfg(not(feature = "compiler"))]
+        return false;
+    }
+
+    /// Adds the metering middleware to the compiler, if `--fuel` is set.
+    #[cfg(feature = "compiler")]
+    pub fn apply_to_compiler(&self, compiler_config: &mut dyn wasmer_compiler::CompilerConfig) {
+        if let Some(fuel) = self.fuel {
+            let metering = wasmer_middlewares::Metering::new(
+                fuel,
+                |_: &wasmer_compiler::wasmparser::Operator| 1,
+            );
+            compiler_config.push_middleware(Arc::new(metering));
+        }
+    }
+
+    /// Converts the error of a guest execution into a [`LimitError`] if
+    /// it has been caused by one of the limits.
+    #[allow(unused_variables)]
+    pub fn check_error(&self, instance: Option<&Instance>, error: anyhow::Error) -> anyhow::Error {
+        if let Some(pages) = self.max_memory {
+            if self.hits.memory.load(Ordering::SeqCst) {
+                return error.context(LimitError::Memory(Pages(pages)));
+            }
+        }
+        if let Some(elements) = self.max_table_elements {
+            if self.hits.table.load(Ordering::SeqCst) {
+                return error.context(LimitError::Table(elements));
+            }
+        }
+        #[cfg(feature = "compiler")]
+        if let (Some(fuel), Some(instance)) = (self.fuel, instance) {
+            use wasmer::RuntimeError;
+            use wasmer_middlewares::metering::{get_remaining_points, MeteringPoints};
+            if error.downcast_ref::<RuntimeError>().is_some()
+                && get_remaining_points(instance) == MeteringPoints::Exhausted
+            {
+                return error.context(LimitError::Fuel(fuel));
+            }
+        }
+        error
+    }
+}
+
+/// A `Tunables` enforcing the memory and table limits set from the
+/// command line, and delegating everything else to a base implementation.
+pub struct LimitingTunables<T: Tunables> {
+    max_memory: Option<Pages>,
+    max_table_elements: Option<u32>,
+    hits: Arc<LimitHits>,
+    base: T,
+}
+
+impl<T: Tunables> LimitingTunables<T> {
+    /// Lowers the maximum of the memory type to the limit.
+    fn adjust_memory(&self, requested: &MemoryType) -> MemoryType {
+        let mut adjusted = *requested;
+        if let Some(limit) = self.max_memory {
+            adjusted.maximum = Some(requested.maximum.map_or(limit, |max| max.min(limit)));
+        }
+        adjusted
+    }
+
+    /// Ensures the initial size of the memory is within the limit.
+    fn validate_memory(&self, ty: &MemoryType) -> Result<(), MemoryError> {
+        match self.max_memory {
+            Some(limit) if ty.minimum > limit => {
+                self.hits.memory.store(true, Ordering::SeqCst);
+                Err(MemoryError::Generic(LimitError::Memory(limit).to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Lowers the maximum of the table type to the limit.
+    fn adjust_table(&self, requested: &TableType) -> TableType {
+        let mut adjusted = *requested;
+        if let Some(limit) = self.max_table_elements {
+            adjusted.maximum = Some(requested.maximum.map_or(limit, |max| max.min(limit)));
+        }
+        adjusted
+    }
+
+    /// Ensures the initial size of the table is within the limit.
+    fn validate_table(&self, ty: &TableType) -> Result<(), String> {
+        match self.max_table_elements {
+            Some(limit) if ty.minimum > limit => {
+                self.hits.table.store(true, Ordering::SeqCst);
+                Err(LimitError::Table(limit).to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn wrap_memory(&self, memory: Arc<dyn vm::Memory>) -> Arc<dyn vm::Memory> {
+        match self.max_memory {
+            Some(_) => Arc::new(LimitedMemory {
+                inner: memory,
+                hits: self.hits.clone(),
+            }),
+            None => memory,
+        }
+    }
+
+    fn wrap_table(&self, table: Arc<dyn vm::Table>) -> Arc<dyn vm::Table> {
+        match self.max_table_elements {
+            Some(_) => Arc::new(LimitedTable {
+                inner: table,
+                hits: self.hits.clone(),
+            }),
+            None => table,
+        }
+    }
+}
+
+impl<T: Tunables> Tunables for LimitingTunables<T> {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        self.base.memory_style(&self.adjust_memory(memory))
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(&self.adjust_table(table))
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        Ok(self.wrap_memory(self.base.create_host_memory(&adjusted, style)?))
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        let adjusted = self.adjust_memory(ty);
+        self.validate_memory(&adjusted)?;
+        Ok(self.wrap_memory(self.base.create_vm_memory(
+            &adjusted,
+            style,
+            vm_definition_location,
+        )?))
+    }
+
+    fn create_host_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        let adjusted = self.adjust_table(ty);
+        self.validate_table(&adjusted)?;
+        Ok(self.wrap_table(self.base.create_host_table(&adjusted, style)?))
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        let adjusted = self.adjust_table(ty);
+        self.validate_table(&adjusted)?;
+        Ok(self.wrap_table(
+            self.base
+                .create_vm_table(&adjusted, style, vm_definition_location)?,
+        ))
+    }
+}
+
+/// A memory recording when it fails to grow because of the limit.
+#[derive(Debug)]
+struct LimitedMemory {
+    inner: Arc<dyn vm::Memory>,
+    hits: Arc<LimitHits>,
+}
+
+impl vm::Memory for LimitedMemory {
+    fn ty(&self) -> &MemoryType {
+        self.inner.ty()
+    }
+
+    fn style(&self) -> &MemoryStyle {
+        self.inner.style()
+    }
+
+    fn size(&self) -> Pages {
+        self.inner.size()
+    }
+
+    fn grow(&self, delta: Pages) -> Result<Pages, MemoryError> {
+        let result = self.inner.grow(delta);
+        if let Err(MemoryError::CouldNotGrow { .. }) = result {
+            self.hits.memory.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+
+    fn vmmemory(&self) -> NonNull<VMMemoryDefinition> {
+        self.inner.vmmemory()
+    }
+
+    fn waiters(&self) -> Option<&vm::Waiters> {
+        self.inner.waiters()
+    }
+}
+
+/// A table recording when it fails to grow because of the limit.
+#[derive(Debug)]
+struct LimitedTable {
+    inner: Arc<dyn vm::Table>,
+    hits: Arc<LimitHits>,
+}
+
+impl vm::Table for LimitedTable {
+    fn style(&self) -> &TableStyle {
+        self.inner.style()
+    }
+
+    fn ty(&self) -> &TableType {
+        self.inner.ty()
+    }
+
+    fn size(&self) -> u32 {
+        self.inner.size()
+    }
+
+    fn grow(&self, delta: u32) -> Option<u32> {
+        let result = self.inner.grow(delta);
+        if result.is_none() {
+            self.hits.table.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+
+    fn get(&self, index: u32) -> Option<TableElement> {
+        self.inner.get(index)
+    }
+
+    fn set(&self, index: u32, element: TableElement) -> Result<(), Trap> {
+        self.inner.set(index, element)
+    }
+
+    fn vmtable(&self) -> NonNull<VMTableDefinition> {
+        self.inner.vmtable()
+    }
+}
@@ -2,6 +2,9 @@
 //! commands.
 
 use crate::common::WasmFeatures;
+use crate::limits::LimitOptions;
+#[cfg(all(feature = "compiler", feature = "engine"))]
+use crate::trace::TraceOptions;
 use anyhow::{Error, Result};
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -113,6 +116,9 @@ impl CompilerOptions {
         if self.features.reference_types || self.features.all {
             features.reference_types(true);
         }
+        if self.features.exceptions || self.features.all {
+            features.exceptions(true);
+        }
         Ok(features)
     }
 
@@ -383,6 +389,22 @@ impl StoreOptions {
         self.get_store_for_target(target)
     }
 
+    /// Gets the store for the host target, enforcing the given resource
+    /// limits on the guest and tracing it as requested
+    pub fn get_store_with_limits(
+        &self,
+        limits: &LimitOptions,
+        trace: &TraceOptions,
+    ) -> Result<(Store, EngineType, CompilerType)> {
+        let (mut compiler_config, compiler_type) = self.compiler.get_compiler_config()?;
+        limits.apply_to_compiler(&mut *compiler_config);
+        trace.apply_to_compiler(&mut *compiler_config);
+        let (engine, engine_type) =
+            self.get_engine_with_compiler(Target::default(), compiler_config)?;
+        let store = limits.new_store(&*engine);
+        Ok((store, engine_type, compiler_type))
+    }
+
     /// Gets the store for a given target, with the engine name and compiler name selected, as
     pub fn get_store_for_target(
         &self,
@@ -462,6 +484,17 @@ impl StoreOptions {
         Ok((store, engine_type, CompilerType::Headless))
     }
 
+    /// Get the store (headless engine), enforcing the given resource limits
+    /// on the guest
+    pub fn get_store_with_limits(
+        &self,
+        limits: &LimitOptions,
+    ) -> Result<(Store, EngineType, CompilerType)> {
+        let (engine, engine_type) = self.get_engine_headless()?;
+        let store = limits.new_store(&*engine);
+        Ok((store, engine_type, CompilerType::Headless))
+    }
+
     /// Gets the store for provided host target
     pub fn get_store_for_target(
         &self,
@@ -479,6 +512,14 @@ impl StoreOptions {
         bail!("No engines are enabled");
     }
 
+    /// Get the store, enforcing the given resource limits on the guest
+    pub fn get_store_with_limits(
+        &self,
+        _limits: &LimitOptions,
+    ) -> Result<(Store, EngineType, CompilerType)> {
+        bail!("No engines are enabled");
+    }
+
     /// Gets the store for the host target
     pub fn get_store_for_target(
         &self,
@@ -0,0 +1,442 @@
+//! Tracing of the host calls, function calls and traps of a guest.
+
+use anyhow::{Error, Result};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use structopt::StructOpt;
+use wasmer::{
+    Exports, Function, FunctionType, HostEnvInitError, ImportObject, Instance, LazyInit,
+    RuntimeError, Store, Val, WasmerEnv,
+};
+use wasmer_middlewares::call_trace::{
+    get_saved_values, CALL_TRACE_NAMESPACE, ENTER_FUNCTION, EXIT_FUNCTION, HOST_CALL_FUNCTION,
+    HOST_RETURN_FUNCTION,
+};
+use wasmer_types::{FunctionIndex, ImportIndex};
+
+/// The names of the WASI errors, indexed by their errno.
+const WASI_ERRNO_NAMES: [&str; 77] = [
+    "ESUCCESS",
+    "E2BIG",
+    "EACCES",
+    "EADDRINUSE",
+    "EADDRNOTAVAIL",
+    "EAFNOSUPPORT",
+    "EAGAIN",
+    "EALREADY",
+    "EBADF",
+    "EBADMSG",
+    "EBUSY",
+    "ECANCELED",
+    "ECHILD",
+    "ECONNABORTED",
+    "ECONNREFUSED",
+    "ECONNRESET",
+    "EDEADLK",
+    "EDESTADDRREQ",
+    "EDOM",
+    "EDQUOT",
+    "EEXIST",
+    "EFAULT",
+    "EFBIG",
+    "EHOSTUNREACH",
+    "EIDRM",
+    "EILSEQ",
+    "EINPROGRESS",
+    "EINTR",
+    "EINVAL",
+    "EIO",
+    "EISCONN",
+    "EISDIR",
+    "ELOOP",
+    "EMFILE",
+    "EMLINK",
+    "EMSGSIZE",
+    "EMULTIHOP",
+    "ENAMETOOLONG",
+    "ENETDOWN",
+    "ENETRESET",
+    "ENETUNREACH",
+    "ENFILE",
+    "ENOBUFS",
+    "ENODEV",
+    "ENOENT",
+    "ENOEXEC",
+    "ENOLCK",
+    "ENOLINK",
+    "ENOMEM",
+    "ENOMSG",
+    "ENOPROTOOPT",
+    "ENOSPC",
+    "ENOSYS",
+    "ENOTCONN",
+    "ENOTDIR",
+    "ENOTEMPTY",
+    "ENOTRECOVERABLE",
+    "ENOTSOCK",
+    "ENOTSUP",
+    "ENOTTY",
+    "ENXIO",
+    "EOVERFLOW",
+    "EOWNERDEAD",
+    "EPERM",
+    "EPIPE",
+    "EPROTO",
+    "EPROTONOSUPPORT",
+    "EPROTOTYPE",
+    "ERANGE",
+    "EROFS",
+    "ESPIPE",
+    "ESRCH",
+    "ESTALE",
+    "ETIMEDOUT",
+    "ETXTBSY",
+    "EXDEV",
+    "ENOTCAPABLE",
+];
+
+#[derive(Debug, Clone, StructOpt)]
+/// The tracing options
+pub struct TraceOptions {
+    /// Trace the host calls (WASI, Emscripten or linked modules) and the
+    /// traps of the guest. Requires a compiler.
+    #[structopt(long = "trace")]
+    enabled: bool,
+
+    /// Also trace the entry and exit of the guest functions.
+    #[structopt(long = "trace-functions")]
+    functions: bool,
+
+    /// Trace output format: `text` or `json` (one object per line).
+    #[structopt(long = "trace-format", default_value = "text")]
+    format: TraceFormat,
+
+    /// Write the trace to a file instead of stderr.
+    #[structopt(long = "trace-output", name = "TRACE_PATH", parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+/// The output format of the trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Human readable text
+    Text,
+    /// JSON lines, for post-processing
+    Json,
+}
+
+impl FromStr for TraceFormat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            format => bail!("The `{}` format is not supported.", format),
+        }
+    }
+}
+
+impl TraceOptions {
+    /// Whether tracing is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled || self.functions
+    }
+
+    /// Adds the call trace middleware to the compiler, if tracing is enabled.
+    pub fn apply_to_compiler(&self, compiler_config: &mut dyn wasmer_compiler::CompilerConfig) {
+        if self.is_enabled() {
+            let call_trace = wasmer_middlewares::CallTrace::new(self.functions, true);
+            compiler_config.push_middleware(Arc::new(call_trace));
+        }
+    }
+
+    /// Opens the trace output, if tracing is enabled.
+    pub fn start(&self) -> Result<Option<Tracer>> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        let output: Box<dyn Write + Send> = match &self.output {
+            Some(path) => Box::new(LineWriter::new(File::create(path)?)),
+            None => Box::new(io::stderr()),
+        };
+        Ok(Some(Tracer(Arc::new(TracerInner {
+            format: self.format,
+            output: Mutex::new(output),
+            start: Instant::now(),
+            depth: AtomicUsize::new(0),
+        }))))
+    }
+}
+
+/// Writes the trace events of the guests.
+#[derive(Clone)]
+pub struct Tracer(Arc<TracerInner>);
+
+struct TracerInner {
+    format: TraceFormat,
+    output: Mutex<Box<dyn Write + Send>>,
+    start: Instant,
+    /// The number of guest functions being executed, to indent the text output.
+    depth: AtomicUsize,
+}
+
+impl Tracer {
+    /// Registers the hooks the instrumented guests call into.
+    pub fn register_hooks(&self, store: &Store, import_object: &mut ImportObject) {
+        let env = HookEnv {
+            tracer: self.clone(),
+            instance: LazyInit::new(),
+            imports: Default::default(),
+        };
+        let mut exports = Exports::new();
+        exports.insert(
+            ENTER_FUNCTION,
+            Function::new_native_with_env(store, env.clone(), |env: &HookEnv, index: i32| {
+                env.function_event(ENTER_FUNCTION, index)
+            }),
+        );
+        exports.insert(
+            EXIT_FUNCTION,
+            Function::new_native_with_env(store, env.clone(), |env: &HookEnv, index: i32| {
+                env.function_event(EXIT_FUNCTION, index)
+            }),
+        );
+        exports.insert(
+            HOST_CALL_FUNCTION,
+            Function::new_native_with_env(store, env.clone(), |env: &HookEnv, index: i32| {
+                env.host_event(HOST_CALL_FUNCTION, index)
+            }),
+        );
+        exports.insert(
+            HOST_RETURN_FUNCTION,
+            Function::new_native_with_env(store, env, |env: &HookEnv, index: i32| {
+                env.host_event(HOST_RETURN_FUNCTION, index)
+            }),
+        );
+        import_object.register(CALL_TRACE_NAMESPACE, exports);
+    }
+
+    /// Records the trap the guest stopped with, if any.
+    pub fn trap(&self, error: &anyhow::Error) {
+        let error = match error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<RuntimeError>())
+        {
+            Some(error) => error,
+            None => return,
+        };
+        let frames = error
+            .trace()
+            .iter()
+            .map(|frame| {
+                format!(
+                    "{}!{}",
+                    frame.module_name(),
+                    frame
+                        .function_name()
+                        .map_or_else(|| format!("<{}>", frame.func_index()), str::to_string)
+                )
+            })
+            .collect::<Vec<_>>();
+        let message = error.message();
+        match self.0.format {
+            TraceFormat::Text => {
+                let mut text = format!("trap: {}", message);
+                for frame in frames.iter() {
+                    text.push_str(&format!("\n    at {}", frame));
+                }
+                self.write_text(&text, 0);
+            }
+            TraceFormat::Json => self.write_json(json!({
+                "event": "trap",
+                "message": message,
+                "frames": frames,
+            })),
+        }
+    }
+
+    fn elapsed(&self) -> f64 {
+        self.0.start.elapsed().as_secs_f64()
+    }
+
+    fn write_text(&self, text: &str, depth: usize) {
+        let line = format!(
+            "[{:>12.6}] {:indent$}{}\n",
+            self.elapsed(),
+            "",
+            text,
+            indent = depth * 2
+        );
+        // The guest keeps running if the trace can't be written
+        let _ = self.0.output.lock().unwrap().write_all(line.as_bytes());
+    }
+
+    fn write_json(&self, mut event: serde_json::Value) {
+        event["time"] = json!(self.elapsed());
+        let line = format!("{}\n", event);
+        let _ = self.0.output.lock().unwrap().write_all(line.as_bytes());
+    }
+}
+
+/// The imported function a host call goes to.
+struct HostFunction {
+    module: String,
+    name: String,
+    ty: FunctionType,
+}
+
+/// The environment of the tracing hooks.
+#[derive(Clone)]
+struct HookEnv {
+    tracer: Tracer,
+    instance: LazyInit<Instance>,
+    /// The imported functions of the instance, by function index.
+    imports: Arc<HashMap<u32, HostFunction>>,
+}
+
+impl WasmerEnv for HookEnv {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        let info = instance.module().info();
+        let imports = info
+            .imports
+            .iter()
+            .filter_map(|((module, name, _), index)| match index {
+                ImportIndex::Function(index) => Some((
+                    index.as_u32(),
+                    HostFunction {
+                        module: module.clone(),
+                        name: name.clone(),
+                        ty: info.signatures[info.functions[*index]].clone(),
+                    },
+                )),
+                _ => None,
+            })
+            .collect();
+        self.imports = Arc::new(imports);
+        self.instance.initialize(instance.clone());
+        Ok(())
+    }
+}
+
+impl HookEnv {
+    fn instance(&self) -> &Instance {
+        self.instance
+            .get_ref()
+            .expect("The tracing hooks have been called before their initialization")
+    }
+
+    fn function_event(&self, event: &str, index: i32) {
+        let instance = self.instance();
+        let module = instance.module().name().unwrap_or("<module>");
+        let function = instance
+            .module()
+            .info()
+            .function_names
+            .get(&FunctionIndex::from_u32(index as u32))
+            .cloned()
+            .unwrap_or_else(|| format!("<{}>", index));
+        let tracer = &self.tracer;
+        let depth = match event {
+            ENTER_FUNCTION => tracer.0.depth.fetch_add(1, Ordering::SeqCst),
+            _ => tracer
+                .0
+                .depth
+                .fetch_sub(1, Ordering::SeqCst)
+                .saturating_sub(1),
+        };
+        match tracer.0.format {
+            TraceFormat::Text => {
+                tracer.write_text(&format!("{} {}!{}", event, module, function), depth)
+            }
+            TraceFormat::Json => tracer.write_json(json!({
+                "event": event,
+                "module": module,
+                "function": function,
+                "index": index,
+            })),
+        }
+    }
+
+    fn host_event(&self, event: &str, index: i32) {
+        let host_function = match self.imports.get(&(index as u32)) {
+            Some(host_function) => host_function,
+            None => return,
+        };
+        let is_call = event == HOST_CALL_FUNCTION;
+        let types = if is_call {
+            host_function.ty.params()
+        } else {
+            host_function.ty.results()
+        };
+        let values = get_saved_values(self.instance(), types);
+
+        // WASI functions return their errno
+        let errno = match values.as_slice() {
+            [Val::I32(errno)] if !is_call && host_function.module.starts_with("wasi") => {
+                WASI_ERRNO_NAMES.get(*errno as usize).copied()
+            }
+            _ => None,
+        };
+
+        let tracer = &self.tracer;
+        let depth = tracer.0.depth.load(Ordering::SeqCst);
+        match tracer.0.format {
+            TraceFormat::Text => {
+                let values = values
+                    .iter()
+                    .map(|value| value.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let text = if is_call {
+                    format!(
+                        "call {}.{}({})",
+                        host_function.module, host_function.name, values
+                    )
+                } else {
+                    format!(
+                        "return {}.{} = [{}]{}",
+                        host_function.module,
+                        host_function.name,
+                        values,
+                        errno.map_or_else(String::new, |errno| format!(" ({})", errno))
+                    )
+                };
+                tracer.write_text(&text, depth)
+            }
+            TraceFormat::Json => {
+                let values = values.iter().map(value_to_json).collect::<Vec<_>>();
+                let mut event = json!({
+                    "event": event,
+                    "module": host_function.module,
+                    "function": host_function.name,
+                });
+                if is_call {
+                    event["args"] = json!(values);
+                } else {
+                    event["results"] = json!(values);
+                    if let Some(errno) = errno {
+                        event["errno"] = json!(errno);
+                    }
+                }
+                tracer.write_json(event)
+            }
+        }
+    }
+}
+
+fn value_to_json(value: &Val) -> serde_json::Value {
+    match value {
+        Val::I32(value) => json!(value),
+        Val::I64(value) => json!(value),
+        Val::F32(value) => json!(value),
+        Val::F64(value) => json!(value),
+        value => json!(value.to_string()),
+    }
+}
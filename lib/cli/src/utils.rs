@@ -2,6 +2,8 @@
 use anyhow::{bail, Result};
 use std::env;
 use std::path::PathBuf;
+use std::time::Duration;
+use wasmer::{Val, ValType};
 
 /// Whether or not Wasmer should print with color
 pub fn wasmer_should_print_color() -> bool {
@@ -64,9 +66,64 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
     }
 }
 
+/// Parses a named module, in the form `<name>=<path>`.
+pub fn parse_named_module(entry: &str) -> Result<(String, PathBuf)> {
+    match entry.find('=') {
+        Some(position) if position > 0 && position < entry.len() - 1 => Ok((
+            entry[..position].to_string(),
+            PathBuf::from(&entry[position + 1..]),
+        )),
+        _ => bail!(
+            "Modules must be of the form `<name>=<path>`; found `{}`",
+            &entry
+        ),
+    }
+}
+
+/// Parses a timeout, expressed in seconds.
+pub fn parse_timeout(entry: &str) -> Result<Duration> {
+    match entry.trim().parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds > 0.0 => Ok(Duration::from_secs_f64(seconds)),
+        _ => bail!(
+            "Timeout must be a positive number of seconds; found `{}`",
+            &entry
+        ),
+    }
+}
+
+/// Parses a WebAssembly value of the given type.
+pub fn parse_value(arg: &str, ty: &ValType) -> Result<Val> {
+    match ty {
+        ValType::I32 => {
+            Ok(Val::I32(arg.parse().map_err(|_| {
+                anyhow!("Can't convert `{}` into a i32", arg)
+            })?))
+        }
+        ValType::I64 => {
+            Ok(Val::I64(arg.parse().map_err(|_| {
+                anyhow!("Can't convert `{}` into a i64", arg)
+            })?))
+        }
+        ValType::F32 => {
+            Ok(Val::F32(arg.parse().map_err(|_| {
+                anyhow!("Can't convert `{}` into a f32", arg)
+            })?))
+        }
+        ValType::F64 => {
+            Ok(Val::F64(arg.parse().map_err(|_| {
+                anyhow!("Can't convert `{}` into a f64", arg)
+            })?))
+        }
+        _ => bail!("Don't know how to convert {} into {:?}", arg, ty),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse_envvar;
+    use super::{parse_envvar, parse_named_module, parse_timeout, parse_value};
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use wasmer::{Val, ValType};
 
     #[test]
     fn test_parse_envvar() {
@@ -89,4 +146,49 @@ mod tests {
             ("A".into(), "B=C=D".into())
         );
     }
+
+    #[test]
+    fn test_parse_named_module() {
+        assert_eq!(
+            parse_named_module("libc=lib/libc.wasm").unwrap(),
+            ("libc".into(), PathBuf::from("lib/libc.wasm"))
+        );
+        assert_eq!(
+            parse_named_module("a=b=c.wasm").unwrap(),
+            ("a".into(), PathBuf::from("b=c.wasm"))
+        );
+        assert_eq!(
+            parse_named_module("libc.wasm").unwrap_err().to_string(),
+            "Modules must be of the form `<name>=<path>`; found `libc.wasm`"
+        );
+        assert_eq!(
+            parse_named_module("=libc.wasm").unwrap_err().to_string(),
+            "Modules must be of the form `<name>=<path>`; found `=libc.wasm`"
+        );
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("2").unwrap(), Duration::from_secs(2));
+        assert_eq!(parse_timeout(" 0.5 ").unwrap(), Duration::from_millis(500));
+        assert_eq!(
+            parse_timeout("0").unwrap_err().to_string(),
+            "Timeout must be a positive number of seconds; found `0`"
+        );
+        assert_eq!(
+            parse_timeout("1m").unwrap_err().to_string(),
+            "Timeout must be a positive number of seconds; found `1m`"
+        );
+    }
+
+    #[test]
+    fn test_parse_value() {
+        assert_eq!(parse_value("42", &ValType::I32).unwrap(), Val::I32(42));
+        assert_eq!(parse_value("-1", &ValType::I64).unwrap(), Val::I64(-1));
+        assert_eq!(parse_value("1.5", &ValType::F64).unwrap(), Val::F64(1.5));
+        assert_eq!(
+            parse_value("foo", &ValType::I32).unwrap_err().to_string(),
+            "Can't convert `foo` into a i32"
+        );
+    }
 }
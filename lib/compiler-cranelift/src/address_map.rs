@@ -1,9 +1,19 @@
 // This file contains code from external sources.
 // Attributions: https://github.com/wasmerio/wasmer/blob/master/ATTRIBUTIONS.md
 
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::{LabelValueLoc, ValueLabel, ValueLoc};
 use cranelift_codegen::machinst::buffer::MachSrcLoc;
+use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::{isa, Context};
-use wasmer_compiler::{FunctionAddressMap, FunctionBodyData, InstructionAddressMap, SourceLoc};
+use std::iter;
+use wasmer_compiler::wasmparser::{BinaryReader, BinaryReaderError};
+use wasmer_compiler::Architecture;
+use wasmer_compiler::{
+    wptype_to_type, CompileError, FunctionAddressMap, FunctionBodyData, InstructionAddressMap,
+    LocalLocations, SourceLoc, ValueLocation, ValueLocationRange,
+};
+use wasmer_types::Type;
 
 pub fn get_function_address_map<'data>(
     context: &Context,
@@ -55,3 +65,83 @@ pub fn get_function_address_map<'data>(
         body_len,
     }
 }
+
+/// Get the locations of the locals of the function compiled in `context`,
+/// parameters first.
+///
+/// The function must have been compiled with `collect_debug_info`.
+pub fn get_function_locals<'data>(
+    context: &Context,
+    data: &FunctionBodyData<'data>,
+    params: &[Type],
+    isa: &dyn isa::TargetIsa,
+) -> Result<Vec<LocalLocations>, CompileError> {
+    let mut types = params.to_vec();
+    let mut reader = BinaryReader::new(data.data);
+    let read_error = |e: BinaryReaderError| CompileError::Wasm(e.into());
+    for _ in 0..reader.read_var_u32().map_err(read_error)? {
+        let count = reader.read_var_u32().map_err(read_error)?;
+        let ty =
+            wptype_to_type(reader.read_type().map_err(read_error)?).map_err(CompileError::Wasm)?;
+        types.extend(iter::repeat(ty).take(count as usize));
+    }
+
+    let ranges = context
+        .build_value_labels_ranges(isa)
+        .map_err(|error| CompileError::Codegen(pretty_error(&context.func, Some(isa), error)))?;
+    Ok(types
+        .into_iter()
+        .enumerate()
+        .map(|(index, ty)| LocalLocations {
+            ty,
+            ranges: ranges
+                .get(&ValueLabel::new(index))
+                .into_iter()
+                .flatten()
+                .filter_map(|range| {
+                    Some(ValueLocationRange {
+                        start: range.start,
+                        end: range.end,
+                        location: get_value_location(context, range.loc, isa)?,
+                    })
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+fn get_value_location(
+    context: &Context,
+    loc: LabelValueLoc,
+    isa: &dyn isa::TargetIsa,
+) -> Option<ValueLocation> {
+    Some(match loc {
+        LabelValueLoc::ValueLoc(ValueLoc::Reg(reg)) => {
+            ValueLocation::Register(isa.map_dwarf_register(reg).ok()?)
+        }
+        // The old x86 backend keeps `rbp` pointing right under the return
+        // address, while stack slot offsets are relative to the address
+        // right above it.
+        LabelValueLoc::ValueLoc(ValueLoc::Stack(slot)) => ValueLocation::Memory {
+            register: X86_64_RBP,
+            offset: i64::from(context.func.stack_slots[slot].offset?) + 16,
+        },
+        LabelValueLoc::ValueLoc(ValueLoc::Unassigned) => return None,
+        LabelValueLoc::Reg(reg) => {
+            ValueLocation::Register(isa.map_regalloc_reg_to_dwarf(reg).ok()?)
+        }
+        LabelValueLoc::SPOffset(offset) => ValueLocation::Memory {
+            register: match isa.triple().architecture {
+                Architecture::X86_64 => X86_64_RSP,
+                Architecture::Aarch64(_) => AARCH64_SP,
+                _ => return None,
+            },
+            offset,
+        },
+    })
+}
+
+/// The DWARF register numbers of the stack and frame pointers.
+const X86_64_RSP: u16 = 7;
+const X86_64_RBP: u16 = 6;
+const AARCH64_SP: u16 = 31;
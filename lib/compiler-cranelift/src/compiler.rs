@@ -1,6 +1,6 @@
 //! Support for compiling with Cranelift.
 
-use crate::address_map::get_function_address_map;
+use crate::address_map::{get_function_address_map, get_function_locals};
 use crate::config::Cranelift;
 #[cfg(feature = "unwind")]
 use crate::dwarf::WriterRelocate;
@@ -14,21 +14,24 @@ use crate::translator::{
     CraneliftUnwindInfo, FuncTranslator,
 };
 use cranelift_codegen::ir;
+use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::{binemit, Context};
 #[cfg(feature = "unwind")]
-use gimli::write::{Address, EhFrame, FrameTable};
+use gimli::write::{Address, CieId, EhFrame, FrameTable};
 use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
-use std::sync::Arc;
+#[cfg(feature = "unwind")]
+use std::sync::{Arc, Mutex};
 use wasmer_compiler::CompileError;
 use wasmer_compiler::{CallingConvention, ModuleTranslationState, Target};
 use wasmer_compiler::{
     Compilation, CompileModuleInfo, CompiledFunction, CompiledFunctionFrameInfo,
-    CompiledFunctionUnwindInfo, Compiler, Dwarf, FunctionBody, FunctionBodyData,
+    CompiledFunctionUnwindInfo, Compiler, CustomSection, Dwarf, FunctionBody, FunctionBodyData,
     ModuleMiddlewareChain, SectionIndex,
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{FunctionIndex, LocalFunctionIndex, SignatureIndex};
+use wasmer_vm::ModuleInfo;
 
 /// A compiler that compiles a WebAssembly module with Cranelift, translating the Wasm to Cranelift IR,
 /// optimizing it and then translating to assembly.
@@ -49,6 +52,20 @@ impl CraneliftCompiler {
 }
 
 impl Compiler for CraneliftCompiler {
+    fn transform_module_info(&self, module: &mut ModuleInfo) {
+        self.config.middlewares.apply_on_module_info(module);
+    }
+
+    fn analyze_function_bodies(
+        &self,
+        module: &ModuleInfo,
+        function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) {
+        self.config
+            .middlewares
+            .apply_on_function_bodies(module, function_body_inputs);
+    }
+
     /// Compile the module using Cranelift, producing a compilation result with
     /// associated relocations.
     fn compile_module(
@@ -60,11 +77,6 @@ impl Compiler for CraneliftCompiler {
     ) -> Result<Compilation, CompileError> {
         let isa = self.config().isa(target);
         let frontend_config = isa.frontend_config();
-        let memory_styles = &compile_info.memory_styles;
-        let table_styles = &compile_info.table_styles;
-        let mut module = (*compile_info.module).clone();
-        self.config.middlewares.apply_on_module_info(&mut module);
-        compile_info.module = Arc::new(module);
         let module = &compile_info.module;
         let signatures = module
             .signatures
@@ -73,28 +85,13 @@ impl Compiler for CraneliftCompiler {
             .collect::<PrimaryMap<SignatureIndex, ir::Signature>>();
 
         // Generate the frametable
-        #[cfg(feature = "unwind")]
         let dwarf_frametable = if function_body_inputs.is_empty() {
             // If we have no function body inputs, we don't need to
             // construct the `FrameTable`. Constructing it, with empty
             // FDEs will cause some issues in Linux.
             None
         } else {
-            use std::sync::Mutex;
-            match target.triple().default_calling_convention() {
-                Ok(CallingConvention::SystemV) => {
-                    match isa.create_systemv_cie() {
-                        Some(cie) => {
-                            let mut dwarf_frametable = FrameTable::default();
-                            let cie_id = dwarf_frametable.add_cie(cie);
-                            Some((Arc::new(Mutex::new(dwarf_frametable)), cie_id))
-                        }
-                        // Even though we are in a SystemV system, Cranelift doesn't support it
-                        None => None,
-                    }
-                }
-                _ => None,
-            }
+            dwarf_frametable(&*isa, target)
         };
 
         let functions = function_body_inputs
@@ -102,117 +99,22 @@ impl Compiler for CraneliftCompiler {
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
             .map_init(FuncTranslator::new, |func_translator, (i, input)| {
-                let func_index = module.func_index(*i);
-                let mut context = Context::new();
-                let mut func_env = FuncEnvironment::new(
-                    isa.frontend_config(),
-                    module,
-                    &signatures,
-                    &memory_styles,
-                    &table_styles,
-                );
-                context.func.name = get_function_name(func_index);
-                context.func.signature = signatures[module.functions[func_index]].clone();
-                // if generate_debug_info {
-                //     context.func.collect_debug_info();
-                // }
-
-                func_translator.translate(
+                self.compile_function_body(
+                    &*isa,
+                    compile_info,
                     module_translation_state,
-                    input.data,
-                    input.module_offset,
-                    &mut context.func,
-                    &mut func_env,
+                    &signatures,
+                    &dwarf_frametable,
+                    func_translator,
                     *i,
-                    &self.config,
-                )?;
-
-                let mut code_buf: Vec<u8> = Vec::new();
-                let mut reloc_sink = RelocSink::new(&module, func_index);
-                let mut trap_sink = TrapSink::new();
-                let mut stackmap_sink = binemit::NullStackMapSink {};
-                context
-                    .compile_and_emit(
-                        &*isa,
-                        &mut code_buf,
-                        &mut reloc_sink,
-                        &mut trap_sink,
-                        &mut stackmap_sink,
-                    )
-                    .map_err(|error| {
-                        CompileError::Codegen(pretty_error(&context.func, Some(&*isa), error))
-                    })?;
-
-                let unwind_info = match compiled_function_unwind_info(&*isa, &context)? {
-                    #[cfg(feature = "unwind")]
-                    CraneliftUnwindInfo::FDE(fde) => {
-                        if let Some((dwarf_frametable, cie_id)) = &dwarf_frametable {
-                            dwarf_frametable
-                                .lock()
-                                .expect("Can't write into DWARF frametable")
-                                .add_fde(
-                                    *cie_id,
-                                    fde.to_fde(Address::Symbol {
-                                        // The symbol is the kind of relocation.
-                                        // "0" is used for functions
-                                        symbol: WriterRelocate::FUNCTION_SYMBOL,
-                                        // We use the addend as a way to specify the
-                                        // function index
-                                        addend: i.index() as _,
-                                    }),
-                                );
-                            // The unwind information is inserted into the dwarf section
-                            Some(CompiledFunctionUnwindInfo::Dwarf)
-                        } else {
-                            None
-                        }
-                    }
-                    other => other.maybe_into_to_windows_unwind(),
-                };
-
-                let address_map = get_function_address_map(&context, input, code_buf.len(), &*isa);
-
-                // We transform the Cranelift JumpTable's into compiler JumpTables
-                let func_jt_offsets = transform_jump_table(context.func.jt_offsets);
-
-                Ok(CompiledFunction {
-                    body: FunctionBody {
-                        body: code_buf,
-                        unwind_info,
-                    },
-                    jt_offsets: func_jt_offsets,
-                    relocations: reloc_sink.func_relocs,
-                    frame_info: CompiledFunctionFrameInfo {
-                        address_map,
-                        traps: trap_sink.traps,
-                    },
-                })
+                    input,
+                )
             })
             .collect::<Result<Vec<_>, CompileError>>()?
             .into_iter()
             .collect::<PrimaryMap<LocalFunctionIndex, _>>();
 
-        #[cfg(feature = "unwind")]
-        let (custom_sections, dwarf) = {
-            let mut custom_sections = PrimaryMap::new();
-            let dwarf = if let Some((dwarf_frametable, _cie_id)) = dwarf_frametable {
-                let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
-                dwarf_frametable
-                    .lock()
-                    .unwrap()
-                    .write_eh_frame(&mut eh_frame)
-                    .unwrap();
-
-                let eh_frame_section = eh_frame.0.into_section();
-                custom_sections.push(eh_frame_section);
-                Some(Dwarf::new(SectionIndex::new(0)))
-            } else {
-                None
-            };
-            (custom_sections, dwarf)
-        };
-        #[cfg(not(feature = "unwind"))]
-        let (custom_sections, dwarf) = (PrimaryMap::new(), None);
+        let (custom_sections, dwarf) = eh_frame_section(target, dwarf_frametable);
 
         // function call trampolines (only for local functions, by signature)
         let function_call_trampolines = module
@@ -249,4 +151,227 @@ impl Compiler for CraneliftCompiler {
             dwarf,
         ))
     }
+
+    /// Compile a single function using Cranelift, along with its unwind
+    /// information.
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &mut CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<Compilation, CompileError> {
+        let isa = self.config().isa(target);
+        let frontend_config = isa.frontend_config();
+        let signatures = compile_info
+            .module
+            .signatures
+            .iter()
+            .map(|(_sig_index, func_type)| signature_to_cranelift_ir(func_type, frontend_config))
+            .collect::<PrimaryMap<SignatureIndex, ir::Signature>>();
+        let dwarf_frametable = dwarf_frametable(&*isa, target);
+
+        let mut functions = PrimaryMap::new();
+        functions.push(self.compile_function_body(
+            &*isa,
+            compile_info,
+            module_translation_state,
+            &signatures,
+            &dwarf_frametable,
+            &mut FuncTranslator::new(),
+            index,
+            input,
+        )?);
+        let (custom_sections, dwarf) = eh_frame_section(target, dwarf_frametable);
+
+        Ok(Compilation::new(
+            functions,
+            custom_sections,
+            PrimaryMap::new(),
+            PrimaryMap::new(),
+            dwarf,
+        ))
+    }
+}
+
+impl CraneliftCompiler {
+    /// Compile the body of a single function, adding its unwind
+    /// information to `dwarf_frametable`.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_function_body(
+        &self,
+        isa: &dyn TargetIsa,
+        compile_info: &CompileModuleInfo,
+        module_translation_state: &ModuleTranslationState,
+        signatures: &PrimaryMap<SignatureIndex, ir::Signature>,
+        dwarf_frametable: &DwarfFrameTable,
+        func_translator: &mut FuncTranslator,
+        i: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<CompiledFunction, CompileError> {
+        let module = &compile_info.module;
+        let func_index = module.func_index(i);
+        let mut context = Context::new();
+        let mut func_env = FuncEnvironment::new(
+            isa.frontend_config(),
+            // The old x86 backend has no encodings for the atomic
+            // instructions.
+            isa.get_mach_backend().is_some(),
+            module,
+            signatures,
+            &compile_info.memory_styles,
+            &compile_info.table_styles,
+        );
+        context.func.name = get_function_name(func_index);
+        context.func.signature = signatures[module.functions[func_index]].clone();
+        // The locals are described to debuggers along with the DWARF of
+        // the module, if it has any.
+        let describe_locals = module.custom_sections.contains_key(".debug_info");
+        if describe_locals {
+            context.func.collect_debug_info();
+        }
+
+        func_translator.translate(
+            module_translation_state,
+            input.data,
+            input.module_offset,
+            &mut context.func,
+            &mut func_env,
+            i,
+            &self.config,
+        )?;
+
+        let mut code_buf: Vec<u8> = Vec::new();
+        let mut reloc_sink = RelocSink::new(&module, func_index);
+        let mut trap_sink = TrapSink::new();
+        let mut stackmap_sink = binemit::NullStackMapSink {};
+        context
+            .compile_and_emit(
+                isa,
+                &mut code_buf,
+                &mut reloc_sink,
+                &mut trap_sink,
+                &mut stackmap_sink,
+            )
+            .map_err(|error| {
+                CompileError::Codegen(pretty_error(&context.func, Some(isa), error))
+            })?;
+
+        let unwind_info = match compiled_function_unwind_info(isa, &context)? {
+            #[cfg(feature = "unwind")]
+            CraneliftUnwindInfo::FDE(fde) => {
+                if let Some((dwarf_frametable, cie_id)) = dwarf_frametable {
+                    dwarf_frametable
+                        .lock()
+                        .expect("Can't write into DWARF frametable")
+                        .add_fde(
+                            *cie_id,
+                            fde.to_fde(Address::Symbol {
+                                // The symbol is the kind of relocation.
+                                // "0" is used for functions
+                                symbol: WriterRelocate::FUNCTION_SYMBOL,
+                                // We use the addend as a way to specify the
+                                // function index
+                                addend: i.index() as _,
+                            }),
+                        );
+                    // The unwind information is inserted into the dwarf section
+                    Some(CompiledFunctionUnwindInfo::Dwarf)
+                } else {
+                    None
+                }
+            }
+            other => other.maybe_into_to_windows_unwind(),
+        };
+
+        let address_map = get_function_address_map(&context, input, code_buf.len(), isa);
+        let locals = if describe_locals {
+            let params = module.signatures[module.functions[func_index]].params();
+            get_function_locals(&context, input, params, isa)?
+        } else {
+            vec![]
+        };
+
+        // We transform the Cranelift JumpTable's into compiler JumpTables
+        let func_jt_offsets = transform_jump_table(context.func.jt_offsets);
+
+        Ok(CompiledFunction {
+            body: FunctionBody {
+                body: code_buf,
+                unwind_info,
+            },
+            jt_offsets: func_jt_offsets,
+            relocations: reloc_sink.func_relocs,
+            frame_info: CompiledFunctionFrameInfo {
+                address_map,
+                traps: trap_sink.traps,
+                locals,
+            },
+        })
+    }
+}
+
+/// The DWARF frame table the unwind information of the functions is
+/// written to, with the id of its CIE.
+#[cfg(feature = "unwind")]
+type DwarfFrameTable = Option<(Arc<Mutex<FrameTable>>, CieId)>;
+#[cfg(not(feature = "unwind"))]
+type DwarfFrameTable = Option<()>;
+
+/// Create the DWARF frame table, if the unwind information of `target`
+/// is written in DWARF.
+#[cfg(feature = "unwind")]
+fn dwarf_frametable(isa: &dyn TargetIsa, target: &Target) -> DwarfFrameTable {
+    match target.triple().default_calling_convention() {
+        Ok(CallingConvention::SystemV) => {
+            match isa.create_systemv_cie() {
+                Some(cie) => {
+                    let mut dwarf_frametable = FrameTable::default();
+                    let cie_id = dwarf_frametable.add_cie(cie);
+                    Some((Arc::new(Mutex::new(dwarf_frametable)), cie_id))
+                }
+                // Even though we are in a SystemV system, Cranelift doesn't support it
+                None => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(not(feature = "unwind"))]
+fn dwarf_frametable(_isa: &dyn TargetIsa, _target: &Target) -> DwarfFrameTable {
+    None
+}
+
+/// Write the DWARF frame table into an `eh_frame` custom section.
+#[cfg(feature = "unwind")]
+fn eh_frame_section(
+    target: &Target,
+    dwarf_frametable: DwarfFrameTable,
+) -> (PrimaryMap<SectionIndex, CustomSection>, Option<Dwarf>) {
+    let mut custom_sections = PrimaryMap::new();
+    let dwarf = if let Some((dwarf_frametable, _cie_id)) = dwarf_frametable {
+        let mut eh_frame = EhFrame(WriterRelocate::new(target.triple().endianness().ok()));
+        dwarf_frametable
+            .lock()
+            .unwrap()
+            .write_eh_frame(&mut eh_frame)
+            .unwrap();
+
+        let eh_frame_section = eh_frame.0.into_section();
+        custom_sections.push(eh_frame_section);
+        Some(Dwarf::new(SectionIndex::new(0)))
+    } else {
+        None
+    };
+    (custom_sections, dwarf)
+}
+
+#[cfg(not(feature = "unwind"))]
+fn eh_frame_section(
+    _target: &Target,
+    _dwarf_frametable: DwarfFrameTable,
+) -> (PrimaryMap<SectionIndex, CustomSection>, Option<Dwarf>) {
+    (PrimaryMap::new(), None)
 }
@@ -9,15 +9,20 @@ use cranelift_codegen::ir;
 use cranelift_codegen::ir::condcodes::*;
 use cranelift_codegen::ir::immediates::{Offset32, Uimm64};
 use cranelift_codegen::ir::types::*;
-use cranelift_codegen::ir::{AbiParam, ArgumentPurpose, Function, InstBuilder, Signature};
+use cranelift_codegen::ir::{
+    AbiParam, ArgumentPurpose, Function, InstBuilder, Signature, StackSlotData, StackSlotKind,
+};
 use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_frontend::FunctionBuilder;
+use std::cmp;
 use std::convert::TryFrom;
+use std::mem;
 use wasmer_compiler::wasmparser::Type;
 use wasmer_compiler::{WasmError, WasmResult};
 use wasmer_types::entity::EntityRef;
 use wasmer_types::entity::PrimaryMap;
-use wasmer_types::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex};
+use wasmer_types::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex, TagIndex};
+use wasmer_vm::libcalls::AtomicRmwOp;
 use wasmer_vm::VMBuiltinFunctionIndex;
 use wasmer_vm::VMOffsets;
 use wasmer_vm::{MemoryStyle, ModuleInfo, TableStyle};
@@ -27,11 +32,6 @@ pub fn get_function_name(func_index: FunctionIndex) -> ir::ExternalName {
     ir::ExternalName::user(0, func_index.as_u32())
 }
 
-/// The type of the `current_length` field.
-pub fn type_of_vmmemory_definition_current_length(vmoffsets: &VMOffsets) -> ir::Type {
-    ir::Type::int(u16::from(vmoffsets.size_of_vmmemory_definition_current_length()) * 8).unwrap()
-}
-
 /// The type of the `current_elements` field.
 pub fn type_of_vmtable_definition_current_elements(vmoffsets: &VMOffsets) -> ir::Type {
     ir::Type::int(u16::from(vmoffsets.size_of_vmtable_definition_current_elements()) * 8).unwrap()
@@ -59,6 +59,14 @@ pub struct FuncEnvironment<'module_environment> {
     /// for locally-defined memories.
     memory_grow_sig: Option<ir::SigRef>,
 
+    /// The external function signature for implementing wasm's `memory.size`
+    /// for 64-bit memories.
+    memory64_size_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `memory.grow`
+    /// for 64-bit memories.
+    memory64_grow_sig: Option<ir::SigRef>,
+
     /// The external function signature for implementing wasm's `table.copy`
     /// (it's the same for both local and imported tables).
     table_copy_sig: Option<ir::SigRef>,
@@ -69,10 +77,29 @@ pub struct FuncEnvironment<'module_environment> {
     /// The external function signature for implementing wasm's `elem.drop`.
     elem_drop_sig: Option<ir::SigRef>,
 
+    /// The external function signature for implementing wasm's `table.get`.
+    table_get_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `table.set`.
+    table_set_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `table.grow`.
+    table_grow_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `table.fill`.
+    table_fill_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `ref.func`.
+    func_ref_sig: Option<ir::SigRef>,
+
     /// The external function signature for implementing wasm's `memory.copy`
     /// (it's the same for both local and imported memories).
     memory_copy_sig: Option<ir::SigRef>,
 
+    /// The external function signature for implementing wasm's `memory.copy`
+    /// between two different memories.
+    memory_copy_between_sig: Option<ir::SigRef>,
+
     /// The external function signature for implementing wasm's `memory.fill`
     /// (it's the same for both local and imported memories).
     memory_fill_sig: Option<ir::SigRef>,
@@ -80,9 +107,83 @@ pub struct FuncEnvironment<'module_environment> {
     /// The external function signature for implementing wasm's `memory.init`.
     memory_init_sig: Option<ir::SigRef>,
 
+    /// The external function signature for implementing wasm's `memory.copy`
+    /// for 64-bit memories.
+    memory64_copy_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `memory.copy`
+    /// between two different memories, one of which is a 64-bit memory.
+    memory64_copy_between_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `memory.fill`
+    /// for 64-bit memories.
+    memory64_fill_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `memory.init`
+    /// for 64-bit memories.
+    memory64_init_sig: Option<ir::SigRef>,
+
     /// The external function signature for implementing wasm's `data.drop`.
     data_drop_sig: Option<ir::SigRef>,
 
+    /// The external function signature for implementing wasm's
+    /// `memory.atomic.wait32` (it's the same for both local and imported
+    /// memories).
+    memory_atomic_wait32_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's
+    /// `memory.atomic.wait64` (it's the same for both local and imported
+    /// memories).
+    memory_atomic_wait64_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's
+    /// `memory.atomic.notify` (it's the same for both local and imported
+    /// memories).
+    memory_atomic_notify_sig: Option<ir::SigRef>,
+
+    /// The external function signature for calls made inside a `try` block.
+    try_call_sig: Option<ir::SigRef>,
+
+    /// The external function signature for creating the exception of
+    /// wasm's `throw`.
+    exception_new_sig: Option<ir::SigRef>,
+
+    /// The external function signature for matching an exception against
+    /// the tag of wasm's `catch`.
+    exception_matches_sig: Option<ir::SigRef>,
+
+    /// The external function signature for taking the values carried by
+    /// an exception.
+    exception_take_payload_sig: Option<ir::SigRef>,
+
+    /// The external function signature for throwing an exception out of
+    /// a function.
+    exception_throw_sig: Option<ir::SigRef>,
+
+    /// The external function signature for freeing an exception that
+    /// isn't thrown again.
+    exception_delete_sig: Option<ir::SigRef>,
+
+    /// Whether the target lowers the atomic memory accesses, or they are
+    /// implemented by builtin functions.
+    native_atomics: bool,
+
+    /// The external function signature for the atomic loads, on the
+    /// targets which can't lower them.
+    atomic_load_sig: Option<ir::SigRef>,
+
+    /// The external function signature for the atomic stores, on the
+    /// targets which can't lower them.
+    atomic_store_sig: Option<ir::SigRef>,
+
+    /// The external function signature for the atomic read-modify-write
+    /// operations, on the targets which can't lower them.
+    atomic_rmw_sig: Option<ir::SigRef>,
+
+    /// The external function signature for the atomic compare-and-exchange
+    /// operations, on the targets which can't lower them.
+    atomic_cmpxchg_sig: Option<ir::SigRef>,
+
     /// Offsets to struct fields accessed by JIT code.
     offsets: VMOffsets,
 
@@ -96,6 +197,7 @@ pub struct FuncEnvironment<'module_environment> {
 impl<'module_environment> FuncEnvironment<'module_environment> {
     pub fn new(
         target_config: TargetFrontendConfig,
+        native_atomics: bool,
         module: &'module_environment ModuleInfo,
         signatures: &'module_environment PrimaryMap<SignatureIndex, ir::Signature>,
         memory_styles: &'module_environment PrimaryMap<MemoryIndex, MemoryStyle>,
@@ -108,13 +210,39 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             vmctx: None,
             memory32_size_sig: None,
             memory_grow_sig: None,
+            memory64_size_sig: None,
+            memory64_grow_sig: None,
             table_copy_sig: None,
             table_init_sig: None,
             elem_drop_sig: None,
+            table_get_sig: None,
+            table_set_sig: None,
+            table_grow_sig: None,
+            table_fill_sig: None,
+            func_ref_sig: None,
             memory_copy_sig: None,
+            memory_copy_between_sig: None,
             memory_fill_sig: None,
             memory_init_sig: None,
+            memory64_copy_sig: None,
+            memory64_copy_between_sig: None,
+            memory64_fill_sig: None,
+            memory64_init_sig: None,
             data_drop_sig: None,
+            memory_atomic_wait32_sig: None,
+            memory_atomic_wait64_sig: None,
+            memory_atomic_notify_sig: None,
+            try_call_sig: None,
+            exception_new_sig: None,
+            exception_matches_sig: None,
+            exception_take_payload_sig: None,
+            exception_throw_sig: None,
+            exception_delete_sig: None,
+            native_atomics,
+            atomic_load_sig: None,
+            atomic_store_sig: None,
+            atomic_rmw_sig: None,
+            atomic_cmpxchg_sig: None,
             offsets: VMOffsets::new(target_config.pointer_bytes(), module),
             memory_styles,
             table_styles,
@@ -125,6 +253,19 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         self.target_config.pointer_type()
     }
 
+    fn is_memory64(&self, index: MemoryIndex) -> bool {
+        self.module.memories[index].memory64
+    }
+
+    /// The type of the addresses into the memory `index`.
+    fn memory_index_type(&self, index: MemoryIndex) -> ir::Type {
+        if self.is_memory64(index) {
+            I64
+        } else {
+            I32
+        }
+    }
+
     fn vmctx(&mut self, func: &mut Function) -> ir::GlobalValue {
         self.vmctx.unwrap_or_else(|| {
             let vmctx = func.create_global_value(ir::GlobalValueData::VMContext);
@@ -149,6 +290,22 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         sig
     }
 
+    fn get_memory64_grow_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.memory64_grow_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    AbiParam::new(I64),
+                    AbiParam::new(I32),
+                ],
+                returns: vec![AbiParam::new(I64)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.memory64_grow_sig = Some(sig);
+        sig
+    }
+
     /// Return the memory.grow function signature to call for the given index, along with the
     /// translated index value to pass to it and its index in `VMBuiltinFunctionsArray`.
     fn get_memory_grow_func(
@@ -156,7 +313,21 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         func: &mut Function,
         index: MemoryIndex,
     ) -> (ir::SigRef, usize, VMBuiltinFunctionIndex) {
-        if self.module.is_imported_memory(index) {
+        if self.is_memory64(index) {
+            if self.module.is_imported_memory(index) {
+                (
+                    self.get_memory64_grow_sig(func),
+                    index.index(),
+                    VMBuiltinFunctionIndex::get_imported_memory64_grow_index(),
+                )
+            } else {
+                (
+                    self.get_memory64_grow_sig(func),
+                    self.module.local_memory_index(index).unwrap().index(),
+                    VMBuiltinFunctionIndex::get_memory64_grow_index(),
+                )
+            }
+        } else if self.module.is_imported_memory(index) {
             (
                 self.get_memory_grow_sig(func),
                 index.index(),
@@ -186,6 +357,21 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         sig
     }
 
+    fn get_memory64_size_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.memory64_size_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    AbiParam::new(I32),
+                ],
+                returns: vec![AbiParam::new(I64)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.memory64_size_sig = Some(sig);
+        sig
+    }
+
     /// Return the memory.size function signature to call for the given index, along with the
     /// translated index value to pass to it and its index in `VMBuiltinFunctionsArray`.
     fn get_memory_size_func(
@@ -193,7 +379,21 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         func: &mut Function,
         index: MemoryIndex,
     ) -> (ir::SigRef, usize, VMBuiltinFunctionIndex) {
-        if self.module.is_imported_memory(index) {
+        if self.is_memory64(index) {
+            if self.module.is_imported_memory(index) {
+                (
+                    self.get_memory64_size_sig(func),
+                    index.index(),
+                    VMBuiltinFunctionIndex::get_imported_memory64_size_index(),
+                )
+            } else {
+                (
+                    self.get_memory64_size_sig(func),
+                    self.module.local_memory_index(index).unwrap().index(),
+                    VMBuiltinFunctionIndex::get_memory64_size_index(),
+                )
+            }
+        } else if self.module.is_imported_memory(index) {
             (
                 self.get_memory32_size_sig(func),
                 index.index(),
@@ -306,25 +506,133 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         (sig, VMBuiltinFunctionIndex::get_elem_drop_index())
     }
 
-    fn get_memory_copy_sig(&mut self, func: &mut Function) -> ir::SigRef {
-        let sig = self.memory_copy_sig.unwrap_or_else(|| {
+    fn get_table_get_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.table_get_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Table index.
+                    AbiParam::new(I32),
+                    // Index within the table.
+                    AbiParam::new(I32),
+                ],
+                // Element.
+                returns: vec![AbiParam::new(self.pointer_type())],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.table_get_sig = Some(sig);
+        sig
+    }
+
+    fn get_table_set_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.table_set_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Table index.
+                    AbiParam::new(I32),
+                    // Index within the table.
+                    AbiParam::new(I32),
+                    // Element.
+                    AbiParam::new(self.pointer_type()),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.table_set_sig = Some(sig);
+        sig
+    }
+
+    fn get_table_grow_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.table_grow_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Table index.
+                    AbiParam::new(I32),
+                    // Initial value of the new elements.
+                    AbiParam::new(self.pointer_type()),
+                    // Number of elements to add.
+                    AbiParam::new(I32),
+                ],
+                // Previous size, or -1.
+                returns: vec![AbiParam::new(I32)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.table_grow_sig = Some(sig);
+        sig
+    }
+
+    fn get_table_fill_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.table_fill_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Table index.
+                    AbiParam::new(I32),
+                    // Destination index within the table.
+                    AbiParam::new(I32),
+                    // Element.
+                    AbiParam::new(self.pointer_type()),
+                    // Number of elements to fill.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.table_fill_sig = Some(sig);
+        sig
+    }
+
+    fn get_func_ref_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.func_ref_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Function index.
+                    AbiParam::new(I32),
+                ],
+                // Function reference.
+                returns: vec![AbiParam::new(self.pointer_type())],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.func_ref_sig = Some(sig);
+        sig
+    }
+
+    fn get_memory_copy_sig(&mut self, func: &mut Function, ty: ir::Type) -> ir::SigRef {
+        let cached = if ty == I64 {
+            self.memory64_copy_sig
+        } else {
+            self.memory_copy_sig
+        };
+        let sig = cached.unwrap_or_else(|| {
             func.import_signature(Signature {
                 params: vec![
                     AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
                     // Memory index.
                     AbiParam::new(I32),
                     // Destination address.
-                    AbiParam::new(I32),
+                    AbiParam::new(ty),
                     // Source address.
-                    AbiParam::new(I32),
+                    AbiParam::new(ty),
                     // Length.
-                    AbiParam::new(I32),
+                    AbiParam::new(ty),
                 ],
                 returns: vec![],
                 call_conv: self.target_config.default_call_conv,
             })
         });
-        self.memory_copy_sig = Some(sig);
+        if ty == I64 {
+            self.memory64_copy_sig = Some(sig);
+        } else {
+            self.memory_copy_sig = Some(sig);
+        }
         sig
     }
 
@@ -333,67 +641,573 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         func: &mut Function,
         memory_index: MemoryIndex,
     ) -> (ir::SigRef, usize, VMBuiltinFunctionIndex) {
-        let sig = self.get_memory_copy_sig(func);
+        let ty = self.memory_index_type(memory_index);
+        let sig = self.get_memory_copy_sig(func, ty);
         if let Some(local_memory_index) = self.module.local_memory_index(memory_index) {
             (
                 sig,
                 local_memory_index.index(),
-                VMBuiltinFunctionIndex::get_local_memory_copy_index(),
+                if ty == I64 {
+                    VMBuiltinFunctionIndex::get_local_memory64_copy_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_local_memory_copy_index()
+                },
             )
         } else {
             (
                 sig,
                 memory_index.index(),
-                VMBuiltinFunctionIndex::get_imported_memory_copy_index(),
+                if ty == I64 {
+                    VMBuiltinFunctionIndex::get_imported_memory64_copy_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_imported_memory_copy_index()
+                },
             )
         }
     }
 
-    fn get_memory_fill_sig(&mut self, func: &mut Function) -> ir::SigRef {
-        let sig = self.memory_fill_sig.unwrap_or_else(|| {
+    fn get_memory_copy_between_sig(&mut self, func: &mut Function, ty: ir::Type) -> ir::SigRef {
+        let cached = if ty == I64 {
+            self.memory64_copy_between_sig
+        } else {
+            self.memory_copy_between_sig
+        };
+        let sig = cached.unwrap_or_else(|| {
             func.import_signature(Signature {
                 params: vec![
                     AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
-                    // Memory index.
+                    // Destination memory index.
+                    AbiParam::new(I32),
+                    // Source memory index.
                     AbiParam::new(I32),
                     // Destination address.
+                    AbiParam::new(ty),
+                    // Source address.
+                    AbiParam::new(ty),
+                    // Length.
+                    AbiParam::new(ty),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        if ty == I64 {
+            self.memory64_copy_between_sig = Some(sig);
+        } else {
+            self.memory_copy_between_sig = Some(sig);
+        }
+        sig
+    }
+
+    fn get_memory_fill_sig(&mut self, func: &mut Function, ty: ir::Type) -> ir::SigRef {
+        let cached = if ty == I64 {
+            self.memory64_fill_sig
+        } else {
+            self.memory_fill_sig
+        };
+        let sig = cached.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Memory index.
                     AbiParam::new(I32),
+                    // Destination address.
+                    AbiParam::new(ty),
                     // Value.
                     AbiParam::new(I32),
                     // Length.
+                    AbiParam::new(ty),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        if ty == I64 {
+            self.memory64_fill_sig = Some(sig);
+        } else {
+            self.memory_fill_sig = Some(sig);
+        }
+        sig
+    }
+
+    fn get_memory_fill_func(
+        &mut self,
+        func: &mut Function,
+        memory_index: MemoryIndex,
+    ) -> (ir::SigRef, usize, VMBuiltinFunctionIndex) {
+        let ty = self.memory_index_type(memory_index);
+        let sig = self.get_memory_fill_sig(func, ty);
+        if let Some(local_memory_index) = self.module.local_memory_index(memory_index) {
+            (
+                sig,
+                local_memory_index.index(),
+                if ty == I64 {
+                    VMBuiltinFunctionIndex::get_memory64_fill_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_memory_fill_index()
+                },
+            )
+        } else {
+            (
+                sig,
+                memory_index.index(),
+                if ty == I64 {
+                    VMBuiltinFunctionIndex::get_imported_memory64_fill_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_imported_memory_fill_index()
+                },
+            )
+        }
+    }
+
+    fn get_memory_atomic_wait_sig(&mut self, func: &mut Function, ty: ir::Type) -> ir::SigRef {
+        let cached = if ty == I64 {
+            self.memory_atomic_wait64_sig
+        } else {
+            self.memory_atomic_wait32_sig
+        };
+        let sig = cached.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Memory index.
+                    AbiParam::new(I32),
+                    // Effective address.
+                    AbiParam::new(I64),
+                    // Expected value.
+                    AbiParam::new(ty),
+                    // Timeout.
+                    AbiParam::new(I64),
+                ],
+                returns: vec![AbiParam::new(I32)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        if ty == I64 {
+            self.memory_atomic_wait64_sig = Some(sig);
+        } else {
+            self.memory_atomic_wait32_sig = Some(sig);
+        }
+        sig
+    }
+
+    /// Returns the signature, memory index argument and builtin function
+    /// for a `memory.atomic.wait` whose expected value is of type `ty`.
+    fn get_memory_atomic_wait_func(
+        &mut self,
+        func: &mut Function,
+        memory_index: MemoryIndex,
+        ty: ir::Type,
+    ) -> (ir::SigRef, usize, VMBuiltinFunctionIndex) {
+        let sig = self.get_memory_atomic_wait_sig(func, ty);
+        if let Some(local_memory_index) = self.module.local_memory_index(memory_index) {
+            (
+                sig,
+                local_memory_index.index(),
+                if ty == I64 {
+                    VMBuiltinFunctionIndex::get_memory_atomic_wait64_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_memory_atomic_wait32_index()
+                },
+            )
+        } else {
+            (
+                sig,
+                memory_index.index(),
+                if ty == I64 {
+                    VMBuiltinFunctionIndex::get_imported_memory_atomic_wait64_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_imported_memory_atomic_wait32_index()
+                },
+            )
+        }
+    }
+
+    fn get_memory_atomic_notify_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.memory_atomic_notify_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Memory index.
+                    AbiParam::new(I32),
+                    // Effective address.
+                    AbiParam::new(I64),
+                    // Count.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![AbiParam::new(I32)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.memory_atomic_notify_sig = Some(sig);
+        sig
+    }
+
+    fn get_memory_atomic_notify_func(
+        &mut self,
+        func: &mut Function,
+        memory_index: MemoryIndex,
+    ) -> (ir::SigRef, usize, VMBuiltinFunctionIndex) {
+        let sig = self.get_memory_atomic_notify_sig(func);
+        if let Some(local_memory_index) = self.module.local_memory_index(memory_index) {
+            (
+                sig,
+                local_memory_index.index(),
+                VMBuiltinFunctionIndex::get_memory_atomic_notify_index(),
+            )
+        } else {
+            (
+                sig,
+                memory_index.index(),
+                VMBuiltinFunctionIndex::get_imported_memory_atomic_notify_index(),
+            )
+        }
+    }
+
+    fn get_try_call_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.try_call_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Signature index.
+                    AbiParam::new(I32),
+                    // Callee address.
+                    AbiParam::new(self.pointer_type()),
+                    // Callee vmctx.
+                    AbiParam::new(self.pointer_type()),
+                    // Values vec.
+                    AbiParam::new(self.pointer_type()),
+                ],
+                // Exception.
+                returns: vec![AbiParam::new(self.pointer_type())],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.try_call_sig = Some(sig);
+        sig
+    }
+
+    fn get_exception_new_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.exception_new_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Tag index.
+                    AbiParam::new(I32),
+                    // Values.
+                    AbiParam::new(self.pointer_type()),
+                ],
+                // Exception.
+                returns: vec![AbiParam::new(self.pointer_type())],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.exception_new_sig = Some(sig);
+        sig
+    }
+
+    fn get_exception_matches_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.exception_matches_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Exception.
+                    AbiParam::new(self.pointer_type()),
+                    // Tag index.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![AbiParam::new(I32)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.exception_matches_sig = Some(sig);
+        sig
+    }
+
+    fn get_exception_take_payload_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.exception_take_payload_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    // Exception.
+                    AbiParam::new(self.pointer_type()),
+                    // Values.
+                    AbiParam::new(self.pointer_type()),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.exception_take_payload_sig = Some(sig);
+        sig
+    }
+
+    fn get_atomic_load_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.atomic_load_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    // Address.
+                    AbiParam::new(self.pointer_type()),
+                    // Size.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![AbiParam::new(I64)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.atomic_load_sig = Some(sig);
+        sig
+    }
+
+    fn get_atomic_store_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.atomic_store_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    // Address.
+                    AbiParam::new(self.pointer_type()),
+                    // Size.
                     AbiParam::new(I32),
+                    // Value.
+                    AbiParam::new(I64),
                 ],
                 returns: vec![],
                 call_conv: self.target_config.default_call_conv,
             })
         });
-        self.memory_fill_sig = Some(sig);
+        self.atomic_store_sig = Some(sig);
+        sig
+    }
+
+    fn get_atomic_rmw_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.atomic_rmw_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    // Address.
+                    AbiParam::new(self.pointer_type()),
+                    // Size.
+                    AbiParam::new(I32),
+                    // Operation.
+                    AbiParam::new(I32),
+                    // Value.
+                    AbiParam::new(I64),
+                ],
+                returns: vec![AbiParam::new(I64)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.atomic_rmw_sig = Some(sig);
+        sig
+    }
+
+    fn get_atomic_cmpxchg_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.atomic_cmpxchg_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    // Address.
+                    AbiParam::new(self.pointer_type()),
+                    // Size.
+                    AbiParam::new(I32),
+                    // Expected.
+                    AbiParam::new(I64),
+                    // Replacement.
+                    AbiParam::new(I64),
+                ],
+                returns: vec![AbiParam::new(I64)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.atomic_cmpxchg_sig = Some(sig);
         sig
     }
 
-    fn get_memory_fill_func(
-        &mut self,
-        func: &mut Function,
-        memory_index: MemoryIndex,
-    ) -> (ir::SigRef, usize, VMBuiltinFunctionIndex) {
-        let sig = self.get_memory_fill_sig(func);
-        if let Some(local_memory_index) = self.module.local_memory_index(memory_index) {
-            (
-                sig,
-                local_memory_index.index(),
-                VMBuiltinFunctionIndex::get_memory_fill_index(),
-            )
-        } else {
-            (
-                sig,
-                memory_index.index(),
-                VMBuiltinFunctionIndex::get_imported_memory_fill_index(),
-            )
+    /// Zero-extends `value` to the `I64` operand of the atomic builtin
+    /// functions.
+    fn atomic_operand(pos: &mut FuncCursor, value: ir::Value) -> ir::Value {
+        if pos.func.dfg.value_type(value) == I64 {
+            value
+        } else {
+            pos.ins().uextend(I64, value)
+        }
+    }
+
+    /// Truncates the `I64` result of an atomic builtin function to
+    /// `access_ty`.
+    fn atomic_result(pos: &mut FuncCursor, access_ty: ir::Type, call: ir::Inst) -> ir::Value {
+        let result = pos.func.dfg.first_result(call);
+        if access_ty == I64 {
+            result
+        } else {
+            pos.ins().ireduce(access_ty, result)
+        }
+    }
+
+    fn get_exception_throw_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.exception_throw_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    // Exception.
+                    AbiParam::new(self.pointer_type()),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.exception_throw_sig = Some(sig);
+        sig
+    }
+
+    fn get_exception_delete_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.exception_delete_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    // Exception.
+                    AbiParam::new(self.pointer_type()),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.exception_delete_sig = Some(sig);
+        sig
+    }
+
+    /// Create a stack slot for `count` raw values, in the layout of the
+    /// `values_vec` of the function call trampolines, and return its
+    /// address.
+    fn make_values_vec(&mut self, pos: &mut FuncCursor<'_>, count: usize) -> ir::Value {
+        let value_size = mem::size_of::<u128>();
+        let slot = pos.func.create_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            u32::try_from(count * value_size).unwrap(),
+        ));
+        pos.ins().stack_addr(self.pointer_type(), slot, 0)
+    }
+
+    /// Call `func_addr` with `callee_vmctx` and `call_args` through the
+    /// `try_call` builtin, which catches the exceptions it throws.
+    ///
+    /// Returns the caught exception and the return values.
+    fn translate_try_call_address(
+        &mut self,
+        pos: &mut FuncCursor<'_>,
+        sig_index: SignatureIndex,
+        func_addr: ir::Value,
+        callee_vmctx: ir::Value,
+        call_args: &[ir::Value],
+    ) -> (ir::Value, Vec<ir::Value>) {
+        let value_size = mem::size_of::<u128>();
+        let return_types = self.signatures[sig_index]
+            .returns
+            .iter()
+            .map(|ret| ret.value_type)
+            .collect::<Vec<_>>();
+        let values_vec = self.make_values_vec(pos, cmp::max(call_args.len(), return_types.len()));
+
+        let mem_flags = ir::MemFlags::trusted();
+        for (i, arg) in call_args.iter().enumerate() {
+            pos.ins()
+                .store(mem_flags, *arg, values_vec, (i * value_size) as i32);
+        }
+
+        let func_sig = self.get_try_call_sig(&mut pos.func);
+        let sig_index_arg = pos.ins().iconst(I32, sig_index.as_u32() as i64);
+        let (vmctx, try_call_addr) = self.translate_load_builtin_function_address(
+            pos,
+            VMBuiltinFunctionIndex::get_try_call_index(),
+        );
+        let call_inst = pos.ins().call_indirect(
+            func_sig,
+            try_call_addr,
+            &[vmctx, sig_index_arg, func_addr, callee_vmctx, values_vec],
+        );
+        let exception = *pos.func.dfg.inst_results(call_inst).first().unwrap();
+
+        let results = return_types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                pos.ins()
+                    .load(*ty, mem_flags, values_vec, (i * value_size) as i32)
+            })
+            .collect();
+        (exception, results)
+    }
+
+    /// Load the function at index `callee` of `table`, checking that its
+    /// signature is `sig_index`.
+    ///
+    /// Returns the function address and its vmctx.
+    fn load_indirect_callee(
+        &mut self,
+        pos: &mut FuncCursor<'_>,
+        table_index: TableIndex,
+        table: ir::Table,
+        sig_index: SignatureIndex,
+        callee: ir::Value,
+    ) -> (ir::Value, ir::Value) {
+        let pointer_type = self.pointer_type();
+
+        let table_entry_addr = pos.ins().table_addr(pointer_type, table, callee, 0);
+
+        // Dereference table_entry_addr to get the function address.
+        let mem_flags = ir::MemFlags::trusted();
+        let func_addr = pos.ins().load(
+            pointer_type,
+            mem_flags,
+            table_entry_addr,
+            i32::from(self.offsets.vmcaller_checked_anyfunc_func_ptr()),
+        );
+
+        // Check whether `func_addr` is null.
+        pos.ins().trapz(func_addr, ir::TrapCode::IndirectCallToNull);
+
+        // If necessary, check the signature.
+        match self.table_styles[table_index] {
+            TableStyle::CallerChecksSignature => {
+                let sig_id_size = self.offsets.size_of_vmshared_signature_index();
+                let sig_id_type = ir::Type::int(u16::from(sig_id_size) * 8).unwrap();
+                let vmctx = self.vmctx(pos.func);
+                let base = pos.ins().global_value(pointer_type, vmctx);
+                let offset =
+                    i32::try_from(self.offsets.vmctx_vmshared_signature_id(sig_index)).unwrap();
+
+                // Load the caller ID.
+                let mut mem_flags = ir::MemFlags::trusted();
+                mem_flags.set_readonly();
+                let caller_sig_id = pos.ins().load(sig_id_type, mem_flags, base, offset);
+
+                // Load the callee ID.
+                let mem_flags = ir::MemFlags::trusted();
+                let callee_sig_id = pos.ins().load(
+                    sig_id_type,
+                    mem_flags,
+                    table_entry_addr,
+                    i32::from(self.offsets.vmcaller_checked_anyfunc_type_index()),
+                );
+
+                // Check that they match.
+                let cmp = pos.ins().icmp(IntCC::Equal, callee_sig_id, caller_sig_id);
+                pos.ins().trapz(cmp, ir::TrapCode::BadSignature);
+            }
         }
+
+        // Load the callee vmctx address.
+        let vmctx = pos.ins().load(
+            pointer_type,
+            mem_flags,
+            table_entry_addr,
+            i32::from(self.offsets.vmcaller_checked_anyfunc_vmctx()),
+        );
+
+        (func_addr, vmctx)
     }
 
-    fn get_memory_init_sig(&mut self, func: &mut Function) -> ir::SigRef {
-        let sig = self.memory_init_sig.unwrap_or_else(|| {
+    fn get_memory_init_sig(&mut self, func: &mut Function, ty: ir::Type) -> ir::SigRef {
+        let cached = if ty == I64 {
+            self.memory64_init_sig
+        } else {
+            self.memory_init_sig
+        };
+        let sig = cached.unwrap_or_else(|| {
             func.import_signature(Signature {
                 params: vec![
                     AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
@@ -402,7 +1216,7 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
                     // Data index.
                     AbiParam::new(I32),
                     // Destination address.
-                    AbiParam::new(I32),
+                    AbiParam::new(ty),
                     // Source index within the data segment.
                     AbiParam::new(I32),
                     // Length.
@@ -412,16 +1226,29 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
                 call_conv: self.target_config.default_call_conv,
             })
         });
-        self.memory_init_sig = Some(sig);
+        if ty == I64 {
+            self.memory64_init_sig = Some(sig);
+        } else {
+            self.memory_init_sig = Some(sig);
+        }
         sig
     }
 
     fn get_memory_init_func(
         &mut self,
         func: &mut Function,
+        memory_index: MemoryIndex,
     ) -> (ir::SigRef, VMBuiltinFunctionIndex) {
-        let sig = self.get_memory_init_sig(func);
-        (sig, VMBuiltinFunctionIndex::get_memory_init_index())
+        let ty = self.memory_index_type(memory_index);
+        let sig = self.get_memory_init_sig(func, ty);
+        (
+            sig,
+            if ty == I64 {
+                VMBuiltinFunctionIndex::get_memory64_init_index()
+            } else {
+                VMBuiltinFunctionIndex::get_memory_init_index()
+            },
+        )
     }
 
     fn get_data_drop_sig(&mut self, func: &mut Function) -> ir::SigRef {
@@ -540,53 +1367,86 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
 
     fn translate_table_grow(
         &mut self,
-        mut _pos: cranelift_codegen::cursor::FuncCursor<'_>,
-        _table_index: TableIndex,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        table_index: TableIndex,
         _table: ir::Table,
-        _delta: ir::Value,
-        _init_value: ir::Value,
+        delta: ir::Value,
+        init_value: ir::Value,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "the `table.grow` instruction is not supported yet".into(),
-        ))
+        let func_sig = self.get_table_grow_sig(&mut pos.func);
+        let table_index_arg = pos.ins().iconst(I32, table_index.as_u32() as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_table_grow_index(),
+        );
+        let call_inst = pos.ins().call_indirect(
+            func_sig,
+            func_addr,
+            &[vmctx, table_index_arg, init_value, delta],
+        );
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
     fn translate_table_get(
         &mut self,
-        _builder: &mut FunctionBuilder,
-        _table_index: TableIndex,
+        builder: &mut FunctionBuilder,
+        table_index: TableIndex,
         _table: ir::Table,
-        _index: ir::Value,
+        index: ir::Value,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "the `table.get` instruction is not supported yet".into(),
-        ))
+        let mut pos = builder.cursor();
+        let func_sig = self.get_table_get_sig(&mut pos.func);
+        let table_index_arg = pos.ins().iconst(I32, table_index.as_u32() as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_table_get_index(),
+        );
+        let call_inst =
+            pos.ins()
+                .call_indirect(func_sig, func_addr, &[vmctx, table_index_arg, index]);
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
     fn translate_table_set(
         &mut self,
-        _builder: &mut FunctionBuilder,
-        _table_index: TableIndex,
+        builder: &mut FunctionBuilder,
+        table_index: TableIndex,
         _table: ir::Table,
-        _value: ir::Value,
-        _index: ir::Value,
+        value: ir::Value,
+        index: ir::Value,
     ) -> WasmResult<()> {
-        Err(WasmError::Unsupported(
-            "the `table.set` instruction is not supported yet".into(),
-        ))
+        let mut pos = builder.cursor();
+        let func_sig = self.get_table_set_sig(&mut pos.func);
+        let table_index_arg = pos.ins().iconst(I32, table_index.as_u32() as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_table_set_index(),
+        );
+        pos.ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, table_index_arg, index, value]);
+        Ok(())
     }
 
     fn translate_table_fill(
         &mut self,
-        mut _pos: cranelift_codegen::cursor::FuncCursor<'_>,
-        _table_index: TableIndex,
-        _dst: ir::Value,
-        _val: ir::Value,
-        _len: ir::Value,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        table_index: TableIndex,
+        dst: ir::Value,
+        val: ir::Value,
+        len: ir::Value,
     ) -> WasmResult<()> {
-        Err(WasmError::Unsupported(
-            "the `table.fill` instruction is not supported yet".into(),
-        ))
+        let func_sig = self.get_table_fill_sig(&mut pos.func);
+        let table_index_arg = pos.ins().iconst(I32, table_index.as_u32() as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_table_fill_index(),
+        );
+        pos.ins().call_indirect(
+            func_sig,
+            func_addr,
+            &[vmctx, table_index_arg, dst, val, len],
+        );
+        Ok(())
     }
 
     fn translate_ref_null(
@@ -595,11 +1455,10 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         ty: Type,
     ) -> WasmResult<ir::Value> {
         Ok(match ty {
-            Type::FuncRef => pos.ins().iconst(self.pointer_type(), 0),
-            // Type::ExternRef => pos.ins().null(self.reference_type(ty)),
+            Type::FuncRef | Type::ExternRef => pos.ins().iconst(self.pointer_type(), 0),
             _ => {
                 return Err(WasmError::Unsupported(
-                    "`ref.null T` that is not a `funcref`".into(),
+                    "`ref.null T` that is not a reference type".into(),
                 ));
             }
         })
@@ -610,28 +1469,28 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         mut pos: cranelift_codegen::cursor::FuncCursor,
         value: ir::Value,
     ) -> WasmResult<ir::Value> {
-        let bool_is_null = match pos.func.dfg.value_type(value) {
-            // `externref`
-            ty if ty.is_ref() => pos.ins().is_null(value),
-            // `funcref`
-            ty if ty == self.pointer_type() => {
-                pos.ins()
-                    .icmp_imm(cranelift_codegen::ir::condcodes::IntCC::Equal, value, 0)
-            }
-            _ => unreachable!(),
-        };
+        let bool_is_null =
+            pos.ins()
+                .icmp_imm(cranelift_codegen::ir::condcodes::IntCC::Equal, value, 0);
 
         Ok(pos.ins().bint(ir::types::I32, bool_is_null))
     }
 
     fn translate_ref_func(
         &mut self,
-        mut _pos: cranelift_codegen::cursor::FuncCursor<'_>,
-        _func_index: FunctionIndex,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        func_index: FunctionIndex,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "the `ref.func` instruction is not supported yet".into(),
-        ))
+        let func_sig = self.get_func_ref_sig(&mut pos.func);
+        let func_index_arg = pos.ins().iconst(I32, func_index.as_u32() as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_func_ref_index(),
+        );
+        let call_inst = pos
+            .ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, func_index_arg]);
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
     fn translate_custom_global_get(
@@ -682,12 +1541,16 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
 
         // If we have a declared maximum, we can make this a "static" heap, which is
         // allocated up front and never moved.
+        let index_type = if self.is_memory64(index) { I64 } else { I32 };
         let (offset_guard_size, heap_style, readonly_base) = match self.memory_styles[index] {
             MemoryStyle::Dynamic { offset_guard_size } => {
+                // Cranelift compares the bound in the index type. The low
+                // half of `current_length` is enough for 32-bit memories,
+                // which never reach 4 GiB.
                 let heap_bound = func.create_global_value(ir::GlobalValueData::Load {
                     base: ptr,
                     offset: Offset32::new(current_length_offset),
-                    global_type: type_of_vmmemory_definition_current_length(&self.offsets),
+                    global_type: index_type,
                     readonly: false,
                 });
                 (
@@ -721,7 +1584,7 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
             min_size: 0.into(),
             offset_guard_size,
             style: heap_style,
-            index_type: I32,
+            index_type,
         }))
     }
 
@@ -791,61 +1654,12 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         callee: ir::Value,
         call_args: &[ir::Value],
     ) -> WasmResult<ir::Inst> {
-        let pointer_type = self.pointer_type();
-
-        let table_entry_addr = pos.ins().table_addr(pointer_type, table, callee, 0);
-
-        // Dereference table_entry_addr to get the function address.
-        let mem_flags = ir::MemFlags::trusted();
-        let func_addr = pos.ins().load(
-            pointer_type,
-            mem_flags,
-            table_entry_addr,
-            i32::from(self.offsets.vmcaller_checked_anyfunc_func_ptr()),
-        );
-
-        // Check whether `func_addr` is null.
-        pos.ins().trapz(func_addr, ir::TrapCode::IndirectCallToNull);
-
-        // If necessary, check the signature.
-        match self.table_styles[table_index] {
-            TableStyle::CallerChecksSignature => {
-                let sig_id_size = self.offsets.size_of_vmshared_signature_index();
-                let sig_id_type = ir::Type::int(u16::from(sig_id_size) * 8).unwrap();
-                let vmctx = self.vmctx(pos.func);
-                let base = pos.ins().global_value(pointer_type, vmctx);
-                let offset =
-                    i32::try_from(self.offsets.vmctx_vmshared_signature_id(sig_index)).unwrap();
-
-                // Load the caller ID.
-                let mut mem_flags = ir::MemFlags::trusted();
-                mem_flags.set_readonly();
-                let caller_sig_id = pos.ins().load(sig_id_type, mem_flags, base, offset);
-
-                // Load the callee ID.
-                let mem_flags = ir::MemFlags::trusted();
-                let callee_sig_id = pos.ins().load(
-                    sig_id_type,
-                    mem_flags,
-                    table_entry_addr,
-                    i32::from(self.offsets.vmcaller_checked_anyfunc_type_index()),
-                );
-
-                // Check that they match.
-                let cmp = pos.ins().icmp(IntCC::Equal, callee_sig_id, caller_sig_id);
-                pos.ins().trapz(cmp, ir::TrapCode::BadSignature);
-            }
-        }
+        let (func_addr, vmctx) =
+            self.load_indirect_callee(&mut pos, table_index, table, sig_index, callee);
 
         let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
 
         // First append the callee vmctx address.
-        let vmctx = pos.ins().load(
-            pointer_type,
-            mem_flags,
-            table_entry_addr,
-            i32::from(self.offsets.vmcaller_checked_anyfunc_vmctx()),
-        );
         real_call_args.push(vmctx);
 
         // Then append the regular call arguments.
@@ -854,6 +1668,21 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         Ok(pos.ins().call_indirect(sig_ref, func_addr, &real_call_args))
     }
 
+    fn translate_try_call_indirect(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        table_index: TableIndex,
+        table: ir::Table,
+        sig_index: SignatureIndex,
+        _sig_ref: ir::SigRef,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> WasmResult<(ir::Value, Vec<ir::Value>)> {
+        let (func_addr, vmctx) =
+            self.load_indirect_callee(&mut pos, table_index, table, sig_index, callee);
+        Ok(self.translate_try_call_address(&mut pos, sig_index, func_addr, vmctx, call_args))
+    }
+
     fn translate_call(
         &mut self,
         mut pos: FuncCursor<'_>,
@@ -903,6 +1732,146 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         Ok(pos.ins().call_indirect(sig_ref, func_addr, &real_call_args))
     }
 
+    fn translate_try_call(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        callee_index: FunctionIndex,
+        callee: ir::FuncRef,
+        call_args: &[ir::Value],
+    ) -> WasmResult<(ir::Value, Vec<ir::Value>)> {
+        let pointer_type = self.pointer_type();
+        let sig_index = self.module.functions[callee_index];
+
+        let (func_addr, vmctx) = if !self.module.is_imported_function(callee_index) {
+            // A locally-defined function shares the caller vmctx.
+            let func_addr = pos.ins().func_addr(pointer_type, callee);
+            let caller_vmctx = pos.func.special_param(ArgumentPurpose::VMContext).unwrap();
+            (func_addr, caller_vmctx)
+        } else {
+            let vmctx = self.vmctx(&mut pos.func);
+            let base = pos.ins().global_value(pointer_type, vmctx);
+            let mem_flags = ir::MemFlags::trusted();
+            let body_offset =
+                i32::try_from(self.offsets.vmctx_vmfunction_import_body(callee_index)).unwrap();
+            let func_addr = pos.ins().load(pointer_type, mem_flags, base, body_offset);
+            let vmctx_offset =
+                i32::try_from(self.offsets.vmctx_vmfunction_import_vmctx(callee_index)).unwrap();
+            let vmctx = pos.ins().load(pointer_type, mem_flags, base, vmctx_offset);
+            (func_addr, vmctx)
+        };
+
+        Ok(self.translate_try_call_address(&mut pos, sig_index, func_addr, vmctx, call_args))
+    }
+
+    fn num_tag_params(&self, index: TagIndex) -> usize {
+        self.signatures[self.module.tags[index]].params.len() - 1
+    }
+
+    fn translate_exception_new(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        index: TagIndex,
+        values: &[ir::Value],
+    ) -> WasmResult<ir::Value> {
+        let value_size = mem::size_of::<u128>();
+        let values_vec = self.make_values_vec(&mut pos, values.len());
+        let mem_flags = ir::MemFlags::trusted();
+        for (i, value) in values.iter().enumerate() {
+            pos.ins()
+                .store(mem_flags, *value, values_vec, (i * value_size) as i32);
+        }
+
+        let func_sig = self.get_exception_new_sig(&mut pos.func);
+        let tag_index = pos.ins().iconst(I32, index.as_u32() as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_exception_new_index(),
+        );
+        let call_inst =
+            pos.ins()
+                .call_indirect(func_sig, func_addr, &[vmctx, tag_index, values_vec]);
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
+    }
+
+    fn translate_exception_matches(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        exception: ir::Value,
+        index: TagIndex,
+    ) -> WasmResult<ir::Value> {
+        let func_sig = self.get_exception_matches_sig(&mut pos.func);
+        let tag_index = pos.ins().iconst(I32, index.as_u32() as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_exception_matches_index(),
+        );
+        let call_inst =
+            pos.ins()
+                .call_indirect(func_sig, func_addr, &[vmctx, exception, tag_index]);
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
+    }
+
+    fn translate_exception_take_payload(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        exception: ir::Value,
+        index: TagIndex,
+    ) -> WasmResult<Vec<ir::Value>> {
+        let value_size = mem::size_of::<u128>();
+        // Skip the vmctx parameter of the tag signature.
+        let types = self.signatures[self.module.tags[index]].params[1..]
+            .iter()
+            .map(|param| param.value_type)
+            .collect::<Vec<_>>();
+        let values_vec = self.make_values_vec(&mut pos, types.len());
+
+        let func_sig = self.get_exception_take_payload_sig(&mut pos.func);
+        let (_, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_exception_take_payload_index(),
+        );
+        pos.ins()
+            .call_indirect(func_sig, func_addr, &[exception, values_vec]);
+
+        let mem_flags = ir::MemFlags::trusted();
+        Ok(types
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| {
+                pos.ins()
+                    .load(*ty, mem_flags, values_vec, (i * value_size) as i32)
+            })
+            .collect())
+    }
+
+    fn translate_exception_throw(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        exception: ir::Value,
+    ) -> WasmResult<()> {
+        let func_sig = self.get_exception_throw_sig(&mut pos.func);
+        let (_, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_exception_throw_index(),
+        );
+        pos.ins().call_indirect(func_sig, func_addr, &[exception]);
+        Ok(())
+    }
+
+    fn translate_exception_delete(
+        &mut self,
+        mut pos: FuncCursor<'_>,
+        exception: ir::Value,
+    ) -> WasmResult<()> {
+        let func_sig = self.get_exception_delete_sig(&mut pos.func);
+        let (_, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_exception_delete_index(),
+        );
+        pos.ins().call_indirect(func_sig, func_addr, &[exception]);
+        Ok(())
+    }
+
     fn translate_memory_grow(
         &mut self,
         mut pos: FuncCursor<'_>,
@@ -939,12 +1908,44 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         mut pos: FuncCursor,
         src_index: MemoryIndex,
         _src_heap: ir::Heap,
-        _dst_index: MemoryIndex,
+        dst_index: MemoryIndex,
         _dst_heap: ir::Heap,
         dst: ir::Value,
         src: ir::Value,
         len: ir::Value,
     ) -> WasmResult<()> {
+        if src_index != dst_index {
+            // The operands are widened to 64 bits if either memory is a
+            // 64-bit memory.
+            let (ty, func_idx) = if self.is_memory64(dst_index) || self.is_memory64(src_index) {
+                (
+                    I64,
+                    VMBuiltinFunctionIndex::get_memory64_copy_between_index(),
+                )
+            } else {
+                (I32, VMBuiltinFunctionIndex::get_memory_copy_between_index())
+            };
+            let mut widen = |value: ir::Value| {
+                if pos.func.dfg.value_type(value) == ty {
+                    value
+                } else {
+                    pos.ins().uextend(ty, value)
+                }
+            };
+            let (dst, src, len) = (widen(dst), widen(src), widen(len));
+            let func_sig = self.get_memory_copy_between_sig(&mut pos.func, ty);
+            let dst_index_arg = pos.ins().iconst(I32, dst_index.index() as i64);
+            let src_index_arg = pos.ins().iconst(I32, src_index.index() as i64);
+            let (vmctx, func_addr) =
+                self.translate_load_builtin_function_address(&mut pos, func_idx);
+            pos.ins().call_indirect(
+                func_sig,
+                func_addr,
+                &[vmctx, dst_index_arg, src_index_arg, dst, src, len],
+            );
+            return Ok(());
+        }
+
         let (func_sig, src_index, func_idx) = self.get_memory_copy_func(&mut pos.func, src_index);
 
         let src_index_arg = pos.ins().iconst(I32, src_index as i64);
@@ -992,7 +1993,7 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
         src: ir::Value,
         len: ir::Value,
     ) -> WasmResult<()> {
-        let (func_sig, func_idx) = self.get_memory_init_func(&mut pos.func);
+        let (func_sig, func_idx) = self.get_memory_init_func(&mut pos.func, memory_index);
 
         let memory_index_arg = pos.ins().iconst(I32, memory_index.index() as i64);
         let seg_index_arg = pos.ins().iconst(I32, seg_index as i64);
@@ -1019,13 +2020,12 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
 
     fn translate_table_size(
         &mut self,
-        mut _pos: FuncCursor,
+        mut pos: FuncCursor,
         _table_index: TableIndex,
-        _table: ir::Table,
+        table: ir::Table,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "bulk memory: `table.size`".to_string(),
-        ))
+        let bound_gv = pos.func.tables[table].bound_gv;
+        Ok(pos.ins().global_value(I32, bound_gv))
     }
 
     fn translate_table_copy(
@@ -1105,28 +2105,132 @@ impl<'module_environment> BaseFuncEnvironment for FuncEnvironment<'module_enviro
 
     fn translate_atomic_wait(
         &mut self,
-        _pos: FuncCursor,
-        _index: MemoryIndex,
+        mut pos: FuncCursor,
+        index: MemoryIndex,
         _heap: ir::Heap,
-        _addr: ir::Value,
-        _expected: ir::Value,
-        _timeout: ir::Value,
+        addr: ir::Value,
+        expected: ir::Value,
+        timeout: ir::Value,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "wasm atomics (fn translate_atomic_wait)".to_string(),
-        ))
+        let ty = pos.func.dfg.value_type(expected);
+        let (func_sig, index_arg, func_idx) =
+            self.get_memory_atomic_wait_func(&mut pos.func, index, ty);
+        let memory_index = pos.ins().iconst(I32, index_arg as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+        let call_inst = pos.ins().call_indirect(
+            func_sig,
+            func_addr,
+            &[vmctx, memory_index, addr, expected, timeout],
+        );
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
     fn translate_atomic_notify(
         &mut self,
-        _pos: FuncCursor,
-        _index: MemoryIndex,
+        mut pos: FuncCursor,
+        index: MemoryIndex,
         _heap: ir::Heap,
-        _addr: ir::Value,
-        _count: ir::Value,
+        addr: ir::Value,
+        count: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let (func_sig, index_arg, func_idx) =
+            self.get_memory_atomic_notify_func(&mut pos.func, index);
+        let memory_index = pos.ins().iconst(I32, index_arg as i64);
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+        let call_inst =
+            pos.ins()
+                .call_indirect(func_sig, func_addr, &[vmctx, memory_index, addr, count]);
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
+    }
+
+    fn has_native_atomics(&self) -> bool {
+        self.native_atomics
+    }
+
+    fn translate_atomic_load(
+        &mut self,
+        mut pos: FuncCursor,
+        access_ty: ir::Type,
+        addr: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let func_sig = self.get_atomic_load_sig(&mut pos.func);
+        let size = pos.ins().iconst(I32, i64::from(access_ty.bytes()));
+        let (_, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_atomic_load_index(),
+        );
+        let call_inst = pos.ins().call_indirect(func_sig, func_addr, &[addr, size]);
+        Ok(Self::atomic_result(&mut pos, access_ty, call_inst))
+    }
+
+    fn translate_atomic_store(
+        &mut self,
+        mut pos: FuncCursor,
+        addr: ir::Value,
+        value: ir::Value,
+    ) -> WasmResult<()> {
+        let access_ty = pos.func.dfg.value_type(value);
+        let func_sig = self.get_atomic_store_sig(&mut pos.func);
+        let size = pos.ins().iconst(I32, i64::from(access_ty.bytes()));
+        let value = Self::atomic_operand(&mut pos, value);
+        let (_, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_atomic_store_index(),
+        );
+        pos.ins()
+            .call_indirect(func_sig, func_addr, &[addr, size, value]);
+        Ok(())
+    }
+
+    fn translate_atomic_rmw(
+        &mut self,
+        mut pos: FuncCursor,
+        op: ir::AtomicRmwOp,
+        addr: ir::Value,
+        value: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let op = match op {
+            ir::AtomicRmwOp::Add => AtomicRmwOp::Add,
+            ir::AtomicRmwOp::Sub => AtomicRmwOp::Sub,
+            ir::AtomicRmwOp::And => AtomicRmwOp::And,
+            ir::AtomicRmwOp::Or => AtomicRmwOp::Or,
+            ir::AtomicRmwOp::Xor => AtomicRmwOp::Xor,
+            ir::AtomicRmwOp::Xchg => AtomicRmwOp::Xchg,
+        };
+        let access_ty = pos.func.dfg.value_type(value);
+        let func_sig = self.get_atomic_rmw_sig(&mut pos.func);
+        let size = pos.ins().iconst(I32, i64::from(access_ty.bytes()));
+        let op = pos.ins().iconst(I32, op as i64);
+        let value = Self::atomic_operand(&mut pos, value);
+        let (_, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_atomic_rmw_index(),
+        );
+        let call_inst = pos
+            .ins()
+            .call_indirect(func_sig, func_addr, &[addr, size, op, value]);
+        Ok(Self::atomic_result(&mut pos, access_ty, call_inst))
+    }
+
+    fn translate_atomic_cas(
+        &mut self,
+        mut pos: FuncCursor,
+        addr: ir::Value,
+        expected: ir::Value,
+        replacement: ir::Value,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "wasm atomics (fn translate_atomic_notify)".to_string(),
-        ))
+        let access_ty = pos.func.dfg.value_type(expected);
+        let func_sig = self.get_atomic_cmpxchg_sig(&mut pos.func);
+        let size = pos.ins().iconst(I32, i64::from(access_ty.bytes()));
+        let expected = Self::atomic_operand(&mut pos, expected);
+        let replacement = Self::atomic_operand(&mut pos, replacement);
+        let (_, func_addr) = self.translate_load_builtin_function_address(
+            &mut pos,
+            VMBuiltinFunctionIndex::get_atomic_cmpxchg_index(),
+        );
+        let call_inst =
+            pos.ins()
+                .call_indirect(func_sig, func_addr, &[addr, size, expected, replacement]);
+        Ok(Self::atomic_result(&mut pos, access_ty, call_inst))
     }
 }
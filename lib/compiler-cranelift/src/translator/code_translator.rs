@@ -27,12 +27,14 @@
 //! argument.
 
 use super::func_environ::{FuncEnvironment, GlobalVariable, ReturnMode};
-use super::func_state::{ControlStackFrame, ElseData, FuncTranslationState};
+use super::func_state::{ControlStackFrame, ElseData, FuncTranslationState, TryClause};
 use super::translation_utils::{block_with_params, f32_translation, f64_translation};
 use crate::{hash_map, HashMap};
 use core::cmp;
 use core::convert::TryFrom;
+use core::iter;
 use core::{i32, u32};
+use cranelift_codegen::entity::EntityRef;
 use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
 use cranelift_codegen::ir::immediates::Offset32;
 use cranelift_codegen::ir::types::*;
@@ -47,7 +49,7 @@ use std::vec::Vec;
 use wasmer_compiler::wasmparser::{MemoryImmediate, Operator};
 use wasmer_compiler::WasmResult;
 use wasmer_compiler::{wasm_unsupported, ModuleTranslationState};
-use wasmer_types::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex};
+use wasmer_types::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex, TagIndex};
 
 // Clippy warns about "align: _" but its important to document that the align field is ignored
 #[cfg_attr(
@@ -156,14 +158,14 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         }
         Operator::Select => {
             let (arg1, arg2, cond) = state.pop3();
-            state.push1(builder.ins().select(cond, arg1, arg2));
+            state.push1(translate_select(cond, arg1, arg2, builder));
         }
         Operator::TypedSelect { ty: _ } => {
             // We ignore the explicit type parameter as it is only needed for
             // validation, which we require to have been performed before
             // translation.
             let (arg1, arg2, cond) = state.pop3();
-            state.push1(builder.ins().select(cond, arg1, arg2));
+            state.push1(translate_select(cond, arg1, arg2, builder));
         }
         Operator::Nop => {
             // We do nothing
@@ -310,6 +312,10 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                         // to `WithElse` because nothing else will read it.
                     }
                 }
+                // wasmparser decodes `catch_all` as `else` inside a `try` block.
+                ControlStackFrame::Try { .. } => {
+                    translate_try_clause(TryClauseKind::CatchAll, builder, state, environ)?
+                }
                 _ => unreachable!(),
             }
         }
@@ -317,7 +323,10 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let frame = state.control_stack.pop().unwrap();
             let next_block = frame.following_code();
 
-            if !builder.is_unreachable() || !builder.is_pristine() {
+            if let ControlStackFrame::Try { .. } = frame {
+                let depth = state.control_stack.len();
+                translate_end_try(&frame, true, depth, builder, state, environ)?;
+            } else if !builder.is_unreachable() || !builder.is_pristine() {
                 let return_count = frame.num_return_values();
                 let return_args = state.peekn_mut(return_count);
                 canonicalise_then_jump(builder, frame.following_code(), return_args);
@@ -376,12 +385,15 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 };
                 (return_count, frame.br_destination())
             };
+            translate_exception_delete_all(&state.held_exceptions(i), builder, environ)?;
             let destination_args = state.peekn_mut(return_count);
             canonicalise_then_jump(builder, br_destination, destination_args);
             state.popn(return_count);
             state.reachable = false;
         }
-        Operator::BrIf { relative_depth } => translate_br_if(*relative_depth, builder, state),
+        Operator::BrIf { relative_depth } => {
+            translate_br_if(*relative_depth, builder, state, environ)?
+        }
         Operator::BrTable { table } => {
             let mut depths = table.targets().collect::<Result<Vec<_>, _>>()?;
             let default = depths.pop().unwrap().0;
@@ -400,9 +412,19 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                     min_depth_frame.num_return_values()
                 }
             };
+            // The branches leaving `unwind` or `catch_all` clauses free the exceptions they hold
+            // on their way, so they need their own blocks too.
+            let frees_exceptions = depths
+                .iter()
+                .map(|(depth, _)| *depth)
+                .chain(iter::once(default))
+                .any(|depth| {
+                    let i = state.control_stack.len() - 1 - (depth as usize);
+                    !state.held_exceptions(i).is_empty()
+                });
             let val = state.pop1();
             let mut data = JumpTableData::with_capacity(depths.len());
-            if jump_args_count == 0 {
+            if jump_args_count == 0 && !frees_exceptions {
                 // No jump arguments
                 for (depth, _) in depths.iter() {
                     let block = {
@@ -422,7 +444,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 };
                 builder.ins().br_table(val, block, jt);
             } else {
-                // Here we have jump arguments, but Cranelift's br_table doesn't support them
+                // Here we have jump arguments, but Cranelift's br_table doesn't support them, or
+                // exceptions to free on some edges.
                 // We then proceed to split the edges going out of the br_table
                 let return_count = jump_args_count;
                 let mut dest_block_sequence = vec![];
@@ -451,12 +474,13 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
                 for (depth, dest_block) in dest_block_sequence {
                     builder.switch_to_block(dest_block);
                     builder.seal_block(dest_block);
+                    let i = state.control_stack.len() - 1 - depth;
                     let real_dest_block = {
-                        let i = state.control_stack.len() - 1 - depth;
                         let frame = &mut state.control_stack[i];
                         frame.set_branched_to_exit();
                         frame.br_destination()
                     };
+                    translate_exception_delete_all(&state.held_exceptions(i), builder, environ)?;
                     let destination_args = state.peekn_mut(return_count);
                     canonicalise_then_jump(builder, real_dest_block, destination_args);
                 }
@@ -464,29 +488,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             }
             state.reachable = false;
         }
-        Operator::Return => {
-            let (return_count, br_destination) = {
-                let frame = &mut state.control_stack[0];
-                frame.set_branched_to_exit();
-                let return_count = frame.num_return_values();
-                (return_count, frame.br_destination())
-            };
-            {
-                let return_args = state.peekn_mut(return_count);
-                let return_types = wasm_param_types(&builder.func.signature.returns, |i| {
-                    environ.is_wasm_return(&builder.func.signature, i)
-                });
-                bitcast_arguments(return_args, &return_types, builder);
-                match environ.return_mode() {
-                    ReturnMode::NormalReturns => builder.ins().return_(return_args),
-                    ReturnMode::FallthroughReturn => {
-                        canonicalise_then_jump(builder, br_destination, return_args)
-                    }
-                };
-            }
-            state.popn(return_count);
-            state.reachable = false;
-        }
+        Operator::Return => translate_return(builder, state, environ)?,
         /************************************ Calls ****************************************
          * The call instructions pop off their arguments from the stack and append their
          * return values to it. `call_indirect` needs environment support because there is an
@@ -494,6 +496,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
          ************************************************************************************/
         Operator::Call { function_index } => {
             let (fref, num_args) = state.get_direct_func(builder.func, *function_index, environ)?;
+            let (handler, held) = state.exception_target(state.control_stack.len());
 
             // Bitcast any vector arguments to their default type, I8X16, before calling.
             let callee_signature =
@@ -504,6 +507,22 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             });
             bitcast_arguments(args, &types, builder);
 
+            if handler.is_some() || !held.is_empty() {
+                // Inside a `try` block, the exceptions thrown by the callee are sent to the
+                // handler of the block. Inside an `unwind` or `catch_all` clause, they are
+                // caught too, to free the exception held by the clause.
+                let (exception, results) = environ.translate_try_call(
+                    builder.cursor(),
+                    FunctionIndex::from_u32(*function_index),
+                    fref,
+                    args,
+                )?;
+                translate_branch_on_exception(exception, handler, &held, builder, environ)?;
+                state.popn(num_args);
+                state.pushn(&results);
+                return Ok(());
+            }
+
             let call = environ.translate_call(
                 builder.cursor(),
                 FunctionIndex::from_u32(*function_index),
@@ -527,6 +546,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let (sigref, num_args) = state.get_indirect_sig(builder.func, *index, environ)?;
             let table = state.get_or_create_table(builder.func, *table_index, environ)?;
             let callee = state.pop1();
+            let (handler, held) = state.exception_target(state.control_stack.len());
 
             // Bitcast any vector arguments to their default type, I8X16, before calling.
             let callee_signature = &builder.func.dfg.signatures[sigref];
@@ -536,6 +556,22 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             });
             bitcast_arguments(args, &types, builder);
 
+            if handler.is_some() || !held.is_empty() {
+                let (exception, results) = environ.translate_try_call_indirect(
+                    builder.cursor(),
+                    TableIndex::from_u32(*table_index),
+                    table,
+                    SignatureIndex::from_u32(*index),
+                    sigref,
+                    callee,
+                    state.peekn(num_args),
+                )?;
+                translate_branch_on_exception(exception, handler, &held, builder, environ)?;
+                state.popn(num_args);
+                state.pushn(&results);
+                return Ok(());
+            }
+
             let call = environ.translate_call_indirect(
                 builder.cursor(),
                 TableIndex::from_u32(*table_index),
@@ -554,6 +590,31 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             state.popn(num_args);
             state.pushn(inst_results);
         }
+        Operator::ReturnCall { function_index } => {
+            let (fref, num_args) = state.get_direct_func(builder.func, *function_index, environ)?;
+
+            // Cranelift can't emit tail calls yet, so only a tail call to the function itself is
+            // supported: it restarts the body with the new parameters in constant stack space.
+            if builder.func.dfg.ext_funcs[fref].name != builder.func.name {
+                return Err(wasm_unsupported!(
+                    "return_call to a function other than the caller"
+                ));
+            }
+
+            // Bitcast any vector arguments to their default type, I8X16, before calling.
+            let callee_signature =
+                &builder.func.dfg.signatures[builder.func.dfg.ext_funcs[fref].signature];
+            let args = state.peekn_mut(num_args);
+            let types = wasm_param_types(&callee_signature.params, |i| {
+                environ.is_wasm_parameter(&callee_signature, i)
+            });
+            bitcast_arguments(args, &types, builder);
+
+            translate_self_tail_call(builder, state, environ, num_args)?;
+        }
+        Operator::ReturnCallIndirect { .. } => {
+            return Err(wasm_unsupported!("return_call_indirect"));
+        }
         /******************************* Memory management ***********************************
          * Memory management is handled by environment. It is usually translated into calls to
          * special functions.
@@ -990,6 +1051,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let timeout = state.pop1(); // 64 (fixed)
             let expected = state.pop1(); // 32 or 64 (per the `Ixx` in `IxxAtomicWait`)
             let addr = state.pop1(); // 32 (fixed)
+            let addr = effective_atomic_addr(addr, memarg, builder);
             assert!(builder.func.dfg.value_type(expected) == implied_ty);
             // `fn translate_atomic_wait` can inspect the type of `expected` to figure out what
             // code it needs to generate, if it wants.
@@ -1008,6 +1070,7 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             let heap = state.get_heap(builder.func, memarg.memory, environ)?;
             let count = state.pop1(); // 32 (fixed)
             let addr = state.pop1(); // 32 (fixed)
+            let addr = effective_atomic_addr(addr, memarg, builder);
             let res =
                 environ.translate_atomic_notify(builder.cursor(), heap_index, heap, addr, count)?;
             state.push1(res);
@@ -1754,10 +1817,6 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             state.push1(builder.ins().nearest(arg));
         }
 
-        Operator::ReturnCall { .. } | Operator::ReturnCallIndirect { .. } => {
-            return Err(wasm_unsupported!("proposed tail-call operator {:?}", op));
-        }
-
         Operator::I64x2Eq
         | Operator::I64x2Ne
         | Operator::I64x2AllTrue
@@ -1796,12 +1855,64 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             return Err(wasm_unsupported!("updated proposed simd operator {:?}", op));
         }
 
-        Operator::Try { .. }
-        | Operator::Catch { .. }
-        | Operator::Throw { .. }
-        | Operator::Rethrow { .. }
-        | Operator::Unwind => {
-            return Err(wasm_unsupported!("proposed exception operator {:?}", op));
+        /******************************* Exception handling ***********************************
+         * Exceptions are thrown and caught by the runtime. Inside the body of a `try` block,
+         * calls go through the runtime, which returns the exception thrown by the callee, and
+         * `throw` jumps to the handler of the block directly. Elsewhere, exceptions are thrown
+         * out of the function.
+         ************************************************************************************/
+        Operator::Try { ty } => {
+            let (params, results) = module_translation_state.blocktype_params_results(*ty)?;
+            let next = block_with_params(builder, results, environ)?;
+            let handler = builder.create_block();
+            builder.append_block_param(handler, environ.pointer_type());
+            state.push_try(next, handler, params.len(), results.len());
+        }
+        Operator::Catch { index } => {
+            let kind = TryClauseKind::Catch(TagIndex::from_u32(*index));
+            translate_try_clause(kind, builder, state, environ)?;
+        }
+        Operator::Unwind => {
+            translate_try_clause(TryClauseKind::Unwind, builder, state, environ)?;
+        }
+        Operator::Throw { index } => {
+            let tag = TagIndex::from_u32(*index);
+            let num_params = environ.num_tag_params(tag);
+            let exception =
+                environ.translate_exception_new(builder.cursor(), tag, state.peekn(num_params))?;
+            state.popn(num_params);
+            let depth = state.control_stack.len();
+            translate_throw_exception(exception, depth, builder, state, environ)?;
+            state.reachable = false;
+        }
+        Operator::Rethrow { relative_depth } => {
+            let i = state.control_stack.len() - 1 - (*relative_depth as usize);
+            let exception = match state.control_stack[i] {
+                // The caught exception was freed when its values were taken, so it is created
+                // again.
+                ControlStackFrame::Try {
+                    clause:
+                        TryClause::Catch {
+                            tag, ref payload, ..
+                        },
+                    ..
+                } => {
+                    let payload = payload.clone();
+                    environ.translate_exception_new(builder.cursor(), tag, &payload)?
+                }
+                ControlStackFrame::Try {
+                    clause: TryClause::CatchAll { exception },
+                    ..
+                }
+                | ControlStackFrame::Try {
+                    clause: TryClause::Unwind { exception },
+                    ..
+                } => exception,
+                _ => unreachable!(),
+            };
+            let depth = state.control_stack.len();
+            translate_throw_exception(exception, depth, builder, state, environ)?;
+            state.reachable = false;
         }
     };
     Ok(())
@@ -1834,12 +1945,30 @@ fn translate_unreachable_operator<FE: FuncEnvironment + ?Sized>(
                 ty,
             );
         }
-        Operator::Loop { ty: _ } | Operator::Block { ty: _ } => {
+        Operator::Loop { ty: _ } | Operator::Block { ty: _ } | Operator::Try { ty: _ } => {
             state.push_block(ir::Block::reserved_value(), 0, 0);
         }
+        Operator::Catch { index } => {
+            // The clauses of a `try` whose head is reachable are reachable through its handler.
+            if let Some(ControlStackFrame::Try { .. }) = state.control_stack.last() {
+                let kind = TryClauseKind::Catch(TagIndex::from_u32(index));
+                translate_try_clause(kind, builder, state, environ)?;
+            }
+        }
+        Operator::Unwind => {
+            if let Some(ControlStackFrame::Try { .. }) = state.control_stack.last() {
+                translate_try_clause(TryClauseKind::Unwind, builder, state, environ)?;
+            }
+        }
         Operator::Else => {
             let i = state.control_stack.len() - 1;
             match state.control_stack[i] {
+                // wasmparser decodes `catch_all` as `else` inside a `try` block.
+                ControlStackFrame::Try { .. } => {
+                    translate_try_clause(TryClauseKind::CatchAll, builder, state, environ)?
+                }
+                // The placeholder of a `try` whose head is unreachable.
+                ControlStackFrame::Block { .. } => {}
                 ControlStackFrame::If {
                     ref else_data,
                     head_is_reachable,
@@ -1886,12 +2015,16 @@ fn translate_unreachable_operator<FE: FuncEnvironment + ?Sized>(
             }
         }
         Operator::End => {
-            let stack = &mut state.stack;
-            let control_stack = &mut state.control_stack;
-            let frame = control_stack.pop().unwrap();
+            let frame = state.control_stack.pop().unwrap();
 
             // Pop unused parameters from stack.
-            frame.truncate_value_stack_to_original_size(stack);
+            frame.truncate_value_stack_to_original_size(&mut state.stack);
+
+            if let ControlStackFrame::Try { .. } = frame {
+                let depth = state.control_stack.len();
+                translate_end_try(&frame, false, depth, builder, state, environ)?;
+            }
+            let stack = &mut state.stack;
 
             let reachable_anyway = match frame {
                 // If it is a loop we also have to seal the body loop block
@@ -2009,6 +2142,17 @@ fn get_heap_addr(
     };
     debug_assert!(adjusted_offset > 0); // want to bounds check at least 1 byte
     let check_size = u32::try_from(adjusted_offset).unwrap_or(u32::MAX);
+
+    // A 64-bit index can wrap around when the checked size is added to it,
+    // which `heap_addr` doesn't account for: trap on those up front.
+    if builder.func.heaps[heap].index_type == I64 {
+        let wraps = builder.ins().icmp_imm(
+            IntCC::UnsignedGreaterThan,
+            addr32,
+            (u64::MAX - u64::from(check_size)) as i64,
+        );
+        builder.ins().trapnz(wraps, ir::TrapCode::HeapOutOfBounds);
+    }
     let base = builder.ins().heap_addr(addr_ty, heap, addr32, check_size);
 
     // Native load/store instructions take a signed `Offset32` immediate, so adjust the base
@@ -2117,6 +2261,22 @@ fn translate_icmp(cc: IntCC, builder: &mut FunctionBuilder, state: &mut FuncTran
 
 // For an atomic memory operation, emit an alignment check for the linear memory address,
 // and then compute the final effective address.
+/// Computes the 64-bit effective address of a `memory.atomic.wait` or
+/// `memory.atomic.notify`, which are lowered to calls that do the bounds
+/// and alignment checks themselves.
+fn effective_atomic_addr(
+    linear_mem_addr: Value,
+    memarg: &MemoryImmediate,
+    builder: &mut FunctionBuilder,
+) -> Value {
+    let addr = if builder.func.dfg.value_type(linear_mem_addr) == I64 {
+        linear_mem_addr
+    } else {
+        builder.ins().uextend(I64, linear_mem_addr)
+    };
+    builder.ins().iadd_imm(addr, i64::from(memarg.offset))
+}
+
 fn finalise_atomic_mem_addr<FE: FuncEnvironment + ?Sized>(
     linear_mem_addr: Value,
     memarg: &MemoryImmediate,
@@ -2197,9 +2357,13 @@ fn translate_atomic_rmw<FE: FuncEnvironment + ?Sized>(
 
     // See the comments in `prepare_load` about the flags.
     let flags = MemFlags::new();
-    let mut res = builder
-        .ins()
-        .atomic_rmw(access_ty, flags, op, final_effective_address, arg2);
+    let mut res = if environ.has_native_atomics() {
+        builder
+            .ins()
+            .atomic_rmw(access_ty, flags, op, final_effective_address, arg2)
+    } else {
+        environ.translate_atomic_rmw(builder.cursor(), op, final_effective_address, arg2)?
+    };
     if access_ty != widened_ty {
         res = builder.ins().uextend(widened_ty, res);
     }
@@ -2250,9 +2414,18 @@ fn translate_atomic_cas<FE: FuncEnvironment + ?Sized>(
 
     // See the comments in `prepare_load` about the flags.
     let flags = MemFlags::new();
-    let mut res = builder
-        .ins()
-        .atomic_cas(flags, final_effective_address, expected, replacement);
+    let mut res = if environ.has_native_atomics() {
+        builder
+            .ins()
+            .atomic_cas(flags, final_effective_address, expected, replacement)
+    } else {
+        environ.translate_atomic_cas(
+            builder.cursor(),
+            final_effective_address,
+            expected,
+            replacement,
+        )?
+    };
     if access_ty != widened_ty {
         res = builder.ins().uextend(widened_ty, res);
     }
@@ -2292,9 +2465,13 @@ fn translate_atomic_load<FE: FuncEnvironment + ?Sized>(
 
     // See the comments in `prepare_load` about the flags.
     let flags = MemFlags::new();
-    let mut res = builder
-        .ins()
-        .atomic_load(access_ty, flags, final_effective_address);
+    let mut res = if environ.has_native_atomics() {
+        builder
+            .ins()
+            .atomic_load(access_ty, flags, final_effective_address)
+    } else {
+        environ.translate_atomic_load(builder.cursor(), access_ty, final_effective_address)?
+    };
     if access_ty != widened_ty {
         res = builder.ins().uextend(widened_ty, res);
     }
@@ -2338,9 +2515,13 @@ fn translate_atomic_store<FE: FuncEnvironment + ?Sized>(
 
     // See the comments in `prepare_load` about the flags.
     let flags = MemFlags::new();
-    builder
-        .ins()
-        .atomic_store(flags, data, final_effective_address);
+    if environ.has_native_atomics() {
+        builder
+            .ins()
+            .atomic_store(flags, data, final_effective_address);
+    } else {
+        environ.translate_atomic_store(builder.cursor(), final_effective_address, data)?;
+    }
     Ok(())
 }
 
@@ -2374,19 +2555,359 @@ fn translate_vector_fcmp(
     state.push1(builder.ins().fcmp(cc, bitcast_a, bitcast_b))
 }
 
-fn translate_br_if(
+/// Return the values on top of the stack from the function.
+fn translate_return<FE: FuncEnvironment + ?Sized>(
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    translate_exception_delete_all(&state.held_exceptions(0), builder, environ)?;
+    let (return_count, br_destination) = {
+        let frame = &mut state.control_stack[0];
+        frame.set_branched_to_exit();
+        let return_count = frame.num_return_values();
+        (return_count, frame.br_destination())
+    };
+    {
+        let return_args = state.peekn_mut(return_count);
+        let return_types = wasm_param_types(&builder.func.signature.returns, |i| {
+            environ.is_wasm_return(&builder.func.signature, i)
+        });
+        bitcast_arguments(return_args, &return_types, builder);
+        match environ.return_mode() {
+            ReturnMode::NormalReturns => builder.ins().return_(return_args),
+            ReturnMode::FallthroughReturn => {
+                canonicalise_then_jump(builder, br_destination, return_args)
+            }
+        };
+    }
+    state.popn(return_count);
+    state.reachable = false;
+    Ok(())
+}
+
+/// Jump back to the start of the function body, with the `num_args` values on top of the stack
+/// as the new parameters and the other locals reset.
+fn translate_self_tail_call<FE: FuncEnvironment + ?Sized>(
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+    num_args: usize,
+) -> WasmResult<()> {
+    translate_exception_delete_all(&state.held_exceptions(0), builder, environ)?;
+    let body_block = state
+        .body_block
+        .expect("the function body should start in its own block");
+    let args = state.peekn(num_args);
+    for (i, init) in state.local_inits.iter().enumerate() {
+        let value = if i < num_args { args[i] } else { *init };
+        builder.def_var(Variable::new(i), value);
+    }
+    builder.ins().jump(body_block, &[]);
+    state.popn(num_args);
+    state.reachable = false;
+    Ok(())
+}
+
+fn translate_br_if<FE: FuncEnvironment + ?Sized>(
     relative_depth: u32,
     builder: &mut FunctionBuilder,
     state: &mut FuncTranslationState,
-) {
+    environ: &mut FE,
+) -> WasmResult<()> {
     let val = state.pop1();
+    let i = state.control_stack.len() - 1 - (relative_depth as usize);
+    let held = state.held_exceptions(i);
     let (br_destination, inputs) = translate_br_if_args(relative_depth, state);
-    canonicalise_then_brnz(builder, val, br_destination, inputs);
 
     let next_block = builder.create_block();
-    canonicalise_then_jump(builder, next_block, &[]);
+    if held.is_empty() {
+        canonicalise_then_brnz(builder, val, br_destination, inputs);
+        canonicalise_then_jump(builder, next_block, &[]);
+    } else {
+        // The branch leaves `unwind` or `catch_all` clauses, so it frees the exceptions they
+        // hold on its way.
+        let exit_block = builder.create_block();
+        builder.ins().brnz(val, exit_block, &[]);
+        builder.ins().jump(next_block, &[]);
+        builder.seal_block(exit_block); // The only predecessor is the current block.
+        builder.switch_to_block(exit_block);
+        translate_exception_delete_all(&held, builder, environ)?;
+        canonicalise_then_jump(builder, br_destination, inputs);
+    }
+    builder.seal_block(next_block); // The only predecessor is the current block.
+    builder.switch_to_block(next_block);
+    Ok(())
+}
+
+/// Send the exception caught by a call to `handler`, or throw it out of the function if there is
+/// no handler, if the call threw one. The exceptions `held` by the clauses left on the way are
+/// freed first.
+fn translate_branch_on_exception<FE: FuncEnvironment + ?Sized>(
+    exception: ir::Value,
+    handler: Option<ir::Block>,
+    held: &[ir::Value],
+    builder: &mut FunctionBuilder,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let landing_block = match handler {
+        Some(handler) if held.is_empty() => handler,
+        _ => {
+            let block = builder.create_block();
+            builder.append_block_param(block, environ.pointer_type());
+            block
+        }
+    };
+    builder.ins().brnz(exception, landing_block, &[exception]);
+
+    let next_block = builder.create_block();
+    builder.ins().jump(next_block, &[]);
+
+    if handler != Some(landing_block) {
+        builder.seal_block(landing_block); // The only predecessor is the current block.
+        builder.switch_to_block(landing_block);
+        let exception = builder.block_params(landing_block)[0];
+        translate_exception_delete_all(held, builder, environ)?;
+        translate_send_exception(exception, handler, builder, environ)?;
+    }
+
     builder.seal_block(next_block); // The only predecessor is the current block.
     builder.switch_to_block(next_block);
+    Ok(())
+}
+
+/// Throw `exception` to the handler of the innermost `try` block among the `depth` outermost
+/// frames whose body is being translated, or out of the function if there is none. The
+/// exceptions held by the clauses left on the way are freed. This ends the current block.
+fn translate_throw_exception<FE: FuncEnvironment + ?Sized>(
+    exception: ir::Value,
+    depth: usize,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (handler, mut held) = state.exception_target(depth);
+    // A `rethrow` throws the exception held by its clause.
+    held.retain(|held_exception| *held_exception != exception);
+    translate_exception_delete_all(&held, builder, environ)?;
+    translate_send_exception(exception, handler, builder, environ)
+}
+
+/// Jump to `handler` with `exception`, or throw it out of the function if there is no handler.
+/// This ends the current block.
+fn translate_send_exception<FE: FuncEnvironment + ?Sized>(
+    exception: ir::Value,
+    handler: Option<ir::Block>,
+    builder: &mut FunctionBuilder,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    match handler {
+        Some(handler) => {
+            builder.ins().jump(handler, &[exception]);
+        }
+        None => {
+            environ.translate_exception_throw(builder.cursor(), exception)?;
+            builder.ins().trap(ir::TrapCode::UnreachableCodeReached);
+        }
+    }
+    Ok(())
+}
+
+/// Free the `exceptions` held by the `unwind` and `catch_all` clauses that are left.
+fn translate_exception_delete_all<FE: FuncEnvironment + ?Sized>(
+    exceptions: &[ir::Value],
+    builder: &mut FunctionBuilder,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    for exception in exceptions {
+        environ.translate_exception_delete(builder.cursor(), *exception)?;
+    }
+    Ok(())
+}
+
+/// The kind of a clause of a `try` block.
+#[derive(Debug, Clone, Copy)]
+enum TryClauseKind {
+    /// A `catch` clause for the tag.
+    Catch(TagIndex),
+    /// A `catch_all` clause.
+    CatchAll,
+    /// An `unwind` clause.
+    Unwind,
+}
+
+/// Start a clause of the innermost `try` block.
+///
+/// The previous part of the block jumps to the code following it if it ends reachable. For a
+/// `catch` clause, the exception that the previous clause didn't match is checked against its
+/// tag; if it matches, the values it carries are pushed on the stack, and otherwise it is sent to
+/// the next clause. The other clauses take any exception.
+fn translate_try_clause<FE: FuncEnvironment + ?Sized>(
+    kind: TryClauseKind,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let reachable = state.reachable;
+    let i = state.control_stack.len() - 1;
+    let (destination, num_return_values, exception_block) = match state.control_stack[i] {
+        ControlStackFrame::Try {
+            destination,
+            num_return_values,
+            handler,
+            ref clause,
+            ref mut exit_is_branched_to,
+            ..
+        } => {
+            let exception_block = match *clause {
+                TryClause::Body => handler,
+                TryClause::Catch { next, .. } => next,
+                TryClause::CatchAll { .. } | TryClause::Unwind { .. } => unreachable!(),
+            };
+            *exit_is_branched_to |= reachable;
+            (destination, num_return_values, exception_block)
+        }
+        _ => unreachable!(),
+    };
+
+    if reachable {
+        let return_args = state.peekn_mut(num_return_values);
+        canonicalise_then_jump(builder, destination, return_args);
+    }
+    state.control_stack[i].truncate_value_stack_to_original_size(&mut state.stack);
+
+    // All the predecessors of the exception block are known once the previous part is done.
+    builder.switch_to_block(exception_block);
+    builder.seal_block(exception_block);
+    let exception = builder.block_params(exception_block)[0];
+
+    let new_clause = match kind {
+        TryClauseKind::Catch(tag) => {
+            let matches = environ.translate_exception_matches(builder.cursor(), exception, tag)?;
+            let next = builder.create_block();
+            builder.append_block_param(next, environ.pointer_type());
+            builder.ins().brz(matches, next, &[exception]);
+
+            let catch_block = builder.create_block();
+            builder.ins().jump(catch_block, &[]);
+            builder.seal_block(catch_block); // The only predecessor is the current block.
+            builder.switch_to_block(catch_block);
+
+            let payload =
+                environ.translate_exception_take_payload(builder.cursor(), exception, tag)?;
+            state.pushn(&payload);
+            TryClause::Catch { next, tag, payload }
+        }
+        TryClauseKind::CatchAll => TryClause::CatchAll { exception },
+        TryClauseKind::Unwind => TryClause::Unwind { exception },
+    };
+    if let ControlStackFrame::Try { ref mut clause, .. } = state.control_stack[i] {
+        *clause = new_clause;
+    }
+    state.reachable = true;
+    Ok(())
+}
+
+/// Finish the `try` block of `frame` at its `end` or `delegate`, once the frame has been popped.
+///
+/// The last part of the block jumps to the code following it if it ends `reachable`, after
+/// freeing its exception for a `catch_all` clause, or throws its exception again for an `unwind`
+/// clause. The exceptions that no clause matched are thrown further, to the handler of the
+/// innermost `try` block among the `depth` outermost frames.
+fn translate_end_try<FE: FuncEnvironment + ?Sized>(
+    frame: &ControlStackFrame,
+    reachable: bool,
+    depth: usize,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (destination, num_return_values, handler, handler_is_branched_to, clause) = match *frame {
+        ControlStackFrame::Try {
+            destination,
+            num_return_values,
+            handler,
+            handler_is_branched_to,
+            ref clause,
+            ..
+        } => (
+            destination,
+            num_return_values,
+            handler,
+            handler_is_branched_to,
+            clause,
+        ),
+        _ => unreachable!(),
+    };
+
+    if reachable {
+        match *clause {
+            TryClause::Unwind { exception } => {
+                translate_throw_exception(exception, depth, builder, state, environ)?
+            }
+            TryClause::CatchAll { exception } => {
+                environ.translate_exception_delete(builder.cursor(), exception)?;
+                let return_args = state.peekn_mut(num_return_values);
+                canonicalise_then_jump(builder, destination, return_args);
+            }
+            _ => {
+                let return_args = state.peekn_mut(num_return_values);
+                canonicalise_then_jump(builder, destination, return_args);
+            }
+        }
+    }
+
+    let uncaught = match *clause {
+        TryClause::Body if handler_is_branched_to => Some(handler),
+        TryClause::Catch { next, .. } => Some(next),
+        _ => None,
+    };
+    if let Some(block) = uncaught {
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+        let exception = builder.block_params(block)[0];
+        translate_throw_exception(exception, depth, builder, state, environ)?;
+    }
+    Ok(())
+}
+
+/// Translate the `delegate` instruction ending the body of the innermost `try` block, which sends
+/// the exceptions thrown in the body to the `try` block `relative_depth` labels out of it.
+///
+/// wasmparser doesn't decode `delegate`, so it is read by the caller and doesn't go through
+/// `translate_operator`.
+pub fn translate_delegate<FE: FuncEnvironment + ?Sized>(
+    relative_depth: u32,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let reachable = state.reachable;
+    let frame = state.control_stack.pop().unwrap();
+    if !reachable {
+        // Pop unused parameters from stack.
+        frame.truncate_value_stack_to_original_size(&mut state.stack);
+    }
+
+    // The exceptions are sent to the handler of the `try` block at the label, or to the
+    // enclosing ones if it isn't a `try` block or its body is already translated.
+    let depth = state.control_stack.len() - relative_depth as usize;
+    if let ControlStackFrame::Try { .. } = frame {
+        translate_end_try(&frame, reachable, depth, builder, state, environ)?;
+    }
+
+    // The frame is a placeholder `block` if the `try` is unreachable.
+    if reachable || frame.exit_is_branched_to() {
+        let next_block = frame.following_code();
+        builder.switch_to_block(next_block);
+        builder.seal_block(next_block);
+        frame.truncate_value_stack_to_original_size(&mut state.stack);
+        state
+            .stack
+            .extend_from_slice(builder.block_params(next_block));
+        state.reachable = true;
+    }
+    Ok(())
 }
 
 fn translate_br_if_args(
@@ -2598,6 +3119,24 @@ fn type_of(operator: &Operator) -> Type {
 
 /// Some SIMD operations only operate on I8X16 in CLIF; this will convert them to that type by
 /// adding a raw_bitcast if necessary.
+/// Translate a `select`. Cranelift can't select vectors, so they are
+/// combined with a mask of all ones or all zeroes instead.
+fn translate_select(cond: Value, arg1: Value, arg2: Value, builder: &mut FunctionBuilder) -> Value {
+    let ty = builder.func.dfg.value_type(arg1);
+    if !ty.is_vector() {
+        return builder.ins().select(cond, arg1, arg2);
+    }
+    let is_true = builder.ins().icmp_imm(IntCC::NotEqual, cond, 0);
+    let mask = builder.ins().bint(I64, is_true);
+    let mask = builder.ins().ineg(mask);
+    let mask = builder.ins().splat(I64X2, mask);
+    let mask = builder.ins().raw_bitcast(I8X16, mask);
+    let arg1 = optionally_bitcast_vector(arg1, I8X16, builder);
+    let arg2 = optionally_bitcast_vector(arg2, I8X16, builder);
+    let selected = builder.ins().bitselect(mask, arg1, arg2);
+    optionally_bitcast_vector(selected, ty, builder)
+}
+
 fn optionally_bitcast_vector(
     value: Value,
     needed_type: Type,
@@ -14,7 +14,7 @@ use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_frontend::FunctionBuilder;
 use wasmer_compiler::wasmparser::{Operator, Type};
 use wasmer_compiler::WasmResult;
-use wasmer_types::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex};
+use wasmer_types::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex, TagIndex};
 
 /// The value of a WebAssembly global variable.
 #[derive(Clone, Copy)]
@@ -193,6 +193,91 @@ pub trait FuncEnvironment: TargetEnvironment {
         Ok(pos.ins().call(callee, call_args))
     }
 
+    /// Translate a `call` WebAssembly instruction inside a `try` block at `pos`.
+    ///
+    /// Insert instructions at `pos` for a direct call to the function `callee_index` that
+    /// catches the exceptions it throws.
+    ///
+    /// Return the caught exception, which is null if the call returned normally, and the
+    /// WebAssembly return values, which are only meaningful in that case.
+    fn translate_try_call(
+        &mut self,
+        pos: FuncCursor,
+        callee_index: FunctionIndex,
+        callee: ir::FuncRef,
+        call_args: &[ir::Value],
+    ) -> WasmResult<(ir::Value, Vec<ir::Value>)>;
+
+    /// Translate a `call_indirect` WebAssembly instruction inside a `try` block at `pos`.
+    ///
+    /// Insert instructions at `pos` for an indirect call to the function `callee` in the table
+    /// `table_index` with WebAssembly signature `sig_index`, that catches the exceptions it
+    /// throws.
+    ///
+    /// Return the caught exception, which is null if the call returned normally, and the
+    /// WebAssembly return values, which are only meaningful in that case.
+    #[allow(clippy::too_many_arguments)]
+    fn translate_try_call_indirect(
+        &mut self,
+        pos: FuncCursor,
+        table_index: TableIndex,
+        table: ir::Table,
+        sig_index: SignatureIndex,
+        sig_ref: ir::SigRef,
+        callee: ir::Value,
+        call_args: &[ir::Value],
+    ) -> WasmResult<(ir::Value, Vec<ir::Value>)>;
+
+    /// Get the number of values carried by the exceptions of the tag `index`.
+    fn num_tag_params(&self, index: TagIndex) -> usize;
+
+    /// Create the exception of a `throw` WebAssembly instruction, carrying `values`.
+    ///
+    /// Returns a pointer to the exception.
+    fn translate_exception_new(
+        &mut self,
+        pos: FuncCursor,
+        index: TagIndex,
+        values: &[ir::Value],
+    ) -> WasmResult<ir::Value>;
+
+    /// Check whether `exception` was thrown with the tag `index`, for a `catch` WebAssembly
+    /// instruction.
+    ///
+    /// Returns an i32, which is non-zero if it was.
+    fn translate_exception_matches(
+        &mut self,
+        pos: FuncCursor,
+        exception: ir::Value,
+        index: TagIndex,
+    ) -> WasmResult<ir::Value>;
+
+    /// Take the values carried by `exception`, which was thrown with the tag `index`. The
+    /// exception is freed, and can't be used afterwards.
+    fn translate_exception_take_payload(
+        &mut self,
+        pos: FuncCursor,
+        exception: ir::Value,
+        index: TagIndex,
+    ) -> WasmResult<Vec<ir::Value>>;
+
+    /// Throw `exception` out of the function, when no `try` block of the function catches it.
+    ///
+    /// The call doesn't return, so the caller must end the block after it.
+    fn translate_exception_throw(
+        &mut self,
+        pos: FuncCursor,
+        exception: ir::Value,
+    ) -> WasmResult<()>;
+
+    /// Free `exception` without throwing it, when the `unwind` or `catch_all` clause holding it
+    /// is left by a branch, or at the end of a `catch_all` clause.
+    fn translate_exception_delete(
+        &mut self,
+        pos: FuncCursor,
+        exception: ir::Value,
+    ) -> WasmResult<()>;
+
     /// Translate a `memory.grow` WebAssembly instruction.
     ///
     /// The `index` provided identifies the linear memory to grow, and `heap` is the heap reference
@@ -410,6 +495,7 @@ pub trait FuncEnvironment: TargetEnvironment {
     /// to wait on, and `heap` is the heap reference returned by `make_heap`
     /// for the same index.  Whether the waited-on value is 32- or 64-bit can be
     /// determined by examining the type of `expected`, which must be only I32 or I64.
+    /// `addr` is the 64-bit effective address, including the static offset.
     ///
     /// Returns an i32, which is negative if the helper call failed.
     fn translate_atomic_wait(
@@ -425,9 +511,10 @@ pub trait FuncEnvironment: TargetEnvironment {
     /// Translate an `atomic.notify` WebAssembly instruction.
     /// The `index` provided identifies the linear memory containing the value
     /// to wait on, and `heap` is the heap reference returned by `make_heap`
-    /// for the same index.
+    /// for the same index. `addr` is the 64-bit effective address, including
+    /// the static offset.
     ///
-    /// Returns an i64, which is negative if the helper call failed.
+    /// Returns an i32, the number of woken waiters.
     fn translate_atomic_notify(
         &mut self,
         pos: FuncCursor,
@@ -437,6 +524,57 @@ pub trait FuncEnvironment: TargetEnvironment {
         count: ir::Value,
     ) -> WasmResult<ir::Value>;
 
+    /// Whether the target lowers the `atomic_load`, `atomic_store`,
+    /// `atomic_rmw` and `atomic_cas` instructions. If it doesn't, the atomic
+    /// memory accesses are translated with `translate_atomic_load`,
+    /// `translate_atomic_store`, `translate_atomic_rmw` and
+    /// `translate_atomic_cas` instead.
+    fn has_native_atomics(&self) -> bool {
+        true
+    }
+
+    /// Translate an atomic load of type `access_ty` from the native address
+    /// `addr`, which is already bounds-checked and aligned.
+    fn translate_atomic_load(
+        &mut self,
+        pos: FuncCursor,
+        access_ty: ir::Type,
+        addr: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
+    /// Translate an atomic store of `value` to the native address `addr`,
+    /// which is already bounds-checked and aligned.
+    fn translate_atomic_store(
+        &mut self,
+        pos: FuncCursor,
+        addr: ir::Value,
+        value: ir::Value,
+    ) -> WasmResult<()>;
+
+    /// Translate an atomic read-modify-write `op` of `value` at the native
+    /// address `addr`, which is already bounds-checked and aligned.
+    ///
+    /// Returns the previous value, of the type of `value`.
+    fn translate_atomic_rmw(
+        &mut self,
+        pos: FuncCursor,
+        op: ir::AtomicRmwOp,
+        addr: ir::Value,
+        value: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
+    /// Translate an atomic compare-and-exchange at the native address `addr`,
+    /// which is already bounds-checked and aligned.
+    ///
+    /// Returns the previous value, of the type of `expected`.
+    fn translate_atomic_cas(
+        &mut self,
+        pos: FuncCursor,
+        addr: ir::Value,
+        expected: ir::Value,
+        replacement: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
     /// Emit code at the beginning of every wasm loop.
     ///
     /// This can be used to insert explicit interrupt or safepoint checking at
@@ -14,7 +14,7 @@ use crate::{HashMap, Occupied, Vacant};
 use cranelift_codegen::ir::{self, Block, Inst, Value};
 use std::vec::Vec;
 use wasmer_compiler::WasmResult;
-use wasmer_types::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex};
+use wasmer_types::{FunctionIndex, GlobalIndex, MemoryIndex, SignatureIndex, TableIndex, TagIndex};
 
 /// Information about the presence of an associated `else` for an `if`, or the
 /// lack thereof.
@@ -43,6 +43,48 @@ pub enum ElseData {
     },
 }
 
+/// The part of a `try` block being translated.
+#[derive(Debug)]
+pub enum TryClause {
+    /// The body of the `try`, whose exceptions go to its handler.
+    Body,
+
+    /// A `catch` clause.
+    Catch {
+        /// The block that checks the exception against the next clause
+        /// when it doesn't match this one. It takes the exception as its
+        /// only parameter.
+        next: Block,
+        /// The tag of the caught exception.
+        tag: TagIndex,
+        /// The values carried by the caught exception, to create it again
+        /// on `rethrow`.
+        payload: Vec<Value>,
+    },
+
+    /// A `catch_all` clause, which frees the exception at its end.
+    CatchAll {
+        /// The caught exception, to throw it again on `rethrow`.
+        exception: Value,
+    },
+
+    /// An `unwind` clause, which throws the exception again at its end.
+    Unwind {
+        /// The exception being unwound.
+        exception: Value,
+    },
+}
+
+impl TryClause {
+    /// Get the exception held by this clause until its end.
+    pub(crate) fn held_exception(&self) -> Option<Value> {
+        match *self {
+            Self::CatchAll { exception } | Self::Unwind { exception } => Some(exception),
+            Self::Body | Self::Catch { .. } => None,
+        }
+    }
+}
+
 /// A control stack frame can be an `if`, a `block` or a `loop`, each one having the following
 /// fields:
 ///
@@ -52,7 +94,9 @@ pub enum ElseData {
 ///
 /// Moreover, the `if` frame has the `branch_inst` field that points to the `brz` instruction
 /// separating the `true` and `false` branch. The `loop` frame has a `header` field that references
-/// the `Block` that contains the beginning of the body of the loop.
+/// the `Block` that contains the beginning of the body of the loop. The `try` frame has a `handler`
+/// field that references the `Block` the exceptions thrown in its body are sent to, and a `clause`
+/// field for the part of the `try` being translated.
 #[derive(Debug)]
 pub enum ControlStackFrame {
     If {
@@ -89,6 +133,19 @@ pub enum ControlStackFrame {
         num_return_values: usize,
         original_stack_size: usize,
     },
+    Try {
+        destination: Block,
+        num_param_values: usize,
+        num_return_values: usize,
+        original_stack_size: usize,
+        exit_is_branched_to: bool,
+        /// The block the exceptions thrown in the body are sent to. It takes the exception as its
+        /// only parameter.
+        handler: Block,
+        /// Was an exception sent to the `handler`?
+        handler_is_branched_to: bool,
+        clause: TryClause,
+    },
 }
 
 /// Helper methods for the control stack objects.
@@ -103,6 +160,9 @@ impl ControlStackFrame {
             }
             | Self::Loop {
                 num_return_values, ..
+            }
+            | Self::Try {
+                num_return_values, ..
             } => num_return_values,
         }
     }
@@ -116,6 +176,9 @@ impl ControlStackFrame {
             }
             | Self::Loop {
                 num_param_values, ..
+            }
+            | Self::Try {
+                num_param_values, ..
             } => num_param_values,
         }
     }
@@ -123,12 +186,15 @@ impl ControlStackFrame {
         match *self {
             Self::If { destination, .. }
             | Self::Block { destination, .. }
-            | Self::Loop { destination, .. } => destination,
+            | Self::Loop { destination, .. }
+            | Self::Try { destination, .. } => destination,
         }
     }
     pub fn br_destination(&self) -> Block {
         match *self {
-            Self::If { destination, .. } | Self::Block { destination, .. } => destination,
+            Self::If { destination, .. }
+            | Self::Block { destination, .. }
+            | Self::Try { destination, .. } => destination,
             Self::Loop { header, .. } => header,
         }
     }
@@ -147,12 +213,16 @@ impl ControlStackFrame {
             | Self::Loop {
                 original_stack_size,
                 ..
+            }
+            | Self::Try {
+                original_stack_size,
+                ..
             } => original_stack_size,
         }
     }
     pub fn is_loop(&self) -> bool {
         match *self {
-            Self::If { .. } | Self::Block { .. } => false,
+            Self::If { .. } | Self::Block { .. } | Self::Try { .. } => false,
             Self::Loop { .. } => true,
         }
     }
@@ -166,6 +236,10 @@ impl ControlStackFrame {
             | Self::Block {
                 exit_is_branched_to,
                 ..
+            }
+            | Self::Try {
+                exit_is_branched_to,
+                ..
             } => exit_is_branched_to,
             Self::Loop { .. } => false,
         }
@@ -180,6 +254,10 @@ impl ControlStackFrame {
             | Self::Block {
                 ref mut exit_is_branched_to,
                 ..
+            }
+            | Self::Try {
+                ref mut exit_is_branched_to,
+                ..
             } => *exit_is_branched_to = true,
             Self::Loop { .. } => {}
         }
@@ -227,6 +305,11 @@ pub struct FuncTranslationState {
     /// Is the current translation state still reachable? This is false when translating operators
     /// like End, Return, or Unreachable.
     pub(crate) reachable: bool,
+    /// The block the function body starts in, after the locals are declared. Tail calls to the
+    /// function itself jump back to it.
+    pub(crate) body_block: Option<Block>,
+    /// The value each local starts with, in order. The first ones are the function parameters.
+    pub(crate) local_inits: Vec<Value>,
 
     // Map of global variables that have already been created by `FuncEnvironment::make_global`.
     globals: HashMap<GlobalIndex, GlobalVariable>,
@@ -265,6 +348,8 @@ impl FuncTranslationState {
             stack: Vec::new(),
             control_stack: Vec::new(),
             reachable: true,
+            body_block: None,
+            local_inits: Vec::new(),
             globals: HashMap::new(),
             heaps: HashMap::new(),
             tables: HashMap::new(),
@@ -277,6 +362,8 @@ impl FuncTranslationState {
         debug_assert!(self.stack.is_empty());
         debug_assert!(self.control_stack.is_empty());
         self.reachable = true;
+        self.body_block = None;
+        self.local_inits.clear();
         self.globals.clear();
         self.heaps.clear();
         self.tables.clear();
@@ -391,6 +478,65 @@ impl FuncTranslationState {
         });
     }
 
+    /// Push a try on the control stack.
+    pub(crate) fn push_try(
+        &mut self,
+        following_code: Block,
+        handler: Block,
+        num_param_types: usize,
+        num_result_types: usize,
+    ) {
+        debug_assert!(num_param_types <= self.stack.len());
+        self.control_stack.push(ControlStackFrame::Try {
+            destination: following_code,
+            original_stack_size: self.stack.len() - num_param_types,
+            num_param_values: num_param_types,
+            num_return_values: num_result_types,
+            exit_is_branched_to: false,
+            handler,
+            handler_is_branched_to: false,
+            clause: TryClause::Body,
+        });
+    }
+
+    /// Get the handler of the innermost `try` block among the `depth` outermost frames whose body
+    /// is being translated, which the exceptions thrown at this point are sent to, and record
+    /// that it is used. The exceptions held by the clauses of all the frames above it are
+    /// returned too, since sending an exception to the handler leaves these clauses.
+    ///
+    /// The handler is `None` if the exceptions are thrown out of the function.
+    pub(crate) fn exception_target(&mut self, depth: usize) -> (Option<Block>, Vec<Value>) {
+        let mut held = self.held_exceptions(depth);
+        for frame in self.control_stack[..depth].iter_mut().rev() {
+            if let ControlStackFrame::Try {
+                handler,
+                handler_is_branched_to,
+                clause,
+                ..
+            } = frame
+            {
+                if let TryClause::Body = clause {
+                    *handler_is_branched_to = true;
+                    return (Some(*handler), held);
+                }
+                held.extend(clause.held_exception());
+            }
+        }
+        (None, held)
+    }
+
+    /// Get the exceptions held by the clauses of the frames from the `depth`-th outermost one,
+    /// which must be freed by a branch leaving these frames.
+    pub(crate) fn held_exceptions(&self, depth: usize) -> Vec<Value> {
+        self.control_stack[depth..]
+            .iter()
+            .filter_map(|frame| match frame {
+                ControlStackFrame::Try { clause, .. } => clause.held_exception(),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Push a loop on the control stack.
     pub(crate) fn push_loop(
         &mut self,
@@ -7,7 +7,9 @@
 //! function to Cranelift IR guided by a `FuncEnvironment` which provides information about the
 //! WebAssembly module and the runtime environment.
 
-use super::code_translator::{bitcast_arguments, translate_operator, wasm_param_types};
+use super::code_translator::{
+    bitcast_arguments, translate_delegate, translate_operator, wasm_param_types,
+};
 use super::func_environ::{FuncEnvironment, ReturnMode};
 use super::func_state::FuncTranslationState;
 use super::translation_utils::get_vmctx_value_label;
@@ -100,7 +102,8 @@ impl FuncTranslator {
 
         // This clears the `FunctionBuilderContext`.
         let mut builder = FunctionBuilder::new(func, &mut self.func_ctx);
-        builder.set_srcloc(cur_srcloc(&reader));
+        let srcloc = cur_srcloc(&reader);
+        builder.set_srcloc(srcloc);
         let entry_block = builder.create_block();
         builder.append_block_params_for_function_params(entry_block);
         builder.switch_to_block(entry_block); // This also creates values for the arguments.
@@ -110,7 +113,7 @@ impl FuncTranslator {
         // `environ`. The callback functions may need to insert things in the entry block.
         builder.ensure_inserted_block();
 
-        let num_params = declare_wasm_parameters(&mut builder, entry_block, environ);
+        let num_params = declare_wasm_parameters(&mut builder, entry_block, environ, srcloc);
 
         // Set up the translation state with a single pushed control block representing the whole
         // function and its return values.
@@ -118,7 +121,18 @@ impl FuncTranslator {
         builder.append_block_params_for_function_returns(exit_block);
         self.state.initialize(&builder.func.signature, exit_block);
 
-        parse_local_decls(&mut reader, &mut builder, num_params, environ)?;
+        let num_locals = parse_local_decls(&mut reader, &mut builder, num_params, environ)?;
+
+        // Start the body in its own block, so that tail calls to this function can jump back to
+        // it with new parameters.
+        self.state.local_inits = (0..num_locals)
+            .map(|i| builder.use_var(Variable::new(i)))
+            .collect();
+        let body_block = builder.create_block();
+        builder.ins().jump(body_block, &[]);
+        builder.switch_to_block(body_block);
+        self.state.body_block = Some(body_block);
+
         parse_function_body(
             module_translation_state,
             reader,
@@ -126,6 +140,7 @@ impl FuncTranslator {
             &mut self.state,
             environ,
         )?;
+        builder.seal_block(body_block);
 
         builder.finalize();
         Ok(())
@@ -139,10 +154,17 @@ fn declare_wasm_parameters<FE: FuncEnvironment + ?Sized>(
     builder: &mut FunctionBuilder,
     entry_block: Block,
     environ: &FE,
+    srcloc: ir::SourceLoc,
 ) -> usize {
     let sig_len = builder.func.signature.params.len();
     let mut next_local = 0;
     for i in 0..sig_len {
+        // Cranelift only tracks one value label per source location, so
+        // the parameters are labelled at distinct locations preceding the
+        // body.
+        builder.set_srcloc(ir::SourceLoc::new(
+            srcloc.bits().saturating_sub((sig_len - 1 - i) as u32),
+        ));
         let param_type = builder.func.signature.params[i];
         // There may be additional special-purpose parameters in addition to the normal WebAssembly
         // signature parameters. For example, a `vmctx` pointer.
@@ -154,12 +176,14 @@ fn declare_wasm_parameters<FE: FuncEnvironment + ?Sized>(
 
             let param_value = builder.block_params(entry_block)[i];
             builder.def_var(local, param_value);
+            builder.set_val_label(param_value, ValueLabel::new(local.index()));
         }
         if param_type.purpose == ir::ArgumentPurpose::VMContext {
             let param_value = builder.block_params(entry_block)[i];
             builder.set_val_label(param_value, get_vmctx_value_label());
         }
     }
+    builder.set_srcloc(srcloc);
 
     next_local
 }
@@ -167,12 +191,14 @@ fn declare_wasm_parameters<FE: FuncEnvironment + ?Sized>(
 /// Parse the local variable declarations that precede the function body.
 ///
 /// Declare local variables, starting from `num_params`.
+///
+/// Return the total number of local variables, including the parameters.
 fn parse_local_decls<FE: FuncEnvironment + ?Sized>(
     reader: &mut MiddlewareBinaryReader,
     builder: &mut FunctionBuilder,
     num_params: usize,
     environ: &mut FE,
-) -> WasmResult<()> {
+) -> WasmResult<usize> {
     let mut next_local = num_params;
     let local_count = reader.read_local_count()?;
 
@@ -182,7 +208,7 @@ fn parse_local_decls<FE: FuncEnvironment + ?Sized>(
         declare_locals(builder, count, ty, &mut next_local, environ)?;
     }
 
-    Ok(())
+    Ok(next_local)
 }
 
 /// Declare `count` local variables of the same type, starting from `next_local`.
@@ -206,8 +232,7 @@ fn declare_locals<FE: FuncEnvironment + ?Sized>(
             let constant_handle = builder.func.dfg.constants.insert([0; 16].to_vec().into());
             builder.ins().vconst(ir::types::I8X16, constant_handle)
         }
-        ExternRef => builder.ins().null(environ.reference_type()),
-        FuncRef => builder.ins().null(environ.reference_type()),
+        ExternRef | FuncRef => builder.ins().iconst(environ.pointer_type(), 0),
         ty => return Err(wasm_unsupported!("unsupported local type {:?}", ty)),
     };
 
@@ -238,8 +263,13 @@ fn parse_function_body<FE: FuncEnvironment + ?Sized>(
 
     // Keep going until the final `End` operator which pops the outermost block.
     while !state.control_stack.is_empty() {
-        builder.set_srcloc(cur_srcloc(&reader));
+        if let Some(relative_depth) = reader.read_delegate()? {
+            builder.set_srcloc(ir::SourceLoc::new(reader.current_operator_offset() as u32));
+            translate_delegate(relative_depth, builder, state, environ)?;
+            continue;
+        }
         let op = reader.read_operator()?;
+        builder.set_srcloc(ir::SourceLoc::new(reader.current_operator_offset() as u32));
         environ.before_translate_operator(&op, builder, state)?;
         translate_operator(module_translation_state, &op, builder, state, environ)?;
         environ.after_translate_operator(&op, builder, state)?;
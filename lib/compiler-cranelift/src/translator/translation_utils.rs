@@ -58,7 +58,9 @@ pub fn type_to_irtype(ty: Type, target_config: TargetFrontendConfig) -> WasmResu
         Type::F32 => Ok(ir::types::F32),
         Type::F64 => Ok(ir::types::F64),
         Type::V128 => Ok(ir::types::I8X16),
-        Type::ExternRef | Type::FuncRef => reference_type(target_config),
+        // `funcref`s are pointers to a `VMCallerCheckedAnyfunc`, or null, and
+        // `externref`s are opaque words the host gives a meaning to.
+        Type::FuncRef | Type::ExternRef => Ok(target_config.pointer_type()),
         // ty => Err(wasm_unsupported!("type_to_type: wasm type {:?}", ty)),
     }
 }
@@ -113,8 +115,8 @@ pub fn block_with_params<PE: TargetEnvironment + ?Sized>(
             wasmparser::Type::F64 => {
                 builder.append_block_param(block, ir::types::F64);
             }
-            wasmparser::Type::ExternRef | wasmparser::Type::FuncRef => {
-                builder.append_block_param(block, environ.reference_type());
+            wasmparser::Type::FuncRef | wasmparser::Type::ExternRef => {
+                builder.append_block_param(block, environ.pointer_type());
             }
             wasmparser::Type::V128 => {
                 builder.append_block_param(block, ir::types::I8X16);
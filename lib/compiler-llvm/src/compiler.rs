@@ -9,7 +9,6 @@ use inkwell::targets::FileType;
 use inkwell::DLLStorageClass;
 use rayon::iter::ParallelBridge;
 use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
-use std::sync::Arc;
 use wasmer_compiler::{
     Compilation, CompileError, CompileModuleInfo, Compiler, CustomSection, CustomSectionProtection,
     Dwarf, FunctionBodyData, ModuleMiddlewareChain, ModuleTranslationState, RelocationTarget,
@@ -17,6 +16,7 @@ use wasmer_compiler::{
 };
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{FunctionIndex, LocalFunctionIndex, SignatureIndex};
+use wasmer_vm::ModuleInfo;
 
 //use std::sync::Mutex;
 
@@ -189,6 +189,20 @@ impl LLVMCompiler {
 }
 
 impl Compiler for LLVMCompiler {
+    fn transform_module_info(&self, module: &mut ModuleInfo) {
+        self.config.middlewares.apply_on_module_info(module);
+    }
+
+    fn analyze_function_bodies(
+        &self,
+        module: &ModuleInfo,
+        function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) {
+        self.config
+            .middlewares
+            .apply_on_function_bodies(module, function_body_inputs);
+    }
+
     fn experimental_native_compile_module<'data, 'module>(
         &self,
         target: &Target,
@@ -200,10 +214,6 @@ impl Compiler for LLVMCompiler {
         // The metadata to inject into the wasmer_metadata section of the object file.
         wasmer_metadata: &[u8],
     ) -> Option<Result<Vec<u8>, CompileError>> {
-        let mut module = (*compile_info.module).clone();
-        self.config.middlewares.apply_on_module_info(&mut module);
-        compile_info.module = Arc::new(module);
-
         Some(self.compile_native_object(
             target,
             compile_info,
@@ -226,18 +236,11 @@ impl Compiler for LLVMCompiler {
         //let data = Arc::new(Mutex::new(0));
         let memory_styles = &compile_info.memory_styles;
         let table_styles = &compile_info.table_styles;
-
-        let mut module = (*compile_info.module).clone();
-        self.config.middlewares.apply_on_module_info(&mut module);
-        compile_info.module = Arc::new(module);
         let module = &compile_info.module;
 
         // TODO: merge constants in sections.
 
-        let mut module_custom_sections = PrimaryMap::new();
-        let mut frame_section_bytes = vec![];
-        let mut frame_section_relocations = vec![];
-        let functions = function_body_inputs
+        let compiled_functions = function_body_inputs
             .iter()
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
@@ -261,75 +264,8 @@ impl Compiler for LLVMCompiler {
                     )
                 },
             )
-            .collect::<Result<Vec<_>, CompileError>>()?
-            .into_iter()
-            .map(|mut compiled_function| {
-                let first_section = module_custom_sections.len() as u32;
-                for (section_index, custom_section) in compiled_function.custom_sections.iter() {
-                    // TODO: remove this call to clone()
-                    let mut custom_section = custom_section.clone();
-                    for mut reloc in &mut custom_section.relocations {
-                        if let RelocationTarget::CustomSection(index) = reloc.reloc_target {
-                            reloc.reloc_target = RelocationTarget::CustomSection(
-                                SectionIndex::from_u32(first_section + index.as_u32()),
-                            )
-                        }
-                    }
-                    if compiled_function
-                        .eh_frame_section_indices
-                        .contains(&section_index)
-                    {
-                        let offset = frame_section_bytes.len() as u32;
-                        for mut reloc in &mut custom_section.relocations {
-                            reloc.offset += offset;
-                        }
-                        frame_section_bytes.extend_from_slice(custom_section.bytes.as_slice());
-                        frame_section_relocations.extend(custom_section.relocations);
-                        // TODO: we do this to keep the count right, remove it.
-                        module_custom_sections.push(CustomSection {
-                            protection: CustomSectionProtection::Read,
-                            bytes: SectionBody::new_with_vec(vec![]),
-                            relocations: vec![],
-                        });
-                    } else {
-                        module_custom_sections.push(custom_section);
-                    }
-                }
-                for mut reloc in &mut compiled_function.compiled_function.relocations {
-                    if let RelocationTarget::CustomSection(index) = reloc.reloc_target {
-                        reloc.reloc_target = RelocationTarget::CustomSection(
-                            SectionIndex::from_u32(first_section + index.as_u32()),
-                        )
-                    }
-                }
-                compiled_function.compiled_function
-            })
-            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
-
-        let dwarf = if !frame_section_bytes.is_empty() {
-            let dwarf = Some(Dwarf::new(SectionIndex::from_u32(
-                module_custom_sections.len() as u32,
-            )));
-            // Terminating zero-length CIE.
-            frame_section_bytes.extend(vec![
-                0x00, 0x00, 0x00, 0x00, // Length
-                0x00, 0x00, 0x00, 0x00, // CIE ID
-                0x10, // Version (must be 1)
-                0x00, // Augmentation data
-                0x00, // Code alignment factor
-                0x00, // Data alignment factor
-                0x00, // Return address register
-                0x00, 0x00, 0x00, // Padding to a multiple of 4 bytes
-            ]);
-            module_custom_sections.push(CustomSection {
-                protection: CustomSectionProtection::Read,
-                bytes: SectionBody::new_with_vec(frame_section_bytes),
-                relocations: frame_section_relocations,
-            });
-            dwarf
-        } else {
-            None
-        };
+            .collect::<Result<Vec<_>, CompileError>>()?;
+        let (functions, module_custom_sections, dwarf) = gather_custom_sections(compiled_functions);
 
         let function_call_trampolines = module
             .signatures
@@ -372,4 +308,117 @@ impl Compiler for LLVMCompiler {
             dwarf,
         ))
     }
+
+    fn compile_function<'data, 'module>(
+        &self,
+        target: &Target,
+        compile_info: &'module mut CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'data>,
+    ) -> Result<Compilation, CompileError> {
+        let target_machine = self.config().target_machine(target);
+        let compiled_function = FuncTranslator::new(target_machine).translate(
+            &compile_info.module,
+            module_translation,
+            &index,
+            input,
+            self.config(),
+            &compile_info.memory_styles,
+            &compile_info.table_styles,
+            &ShortNames {},
+        )?;
+        let (functions, custom_sections, dwarf) = gather_custom_sections(vec![compiled_function]);
+        Ok(Compilation::new(
+            functions,
+            custom_sections,
+            PrimaryMap::new(),
+            PrimaryMap::new(),
+            dwarf,
+        ))
+    }
+}
+
+/// Gather the custom sections of the compiled functions, merging their
+/// `.eh_frame` sections into one.
+fn gather_custom_sections(
+    compiled_functions: Vec<crate::object_file::CompiledFunction>,
+) -> (
+    PrimaryMap<LocalFunctionIndex, wasmer_compiler::CompiledFunction>,
+    PrimaryMap<SectionIndex, CustomSection>,
+    Option<Dwarf>,
+) {
+    let mut module_custom_sections = PrimaryMap::new();
+    let mut frame_section_bytes = vec![];
+    let mut frame_section_relocations = vec![];
+    let functions = compiled_functions
+        .into_iter()
+        .map(|mut compiled_function| {
+            let first_section = module_custom_sections.len() as u32;
+            for (section_index, custom_section) in compiled_function.custom_sections.iter() {
+                // TODO: remove this call to clone()
+                let mut custom_section = custom_section.clone();
+                for mut reloc in &mut custom_section.relocations {
+                    if let RelocationTarget::CustomSection(index) = reloc.reloc_target {
+                        reloc.reloc_target = RelocationTarget::CustomSection(
+                            SectionIndex::from_u32(first_section + index.as_u32()),
+                        )
+                    }
+                }
+                if compiled_function
+                    .eh_frame_section_indices
+                    .contains(&section_index)
+                {
+                    let offset = frame_section_bytes.len() as u32;
+                    for mut reloc in &mut custom_section.relocations {
+                        reloc.offset += offset;
+                    }
+                    frame_section_bytes.extend_from_slice(custom_section.bytes.as_slice());
+                    frame_section_relocations.extend(custom_section.relocations);
+                    // TODO: we do this to keep the count right, remove it.
+                    module_custom_sections.push(CustomSection {
+                        protection: CustomSectionProtection::Read,
+                        bytes: SectionBody::new_with_vec(vec![]),
+                        relocations: vec![],
+                    });
+                } else {
+                    module_custom_sections.push(custom_section);
+                }
+            }
+            for mut reloc in &mut compiled_function.compiled_function.relocations {
+                if let RelocationTarget::CustomSection(index) = reloc.reloc_target {
+                    reloc.reloc_target = RelocationTarget::CustomSection(SectionIndex::from_u32(
+                        first_section + index.as_u32(),
+                    ))
+                }
+            }
+            compiled_function.compiled_function
+        })
+        .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+
+    let dwarf = if !frame_section_bytes.is_empty() {
+        let dwarf = Some(Dwarf::new(SectionIndex::from_u32(
+            module_custom_sections.len() as u32,
+        )));
+        // Terminating zero-length CIE.
+        frame_section_bytes.extend(vec![
+            0x00, 0x00, 0x00, 0x00, // Length
+            0x00, 0x00, 0x00, 0x00, // CIE ID
+            0x10, // Version (must be 1)
+            0x00, // Augmentation data
+            0x00, // Code alignment factor
+            0x00, // Data alignment factor
+            0x00, // Return address register
+            0x00, 0x00, 0x00, // Padding to a multiple of 4 bytes
+        ]);
+        module_custom_sections.push(CustomSection {
+            protection: CustomSectionProtection::Read,
+            bytes: SectionBody::new_with_vec(frame_section_bytes),
+            relocations: frame_section_relocations,
+        });
+        dwarf
+    } else {
+        None
+    };
+    (functions, module_custom_sections, dwarf)
 }
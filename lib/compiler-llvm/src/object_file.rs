@@ -299,6 +299,7 @@ where
             frame_info: CompiledFunctionFrameInfo {
                 address_map,
                 traps: vec![],
+                locals: vec![],
             },
         },
         custom_sections,
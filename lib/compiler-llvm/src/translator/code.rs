@@ -1,12 +1,14 @@
 use super::{
     intrinsics::{
-        tbaa_label, type_to_llvm, CtxType, FunctionCache, GlobalCache, Intrinsics, MemoryCache,
+        tbaa_label, type_to_llvm, type_to_llvm_ptr, CtxType, FunctionCache, GlobalCache,
+        Intrinsics, MemoryCache,
     },
     // stackmap::{StackmapEntry, StackmapEntryKind, StackmapRegistry, ValueSemantic},
-    state::{ControlFrame, ExtraInfo, IfElseState, State},
+    state::{ControlFrame, ExtraInfo, IfElseState, State, TryClause},
 };
 use inkwell::{
     attributes::AttributeLoc,
+    basic_block::BasicBlock,
     builder::Builder,
     context::Context,
     module::{Linkage, Module},
@@ -20,6 +22,7 @@ use inkwell::{
     AddressSpace, AtomicOrdering, AtomicRMWBinOp, DLLStorageClass, FloatPredicate, IntPredicate,
 };
 use smallvec::SmallVec;
+use std::cmp;
 
 use crate::abi::{get_abi, Abi};
 use crate::config::{CompiledKind, LLVM};
@@ -32,9 +35,9 @@ use wasmer_compiler::{
 use wasmer_types::entity::PrimaryMap;
 use wasmer_types::{
     FunctionIndex, FunctionType, GlobalIndex, LocalFunctionIndex, MemoryIndex, SignatureIndex,
-    TableIndex, Type,
+    TableIndex, TagIndex, Type,
 };
-use wasmer_vm::{MemoryStyle, ModuleInfo, TableStyle, VMOffsets};
+use wasmer_vm::{MemoryStyle, ModuleInfo, TableStyle, VMBuiltinFunctionIndex, VMOffsets};
 
 const FUNCTION_SECTION: &str = "__TEXT,wasmer_function";
 
@@ -169,6 +172,7 @@ impl FuncTranslator {
         }
 
         let mut locals = vec![];
+        let mut local_inits = vec![];
         let num_locals = reader.read_local_count()?;
         for _ in 0..num_locals {
             let (count, ty) = reader.read_local_decl()?;
@@ -178,6 +182,7 @@ impl FuncTranslator {
                 let alloca = insert_alloca(ty, "local");
                 cache_builder.build_store(alloca, ty.const_zero());
                 locals.push(alloca);
+                local_inits.push(ty.const_zero());
             }
         }
 
@@ -192,6 +197,9 @@ impl FuncTranslator {
             state,
             function: func,
             locals: params_locals,
+            local_inits,
+            start_of_code,
+            func_index,
             ctx: CtxType::new(wasm_module, &func, &cache_builder, &*self.abi),
             unreachable_depth: 0,
             memory_styles,
@@ -199,6 +207,7 @@ impl FuncTranslator {
             module: &module,
             module_translation,
             wasm_module,
+            wasm_fn_type,
             symbol_registry,
             abi: &*self.abi,
         };
@@ -210,6 +219,10 @@ impl FuncTranslator {
         );
 
         while fcg.state.has_control_frames() {
+            if let Some(relative_depth) = reader.read_delegate()? {
+                fcg.translate_delegate(relative_depth)?;
+                continue;
+            }
             let pos = reader.current_position() as u32;
             let op = reader.read_operator()?;
             fcg.translate_operator(op, pos)?;
@@ -979,9 +992,11 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
         let context = &self.context;
         let function = &self.function;
 
-        // Compute the offset into the storage.
+        // Compute the offset into the storage. 64-bit memories already
+        // have 64-bit addresses, but adding to them may wrap around.
+        let memory64 = self.wasm_module.memories[memory_index].memory64;
         let imm_offset = intrinsics.i64_ty.const_int(memarg.offset as u64, false);
-        let var_offset = builder.build_int_z_extend(var_offset, intrinsics.i64_ty, "");
+        let var_offset = builder.build_int_z_extend_or_bit_cast(var_offset, intrinsics.i64_ty, "");
         let offset = builder.build_int_add(var_offset, imm_offset, "");
 
         // Look up the memory base (as pointer) and bounds (as unsigned integer).
@@ -1005,6 +1020,13 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                             IntPredicate::ULE,
                             intrinsics.i64_ty.const_int(minimum.bytes().0 as u64, false),
                         );
+                        let ptr_in_bounds = if memory64 {
+                            ptr_in_bounds.const_and(
+                                load_offset_end.const_int_compare(IntPredicate::UGE, var_offset),
+                            )
+                        } else {
+                            ptr_in_bounds
+                        };
                         if ptr_in_bounds.get_zero_extended_constant() == Some(1) {
                             Some(ptr_in_bounds)
                         } else {
@@ -1025,15 +1047,24 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                             format!("memory {} length", memory_index.as_u32()),
                             current_length.as_instruction_value().unwrap(),
                         );
-                        let current_length =
-                            builder.build_int_z_extend(current_length, intrinsics.i64_ty, "");
 
-                        builder.build_int_compare(
+                        let ptr_in_bounds = builder.build_int_compare(
                             IntPredicate::ULE,
                             load_offset_end,
                             current_length,
                             "",
-                        )
+                        );
+                        if memory64 {
+                            let no_wrap = builder.build_int_compare(
+                                IntPredicate::UGE,
+                                load_offset_end,
+                                var_offset,
+                                "",
+                            );
+                            builder.build_and(ptr_in_bounds, no_wrap, "")
+                        } else {
+                            ptr_in_bounds
+                        }
                     });
                     if !ptr_in_bounds.is_constant_int()
                         || ptr_in_bounds.get_zero_extended_constant().unwrap() != 1
@@ -1084,6 +1115,11 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     );
                     ptr_to_base
                 }
+                MemoryCache::Static { .. } if memory64 => {
+                    return Err(CompileError::Codegen(
+                        "64-bit memories can't use a static memory style".to_string(),
+                    ));
+                }
                 MemoryCache::Static { base_ptr } => base_ptr,
             };
         let value_ptr = unsafe { builder.build_gep(base_ptr, &[offset], "") };
@@ -1092,14 +1128,62 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             .into_pointer_value())
     }
 
+    /// The `u64` address in the linear memory that `memory.atomic.wait`
+    /// and `memory.atomic.notify` operate on, checked by the builtins.
+    fn atomic_builtin_address(
+        &self,
+        memarg: &MemoryImmediate,
+        addr: BasicValueEnum<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        let memory_index = MemoryIndex::from_u32(memarg.memory);
+        let addr = if self.wasm_module.memories[memory_index].memory64 {
+            addr.into_int_value()
+        } else {
+            self.builder
+                .build_int_z_extend(addr.into_int_value(), self.intrinsics.i64_ty, "")
+        };
+        let offset = self
+            .intrinsics
+            .i64_ty
+            .const_int(memarg.offset.into(), false);
+        self.builder
+            .build_int_add(addr, offset, "")
+            .as_basic_value_enum()
+    }
+
+    /// The builtin implementing `memory.atomic.wait` or `memory.atomic.notify`
+    /// on the memory of `memarg`, and the index of that memory to pass it.
+    fn atomic_builtin(
+        &self,
+        memarg: &MemoryImmediate,
+        local: VMBuiltinFunctionIndex,
+        imported: VMBuiltinFunctionIndex,
+    ) -> (VMBuiltinFunctionIndex, BasicValueEnum<'ctx>) {
+        let memory_index = MemoryIndex::from_u32(memarg.memory);
+        let (builtin, index) = match self.wasm_module.local_memory_index(memory_index) {
+            Some(local_index) => (local, local_index.as_u32()),
+            None => (imported, memory_index.as_u32()),
+        };
+        let index = self
+            .intrinsics
+            .i32_ty
+            .const_int(index.into(), false)
+            .as_basic_value_enum();
+        (builtin, index)
+    }
+
     fn trap_if_misaligned(&self, memarg: &MemoryImmediate, ptr: PointerValue<'ctx>) {
+        // `align` is the log2 of the alignment.
         let align = memarg.align;
+        if align == 0 {
+            return;
+        }
         let value = self
             .builder
             .build_ptr_to_int(ptr, self.intrinsics.i64_ty, "");
         let and = self.builder.build_and(
             value,
-            self.intrinsics.i64_ty.const_int((align - 1).into(), false),
+            self.intrinsics.i64_ty.const_int((1u64 << align) - 1, false),
             "misaligncheck",
         );
         let aligned =
@@ -1143,6 +1227,480 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
         self.builder.position_at_end(continue_block);
     }
 
+    /// Allocate a buffer of `count` 16-byte slots, in the layout the
+    /// function call trampolines use for arguments and results.
+    fn build_values_vec(&self, count: usize) -> PointerValue<'ctx> {
+        let values_vec = self.alloca_builder.build_array_alloca(
+            self.intrinsics.i128_ty,
+            self.intrinsics
+                .i32_ty
+                .const_int(cmp::max(count, 1) as u64, false),
+            "values_vec",
+        );
+        values_vec
+            .as_instruction_value()
+            .unwrap()
+            .set_alignment(16)
+            .unwrap();
+        values_vec
+    }
+
+    /// Store `values` in the slots of `values_vec`.
+    fn store_values(
+        &self,
+        values_vec: PointerValue<'ctx>,
+        types: &[Type],
+        values: &[BasicValueEnum<'ctx>],
+    ) -> Result<(), CompileError> {
+        for (i, (&ty, value)) in types.iter().zip(values.iter()).enumerate() {
+            let slot = self.values_vec_slot(values_vec, i, ty)?;
+            let value = self
+                .builder
+                .build_bitcast(*value, type_to_llvm(self.intrinsics, ty)?, "");
+            self.builder.build_store(slot, value);
+        }
+        Ok(())
+    }
+
+    /// Load values of `types` from the slots of `values_vec`.
+    fn load_values(
+        &self,
+        values_vec: PointerValue<'ctx>,
+        types: &[Type],
+    ) -> Result<Vec<BasicValueEnum<'ctx>>, CompileError> {
+        types
+            .iter()
+            .enumerate()
+            .map(|(i, &ty)| {
+                let slot = self.values_vec_slot(values_vec, i, ty)?;
+                Ok(self.builder.build_load(slot, ""))
+            })
+            .collect()
+    }
+
+    fn values_vec_slot(
+        &self,
+        values_vec: PointerValue<'ctx>,
+        index: usize,
+        ty: Type,
+    ) -> Result<PointerValue<'ctx>, CompileError> {
+        let slot = unsafe {
+            self.builder.build_in_bounds_gep(
+                values_vec,
+                &[self.intrinsics.i32_ty.const_int(index as u64, false)],
+                "",
+            )
+        };
+        Ok(self
+            .builder
+            .build_pointer_cast(slot, type_to_llvm_ptr(self.intrinsics, ty)?, ""))
+    }
+
+    /// Call `func` from the body of a `try` block, sending the exceptions it
+    /// throws to `handler`, and push its results.
+    ///
+    /// The call goes through the `try_call` builtin, which runs the callee
+    /// with the function call trampoline of `sigindex` and returns the
+    /// exception it threw, if any.
+    fn translate_try_call(
+        &mut self,
+        sigindex: SignatureIndex,
+        func: PointerValue<'ctx>,
+        callee_vmctx: PointerValue<'ctx>,
+        params: &[BasicValueEnum<'ctx>],
+        handler: Option<(BasicBlock<'ctx>, PhiValue<'ctx>)>,
+        held: &[PointerValue<'ctx>],
+    ) -> Result<(), CompileError> {
+        let func_type = &self.wasm_module.signatures[sigindex];
+        let values_vec = self.build_values_vec(cmp::max(
+            func_type.params().len(),
+            func_type.results().len(),
+        ));
+        self.store_values(values_vec, func_type.params(), params)?;
+
+        let try_call = self.ctx.builtin_function(
+            VMBuiltinFunctionIndex::get_try_call_index(),
+            self.intrinsics.try_call_ptr_ty,
+            self.intrinsics,
+        );
+        let func = self
+            .builder
+            .build_pointer_cast(func, self.intrinsics.i8_ptr_ty, "");
+        let callee_vmctx =
+            self.builder
+                .build_pointer_cast(callee_vmctx, self.intrinsics.ctx_ptr_ty, "");
+        let exception = self
+            .builder
+            .build_call(
+                try_call,
+                &[
+                    self.ctx.basic(),
+                    self.intrinsics
+                        .i32_ty
+                        .const_int(sigindex.as_u32().into(), false)
+                        .as_basic_value_enum(),
+                    func.as_basic_value_enum(),
+                    callee_vmctx.as_basic_value_enum(),
+                    values_vec.as_basic_value_enum(),
+                ],
+                "",
+            )
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+
+        let current_block = self
+            .builder
+            .get_insert_block()
+            .ok_or_else(|| CompileError::Codegen("not currently in a block".to_string()))?;
+        let continue_block = self
+            .context
+            .append_basic_block(self.function, "try_call_continue");
+        let no_exception = self.builder.build_is_null(exception, "");
+        match handler {
+            Some((handler, handler_phi)) if held.is_empty() => {
+                handler_phi.add_incoming(&[(&exception, current_block)]);
+                self.builder
+                    .build_conditional_branch(no_exception, continue_block, handler);
+            }
+            _ => {
+                // The exceptions held by the clauses left on the way to the
+                // handler are freed first.
+                let landing_block = self
+                    .context
+                    .append_basic_block(self.function, "try_call_landing");
+                self.builder
+                    .build_conditional_branch(no_exception, continue_block, landing_block);
+                self.builder.position_at_end(landing_block);
+                self.translate_exception_delete_all(held);
+                self.translate_send_exception(exception, handler)?;
+            }
+        }
+        self.builder.position_at_end(continue_block);
+
+        for result in self.load_values(values_vec, func_type.results())? {
+            self.state.push1(result);
+        }
+        Ok(())
+    }
+
+    /// Create an exception with tag `tag_index` carrying `values`.
+    fn translate_exception_new(
+        &mut self,
+        tag_index: TagIndex,
+        values: &[BasicValueEnum<'ctx>],
+    ) -> Result<PointerValue<'ctx>, CompileError> {
+        let tag_type = &self.wasm_module.signatures[self.wasm_module.tags[tag_index]];
+        let values_vec = self.build_values_vec(values.len());
+        self.store_values(values_vec, tag_type.params(), values)?;
+
+        let exception_new = self.ctx.builtin_function(
+            VMBuiltinFunctionIndex::get_exception_new_index(),
+            self.intrinsics.exception_new_ptr_ty,
+            self.intrinsics,
+        );
+        Ok(self
+            .builder
+            .build_call(
+                exception_new,
+                &[
+                    self.ctx.basic(),
+                    self.intrinsics
+                        .i32_ty
+                        .const_int(tag_index.as_u32().into(), false)
+                        .as_basic_value_enum(),
+                    values_vec.as_basic_value_enum(),
+                ],
+                "",
+            )
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value())
+    }
+
+    /// Throw `exception` to the handler of the innermost `try` block among
+    /// the `depth` outermost frames whose body is being translated, or out
+    /// of the function if there is none. The exceptions held by the clauses
+    /// left on the way are freed. This ends the current block.
+    fn translate_throw_exception(
+        &mut self,
+        exception: PointerValue<'ctx>,
+        depth: usize,
+    ) -> Result<(), CompileError> {
+        let (handler, mut held) = self.state.exception_target(depth);
+        // A `rethrow` throws the exception held by its clause.
+        held.retain(|held_exception| *held_exception != exception);
+        self.translate_exception_delete_all(&held);
+        self.translate_send_exception(exception, handler)
+    }
+
+    /// Branch to `handler` with `exception`, or throw it out of the function
+    /// if there is no handler. This ends the current block.
+    fn translate_send_exception(
+        &mut self,
+        exception: PointerValue<'ctx>,
+        handler: Option<(BasicBlock<'ctx>, PhiValue<'ctx>)>,
+    ) -> Result<(), CompileError> {
+        match handler {
+            Some((handler, handler_phi)) => {
+                let current_block = self
+                    .builder
+                    .get_insert_block()
+                    .ok_or_else(|| CompileError::Codegen("not currently in a block".to_string()))?;
+                handler_phi.add_incoming(&[(&exception, current_block)]);
+                self.builder.build_unconditional_branch(handler);
+            }
+            None => {
+                let exception_throw = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_exception_throw_index(),
+                    self.intrinsics.exception_throw_ptr_ty,
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    exception_throw,
+                    &[exception.as_basic_value_enum()],
+                    "throw",
+                );
+                self.builder.build_unreachable();
+            }
+        }
+        Ok(())
+    }
+
+    /// Free the `exceptions` held by the `unwind` and `catch_all` clauses
+    /// that are left.
+    fn translate_exception_delete_all(&mut self, exceptions: &[PointerValue<'ctx>]) {
+        if exceptions.is_empty() {
+            return;
+        }
+        let exception_delete = self.ctx.builtin_function(
+            VMBuiltinFunctionIndex::get_exception_delete_index(),
+            self.intrinsics.exception_delete_ptr_ty,
+            self.intrinsics,
+        );
+        for exception in exceptions {
+            self.builder
+                .build_call(exception_delete, &[exception.as_basic_value_enum()], "");
+        }
+    }
+
+    /// Get the exception received by the handler of a `try` block, once its
+    /// body is translated.
+    fn handler_exception(&self, handler_phi: PhiValue<'ctx>) -> PointerValue<'ctx> {
+        if handler_phi.count_incoming() != 0 {
+            handler_phi.as_basic_value().into_pointer_value()
+        } else {
+            // Nothing in the body throws, so the clauses are unreachable.
+            handler_phi.as_instruction().erase_from_basic_block();
+            self.intrinsics.i8_ptr_ty.const_null()
+        }
+    }
+
+    /// Start a clause of the innermost `try` block.
+    ///
+    /// The previous part of the block branches to the code following it if
+    /// it ends reachable. For a `catch` clause, the exception that the
+    /// previous clause didn't match is checked against its tag; if it
+    /// matches, the values it carries are pushed on the stack, and otherwise
+    /// it is sent to the next clause. The other clauses take any exception.
+    fn translate_try_clause(&mut self, kind: TryClauseKind) -> Result<(), CompileError> {
+        let current_block = self
+            .builder
+            .get_insert_block()
+            .ok_or_else(|| CompileError::Codegen("not currently in a block".to_string()))?;
+        if self.state.reachable {
+            let frame = self.state.frame_at_depth(0)?;
+            for phi in frame.phis().to_vec().iter().rev() {
+                let (value, info) = self.state.pop1_extra()?;
+                let value = self.apply_pending_canonicalization(value, info);
+                phi.add_incoming(&[(&value, current_block)]);
+            }
+            let frame = self.state.frame_at_depth(0)?;
+            self.builder.build_unconditional_branch(*frame.code_after());
+        }
+
+        let (exception, exception_block, stack_size_snapshot) =
+            match self.state.frame_at_depth(0)? {
+                ControlFrame::Try {
+                    handler,
+                    handler_phi,
+                    clause: TryClause::Body,
+                    stack_size_snapshot,
+                    ..
+                } => (
+                    self.handler_exception(*handler_phi),
+                    *handler,
+                    *stack_size_snapshot,
+                ),
+                ControlFrame::Try {
+                    clause:
+                        TryClause::Catch {
+                            next, exception, ..
+                        },
+                    stack_size_snapshot,
+                    ..
+                } => (*exception, *next, *stack_size_snapshot),
+                _ => {
+                    return Err(CompileError::Codegen(
+                        "catch, catch_all or unwind outside of a try block".to_string(),
+                    ))
+                }
+            };
+        self.state.stack.truncate(stack_size_snapshot);
+        self.builder.position_at_end(exception_block);
+
+        let new_clause = match kind {
+            TryClauseKind::Catch(tag) => {
+                let exception_matches = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_exception_matches_index(),
+                    self.intrinsics.exception_matches_ptr_ty,
+                    self.intrinsics,
+                );
+                let matches = self
+                    .builder
+                    .build_call(
+                        exception_matches,
+                        &[
+                            self.ctx.basic(),
+                            exception.as_basic_value_enum(),
+                            self.intrinsics
+                                .i32_ty
+                                .const_int(tag.as_u32().into(), false)
+                                .as_basic_value_enum(),
+                        ],
+                        "",
+                    )
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_int_value();
+                let matches = self.builder.build_int_compare(
+                    IntPredicate::NE,
+                    matches,
+                    self.intrinsics.i32_zero,
+                    "",
+                );
+                let catch_block = self.context.append_basic_block(self.function, "catch");
+                let next = self.context.append_basic_block(self.function, "catch_next");
+                self.builder
+                    .build_conditional_branch(matches, catch_block, next);
+                self.builder.position_at_end(catch_block);
+
+                // Taking the payload frees the exception.
+                let tag_type = &self.wasm_module.signatures[self.wasm_module.tags[tag]];
+                let values_vec = self.build_values_vec(tag_type.params().len());
+                let exception_take_payload = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_exception_take_payload_index(),
+                    self.intrinsics.exception_take_payload_ptr_ty,
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    exception_take_payload,
+                    &[
+                        exception.as_basic_value_enum(),
+                        values_vec.as_basic_value_enum(),
+                    ],
+                    "",
+                );
+                let payload = self.load_values(values_vec, tag_type.params())?;
+                for value in &payload {
+                    self.state.push1(*value);
+                }
+                TryClause::Catch {
+                    next,
+                    exception,
+                    tag,
+                    payload,
+                }
+            }
+            TryClauseKind::CatchAll => TryClause::CatchAll { exception },
+            TryClauseKind::Unwind => TryClause::Unwind { exception },
+        };
+        if let ControlFrame::Try { clause, .. } = self.state.frame_at_depth_mut(0)? {
+            *clause = new_clause;
+        }
+        self.state.reachable = true;
+        Ok(())
+    }
+
+    /// Finish the `try` block of `frame` at its `end` or `delegate`, once the
+    /// frame has been popped, by throwing the exceptions no clause matched
+    /// further, to the handler of the innermost `try` block among the
+    /// `depth` outermost frames.
+    fn translate_end_try(
+        &mut self,
+        frame: &ControlFrame<'ctx>,
+        depth: usize,
+    ) -> Result<(), CompileError> {
+        let (block, exception) = match frame {
+            ControlFrame::Try {
+                handler,
+                handler_phi,
+                clause: TryClause::Body,
+                ..
+            } => (*handler, self.handler_exception(*handler_phi)),
+            ControlFrame::Try {
+                clause: TryClause::Catch {
+                    next, exception, ..
+                },
+                ..
+            } => (*next, *exception),
+            _ => return Ok(()),
+        };
+        self.builder.position_at_end(block);
+        self.translate_throw_exception(exception, depth)
+    }
+
+    /// Translate the `delegate` instruction ending the body of the innermost
+    /// `try` block, which sends the exceptions thrown in the body to the
+    /// `try` block `relative_depth` labels out of it.
+    ///
+    /// wasmparser doesn't decode `delegate`, so it is read by the caller and
+    /// doesn't go through `translate_operator`.
+    fn translate_delegate(&mut self, relative_depth: u32) -> Result<(), CompileError> {
+        if !self.state.reachable && self.unreachable_depth != 0 {
+            self.unreachable_depth -= 1;
+            return Ok(());
+        }
+
+        let frame = self.state.pop_frame()?;
+        if self.state.reachable {
+            let current_block = self
+                .builder
+                .get_insert_block()
+                .ok_or_else(|| CompileError::Codegen("not currently in a block".to_string()))?;
+            for phi in frame.phis().iter().rev() {
+                let (value, info) = self.state.pop1_extra()?;
+                let value = self.apply_pending_canonicalization(value, info);
+                phi.add_incoming(&[(&value, current_block)]);
+            }
+            self.builder.build_unconditional_branch(*frame.code_after());
+        }
+
+        // The exceptions are sent to the handler of the `try` block at the
+        // label, or to the enclosing ones if it isn't a `try` block or its
+        // body is already translated.
+        let depth = self.state.num_control_frames() - relative_depth as usize;
+        self.translate_end_try(&frame, depth)?;
+
+        self.builder.position_at_end(*frame.code_after());
+        self.state.reset_stack(&frame);
+        self.state.reachable = true;
+        for phi in frame.phis() {
+            if phi.count_incoming() != 0 {
+                self.state.push1(phi.as_basic_value());
+            } else {
+                let basic_ty = phi.as_basic_value().get_type();
+                let placeholder_value = basic_ty.const_zero();
+                self.state.push1(placeholder_value);
+                phi.as_instruction().erase_from_basic_block();
+            }
+        }
+        Ok(())
+    }
+
     fn finalize(&mut self, wasm_fn_type: &FunctionType) -> Result<(), CompileError> {
         let func_type = self.function.get_type();
 
@@ -1275,6 +1833,17 @@ fn finalize_opcode_stack_map<'ctx>(
 }
  */
 
+/// The kind of a clause of a `try` block.
+#[derive(Debug, Clone, Copy)]
+enum TryClauseKind {
+    /// A `catch` clause for the tag.
+    Catch(TagIndex),
+    /// A `catch_all` clause.
+    CatchAll,
+    /// An `unwind` clause.
+    Unwind,
+}
+
 pub struct LLVMFunctionCodeGenerator<'ctx, 'a> {
     context: &'ctx Context,
     builder: Builder<'ctx>,
@@ -1283,6 +1852,9 @@ pub struct LLVMFunctionCodeGenerator<'ctx, 'a> {
     state: State<'ctx>,
     function: FunctionValue<'ctx>,
     locals: Vec<PointerValue<'ctx>>, // Contains params and locals
+    local_inits: Vec<BasicValueEnum<'ctx>>, // Initial values of the non-param locals
+    start_of_code: BasicBlock<'ctx>,
+    func_index: FunctionIndex,
     ctx: CtxType<'ctx, 'a>,
     unreachable_depth: usize,
     memory_styles: &'a PrimaryMap<MemoryIndex, MemoryStyle>,
@@ -1298,6 +1870,7 @@ pub struct LLVMFunctionCodeGenerator<'ctx, 'a> {
     module: &'a Module<'ctx>,
     module_translation: &'a ModuleTranslationState,
     wasm_module: &'a ModuleInfo,
+    wasm_fn_type: &'a FunctionType,
     symbol_registry: &'a dyn SymbolRegistry,
     abi: &'a dyn Abi,
 }
@@ -1312,11 +1885,14 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
         if !self.state.reachable {
             match op {
-                Operator::Block { ty: _ } | Operator::Loop { ty: _ } | Operator::If { ty: _ } => {
+                Operator::Block { ty: _ }
+                | Operator::Loop { ty: _ }
+                | Operator::If { ty: _ }
+                | Operator::Try { ty: _ } => {
                     self.unreachable_depth += 1;
                     return Ok(());
                 }
-                Operator::Else => {
+                Operator::Else | Operator::Catch { .. } | Operator::Unwind => {
                     if self.unreachable_depth != 0 {
                         return Ok(());
                     }
@@ -1443,6 +2019,8 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 self.state.push_loop(loop_body, loop_next, loop_phis, phis);
             }
             Operator::Br { relative_depth } => {
+                let held = self.state.held_exceptions_at_depth(relative_depth);
+                self.translate_exception_delete_all(&held);
                 let frame = self.state.frame_at_depth(relative_depth)?;
 
                 let current_block = self
@@ -1476,12 +2054,20 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::BrIf { relative_depth } => {
                 let cond = self.state.pop1()?;
+                let held = self.state.held_exceptions_at_depth(relative_depth);
                 let frame = self.state.frame_at_depth(relative_depth)?;
 
                 let current_block = self
                     .builder
                     .get_insert_block()
                     .ok_or_else(|| CompileError::Codegen("not currently in a block".to_string()))?;
+                // If the branch leaves `unwind` or `catch_all` clauses, it
+                // frees the exceptions they hold on its way.
+                let exit_block = if held.is_empty() {
+                    current_block
+                } else {
+                    self.context.append_basic_block(self.function, "br_if_exit")
+                };
 
                 let phis = if frame.is_loop() {
                     frame.loop_body_phis()
@@ -1495,9 +2081,10 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     .map(|(v, info)| self.apply_pending_canonicalization(*v, *info));
 
                 for (phi, value) in phis.iter().zip(param_stack) {
-                    phi.add_incoming(&[(&value, current_block)]);
+                    phi.add_incoming(&[(&value, exit_block)]);
                 }
 
+                let br_dest = *frame.br_dest();
                 let else_block = self.context.append_basic_block(self.function, "else");
 
                 let cond_value = self.builder.build_int_compare(
@@ -1506,8 +2093,14 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     self.intrinsics.i32_zero,
                     "",
                 );
+                let taken_block = if held.is_empty() { br_dest } else { exit_block };
                 self.builder
-                    .build_conditional_branch(cond_value, *frame.br_dest(), else_block);
+                    .build_conditional_branch(cond_value, taken_block, else_block);
+                if !held.is_empty() {
+                    self.builder.position_at_end(exit_block);
+                    self.translate_exception_delete_all(&held);
+                    self.builder.build_unconditional_branch(br_dest);
+                }
                 self.builder.position_at_end(else_block);
             }
             Operator::BrTable { ref table } => {
@@ -1521,7 +2114,26 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
                 let index = self.state.pop1()?;
 
+                // The branches leaving `unwind` or `catch_all` clauses go
+                // through their own blocks, which free the exceptions held
+                // by these clauses.
+                let mut exits = vec![];
+                let mut exit_block = |depth: u32, br_dest: BasicBlock<'ctx>| {
+                    let held = self.state.held_exceptions_at_depth(depth);
+                    if held.is_empty() {
+                        (current_block, br_dest)
+                    } else {
+                        let block = self
+                            .context
+                            .append_basic_block(self.function, "br_table_exit");
+                        exits.push((block, held, br_dest));
+                        (block, block)
+                    }
+                };
+
                 let default_frame = self.state.frame_at_depth(default_depth)?;
+                let (default_source, default_dest) =
+                    exit_block(default_depth, *default_frame.br_dest());
 
                 let phis = if default_frame.is_loop() {
                     default_frame.loop_body_phis()
@@ -1531,7 +2143,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let args = self.state.peekn(phis.len())?;
 
                 for (phi, value) in phis.iter().zip(args.iter()) {
-                    phi.add_incoming(&[(value, current_block)]);
+                    phi.add_incoming(&[(value, default_source)]);
                 }
 
                 let cases: Vec<_> = label_depths
@@ -1546,23 +2158,26 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                         };
                         let case_index_literal =
                             self.context.i32_type().const_int(case_index as u64, false);
+                        let (source, dest) = exit_block(depth, *frame.br_dest());
 
                         for (phi, value) in frame.phis().iter().zip(args.iter()) {
-                            phi.add_incoming(&[(value, current_block)]);
+                            phi.add_incoming(&[(value, source)]);
                         }
 
-                        Ok((case_index_literal, *frame.br_dest()))
+                        Ok((case_index_literal, dest))
                     })
                     .collect::<Result<_, _>>()?;
 
-                self.builder.build_switch(
-                    index.into_int_value(),
-                    *default_frame.br_dest(),
-                    &cases[..],
-                );
+                self.builder
+                    .build_switch(index.into_int_value(), default_dest, &cases[..]);
 
                 let args_len = args.len();
                 self.state.popn(args_len)?;
+                for (block, held, br_dest) in exits {
+                    self.builder.position_at_end(block);
+                    self.translate_exception_delete_all(&held);
+                    self.builder.build_unconditional_branch(br_dest);
+                }
                 self.state.reachable = false;
             }
             Operator::If { ty } => {
@@ -1648,6 +2263,10 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 );
             }
             Operator::Else => {
+                // wasmparser decodes `catch_all` as `else` inside a `try` block.
+                if let ControlFrame::Try { .. } = self.state.frame_at_depth(0)? {
+                    return self.translate_try_clause(TryClauseKind::CatchAll);
+                }
                 if self.state.reachable {
                     let frame = self.state.frame_at_depth(0)?;
                     let current_block = self.builder.get_insert_block().ok_or_else(|| {
@@ -1695,14 +2314,32 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     .get_insert_block()
                     .ok_or_else(|| CompileError::Codegen("not currently in a block".to_string()))?;
 
+                let depth = self.state.num_control_frames();
                 if self.state.reachable {
-                    for phi in frame.phis().iter().rev() {
-                        let (value, info) = self.state.pop1_extra()?;
-                        let value = self.apply_pending_canonicalization(value, info);
-                        phi.add_incoming(&[(&value, current_block)]);
-                    }
+                    if let ControlFrame::Try {
+                        clause: TryClause::Unwind { exception },
+                        ..
+                    } = &frame
+                    {
+                        // The end of an `unwind` clause throws its exception further.
+                        self.translate_throw_exception(*exception, depth)?;
+                    } else {
+                        if let ControlFrame::Try {
+                            clause: TryClause::CatchAll { exception },
+                            ..
+                        } = &frame
+                        {
+                            // The end of a `catch_all` clause frees its exception.
+                            self.translate_exception_delete_all(&[*exception]);
+                        }
+                        for phi in frame.phis().iter().rev() {
+                            let (value, info) = self.state.pop1_extra()?;
+                            let value = self.apply_pending_canonicalization(value, info);
+                            phi.add_incoming(&[(&value, current_block)]);
+                        }
 
-                    self.builder.build_unconditional_branch(*frame.code_after());
+                        self.builder.build_unconditional_branch(*frame.code_after());
+                    }
                 }
 
                 if let ControlFrame::IfElse {
@@ -1721,6 +2358,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                         self.builder.build_unconditional_branch(*next);
                     }
                 }
+                self.translate_end_try(&frame, depth)?;
 
                 self.builder.position_at_end(*frame.code_after());
                 self.state.reset_stack(&frame);
@@ -1739,7 +2377,88 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     }
                 }
             }
+            Operator::Try { ty } => {
+                let current_block = self
+                    .builder
+                    .get_insert_block()
+                    .ok_or_else(|| CompileError::Codegen("not currently in a block".to_string()))?;
+
+                let end_block = self.context.append_basic_block(self.function, "try_end");
+                self.builder.position_at_end(end_block);
+
+                let (params, results) = self.module_translation.blocktype_params_results(ty)?;
+                let phis: SmallVec<[PhiValue<'ctx>; 1]> = results
+                    .iter()
+                    .map(|&wp_ty| {
+                        wptype_to_type(wp_ty)
+                            .map_err(to_compile_error)
+                            .and_then(|wasm_ty| {
+                                type_to_llvm(self.intrinsics, wasm_ty)
+                                    .map(|ty| self.builder.build_phi(ty, ""))
+                            })
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                let handler = self
+                    .context
+                    .append_basic_block(self.function, "try_handler");
+                self.builder.position_at_end(handler);
+                let handler_phi = self.builder.build_phi(self.intrinsics.i8_ptr_ty, "");
+
+                self.state
+                    .push_try(end_block, phis, params.len(), handler, handler_phi);
+                self.builder.position_at_end(current_block);
+            }
+            Operator::Catch { index } => {
+                self.translate_try_clause(TryClauseKind::Catch(TagIndex::from_u32(index)))?;
+            }
+            Operator::Unwind => {
+                self.translate_try_clause(TryClauseKind::Unwind)?;
+            }
+            Operator::Throw { index } => {
+                let tag_index = TagIndex::from_u32(index);
+                let tag_type = &self.wasm_module.signatures[self.wasm_module.tags[tag_index]];
+                let values = self.state.popn_save_extra(tag_type.params().len())?;
+                let values = values
+                    .iter()
+                    .map(|(v, info)| self.apply_pending_canonicalization(*v, *info))
+                    .collect::<Vec<_>>();
+                let exception = self.translate_exception_new(tag_index, &values)?;
+                self.translate_throw_exception(exception, self.state.num_control_frames())?;
+                self.state.reachable = false;
+            }
+            Operator::Rethrow { relative_depth } => {
+                let (exception, caught) = match self.state.frame_at_depth(relative_depth)? {
+                    ControlFrame::Try {
+                        clause: TryClause::Catch { tag, payload, .. },
+                        ..
+                    } => (None, Some((*tag, payload.clone()))),
+                    ControlFrame::Try {
+                        clause: TryClause::CatchAll { exception },
+                        ..
+                    }
+                    | ControlFrame::Try {
+                        clause: TryClause::Unwind { exception },
+                        ..
+                    } => (Some(*exception), None),
+                    _ => {
+                        return Err(CompileError::Codegen(
+                            "rethrow outside of a catch, catch_all or unwind clause".to_string(),
+                        ))
+                    }
+                };
+                let exception = match (exception, caught) {
+                    (Some(exception), _) => exception,
+                    // The caught exception was freed when its payload was taken.
+                    (None, Some((tag, payload))) => self.translate_exception_new(tag, &payload)?,
+                    (None, None) => unreachable!(),
+                };
+                self.translate_throw_exception(exception, self.state.num_control_frames())?;
+                self.state.reachable = false;
+            }
             Operator::Return => {
+                let held = self.state.held_exceptions(0);
+                self.translate_exception_delete_all(&held);
                 let current_block = self
                     .builder
                     .get_insert_block()
@@ -2022,7 +2741,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 }
             }
 
-            Operator::Select => {
+            Operator::Select | Operator::TypedSelect { .. } => {
                 let ((v1, i1), (v2, i2), (cond, _)) = self.state.pop3_extra()?;
                 // We don't bother canonicalizing 'cond' here because we only
                 // compare it to zero, and that's invariant under
@@ -2121,12 +2840,31 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                             _ => *v,
                         });
 
+                let params = params.collect::<Vec<_>>();
+
+                if let Operator::Call { .. } = op {
+                    // Inside an `unwind` or `catch_all` clause, the exceptions
+                    // are caught too, to free the exception held by the clause.
+                    let depth = self.state.num_control_frames();
+                    let (handler, held) = self.state.exception_target(depth);
+                    if handler.is_some() || !held.is_empty() {
+                        return self.translate_try_call(
+                            *sigindex,
+                            func,
+                            callee_vmctx.into_pointer_value(),
+                            &params,
+                            handler,
+                            &held,
+                        );
+                    }
+                }
+
                 let params = self.abi.args_to_call(
                     &self.alloca_builder,
                     func_type,
                     callee_vmctx.into_pointer_value(),
                     &func.get_type().get_element_type().into_function_type(),
-                    params.collect::<Vec<_>>().as_slice(),
+                    params.as_slice(),
                 );
 
                 /*
@@ -2184,6 +2922,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     TableIndex::from_u32(table_index),
                     self.intrinsics,
                     self.module,
+                    &self.builder,
                 );
                 let func_index = self.state.pop1()?.into_int_value();
 
@@ -2369,12 +3108,29 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                             _ => *v,
                         });
 
+                let params = params.collect::<Vec<_>>();
+
+                if let Operator::CallIndirect { .. } = op {
+                    let depth = self.state.num_control_frames();
+                    let (handler, held) = self.state.exception_target(depth);
+                    if handler.is_some() || !held.is_empty() {
+                        return self.translate_try_call(
+                            sigindex,
+                            func_ptr,
+                            ctx_ptr.into_pointer_value(),
+                            &params,
+                            handler,
+                            &held,
+                        );
+                    }
+                }
+
                 let params = self.abi.args_to_call(
                     &self.alloca_builder,
                     func_type,
                     ctx_ptr.into_pointer_value(),
                     &llvm_func_type,
-                    params.collect::<Vec<_>>().as_slice(),
+                    params.as_slice(),
                 );
 
                 let typed_func_ptr = self.builder.build_pointer_cast(
@@ -2429,6 +3185,39 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                     .iter()
                     .for_each(|ret| self.state.push1(*ret));
             }
+            Operator::ReturnCall { function_index } => {
+                // LLVM can't be made to guarantee a tail call here, so only a tail call
+                // to the function itself is supported: it restarts the body with the
+                // new parameters and runs in constant stack space.
+                if FunctionIndex::from_u32(function_index) != self.func_index {
+                    return Err(CompileError::UnsupportedFeature(
+                        "return_call to a function other than the caller".to_string(),
+                    ));
+                }
+                let held = self.state.held_exceptions(0);
+                self.translate_exception_delete_all(&held);
+                let params = self
+                    .state
+                    .popn_save_extra(self.wasm_fn_type.params().len())?;
+                for ((v, i), pointer_value) in params.into_iter().zip(self.locals.iter()) {
+                    let v = self.apply_pending_canonicalization(v, i);
+                    self.builder.build_store(*pointer_value, v);
+                }
+                let num_params = self.wasm_fn_type.params().len();
+                for (pointer_value, init) in self.locals[num_params..]
+                    .iter()
+                    .zip(self.local_inits.iter())
+                {
+                    self.builder.build_store(*pointer_value, *init);
+                }
+                self.builder.build_unconditional_branch(self.start_of_code);
+                self.state.reachable = false;
+            }
+            Operator::ReturnCallIndirect { .. } => {
+                return Err(CompileError::UnsupportedFeature(
+                    "return_call_indirect".to_string(),
+                ));
+            }
 
             /***************************
              * Integer Arithmetic instructions.
@@ -6212,7 +7001,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
              ***************************/
             Operator::I32Load { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6231,7 +7020,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64Load { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6250,7 +7039,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::F32Load { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6269,7 +7058,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::F64Load { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6288,7 +7077,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6309,7 +7098,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32Store { ref memarg } => {
                 let value = self.state.pop1()?;
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6330,7 +7119,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64Store { ref memarg } => {
                 let value = self.state.pop1()?;
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6352,7 +7141,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let (v, i) = self.state.pop1_extra()?;
                 let v = self.apply_pending_canonicalization(v, i);
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6374,7 +7163,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let (v, i) = self.state.pop1_extra()?;
                 let v = self.apply_pending_canonicalization(v, i);
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6396,7 +7185,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let (v, i) = self.state.pop1_extra()?;
                 let v = self.apply_pending_canonicalization(v, i);
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6416,7 +7205,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I32Load8S { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6440,7 +7229,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I32Load16S { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6464,7 +7253,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64Load8S { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6489,7 +7278,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64Load16S { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6514,7 +7303,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64Load32S { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6539,7 +7328,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
 
             Operator::I32Load8U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6563,7 +7352,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I32Load16U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6587,7 +7376,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64Load8U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6611,7 +7400,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64Load16U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6635,7 +7424,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64Load32U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6661,7 +7450,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32Store8 { ref memarg } | Operator::I64Store8 { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6685,7 +7474,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32Store16 { ref memarg } | Operator::I64Store16 { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -6709,7 +7498,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64Store32 { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7089,7 +7878,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load8x8S { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7110,7 +7899,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load8x8U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7131,7 +7920,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load16x4S { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7152,7 +7941,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load16x4U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7173,7 +7962,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load32x2S { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7194,7 +7983,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load32x2U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7215,7 +8004,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load32Zero { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7239,7 +8028,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load64Zero { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7263,7 +8052,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load8Splat { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7284,7 +8073,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load16Splat { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7305,7 +8094,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load32Splat { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7326,7 +8115,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::V128Load64Splat { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7345,6 +8134,66 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let res = self.builder.build_bitcast(res, self.intrinsics.i128_ty, "");
                 self.state.push1(res);
             }
+            Operator::MemoryAtomicWait32 { ref memarg } => {
+                let ((addr, _), (expected, _), (timeout, _)) = self.state.pop3_extra()?;
+                let addr = self.atomic_builtin_address(memarg, addr);
+                let (builtin, index) = self.atomic_builtin(
+                    memarg,
+                    VMBuiltinFunctionIndex::get_memory_atomic_wait32_index(),
+                    VMBuiltinFunctionIndex::get_imported_memory_atomic_wait32_index(),
+                );
+                let wait = self.ctx.builtin_function(
+                    builtin,
+                    self.intrinsics.memory_atomic_wait32_ptr_ty,
+                    self.intrinsics,
+                );
+                let ret = self.builder.build_call(
+                    wait,
+                    &[vmctx.as_basic_value_enum(), index, addr, expected, timeout],
+                    "",
+                );
+                self.state.push1(ret.try_as_basic_value().left().unwrap());
+            }
+            Operator::MemoryAtomicWait64 { ref memarg } => {
+                let ((addr, _), (expected, _), (timeout, _)) = self.state.pop3_extra()?;
+                let addr = self.atomic_builtin_address(memarg, addr);
+                let (builtin, index) = self.atomic_builtin(
+                    memarg,
+                    VMBuiltinFunctionIndex::get_memory_atomic_wait64_index(),
+                    VMBuiltinFunctionIndex::get_imported_memory_atomic_wait64_index(),
+                );
+                let wait = self.ctx.builtin_function(
+                    builtin,
+                    self.intrinsics.memory_atomic_wait64_ptr_ty,
+                    self.intrinsics,
+                );
+                let ret = self.builder.build_call(
+                    wait,
+                    &[vmctx.as_basic_value_enum(), index, addr, expected, timeout],
+                    "",
+                );
+                self.state.push1(ret.try_as_basic_value().left().unwrap());
+            }
+            Operator::MemoryAtomicNotify { ref memarg } => {
+                let ((addr, _), (count, _)) = self.state.pop2_extra()?;
+                let addr = self.atomic_builtin_address(memarg, addr);
+                let (builtin, index) = self.atomic_builtin(
+                    memarg,
+                    VMBuiltinFunctionIndex::get_memory_atomic_notify_index(),
+                    VMBuiltinFunctionIndex::get_imported_memory_atomic_notify_index(),
+                );
+                let notify = self.ctx.builtin_function(
+                    builtin,
+                    self.intrinsics.memory_atomic_notify_ptr_ty,
+                    self.intrinsics,
+                );
+                let ret = self.builder.build_call(
+                    notify,
+                    &[vmctx.as_basic_value_enum(), index, addr, count],
+                    "",
+                );
+                self.state.push1(ret.try_as_basic_value().left().unwrap());
+            }
             Operator::AtomicFence { flags: _ } => {
                 // Fence is a nop.
                 //
@@ -7356,7 +8205,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I32AtomicLoad { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7374,7 +8223,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64AtomicLoad { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7392,7 +8241,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I32AtomicLoad8U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7416,7 +8265,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I32AtomicLoad16U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7440,7 +8289,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64AtomicLoad8U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7464,7 +8313,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64AtomicLoad16U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7488,7 +8337,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             }
             Operator::I64AtomicLoad32U { ref memarg } => {
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7513,7 +8362,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicStore { ref memarg } => {
                 let value = self.state.pop1()?;
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7531,7 +8380,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicStore { ref memarg } => {
                 let value = self.state.pop1()?;
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7549,7 +8398,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicStore8 { ref memarg } | Operator::I64AtomicStore8 { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7571,7 +8420,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             | Operator::I64AtomicStore16 { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7592,7 +8441,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicStore32 { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7613,7 +8462,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw8AddU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7648,7 +8497,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw16AddU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7683,7 +8532,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmwAdd { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7712,7 +8561,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw8AddU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7747,7 +8596,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw16AddU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7782,7 +8631,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw32AddU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7817,7 +8666,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmwAdd { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7846,7 +8695,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw8SubU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7881,7 +8730,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw16SubU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7916,7 +8765,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmwSub { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7945,7 +8794,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw8SubU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -7980,7 +8829,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw16SubU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8015,7 +8864,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw32SubU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8050,7 +8899,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmwSub { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8079,7 +8928,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw8AndU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8114,7 +8963,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw16AndU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8149,7 +8998,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmwAnd { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8178,7 +9027,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw8AndU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8213,7 +9062,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw16AndU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8248,7 +9097,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw32AndU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8283,7 +9132,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmwAnd { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8312,7 +9161,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw8OrU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8347,7 +9196,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw16OrU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8382,7 +9231,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmwOr { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8414,7 +9263,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw8OrU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8449,7 +9298,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw16OrU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8484,7 +9333,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw32OrU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8519,7 +9368,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmwOr { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8548,7 +9397,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw8XorU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8583,7 +9432,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw16XorU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8618,7 +9467,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmwXor { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8647,7 +9496,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw8XorU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8682,7 +9531,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw16XorU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8717,7 +9566,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw32XorU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8752,7 +9601,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmwXor { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8781,7 +9630,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw8XchgU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8816,7 +9665,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmw16XchgU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8851,7 +9700,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I32AtomicRmwXchg { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8880,7 +9729,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw8XchgU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8915,7 +9764,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw16XchgU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8950,7 +9799,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmw32XchgU { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -8985,7 +9834,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
             Operator::I64AtomicRmwXchg { ref memarg } => {
                 let value = self.state.pop1()?.into_int_value();
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -9017,7 +9866,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let new = self.apply_pending_canonicalization(new, new_info);
                 let (cmp, new) = (cmp.into_int_value(), new.into_int_value());
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -9064,7 +9913,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let new = self.apply_pending_canonicalization(new, new_info);
                 let (cmp, new) = (cmp.into_int_value(), new.into_int_value());
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -9111,7 +9960,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let new = self.apply_pending_canonicalization(new, new_info);
                 let (cmp, new) = (cmp.into_int_value(), new.into_int_value());
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -9145,7 +9994,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let new = self.apply_pending_canonicalization(new, new_info);
                 let (cmp, new) = (cmp.into_int_value(), new.into_int_value());
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -9192,7 +10041,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let new = self.apply_pending_canonicalization(new, new_info);
                 let (cmp, new) = (cmp.into_int_value(), new.into_int_value());
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -9239,7 +10088,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let new = self.apply_pending_canonicalization(new, new_info);
                 let (cmp, new) = (cmp.into_int_value(), new.into_int_value());
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -9286,7 +10135,7 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 let new = self.apply_pending_canonicalization(new, new_info);
                 let (cmp, new) = (cmp.into_int_value(), new.into_int_value());
                 let offset = self.state.pop1()?.into_int_value();
-                let memory_index = MemoryIndex::from_u32(0);
+                let memory_index = MemoryIndex::from_u32(memarg.memory);
                 let effective_address = self.resolve_memory_ptr(
                     memory_index,
                     memarg,
@@ -9350,6 +10199,425 @@ impl<'ctx, 'a> LLVMFunctionCodeGenerator<'ctx, 'a> {
                 size.add_attribute(AttributeLoc::Function, self.intrinsics.readonly);
                 self.state.push1(size.try_as_basic_value().left().unwrap());
             }
+            Operator::MemoryCopy { src, dst } => {
+                let ((dst_pos, _), (src_pos, _), (len, _)) = self.state.pop3_extra()?;
+                let src_index = MemoryIndex::from_u32(src);
+                let dst_index = MemoryIndex::from_u32(dst);
+                let src_memory64 = self.wasm_module.memories[src_index].memory64;
+                let dst_memory64 = self.wasm_module.memories[dst_index].memory64;
+                if src_index == dst_index {
+                    let (builtin, index) = match self.wasm_module.local_memory_index(src_index) {
+                        Some(local_index) if src_memory64 => (
+                            VMBuiltinFunctionIndex::get_local_memory64_copy_index(),
+                            local_index.as_u32(),
+                        ),
+                        Some(local_index) => (
+                            VMBuiltinFunctionIndex::get_local_memory_copy_index(),
+                            local_index.as_u32(),
+                        ),
+                        None if src_memory64 => (
+                            VMBuiltinFunctionIndex::get_imported_memory64_copy_index(),
+                            src_index.as_u32(),
+                        ),
+                        None => (
+                            VMBuiltinFunctionIndex::get_imported_memory_copy_index(),
+                            src_index.as_u32(),
+                        ),
+                    };
+                    let memory_copy = self.ctx.builtin_function(
+                        builtin,
+                        if src_memory64 {
+                            self.intrinsics.memory64_copy_ptr_ty
+                        } else {
+                            self.intrinsics.memory_copy_ptr_ty
+                        },
+                        self.intrinsics,
+                    );
+                    self.builder.build_call(
+                        memory_copy,
+                        &[
+                            vmctx.as_basic_value_enum(),
+                            self.intrinsics
+                                .i32_ty
+                                .const_int(index.into(), false)
+                                .as_basic_value_enum(),
+                            dst_pos,
+                            src_pos,
+                            len,
+                        ],
+                        "",
+                    );
+                } else if src_memory64 || dst_memory64 {
+                    // The 64-bit builtin takes every operand as an `i64`, so
+                    // the `i32` ones are zero-extended.
+                    let widen = |value: BasicValueEnum<'ctx>, memory64: bool| {
+                        if memory64 {
+                            value
+                        } else {
+                            self.builder
+                                .build_int_z_extend(
+                                    value.into_int_value(),
+                                    self.intrinsics.i64_ty,
+                                    "",
+                                )
+                                .as_basic_value_enum()
+                        }
+                    };
+                    let dst_pos = widen(dst_pos, dst_memory64);
+                    let src_pos = widen(src_pos, src_memory64);
+                    let len = widen(len, dst_memory64 && src_memory64);
+                    let memory_copy = self.ctx.builtin_function(
+                        VMBuiltinFunctionIndex::get_memory64_copy_between_index(),
+                        self.intrinsics.memory64_copy_between_ptr_ty,
+                        self.intrinsics,
+                    );
+                    self.builder.build_call(
+                        memory_copy,
+                        &[
+                            vmctx.as_basic_value_enum(),
+                            self.intrinsics
+                                .i32_ty
+                                .const_int(dst.into(), false)
+                                .as_basic_value_enum(),
+                            self.intrinsics
+                                .i32_ty
+                                .const_int(src.into(), false)
+                                .as_basic_value_enum(),
+                            dst_pos,
+                            src_pos,
+                            len,
+                        ],
+                        "",
+                    );
+                } else {
+                    let memory_copy = self.ctx.builtin_function(
+                        VMBuiltinFunctionIndex::get_memory_copy_between_index(),
+                        self.intrinsics.memory_copy_between_ptr_ty,
+                        self.intrinsics,
+                    );
+                    self.builder.build_call(
+                        memory_copy,
+                        &[
+                            vmctx.as_basic_value_enum(),
+                            self.intrinsics
+                                .i32_ty
+                                .const_int(dst.into(), false)
+                                .as_basic_value_enum(),
+                            self.intrinsics
+                                .i32_ty
+                                .const_int(src.into(), false)
+                                .as_basic_value_enum(),
+                            dst_pos,
+                            src_pos,
+                            len,
+                        ],
+                        "",
+                    );
+                }
+            }
+            Operator::MemoryFill { mem } => {
+                let ((dst, _), (val, _), (len, _)) = self.state.pop3_extra()?;
+                let memory_index = MemoryIndex::from_u32(mem);
+                let memory64 = self.wasm_module.memories[memory_index].memory64;
+                let (builtin, index) = match self.wasm_module.local_memory_index(memory_index) {
+                    Some(local_index) if memory64 => (
+                        VMBuiltinFunctionIndex::get_memory64_fill_index(),
+                        local_index.as_u32(),
+                    ),
+                    Some(local_index) => (
+                        VMBuiltinFunctionIndex::get_memory_fill_index(),
+                        local_index.as_u32(),
+                    ),
+                    None if memory64 => (
+                        VMBuiltinFunctionIndex::get_imported_memory64_fill_index(),
+                        memory_index.as_u32(),
+                    ),
+                    None => (
+                        VMBuiltinFunctionIndex::get_imported_memory_fill_index(),
+                        memory_index.as_u32(),
+                    ),
+                };
+                let memory_fill = self.ctx.builtin_function(
+                    builtin,
+                    if memory64 {
+                        self.intrinsics.memory64_fill_ptr_ty
+                    } else {
+                        self.intrinsics.memory_fill_ptr_ty
+                    },
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    memory_fill,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(index.into(), false)
+                            .as_basic_value_enum(),
+                        dst,
+                        val,
+                        len,
+                    ],
+                    "",
+                );
+            }
+            Operator::MemoryInit { segment, mem } => {
+                let ((dst, _), (src, _), (len, _)) = self.state.pop3_extra()?;
+                let memory64 = self.wasm_module.memories[MemoryIndex::from_u32(mem)].memory64;
+                let memory_init = if memory64 {
+                    self.ctx.builtin_function(
+                        VMBuiltinFunctionIndex::get_memory64_init_index(),
+                        self.intrinsics.memory64_init_ptr_ty,
+                        self.intrinsics,
+                    )
+                } else {
+                    self.ctx.builtin_function(
+                        VMBuiltinFunctionIndex::get_memory_init_index(),
+                        self.intrinsics.memory_init_ptr_ty,
+                        self.intrinsics,
+                    )
+                };
+                self.builder.build_call(
+                    memory_init,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(mem.into(), false)
+                            .as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(segment.into(), false)
+                            .as_basic_value_enum(),
+                        dst,
+                        src,
+                        len,
+                    ],
+                    "",
+                );
+            }
+            Operator::DataDrop { segment } => {
+                let data_drop = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_data_drop_index(),
+                    self.intrinsics.data_drop_ptr_ty,
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    data_drop,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(segment.into(), false)
+                            .as_basic_value_enum(),
+                    ],
+                    "",
+                );
+            }
+            Operator::TableCopy {
+                dst_table,
+                src_table,
+            } => {
+                let ((dst, _), (src, _), (len, _)) = self.state.pop3_extra()?;
+                let table_copy = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_table_copy_index(),
+                    self.intrinsics.table_copy_ptr_ty,
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    table_copy,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(dst_table.into(), false)
+                            .as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(src_table.into(), false)
+                            .as_basic_value_enum(),
+                        dst,
+                        src,
+                        len,
+                    ],
+                    "",
+                );
+            }
+            Operator::TableInit { segment, table } => {
+                let ((dst, _), (src, _), (len, _)) = self.state.pop3_extra()?;
+                let table_init = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_table_init_index(),
+                    self.intrinsics.table_init_ptr_ty,
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    table_init,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(table.into(), false)
+                            .as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(segment.into(), false)
+                            .as_basic_value_enum(),
+                        dst,
+                        src,
+                        len,
+                    ],
+                    "",
+                );
+            }
+            Operator::ElemDrop { segment } => {
+                let elem_drop = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_elem_drop_index(),
+                    self.intrinsics.elem_drop_ptr_ty,
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    elem_drop,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(segment.into(), false)
+                            .as_basic_value_enum(),
+                    ],
+                    "",
+                );
+            }
+            Operator::TableSize { table } => {
+                let (_, size) = self.ctx.table(
+                    TableIndex::from_u32(table),
+                    self.intrinsics,
+                    self.module,
+                    &self.builder,
+                );
+                self.state.push1(size);
+            }
+            Operator::TableGet { table } => {
+                let index = self.state.pop1()?;
+                let table_get = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_table_get_index(),
+                    self.intrinsics.table_get_ptr_ty,
+                    self.intrinsics,
+                );
+                let value = self.builder.build_call(
+                    table_get,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(table.into(), false)
+                            .as_basic_value_enum(),
+                        index,
+                    ],
+                    "",
+                );
+                self.state.push1(value.try_as_basic_value().left().unwrap());
+            }
+            Operator::TableSet { table } => {
+                let (index, value) = self.state.pop2()?;
+                let table_set = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_table_set_index(),
+                    self.intrinsics.table_set_ptr_ty,
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    table_set,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(table.into(), false)
+                            .as_basic_value_enum(),
+                        index,
+                        value,
+                    ],
+                    "",
+                );
+            }
+            Operator::TableGrow { table } => {
+                let (init_value, delta) = self.state.pop2()?;
+                let table_grow = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_table_grow_index(),
+                    self.intrinsics.table_grow_ptr_ty,
+                    self.intrinsics,
+                );
+                let size = self.builder.build_call(
+                    table_grow,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(table.into(), false)
+                            .as_basic_value_enum(),
+                        init_value,
+                        delta,
+                    ],
+                    "",
+                );
+                self.state.push1(size.try_as_basic_value().left().unwrap());
+            }
+            Operator::TableFill { table } => {
+                let ((dst, _), (value, _), (len, _)) = self.state.pop3_extra()?;
+                let table_fill = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_table_fill_index(),
+                    self.intrinsics.table_fill_ptr_ty,
+                    self.intrinsics,
+                );
+                self.builder.build_call(
+                    table_fill,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(table.into(), false)
+                            .as_basic_value_enum(),
+                        dst,
+                        value,
+                        len,
+                    ],
+                    "",
+                );
+            }
+            Operator::RefFunc { function_index } => {
+                let func_ref = self.ctx.builtin_function(
+                    VMBuiltinFunctionIndex::get_func_ref_index(),
+                    self.intrinsics.func_ref_ptr_ty,
+                    self.intrinsics,
+                );
+                let value = self.builder.build_call(
+                    func_ref,
+                    &[
+                        vmctx.as_basic_value_enum(),
+                        self.intrinsics
+                            .i32_ty
+                            .const_int(function_index.into(), false)
+                            .as_basic_value_enum(),
+                    ],
+                    "",
+                );
+                self.state.push1(value.try_as_basic_value().left().unwrap());
+            }
+            Operator::RefNull { .. } => {
+                // Both `funcref` and `externref` are words, null being 0.
+                self.state.push1(self.intrinsics.i64_zero);
+            }
+            Operator::RefIsNull => {
+                let value = self.state.pop1()?.into_int_value();
+                let is_null = self.builder.build_int_compare(
+                    IntPredicate::EQ,
+                    value,
+                    self.intrinsics.i64_zero,
+                    "",
+                );
+                let is_null = self
+                    .builder
+                    .build_int_z_extend(is_null, self.intrinsics.i32_ty, "");
+                self.state.push1(is_null);
+            }
             _ => {
                 return Err(CompileError::Codegen(format!(
                     "Operator {:?} unimplemented",
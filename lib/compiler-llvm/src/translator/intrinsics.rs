@@ -39,10 +39,7 @@ pub fn type_to_llvm_ptr<'ctx>(
         Type::F32 => Ok(intrinsics.f32_ptr_ty),
         Type::F64 => Ok(intrinsics.f64_ptr_ty),
         Type::V128 => Ok(intrinsics.i128_ptr_ty),
-        ty => Err(CompileError::Codegen(format!(
-            "type_to_llvm: unimplemented wasmer_types type {:?}",
-            ty
-        ))),
+        Type::ExternRef | Type::FuncRef => Ok(intrinsics.i64_ptr_ty),
     }
 }
 
@@ -56,10 +53,8 @@ pub fn type_to_llvm<'ctx>(
         Type::F32 => Ok(intrinsics.f32_ty.as_basic_type_enum()),
         Type::F64 => Ok(intrinsics.f64_ty.as_basic_type_enum()),
         Type::V128 => Ok(intrinsics.i128_ty.as_basic_type_enum()),
-        ty => Err(CompileError::Codegen(format!(
-            "type_to_llvm: unimplemented wasmer_types type {:?}",
-            ty
-        ))),
+        // Both references are pointer-sized words, null being 0.
+        Type::ExternRef | Type::FuncRef => Ok(intrinsics.i64_ty.as_basic_type_enum()),
     }
 }
 
@@ -184,6 +179,37 @@ pub struct Intrinsics<'ctx> {
     pub imported_memory32_grow_ptr_ty: PointerType<'ctx>,
     pub memory32_size_ptr_ty: PointerType<'ctx>,
     pub imported_memory32_size_ptr_ty: PointerType<'ctx>,
+    pub memory64_grow_ptr_ty: PointerType<'ctx>,
+    pub imported_memory64_grow_ptr_ty: PointerType<'ctx>,
+    pub memory64_size_ptr_ty: PointerType<'ctx>,
+    pub imported_memory64_size_ptr_ty: PointerType<'ctx>,
+
+    pub try_call_ptr_ty: PointerType<'ctx>,
+    pub exception_new_ptr_ty: PointerType<'ctx>,
+    pub exception_matches_ptr_ty: PointerType<'ctx>,
+    pub exception_take_payload_ptr_ty: PointerType<'ctx>,
+    pub exception_throw_ptr_ty: PointerType<'ctx>,
+    pub exception_delete_ptr_ty: PointerType<'ctx>,
+    pub memory_copy_ptr_ty: PointerType<'ctx>,
+    pub memory_copy_between_ptr_ty: PointerType<'ctx>,
+    pub memory64_copy_ptr_ty: PointerType<'ctx>,
+    pub memory64_copy_between_ptr_ty: PointerType<'ctx>,
+    pub memory_atomic_wait32_ptr_ty: PointerType<'ctx>,
+    pub memory_atomic_wait64_ptr_ty: PointerType<'ctx>,
+    pub memory_atomic_notify_ptr_ty: PointerType<'ctx>,
+    pub memory_fill_ptr_ty: PointerType<'ctx>,
+    pub memory64_fill_ptr_ty: PointerType<'ctx>,
+    pub memory_init_ptr_ty: PointerType<'ctx>,
+    pub memory64_init_ptr_ty: PointerType<'ctx>,
+    pub data_drop_ptr_ty: PointerType<'ctx>,
+    pub table_copy_ptr_ty: PointerType<'ctx>,
+    pub table_init_ptr_ty: PointerType<'ctx>,
+    pub elem_drop_ptr_ty: PointerType<'ctx>,
+    pub func_ref_ptr_ty: PointerType<'ctx>,
+    pub table_get_ptr_ty: PointerType<'ctx>,
+    pub table_set_ptr_ty: PointerType<'ctx>,
+    pub table_grow_ptr_ty: PointerType<'ctx>,
+    pub table_fill_ptr_ty: PointerType<'ctx>,
 
     pub ctx_ptr_ty: PointerType<'ctx>,
 }
@@ -461,9 +487,9 @@ impl<'ctx> Intrinsics<'ctx> {
             vmfunction_import_body_element: 0,
             vmfunction_import_vmctx_element: 1,
 
-            // TODO: this i64 is actually a rust usize
+            // The i64 is a rust usize.
             vmmemory_definition_ptr_ty: context
-                .struct_type(&[i8_ptr_ty_basic, i32_ty_basic], false)
+                .struct_type(&[i8_ptr_ty_basic, i64_ty_basic], false)
                 .ptr_type(AddressSpace::Generic),
             vmmemory_definition_base_element: 0,
             vmmemory_definition_current_length_element: 1,
@@ -486,6 +512,277 @@ impl<'ctx> Intrinsics<'ctx> {
             imported_memory32_size_ptr_ty: i32_ty
                 .fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false)
                 .ptr_type(AddressSpace::Generic),
+            memory64_grow_ptr_ty: i64_ty
+                .fn_type(
+                    &[ctx_ptr_ty.as_basic_type_enum(), i64_ty_basic, i32_ty_basic],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            imported_memory64_grow_ptr_ty: i64_ty
+                .fn_type(
+                    &[ctx_ptr_ty.as_basic_type_enum(), i64_ty_basic, i32_ty_basic],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory64_size_ptr_ty: i64_ty
+                .fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false)
+                .ptr_type(AddressSpace::Generic),
+            imported_memory64_size_ptr_ty: i64_ty
+                .fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false)
+                .ptr_type(AddressSpace::Generic),
+
+            try_call_ptr_ty: i8_ptr_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i8_ptr_ty_basic,
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i128_ptr_ty.as_basic_type_enum(),
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            exception_new_ptr_ty: i8_ptr_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i128_ptr_ty.as_basic_type_enum(),
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            exception_matches_ptr_ty: i32_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i8_ptr_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            exception_take_payload_ptr_ty: void_ty
+                .fn_type(&[i8_ptr_ty_basic, i128_ptr_ty.as_basic_type_enum()], false)
+                .ptr_type(AddressSpace::Generic),
+            exception_throw_ptr_ty: void_ty
+                .fn_type(&[i8_ptr_ty_basic], false)
+                .ptr_type(AddressSpace::Generic),
+            exception_delete_ptr_ty: void_ty
+                .fn_type(&[i8_ptr_ty_basic], false)
+                .ptr_type(AddressSpace::Generic),
+            memory_copy_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory_copy_between_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory64_copy_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i64_ty_basic,
+                        i64_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory64_copy_between_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i64_ty_basic,
+                        i64_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory_atomic_wait32_ptr_ty: i32_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i32_ty_basic,
+                        i64_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory_atomic_wait64_ptr_ty: i32_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i64_ty_basic,
+                        i64_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory_atomic_notify_ptr_ty: i32_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory_fill_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory64_fill_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i32_ty_basic,
+                        i64_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory_init_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            memory64_init_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            data_drop_ptr_ty: void_ty
+                .fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false)
+                .ptr_type(AddressSpace::Generic),
+            table_copy_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            table_init_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            elem_drop_ptr_ty: void_ty
+                .fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false)
+                .ptr_type(AddressSpace::Generic),
+            // References are passed around as `i64`s, see `type_to_llvm`.
+            func_ref_ptr_ty: i64_ty
+                .fn_type(&[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic], false)
+                .ptr_type(AddressSpace::Generic),
+            table_get_ptr_ty: i64_ty
+                .fn_type(
+                    &[ctx_ptr_ty.as_basic_type_enum(), i32_ty_basic, i32_ty_basic],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            table_set_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i64_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            table_grow_ptr_ty: i32_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
+            table_fill_ptr_ty: void_ty
+                .fn_type(
+                    &[
+                        ctx_ptr_ty.as_basic_type_enum(),
+                        i32_ty_basic,
+                        i32_ty_basic,
+                        i64_ty_basic,
+                        i32_ty_basic,
+                    ],
+                    false,
+                )
+                .ptr_type(AddressSpace::Generic),
 
             ctx_ptr_ty,
         };
@@ -545,6 +842,7 @@ pub struct CtxType<'ctx, 'a> {
     cached_functions: HashMap<FunctionIndex, FunctionCache<'ctx>>,
     cached_memory_grow: HashMap<MemoryIndex, PointerValue<'ctx>>,
     cached_memory_size: HashMap<MemoryIndex, PointerValue<'ctx>>,
+    cached_builtin_functions: HashMap<u32, PointerValue<'ctx>>,
 
     offsets: VMOffsets,
 }
@@ -570,6 +868,7 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
             cached_functions: HashMap::new(),
             cached_memory_grow: HashMap::new(),
             cached_memory_size: HashMap::new(),
+            cached_builtin_functions: HashMap::new(),
 
             // TODO: pointer width
             offsets: VMOffsets::new(8, &wasm_module),
@@ -764,21 +1063,20 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
         (ptr_to_base_ptr, ptr_to_bounds)
     }
 
+    /// Loads the base and the bounds of a table at the position of
+    /// `builder`. They are not cached, as `table.grow` may move the table.
     pub fn table(
         &mut self,
         index: TableIndex,
         intrinsics: &Intrinsics<'ctx>,
         module: &Module<'ctx>,
+        builder: &Builder<'ctx>,
     ) -> (PointerValue<'ctx>, IntValue<'ctx>) {
         let (ptr_to_base_ptr, ptr_to_bounds) = self.table_prepare(index, intrinsics, module);
-        let base_ptr = self
-            .cache_builder
+        let base_ptr = builder
             .build_load(ptr_to_base_ptr, "base_ptr")
             .into_pointer_value();
-        let bounds = self
-            .cache_builder
-            .build_load(ptr_to_bounds, "bounds")
-            .into_int_value();
+        let bounds = builder.build_load(ptr_to_bounds, "bounds").into_int_value();
         tbaa_label(
             module,
             intrinsics,
@@ -1034,16 +1332,24 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
             &self.ctx_ptr_value,
         );
         *cached_memory_grow.entry(memory_index).or_insert_with(|| {
-            let (grow_fn, grow_fn_ty) = if wasm_module.local_memory_index(memory_index).is_some() {
-                (
+            let local = wasm_module.local_memory_index(memory_index).is_some();
+            let (grow_fn, grow_fn_ty) = match (wasm_module.memories[memory_index].memory64, local) {
+                (false, true) => (
                     VMBuiltinFunctionIndex::get_memory32_grow_index(),
                     intrinsics.memory32_grow_ptr_ty,
-                )
-            } else {
-                (
+                ),
+                (false, false) => (
                     VMBuiltinFunctionIndex::get_imported_memory32_grow_index(),
                     intrinsics.imported_memory32_grow_ptr_ty,
-                )
+                ),
+                (true, true) => (
+                    VMBuiltinFunctionIndex::get_memory64_grow_index(),
+                    intrinsics.memory64_grow_ptr_ty,
+                ),
+                (true, false) => (
+                    VMBuiltinFunctionIndex::get_imported_memory64_grow_index(),
+                    intrinsics.imported_memory64_grow_ptr_ty,
+                ),
             };
             let offset = offsets.vmctx_builtin_function(grow_fn);
             let offset = intrinsics.i32_ty.const_int(offset.into(), false);
@@ -1075,16 +1381,24 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
             &self.ctx_ptr_value,
         );
         *cached_memory_size.entry(memory_index).or_insert_with(|| {
-            let (size_fn, size_fn_ty) = if wasm_module.local_memory_index(memory_index).is_some() {
-                (
+            let local = wasm_module.local_memory_index(memory_index).is_some();
+            let (size_fn, size_fn_ty) = match (wasm_module.memories[memory_index].memory64, local) {
+                (false, true) => (
                     VMBuiltinFunctionIndex::get_memory32_size_index(),
                     intrinsics.memory32_size_ptr_ty,
-                )
-            } else {
-                (
+                ),
+                (false, false) => (
                     VMBuiltinFunctionIndex::get_imported_memory32_size_index(),
                     intrinsics.imported_memory32_size_ptr_ty,
-                )
+                ),
+                (true, true) => (
+                    VMBuiltinFunctionIndex::get_memory64_size_index(),
+                    intrinsics.memory64_size_ptr_ty,
+                ),
+                (true, false) => (
+                    VMBuiltinFunctionIndex::get_imported_memory64_size_index(),
+                    intrinsics.imported_memory64_size_ptr_ty,
+                ),
             };
             let offset = offsets.vmctx_builtin_function(size_fn);
             let offset = intrinsics.i32_ty.const_int(offset.into(), false);
@@ -1104,6 +1418,35 @@ impl<'ctx, 'a> CtxType<'ctx, 'a> {
         })
     }
 
+    /// Load the pointer to the builtin function `index`, of type `fn_ty`.
+    pub fn builtin_function(
+        &mut self,
+        index: VMBuiltinFunctionIndex,
+        fn_ty: PointerType<'ctx>,
+        intrinsics: &Intrinsics<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let (cached_builtin_functions, offsets, cache_builder, ctx_ptr_value) = (
+            &mut self.cached_builtin_functions,
+            &self.offsets,
+            &self.cache_builder,
+            &self.ctx_ptr_value,
+        );
+        *cached_builtin_functions
+            .entry(index.index())
+            .or_insert_with(|| {
+                let offset = offsets.vmctx_builtin_function(index);
+                let offset = intrinsics.i32_ty.const_int(offset.into(), false);
+                let fn_ptr_ptr = unsafe { cache_builder.build_gep(*ctx_ptr_value, &[offset], "") };
+
+                let fn_ptr_ptr = cache_builder
+                    .build_bitcast(fn_ptr_ptr, fn_ty.ptr_type(AddressSpace::Generic), "")
+                    .into_pointer_value();
+                cache_builder
+                    .build_load(fn_ptr_ptr, "")
+                    .into_pointer_value()
+            })
+    }
+
     pub fn get_offsets(&self) -> &VMOffsets {
         &self.offsets
     }
@@ -1,10 +1,11 @@
 use inkwell::{
     basic_block::BasicBlock,
-    values::{BasicValue, BasicValueEnum, PhiValue},
+    values::{BasicValue, BasicValueEnum, PhiValue, PointerValue},
 };
 use smallvec::SmallVec;
 use std::ops::{BitAnd, BitOr, BitOrAssign};
 use wasmer_compiler::CompileError;
+use wasmer_types::TagIndex;
 
 #[derive(Debug)]
 pub enum ControlFrame<'ctx> {
@@ -30,6 +31,16 @@ pub enum ControlFrame<'ctx> {
         stack_size_snapshot: usize,
         if_else_state: IfElseState,
     },
+    Try {
+        next: BasicBlock<'ctx>,
+        phis: SmallVec<[PhiValue<'ctx>; 1]>,
+        stack_size_snapshot: usize,
+        /// The block the exceptions thrown in the body are sent to.
+        handler: BasicBlock<'ctx>,
+        /// The exception received by `handler`.
+        handler_phi: PhiValue<'ctx>,
+        clause: TryClause<'ctx>,
+    },
 }
 
 #[derive(Debug)]
@@ -38,27 +49,64 @@ pub enum IfElseState {
     Else,
 }
 
+/// The part of a `try` block being translated.
+#[derive(Debug)]
+pub enum TryClause<'ctx> {
+    /// The body of the `try`, whose exceptions go to its handler.
+    Body,
+    /// A `catch` clause.
+    Catch {
+        /// The block that checks the exception against the next clause
+        /// when it doesn't match this one.
+        next: BasicBlock<'ctx>,
+        /// The exception checked by the clauses.
+        exception: PointerValue<'ctx>,
+        /// The tag of the caught exception.
+        tag: TagIndex,
+        /// The values carried by the caught exception, to create it again
+        /// on `rethrow`.
+        payload: Vec<BasicValueEnum<'ctx>>,
+    },
+    /// A `catch_all` clause, which frees the exception at its end.
+    CatchAll { exception: PointerValue<'ctx> },
+    /// An `unwind` clause, which throws the exception again at its end.
+    Unwind { exception: PointerValue<'ctx> },
+}
+
+impl<'ctx> TryClause<'ctx> {
+    /// Get the exception held by this clause until its end.
+    pub fn held_exception(&self) -> Option<PointerValue<'ctx>> {
+        match *self {
+            TryClause::CatchAll { exception } | TryClause::Unwind { exception } => Some(exception),
+            TryClause::Body | TryClause::Catch { .. } => None,
+        }
+    }
+}
+
 impl<'ctx> ControlFrame<'ctx> {
     pub fn code_after(&self) -> &BasicBlock<'ctx> {
         match self {
             ControlFrame::Block { ref next, .. }
             | ControlFrame::Loop { ref next, .. }
-            | ControlFrame::IfElse { ref next, .. } => next,
+            | ControlFrame::IfElse { ref next, .. }
+            | ControlFrame::Try { ref next, .. } => next,
         }
     }
 
     pub fn br_dest(&self) -> &BasicBlock<'ctx> {
         match self {
-            ControlFrame::Block { ref next, .. } | ControlFrame::IfElse { ref next, .. } => next,
+            ControlFrame::Block { ref next, .. }
+            | ControlFrame::IfElse { ref next, .. }
+            | ControlFrame::Try { ref next, .. } => next,
             ControlFrame::Loop { ref body, .. } => body,
         }
     }
 
     pub fn phis(&self) -> &[PhiValue<'ctx>] {
         match self {
-            ControlFrame::Block { ref phis, .. } | ControlFrame::Loop { ref phis, .. } => {
-                phis.as_slice()
-            }
+            ControlFrame::Block { ref phis, .. }
+            | ControlFrame::Loop { ref phis, .. }
+            | ControlFrame::Try { ref phis, .. } => phis.as_slice(),
             ControlFrame::IfElse { ref next_phis, .. } => next_phis.as_slice(),
         }
     }
@@ -66,7 +114,9 @@ impl<'ctx> ControlFrame<'ctx> {
     /// PHI nodes for stack values in the loop body.
     pub fn loop_body_phis(&self) -> &[PhiValue<'ctx>] {
         match self {
-            ControlFrame::Block { .. } | ControlFrame::IfElse { .. } => &[],
+            ControlFrame::Block { .. } | ControlFrame::IfElse { .. } | ControlFrame::Try { .. } => {
+                &[]
+            }
             ControlFrame::Loop {
                 ref loop_body_phis, ..
             } => loop_body_phis.as_slice(),
@@ -222,6 +272,10 @@ impl<'ctx> State<'ctx> {
         !self.control_stack.is_empty()
     }
 
+    pub fn num_control_frames(&self) -> usize {
+        self.control_stack.len()
+    }
+
     pub fn reset_stack(&mut self, frame: &ControlFrame<'ctx>) {
         let stack_size_snapshot = match frame {
             ControlFrame::Block {
@@ -235,6 +289,10 @@ impl<'ctx> State<'ctx> {
             | ControlFrame::IfElse {
                 stack_size_snapshot,
                 ..
+            }
+            | ControlFrame::Try {
+                stack_size_snapshot,
+                ..
             } => *stack_size_snapshot,
         };
         self.stack.truncate(stack_size_snapshot);
@@ -271,6 +329,59 @@ impl<'ctx> State<'ctx> {
         Ok(&mut self.control_stack[index])
     }
 
+    /// Get the handler of the innermost `try` block among the `depth`
+    /// outermost frames whose body is being translated, which the
+    /// exceptions thrown at this point are sent to, and the phi receiving
+    /// them. The exceptions held by the clauses of all the frames above it
+    /// are returned too, since sending an exception to the handler leaves
+    /// these clauses.
+    ///
+    /// The handler is `None` if the exceptions are thrown out of the
+    /// function.
+    pub fn exception_target(
+        &self,
+        depth: usize,
+    ) -> (
+        Option<(BasicBlock<'ctx>, PhiValue<'ctx>)>,
+        Vec<PointerValue<'ctx>>,
+    ) {
+        let mut held = self.held_exceptions(depth);
+        for frame in self.control_stack[..depth].iter().rev() {
+            if let ControlFrame::Try {
+                handler,
+                handler_phi,
+                clause,
+                ..
+            } = frame
+            {
+                if let TryClause::Body = clause {
+                    return (Some((*handler, *handler_phi)), held);
+                }
+                held.extend(clause.held_exception());
+            }
+        }
+        (None, held)
+    }
+
+    /// Get the exceptions held by the clauses of the frames from the
+    /// `depth`-th outermost one, which must be freed by a branch leaving
+    /// these frames.
+    pub fn held_exceptions(&self, depth: usize) -> Vec<PointerValue<'ctx>> {
+        self.control_stack[depth..]
+            .iter()
+            .filter_map(|frame| match frame {
+                ControlFrame::Try { clause, .. } => clause.held_exception(),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Get the exceptions held by the clauses of the frames left by a
+    /// branch to the label `depth`.
+    pub fn held_exceptions_at_depth(&self, depth: u32) -> Vec<PointerValue<'ctx>> {
+        self.held_exceptions(self.control_stack.len() - 1 - depth as usize)
+    }
+
     pub fn pop_frame(&mut self) -> Result<ControlFrame<'ctx>, CompileError> {
         self.control_stack.pop().ok_or_else(|| {
             CompileError::Codegen("pop_frame: cannot pop from control stack".to_string())
@@ -382,6 +493,25 @@ impl<'ctx> State<'ctx> {
         });
     }
 
+    pub fn push_try(
+        &mut self,
+        next: BasicBlock<'ctx>,
+        phis: SmallVec<[PhiValue<'ctx>; 1]>,
+        num_params: usize,
+        handler: BasicBlock<'ctx>,
+        handler_phi: PhiValue<'ctx>,
+    ) {
+        self.control_stack.push(ControlFrame::Try {
+            next,
+            phis,
+            // The clauses don't see the parameters of the block.
+            stack_size_snapshot: self.stack.len() - num_params,
+            handler,
+            handler_phi,
+            clause: TryClause::Body,
+        });
+    }
+
     pub fn push_loop(
         &mut self,
         body: BasicBlock<'ctx>,
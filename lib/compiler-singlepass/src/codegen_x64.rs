@@ -8,9 +8,10 @@ use wasmer_compiler::wasmparser::{
     MemoryImmediate, Operator, Type as WpType, TypeOrFuncType as WpTypeOrFuncType,
 };
 use wasmer_compiler::{
-    CompiledFunction, CompiledFunctionFrameInfo, CustomSection, CustomSectionProtection,
-    FunctionBody, FunctionBodyData, InstructionAddressMap, Relocation, RelocationKind,
-    RelocationTarget, SectionBody, SectionIndex, SourceLoc, TrapInformation,
+    wptype_to_type, CompiledFunction, CompiledFunctionFrameInfo, CustomSection,
+    CustomSectionProtection, FunctionBody, FunctionBodyData, InstructionAddressMap, LocalLocations,
+    Relocation, RelocationKind, RelocationTarget, SectionBody, SectionIndex, SourceLoc,
+    TrapInformation, ValueLocation, ValueLocationRange,
 };
 use wasmer_types::{
     entity::{EntityRef, PrimaryMap, SecondaryMap},
@@ -55,6 +56,10 @@ pub struct FuncGen<'a> {
     /// Types of local variables, including arguments.
     local_types: Vec<WpType>,
 
+    /// The code offsets between which the local variables are at their
+    /// locations: from the end of the prologue to the epilogue.
+    locals_code_range: (usize, usize),
+
     /// Value stack.
     value_stack: Vec<Location>,
 
@@ -89,11 +94,20 @@ pub struct FuncGen<'a> {
     ///
     // Ordered by increasing InstructionAddressMap::srcloc.
     instructions_address_map: Vec<InstructionAddressMap>,
+
+    /// Stack slot saving the address of the area where the function
+    /// returns its results, if they don't fit in registers.
+    results_area_ptr: Option<Location>,
+
+    /// Stack area where the called functions return their results, if
+    /// they don't fit in registers.
+    call_results_area: Option<Location>,
 }
 
 struct SpecialLabelSet {
     integer_division_by_zero: DynamicLabel,
     heap_access_oob: DynamicLabel,
+    unaligned_atomic: DynamicLabel,
     table_access_oob: DynamicLabel,
     indirect_call_null: DynamicLabel,
     bad_signature: DynamicLabel,
@@ -232,13 +246,33 @@ pub struct ControlFrame {
     pub label: DynamicLabel,
     pub loop_like: bool,
     pub if_else: IfElseState,
+    pub params: SmallVec<[WpType; 8]>,
     pub returns: SmallVec<[WpType; 1]>,
+    /// Number of stack slots the results are passed in. Blocks returning
+    /// a single value pass it in RAX instead.
+    pub return_slots: usize,
+    /// Depth of the value stack where the return slots start.
+    pub slots_depth: usize,
     pub value_stack_depth: usize,
     pub fp_stack_depth: usize,
     pub state: MachineState,
     pub state_diff_id: usize,
 }
 
+impl ControlFrame {
+    /// Depth of the value stack once a branch to this frame is taken.
+    ///
+    /// Loops keep the slots their parameters are passed in, while `if`s
+    /// release the parameters they kept for their `else` branch.
+    fn branch_depth(&self) -> usize {
+        if self.loop_like {
+            self.value_stack_depth
+        } else {
+            self.slots_depth + self.return_slots
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum IfElseState {
     None,
@@ -505,6 +539,11 @@ impl<'a> FuncGen<'a> {
                 RelaxMode::SrcToGPR
             }
             (_, Location::XMM(_)) => RelaxMode::SrcToGPR,
+            (Location::Imm32(_), _) | (Location::Imm64(_), _)
+                if (op as *const u8 == Assembler::emit_xchg as *const u8) =>
+            {
+                RelaxMode::SrcToGPR
+            }
             _ => RelaxMode::Direct,
         };
 
@@ -956,7 +995,8 @@ impl<'a> FuncGen<'a> {
         Ok(())
     }
 
-    /// Emits a System V call sequence.
+    /// Emits a System V call sequence to a function taking `vmctx` and
+    /// integer `params`.
     ///
     /// This function will not use RAX before `cb` is called.
     ///
@@ -966,6 +1006,25 @@ impl<'a> FuncGen<'a> {
         &mut self,
         cb: F,
         params: I,
+    ) -> Result<(), CodegenError> {
+        let params: SmallVec<[Location; 8]> = params.collect();
+        let abi = FunctionAbi::new(&vec![Type::I64; params.len()], &[]);
+        self.emit_call_with_abi(cb, &params, &vec![WpType::I64; params.len()], &abi)
+    }
+
+    /// Emits a call sequence to a function with the given `FunctionAbi`,
+    /// passing `vmctx` and `params` of types `param_types`.
+    ///
+    /// This function will not use RAX before `cb` is called.
+    ///
+    /// The caller MUST NOT hold any temporary registers allocated by `acquire_temp_gpr` when calling
+    /// this function.
+    fn emit_call_with_abi<F: FnOnce(&mut Self)>(
+        &mut self,
+        cb: F,
+        params: &[Location],
+        param_types: &[WpType],
+        abi: &FunctionAbi,
     ) -> Result<(), CodegenError> {
         // Values pushed in this function are above the shadow region.
         self.machine
@@ -973,8 +1032,6 @@ impl<'a> FuncGen<'a> {
             .stack_values
             .push(MachineValue::ExplicitShadow);
 
-        let params: Vec<_> = params.collect();
-
         // Save used GPRs.
         let used_gprs = self.machine.get_used_gprs();
         for r in used_gprs.iter() {
@@ -989,20 +1046,19 @@ impl<'a> FuncGen<'a> {
             self.machine.state.stack_values.push(content);
         }
 
-        // Save used XMM registers.
+        // Save used XMM registers. They are saved whole since they may hold `v128` values.
         let used_xmms = self.machine.get_used_xmms();
         if used_xmms.len() > 0 {
             self.assembler.emit_sub(
                 Size::S64,
-                Location::Imm32((used_xmms.len() * 8) as u32),
+                Location::Imm32((used_xmms.len() * 16) as u32),
                 Location::GPR(GPR::RSP),
             );
 
             for (i, r) in used_xmms.iter().enumerate() {
-                self.assembler.emit_mov(
-                    Size::S64,
-                    Location::XMM(*r),
-                    Location::Memory(GPR::RSP, (i * 8) as i32),
+                self.assembler.emit_movdqu(
+                    XMMOrMemory::XMM(*r),
+                    XMMOrMemory::Memory(GPR::RSP, (i * 16) as i32),
                 );
             }
             for r in used_xmms.iter().rev() {
@@ -1013,23 +1069,20 @@ impl<'a> FuncGen<'a> {
                         message: "emit_call_sysv: Undefined used_xmms content".to_string(),
                     });
                 }
+                self.machine
+                    .state
+                    .stack_values
+                    .push(MachineValue::Undefined);
                 self.machine.state.stack_values.push(content);
             }
         }
 
-        let mut stack_offset: usize = 0;
-
-        // Calculate stack offset.
-        for (i, _param) in params.iter().enumerate() {
-            if let Location::Memory(_, _) = Machine::get_param_location(1 + i) {
-                stack_offset += 8;
-            }
-        }
+        let mut stack_offset: usize = abi.stack_params_size;
 
         // Align stack to 16 bytes.
         if (self.machine.get_stack_offset()
             + used_gprs.len() * 8
-            + used_xmms.len() * 8
+            + used_xmms.len() * 16
             + stack_offset)
             % 16
             != 0
@@ -1043,16 +1096,31 @@ impl<'a> FuncGen<'a> {
                 .push(MachineValue::Undefined);
         }
 
-        let mut call_movs: Vec<(Location, GPR)> = vec![];
-
-        // Prepare register & stack parameters.
-        for (i, param) in params.iter().enumerate().rev() {
-            let loc = Machine::get_param_location(1 + i);
-            match loc {
-                Location::GPR(x) => {
-                    call_movs.push((*param, x));
+        // Prepare stack parameters.
+        for ((param, &ty), loc) in params
+            .iter()
+            .zip(param_types.iter())
+            .zip(abi.params.iter())
+            .rev()
+        {
+            match *loc {
+                AbiLocation::Reg(_) => {}
+                AbiLocation::Memory(_) if ty == WpType::V128 => {
+                    // `v128` values are on the stack, and pushed high half first.
+                    let (base, offset) = match *param {
+                        Location::Memory(base, offset) => (base, offset),
+                        _ => unreachable!(),
+                    };
+                    for half in &[8, 0] {
+                        self.assembler
+                            .emit_push(Size::S64, Location::Memory(base, offset + half));
+                        self.machine
+                            .state
+                            .stack_values
+                            .push(MachineValue::Undefined);
+                    }
                 }
-                Location::Memory(_, _) => {
+                AbiLocation::Memory(_) => {
                     match *param {
                         Location::GPR(x) => {
                             let content = self.machine.state.register_values
@@ -1123,30 +1191,62 @@ impl<'a> FuncGen<'a> {
                         _ => self.assembler.emit_push(Size::S64, *param),
                     }
                 }
-                _ => {
-                    return Err(CodegenError {
-                        message: "emit_call_sysv loc: unreachable code".to_string(),
-                    })
-                }
             }
         }
 
-        // Sort register moves so that register are not overwritten before read.
-        sort_call_movs(&mut call_movs);
-
-        // Emit register moves.
-        for (loc, gpr) in call_movs {
-            if loc != Location::GPR(gpr) {
-                self.assembler.emit_mov(Size::S64, loc, Location::GPR(gpr));
+        // Prepare register parameters. The moves to XMM registers come
+        // first, since they may read GPRs holding other parameters, and the
+        // ones from XMM registers first among them, since they may read XMM
+        // registers holding other parameters.
+        let mut xmm_movs = vec![];
+        let mut other_xmm_movs = vec![];
+        let mut gpr_movs = vec![];
+        for ((&param, &ty), loc) in params.iter().zip(param_types.iter()).zip(abi.params.iter()) {
+            match *loc {
+                AbiLocation::Reg(X64Register::XMM(xmm)) => match param {
+                    Location::XMM(_) => xmm_movs.push((param, Location::XMM(xmm))),
+                    _ => other_xmm_movs.push((param, xmm, ty)),
+                },
+                AbiLocation::Reg(X64Register::GPR(gpr)) => {
+                    gpr_movs.push((param, Location::GPR(gpr)))
+                }
+                AbiLocation::Memory(_) => {}
+            }
+        }
+        self.emit_parallel_movs(xmm_movs);
+        for (param, xmm, ty) in other_xmm_movs {
+            match param {
+                _ if ty == WpType::V128 => self
+                    .assembler
+                    .emit_movdqu(v128_operand(param), XMMOrMemory::XMM(xmm)),
+                Location::Imm32(_) | Location::Imm64(_) => {
+                    // RCX holds no value, and is written after as a GPR parameter.
+                    self.assembler
+                        .emit_mov(Size::S64, param, Location::GPR(GPR::RCX));
+                    self.assembler
+                        .emit_mov(Size::S64, Location::GPR(GPR::RCX), Location::XMM(xmm));
+                }
+                _ => self
+                    .assembler
+                    .emit_mov(Size::S64, param, Location::XMM(xmm)),
             }
         }
+        self.emit_parallel_movs(gpr_movs);
 
-        // Put vmctx as the first parameter.
+        // Put vmctx, and the address of the results area if the callee returns
+        // its results there.
         self.assembler.emit_mov(
             Size::S64,
             Location::GPR(Machine::get_vmctx_reg()),
-            Machine::get_param_location(0),
-        ); // vmctx
+            Location::GPR(abi.vmctx),
+        );
+        if let Some(results_area) = abi.results_area {
+            self.assembler.emit_lea(
+                Size::S64,
+                self.call_results_area.unwrap(),
+                Location::GPR(results_area),
+            );
+        }
 
         if (self.machine.state.stack_values.len() % 2) != 1 {
             return Err(CodegenError {
@@ -1195,18 +1295,17 @@ impl<'a> FuncGen<'a> {
         // Restore XMMs.
         if !used_xmms.is_empty() {
             for (i, r) in used_xmms.iter().enumerate() {
-                self.assembler.emit_mov(
-                    Size::S64,
-                    Location::Memory(GPR::RSP, (i * 8) as i32),
-                    Location::XMM(*r),
+                self.assembler.emit_movdqu(
+                    XMMOrMemory::Memory(GPR::RSP, (i * 16) as i32),
+                    XMMOrMemory::XMM(*r),
                 );
             }
             self.assembler.emit_add(
                 Size::S64,
-                Location::Imm32((used_xmms.len() * 8) as u32),
+                Location::Imm32((used_xmms.len() * 16) as u32),
                 Location::GPR(GPR::RSP),
             );
-            for _ in 0..used_xmms.len() {
+            for _ in 0..used_xmms.len() * 2 {
                 self.machine.state.stack_values.pop().unwrap();
             }
         }
@@ -1225,6 +1324,40 @@ impl<'a> FuncGen<'a> {
         Ok(())
     }
 
+    /// Emits the 64-bit moves `movs` to registers as if they all happened at
+    /// once, so that no register is overwritten before it is read.
+    fn emit_parallel_movs(&mut self, mut movs: Vec<(Location, Location)>) {
+        movs.retain(|(src, dst)| src != dst);
+        let mut saved = vec![];
+        while !movs.is_empty() {
+            match movs
+                .iter()
+                .position(|(_, dst)| movs.iter().all(|(src, _)| src != dst))
+            {
+                Some(i) => {
+                    let (src, dst) = movs.remove(i);
+                    self.assembler.emit_mov(Size::S64, src, dst);
+                }
+                None => {
+                    // The moves left form cycles: break one by saving a
+                    // source on the stack until the end.
+                    let (src, dst) = movs.remove(0);
+                    self.assembler
+                        .emit_sub(Size::S64, Location::Imm32(8), Location::GPR(GPR::RSP));
+                    self.assembler
+                        .emit_mov(Size::S64, src, Location::Memory(GPR::RSP, 0));
+                    saved.push(dst);
+                }
+            }
+        }
+        for dst in saved.into_iter().rev() {
+            self.assembler
+                .emit_mov(Size::S64, Location::Memory(GPR::RSP, 0), dst);
+            self.assembler
+                .emit_add(Size::S64, Location::Imm32(8), Location::GPR(GPR::RSP));
+        }
+    }
+
     /// Emits a System V call sequence, specialized for labels as the call target.
     fn _emit_call_sysv_label<I: Iterator<Item = Location>>(
         &mut self,
@@ -1235,201 +1368,858 @@ impl<'a> FuncGen<'a> {
         Ok(())
     }
 
-    /// Emits a memory operation.
-    fn emit_memory_op<F: FnOnce(&mut Self, GPR) -> Result<(), CodegenError>>(
+    /// Emits a call to a VM builtin function that returns nothing.
+    ///
+    /// The builtin receives `vmctx`, then the `imms`, then the top `n_values`
+    /// values of the wasm stack, which are popped.
+    fn emit_call_builtin(
         &mut self,
-        addr: Location,
-        memarg: &MemoryImmediate,
-        check_alignment: bool,
-        value_size: usize,
-        cb: F,
+        builtin: VMBuiltinFunctionIndex,
+        imms: &[u32],
+        n_values: usize,
     ) -> Result<(), CodegenError> {
-        let need_check = match self.memory_styles[MemoryIndex::new(0)] {
-            MemoryStyle::Static { .. } => false,
-            MemoryStyle::Dynamic { .. } => true,
-        };
-        let tmp_addr = self.machine.acquire_temp_gpr().unwrap();
+        let values = self
+            .value_stack
+            .split_off(self.value_stack.len() - n_values);
 
-        // Reusing `tmp_addr` for temporary indirection here, since it's not used before the last reference to `{base,bound}_loc`.
-        let (base_loc, bound_loc) = if self.module.num_imported_memories != 0 {
-            // Imported memories require one level of indirection.
-            let offset = self
-                .vmoffsets
-                .vmctx_vmmemory_import_definition(MemoryIndex::new(0));
-            self.emit_relaxed_binop(
-                Assembler::emit_mov,
-                Size::S64,
-                Location::Memory(Machine::get_vmctx_reg(), offset as i32),
-                Location::GPR(tmp_addr),
-            );
-            (Location::Memory(tmp_addr, 0), Location::Memory(tmp_addr, 8))
-        } else {
-            let offset = self
-                .vmoffsets
-                .vmctx_vmmemory_definition(LocalMemoryIndex::new(0));
-            (
-                Location::Memory(Machine::get_vmctx_reg(), offset as i32),
-                Location::Memory(Machine::get_vmctx_reg(), (offset + 8) as i32),
-            )
-        };
+        self.machine.release_locations_only_regs(&values);
 
-        let tmp_base = self.machine.acquire_temp_gpr().unwrap();
-        let tmp_bound = self.machine.acquire_temp_gpr().unwrap();
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Memory(
+                Machine::get_vmctx_reg(),
+                self.vmoffsets.vmctx_builtin_function(builtin) as i32,
+            ),
+            Location::GPR(GPR::RAX),
+        );
 
-        // Load base into temporary register.
-        self.assembler
-            .emit_mov(Size::S64, base_loc, Location::GPR(tmp_base));
+        self.machine.release_locations_only_osr_state(n_values);
 
-        // Load bound into temporary register, if needed.
-        if need_check {
-            self.assembler
-                .emit_mov(Size::S32, bound_loc, Location::GPR(tmp_bound));
+        self.emit_call_sysv(
+            |this| {
+                this.assembler.emit_call_register(GPR::RAX);
+            },
+            imms.iter()
+                .map(|imm| Location::Imm32(*imm))
+                .chain(values.iter().cloned()),
+        )?;
 
-            // Wasm -> Effective.
-            // Assuming we never underflow - should always be true on Linux/macOS and Windows >=8,
-            // since the first page from 0x0 to 0x1000 is not accepted by mmap.
+        self.machine
+            .release_locations_only_stack(&mut self.assembler, &values);
+        Ok(())
+    }
 
-            // This `lea` calculates the upper bound allowed for the beginning of the word.
-            // Since the upper bound of the memory is (exclusively) `tmp_bound + tmp_base`,
-            // the maximum allowed beginning of word is (inclusively)
-            // `tmp_bound + tmp_base - value_size`.
-            self.assembler.emit_lea(
-                Size::S64,
-                Location::MemoryAddTriple(tmp_bound, tmp_base, -(value_size as i32)),
-                Location::GPR(tmp_bound),
-            );
+    /// Clears the upper 32 bits of the `i32` at `depth` in the value stack, so
+    /// that it can be passed to a builtin as an `i64`.
+    fn emit_zero_extend_i32(&mut self, depth: usize) {
+        match self.value_stack[depth] {
+            Location::Imm32(value) => {
+                self.value_stack[depth] = Location::Imm64(u64::from(value));
+            }
+            Location::GPR(gpr) => {
+                self.assembler
+                    .emit_mov(Size::S32, Location::GPR(gpr), Location::GPR(gpr));
+            }
+            loc => {
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+                self.assembler.emit_mov(Size::S32, loc, Location::GPR(tmp));
+                self.assembler.emit_mov(Size::S64, Location::GPR(tmp), loc);
+                self.machine.release_temp_gpr(tmp);
+            }
         }
+    }
 
-        // Load effective address.
-        // `base_loc` and `bound_loc` becomes INVALID after this line, because `tmp_addr`
-        // might be reused.
-        self.assembler
-            .emit_mov(Size::S32, addr, Location::GPR(tmp_addr));
-
-        // Add offset to memory address.
-        if memarg.offset != 0 {
-            self.assembler.emit_add(
-                Size::S32,
-                Location::Imm32(memarg.offset),
-                Location::GPR(tmp_addr),
-            );
-
-            // Trap if offset calculation overflowed.
+    /// Adds the unsigned `offset` to the 64-bit address in `addr`, trapping
+    /// if it overflows.
+    fn emit_add_offset64(&mut self, offset: u32, addr: GPR) {
+        // A 64-bit add sign-extends its immediate, so the offset is added in
+        // chunks that fit in an `i32`.
+        let mut offset = offset;
+        while offset != 0 {
+            let chunk = offset.min(i32::MAX as u32);
+            self.assembler
+                .emit_add(Size::S64, Location::Imm32(chunk), Location::GPR(addr));
             self.assembler
                 .emit_jmp(Condition::Carry, self.special_labels.heap_access_oob);
+            offset -= chunk;
         }
+    }
 
-        // Wasm linear memory -> real memory
-        self.assembler
-            .emit_add(Size::S64, Location::GPR(tmp_base), Location::GPR(tmp_addr));
-
-        if need_check {
-            // Trap if the end address of the requested area is above that of the linear memory.
-            self.assembler
-                .emit_cmp(Size::S64, Location::GPR(tmp_bound), Location::GPR(tmp_addr));
-
-            // `tmp_bound` is inclusive. So trap only if `tmp_addr > tmp_bound`.
-            self.assembler
-                .emit_jmp(Condition::Above, self.special_labels.heap_access_oob);
+    /// Replaces the address at `depth` in the value stack with the effective
+    /// address of `memarg`, as an `i64`, so that it can be passed to a
+    /// builtin.
+    fn emit_effective_address(&mut self, depth: usize, memarg: &MemoryImmediate) {
+        let memory64 = self.module.memories[MemoryIndex::new(memarg.memory as usize)].memory64;
+        let loc = self.value_stack[depth];
+        let addr = match loc {
+            Location::Imm32(addr) if memory64 => Some(addr as i32 as u64),
+            Location::Imm32(addr) => Some(u64::from(addr)),
+            Location::Imm64(addr) => Some(addr),
+            _ => None,
+        };
+        if let Some(addr) = addr {
+            let addr = addr
+                .checked_add(u64::from(memarg.offset))
+                .unwrap_or_else(|| {
+                    self.assembler
+                        .emit_jmp(Condition::None, self.special_labels.heap_access_oob);
+                    0
+                });
+            self.value_stack[depth] = Location::Imm64(addr);
+            return;
         }
 
-        self.machine.release_temp_gpr(tmp_bound);
-        self.machine.release_temp_gpr(tmp_base);
-
-        let align = memarg.align;
-        if check_alignment && align != 1 {
-            let tmp_aligncheck = self.machine.acquire_temp_gpr().unwrap();
-            self.assembler.emit_mov(
-                Size::S32,
-                Location::GPR(tmp_addr),
-                Location::GPR(tmp_aligncheck),
-            );
-            self.assembler.emit_and(
-                Size::S64,
-                Location::Imm32((align - 1).into()),
-                Location::GPR(tmp_aligncheck),
-            );
-            self.assembler
-                .emit_jmp(Condition::NotEqual, self.special_labels.heap_access_oob);
-            self.machine.release_temp_gpr(tmp_aligncheck);
+        let size = if memory64 { Size::S64 } else { Size::S32 };
+        match loc {
+            Location::GPR(gpr) => {
+                self.assembler.emit_mov(size, loc, loc);
+                self.emit_add_offset64(memarg.offset, gpr);
+            }
+            _ => {
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+                self.assembler.emit_mov(size, loc, Location::GPR(tmp));
+                self.emit_add_offset64(memarg.offset, tmp);
+                self.assembler.emit_mov(Size::S64, Location::GPR(tmp), loc);
+                self.machine.release_temp_gpr(tmp);
+            }
         }
-
-        self.mark_range_with_trap_code(TrapCode::HeapAccessOutOfBounds, |this| cb(this, tmp_addr))?;
-
-        self.machine.release_temp_gpr(tmp_addr);
-        Ok(())
     }
 
-    /// Emits a memory operation.
-    fn emit_compare_and_swap<F: FnOnce(&mut Self, GPR, GPR)>(
+    /// Emits a call to a VM builtin function like `emit_call_builtin`, and
+    /// pushes its result, of type `ret_ty`, onto the wasm stack.
+    fn emit_call_builtin_with_result(
         &mut self,
-        loc: Location,
-        target: Location,
-        ret: Location,
-        memarg: &MemoryImmediate,
-        value_size: usize,
-        memory_sz: Size,
-        stack_sz: Size,
-        cb: F,
+        builtin: VMBuiltinFunctionIndex,
+        imms: &[u32],
+        n_values: usize,
+        ret_ty: WpType,
     ) -> Result<(), CodegenError> {
-        if memory_sz > stack_sz {
-            return Err(CodegenError {
-                message: "emit_compare_and_swap: memory size > stack size".to_string(),
-            });
-        }
-
-        let compare = self.machine.reserve_unused_temp_gpr(GPR::RAX);
-        let value = if loc == Location::GPR(GPR::R14) {
-            GPR::R13
-        } else {
-            GPR::R14
-        };
-        self.assembler.emit_push(Size::S64, Location::GPR(value));
-
-        self.assembler.emit_mov(stack_sz, loc, Location::GPR(value));
+        self.emit_call_builtin(builtin, imms, n_values)?;
 
-        let retry = self.assembler.get_label();
-        self.assembler.emit_label(retry);
+        let ret = self.machine.acquire_locations(
+            &mut self.assembler,
+            &[(ret_ty, MachineValue::WasmStack(self.value_stack.len()))],
+            false,
+        )[0];
+        self.value_stack.push(ret);
+        self.assembler
+            .emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
+        Ok(())
+    }
 
-        self.emit_memory_op(target, memarg, true, value_size, |this, addr| {
-            // Memory moves with size < 32b do not zero upper bits.
-            if memory_sz < Size::S32 {
-                this.assembler
-                    .emit_xor(Size::S32, Location::GPR(compare), Location::GPR(compare));
+    /// Moves a `v128` value between XMM registers and 16-byte stack slots.
+    fn emit_v128_mov(&mut self, src: Location, dst: Location) {
+        if src == dst {
+            return;
+        }
+        match (src, dst) {
+            (Location::Memory(_, _), Location::Memory(_, _)) => {
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+                self.assembler
+                    .emit_movdqu(v128_operand(src), XMMOrMemory::XMM(tmp));
+                self.assembler
+                    .emit_movdqu(XMMOrMemory::XMM(tmp), v128_operand(dst));
+                self.machine.release_temp_xmm(tmp);
             }
-            this.assembler
-                .emit_mov(memory_sz, Location::Memory(addr, 0), Location::GPR(compare));
-            this.assembler
-                .emit_mov(stack_sz, Location::GPR(compare), ret);
-            cb(this, compare, value);
-            this.assembler.emit_lock_cmpxchg(
-                memory_sz,
-                Location::GPR(value),
-                Location::Memory(addr, 0),
-            );
-            Ok(())
-        })?;
-
-        self.assembler.emit_jmp(Condition::NotEqual, retry);
+            _ => self
+                .assembler
+                .emit_movdqu(v128_operand(src), v128_operand(dst)),
+        }
+    }
 
-        self.assembler.emit_pop(Size::S64, Location::GPR(value));
-        self.machine.release_temp_gpr(compare);
-        Ok(())
+    /// Pushes the `v128` held in `src` on the value stack.
+    fn push_v128(&mut self, src: XMM) {
+        let ret = self.machine.acquire_locations(
+            &mut self.assembler,
+            &[(
+                WpType::V128,
+                MachineValue::WasmStack(self.value_stack.len()),
+            )],
+            false,
+        )[0];
+        self.value_stack.push(ret);
+        self.emit_v128_mov(Location::XMM(src), ret);
     }
 
-    // Checks for underflow/overflow/nan.
-    fn emit_f32_int_conv_check(
-        &mut self,
-        reg: XMM,
-        lower_bound: f32,
-        upper_bound: f32,
-        underflow_label: <Assembler as Emitter>::Label,
-        overflow_label: <Assembler as Emitter>::Label,
-        nan_label: <Assembler as Emitter>::Label,
-        succeed_label: <Assembler as Emitter>::Label,
-    ) {
-        let lower_bound = f32::to_bits(lower_bound);
-        let upper_bound = f32::to_bits(upper_bound);
+    /// Loads the 16 bytes of `value` into `dst`.
+    fn emit_v128_const(&mut self, value: [u8; 16], dst: XMM) {
+        let mut low = [0u8; 8];
+        let mut high = [0u8; 8];
+        low.copy_from_slice(&value[..8]);
+        high.copy_from_slice(&value[8..]);
+
+        let tmp = self.machine.acquire_temp_gpr().unwrap();
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Imm64(u64::from_le_bytes(low)),
+            Location::GPR(tmp),
+        );
+        self.assembler
+            .emit_mov(Size::S64, Location::GPR(tmp), Location::XMM(dst));
+        self.assembler.emit_mov(
+            Size::S64,
+            Location::Imm64(u64::from_le_bytes(high)),
+            Location::GPR(tmp),
+        );
+        self.assembler.emit_pinsrq(tmp, 1, dst);
+        self.machine.release_temp_gpr(tmp);
+    }
+
+    /// Fills the lanes of `dst` with the `lane_size` low bits of `src`.
+    fn emit_v128_splat(&mut self, lane_size: Size, src: GPR, dst: XMM) {
+        // Replicate the lane over 64 bits by multiplying it with a pattern of ones.
+        let pattern = match lane_size {
+            Size::S8 => Some((Size::S8, 0x0101_0101_0101_0101u64)),
+            Size::S16 => Some((Size::S16, 0x0001_0001_0001_0001u64)),
+            Size::S32 => Some((Size::S32, 0x0000_0001_0000_0001u64)),
+            Size::S64 => None,
+        };
+        if let Some((size, ones)) = pattern {
+            match size {
+                Size::S32 => {
+                    self.assembler
+                        .emit_mov(Size::S32, Location::GPR(src), Location::GPR(src));
+                }
+                _ => {
+                    self.assembler.emit_movzx(
+                        size,
+                        Location::GPR(src),
+                        Size::S64,
+                        Location::GPR(src),
+                    );
+                }
+            }
+            let tmp = self.machine.acquire_temp_gpr().unwrap();
+            self.assembler
+                .emit_mov(Size::S64, Location::Imm64(ones), Location::GPR(tmp));
+            self.assembler
+                .emit_imul(Size::S64, Location::GPR(tmp), Location::GPR(src));
+            self.machine.release_temp_gpr(tmp);
+        }
+        self.assembler
+            .emit_mov(Size::S64, Location::GPR(src), Location::XMM(dst));
+        // Duplicate the low quadword.
+        self.assembler.emit_pshufd(XMMOrMemory::XMM(dst), 0x44, dst);
+    }
+
+    /// Pops `n` `v128` values into the temporary XMM registers, in stack
+    /// order, lets `f` compute a result in the first register and pushes it.
+    fn emit_v128_op<F: FnOnce(&mut Self, [XMM; 3])>(&mut self, n: usize, f: F) {
+        let mut locs: SmallVec<[Location; 3]> = (0..n).map(|_| self.pop_value_released()).collect();
+        locs.reverse();
+
+        let tmps = [
+            self.machine.acquire_temp_xmm().unwrap(),
+            self.machine.acquire_temp_xmm().unwrap(),
+            self.machine.acquire_temp_xmm().unwrap(),
+        ];
+        for (loc, tmp) in locs.iter().zip(tmps.iter()) {
+            self.emit_v128_mov(*loc, Location::XMM(*tmp));
+        }
+
+        f(self, tmps);
+
+        self.push_v128(tmps[0]);
+        for tmp in tmps.iter().rev() {
+            self.machine.release_temp_xmm(*tmp);
+        }
+    }
+
+    /// `v128` binary operation.
+    fn emit_v128_binop(&mut self, f: fn(&mut Assembler, XMM, XMMOrMemory, XMM)) {
+        self.emit_v128_op(2, |this, t| {
+            f(&mut this.assembler, t[0], XMMOrMemory::XMM(t[1]), t[0])
+        });
+    }
+
+    /// `v128` binary operation with the operands swapped.
+    fn emit_v128_binop_swapped(&mut self, f: fn(&mut Assembler, XMM, XMMOrMemory, XMM)) {
+        self.emit_v128_op(2, |this, t| {
+            f(&mut this.assembler, t[1], XMMOrMemory::XMM(t[0]), t[0])
+        });
+    }
+
+    /// `v128` binary operation whose result is inverted bitwise.
+    fn emit_v128_binop_not(&mut self, f: fn(&mut Assembler, XMM, XMMOrMemory, XMM), swapped: bool) {
+        self.emit_v128_op(2, |this, t| {
+            if swapped {
+                f(&mut this.assembler, t[1], XMMOrMemory::XMM(t[0]), t[0]);
+            } else {
+                f(&mut this.assembler, t[0], XMMOrMemory::XMM(t[1]), t[0]);
+            }
+            this.assembler
+                .emit_vpcmpeqd(t[2], XMMOrMemory::XMM(t[2]), t[2]);
+            this.assembler
+                .emit_vpxor(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+        });
+    }
+
+    /// Unsigned `v128` comparison, computed as `f(a, b) == a` where `f` is
+    /// an unsigned lane-wise minimum or maximum.
+    fn emit_v128_cmp_unsigned(
+        &mut self,
+        f: fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+        eq: fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+        negate: bool,
+    ) {
+        self.emit_v128_op(2, |this, t| {
+            f(&mut this.assembler, t[0], XMMOrMemory::XMM(t[1]), t[1]);
+            eq(&mut this.assembler, t[0], XMMOrMemory::XMM(t[1]), t[0]);
+            if negate {
+                this.assembler
+                    .emit_vpcmpeqd(t[2], XMMOrMemory::XMM(t[2]), t[2]);
+                this.assembler
+                    .emit_vpxor(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+            }
+        });
+    }
+
+    /// `v128` lane-wise negation, as a subtraction from zero.
+    fn emit_v128_neg(&mut self, sub: fn(&mut Assembler, XMM, XMMOrMemory, XMM)) {
+        self.emit_v128_op(1, |this, t| {
+            this.assembler
+                .emit_vpxor(t[1], XMMOrMemory::XMM(t[1]), t[1]);
+            sub(&mut this.assembler, t[1], XMMOrMemory::XMM(t[0]), t[0]);
+        });
+    }
+
+    /// `v128` operation with a single operand.
+    fn emit_v128_unop(&mut self, f: fn(&mut Assembler, XMMOrMemory, XMM)) {
+        self.emit_v128_op(1, |this, t| {
+            f(&mut this.assembler, XMMOrMemory::XMM(t[0]), t[0])
+        });
+    }
+
+    /// `v128` floating point operation with `n` operands, whose NaN lanes
+    /// are made canonical. x86 keeps the sign and payload of a NaN
+    /// operand, and the SIMD tests expect a positive NaN.
+    fn emit_v128_float_op(&mut self, n: usize, lane_size: Size, f: fn(&mut Assembler, [XMM; 3])) {
+        self.emit_v128_op(n, |this, t| {
+            f(&mut this.assembler, t);
+            let canonical_nan: u64 = if lane_size == Size::S32 {
+                this.assembler
+                    .emit_vcmpunordps(t[0], XMMOrMemory::XMM(t[0]), t[1]);
+                0x7fc0_0000
+            } else {
+                this.assembler
+                    .emit_vcmpunordpd(t[0], XMMOrMemory::XMM(t[0]), t[1]);
+                0x7ff8_0000_0000_0000
+            };
+            let tmp = this.machine.acquire_temp_gpr().unwrap();
+            this.assembler.emit_mov(
+                Size::S64,
+                Location::Imm64(canonical_nan),
+                Location::GPR(tmp),
+            );
+            this.emit_v128_splat(lane_size, tmp, t[2]);
+            this.machine.release_temp_gpr(tmp);
+            // t[0] = (t[0] & !nan) | (canonical & nan)
+            this.assembler
+                .emit_vpand(t[1], XMMOrMemory::XMM(t[2]), t[2]);
+            this.assembler
+                .emit_vpandn(t[1], XMMOrMemory::XMM(t[0]), t[0]);
+            this.assembler.emit_vpor(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+        });
+    }
+
+    /// Applies `f` to the lanes of a `v128` and a mask splatted from
+    /// `mask`, to clear or flip the sign bits of floating point lanes.
+    fn emit_v128_sign_op(
+        &mut self,
+        lane_size: Size,
+        mask: u64,
+        f: fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+    ) {
+        self.emit_v128_op(1, |this, t| {
+            let tmp = this.machine.acquire_temp_gpr().unwrap();
+            this.assembler
+                .emit_mov(Size::S64, Location::Imm64(mask), Location::GPR(tmp));
+            this.emit_v128_splat(lane_size, tmp, t[1]);
+            this.machine.release_temp_gpr(tmp);
+            f(&mut this.assembler, t[0], XMMOrMemory::XMM(t[1]), t[0]);
+        });
+    }
+
+    /// `v128` lane-wise shift by a scalar popped from the value stack,
+    /// taken modulo the lane width.
+    fn emit_v128_shift(&mut self, lane_bits: u32, f: fn(&mut Assembler, XMM, XMMOrMemory, XMM)) {
+        self.emit_v128_shift_with(lane_bits, |this, t, amount| {
+            this.assembler
+                .emit_mov(Size::S32, Location::GPR(amount), Location::XMM(t[1]));
+            f(&mut this.assembler, t[0], XMMOrMemory::XMM(t[1]), t[0]);
+        });
+    }
+
+    /// Pops a shift amount taken modulo the lane width into a temporary
+    /// GPR for `f`, which shifts the `v128` below it.
+    fn emit_v128_shift_with<F: FnOnce(&mut Self, [XMM; 3], GPR)>(&mut self, lane_bits: u32, f: F) {
+        let amount = self.pop_value_released();
+        let tmp = self.machine.acquire_temp_gpr().unwrap();
+        self.assembler
+            .emit_mov(Size::S32, amount, Location::GPR(tmp));
+        self.assembler.emit_and(
+            Size::S32,
+            Location::Imm32(lane_bits - 1),
+            Location::GPR(tmp),
+        );
+        self.emit_v128_op(1, |this, t| f(this, t, tmp));
+        self.machine.release_temp_gpr(tmp);
+    }
+
+    /// `i8x16` shift, computed on the bytes widened to 16 bits and
+    /// narrowed back with `pack`.
+    fn emit_i8x16_shift(
+        &mut self,
+        shift: fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+        pack: fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+        left: bool,
+    ) {
+        self.emit_v128_shift_with(8, |this, t, amount| {
+            // Each 16-bit lane holds its byte twice; right shifts keep the
+            // high copy, left shifts the low one.
+            this.assembler
+                .emit_vpunpckhbw(t[0], XMMOrMemory::XMM(t[0]), t[2]);
+            this.assembler
+                .emit_vpunpcklbw(t[0], XMMOrMemory::XMM(t[0]), t[0]);
+            if !left {
+                this.assembler
+                    .emit_add(Size::S32, Location::Imm32(8), Location::GPR(amount));
+            }
+            this.assembler
+                .emit_mov(Size::S32, Location::GPR(amount), Location::XMM(t[1]));
+            for &x in &[t[0], t[2]] {
+                shift(&mut this.assembler, x, XMMOrMemory::XMM(t[1]), x);
+                if left {
+                    this.assembler.emit_psllw_imm8(8, x);
+                    this.assembler.emit_psrlw_imm8(8, x);
+                }
+            }
+            pack(&mut this.assembler, t[0], XMMOrMemory::XMM(t[2]), t[0]);
+        });
+    }
+
+    /// Pushes a scalar computed by `f` from a `v128` popped from the value stack.
+    fn emit_v128_extract<F: FnOnce(&mut Self, XMM, GPR)>(&mut self, ty: WpType, f: F) {
+        let loc = self.pop_value_released();
+        let tmp_xmm = self.machine.acquire_temp_xmm().unwrap();
+        let tmp = self.machine.acquire_temp_gpr().unwrap();
+        self.emit_v128_mov(loc, Location::XMM(tmp_xmm));
+        f(self, tmp_xmm, tmp);
+        self.machine.release_temp_xmm(tmp_xmm);
+
+        let ret = self.machine.acquire_locations(
+            &mut self.assembler,
+            &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
+            false,
+        )[0];
+        self.value_stack.push(ret);
+        if ty.is_float() {
+            self.fp_stack
+                .push(FloatValue::new(self.value_stack.len() - 1));
+        }
+        let size = match ty {
+            WpType::I32 | WpType::F32 => Size::S32,
+            _ => Size::S64,
+        };
+        self.assembler.emit_mov(size, Location::GPR(tmp), ret);
+        self.machine.release_temp_gpr(tmp);
+    }
+
+    /// Pops a scalar of type `ty` into a temporary GPR for `f`, which
+    /// computes a `v128` from it and the `n` `v128` values below it.
+    fn emit_v128_from_scalar<F: FnOnce(&mut Self, [XMM; 3], GPR)>(
+        &mut self,
+        ty: WpType,
+        n: usize,
+        f: F,
+    ) -> Result<(), CodegenError> {
+        let value = self.pop_value_released();
+        if ty.is_float() {
+            self.fp_stack.pop1()?;
+        }
+        let tmp = self.machine.acquire_temp_gpr().unwrap();
+        self.assembler
+            .emit_mov(Size::S64, value, Location::GPR(tmp));
+        self.emit_v128_op(n, |this, t| f(this, t, tmp));
+        self.machine.release_temp_gpr(tmp);
+        Ok(())
+    }
+
+    /// Pushes whether all the lanes of a `v128` are non-zero.
+    fn emit_v128_all_true(&mut self, eq: fn(&mut Assembler, XMM, XMMOrMemory, XMM)) {
+        self.emit_v128_extract(WpType::I32, |this, v, dst| {
+            let zero = this.machine.acquire_temp_xmm().unwrap();
+            this.assembler
+                .emit_vpxor(zero, XMMOrMemory::XMM(zero), zero);
+            // Lanes equal to zero become all ones.
+            eq(&mut this.assembler, v, XMMOrMemory::XMM(zero), v);
+            this.machine.release_temp_xmm(zero);
+            this.assembler.emit_ptest(XMMOrMemory::XMM(v), v);
+            this.assembler.emit_set(Condition::Equal, dst);
+            this.assembler
+                .emit_and(Size::S32, Location::Imm32(0xff), Location::GPR(dst));
+        });
+    }
+
+    /// Pushes a `v128` computed by `f` from the `value_size` bytes loaded
+    /// at the address popped from the value stack.
+    fn emit_v128_load<F: FnOnce(&mut Self, GPR, XMM)>(
+        &mut self,
+        memarg: &MemoryImmediate,
+        value_size: usize,
+        f: F,
+    ) -> Result<(), CodegenError> {
+        let target = self.pop_value_released();
+        let tmp = self.machine.acquire_temp_xmm().unwrap();
+        self.emit_memory_op(target, memarg, false, value_size, |this, addr| {
+            f(this, addr, tmp);
+            Ok(())
+        })?;
+        self.push_v128(tmp);
+        self.machine.release_temp_xmm(tmp);
+        Ok(())
+    }
+
+    /// Pushes a `v128` whose lanes are splatted from a `lane_size` value
+    /// loaded from memory.
+    fn emit_v128_load_splat(
+        &mut self,
+        memarg: &MemoryImmediate,
+        lane_size: Size,
+    ) -> Result<(), CodegenError> {
+        let value_size = match lane_size {
+            Size::S8 => 1,
+            Size::S16 => 2,
+            Size::S32 => 4,
+            Size::S64 => 8,
+        };
+        self.emit_v128_load(memarg, value_size, |this, addr, dst| {
+            let tmp = this.machine.acquire_temp_gpr().unwrap();
+            match lane_size {
+                Size::S8 | Size::S16 => this.assembler.emit_movzx(
+                    lane_size,
+                    Location::Memory(addr, 0),
+                    Size::S32,
+                    Location::GPR(tmp),
+                ),
+                _ => this.assembler.emit_mov(
+                    lane_size,
+                    Location::Memory(addr, 0),
+                    Location::GPR(tmp),
+                ),
+            }
+            this.emit_v128_splat(lane_size, tmp, dst);
+            this.machine.release_temp_gpr(tmp);
+        })
+    }
+
+    /// Pushes a `v128` whose lanes are extended by `extend` from the 8 bytes
+    /// loaded from memory.
+    fn emit_v128_load_extend(
+        &mut self,
+        memarg: &MemoryImmediate,
+        extend: fn(&mut Assembler, XMMOrMemory, XMM),
+    ) -> Result<(), CodegenError> {
+        self.emit_v128_load(memarg, 8, |this, addr, dst| {
+            this.assembler
+                .emit_mov(Size::S64, Location::Memory(addr, 0), Location::XMM(dst));
+            extend(&mut this.assembler, XMMOrMemory::XMM(dst), dst);
+        })
+    }
+
+    /// Widens the low or high half of the lanes of a `v128` with `extend`.
+    fn emit_v128_widen(&mut self, extend: fn(&mut Assembler, XMMOrMemory, XMM), high: bool) {
+        self.emit_v128_op(1, |this, t| {
+            if high {
+                this.assembler
+                    .emit_pshufd(XMMOrMemory::XMM(t[0]), 0xee, t[0]);
+            }
+            extend(&mut this.assembler, XMMOrMemory::XMM(t[0]), t[0]);
+        });
+    }
+
+    /// Lane-wise floating point minimum or maximum following the
+    /// WebAssembly semantics: NaNs propagate and are canonicalized, and
+    /// -0 is less than +0.
+    fn emit_v128_float_min_max(&mut self, lane_size: Size, max: bool) {
+        let (min_max, unord, sub): (
+            fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+            fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+            fn(&mut Assembler, XMM, XMMOrMemory, XMM),
+        ) = match (lane_size, max) {
+            (Size::S32, false) => (
+                Assembler::emit_vminps,
+                Assembler::emit_vcmpunordps,
+                Assembler::emit_vsubps,
+            ),
+            (Size::S32, true) => (
+                Assembler::emit_vmaxps,
+                Assembler::emit_vcmpunordps,
+                Assembler::emit_vsubps,
+            ),
+            (_, false) => (
+                Assembler::emit_vminpd,
+                Assembler::emit_vcmpunordpd,
+                Assembler::emit_vsubpd,
+            ),
+            (_, true) => (
+                Assembler::emit_vmaxpd,
+                Assembler::emit_vcmpunordpd,
+                Assembler::emit_vsubpd,
+            ),
+        };
+        self.emit_v128_op(2, |this, t| {
+            // `minps` and `maxps` return their second operand if either is a NaN
+            // or both are zeros, so compute them in both orders.
+            min_max(&mut this.assembler, t[0], XMMOrMemory::XMM(t[1]), t[2]);
+            min_max(&mut this.assembler, t[1], XMMOrMemory::XMM(t[0]), t[0]);
+            if max {
+                // Propagate the NaNs, and clear the sign of +0 through the
+                // subtraction of the discrepancies.
+                this.assembler
+                    .emit_vpxor(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                this.assembler.emit_vpor(t[2], XMMOrMemory::XMM(t[0]), t[2]);
+                sub(&mut this.assembler, t[2], XMMOrMemory::XMM(t[0]), t[2]);
+                unord(&mut this.assembler, t[0], XMMOrMemory::XMM(t[2]), t[0]);
+            } else {
+                // Propagate the NaNs and the sign of -0.
+                this.assembler.emit_vpor(t[2], XMMOrMemory::XMM(t[0]), t[2]);
+                unord(&mut this.assembler, t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                this.assembler.emit_vpor(t[2], XMMOrMemory::XMM(t[0]), t[2]);
+            }
+            // Canonicalize the NaNs by clearing their payload.
+            match lane_size {
+                Size::S32 => this.assembler.emit_psrld_imm8(10, t[0]),
+                _ => this.assembler.emit_psrlq_imm8(13, t[0]),
+            }
+            this.assembler
+                .emit_vpandn(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+        });
+    }
+
+    /// Emits a memory operation.
+    fn emit_memory_op<F: FnOnce(&mut Self, GPR) -> Result<(), CodegenError>>(
+        &mut self,
+        addr: Location,
+        memarg: &MemoryImmediate,
+        check_alignment: bool,
+        value_size: usize,
+        cb: F,
+    ) -> Result<(), CodegenError> {
+        let memory_index = MemoryIndex::new(memarg.memory as usize);
+        let memory64 = self.module.memories[memory_index].memory64;
+        // Guard pages only cover 32-bit addresses, so 64-bit memories are
+        // always bounds checked.
+        let need_check = memory64
+            || match self.memory_styles[memory_index] {
+                MemoryStyle::Static { .. } => false,
+                MemoryStyle::Dynamic { .. } => true,
+            };
+        let tmp_addr = self.machine.acquire_temp_gpr().unwrap();
+
+        // Reusing `tmp_addr` for temporary indirection here, since it's not used before the last reference to `{base,bound}_loc`.
+        let (base_loc, bound_loc) = match self.module.local_memory_index(memory_index) {
+            None => {
+                // Imported memories require one level of indirection.
+                let offset = self
+                    .vmoffsets
+                    .vmctx_vmmemory_import_definition(memory_index);
+                self.emit_relaxed_binop(
+                    Assembler::emit_mov,
+                    Size::S64,
+                    Location::Memory(Machine::get_vmctx_reg(), offset as i32),
+                    Location::GPR(tmp_addr),
+                );
+                (Location::Memory(tmp_addr, 0), Location::Memory(tmp_addr, 8))
+            }
+            Some(local_index) => {
+                let offset = self.vmoffsets.vmctx_vmmemory_definition(local_index);
+                (
+                    Location::Memory(Machine::get_vmctx_reg(), offset as i32),
+                    Location::Memory(Machine::get_vmctx_reg(), (offset + 8) as i32),
+                )
+            }
+        };
+
+        let tmp_base = self.machine.acquire_temp_gpr().unwrap();
+        // Only bounds checks need the bound, and atomic operators reserve
+        // one of the few temporary registers for themselves.
+        let tmp_bound = if need_check {
+            Some(self.machine.acquire_temp_gpr().unwrap())
+        } else {
+            None
+        };
+
+        // Load base into temporary register.
+        self.assembler
+            .emit_mov(Size::S64, base_loc, Location::GPR(tmp_base));
+
+        // Load bound into temporary register, if needed.
+        if let Some(tmp_bound) = tmp_bound {
+            self.assembler
+                .emit_mov(Size::S64, bound_loc, Location::GPR(tmp_bound));
+
+            // Wasm -> Effective.
+            // Assuming we never underflow - should always be true on Linux/macOS and Windows >=8,
+            // since the first page from 0x0 to 0x1000 is not accepted by mmap.
+
+            // This `lea` calculates the upper bound allowed for the beginning of the word.
+            // Since the upper bound of the memory is (exclusively) `tmp_bound + tmp_base`,
+            // the maximum allowed beginning of word is (inclusively)
+            // `tmp_bound + tmp_base - value_size`.
+            self.assembler.emit_lea(
+                Size::S64,
+                Location::MemoryAddTriple(tmp_bound, tmp_base, -(value_size as i32)),
+                Location::GPR(tmp_bound),
+            );
+        }
+
+        // Load effective address.
+        // `base_loc` and `bound_loc` becomes INVALID after this line, because `tmp_addr`
+        // might be reused.
+        if memory64 {
+            self.assembler
+                .emit_mov(Size::S64, addr, Location::GPR(tmp_addr));
+        } else {
+            self.assembler
+                .emit_mov(Size::S32, addr, Location::GPR(tmp_addr));
+        }
+
+        // Add offset to memory address.
+        if memarg.offset != 0 {
+            if !memory64 {
+                self.assembler.emit_add(
+                    Size::S32,
+                    Location::Imm32(memarg.offset),
+                    Location::GPR(tmp_addr),
+                );
+                // Trap if offset calculation overflowed.
+                self.assembler
+                    .emit_jmp(Condition::Carry, self.special_labels.heap_access_oob);
+            } else {
+                self.emit_add_offset64(memarg.offset, tmp_addr);
+            }
+        }
+
+        // Wasm linear memory -> real memory
+        self.assembler
+            .emit_add(Size::S64, Location::GPR(tmp_base), Location::GPR(tmp_addr));
+        if memory64 {
+            // A 64-bit address can wrap around the host address space.
+            self.assembler
+                .emit_jmp(Condition::Carry, self.special_labels.heap_access_oob);
+        }
+
+        if let Some(tmp_bound) = tmp_bound {
+            // Trap if the end address of the requested area is above that of the linear memory.
+            self.assembler
+                .emit_cmp(Size::S64, Location::GPR(tmp_bound), Location::GPR(tmp_addr));
+
+            // `tmp_bound` is inclusive. So trap only if `tmp_addr > tmp_bound`.
+            self.assembler
+                .emit_jmp(Condition::Above, self.special_labels.heap_access_oob);
+        }
+
+        if let Some(tmp_bound) = tmp_bound {
+            self.machine.release_temp_gpr(tmp_bound);
+        }
+        self.machine.release_temp_gpr(tmp_base);
+
+        // `align` is the log2 of the alignment.
+        let align = memarg.align;
+        if check_alignment && align != 0 {
+            let tmp_aligncheck = self.machine.acquire_temp_gpr().unwrap();
+            self.assembler.emit_mov(
+                Size::S32,
+                Location::GPR(tmp_addr),
+                Location::GPR(tmp_aligncheck),
+            );
+            self.assembler.emit_and(
+                Size::S64,
+                Location::Imm32((1u32 << align) - 1),
+                Location::GPR(tmp_aligncheck),
+            );
+            self.assembler
+                .emit_jmp(Condition::NotEqual, self.special_labels.unaligned_atomic);
+            self.machine.release_temp_gpr(tmp_aligncheck);
+        }
+
+        self.mark_range_with_trap_code(TrapCode::HeapAccessOutOfBounds, |this| cb(this, tmp_addr))?;
+
+        self.machine.release_temp_gpr(tmp_addr);
+        Ok(())
+    }
+
+    /// Emits a memory operation.
+    fn emit_compare_and_swap<F: FnOnce(&mut Self, GPR, GPR)>(
+        &mut self,
+        loc: Location,
+        target: Location,
+        ret: Location,
+        memarg: &MemoryImmediate,
+        value_size: usize,
+        memory_sz: Size,
+        stack_sz: Size,
+        cb: F,
+    ) -> Result<(), CodegenError> {
+        if memory_sz > stack_sz {
+            return Err(CodegenError {
+                message: "emit_compare_and_swap: memory size > stack size".to_string(),
+            });
+        }
+
+        let compare = self.machine.reserve_unused_temp_gpr(GPR::RAX);
+        let value = if loc == Location::GPR(GPR::R14) {
+            GPR::R13
+        } else {
+            GPR::R14
+        };
+        self.assembler.emit_push(Size::S64, Location::GPR(value));
+
+        self.assembler.emit_mov(stack_sz, loc, Location::GPR(value));
+
+        let retry = self.assembler.get_label();
+        self.assembler.emit_label(retry);
+
+        self.emit_memory_op(target, memarg, true, value_size, |this, addr| {
+            // Memory moves with size < 32b do not zero upper bits.
+            if memory_sz < Size::S32 {
+                this.assembler
+                    .emit_xor(Size::S32, Location::GPR(compare), Location::GPR(compare));
+            }
+            this.assembler
+                .emit_mov(memory_sz, Location::Memory(addr, 0), Location::GPR(compare));
+            this.assembler
+                .emit_mov(stack_sz, Location::GPR(compare), ret);
+            cb(this, compare, value);
+            this.assembler.emit_lock_cmpxchg(
+                memory_sz,
+                Location::GPR(value),
+                Location::Memory(addr, 0),
+            );
+            Ok(())
+        })?;
+
+        self.assembler.emit_jmp(Condition::NotEqual, retry);
+
+        self.assembler.emit_pop(Size::S64, Location::GPR(value));
+        self.machine.release_temp_gpr(compare);
+        Ok(())
+    }
+
+    // Checks for underflow/overflow/nan.
+    fn emit_f32_int_conv_check(
+        &mut self,
+        reg: XMM,
+        lower_bound: f32,
+        upper_bound: f32,
+        underflow_label: <Assembler as Emitter>::Label,
+        overflow_label: <Assembler as Emitter>::Label,
+        nan_label: <Assembler as Emitter>::Label,
+        succeed_label: <Assembler as Emitter>::Label,
+    ) {
+        let lower_bound = f32::to_bits(lower_bound);
+        let upper_bound = f32::to_bits(upper_bound);
 
         let tmp = self.machine.acquire_temp_gpr().unwrap();
         let tmp_x = self.machine.acquire_temp_xmm().unwrap();
@@ -1736,6 +2526,455 @@ impl<'a> FuncGen<'a> {
         id
     }
 
+    /// Returns the parameter and result types of a block.
+    fn block_signature(
+        &self,
+        ty: WpTypeOrFuncType,
+    ) -> Result<(SmallVec<[WpType; 8]>, SmallVec<[WpType; 1]>), CodegenError> {
+        let (params, returns): (SmallVec<[WpType; 8]>, SmallVec<[WpType; 1]>) = match ty {
+            WpTypeOrFuncType::Type(WpType::EmptyBlockType) => (smallvec![], smallvec![]),
+            WpTypeOrFuncType::Type(inner_ty) => (smallvec![], smallvec![inner_ty]),
+            WpTypeOrFuncType::FuncType(sig_index) => {
+                let sig = &self.module.signatures[SignatureIndex::new(sig_index as usize)];
+                (
+                    sig.params().iter().cloned().map(type_to_wp_type).collect(),
+                    sig.results().iter().cloned().map(type_to_wp_type).collect(),
+                )
+            }
+        };
+        Ok((params, returns))
+    }
+
+    /// Returns the number of floating point values below `depth` in the value stack.
+    fn fp_stack_depth_at(&self, depth: usize) -> usize {
+        self.fp_stack
+            .iter()
+            .take_while(|fp| fp.depth < depth)
+            .count()
+    }
+
+    /// Reserves stack slots of types `tys` under the `n` values on top of
+    /// the value stack, moving those on the stack above the new slots.
+    ///
+    /// Returns the depth of the value stack where the slots start.
+    fn reserve_frame_slots(&mut self, n: usize, tys: &[WpType]) -> usize {
+        let depth = self.value_stack.len() - n;
+        if tys.is_empty() {
+            return depth;
+        }
+
+        let mut values: SmallVec<[Location; 8]> = self.value_stack.drain(depth..).collect();
+        let stack: SmallVec<[Location; 8]> = values
+            .iter()
+            .copied()
+            .filter(|loc| matches!(loc, Location::Memory(_, _)))
+            .collect();
+        let is_v128: SmallVec<[bool; 8]> =
+            stack.iter().map(|loc| self.machine.is_v128(*loc)).collect();
+        let (slots, moved) = self.machine.insert_stack_locations(
+            &mut self.assembler,
+            &stack,
+            &tys.iter()
+                .enumerate()
+                .map(|(i, &ty)| (ty, MachineValue::WasmStack(depth + i)))
+                .collect::<Vec<_>>(),
+        );
+        self.value_stack.extend_from_slice(&slots);
+
+        // The values on the stack move down under the new slots, so move
+        // them starting from the last one.
+        for i in (0..stack.len()).rev() {
+            if is_v128[i] {
+                self.emit_v128_mov(stack[i], moved[i]);
+            } else {
+                self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, stack[i], moved[i]);
+            }
+        }
+        let mut moved = moved.into_iter();
+        for value in values.iter_mut() {
+            if let Location::Memory(_, _) = *value {
+                *value = moved.next().unwrap();
+            }
+        }
+        self.value_stack.extend_from_slice(&values);
+
+        for fp in self.fp_stack.iter_mut() {
+            if fp.depth >= depth {
+                fp.depth += tys.len();
+            }
+        }
+        depth
+    }
+
+    /// Pushes copies of the values of types `tys` starting at `depth` in
+    /// the value stack.
+    fn push_value_copies(&mut self, depth: usize, tys: &[WpType]) {
+        for (i, &ty) in tys.iter().enumerate() {
+            let src = self.value_stack[depth + i];
+            let loc = self.machine.acquire_locations(
+                &mut self.assembler,
+                &[(ty, MachineValue::WasmStack(self.value_stack.len()))],
+                false,
+            )[0];
+            if ty == WpType::V128 {
+                self.emit_v128_mov(src, loc);
+            } else {
+                self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, src, loc);
+            }
+            self.value_stack.push(loc);
+            if ty.is_float() {
+                let canonicalization = self
+                    .fp_stack
+                    .iter()
+                    .find(|fp| fp.depth == depth + i)
+                    .and_then(|fp| fp.canonicalization);
+                self.fp_stack.push(FloatValue {
+                    canonicalization,
+                    depth: self.value_stack.len() - 1,
+                });
+            }
+        }
+    }
+
+    /// Moves the values of types `tys` starting at `depth` in the value
+    /// stack to `dsts`, canonicalizing them if needed.
+    fn emit_move_values(&mut self, depth: usize, tys: &[WpType], dsts: &[Location]) {
+        for (i, (&ty, &dst)) in tys.iter().zip(dsts.iter()).enumerate() {
+            let loc = self.value_stack[depth + i];
+            match self.pending_canonicalization(depth + i) {
+                Some(canonicalization) if ty.is_float() => {
+                    self.canonicalize_nan(canonicalization.to_size(), loc, dst);
+                }
+                _ if ty == WpType::V128 => self.emit_v128_mov(loc, dst),
+                _ => {
+                    if loc != dst {
+                        self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, loc, dst);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the canonicalization the float at `depth` in the value stack
+    /// needs before leaving it, if any.
+    fn pending_canonicalization(&self, depth: usize) -> Option<CanonicalizeType> {
+        if !self.assembler.arch_supports_canonicalize_nan()
+            || !self.config.enable_nan_canonicalization
+        {
+            return None;
+        }
+        self.fp_stack
+            .iter()
+            .rev()
+            .find(|fp| fp.depth == depth)
+            .and_then(|fp| fp.canonicalization)
+    }
+
+    /// Moves the results on top of the value stack to where the function
+    /// returns them, following its `FunctionAbi`.
+    fn emit_function_results(&mut self, tys: &[WpType]) {
+        let depth = self.value_stack.len() - tys.len();
+        let abi = FunctionAbi::new(self.signature.params(), self.signature.results());
+
+        if let Some(ptr) = self.results_area_ptr {
+            let tmp = self.machine.acquire_temp_gpr().unwrap();
+            self.assembler.emit_mov(Size::S64, ptr, Location::GPR(tmp));
+            for (i, (&ty, result)) in tys.iter().zip(abi.results.iter()).enumerate() {
+                let offset = match *result {
+                    AbiLocation::Memory(offset) => offset,
+                    AbiLocation::Reg(_) => unreachable!(),
+                };
+                let loc = self.value_stack[depth + i];
+                let dst = Location::Memory(tmp, offset);
+                let size = match ty {
+                    WpType::I32 | WpType::F32 => Size::S32,
+                    _ => Size::S64,
+                };
+                match self.pending_canonicalization(depth + i) {
+                    Some(canonicalization) if ty.is_float() => {
+                        self.canonicalize_nan(canonicalization.to_size(), loc, dst);
+                    }
+                    _ if ty == WpType::V128 => self.emit_v128_mov(loc, dst),
+                    _ => self.emit_relaxed_binop(Assembler::emit_mov, size, loc, dst),
+                }
+            }
+            // The address of the results area is returned in RAX.
+            if tmp != GPR::RAX {
+                self.assembler
+                    .emit_mov(Size::S64, Location::GPR(tmp), Location::GPR(GPR::RAX));
+            }
+            self.machine.release_temp_gpr(tmp);
+            return;
+        }
+
+        // Canonicalize the floats first, since it takes the registers the
+        // results are returned in.
+        for (i, &ty) in tys.iter().enumerate() {
+            if let Some(canonicalization) = self.pending_canonicalization(depth + i) {
+                if ty.is_float() {
+                    let loc = self.value_stack[depth + i];
+                    self.canonicalize_nan(canonicalization.to_size(), loc, loc);
+                }
+            }
+        }
+        // Moving a constant to an XMM register takes a GPR, so the results
+        // returned in XMM registers are moved first.
+        for (i, (&ty, result)) in tys.iter().zip(abi.results.iter()).enumerate() {
+            let loc = self.value_stack[depth + i];
+            match *result {
+                AbiLocation::Reg(X64Register::XMM(xmm)) if ty == WpType::V128 => {
+                    self.emit_v128_mov(loc, Location::XMM(xmm));
+                }
+                AbiLocation::Reg(X64Register::XMM(xmm)) => {
+                    self.emit_relaxed_binop(
+                        Assembler::emit_mov,
+                        Size::S64,
+                        loc,
+                        Location::XMM(xmm),
+                    );
+                }
+                _ => {}
+            }
+        }
+        for (i, result) in abi.results.iter().enumerate() {
+            if let AbiLocation::Reg(X64Register::GPR(gpr)) = *result {
+                let loc = self.value_stack[depth + i];
+                self.assembler.emit_mov(Size::S64, loc, Location::GPR(gpr));
+            }
+        }
+    }
+
+    /// Moves the results on top of the value stack to where the frame at
+    /// `frame_index` expects them.
+    ///
+    /// Blocks return a single scalar result in RAX, and the other results
+    /// in their return slots.
+    fn emit_frame_results(&mut self, frame_index: usize) {
+        let frame = &self.control_stack[frame_index];
+        let tys = frame.returns.clone();
+        if tys.is_empty() {
+            return;
+        }
+        let depth = self.value_stack.len() - tys.len();
+        if frame.return_slots > 0 {
+            let slots: SmallVec<[Location; 8]> = self.value_stack
+                [frame.slots_depth..frame.slots_depth + frame.return_slots]
+                .iter()
+                .copied()
+                .collect();
+            self.emit_move_values(depth, &tys, &slots);
+            return;
+        }
+        if frame_index == 0 {
+            self.emit_function_results(&tys);
+            return;
+        }
+        self.emit_move_values(depth, &tys, &[Location::GPR(GPR::RAX)]);
+    }
+
+    /// Moves the values on top of the value stack to where a branch to the
+    /// frame at `frame_index` passes them: the parameter slots of a loop,
+    /// or the results of other frames.
+    fn emit_branch_values(&mut self, frame_index: usize) {
+        let frame = &self.control_stack[frame_index];
+        if frame.loop_like {
+            let tys = frame.params.clone();
+            let slots: SmallVec<[Location; 8]> = self.value_stack
+                [frame.value_stack_depth - tys.len()..frame.value_stack_depth]
+                .iter()
+                .copied()
+                .collect();
+            self.emit_move_values(self.value_stack.len() - tys.len(), &tys, &slots);
+        } else {
+            self.emit_frame_results(frame_index);
+        }
+    }
+
+    /// Emits a branch to the frame at `frame_index`.
+    fn emit_branch(&mut self, frame_index: usize) {
+        self.emit_branch_values(frame_index);
+        let frame = &self.control_stack[frame_index];
+        let released = &self.value_stack[frame.branch_depth()..];
+        self.machine
+            .release_locations_keep_state(&mut self.assembler, released);
+        self.assembler.emit_jmp(Condition::None, frame.label);
+    }
+
+    /// Pushes the results of a call to a function with the given
+    /// `FunctionAbi`.
+    fn push_call_results(&mut self, return_types: &[WpType], abi: &FunctionAbi) {
+        if return_types.is_empty() {
+            return;
+        }
+        let depth = self.value_stack.len();
+        let rets = self.machine.acquire_locations(
+            &mut self.assembler,
+            &return_types
+                .iter()
+                .enumerate()
+                .map(|(i, &ty)| (ty, MachineValue::WasmStack(depth + i)))
+                .collect::<Vec<_>>(),
+            false,
+        );
+        for ((&ty, result), &ret) in return_types.iter().zip(abi.results.iter()).zip(rets.iter()) {
+            match *result {
+                AbiLocation::Reg(X64Register::GPR(gpr)) => {
+                    self.assembler.emit_mov(Size::S64, Location::GPR(gpr), ret);
+                }
+                AbiLocation::Reg(X64Register::XMM(xmm)) if ty == WpType::V128 => {
+                    self.emit_v128_mov(Location::XMM(xmm), ret);
+                }
+                AbiLocation::Reg(X64Register::XMM(xmm)) => {
+                    self.assembler.emit_mov(Size::S64, Location::XMM(xmm), ret);
+                }
+                AbiLocation::Memory(offset) => {
+                    let src = match self.call_results_area.unwrap() {
+                        Location::Memory(base, area_offset) => {
+                            Location::Memory(base, area_offset + offset)
+                        }
+                        _ => unreachable!(),
+                    };
+                    match ty {
+                        WpType::V128 => self.emit_v128_mov(src, ret),
+                        WpType::I32 | WpType::F32 => {
+                            self.emit_relaxed_binop(Assembler::emit_mov, Size::S32, src, ret)
+                        }
+                        _ => self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, src, ret),
+                    }
+                }
+            }
+        }
+        for (&ty, &ret) in return_types.iter().zip(rets.iter()) {
+            self.value_stack.push(ret);
+            if ty.is_float() {
+                self.fp_stack
+                    .push(FloatValue::new(self.value_stack.len() - 1));
+            }
+        }
+    }
+
+    /// Emits a `select` of two values, which are `v128` values if `v128`.
+    fn emit_select(&mut self, v128: bool) -> Result<(), CodegenError> {
+        let cond = self.pop_value_released();
+        let v_b = self.pop_value_released();
+        let v_a = self.pop_value_released();
+        if v128 {
+            let ret = self.machine.acquire_locations(
+                &mut self.assembler,
+                &[(
+                    WpType::V128,
+                    MachineValue::WasmStack(self.value_stack.len()),
+                )],
+                false,
+            )[0];
+            self.value_stack.push(ret);
+
+            let end_label = self.assembler.get_label();
+            let zero_label = self.assembler.get_label();
+            self.emit_relaxed_binop(Assembler::emit_cmp, Size::S32, Location::Imm32(0), cond);
+            self.assembler.emit_jmp(Condition::Equal, zero_label);
+            if v_a != ret {
+                self.emit_v128_mov(v_a, ret);
+            }
+            self.assembler.emit_jmp(Condition::None, end_label);
+            self.assembler.emit_label(zero_label);
+            if v_b != ret {
+                self.emit_v128_mov(v_b, ret);
+            }
+            self.assembler.emit_label(end_label);
+            return Ok(());
+        }
+        let cncl: Option<(Option<CanonicalizeType>, Option<CanonicalizeType>)> =
+            if self.fp_stack.len() >= 2
+                && self.fp_stack[self.fp_stack.len() - 2].depth == self.value_stack.len()
+                && self.fp_stack[self.fp_stack.len() - 1].depth == self.value_stack.len() + 1
+            {
+                let (left, right) = self.fp_stack.pop2()?;
+                self.fp_stack.push(FloatValue::new(self.value_stack.len()));
+                Some((left.canonicalization, right.canonicalization))
+            } else {
+                None
+            };
+        let ret = self.machine.acquire_locations(
+            &mut self.assembler,
+            &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+            false,
+        )[0];
+        self.value_stack.push(ret);
+
+        let end_label = self.assembler.get_label();
+        let zero_label = self.assembler.get_label();
+
+        self.emit_relaxed_binop(Assembler::emit_cmp, Size::S32, Location::Imm32(0), cond);
+        self.assembler.emit_jmp(Condition::Equal, zero_label);
+        match cncl {
+            Some((Some(fp), _))
+                if self.assembler.arch_supports_canonicalize_nan()
+                    && self.config.enable_nan_canonicalization =>
+            {
+                self.canonicalize_nan(fp.to_size(), v_a, ret);
+            }
+            _ => {
+                if v_a != ret {
+                    self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, v_a, ret);
+                }
+            }
+        }
+        self.assembler.emit_jmp(Condition::None, end_label);
+        self.assembler.emit_label(zero_label);
+        match cncl {
+            Some((_, Some(fp)))
+                if self.assembler.arch_supports_canonicalize_nan()
+                    && self.config.enable_nan_canonicalization =>
+            {
+                self.canonicalize_nan(fp.to_size(), v_b, ret);
+            }
+            _ => {
+                if v_b != ret {
+                    self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, v_b, ret);
+                }
+            }
+        }
+        self.assembler.emit_label(end_label);
+        Ok(())
+    }
+
+    /// Emits the start of the `else` branch of the current `if` frame.
+    fn emit_else(&mut self, was_unreachable: bool) -> Result<(), CodegenError> {
+        let frame_index = self.control_stack.len() - 1;
+        if !was_unreachable {
+            self.emit_frame_results(frame_index);
+        }
+
+        let frame = &mut self.control_stack[frame_index];
+        let released: &[Location] = &self.value_stack[frame.value_stack_depth..];
+        self.machine
+            .release_locations(&mut self.assembler, released);
+        self.value_stack.truncate(frame.value_stack_depth);
+        self.fp_stack.truncate(frame.fp_stack_depth);
+
+        match frame.if_else {
+            IfElseState::If(label) => {
+                // The parameters kept for the `else` branch are released at the end.
+                let kept = &self.value_stack[frame.branch_depth()..];
+                self.machine
+                    .release_locations_keep_state(&mut self.assembler, kept);
+                self.assembler.emit_jmp(Condition::None, frame.label);
+                self.assembler.emit_label(label);
+                frame.if_else = IfElseState::Else;
+            }
+            _ => {
+                return Err(CodegenError {
+                    message: "Else: frame.if_else unreachable code".to_string(),
+                })
+            }
+        }
+
+        let params = frame.params.clone();
+        self.push_value_copies(self.value_stack.len() - params.len(), &params);
+        Ok(())
+    }
+
     fn emit_head(&mut self) -> Result<(), CodegenError> {
         // TODO: Patchpoint is not emitted for now, and ARM trampoline is not prepended.
 
@@ -1744,17 +2983,70 @@ impl<'a> FuncGen<'a> {
         self.assembler
             .emit_mov(Size::S64, Location::GPR(GPR::RSP), Location::GPR(GPR::RBP));
 
+        let abi = FunctionAbi::new(self.signature.params(), self.signature.results());
+
+        // `init_locals` overwrites the parameter registers, so keep the
+        // address of the results area in R11 until it is saved.
+        if let Some(results_area) = abi.results_area {
+            self.assembler.emit_mov(
+                Size::S64,
+                Location::GPR(results_area),
+                Location::GPR(GPR::R11),
+            );
+        }
+
         // Initialize locals.
-        self.locals = self.machine.init_locals(
-            &mut self.assembler,
-            self.local_types.len(),
-            self.signature.params().len(),
-        );
+        let params: Vec<Location> = abi
+            .params
+            .iter()
+            .map(|param| match *param {
+                AbiLocation::Reg(X64Register::GPR(gpr)) => Location::GPR(gpr),
+                AbiLocation::Reg(X64Register::XMM(xmm)) => Location::XMM(xmm),
+                AbiLocation::Memory(offset) => Location::Memory(GPR::RBP, 16 + offset),
+            })
+            .collect();
+        self.locals =
+            self.machine
+                .init_locals(&mut self.assembler, &self.local_types, &params, abi.vmctx);
+        self.locals_code_range.0 = self.assembler.get_offset().0;
 
         // Mark vmctx register. The actual loading of the vmctx value is handled by init_local.
         self.machine.state.register_values
             [X64Register::GPR(Machine::get_vmctx_reg()).to_index().0] = MachineValue::Vmctx;
 
+        if abi.results_area.is_some() {
+            let ptr = self.machine.acquire_stack_locations(
+                &mut self.assembler,
+                &[(WpType::I64, MachineValue::Undefined)],
+            )[0];
+            self.assembler
+                .emit_mov(Size::S64, Location::GPR(GPR::R11), ptr);
+            self.machine.release_locations_only_osr_state(1);
+            self.results_area_ptr = Some(ptr);
+        }
+
+        // Reserve the area where the called functions return their results
+        // if they don't fit in registers, aligned for `v128` values.
+        let call_results_area_size = self
+            .module
+            .signatures
+            .values()
+            .map(|sig| FunctionAbi::new(sig.params(), sig.results()).results_area_size)
+            .max()
+            .unwrap_or(0);
+        if call_results_area_size > 0 {
+            let mut slots = (call_results_area_size + 15) / 16 * 2;
+            if self.machine.get_stack_offset() % 16 != 0 {
+                slots += 1;
+            }
+            let area = self.machine.acquire_stack_locations(
+                &mut self.assembler,
+                &vec![(WpType::I64, MachineValue::Undefined); slots],
+            );
+            self.machine.release_locations_only_osr_state(slots);
+            self.call_results_area = area.last().copied();
+        }
+
         // TODO: Explicit stack check is not supported for now.
         let diff = self.machine.state.diff(&new_machine_state());
         let state_diff_id = self.fsm.diffs.len();
@@ -1767,12 +3059,15 @@ impl<'a> FuncGen<'a> {
             label: self.assembler.get_label(),
             loop_like: false,
             if_else: IfElseState::None,
+            params: smallvec![],
             returns: self
                 .signature
                 .results()
                 .iter()
                 .map(|&x| type_to_wp_type(x))
                 .collect(),
+            return_slots: 0,
+            slots_depth: 0,
             value_stack_depth: 0,
             fp_stack_depth: 0,
             state: self.machine.state.clone(),
@@ -1840,6 +3135,7 @@ impl<'a> FuncGen<'a> {
         let special_labels = SpecialLabelSet {
             integer_division_by_zero: assembler.get_label(),
             heap_access_oob: assembler.get_label(),
+            unaligned_atomic: assembler.get_label(),
             table_access_oob: assembler.get_label(),
             indirect_call_null: assembler.get_label(),
             bad_signature: assembler.get_label(),
@@ -1855,6 +3151,7 @@ impl<'a> FuncGen<'a> {
             assembler,
             locals: vec![], // initialization deferred to emit_head
             local_types,
+            locals_code_range: (0, 0),
             value_stack: vec![],
             fp_stack: vec![],
             control_stack: vec![],
@@ -1866,6 +3163,8 @@ impl<'a> FuncGen<'a> {
             special_labels,
             src_loc: 0,
             instructions_address_map: vec![],
+            results_area_ptr: None,
+            call_results_area: None,
         };
         fg.emit_head()?;
         Ok(fg)
@@ -1954,7 +3253,11 @@ impl<'a> FuncGen<'a> {
                     Location::Memory(tmp, 0)
                 };
 
-                self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, src, loc);
+                if ty == WpType::V128 {
+                    self.emit_v128_mov(src, loc);
+                } else {
+                    self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, src, loc);
+                }
 
                 self.machine.release_temp_gpr(tmp);
             }
@@ -2005,6 +3308,8 @@ impl<'a> FuncGen<'a> {
                     } else {
                         self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, loc, dst);
                     }
+                } else if ty == WpType::V128 {
+                    self.emit_v128_mov(loc, dst);
                 } else {
                     self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, loc, dst);
                 }
@@ -2012,6 +3317,19 @@ impl<'a> FuncGen<'a> {
             }
             Operator::LocalGet { local_index } => {
                 let local_index = local_index as usize;
+                if self.local_types[local_index] == WpType::V128 {
+                    let ret = self.machine.acquire_locations(
+                        &mut self.assembler,
+                        &[(
+                            WpType::V128,
+                            MachineValue::WasmStack(self.value_stack.len()),
+                        )],
+                        false,
+                    )[0];
+                    self.emit_v128_mov(self.locals[local_index], ret);
+                    self.value_stack.push(ret);
+                    return Ok(());
+                }
                 let ret = self.machine.acquire_locations(
                     &mut self.assembler,
                     &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
@@ -2033,7 +3351,9 @@ impl<'a> FuncGen<'a> {
                 let local_index = local_index as usize;
                 let loc = self.pop_value_released();
 
-                if self.local_types[local_index].is_float() {
+                if self.local_types[local_index] == WpType::V128 {
+                    self.emit_v128_mov(loc, self.locals[local_index]);
+                } else if self.local_types[local_index].is_float() {
                     let fp = self.fp_stack.pop1()?;
                     if self.assembler.arch_supports_canonicalize_nan()
                         && self.config.enable_nan_canonicalization
@@ -2069,7 +3389,9 @@ impl<'a> FuncGen<'a> {
                 let local_index = local_index as usize;
                 let loc = *self.value_stack.last().unwrap();
 
-                if self.local_types[local_index].is_float() {
+                if self.local_types[local_index] == WpType::V128 {
+                    self.emit_v128_mov(loc, self.locals[local_index]);
+                } else if self.local_types[local_index].is_float() {
                     let fp = self.fp_stack.peek1()?;
                     if self.assembler.arch_supports_canonicalize_nan()
                         && self.config.enable_nan_canonicalization
@@ -5210,7 +6532,8 @@ impl<'a> FuncGen<'a> {
                     Location::GPR(GPR::RAX),
                 );
 
-                self.emit_call_sysv(
+                let abi = FunctionAbi::new(sig.params(), sig.results());
+                self.emit_call_with_abi(
                     |this| {
                         let offset = this.assembler.get_offset().0;
                         this.trap_table
@@ -5219,39 +6542,17 @@ impl<'a> FuncGen<'a> {
                         this.assembler.emit_call_location(Location::GPR(GPR::RAX));
                         this.mark_instruction_address_end(offset);
                     },
-                    params.iter().copied(),
+                    &params,
+                    &param_types,
+                    &abi,
                 )?;
 
                 self.machine
                     .release_locations_only_stack(&mut self.assembler, &params);
 
-                if !return_types.is_empty() {
-                    let ret = self.machine.acquire_locations(
-                        &mut self.assembler,
-                        &[(
-                            return_types[0],
-                            MachineValue::WasmStack(self.value_stack.len()),
-                        )],
-                        false,
-                    )[0];
-                    self.value_stack.push(ret);
-                    if return_types[0].is_float() {
-                        self.assembler
-                            .emit_mov(Size::S64, Location::XMM(XMM::XMM0), ret);
-                        self.fp_stack
-                            .push(FloatValue::new(self.value_stack.len() - 1));
-                    } else {
-                        self.assembler
-                            .emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
-                    }
-                }
+                self.push_call_results(&return_types, &abi);
             }
             Operator::CallIndirect { index, table_index } => {
-                if table_index != 0 {
-                    return Err(CodegenError {
-                        message: "CallIndirect: table_index is not 0".to_string(),
-                    });
-                }
                 let table_index = TableIndex::new(table_index as _);
                 let index = SignatureIndex::new(index as usize);
                 let sig = self.module.signatures.get(index).unwrap();
@@ -5399,9 +6700,19 @@ impl<'a> FuncGen<'a> {
 
                 let vmcaller_checked_anyfunc_func_ptr =
                     self.vmoffsets.vmcaller_checked_anyfunc_func_ptr() as usize;
+                let vmcaller_checked_anyfunc_vmctx =
+                    self.vmoffsets.vmcaller_checked_anyfunc_vmctx() as usize;
 
-                self.emit_call_sysv(
+                let abi = FunctionAbi::new(sig.params(), sig.results());
+                let vmctx = abi.vmctx;
+                self.emit_call_with_abi(
                     |this| {
+                        // The callee may belong to another instance, or be a host function.
+                        this.assembler.emit_mov(
+                            Size::S64,
+                            Location::Memory(GPR::RAX, vmcaller_checked_anyfunc_vmctx as i32),
+                            Location::GPR(vmctx),
+                        );
                         if this.assembler.arch_requires_indirect_call_trampoline() {
                             this.assembler.arch_emit_indirect_call_with_trampoline(
                                 Location::Memory(
@@ -5421,202 +6732,123 @@ impl<'a> FuncGen<'a> {
                             this.mark_instruction_address_end(offset);
                         }
                     },
-                    params.iter().copied(),
+                    &params,
+                    &param_types,
+                    &abi,
                 )?;
 
                 self.machine
                     .release_locations_only_stack(&mut self.assembler, &params);
 
-                if !return_types.is_empty() {
-                    let ret = self.machine.acquire_locations(
-                        &mut self.assembler,
-                        &[(
-                            return_types[0],
-                            MachineValue::WasmStack(self.value_stack.len()),
-                        )],
-                        false,
-                    )[0];
-                    self.value_stack.push(ret);
-                    if return_types[0].is_float() {
-                        self.assembler
-                            .emit_mov(Size::S64, Location::XMM(XMM::XMM0), ret);
-                        self.fp_stack
-                            .push(FloatValue::new(self.value_stack.len() - 1));
-                    } else {
-                        self.assembler
-                            .emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
-                    }
-                }
+                self.push_call_results(&return_types, &abi);
             }
             Operator::If { ty } => {
                 let label_end = self.assembler.get_label();
                 let label_else = self.assembler.get_label();
 
+                let (params, returns) = self.block_signature(ty)?;
+                let return_slots = block_return_slots(&returns);
+
                 let cond = self.pop_value_released();
 
+                // Reserving the return slots reuses the stack, so keep the
+                // condition in a register until the branch.
+                let tmp = if params.is_empty() && return_slots == 0 {
+                    None
+                } else {
+                    let tmp = self.machine.acquire_temp_gpr().unwrap();
+                    self.assembler.emit_mov(Size::S32, cond, Location::GPR(tmp));
+                    Some(tmp)
+                };
+
+                // The parameters are kept under the frame for the `else` branch.
+                let slots_depth = self.reserve_frame_slots(params.len(), &returns[..return_slots]);
+                let value_stack_depth = self.value_stack.len();
+
                 let frame = ControlFrame {
                     label: label_end,
                     loop_like: false,
                     if_else: IfElseState::If(label_else),
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CodegenError {
-                                message: "If: multi-value returns not yet implemented".to_string(),
-                            })
-                        }
-                    },
-                    value_stack_depth: self.value_stack.len(),
+                    params,
+                    returns,
+                    return_slots,
+                    slots_depth,
+                    value_stack_depth,
                     fp_stack_depth: self.fp_stack.len(),
                     state: self.machine.state.clone(),
                     state_diff_id: self.get_state_diff(),
                 };
+                let params = frame.params.clone();
                 self.control_stack.push(frame);
-                self.emit_relaxed_binop(Assembler::emit_cmp, Size::S32, Location::Imm32(0), cond);
+                match tmp {
+                    Some(tmp) => {
+                        self.assembler
+                            .emit_cmp(Size::S32, Location::Imm32(0), Location::GPR(tmp));
+                        self.machine.release_temp_gpr(tmp);
+                    }
+                    None => self.emit_relaxed_binop(
+                        Assembler::emit_cmp,
+                        Size::S32,
+                        Location::Imm32(0),
+                        cond,
+                    ),
+                }
                 self.assembler.emit_jmp(Condition::Equal, label_else);
+                self.push_value_copies(value_stack_depth - params.len(), &params);
             }
             Operator::Else => {
-                let frame = self.control_stack.last_mut().unwrap();
-
-                if !was_unreachable && !frame.returns.is_empty() {
-                    let first_return = frame.returns[0];
-                    let loc = *self.value_stack.last().unwrap();
-                    if first_return.is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        if self.assembler.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                        {
-                            self.canonicalize_nan(
-                                match first_return {
-                                    WpType::F32 => Size::S32,
-                                    WpType::F64 => Size::S64,
-                                    _ => unreachable!(),
-                                },
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        } else {
-                            self.emit_relaxed_binop(
-                                Assembler::emit_mov,
-                                Size::S64,
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        }
-                    } else {
-                        self.emit_relaxed_binop(
-                            Assembler::emit_mov,
-                            Size::S64,
-                            loc,
-                            Location::GPR(GPR::RAX),
-                        );
-                    }
-                }
-
-                let mut frame = self.control_stack.last_mut().unwrap();
-
-                let released: &[Location] = &self.value_stack[frame.value_stack_depth..];
-                self.machine
-                    .release_locations(&mut self.assembler, released);
-                self.value_stack.truncate(frame.value_stack_depth);
-                self.fp_stack.truncate(frame.fp_stack_depth);
-
-                match frame.if_else {
-                    IfElseState::If(label) => {
-                        self.assembler.emit_jmp(Condition::None, frame.label);
-                        self.assembler.emit_label(label);
-                        frame.if_else = IfElseState::Else;
-                    }
-                    _ => {
-                        return Err(CodegenError {
-                            message: "Else: frame.if_else unreachable code".to_string(),
-                        })
-                    }
-                }
+                self.emit_else(was_unreachable)?;
             }
             Operator::Select => {
-                let cond = self.pop_value_released();
-                let v_b = self.pop_value_released();
-                let v_a = self.pop_value_released();
-                let cncl: Option<(Option<CanonicalizeType>, Option<CanonicalizeType>)> =
-                    if self.fp_stack.len() >= 2
-                        && self.fp_stack[self.fp_stack.len() - 2].depth == self.value_stack.len()
-                        && self.fp_stack[self.fp_stack.len() - 1].depth
-                            == self.value_stack.len() + 1
-                    {
-                        let (left, right) = self.fp_stack.pop2()?;
-                        self.fp_stack.push(FloatValue::new(self.value_stack.len()));
-                        Some((left.canonicalization, right.canonicalization))
-                    } else {
-                        None
-                    };
-                let ret = self.machine.acquire_locations(
-                    &mut self.assembler,
-                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
-                    false,
-                )[0];
-                self.value_stack.push(ret);
-
-                let end_label = self.assembler.get_label();
-                let zero_label = self.assembler.get_label();
-
-                self.emit_relaxed_binop(Assembler::emit_cmp, Size::S32, Location::Imm32(0), cond);
-                self.assembler.emit_jmp(Condition::Equal, zero_label);
-                match cncl {
-                    Some((Some(fp), _))
-                        if self.assembler.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization =>
-                    {
-                        self.canonicalize_nan(fp.to_size(), v_a, ret);
-                    }
-                    _ => {
-                        if v_a != ret {
-                            self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, v_a, ret);
-                        }
-                    }
-                }
-                self.assembler.emit_jmp(Condition::None, end_label);
-                self.assembler.emit_label(zero_label);
-                match cncl {
-                    Some((_, Some(fp)))
-                        if self.assembler.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization =>
-                    {
-                        self.canonicalize_nan(fp.to_size(), v_b, ret);
-                    }
-                    _ => {
-                        if v_b != ret {
-                            self.emit_relaxed_binop(Assembler::emit_mov, Size::S64, v_b, ret);
-                        }
-                    }
-                }
-                self.assembler.emit_label(end_label);
+                let v128 = self
+                    .machine
+                    .is_v128(self.value_stack[self.value_stack.len() - 2]);
+                self.emit_select(v128)?;
             }
+            Operator::TypedSelect { ty } => self.emit_select(ty == WpType::V128)?,
             Operator::Block { ty } => {
+                let (params, returns) = self.block_signature(ty)?;
+                let return_slots = block_return_slots(&returns);
+                let slots_depth = self.reserve_frame_slots(params.len(), &returns[..return_slots]);
+                let value_stack_depth = self.value_stack.len() - params.len();
+
                 let frame = ControlFrame {
                     label: self.assembler.get_label(),
                     loop_like: false,
                     if_else: IfElseState::None,
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CodegenError {
-                                message: "Block: multi-value returns not yet implemented"
-                                    .to_string(),
-                            })
-                        }
-                    },
-                    value_stack_depth: self.value_stack.len(),
-                    fp_stack_depth: self.fp_stack.len(),
+                    params,
+                    returns,
+                    return_slots,
+                    slots_depth,
+                    value_stack_depth,
+                    fp_stack_depth: self.fp_stack_depth_at(value_stack_depth),
                     state: self.machine.state.clone(),
                     state_diff_id: self.get_state_diff(),
                 };
                 self.control_stack.push(frame);
             }
             Operator::Loop { ty } => {
+                let (params, returns) = self.block_signature(ty)?;
+                let return_slots = block_return_slots(&returns);
+
+                // Branches to the loop pass its parameters in slots under the frame.
+                let mut slots: SmallVec<[WpType; 8]> = returns[..return_slots].into();
+                slots.extend_from_slice(&params);
+                let slots_depth = self.reserve_frame_slots(params.len(), &slots);
+                let value_stack_depth = self.value_stack.len() - params.len();
+                let param_slots: SmallVec<[Location; 8]> = self.value_stack
+                    [slots_depth + return_slots..value_stack_depth]
+                    .iter()
+                    .copied()
+                    .collect();
+                self.emit_move_values(value_stack_depth, &params, &param_slots);
+                let released: SmallVec<[Location; 8]> =
+                    self.value_stack.drain(value_stack_depth..).collect();
+                self.machine
+                    .release_locations(&mut self.assembler, &released);
+                let fp_stack_depth = self.fp_stack_depth_at(value_stack_depth);
+                self.fp_stack.truncate(fp_stack_depth);
+
                 // Pad with NOPs to the next 16-byte boundary.
                 // Here we don't use the dynasm `.align 16` attribute because it pads the alignment with single-byte nops
                 // which may lead to efficiency problems.
@@ -5636,22 +6868,17 @@ impl<'a> FuncGen<'a> {
                     label,
                     loop_like: true,
                     if_else: IfElseState::None,
-                    returns: match ty {
-                        WpTypeOrFuncType::Type(WpType::EmptyBlockType) => smallvec![],
-                        WpTypeOrFuncType::Type(inner_ty) => smallvec![inner_ty],
-                        _ => {
-                            return Err(CodegenError {
-                                message: "Loop: multi-value returns not yet implemented"
-                                    .to_string(),
-                            })
-                        }
-                    },
-                    value_stack_depth: self.value_stack.len(),
-                    fp_stack_depth: self.fp_stack.len(),
+                    params: params.clone(),
+                    returns,
+                    return_slots,
+                    slots_depth,
+                    value_stack_depth,
+                    fp_stack_depth,
                     state: self.machine.state.clone(),
                     state_diff_id,
                 });
                 self.assembler.emit_label(label);
+                self.push_value_copies(value_stack_depth - params.len(), &params);
 
                 // TODO: Re-enable interrupt signal check without branching
             }
@@ -5663,10 +6890,18 @@ impl<'a> FuncGen<'a> {
                     Location::Memory(
                         Machine::get_vmctx_reg(),
                         self.vmoffsets.vmctx_builtin_function(
-                            if self.module.local_memory_index(memory_index).is_some() {
-                                VMBuiltinFunctionIndex::get_memory32_size_index()
-                            } else {
-                                VMBuiltinFunctionIndex::get_imported_memory32_size_index()
+                            match (
+                                self.module.memories[memory_index].memory64,
+                                self.module.local_memory_index(memory_index).is_some(),
+                            ) {
+                                (false, true) => VMBuiltinFunctionIndex::get_memory32_size_index(),
+                                (false, false) => {
+                                    VMBuiltinFunctionIndex::get_imported_memory32_size_index()
+                                }
+                                (true, true) => VMBuiltinFunctionIndex::get_memory64_size_index(),
+                                (true, false) => {
+                                    VMBuiltinFunctionIndex::get_imported_memory64_size_index()
+                                }
                             },
                         ) as i32,
                     ),
@@ -5699,10 +6934,18 @@ impl<'a> FuncGen<'a> {
                     Location::Memory(
                         Machine::get_vmctx_reg(),
                         self.vmoffsets.vmctx_builtin_function(
-                            if self.module.local_memory_index(memory_index).is_some() {
-                                VMBuiltinFunctionIndex::get_memory32_grow_index()
-                            } else {
-                                VMBuiltinFunctionIndex::get_imported_memory32_grow_index()
+                            match (
+                                self.module.memories[memory_index].memory64,
+                                self.module.local_memory_index(memory_index).is_some(),
+                            ) {
+                                (false, true) => VMBuiltinFunctionIndex::get_memory32_grow_index(),
+                                (false, false) => {
+                                    VMBuiltinFunctionIndex::get_imported_memory32_grow_index()
+                                }
+                                (true, true) => VMBuiltinFunctionIndex::get_memory64_grow_index(),
+                                (true, false) => {
+                                    VMBuiltinFunctionIndex::get_imported_memory64_grow_index()
+                                }
                             },
                         ) as i32,
                     ),
@@ -5732,6 +6975,213 @@ impl<'a> FuncGen<'a> {
                 self.assembler
                     .emit_mov(Size::S64, Location::GPR(GPR::RAX), ret);
             }
+            Operator::MemoryCopy { src, dst } if src != dst => {
+                let dst_memory64 = self.module.memories[MemoryIndex::new(dst as usize)].memory64;
+                let src_memory64 = self.module.memories[MemoryIndex::new(src as usize)].memory64;
+                let builtin = if dst_memory64 || src_memory64 {
+                    // The 64-bit builtin takes every operand as an `i64`, so
+                    // the `i32` ones are zero-extended.
+                    let len = self.value_stack.len();
+                    for (i, memory64) in [dst_memory64, src_memory64, dst_memory64 && src_memory64]
+                        .iter()
+                        .enumerate()
+                    {
+                        if !memory64 {
+                            self.emit_zero_extend_i32(len - 3 + i);
+                        }
+                    }
+                    VMBuiltinFunctionIndex::get_memory64_copy_between_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_memory_copy_between_index()
+                };
+                // [vmctx, dst_memory_index, src_memory_index, dst, src, len]
+                self.emit_call_builtin(builtin, &[dst, src], 3)?;
+            }
+            Operator::MemoryCopy { src, dst: _ } => {
+                let memory_index = MemoryIndex::new(src as usize);
+                let memory64 = self.module.memories[memory_index].memory64;
+                let (builtin, index) = match self.module.local_memory_index(memory_index) {
+                    Some(local_index) if memory64 => (
+                        VMBuiltinFunctionIndex::get_local_memory64_copy_index(),
+                        local_index.index(),
+                    ),
+                    Some(local_index) => (
+                        VMBuiltinFunctionIndex::get_local_memory_copy_index(),
+                        local_index.index(),
+                    ),
+                    None if memory64 => (
+                        VMBuiltinFunctionIndex::get_imported_memory64_copy_index(),
+                        memory_index.index(),
+                    ),
+                    None => (
+                        VMBuiltinFunctionIndex::get_imported_memory_copy_index(),
+                        memory_index.index(),
+                    ),
+                };
+                // [vmctx, memory_index, dst, src, len]
+                self.emit_call_builtin(builtin, &[index as u32], 3)?;
+            }
+            Operator::MemoryFill { mem } => {
+                let memory_index = MemoryIndex::new(mem as usize);
+                let memory64 = self.module.memories[memory_index].memory64;
+                let (builtin, index) = match self.module.local_memory_index(memory_index) {
+                    Some(local_index) if memory64 => (
+                        VMBuiltinFunctionIndex::get_memory64_fill_index(),
+                        local_index.index(),
+                    ),
+                    Some(local_index) => (
+                        VMBuiltinFunctionIndex::get_memory_fill_index(),
+                        local_index.index(),
+                    ),
+                    None if memory64 => (
+                        VMBuiltinFunctionIndex::get_imported_memory64_fill_index(),
+                        memory_index.index(),
+                    ),
+                    None => (
+                        VMBuiltinFunctionIndex::get_imported_memory_fill_index(),
+                        memory_index.index(),
+                    ),
+                };
+                // [vmctx, memory_index, dst, val, len]
+                self.emit_call_builtin(builtin, &[index as u32], 3)?;
+            }
+            Operator::MemoryInit { segment, mem } => {
+                let builtin = if self.module.memories[MemoryIndex::new(mem as usize)].memory64 {
+                    VMBuiltinFunctionIndex::get_memory64_init_index()
+                } else {
+                    VMBuiltinFunctionIndex::get_memory_init_index()
+                };
+                // [vmctx, memory_index, data_index, dst, src, len]
+                self.emit_call_builtin(builtin, &[mem, segment], 3)?;
+            }
+            Operator::DataDrop { segment } => {
+                // [vmctx, data_index]
+                self.emit_call_builtin(
+                    VMBuiltinFunctionIndex::get_data_drop_index(),
+                    &[segment],
+                    0,
+                )?;
+            }
+            Operator::TableCopy {
+                dst_table,
+                src_table,
+            } => {
+                // [vmctx, dst_table_index, src_table_index, dst, src, len]
+                self.emit_call_builtin(
+                    VMBuiltinFunctionIndex::get_table_copy_index(),
+                    &[dst_table, src_table],
+                    3,
+                )?;
+            }
+            Operator::TableInit { segment, table } => {
+                // [vmctx, table_index, elem_index, dst, src, len]
+                self.emit_call_builtin(
+                    VMBuiltinFunctionIndex::get_table_init_index(),
+                    &[table, segment],
+                    3,
+                )?;
+            }
+            Operator::ElemDrop { segment } => {
+                // [vmctx, elem_index]
+                self.emit_call_builtin(
+                    VMBuiltinFunctionIndex::get_elem_drop_index(),
+                    &[segment],
+                    0,
+                )?;
+            }
+            Operator::TableSize { table } => {
+                let table_index = TableIndex::new(table as usize);
+                let ret = self.machine.acquire_locations(
+                    &mut self.assembler,
+                    &[(WpType::I32, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                let tmp = self.machine.acquire_temp_gpr().unwrap();
+                if let Some(local_table_index) = self.module.local_table_index(table_index) {
+                    self.assembler.emit_mov(
+                        Size::S32,
+                        Location::Memory(
+                            Machine::get_vmctx_reg(),
+                            self.vmoffsets
+                                .vmctx_vmtable_definition_current_elements(local_table_index)
+                                as i32,
+                        ),
+                        Location::GPR(tmp),
+                    );
+                } else {
+                    // Do an indirection.
+                    self.assembler.emit_mov(
+                        Size::S64,
+                        Location::Memory(
+                            Machine::get_vmctx_reg(),
+                            self.vmoffsets.vmctx_vmtable_import(table_index) as i32,
+                        ),
+                        Location::GPR(tmp),
+                    );
+                    self.assembler.emit_mov(
+                        Size::S32,
+                        Location::Memory(
+                            tmp,
+                            self.vmoffsets.vmtable_definition_current_elements() as _,
+                        ),
+                        Location::GPR(tmp),
+                    );
+                }
+                self.assembler.emit_mov(Size::S32, Location::GPR(tmp), ret);
+                self.machine.release_temp_gpr(tmp);
+            }
+            Operator::TableGet { table } => {
+                // [vmctx, table_index, index] -> funcref
+                self.emit_call_builtin_with_result(
+                    VMBuiltinFunctionIndex::get_table_get_index(),
+                    &[table],
+                    1,
+                    WpType::FuncRef,
+                )?;
+            }
+            Operator::TableSet { table } => {
+                // [vmctx, table_index, index, value]
+                self.emit_call_builtin(VMBuiltinFunctionIndex::get_table_set_index(), &[table], 2)?;
+            }
+            Operator::TableGrow { table } => {
+                // [vmctx, table_index, init_value, delta] -> i32
+                self.emit_call_builtin_with_result(
+                    VMBuiltinFunctionIndex::get_table_grow_index(),
+                    &[table],
+                    2,
+                    WpType::I32,
+                )?;
+            }
+            Operator::TableFill { table } => {
+                // [vmctx, table_index, dst, value, len]
+                self.emit_call_builtin(
+                    VMBuiltinFunctionIndex::get_table_fill_index(),
+                    &[table],
+                    3,
+                )?;
+            }
+            Operator::RefFunc { function_index } => {
+                // [vmctx, function_index] -> funcref
+                self.emit_call_builtin_with_result(
+                    VMBuiltinFunctionIndex::get_func_ref_index(),
+                    &[function_index],
+                    0,
+                    WpType::FuncRef,
+                )?;
+            }
+            Operator::RefNull { .. } => {
+                // Both `funcref` and `externref` are words, null being 0.
+                self.value_stack.push(Location::Imm64(0));
+                self.machine
+                    .state
+                    .wasm_stack
+                    .push(WasmAbstractValue::Const(0));
+            }
+            Operator::RefIsNull => {
+                self.emit_cmpop_i64_dynamic_b(Condition::Equal, Location::Imm64(0))?
+            }
             Operator::I32Load { ref memarg } => {
                 let target = self.pop_value_released();
                 let ret = self.machine.acquire_locations(
@@ -6182,101 +7632,11 @@ impl<'a> FuncGen<'a> {
                 self.unreachable_depth = 1;
             }
             Operator::Return => {
-                let frame = &self.control_stack[0];
-                if !frame.returns.is_empty() {
-                    if frame.returns.len() != 1 {
-                        return Err(CodegenError {
-                            message: "Return: incorrect frame.returns".to_string(),
-                        });
-                    }
-                    let first_return = frame.returns[0];
-                    let loc = *self.value_stack.last().unwrap();
-                    if first_return.is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        if self.assembler.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                        {
-                            self.canonicalize_nan(
-                                match first_return {
-                                    WpType::F32 => Size::S32,
-                                    WpType::F64 => Size::S64,
-                                    _ => unreachable!(),
-                                },
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        } else {
-                            self.emit_relaxed_binop(
-                                Assembler::emit_mov,
-                                Size::S64,
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        }
-                    } else {
-                        self.emit_relaxed_binop(
-                            Assembler::emit_mov,
-                            Size::S64,
-                            loc,
-                            Location::GPR(GPR::RAX),
-                        );
-                    }
-                }
-                let frame = &self.control_stack[0];
-                let released = &self.value_stack[frame.value_stack_depth..];
-                self.machine
-                    .release_locations_keep_state(&mut self.assembler, released);
-                self.assembler.emit_jmp(Condition::None, frame.label);
+                self.emit_branch(0);
                 self.unreachable_depth = 1;
             }
             Operator::Br { relative_depth } => {
-                let frame =
-                    &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                if !frame.loop_like && !frame.returns.is_empty() {
-                    if frame.returns.len() != 1 {
-                        return Err(CodegenError {
-                            message: "Br: incorrect frame.returns".to_string(),
-                        });
-                    }
-                    let first_return = frame.returns[0];
-                    let loc = *self.value_stack.last().unwrap();
-
-                    if first_return.is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        if self.assembler.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                        {
-                            self.canonicalize_nan(
-                                match first_return {
-                                    WpType::F32 => Size::S32,
-                                    WpType::F64 => Size::S64,
-                                    _ => unreachable!(),
-                                },
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        } else {
-                            self.emit_relaxed_binop(
-                                Assembler::emit_mov,
-                                Size::S64,
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        }
-                    } else {
-                        self.assembler
-                            .emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                    }
-                }
-                let frame =
-                    &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-
-                let released = &self.value_stack[frame.value_stack_depth..];
-                self.machine
-                    .release_locations_keep_state(&mut self.assembler, released);
-                self.assembler.emit_jmp(Condition::None, frame.label);
+                self.emit_branch(self.control_stack.len() - 1 - (relative_depth as usize));
                 self.unreachable_depth = 1;
             }
             Operator::BrIf { relative_depth } => {
@@ -6285,51 +7645,7 @@ impl<'a> FuncGen<'a> {
                 self.emit_relaxed_binop(Assembler::emit_cmp, Size::S32, Location::Imm32(0), cond);
                 self.assembler.emit_jmp(Condition::Equal, after);
 
-                let frame =
-                    &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                if !frame.loop_like && !frame.returns.is_empty() {
-                    if frame.returns.len() != 1 {
-                        return Err(CodegenError {
-                            message: "BrIf: incorrect frame.returns".to_string(),
-                        });
-                    }
-
-                    let first_return = frame.returns[0];
-                    let loc = *self.value_stack.last().unwrap();
-                    if first_return.is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        if self.assembler.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                        {
-                            self.canonicalize_nan(
-                                match first_return {
-                                    WpType::F32 => Size::S32,
-                                    WpType::F64 => Size::S64,
-                                    _ => unreachable!(),
-                                },
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        } else {
-                            self.emit_relaxed_binop(
-                                Assembler::emit_mov,
-                                Size::S64,
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        }
-                    } else {
-                        self.assembler
-                            .emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                    }
-                }
-                let frame =
-                    &self.control_stack[self.control_stack.len() - 1 - (relative_depth as usize)];
-                let released = &self.value_stack[frame.value_stack_depth..];
-                self.machine
-                    .release_locations_keep_state(&mut self.assembler, released);
-                self.assembler.emit_jmp(Condition::None, frame.label);
+                self.emit_branch(self.control_stack.len() - 1 - (relative_depth as usize));
 
                 self.assembler.emit_label(after);
             }
@@ -6372,104 +7688,11 @@ impl<'a> FuncGen<'a> {
                     let label = self.assembler.get_label();
                     self.assembler.emit_label(label);
                     table.push(label);
-                    let frame =
-                        &self.control_stack[self.control_stack.len() - 1 - (*target as usize)];
-                    if !frame.loop_like && !frame.returns.is_empty() {
-                        if frame.returns.len() != 1 {
-                            return Err(CodegenError {
-                                message: format!(
-                                    "BrTable: incorrect frame.returns for {:?}",
-                                    target
-                                ),
-                            });
-                        }
-
-                        let first_return = frame.returns[0];
-                        let loc = *self.value_stack.last().unwrap();
-                        if first_return.is_float() {
-                            let fp = self.fp_stack.peek1()?;
-                            if self.assembler.arch_supports_canonicalize_nan()
-                                && self.config.enable_nan_canonicalization
-                                && fp.canonicalization.is_some()
-                            {
-                                self.canonicalize_nan(
-                                    match first_return {
-                                        WpType::F32 => Size::S32,
-                                        WpType::F64 => Size::S64,
-                                        _ => unreachable!(),
-                                    },
-                                    loc,
-                                    Location::GPR(GPR::RAX),
-                                );
-                            } else {
-                                self.emit_relaxed_binop(
-                                    Assembler::emit_mov,
-                                    Size::S64,
-                                    loc,
-                                    Location::GPR(GPR::RAX),
-                                );
-                            }
-                        } else {
-                            self.assembler
-                                .emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                        }
-                    }
-                    let frame =
-                        &self.control_stack[self.control_stack.len() - 1 - (*target as usize)];
-                    let released = &self.value_stack[frame.value_stack_depth..];
-                    self.machine
-                        .release_locations_keep_state(&mut self.assembler, released);
-                    self.assembler.emit_jmp(Condition::None, frame.label);
+                    self.emit_branch(self.control_stack.len() - 1 - (*target as usize));
                 }
                 self.assembler.emit_label(default_br);
 
-                {
-                    let frame = &self.control_stack
-                        [self.control_stack.len() - 1 - (default_target as usize)];
-                    if !frame.loop_like && !frame.returns.is_empty() {
-                        if frame.returns.len() != 1 {
-                            return Err(CodegenError {
-                                message: "BrTable: incorrect frame.returns".to_string(),
-                            });
-                        }
-
-                        let first_return = frame.returns[0];
-                        let loc = *self.value_stack.last().unwrap();
-                        if first_return.is_float() {
-                            let fp = self.fp_stack.peek1()?;
-                            if self.assembler.arch_supports_canonicalize_nan()
-                                && self.config.enable_nan_canonicalization
-                                && fp.canonicalization.is_some()
-                            {
-                                self.canonicalize_nan(
-                                    match first_return {
-                                        WpType::F32 => Size::S32,
-                                        WpType::F64 => Size::S64,
-                                        _ => unreachable!(),
-                                    },
-                                    loc,
-                                    Location::GPR(GPR::RAX),
-                                );
-                            } else {
-                                self.emit_relaxed_binop(
-                                    Assembler::emit_mov,
-                                    Size::S64,
-                                    loc,
-                                    Location::GPR(GPR::RAX),
-                                );
-                            }
-                        } else {
-                            self.assembler
-                                .emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                        }
-                    }
-                    let frame = &self.control_stack
-                        [self.control_stack.len() - 1 - (default_target as usize)];
-                    let released = &self.value_stack[frame.value_stack_depth..];
-                    self.machine
-                        .release_locations_keep_state(&mut self.assembler, released);
-                    self.assembler.emit_jmp(Condition::None, frame.label);
-                }
+                self.emit_branch(self.control_stack.len() - 1 - (default_target as usize));
 
                 self.assembler.emit_label(table_label);
                 for x in table {
@@ -6486,72 +7709,42 @@ impl<'a> FuncGen<'a> {
                 }
             }
             Operator::End => {
-                let frame = self.control_stack.pop().unwrap();
-
-                if !was_unreachable && !frame.returns.is_empty() {
-                    let loc = *self.value_stack.last().unwrap();
-                    if frame.returns[0].is_float() {
-                        let fp = self.fp_stack.peek1()?;
-                        if self.assembler.arch_supports_canonicalize_nan()
-                            && self.config.enable_nan_canonicalization
-                            && fp.canonicalization.is_some()
-                        {
-                            self.canonicalize_nan(
-                                match frame.returns[0] {
-                                    WpType::F32 => Size::S32,
-                                    WpType::F64 => Size::S64,
-                                    _ => unreachable!(),
-                                },
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
-                        } else {
-                            self.emit_relaxed_binop(
-                                Assembler::emit_mov,
-                                Size::S64,
-                                loc,
-                                Location::GPR(GPR::RAX),
-                            );
+                let mut was_unreachable = was_unreachable;
+                // An `if` without `else` passes its parameters as results.
+                if let Some(frame) = self.control_stack.last() {
+                    if let IfElseState::If(_) = frame.if_else {
+                        if !frame.params.is_empty() {
+                            self.emit_else(was_unreachable)?;
+                            was_unreachable = false;
                         }
-                    } else {
-                        self.emit_relaxed_binop(
-                            Assembler::emit_mov,
-                            Size::S64,
-                            loc,
-                            Location::GPR(GPR::RAX),
-                        );
                     }
                 }
 
+                if !was_unreachable {
+                    self.emit_frame_results(self.control_stack.len() - 1);
+                }
+
+                let frame = self.control_stack.pop().unwrap();
+
                 if self.control_stack.is_empty() {
                     self.assembler.emit_label(frame.label);
-                    self.machine
-                        .finalize_locals(&mut self.assembler, &self.locals);
+                    self.locals_code_range.1 = self.assembler.get_offset().0;
+                    self.machine.finalize_locals(&mut self.assembler);
                     self.assembler.emit_mov(
                         Size::S64,
                         Location::GPR(GPR::RBP),
                         Location::GPR(GPR::RSP),
                     );
                     self.assembler.emit_pop(Size::S64, Location::GPR(GPR::RBP));
-
-                    // Make a copy of the return value in XMM0, as required by the SysV CC.
-                    match self.signature.results() {
-                        [x] if *x == Type::F32 || *x == Type::F64 => {
-                            self.assembler.emit_mov(
-                                Size::S64,
-                                Location::GPR(GPR::RAX),
-                                Location::XMM(XMM::XMM0),
-                            );
-                        }
-                        _ => {}
-                    }
                     self.assembler.emit_ret();
                 } else {
-                    let released = &self.value_stack[frame.value_stack_depth..];
+                    let depth = frame.slots_depth + frame.return_slots;
+                    let released = &self.value_stack[depth..];
                     self.machine
                         .release_locations(&mut self.assembler, released);
-                    self.value_stack.truncate(frame.value_stack_depth);
-                    self.fp_stack.truncate(frame.fp_stack_depth);
+                    self.value_stack.truncate(depth);
+                    let fp_stack_depth = self.fp_stack_depth_at(depth);
+                    self.fp_stack.truncate(fp_stack_depth);
 
                     if !frame.loop_like {
                         self.assembler.emit_label(frame.label);
@@ -6561,12 +7754,14 @@ impl<'a> FuncGen<'a> {
                         self.assembler.emit_label(label);
                     }
 
-                    if !frame.returns.is_empty() {
-                        if frame.returns.len() != 1 {
-                            return Err(CodegenError {
-                                message: "End: incorrect frame.returns".to_string(),
-                            });
+                    if frame.return_slots > 0 {
+                        // The results are already in the return slots.
+                        for (i, ty) in frame.returns.iter().enumerate() {
+                            if ty.is_float() {
+                                self.fp_stack.push(FloatValue::new(frame.slots_depth + i));
+                            }
                         }
+                    } else if !frame.returns.is_empty() {
                         let loc = self.machine.acquire_locations(
                             &mut self.assembler,
                             &[(
@@ -6586,6 +7781,48 @@ impl<'a> FuncGen<'a> {
                     }
                 }
             }
+            Operator::MemoryAtomicWait32 { ref memarg }
+            | Operator::MemoryAtomicWait64 { ref memarg } => {
+                let memory_index = MemoryIndex::new(memarg.memory as usize);
+                let wait64 = matches!(op, Operator::MemoryAtomicWait64 { .. });
+                let (builtin, index) = match self.module.local_memory_index(memory_index) {
+                    Some(local_index) if wait64 => (
+                        VMBuiltinFunctionIndex::get_memory_atomic_wait64_index(),
+                        local_index.index(),
+                    ),
+                    Some(local_index) => (
+                        VMBuiltinFunctionIndex::get_memory_atomic_wait32_index(),
+                        local_index.index(),
+                    ),
+                    None if wait64 => (
+                        VMBuiltinFunctionIndex::get_imported_memory_atomic_wait64_index(),
+                        memory_index.index(),
+                    ),
+                    None => (
+                        VMBuiltinFunctionIndex::get_imported_memory_atomic_wait32_index(),
+                        memory_index.index(),
+                    ),
+                };
+                self.emit_effective_address(self.value_stack.len() - 3, memarg);
+                // [vmctx, memory_index, dst, expected, timeout]
+                self.emit_call_builtin_with_result(builtin, &[index as u32], 3, WpType::I32)?;
+            }
+            Operator::MemoryAtomicNotify { ref memarg } => {
+                let memory_index = MemoryIndex::new(memarg.memory as usize);
+                let (builtin, index) = match self.module.local_memory_index(memory_index) {
+                    Some(local_index) => (
+                        VMBuiltinFunctionIndex::get_memory_atomic_notify_index(),
+                        local_index.index(),
+                    ),
+                    None => (
+                        VMBuiltinFunctionIndex::get_imported_memory_atomic_notify_index(),
+                        memory_index.index(),
+                    ),
+                };
+                self.emit_effective_address(self.value_stack.len() - 2, memarg);
+                // [vmctx, memory_index, dst, count]
+                self.emit_call_builtin_with_result(builtin, &[index as u32], 2, WpType::I32)?;
+            }
             Operator::AtomicFence { flags: _ } => {
                 // Fence is a nop.
                 //
@@ -6906,8 +8143,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S8, loc, Size::S32, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S8,
+                    loc,
+                    Size::S32,
+                    Location::GPR(value),
+                )?;
                 self.emit_memory_op(target, memarg, true, 1, |this, addr| {
                     this.assembler.emit_lock_xadd(
                         Size::S8,
@@ -6931,8 +8173,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S16, loc, Size::S32, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S16,
+                    loc,
+                    Size::S32,
+                    Location::GPR(value),
+                )?;
                 self.emit_memory_op(target, memarg, true, 2, |this, addr| {
                     this.assembler.emit_lock_xadd(
                         Size::S16,
@@ -6956,8 +8203,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S8, loc, Size::S64, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S8,
+                    loc,
+                    Size::S64,
+                    Location::GPR(value),
+                )?;
                 self.emit_memory_op(target, memarg, true, 1, |this, addr| {
                     this.assembler.emit_lock_xadd(
                         Size::S8,
@@ -6981,8 +8233,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S16, loc, Size::S64, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S16,
+                    loc,
+                    Size::S64,
+                    Location::GPR(value),
+                )?;
                 self.emit_memory_op(target, memarg, true, 2, |this, addr| {
                     this.assembler.emit_lock_xadd(
                         Size::S16,
@@ -7083,8 +8340,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S8, loc, Size::S32, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S8,
+                    loc,
+                    Size::S32,
+                    Location::GPR(value),
+                )?;
                 self.assembler.emit_neg(Size::S8, Location::GPR(value));
                 self.emit_memory_op(target, memarg, true, 1, |this, addr| {
                     this.assembler.emit_lock_xadd(
@@ -7109,8 +8371,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S16, loc, Size::S32, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S16,
+                    loc,
+                    Size::S32,
+                    Location::GPR(value),
+                )?;
                 self.assembler.emit_neg(Size::S16, Location::GPR(value));
                 self.emit_memory_op(target, memarg, true, 2, |this, addr| {
                     this.assembler.emit_lock_xadd(
@@ -7135,8 +8402,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S8, loc, Size::S64, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S8,
+                    loc,
+                    Size::S64,
+                    Location::GPR(value),
+                )?;
                 self.assembler.emit_neg(Size::S8, Location::GPR(value));
                 self.emit_memory_op(target, memarg, true, 1, |this, addr| {
                     this.assembler.emit_lock_xadd(
@@ -7161,8 +8433,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S16, loc, Size::S64, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S16,
+                    loc,
+                    Size::S64,
+                    Location::GPR(value),
+                )?;
                 self.assembler.emit_neg(Size::S16, Location::GPR(value));
                 self.emit_memory_op(target, memarg, true, 2, |this, addr| {
                     this.assembler.emit_lock_xadd(
@@ -7767,8 +9044,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S8, loc, Size::S32, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S8,
+                    loc,
+                    Size::S32,
+                    Location::GPR(value),
+                )?;
                 self.emit_memory_op(target, memarg, true, 1, |this, addr| {
                     this.assembler.emit_xchg(
                         Size::S8,
@@ -7792,8 +9074,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S16, loc, Size::S32, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S16,
+                    loc,
+                    Size::S32,
+                    Location::GPR(value),
+                )?;
                 self.emit_memory_op(target, memarg, true, 2, |this, addr| {
                     this.assembler.emit_xchg(
                         Size::S16,
@@ -7817,8 +9104,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S8, loc, Size::S64, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S8,
+                    loc,
+                    Size::S64,
+                    Location::GPR(value),
+                )?;
                 self.emit_memory_op(target, memarg, true, 1, |this, addr| {
                     this.assembler.emit_xchg(
                         Size::S8,
@@ -7842,8 +9134,13 @@ impl<'a> FuncGen<'a> {
                 self.value_stack.push(ret);
 
                 let value = self.machine.acquire_temp_gpr().unwrap();
-                self.assembler
-                    .emit_movzx(Size::S16, loc, Size::S64, Location::GPR(value));
+                self.emit_relaxed_zx_sx(
+                    Assembler::emit_movzx,
+                    Size::S16,
+                    loc,
+                    Size::S64,
+                    Location::GPR(value),
+                )?;
                 self.emit_memory_op(target, memarg, true, 2, |this, addr| {
                     this.assembler.emit_xchg(
                         Size::S16,
@@ -8115,51 +9412,643 @@ impl<'a> FuncGen<'a> {
                         Location::Memory(addr, 0),
                     );
                     this.assembler
-                        .emit_movzx(Size::S16, Location::GPR(compare), Size::S64, ret);
-                    Ok(())
-                })?;
-                self.assembler.emit_pop(Size::S64, Location::GPR(value));
-                self.machine.release_temp_gpr(compare);
+                        .emit_movzx(Size::S16, Location::GPR(compare), Size::S64, ret);
+                    Ok(())
+                })?;
+                self.assembler.emit_pop(Size::S64, Location::GPR(value));
+                self.machine.release_temp_gpr(compare);
+            }
+            Operator::I64AtomicRmw32CmpxchgU { ref memarg } => {
+                let new = self.pop_value_released();
+                let cmp = self.pop_value_released();
+                let target = self.pop_value_released();
+                let ret = self.machine.acquire_locations(
+                    &mut self.assembler,
+                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                let compare = self.machine.reserve_unused_temp_gpr(GPR::RAX);
+                let value = if cmp == Location::GPR(GPR::R14) {
+                    if new == Location::GPR(GPR::R13) {
+                        GPR::R12
+                    } else {
+                        GPR::R13
+                    }
+                } else {
+                    GPR::R14
+                };
+                self.assembler.emit_push(Size::S64, Location::GPR(value));
+                self.assembler
+                    .emit_mov(Size::S64, cmp, Location::GPR(compare));
+                self.assembler
+                    .emit_mov(Size::S64, new, Location::GPR(value));
+
+                self.emit_memory_op(target, memarg, true, 1, |this, addr| {
+                    this.assembler.emit_lock_cmpxchg(
+                        Size::S32,
+                        Location::GPR(value),
+                        Location::Memory(addr, 0),
+                    );
+                    this.assembler
+                        .emit_mov(Size::S32, Location::GPR(compare), ret);
+                    Ok(())
+                })?;
+                self.assembler.emit_pop(Size::S64, Location::GPR(value));
+                self.machine.release_temp_gpr(compare);
+            }
+            Operator::V128Load { ref memarg } => {
+                let target = self.pop_value_released();
+                let ret = self.machine.acquire_locations(
+                    &mut self.assembler,
+                    &[(
+                        WpType::V128,
+                        MachineValue::WasmStack(self.value_stack.len()),
+                    )],
+                    false,
+                )[0];
+                self.value_stack.push(ret);
+
+                self.emit_memory_op(target, memarg, false, 16, |this, addr| {
+                    this.emit_v128_mov(Location::Memory(addr, 0), ret);
+                    Ok(())
+                })?;
+            }
+            Operator::V128Store { ref memarg } => {
+                let target_value = self.pop_value_released();
+                let target_addr = self.pop_value_released();
+
+                self.emit_memory_op(target_addr, memarg, false, 16, |this, addr| {
+                    this.emit_v128_mov(target_value, Location::Memory(addr, 0));
+                    Ok(())
+                })?;
+            }
+            Operator::V128Const { value } => {
+                let mut bytes = [0u8; 16];
+                bytes.copy_from_slice(value.bytes());
+                let tmp = self.machine.acquire_temp_xmm().unwrap();
+                self.emit_v128_const(bytes, tmp);
+                self.push_v128(tmp);
+                self.machine.release_temp_xmm(tmp);
+            }
+
+            Operator::I8x16Splat => {
+                self.emit_v128_from_scalar(WpType::I32, 0, |this, t, src| {
+                    this.emit_v128_splat(Size::S8, src, t[0])
+                })?
+            }
+            Operator::I16x8Splat => {
+                self.emit_v128_from_scalar(WpType::I32, 0, |this, t, src| {
+                    this.emit_v128_splat(Size::S16, src, t[0])
+                })?
+            }
+            Operator::I32x4Splat => {
+                self.emit_v128_from_scalar(WpType::I32, 0, |this, t, src| {
+                    this.emit_v128_splat(Size::S32, src, t[0])
+                })?
+            }
+            Operator::I64x2Splat => {
+                self.emit_v128_from_scalar(WpType::I64, 0, |this, t, src| {
+                    this.emit_v128_splat(Size::S64, src, t[0])
+                })?
+            }
+            Operator::F32x4Splat => {
+                self.emit_v128_from_scalar(WpType::F32, 0, |this, t, src| {
+                    this.emit_v128_splat(Size::S32, src, t[0])
+                })?
+            }
+            Operator::F64x2Splat => {
+                self.emit_v128_from_scalar(WpType::F64, 0, |this, t, src| {
+                    this.emit_v128_splat(Size::S64, src, t[0])
+                })?
+            }
+
+            Operator::I8x16ExtractLaneS { lane } => {
+                self.emit_v128_extract(WpType::I32, |this, v, dst| {
+                    this.assembler.emit_pextrb(v, lane, dst);
+                    this.assembler.emit_movsx(
+                        Size::S8,
+                        Location::GPR(dst),
+                        Size::S32,
+                        Location::GPR(dst),
+                    );
+                })
+            }
+            Operator::I8x16ExtractLaneU { lane } => self
+                .emit_v128_extract(WpType::I32, |this, v, dst| {
+                    this.assembler.emit_pextrb(v, lane, dst)
+                }),
+            Operator::I16x8ExtractLaneS { lane } => {
+                self.emit_v128_extract(WpType::I32, |this, v, dst| {
+                    this.assembler.emit_pextrw(v, lane, dst);
+                    this.assembler.emit_movsx(
+                        Size::S16,
+                        Location::GPR(dst),
+                        Size::S32,
+                        Location::GPR(dst),
+                    );
+                })
+            }
+            Operator::I16x8ExtractLaneU { lane } => self
+                .emit_v128_extract(WpType::I32, |this, v, dst| {
+                    this.assembler.emit_pextrw(v, lane, dst)
+                }),
+            Operator::I32x4ExtractLane { lane } => self
+                .emit_v128_extract(WpType::I32, |this, v, dst| {
+                    this.assembler.emit_pextrd(v, lane, dst)
+                }),
+            Operator::I64x2ExtractLane { lane } => self
+                .emit_v128_extract(WpType::I64, |this, v, dst| {
+                    this.assembler.emit_pextrq(v, lane, dst)
+                }),
+            Operator::F32x4ExtractLane { lane } => self
+                .emit_v128_extract(WpType::F32, |this, v, dst| {
+                    this.assembler.emit_pextrd(v, lane, dst)
+                }),
+            Operator::F64x2ExtractLane { lane } => self
+                .emit_v128_extract(WpType::F64, |this, v, dst| {
+                    this.assembler.emit_pextrq(v, lane, dst)
+                }),
+
+            Operator::I8x16ReplaceLane { lane } => {
+                self.emit_v128_from_scalar(WpType::I32, 1, |this, t, src| {
+                    this.assembler.emit_pinsrb(src, lane, t[0])
+                })?
+            }
+            Operator::I16x8ReplaceLane { lane } => {
+                self.emit_v128_from_scalar(WpType::I32, 1, |this, t, src| {
+                    this.assembler.emit_pinsrw(src, lane, t[0])
+                })?
+            }
+            Operator::I32x4ReplaceLane { lane } => {
+                self.emit_v128_from_scalar(WpType::I32, 1, |this, t, src| {
+                    this.assembler.emit_pinsrd(src, lane, t[0])
+                })?
+            }
+            Operator::I64x2ReplaceLane { lane } => {
+                self.emit_v128_from_scalar(WpType::I64, 1, |this, t, src| {
+                    this.assembler.emit_pinsrq(src, lane, t[0])
+                })?
+            }
+            Operator::F32x4ReplaceLane { lane } => {
+                self.emit_v128_from_scalar(WpType::F32, 1, |this, t, src| {
+                    this.assembler.emit_pinsrd(src, lane, t[0])
+                })?
+            }
+            Operator::F64x2ReplaceLane { lane } => {
+                self.emit_v128_from_scalar(WpType::F64, 1, |this, t, src| {
+                    this.assembler.emit_pinsrq(src, lane, t[0])
+                })?
+            }
+
+            Operator::I8x16Shuffle { lanes } => {
+                // Select the lanes of each operand separately; out of range
+                // indices (with the high bit set) make `pshufb` write zeros.
+                let mut from_a = [0u8; 16];
+                let mut from_b = [0u8; 16];
+                for (i, &lane) in lanes.iter().enumerate() {
+                    from_a[i] = if lane < 16 { lane } else { 0x80 };
+                    from_b[i] = if lane >= 16 { lane - 16 } else { 0x80 };
+                }
+                self.emit_v128_op(2, |this, t| {
+                    this.emit_v128_const(from_a, t[2]);
+                    this.assembler
+                        .emit_vpshufb(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                    this.emit_v128_const(from_b, t[2]);
+                    this.assembler
+                        .emit_vpshufb(t[1], XMMOrMemory::XMM(t[2]), t[1]);
+                    this.assembler.emit_vpor(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                });
+            }
+            Operator::I8x16Swizzle => {
+                self.emit_v128_op(2, |this, t| {
+                    // Push the indices above 15 to 0x80 and up, which `pshufb` turns into zeros.
+                    this.emit_v128_const([0x70; 16], t[2]);
+                    this.assembler
+                        .emit_vpaddusb(t[1], XMMOrMemory::XMM(t[2]), t[1]);
+                    this.assembler
+                        .emit_vpshufb(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                });
+            }
+
+            Operator::V128Not => {
+                self.emit_v128_op(1, |this, t| {
+                    this.assembler
+                        .emit_vpcmpeqd(t[1], XMMOrMemory::XMM(t[1]), t[1]);
+                    this.assembler
+                        .emit_vpxor(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                });
+            }
+            Operator::V128And => self.emit_v128_binop(Assembler::emit_vpand),
+            Operator::V128AndNot => self.emit_v128_binop_swapped(Assembler::emit_vpandn),
+            Operator::V128Or => self.emit_v128_binop(Assembler::emit_vpor),
+            Operator::V128Xor => self.emit_v128_binop(Assembler::emit_vpxor),
+            Operator::V128Bitselect => {
+                self.emit_v128_op(3, |this, t| {
+                    this.assembler
+                        .emit_vpand(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                    this.assembler
+                        .emit_vpandn(t[2], XMMOrMemory::XMM(t[1]), t[2]);
+                    this.assembler.emit_vpor(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                });
+            }
+            Operator::V128AnyTrue => self.emit_v128_extract(WpType::I32, |this, v, dst| {
+                this.assembler.emit_ptest(XMMOrMemory::XMM(v), v);
+                this.assembler.emit_set(Condition::NotEqual, dst);
+                this.assembler
+                    .emit_and(Size::S32, Location::Imm32(0xff), Location::GPR(dst));
+            }),
+            Operator::I8x16AllTrue => self.emit_v128_all_true(Assembler::emit_vpcmpeqb),
+            Operator::I16x8AllTrue => self.emit_v128_all_true(Assembler::emit_vpcmpeqw),
+            Operator::I32x4AllTrue => self.emit_v128_all_true(Assembler::emit_vpcmpeqd),
+            Operator::I8x16Bitmask => self.emit_v128_extract(WpType::I32, |this, v, dst| {
+                this.assembler.emit_pmovmskb(v, dst)
+            }),
+
+            Operator::I8x16Eq => self.emit_v128_binop(Assembler::emit_vpcmpeqb),
+            Operator::I8x16Ne => self.emit_v128_binop_not(Assembler::emit_vpcmpeqb, false),
+            Operator::I8x16GtS => self.emit_v128_binop(Assembler::emit_vpcmpgtb),
+            Operator::I8x16LtS => self.emit_v128_binop_swapped(Assembler::emit_vpcmpgtb),
+            Operator::I8x16LeS => self.emit_v128_binop_not(Assembler::emit_vpcmpgtb, false),
+            Operator::I8x16GeS => self.emit_v128_binop_not(Assembler::emit_vpcmpgtb, true),
+            Operator::I8x16GeU => self.emit_v128_cmp_unsigned(
+                Assembler::emit_vpmaxub,
+                Assembler::emit_vpcmpeqb,
+                false,
+            ),
+            Operator::I8x16LeU => self.emit_v128_cmp_unsigned(
+                Assembler::emit_vpminub,
+                Assembler::emit_vpcmpeqb,
+                false,
+            ),
+            Operator::I8x16LtU => {
+                self.emit_v128_cmp_unsigned(Assembler::emit_vpmaxub, Assembler::emit_vpcmpeqb, true)
+            }
+            Operator::I8x16GtU => {
+                self.emit_v128_cmp_unsigned(Assembler::emit_vpminub, Assembler::emit_vpcmpeqb, true)
+            }
+            Operator::I16x8Eq => self.emit_v128_binop(Assembler::emit_vpcmpeqw),
+            Operator::I16x8Ne => self.emit_v128_binop_not(Assembler::emit_vpcmpeqw, false),
+            Operator::I16x8GtS => self.emit_v128_binop(Assembler::emit_vpcmpgtw),
+            Operator::I16x8LtS => self.emit_v128_binop_swapped(Assembler::emit_vpcmpgtw),
+            Operator::I16x8LeS => self.emit_v128_binop_not(Assembler::emit_vpcmpgtw, false),
+            Operator::I16x8GeS => self.emit_v128_binop_not(Assembler::emit_vpcmpgtw, true),
+            Operator::I16x8GeU => self.emit_v128_cmp_unsigned(
+                Assembler::emit_vpmaxuw,
+                Assembler::emit_vpcmpeqw,
+                false,
+            ),
+            Operator::I16x8LeU => self.emit_v128_cmp_unsigned(
+                Assembler::emit_vpminuw,
+                Assembler::emit_vpcmpeqw,
+                false,
+            ),
+            Operator::I16x8LtU => {
+                self.emit_v128_cmp_unsigned(Assembler::emit_vpmaxuw, Assembler::emit_vpcmpeqw, true)
+            }
+            Operator::I16x8GtU => {
+                self.emit_v128_cmp_unsigned(Assembler::emit_vpminuw, Assembler::emit_vpcmpeqw, true)
+            }
+            Operator::I32x4Eq => self.emit_v128_binop(Assembler::emit_vpcmpeqd),
+            Operator::I32x4Ne => self.emit_v128_binop_not(Assembler::emit_vpcmpeqd, false),
+            Operator::I32x4GtS => self.emit_v128_binop(Assembler::emit_vpcmpgtd),
+            Operator::I32x4LtS => self.emit_v128_binop_swapped(Assembler::emit_vpcmpgtd),
+            Operator::I32x4LeS => self.emit_v128_binop_not(Assembler::emit_vpcmpgtd, false),
+            Operator::I32x4GeS => self.emit_v128_binop_not(Assembler::emit_vpcmpgtd, true),
+            Operator::I32x4GeU => self.emit_v128_cmp_unsigned(
+                Assembler::emit_vpmaxud,
+                Assembler::emit_vpcmpeqd,
+                false,
+            ),
+            Operator::I32x4LeU => self.emit_v128_cmp_unsigned(
+                Assembler::emit_vpminud,
+                Assembler::emit_vpcmpeqd,
+                false,
+            ),
+            Operator::I32x4LtU => {
+                self.emit_v128_cmp_unsigned(Assembler::emit_vpmaxud, Assembler::emit_vpcmpeqd, true)
+            }
+            Operator::I32x4GtU => {
+                self.emit_v128_cmp_unsigned(Assembler::emit_vpminud, Assembler::emit_vpcmpeqd, true)
+            }
+            Operator::F32x4Eq => self.emit_v128_binop(Assembler::emit_vcmpeqps),
+            Operator::F32x4Ne => self.emit_v128_binop(Assembler::emit_vcmpneqps),
+            Operator::F32x4Lt => self.emit_v128_binop(Assembler::emit_vcmpltps),
+            Operator::F32x4Le => self.emit_v128_binop(Assembler::emit_vcmpleps),
+            Operator::F32x4Gt => self.emit_v128_binop_swapped(Assembler::emit_vcmpltps),
+            Operator::F32x4Ge => self.emit_v128_binop_swapped(Assembler::emit_vcmpleps),
+            Operator::F64x2Eq => self.emit_v128_binop(Assembler::emit_vcmpeqpd),
+            Operator::F64x2Ne => self.emit_v128_binop(Assembler::emit_vcmpneqpd),
+            Operator::F64x2Lt => self.emit_v128_binop(Assembler::emit_vcmpltpd),
+            Operator::F64x2Le => self.emit_v128_binop(Assembler::emit_vcmplepd),
+            Operator::F64x2Gt => self.emit_v128_binop_swapped(Assembler::emit_vcmpltpd),
+            Operator::F64x2Ge => self.emit_v128_binop_swapped(Assembler::emit_vcmplepd),
+
+            Operator::I8x16Abs => self.emit_v128_unop(Assembler::emit_pabsb),
+            Operator::I16x8Abs => self.emit_v128_unop(Assembler::emit_pabsw),
+            Operator::I32x4Abs => self.emit_v128_unop(Assembler::emit_pabsd),
+            Operator::I8x16Neg => self.emit_v128_neg(Assembler::emit_vpsubb),
+            Operator::I16x8Neg => self.emit_v128_neg(Assembler::emit_vpsubw),
+            Operator::I32x4Neg => self.emit_v128_neg(Assembler::emit_vpsubd),
+            Operator::I64x2Neg => self.emit_v128_neg(Assembler::emit_vpsubq),
+            Operator::I8x16Add => self.emit_v128_binop(Assembler::emit_vpaddb),
+            Operator::I16x8Add => self.emit_v128_binop(Assembler::emit_vpaddw),
+            Operator::I32x4Add => self.emit_v128_binop(Assembler::emit_vpaddd),
+            Operator::I64x2Add => self.emit_v128_binop(Assembler::emit_vpaddq),
+            Operator::I8x16Sub => self.emit_v128_binop(Assembler::emit_vpsubb),
+            Operator::I16x8Sub => self.emit_v128_binop(Assembler::emit_vpsubw),
+            Operator::I32x4Sub => self.emit_v128_binop(Assembler::emit_vpsubd),
+            Operator::I64x2Sub => self.emit_v128_binop(Assembler::emit_vpsubq),
+            Operator::I16x8Mul => self.emit_v128_binop(Assembler::emit_vpmullw),
+            Operator::I32x4Mul => self.emit_v128_binop(Assembler::emit_vpmulld),
+            Operator::I8x16AddSatS => self.emit_v128_binop(Assembler::emit_vpaddsb),
+            Operator::I8x16AddSatU => self.emit_v128_binop(Assembler::emit_vpaddusb),
+            Operator::I16x8AddSatS => self.emit_v128_binop(Assembler::emit_vpaddsw),
+            Operator::I16x8AddSatU => self.emit_v128_binop(Assembler::emit_vpaddusw),
+            Operator::I8x16SubSatS => self.emit_v128_binop(Assembler::emit_vpsubsb),
+            Operator::I8x16SubSatU => self.emit_v128_binop(Assembler::emit_vpsubusb),
+            Operator::I16x8SubSatS => self.emit_v128_binop(Assembler::emit_vpsubsw),
+            Operator::I16x8SubSatU => self.emit_v128_binop(Assembler::emit_vpsubusw),
+            Operator::I8x16MinS => self.emit_v128_binop(Assembler::emit_vpminsb),
+            Operator::I8x16MinU => self.emit_v128_binop(Assembler::emit_vpminub),
+            Operator::I8x16MaxS => self.emit_v128_binop(Assembler::emit_vpmaxsb),
+            Operator::I8x16MaxU => self.emit_v128_binop(Assembler::emit_vpmaxub),
+            Operator::I16x8MinS => self.emit_v128_binop(Assembler::emit_vpminsw),
+            Operator::I16x8MinU => self.emit_v128_binop(Assembler::emit_vpminuw),
+            Operator::I16x8MaxS => self.emit_v128_binop(Assembler::emit_vpmaxsw),
+            Operator::I16x8MaxU => self.emit_v128_binop(Assembler::emit_vpmaxuw),
+            Operator::I32x4MinS => self.emit_v128_binop(Assembler::emit_vpminsd),
+            Operator::I32x4MinU => self.emit_v128_binop(Assembler::emit_vpminud),
+            Operator::I32x4MaxS => self.emit_v128_binop(Assembler::emit_vpmaxsd),
+            Operator::I32x4MaxU => self.emit_v128_binop(Assembler::emit_vpmaxud),
+            Operator::I8x16RoundingAverageU => self.emit_v128_binop(Assembler::emit_vpavgb),
+            Operator::I16x8RoundingAverageU => self.emit_v128_binop(Assembler::emit_vpavgw),
+            Operator::I16x8Shl => self.emit_v128_shift(16, Assembler::emit_vpsllw),
+            Operator::I16x8ShrS => self.emit_v128_shift(16, Assembler::emit_vpsraw),
+            Operator::I16x8ShrU => self.emit_v128_shift(16, Assembler::emit_vpsrlw),
+            Operator::I32x4Shl => self.emit_v128_shift(32, Assembler::emit_vpslld),
+            Operator::I32x4ShrS => self.emit_v128_shift(32, Assembler::emit_vpsrad),
+            Operator::I32x4ShrU => self.emit_v128_shift(32, Assembler::emit_vpsrld),
+            Operator::I64x2Shl => self.emit_v128_shift(64, Assembler::emit_vpsllq),
+            Operator::I64x2ShrU => self.emit_v128_shift(64, Assembler::emit_vpsrlq),
+
+            Operator::F32x4Abs => {
+                self.emit_v128_sign_op(Size::S32, 0x7fff_ffff, Assembler::emit_vpand)
+            }
+            Operator::F64x2Abs => {
+                self.emit_v128_sign_op(Size::S64, 0x7fff_ffff_ffff_ffff, Assembler::emit_vpand)
+            }
+            Operator::F32x4Neg => {
+                self.emit_v128_sign_op(Size::S32, 0x8000_0000, Assembler::emit_vpxor)
+            }
+            Operator::F64x2Neg => {
+                self.emit_v128_sign_op(Size::S64, 0x8000_0000_0000_0000, Assembler::emit_vpxor)
+            }
+            Operator::F32x4Sqrt => self.emit_v128_float_op(1, Size::S32, |a, t| {
+                a.emit_sqrtps(XMMOrMemory::XMM(t[0]), t[0])
+            }),
+            Operator::F64x2Sqrt => self.emit_v128_float_op(1, Size::S64, |a, t| {
+                a.emit_sqrtpd(XMMOrMemory::XMM(t[0]), t[0])
+            }),
+            Operator::F32x4Add => self.emit_v128_float_op(2, Size::S32, |a, t| {
+                a.emit_vaddps(t[0], XMMOrMemory::XMM(t[1]), t[0])
+            }),
+            Operator::F64x2Add => self.emit_v128_float_op(2, Size::S64, |a, t| {
+                a.emit_vaddpd(t[0], XMMOrMemory::XMM(t[1]), t[0])
+            }),
+            Operator::F32x4Sub => self.emit_v128_float_op(2, Size::S32, |a, t| {
+                a.emit_vsubps(t[0], XMMOrMemory::XMM(t[1]), t[0])
+            }),
+            Operator::F64x2Sub => self.emit_v128_float_op(2, Size::S64, |a, t| {
+                a.emit_vsubpd(t[0], XMMOrMemory::XMM(t[1]), t[0])
+            }),
+            Operator::F32x4Mul => self.emit_v128_float_op(2, Size::S32, |a, t| {
+                a.emit_vmulps(t[0], XMMOrMemory::XMM(t[1]), t[0])
+            }),
+            Operator::F64x2Mul => self.emit_v128_float_op(2, Size::S64, |a, t| {
+                a.emit_vmulpd(t[0], XMMOrMemory::XMM(t[1]), t[0])
+            }),
+            Operator::F32x4Div => self.emit_v128_float_op(2, Size::S32, |a, t| {
+                a.emit_vdivps(t[0], XMMOrMemory::XMM(t[1]), t[0])
+            }),
+            Operator::F64x2Div => self.emit_v128_float_op(2, Size::S64, |a, t| {
+                a.emit_vdivpd(t[0], XMMOrMemory::XMM(t[1]), t[0])
+            }),
+            Operator::F32x4ConvertI32x4S => self.emit_v128_unop(Assembler::emit_cvtdq2ps),
+            Operator::F32x4ConvertI32x4U => {
+                self.emit_v128_op(1, |this, t| {
+                    // Convert the low 16 bits and the rest separately, both exactly,
+                    // halving the rest so that it converts as a signed value.
+                    this.emit_v128_const(
+                        [
+                            0xff, 0xff, 0, 0, 0xff, 0xff, 0, 0, 0xff, 0xff, 0, 0, 0xff, 0xff, 0, 0,
+                        ],
+                        t[1],
+                    );
+                    this.assembler
+                        .emit_vpand(t[0], XMMOrMemory::XMM(t[1]), t[1]);
+                    this.assembler
+                        .emit_vpsubd(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                    this.assembler.emit_cvtdq2ps(XMMOrMemory::XMM(t[1]), t[1]);
+                    this.assembler.emit_psrld_imm8(1, t[0]);
+                    this.assembler.emit_cvtdq2ps(XMMOrMemory::XMM(t[0]), t[0]);
+                    this.assembler
+                        .emit_vaddps(t[0], XMMOrMemory::XMM(t[0]), t[0]);
+                    this.assembler
+                        .emit_vaddps(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                });
+            }
+            Operator::I32x4TruncSatF32x4S => {
+                self.emit_v128_op(1, |this, t| {
+                    // Zero the NaNs.
+                    this.assembler
+                        .emit_vcmpeqps(t[0], XMMOrMemory::XMM(t[0]), t[1]);
+                    this.assembler
+                        .emit_vpand(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                    // The sign bit of `t[1]` is set for the lanes that are not negative.
+                    this.assembler
+                        .emit_vpxor(t[1], XMMOrMemory::XMM(t[0]), t[1]);
+                    this.assembler.emit_cvttps2dq(XMMOrMemory::XMM(t[0]), t[0]);
+                    // Out of range lanes convert to 0x80000000, turn the positive
+                    // ones into 0x7fffffff.
+                    this.assembler
+                        .emit_vpand(t[1], XMMOrMemory::XMM(t[0]), t[1]);
+                    this.assembler.emit_psrad_imm8(31, t[1]);
+                    this.assembler
+                        .emit_vpxor(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                });
+            }
+            Operator::I32x4TruncSatF32x4U => {
+                self.emit_v128_op(1, |this, t| {
+                    // Zero the NaNs and the negative lanes.
+                    this.assembler
+                        .emit_vpxor(t[1], XMMOrMemory::XMM(t[1]), t[1]);
+                    this.assembler
+                        .emit_vmaxps(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                    // Convert the lanes from 2^31 (`0x4f000000`) separately, saturating them.
+                    this.emit_v128_const(
+                        [0, 0, 0, 0x4f, 0, 0, 0, 0x4f, 0, 0, 0, 0x4f, 0, 0, 0, 0x4f],
+                        t[1],
+                    );
+                    this.assembler
+                        .emit_vsubps(t[0], XMMOrMemory::XMM(t[1]), t[2]);
+                    this.assembler
+                        .emit_vcmpleps(t[1], XMMOrMemory::XMM(t[2]), t[1]);
+                    this.assembler.emit_cvttps2dq(XMMOrMemory::XMM(t[2]), t[2]);
+                    this.assembler
+                        .emit_vpxor(t[2], XMMOrMemory::XMM(t[1]), t[2]);
+                    this.assembler
+                        .emit_vpxor(t[1], XMMOrMemory::XMM(t[1]), t[1]);
+                    this.assembler
+                        .emit_vpmaxsd(t[2], XMMOrMemory::XMM(t[1]), t[2]);
+                    // The lanes from 2^31 convert to 0x80000000.
+                    this.assembler.emit_cvttps2dq(XMMOrMemory::XMM(t[0]), t[0]);
+                    this.assembler
+                        .emit_vpaddd(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                });
             }
-            Operator::I64AtomicRmw32CmpxchgU { ref memarg } => {
-                let new = self.pop_value_released();
-                let cmp = self.pop_value_released();
-                let target = self.pop_value_released();
-                let ret = self.machine.acquire_locations(
-                    &mut self.assembler,
-                    &[(WpType::I64, MachineValue::WasmStack(self.value_stack.len()))],
-                    false,
-                )[0];
-                self.value_stack.push(ret);
 
-                let compare = self.machine.reserve_unused_temp_gpr(GPR::RAX);
-                let value = if cmp == Location::GPR(GPR::R14) {
-                    if new == Location::GPR(GPR::R13) {
-                        GPR::R12
-                    } else {
-                        GPR::R13
-                    }
-                } else {
-                    GPR::R14
-                };
-                self.assembler.emit_push(Size::S64, Location::GPR(value));
-                self.assembler
-                    .emit_mov(Size::S64, cmp, Location::GPR(compare));
-                self.assembler
-                    .emit_mov(Size::S64, new, Location::GPR(value));
+            Operator::F32x4Min => self.emit_v128_float_min_max(Size::S32, false),
+            Operator::F32x4Max => self.emit_v128_float_min_max(Size::S32, true),
+            Operator::F64x2Min => self.emit_v128_float_min_max(Size::S64, false),
+            Operator::F64x2Max => self.emit_v128_float_min_max(Size::S64, true),
+            // `minps` and `maxps` return their second operand unless the first one
+            // is strictly less or greater.
+            Operator::F32x4PMin => self.emit_v128_binop_swapped(Assembler::emit_vminps),
+            Operator::F32x4PMax => self.emit_v128_binop_swapped(Assembler::emit_vmaxps),
+            Operator::F64x2PMin => self.emit_v128_binop_swapped(Assembler::emit_vminpd),
+            Operator::F64x2PMax => self.emit_v128_binop_swapped(Assembler::emit_vmaxpd),
+            Operator::F32x4Ceil => self.emit_v128_unop(Assembler::emit_roundps_ceil),
+            Operator::F32x4Floor => self.emit_v128_unop(Assembler::emit_roundps_floor),
+            Operator::F32x4Trunc => self.emit_v128_unop(Assembler::emit_roundps_trunc),
+            Operator::F32x4Nearest => self.emit_v128_unop(Assembler::emit_roundps_nearest),
+            Operator::F64x2Ceil => self.emit_v128_unop(Assembler::emit_roundpd_ceil),
+            Operator::F64x2Floor => self.emit_v128_unop(Assembler::emit_roundpd_floor),
+            Operator::F64x2Trunc => self.emit_v128_unop(Assembler::emit_roundpd_trunc),
+            Operator::F64x2Nearest => self.emit_v128_unop(Assembler::emit_roundpd_nearest),
+
+            Operator::I8x16NarrowI16x8S => self.emit_v128_binop(Assembler::emit_vpacksswb),
+            Operator::I8x16NarrowI16x8U => self.emit_v128_binop(Assembler::emit_vpackuswb),
+            Operator::I16x8NarrowI32x4S => self.emit_v128_binop(Assembler::emit_vpackssdw),
+            Operator::I16x8NarrowI32x4U => self.emit_v128_binop(Assembler::emit_vpackusdw),
+            Operator::I16x8WidenLowI8x16S => self.emit_v128_widen(Assembler::emit_pmovsxbw, false),
+            Operator::I16x8WidenHighI8x16S => self.emit_v128_widen(Assembler::emit_pmovsxbw, true),
+            Operator::I16x8WidenLowI8x16U => self.emit_v128_widen(Assembler::emit_pmovzxbw, false),
+            Operator::I16x8WidenHighI8x16U => self.emit_v128_widen(Assembler::emit_pmovzxbw, true),
+            Operator::I32x4WidenLowI16x8S => self.emit_v128_widen(Assembler::emit_pmovsxwd, false),
+            Operator::I32x4WidenHighI16x8S => self.emit_v128_widen(Assembler::emit_pmovsxwd, true),
+            Operator::I32x4WidenLowI16x8U => self.emit_v128_widen(Assembler::emit_pmovzxwd, false),
+            Operator::I32x4WidenHighI16x8U => self.emit_v128_widen(Assembler::emit_pmovzxwd, true),
+
+            Operator::I8x16Shl => {
+                self.emit_i8x16_shift(Assembler::emit_vpsllw, Assembler::emit_vpackuswb, true)
+            }
+            Operator::I8x16ShrS => {
+                self.emit_i8x16_shift(Assembler::emit_vpsraw, Assembler::emit_vpacksswb, false)
+            }
+            Operator::I8x16ShrU => {
+                self.emit_i8x16_shift(Assembler::emit_vpsrlw, Assembler::emit_vpackuswb, false)
+            }
+            Operator::I64x2ShrS => {
+                self.emit_v128_shift_with(64, |this, t, amount| {
+                    // Shift logically and sign extend with `(x ^ m) - m`, where `m`
+                    // is the shifted sign bit.
+                    this.assembler
+                        .emit_mov(Size::S32, Location::GPR(amount), Location::XMM(t[1]));
+                    this.emit_v128_const(
+                        [0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0, 0, 0, 0, 0, 0, 0x80],
+                        t[2],
+                    );
+                    this.assembler
+                        .emit_vpsrlq(t[2], XMMOrMemory::XMM(t[1]), t[2]);
+                    this.assembler
+                        .emit_vpsrlq(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                    this.assembler
+                        .emit_vpxor(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                    this.assembler
+                        .emit_vpsubq(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                });
+            }
+            Operator::I64x2Mul => {
+                self.emit_v128_op(2, |this, t| {
+                    // The low halves of the cross products, added up in the
+                    // high half of each lane, plus the product of the low halves.
+                    this.assembler
+                        .emit_pshufd(XMMOrMemory::XMM(t[1]), 0xb1, t[2]);
+                    this.assembler
+                        .emit_vpmulld(t[2], XMMOrMemory::XMM(t[0]), t[2]);
+                    this.assembler
+                        .emit_vpmuludq(t[0], XMMOrMemory::XMM(t[1]), t[0]);
+                    this.assembler
+                        .emit_pshufd(XMMOrMemory::XMM(t[2]), 0xb1, t[1]);
+                    this.assembler
+                        .emit_vpaddd(t[2], XMMOrMemory::XMM(t[1]), t[2]);
+                    this.assembler.emit_psllq_imm8(32, t[2]);
+                    this.assembler
+                        .emit_vpaddq(t[0], XMMOrMemory::XMM(t[2]), t[0]);
+                });
+            }
+            Operator::I32x4DotI16x8S => self.emit_v128_binop(Assembler::emit_vpmaddwd),
+            Operator::I16x8Bitmask => self.emit_v128_extract(WpType::I32, |this, v, dst| {
+                // Saturating the lanes to bytes keeps their sign.
+                this.assembler.emit_vpacksswb(v, XMMOrMemory::XMM(v), v);
+                this.assembler.emit_pmovmskb(v, dst);
+                this.assembler
+                    .emit_and(Size::S32, Location::Imm32(0xff), Location::GPR(dst));
+            }),
+            Operator::I32x4Bitmask => self.emit_v128_extract(WpType::I32, |this, v, dst| {
+                this.assembler.emit_movmskps(v, dst)
+            }),
 
-                self.emit_memory_op(target, memarg, true, 1, |this, addr| {
-                    this.assembler.emit_lock_cmpxchg(
+            Operator::V128Load8Splat { ref memarg } => {
+                self.emit_v128_load_splat(memarg, Size::S8)?
+            }
+            Operator::V128Load16Splat { ref memarg } => {
+                self.emit_v128_load_splat(memarg, Size::S16)?
+            }
+            Operator::V128Load32Splat { ref memarg } => {
+                self.emit_v128_load_splat(memarg, Size::S32)?
+            }
+            Operator::V128Load64Splat { ref memarg } => {
+                self.emit_v128_load_splat(memarg, Size::S64)?
+            }
+            Operator::V128Load8x8S { ref memarg } => {
+                self.emit_v128_load_extend(memarg, Assembler::emit_pmovsxbw)?
+            }
+            Operator::V128Load8x8U { ref memarg } => {
+                self.emit_v128_load_extend(memarg, Assembler::emit_pmovzxbw)?
+            }
+            Operator::V128Load16x4S { ref memarg } => {
+                self.emit_v128_load_extend(memarg, Assembler::emit_pmovsxwd)?
+            }
+            Operator::V128Load16x4U { ref memarg } => {
+                self.emit_v128_load_extend(memarg, Assembler::emit_pmovzxwd)?
+            }
+            Operator::V128Load32x2S { ref memarg } => {
+                self.emit_v128_load_extend(memarg, Assembler::emit_pmovsxdq)?
+            }
+            Operator::V128Load32x2U { ref memarg } => {
+                self.emit_v128_load_extend(memarg, Assembler::emit_pmovzxdq)?
+            }
+            // `movd` and `movq` clear the rest of the register.
+            Operator::V128Load32Zero { ref memarg } => {
+                self.emit_v128_load(memarg, 4, |this, addr, dst| {
+                    this.assembler.emit_mov(
                         Size::S32,
-                        Location::GPR(value),
                         Location::Memory(addr, 0),
-                    );
-                    this.assembler
-                        .emit_mov(Size::S32, Location::GPR(compare), ret);
-                    Ok(())
-                })?;
-                self.assembler.emit_pop(Size::S64, Location::GPR(value));
-                self.machine.release_temp_gpr(compare);
+                        Location::XMM(dst),
+                    )
+                })?
+            }
+            Operator::V128Load64Zero { ref memarg } => {
+                self.emit_v128_load(memarg, 8, |this, addr, dst| {
+                    this.assembler.emit_mov(
+                        Size::S64,
+                        Location::Memory(addr, 0),
+                        Location::XMM(dst),
+                    )
+                })?
             }
             _ => {
                 return Err(CodegenError {
@@ -8183,6 +10072,11 @@ impl<'a> FuncGen<'a> {
         self.mark_address_with_trap_code(TrapCode::HeapAccessOutOfBounds);
         self.assembler.emit_ud2();
 
+        self.assembler
+            .emit_label(self.special_labels.unaligned_atomic);
+        self.mark_address_with_trap_code(TrapCode::UnalignedAtomic);
+        self.assembler.emit_ud2();
+
         self.assembler
             .emit_label(self.special_labels.table_access_oob);
         self.mark_address_with_trap_code(TrapCode::TableAccessOutOfBounds);
@@ -8203,6 +10097,29 @@ impl<'a> FuncGen<'a> {
         let body_len = self.assembler.get_offset().0;
         let instructions_address_map = self.instructions_address_map;
         let address_map = get_function_address_map(instructions_address_map, data, body_len);
+        let (start, end) = self.locals_code_range;
+        let locals = self
+            .locals
+            .iter()
+            .zip(&self.local_types)
+            .map(|(location, ty)| LocalLocations {
+                ty: wptype_to_type(*ty).unwrap(),
+                ranges: vec![ValueLocationRange {
+                    start: start as u32,
+                    end: end as u32,
+                    location: match *location {
+                        Location::GPR(x) => {
+                            ValueLocation::Register(X64Register::GPR(x).to_dwarf_regnum())
+                        }
+                        Location::Memory(base, offset) => ValueLocation::Memory {
+                            register: X64Register::GPR(base).to_dwarf_regnum(),
+                            offset: offset.into(),
+                        },
+                        _ => unreachable!("local at {:?}", location),
+                    },
+                }],
+            })
+            .collect();
 
         CompiledFunction {
             body: FunctionBody {
@@ -8222,11 +10139,31 @@ impl<'a> FuncGen<'a> {
                     })
                     .collect(),
                 address_map,
+                locals,
             },
         }
     }
 }
 
+/// Converts the location of a `v128` value to an XMM instruction operand.
+fn v128_operand(loc: Location) -> XMMOrMemory {
+    match loc {
+        Location::XMM(x) => XMMOrMemory::XMM(x),
+        Location::Memory(base, offset) => XMMOrMemory::Memory(base, offset),
+        _ => unreachable!("v128 value at {:?}", loc),
+    }
+}
+
+/// Returns the number of results of a block returned in return slots:
+/// all of them if there are several or if one is a `v128`, none otherwise.
+fn block_return_slots(returns: &[WpType]) -> usize {
+    if returns.len() > 1 || returns.contains(&WpType::V128) {
+        returns.len()
+    } else {
+        0
+    }
+}
+
 fn type_to_wp_type(ty: Type) -> WpType {
     match ty {
         Type::I32 => WpType::I32,
@@ -8239,71 +10176,16 @@ fn type_to_wp_type(ty: Type) -> WpType {
     }
 }
 
-// FIXME: This implementation seems to be not enough to resolve all kinds of register dependencies
-// at call place.
-fn sort_call_movs(movs: &mut [(Location, GPR)]) {
-    for i in 0..movs.len() {
-        for j in (i + 1)..movs.len() {
-            if let Location::GPR(src_gpr) = movs[j].0 {
-                if src_gpr == movs[i].1 {
-                    movs.swap(i, j);
-                }
-            }
-        }
-    }
-
-    // Cycle detector. Uncomment this to debug possibly incorrect call-mov sequences.
-    /*
-    {
-        use std::collections::{HashMap, HashSet, VecDeque};
-        let mut mov_map: HashMap<GPR, HashSet<GPR>> = HashMap::new();
-        for mov in movs.iter() {
-            if let Location::GPR(src_gpr) = mov.0 {
-                if src_gpr != mov.1 {
-                    mov_map.entry(src_gpr).or_insert_with(|| HashSet::new()).insert(mov.1);
-                }
-            }
-        }
-
-        for (start, _) in mov_map.iter() {
-            let mut q: VecDeque<GPR> = VecDeque::new();
-            let mut black: HashSet<GPR> = HashSet::new();
-
-            q.push_back(*start);
-            black.insert(*start);
-
-            while q.len() > 0 {
-                let reg = q.pop_front().unwrap();
-                let empty_set = HashSet::new();
-                for x in mov_map.get(&reg).unwrap_or(&empty_set).iter() {
-                    if black.contains(x) {
-                        panic!("cycle detected");
-                    }
-                    q.push_back(*x);
-                    black.insert(*x);
-                }
-            }
-        }
-    }
-    */
-}
-
 // Standard entry trampoline.
 pub fn gen_std_trampoline(sig: &FunctionType) -> FunctionBody {
     let mut a = Assembler::new().unwrap();
+    let abi = FunctionAbi::new(sig.params(), sig.results());
 
-    // Calculate stack offset.
-    let mut stack_offset: u32 = 0;
-    for (i, _param) in sig.params().iter().enumerate() {
-        if let Location::Memory(_, _) = Machine::get_param_location(1 + i) {
-            stack_offset += 8;
-        }
-    }
+    // The stack arguments are followed by the results area, aligned for `v128` values.
+    let results_area_offset = (abi.stack_params_size + 15) / 16 * 16;
 
     // Align to 16 bytes. We push two 8-byte registers below, so here we need to ensure stack_offset % 16 == 8.
-    if stack_offset % 16 != 8 {
-        stack_offset += 8;
-    }
+    let stack_offset = ((results_area_offset + abi.results_area_size + 15) / 16 * 16 + 8) as u32;
 
     // Used callee-saved registers
     a.emit_push(Size::S64, Location::GPR(GPR::R15));
@@ -8317,41 +10199,51 @@ pub fn gen_std_trampoline(sig: &FunctionType) -> FunctionBody {
     );
 
     // Arguments
-    a.emit_mov(
-        Size::S64,
-        Machine::get_param_location(1),
-        Location::GPR(GPR::R15),
-    ); // func_ptr
-    a.emit_mov(
-        Size::S64,
-        Machine::get_param_location(2),
-        Location::GPR(GPR::R14),
-    ); // args_rets
+    a.emit_mov(Size::S64, Location::GPR(GPR::RSI), Location::GPR(GPR::R15)); // func_ptr
+    a.emit_mov(Size::S64, Location::GPR(GPR::RDX), Location::GPR(GPR::R14)); // args_rets
+
+    // `callee_vmctx` is already in the first argument register, unless the
+    // address of the results area comes first.
+    if let Some(results_area) = abi.results_area {
+        a.emit_mov(Size::S64, Location::GPR(GPR::RDI), Location::GPR(abi.vmctx));
+        a.emit_lea(
+            Size::S64,
+            Location::Memory(GPR::RSP, results_area_offset as i32),
+            Location::GPR(results_area),
+        );
+    }
 
     // Move arguments to their locations.
-    // `callee_vmctx` is already in the first argument register, so no need to move.
-    {
-        let mut n_stack_args: usize = 0;
-        for (i, _param) in sig.params().iter().enumerate() {
-            let src_loc = Location::Memory(GPR::R14, (i * 16) as _); // args_rets[i]
-            let dst_loc = Machine::get_param_location(1 + i);
-
-            match dst_loc {
-                Location::GPR(_) => {
-                    a.emit_mov(Size::S64, src_loc, dst_loc);
-                }
-                Location::Memory(_, _) => {
-                    // This location is for reading arguments but we are writing arguments here.
-                    // So recalculate it.
-                    a.emit_mov(Size::S64, src_loc, Location::GPR(GPR::RAX));
+    for (i, (ty, loc)) in sig.params().iter().zip(abi.params.iter()).enumerate() {
+        let src = (i * 16) as i32; // args_rets[i]
+        match *loc {
+            AbiLocation::Reg(X64Register::XMM(xmm)) if *ty == Type::V128 => {
+                a.emit_movdqu(XMMOrMemory::Memory(GPR::R14, src), XMMOrMemory::XMM(xmm))
+            }
+            AbiLocation::Reg(X64Register::XMM(xmm)) => a.emit_mov(
+                Size::S64,
+                Location::Memory(GPR::R14, src),
+                Location::XMM(xmm),
+            ),
+            AbiLocation::Reg(X64Register::GPR(gpr)) => a.emit_mov(
+                Size::S64,
+                Location::Memory(GPR::R14, src),
+                Location::GPR(gpr),
+            ),
+            AbiLocation::Memory(offset) => {
+                let halves = if *ty == Type::V128 { 2 } else { 1 };
+                for half in 0..halves {
+                    a.emit_mov(
+                        Size::S64,
+                        Location::Memory(GPR::R14, src + half * 8),
+                        Location::GPR(GPR::RAX),
+                    );
                     a.emit_mov(
                         Size::S64,
                         Location::GPR(GPR::RAX),
-                        Location::Memory(GPR::RSP, (n_stack_args * 8) as _),
+                        Location::Memory(GPR::RSP, offset + half * 8),
                     );
-                    n_stack_args += 1;
                 }
-                _ => unreachable!(),
             }
         }
     }
@@ -8359,6 +10251,65 @@ pub fn gen_std_trampoline(sig: &FunctionType) -> FunctionBody {
     // Call.
     a.emit_call_location(Location::GPR(GPR::R15));
 
+    // Write return values.
+    for (i, (ty, loc)) in sig.results().iter().zip(abi.results.iter()).enumerate() {
+        let dst = (i * 16) as i32; // args_rets[i]
+        match *loc {
+            AbiLocation::Reg(X64Register::XMM(xmm)) if *ty == Type::V128 => {
+                a.emit_movdqu(XMMOrMemory::XMM(xmm), XMMOrMemory::Memory(GPR::R14, dst))
+            }
+            AbiLocation::Reg(X64Register::XMM(xmm)) => a.emit_mov(
+                Size::S64,
+                Location::XMM(xmm),
+                Location::Memory(GPR::R14, dst),
+            ),
+            AbiLocation::Reg(X64Register::GPR(gpr)) => a.emit_mov(
+                Size::S64,
+                Location::GPR(gpr),
+                Location::Memory(GPR::R14, dst),
+            ),
+            AbiLocation::Memory(offset) => {
+                let src = results_area_offset as i32 + offset;
+                match ty {
+                    Type::V128 => {
+                        a.emit_movdqu(
+                            XMMOrMemory::Memory(GPR::RSP, src),
+                            XMMOrMemory::XMM(XMM::XMM0),
+                        );
+                        a.emit_movdqu(
+                            XMMOrMemory::XMM(XMM::XMM0),
+                            XMMOrMemory::Memory(GPR::R14, dst),
+                        );
+                    }
+                    Type::I32 | Type::F32 => {
+                        a.emit_mov(
+                            Size::S32,
+                            Location::Memory(GPR::RSP, src),
+                            Location::GPR(GPR::RAX),
+                        );
+                        a.emit_mov(
+                            Size::S64,
+                            Location::GPR(GPR::RAX),
+                            Location::Memory(GPR::R14, dst),
+                        );
+                    }
+                    _ => {
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(GPR::RSP, src),
+                            Location::GPR(GPR::RAX),
+                        );
+                        a.emit_mov(
+                            Size::S64,
+                            Location::GPR(GPR::RAX),
+                            Location::Memory(GPR::R14, dst),
+                        );
+                    }
+                }
+            }
+        }
+    }
+
     // Restore stack.
     a.emit_add(
         Size::S64,
@@ -8366,15 +10317,6 @@ pub fn gen_std_trampoline(sig: &FunctionType) -> FunctionBody {
         Location::GPR(GPR::RSP),
     );
 
-    // Write return value.
-    if !sig.results().is_empty() {
-        a.emit_mov(
-            Size::S64,
-            Location::GPR(GPR::RAX),
-            Location::Memory(GPR::R14, 0),
-        );
-    }
-
     // Restore callee-saved registers.
     a.emit_pop(Size::S64, Location::GPR(GPR::R14));
     a.emit_pop(Size::S64, Location::GPR(GPR::R15));
@@ -8393,76 +10335,159 @@ pub fn gen_std_dynamic_import_trampoline(
     sig: &FunctionType,
 ) -> FunctionBody {
     let mut a = Assembler::new().unwrap();
+    let abi = FunctionAbi::new(sig.params(), sig.results());
 
     // Allocate argument array.
-    let stack_offset: usize = 16 * std::cmp::max(sig.params().len(), sig.results().len()) + 8; // 16 bytes each + 8 bytes sysv call padding
+    let values_size: usize = 16 * std::cmp::max(sig.params().len(), sig.results().len());
+    let mut stack_offset: usize = values_size + 8; // 16 bytes each + 8 bytes sysv call padding
+    if abi.results_area.is_some() {
+        // Room to save the address of the results area, keeping the stack aligned.
+        stack_offset += 16;
+    }
     a.emit_sub(
         Size::S64,
         Location::Imm32(stack_offset as _),
         Location::GPR(GPR::RSP),
     );
+    if let Some(results_area) = abi.results_area {
+        a.emit_mov(
+            Size::S64,
+            Location::GPR(results_area),
+            Location::Memory(GPR::RSP, values_size as _),
+        );
+    }
 
     // Copy arguments.
-    if !sig.params().is_empty() {
-        let mut argalloc = ArgumentRegisterAllocator::default();
-        argalloc.next(Type::I64).unwrap(); // skip VMContext
-
-        let mut stack_param_count: usize = 0;
-
-        for (i, ty) in sig.params().iter().enumerate() {
-            let source_loc = match argalloc.next(*ty) {
-                Some(X64Register::GPR(gpr)) => Location::GPR(gpr),
-                Some(X64Register::XMM(xmm)) => Location::XMM(xmm),
-                None => {
+    for (i, (ty, loc)) in sig.params().iter().zip(abi.params.iter()).enumerate() {
+        let dst = (i * 16) as i32;
+        match *loc {
+            AbiLocation::Reg(X64Register::XMM(xmm)) if *ty == Type::V128 => {
+                a.emit_movdqu(XMMOrMemory::XMM(xmm), XMMOrMemory::Memory(GPR::RSP, dst));
+                continue;
+            }
+            AbiLocation::Reg(X64Register::XMM(xmm)) => {
+                a.emit_mov(
+                    Size::S64,
+                    Location::XMM(xmm),
+                    Location::Memory(GPR::RSP, dst),
+                );
+            }
+            AbiLocation::Reg(X64Register::GPR(gpr)) => {
+                a.emit_mov(
+                    Size::S64,
+                    Location::GPR(gpr),
+                    Location::Memory(GPR::RSP, dst),
+                );
+            }
+            AbiLocation::Memory(offset) => {
+                let halves = if *ty == Type::V128 { 2 } else { 1 };
+                for half in 0..halves {
+                    a.emit_mov(
+                        Size::S64,
+                        Location::Memory(GPR::RSP, (stack_offset + 8) as i32 + offset + half * 8),
+                        Location::GPR(GPR::RAX),
+                    );
                     a.emit_mov(
                         Size::S64,
-                        Location::Memory(GPR::RSP, (stack_offset + 8 + stack_param_count * 8) as _),
                         Location::GPR(GPR::RAX),
+                        Location::Memory(GPR::RSP, dst + half * 8),
                     );
-                    stack_param_count += 1;
-                    Location::GPR(GPR::RAX)
                 }
-            };
-            a.emit_mov(
-                Size::S64,
-                source_loc,
-                Location::Memory(GPR::RSP, (i * 16) as _),
-            );
-
-            // Zero upper 64 bits.
-            a.emit_mov(
-                Size::S64,
-                Location::Imm32(0),
-                Location::Memory(GPR::RSP, (i * 16 + 8) as _),
-            );
+                if *ty == Type::V128 {
+                    continue;
+                }
+            }
         }
+
+        // Zero upper 64 bits.
+        a.emit_mov(
+            Size::S64,
+            Location::Imm32(0),
+            Location::Memory(GPR::RSP, dst + 8),
+        );
     }
 
     // Load target address.
     a.emit_mov(
         Size::S64,
         Location::Memory(
-            GPR::RDI,
+            abi.vmctx,
             vmoffsets.vmdynamicfunction_import_context_address() as i32,
         ),
         Location::GPR(GPR::RAX),
     );
 
-    // Load values array.
+    // Pass the context and the values array.
+    if abi.vmctx != GPR::RDI {
+        a.emit_mov(Size::S64, Location::GPR(abi.vmctx), Location::GPR(GPR::RDI));
+    }
     a.emit_mov(Size::S64, Location::GPR(GPR::RSP), Location::GPR(GPR::RSI));
 
     // Call target.
     a.emit_call_location(Location::GPR(GPR::RAX));
 
-    // Fetch return value.
-    if !sig.results().is_empty() {
-        assert_eq!(sig.results().len(), 1);
+    // Fetch return values.
+    if abi.results_area.is_some() {
         a.emit_mov(
             Size::S64,
-            Location::Memory(GPR::RSP, 0),
-            Location::GPR(GPR::RAX),
+            Location::Memory(GPR::RSP, values_size as _),
+            Location::GPR(GPR::RCX),
         );
     }
+    for (i, (ty, loc)) in sig.results().iter().zip(abi.results.iter()).enumerate() {
+        let src = (i * 16) as i32;
+        match *loc {
+            AbiLocation::Reg(X64Register::XMM(xmm)) if *ty == Type::V128 => {
+                a.emit_movdqu(XMMOrMemory::Memory(GPR::RSP, src), XMMOrMemory::XMM(xmm));
+            }
+            AbiLocation::Reg(X64Register::XMM(xmm)) => {
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(GPR::RSP, src),
+                    Location::XMM(xmm),
+                );
+            }
+            AbiLocation::Reg(X64Register::GPR(gpr)) => {
+                a.emit_mov(
+                    Size::S64,
+                    Location::Memory(GPR::RSP, src),
+                    Location::GPR(gpr),
+                );
+            }
+            AbiLocation::Memory(offset) => match ty {
+                Type::V128 => {
+                    a.emit_movdqu(
+                        XMMOrMemory::Memory(GPR::RSP, src),
+                        XMMOrMemory::XMM(XMM::XMM0),
+                    );
+                    a.emit_movdqu(
+                        XMMOrMemory::XMM(XMM::XMM0),
+                        XMMOrMemory::Memory(GPR::RCX, offset),
+                    );
+                }
+                _ => {
+                    let size = match ty {
+                        Type::I32 | Type::F32 => Size::S32,
+                        _ => Size::S64,
+                    };
+                    a.emit_mov(
+                        Size::S64,
+                        Location::Memory(GPR::RSP, src),
+                        Location::GPR(GPR::RAX),
+                    );
+                    a.emit_mov(
+                        size,
+                        Location::GPR(GPR::RAX),
+                        Location::Memory(GPR::RCX, offset),
+                    );
+                }
+            },
+        }
+    }
+    // The address of the results area is returned in RAX.
+    if abi.results_area.is_some() {
+        a.emit_mov(Size::S64, Location::GPR(GPR::RCX), Location::GPR(GPR::RAX));
+    }
 
     // Release values array.
     a.emit_add(
@@ -8480,7 +10505,10 @@ pub fn gen_std_dynamic_import_trampoline(
     }
 }
 
-// Singlepass calls import functions through a trampoline.
+/// Generates the trampoline calling the imported function `index`.
+///
+/// Imports are called with the calling convention of the other functions,
+/// so it only loads the function and its `vmctx` from the caller's `vmctx`.
 pub fn gen_import_call_trampoline(
     vmoffsets: &VMOffsets,
     index: FunctionIndex,
@@ -8490,99 +10518,21 @@ pub fn gen_import_call_trampoline(
 
     // TODO: ARM entry trampoline is not emitted.
 
-    // Singlepass internally treats all arguments as integers, but the standard System V calling convention requires
-    // floating point arguments to be passed in XMM registers.
-    //
-    // FIXME: This is only a workaround. We should fix singlepass to use the standard CC.
-
-    // Translation is expensive, so only do it if needed.
-    if sig
-        .params()
-        .iter()
-        .any(|&x| x == Type::F32 || x == Type::F64)
-    {
-        let mut param_locations: Vec<Location> = vec![];
-
-        // Allocate stack space for arguments.
-        let stack_offset: i32 = if sig.params().len() > 5 {
-            5 * 8
-        } else {
-            (sig.params().len() as i32) * 8
-        };
-        if stack_offset > 0 {
-            a.emit_sub(
-                Size::S64,
-                Location::Imm32(stack_offset as u32),
-                Location::GPR(GPR::RSP),
-            );
-        }
-
-        // Store all arguments to the stack to prevent overwrite.
-        for i in 0..sig.params().len() {
-            let loc = match i {
-                0..=4 => {
-                    static PARAM_REGS: &[GPR] = &[GPR::RSI, GPR::RDX, GPR::RCX, GPR::R8, GPR::R9];
-                    let loc = Location::Memory(GPR::RSP, (i * 8) as i32);
-                    a.emit_mov(Size::S64, Location::GPR(PARAM_REGS[i]), loc);
-                    loc
-                }
-                _ => Location::Memory(GPR::RSP, stack_offset + 8 + ((i - 5) * 8) as i32),
-            };
-            param_locations.push(loc);
-        }
-
-        // Copy arguments.
-        let mut argalloc = ArgumentRegisterAllocator::default();
-        argalloc.next(Type::I64).unwrap(); // skip VMContext
-        let mut caller_stack_offset: i32 = 0;
-        for (i, ty) in sig.params().iter().enumerate() {
-            let prev_loc = param_locations[i];
-            let target = match argalloc.next(*ty) {
-                Some(X64Register::GPR(gpr)) => Location::GPR(gpr),
-                Some(X64Register::XMM(xmm)) => Location::XMM(xmm),
-                None => {
-                    // No register can be allocated. Put this argument on the stack.
-                    //
-                    // Since here we never use fewer registers than by the original call, on the caller's frame
-                    // we always have enough space to store the rearranged arguments, and the copy "backward" between different
-                    // slots in the caller argument region will always work.
-                    a.emit_mov(Size::S64, prev_loc, Location::GPR(GPR::RAX));
-                    a.emit_mov(
-                        Size::S64,
-                        Location::GPR(GPR::RAX),
-                        Location::Memory(GPR::RSP, stack_offset + 8 + caller_stack_offset),
-                    );
-                    caller_stack_offset += 8;
-                    continue;
-                }
-            };
-            a.emit_mov(Size::S64, prev_loc, target);
-        }
-
-        // Restore stack pointer.
-        if stack_offset > 0 {
-            a.emit_add(
-                Size::S64,
-                Location::Imm32(stack_offset as u32),
-                Location::GPR(GPR::RSP),
-            );
-        }
-    }
-
     // Emits a tail call trampoline that loads the address of the target import function
     // from Ctx and jumps to it.
 
+    let vmctx = FunctionAbi::new(sig.params(), sig.results()).vmctx;
     let offset = vmoffsets.vmctx_vmfunction_import(index);
 
     a.emit_mov(
         Size::S64,
-        Location::Memory(GPR::RDI, offset as i32), // function pointer
+        Location::Memory(vmctx, offset as i32), // function pointer
         Location::GPR(GPR::RAX),
     );
     a.emit_mov(
         Size::S64,
-        Location::Memory(GPR::RDI, offset as i32 + 8), // target vmctx
-        Location::GPR(GPR::RDI),
+        Location::Memory(vmctx, offset as i32 + 8), // target vmctx
+        Location::GPR(vmctx),
     );
     a.emit_host_redirection(GPR::RAX);
 
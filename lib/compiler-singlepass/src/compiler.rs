@@ -9,12 +9,15 @@ use crate::codegen_x64::{
 use crate::config::Singlepass;
 use rayon::prelude::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use std::sync::Arc;
+use wasmer_compiler::wasmparser::Operator;
 use wasmer_compiler::TrapInformation;
 use wasmer_compiler::{
     Architecture, CompileModuleInfo, CompilerConfig, MiddlewareBinaryReader, ModuleMiddlewareChain,
     ModuleTranslationState, OperatingSystem, Target,
 };
-use wasmer_compiler::{Compilation, CompileError, CompiledFunction, Compiler, SectionIndex};
+use wasmer_compiler::{
+    Compilation, CompileError, CompiledFunction, Compiler, CustomSection, SectionIndex,
+};
 use wasmer_compiler::{FunctionBody, FunctionBodyData};
 use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{FunctionIndex, FunctionType, LocalFunctionIndex, MemoryIndex, TableIndex};
@@ -39,6 +42,20 @@ impl SinglepassCompiler {
 }
 
 impl Compiler for SinglepassCompiler {
+    fn transform_module_info(&self, module: &mut ModuleInfo) {
+        self.config.middlewares.apply_on_module_info(module);
+    }
+
+    fn analyze_function_bodies(
+        &self,
+        module: &ModuleInfo,
+        function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) {
+        self.config
+            .middlewares
+            .apply_on_function_bodies(module, function_body_inputs);
+    }
+
     /// Compile the module using Singlepass, producing a compilation result with
     /// associated relocations.
     fn compile_module(
@@ -48,76 +65,15 @@ impl Compiler for SinglepassCompiler {
         _module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
     ) -> Result<Compilation, CompileError> {
-        if target.triple().operating_system == OperatingSystem::Windows {
-            return Err(CompileError::UnsupportedTarget(
-                OperatingSystem::Windows.to_string(),
-            ));
-        }
-        if let Architecture::X86_32(arch) = target.triple().architecture {
-            return Err(CompileError::UnsupportedTarget(arch.to_string()));
-        }
-        if compile_info.features.multi_value {
-            return Err(CompileError::UnsupportedFeature("multivalue".to_string()));
-        }
-        let memory_styles = &compile_info.memory_styles;
-        let table_styles = &compile_info.table_styles;
-        let mut module = (*compile_info.module).clone();
-        self.config.middlewares.apply_on_module_info(&mut module);
-        compile_info.module = Arc::new(module);
+        check_supported(target, compile_info)?;
         let vmoffsets = VMOffsets::new(8, &compile_info.module);
         let module = &compile_info.module;
-        let import_trampolines: PrimaryMap<SectionIndex, _> = (0..module.num_imported_functions)
-            .map(FunctionIndex::new)
-            .collect::<Vec<_>>()
-            .into_par_iter()
-            .map(|i| {
-                gen_import_call_trampoline(&vmoffsets, i, &module.signatures[module.functions[i]])
-            })
-            .collect::<Vec<_>>()
-            .into_iter()
-            .collect();
+        let import_trampolines = self.import_trampolines(module, &vmoffsets);
         let functions = function_body_inputs
             .iter()
             .collect::<Vec<(LocalFunctionIndex, &FunctionBodyData<'_>)>>()
             .par_iter()
-            .map(|(i, input)| {
-                let middleware_chain = self
-                    .config
-                    .middlewares
-                    .generate_function_middleware_chain(*i);
-                let mut reader =
-                    MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
-                reader.set_middleware_chain(middleware_chain);
-
-                // This local list excludes arguments.
-                let mut locals = vec![];
-                let num_locals = reader.read_local_count()?;
-                for _ in 0..num_locals {
-                    let (count, ty) = reader.read_local_decl()?;
-                    for _ in 0..count {
-                        locals.push(ty);
-                    }
-                }
-
-                let mut generator = FuncGen::new(
-                    module,
-                    &self.config,
-                    &vmoffsets,
-                    &memory_styles,
-                    &table_styles,
-                    *i,
-                    &locals,
-                )
-                .map_err(to_compile_error)?;
-
-                while generator.has_control_frames() {
-                    generator.set_srcloc(reader.original_position() as u32);
-                    let op = reader.read_operator()?;
-                    generator.feed_operator(op).map_err(to_compile_error)?;
-                }
-
-                Ok(generator.finalize(&input))
-            })
+            .map(|(i, input)| self.compile_function_body(compile_info, &vmoffsets, *i, input))
             .collect::<Result<Vec<CompiledFunction>, CompileError>>()?
             .into_iter()
             .collect::<PrimaryMap<LocalFunctionIndex, CompiledFunction>>();
@@ -150,6 +106,117 @@ impl Compiler for SinglepassCompiler {
             None,
         ))
     }
+
+    /// Compile a single function using Singlepass, along with the import
+    /// trampolines it may call.
+    fn compile_function(
+        &self,
+        target: &Target,
+        compile_info: &mut CompileModuleInfo,
+        _module_translation: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<Compilation, CompileError> {
+        check_supported(target, compile_info)?;
+        let vmoffsets = VMOffsets::new(8, &compile_info.module);
+        let import_trampolines = self.import_trampolines(&compile_info.module, &vmoffsets);
+        let mut functions = PrimaryMap::new();
+        functions.push(self.compile_function_body(compile_info, &vmoffsets, index, input)?);
+        Ok(Compilation::new(
+            functions,
+            import_trampolines,
+            PrimaryMap::new(),
+            PrimaryMap::new(),
+            None,
+        ))
+    }
+}
+
+impl SinglepassCompiler {
+    /// Generate the trampolines calling the imported functions, as the
+    /// custom sections of a compilation.
+    fn import_trampolines(
+        &self,
+        module: &ModuleInfo,
+        vmoffsets: &VMOffsets,
+    ) -> PrimaryMap<SectionIndex, CustomSection> {
+        (0..module.num_imported_functions)
+            .map(FunctionIndex::new)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|i| {
+                gen_import_call_trampoline(vmoffsets, i, &module.signatures[module.functions[i]])
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    /// Compile the body of a single function.
+    fn compile_function_body(
+        &self,
+        compile_info: &CompileModuleInfo,
+        vmoffsets: &VMOffsets,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'_>,
+    ) -> Result<CompiledFunction, CompileError> {
+        let middleware_chain = self
+            .config
+            .middlewares
+            .generate_function_middleware_chain(index);
+        let mut reader = MiddlewareBinaryReader::new_with_offset(input.data, input.module_offset);
+        reader.set_middleware_chain(middleware_chain);
+
+        // This local list excludes arguments.
+        let mut locals = vec![];
+        let num_locals = reader.read_local_count()?;
+        for _ in 0..num_locals {
+            let (count, ty) = reader.read_local_decl()?;
+            for _ in 0..count {
+                locals.push(ty);
+            }
+        }
+
+        let mut generator = FuncGen::new(
+            &compile_info.module,
+            &self.config,
+            vmoffsets,
+            &compile_info.memory_styles,
+            &compile_info.table_styles,
+            index,
+            &locals,
+        )
+        .map_err(to_compile_error)?;
+
+        while generator.has_control_frames() {
+            let op = reader.read_operator()?;
+            // The other instructions of the exception handling proposal can
+            // only appear inside a `try` block.
+            if let Operator::Try { .. } | Operator::Throw { .. } | Operator::Rethrow { .. } = op {
+                return Err(CompileError::UnsupportedFeature("exceptions".to_string()));
+            }
+            generator.set_srcloc(reader.current_operator_offset() as u32);
+            generator.feed_operator(op).map_err(to_compile_error)?;
+        }
+
+        Ok(generator.finalize(input))
+    }
+}
+
+/// Check that Singlepass can compile the module for `target`.
+fn check_supported(target: &Target, compile_info: &CompileModuleInfo) -> Result<(), CompileError> {
+    if target.triple().operating_system == OperatingSystem::Windows {
+        return Err(CompileError::UnsupportedTarget(
+            OperatingSystem::Windows.to_string(),
+        ));
+    }
+    if let Architecture::X86_32(arch) = target.triple().architecture {
+        return Err(CompileError::UnsupportedTarget(arch.to_string()));
+    }
+    if !compile_info.module.tags.is_empty() {
+        return Err(CompileError::UnsupportedFeature("exceptions".to_string()));
+    }
+    Ok(())
 }
 
 trait ToCompileError {
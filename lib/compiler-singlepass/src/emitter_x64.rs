@@ -198,6 +198,148 @@ pub trait Emitter {
     fn emit_vblendvps(&mut self, src1: XMM, src2: XMMOrMemory, mask: XMM, dst: XMM);
     fn emit_vblendvpd(&mut self, src1: XMM, src2: XMMOrMemory, mask: XMM, dst: XMM);
 
+    // Packed integer and floating point operations on `v128` values.
+    fn emit_vpaddb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpaddsb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddsw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddusb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpaddusw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubsb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubsw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubusb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsubusw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpmullw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmulld(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpminsb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminsw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminub(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminuw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpminud(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxsb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxsw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxsd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxub(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxuw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaxud(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpavgb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpavgw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpand(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpandn(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpor(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpxor(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpcmpeqb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpeqw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpeqd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpeqq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpgtb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpgtw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpgtd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpcmpgtq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpsllw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpslld(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsllq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsrlw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsrld(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsrlq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsraw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpsrad(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vpmuludq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpmaddwd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_psllw_imm8(&mut self, imm: u8, dst: XMM);
+    fn emit_psllq_imm8(&mut self, imm: u8, dst: XMM);
+    fn emit_psrlw_imm8(&mut self, imm: u8, dst: XMM);
+    fn emit_psrld_imm8(&mut self, imm: u8, dst: XMM);
+    fn emit_psrlq_imm8(&mut self, imm: u8, dst: XMM);
+    fn emit_psrad_imm8(&mut self, imm: u8, dst: XMM);
+
+    fn emit_vpshufb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpunpcklqdq(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpunpcklbw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpunpckhbw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpacksswb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpackuswb(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpackssdw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vpackusdw(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vaddps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vaddpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vsubps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vsubpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vmulps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vmulpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vdivps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vdivpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vminps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vminpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vmaxps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vmaxpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_vcmpeqps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpeqpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpneqps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpneqpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpltps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpltpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpleps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmplepd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpunordps(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+    fn emit_vcmpunordpd(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM);
+
+    fn emit_roundps_nearest(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_roundps_floor(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_roundps_ceil(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_roundps_trunc(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_roundpd_nearest(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_roundpd_floor(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_roundpd_ceil(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_roundpd_trunc(&mut self, src: XMMOrMemory, dst: XMM);
+
+    fn emit_sqrtps(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_sqrtpd(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_cvtdq2ps(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_cvttps2dq(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pmovsxbw(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pmovzxbw(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pmovsxwd(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pmovzxwd(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pmovsxdq(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pmovzxdq(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pabsb(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pabsw(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_pabsd(&mut self, src: XMMOrMemory, dst: XMM);
+    fn emit_ptest(&mut self, src: XMMOrMemory, dst: XMM);
+
+    fn emit_movdqu(&mut self, src: XMMOrMemory, dst: XMMOrMemory);
+    fn emit_pshufd(&mut self, src: XMMOrMemory, imm: u8, dst: XMM);
+    fn emit_pinsrb(&mut self, src: GPR, lane: u8, dst: XMM);
+    fn emit_pinsrw(&mut self, src: GPR, lane: u8, dst: XMM);
+    fn emit_pinsrd(&mut self, src: GPR, lane: u8, dst: XMM);
+    fn emit_pinsrq(&mut self, src: GPR, lane: u8, dst: XMM);
+    fn emit_pextrb(&mut self, src: XMM, lane: u8, dst: GPR);
+    fn emit_pextrw(&mut self, src: XMM, lane: u8, dst: GPR);
+    fn emit_pextrd(&mut self, src: XMM, lane: u8, dst: GPR);
+    fn emit_pextrq(&mut self, src: XMM, lane: u8, dst: GPR);
+    fn emit_pmovmskb(&mut self, src: XMM, dst: GPR);
+    fn emit_movmskps(&mut self, src: XMM, dst: GPR);
+    fn emit_movmskpd(&mut self, src: XMM, dst: GPR);
+
     fn emit_test_gpr_64(&mut self, reg: GPR);
 
     fn emit_ud2(&mut self);
@@ -614,6 +756,17 @@ macro_rules! avx_i2f_32_fn {
     }
 }
 
+macro_rules! sse_fn {
+    ($ins:ident, $name:ident) => {
+        fn $name(&mut self, src: XMMOrMemory, dst: XMM) {
+            match src {
+                XMMOrMemory::XMM(x) => dynasm!(self ; $ins Rx((dst as u8)), Rx((x as u8))),
+                XMMOrMemory::Memory(base, disp) => dynasm!(self ; $ins Rx((dst as u8)), [Rq((base as u8)) + disp]),
+            }
+        }
+    }
+}
+
 macro_rules! avx_round_fn {
     ($ins:ident, $name:ident, $mode:expr) => {
         fn $name(&mut self, src1: XMM, src2: XMMOrMemory, dst: XMM) {
@@ -625,6 +778,25 @@ macro_rules! avx_round_fn {
     }
 }
 
+macro_rules! sse_round_fn {
+    ($ins:ident, $name:ident, $mode:expr) => {
+        fn $name(&mut self, src: XMMOrMemory, dst: XMM) {
+            match src {
+                XMMOrMemory::XMM(x) => dynasm!(self ; $ins Rx((dst as u8)), Rx((x as u8)), $mode),
+                XMMOrMemory::Memory(base, disp) => dynasm!(self ; $ins Rx((dst as u8)), [Rq((base as u8)) + disp], $mode),
+            }
+        }
+    }
+}
+
+macro_rules! sse_shift_imm8_fn {
+    ($ins:ident, $name:ident) => {
+        fn $name(&mut self, imm: u8, dst: XMM) {
+            dynasm!(self ; $ins Rx((dst as u8)), imm as i8)
+        }
+    }
+}
+
 impl Emitter for Assembler {
     type Label = DynamicLabel;
     type Offset = AssemblyOffset;
@@ -1315,6 +1487,193 @@ impl Emitter for Assembler {
         }
     }
 
+    avx_fn!(vpaddb, emit_vpaddb);
+    avx_fn!(vpaddw, emit_vpaddw);
+    avx_fn!(vpaddd, emit_vpaddd);
+    avx_fn!(vpaddq, emit_vpaddq);
+    avx_fn!(vpsubb, emit_vpsubb);
+    avx_fn!(vpsubw, emit_vpsubw);
+    avx_fn!(vpsubd, emit_vpsubd);
+    avx_fn!(vpsubq, emit_vpsubq);
+
+    avx_fn!(vpaddsb, emit_vpaddsb);
+    avx_fn!(vpaddsw, emit_vpaddsw);
+    avx_fn!(vpaddusb, emit_vpaddusb);
+    avx_fn!(vpaddusw, emit_vpaddusw);
+    avx_fn!(vpsubsb, emit_vpsubsb);
+    avx_fn!(vpsubsw, emit_vpsubsw);
+    avx_fn!(vpsubusb, emit_vpsubusb);
+    avx_fn!(vpsubusw, emit_vpsubusw);
+
+    avx_fn!(vpmullw, emit_vpmullw);
+    avx_fn!(vpmulld, emit_vpmulld);
+
+    avx_fn!(vpminsb, emit_vpminsb);
+    avx_fn!(vpminsw, emit_vpminsw);
+    avx_fn!(vpminsd, emit_vpminsd);
+    avx_fn!(vpminub, emit_vpminub);
+    avx_fn!(vpminuw, emit_vpminuw);
+    avx_fn!(vpminud, emit_vpminud);
+    avx_fn!(vpmaxsb, emit_vpmaxsb);
+    avx_fn!(vpmaxsw, emit_vpmaxsw);
+    avx_fn!(vpmaxsd, emit_vpmaxsd);
+    avx_fn!(vpmaxub, emit_vpmaxub);
+    avx_fn!(vpmaxuw, emit_vpmaxuw);
+    avx_fn!(vpmaxud, emit_vpmaxud);
+
+    avx_fn!(vpavgb, emit_vpavgb);
+    avx_fn!(vpavgw, emit_vpavgw);
+
+    avx_fn!(vpand, emit_vpand);
+    avx_fn!(vpandn, emit_vpandn);
+    avx_fn!(vpor, emit_vpor);
+    avx_fn!(vpxor, emit_vpxor);
+
+    avx_fn!(vpcmpeqb, emit_vpcmpeqb);
+    avx_fn!(vpcmpeqw, emit_vpcmpeqw);
+    avx_fn!(vpcmpeqd, emit_vpcmpeqd);
+    avx_fn!(vpcmpeqq, emit_vpcmpeqq);
+    avx_fn!(vpcmpgtb, emit_vpcmpgtb);
+    avx_fn!(vpcmpgtw, emit_vpcmpgtw);
+    avx_fn!(vpcmpgtd, emit_vpcmpgtd);
+    avx_fn!(vpcmpgtq, emit_vpcmpgtq);
+
+    avx_fn!(vpsllw, emit_vpsllw);
+    avx_fn!(vpslld, emit_vpslld);
+    avx_fn!(vpsllq, emit_vpsllq);
+    avx_fn!(vpsrlw, emit_vpsrlw);
+    avx_fn!(vpsrld, emit_vpsrld);
+    avx_fn!(vpsrlq, emit_vpsrlq);
+    avx_fn!(vpsraw, emit_vpsraw);
+    avx_fn!(vpsrad, emit_vpsrad);
+
+    avx_fn!(vpmuludq, emit_vpmuludq);
+    avx_fn!(vpmaddwd, emit_vpmaddwd);
+
+    sse_shift_imm8_fn!(psllw, emit_psllw_imm8);
+    sse_shift_imm8_fn!(psllq, emit_psllq_imm8);
+    sse_shift_imm8_fn!(psrlw, emit_psrlw_imm8);
+    sse_shift_imm8_fn!(psrld, emit_psrld_imm8);
+    sse_shift_imm8_fn!(psrlq, emit_psrlq_imm8);
+    sse_shift_imm8_fn!(psrad, emit_psrad_imm8);
+
+    avx_fn!(vpshufb, emit_vpshufb);
+    avx_fn!(vpunpcklqdq, emit_vpunpcklqdq);
+    avx_fn!(vpunpcklbw, emit_vpunpcklbw);
+    avx_fn!(vpunpckhbw, emit_vpunpckhbw);
+    avx_fn!(vpacksswb, emit_vpacksswb);
+    avx_fn!(vpackuswb, emit_vpackuswb);
+    avx_fn!(vpackssdw, emit_vpackssdw);
+    avx_fn!(vpackusdw, emit_vpackusdw);
+
+    avx_fn!(vaddps, emit_vaddps);
+    avx_fn!(vaddpd, emit_vaddpd);
+    avx_fn!(vsubps, emit_vsubps);
+    avx_fn!(vsubpd, emit_vsubpd);
+    avx_fn!(vmulps, emit_vmulps);
+    avx_fn!(vmulpd, emit_vmulpd);
+    avx_fn!(vdivps, emit_vdivps);
+    avx_fn!(vdivpd, emit_vdivpd);
+    avx_fn!(vminps, emit_vminps);
+    avx_fn!(vminpd, emit_vminpd);
+    avx_fn!(vmaxps, emit_vmaxps);
+    avx_fn!(vmaxpd, emit_vmaxpd);
+
+    avx_fn!(vcmpeqps, emit_vcmpeqps);
+    avx_fn!(vcmpeqpd, emit_vcmpeqpd);
+    avx_fn!(vcmpneqps, emit_vcmpneqps);
+    avx_fn!(vcmpneqpd, emit_vcmpneqpd);
+    avx_fn!(vcmpltps, emit_vcmpltps);
+    avx_fn!(vcmpltpd, emit_vcmpltpd);
+    avx_fn!(vcmpleps, emit_vcmpleps);
+    avx_fn!(vcmplepd, emit_vcmplepd);
+    avx_fn!(vcmpunordps, emit_vcmpunordps);
+    avx_fn!(vcmpunordpd, emit_vcmpunordpd);
+
+    sse_round_fn!(roundps, emit_roundps_nearest, 0);
+    sse_round_fn!(roundps, emit_roundps_floor, 1);
+    sse_round_fn!(roundps, emit_roundps_ceil, 2);
+    sse_round_fn!(roundps, emit_roundps_trunc, 3);
+    sse_round_fn!(roundpd, emit_roundpd_nearest, 0);
+    sse_round_fn!(roundpd, emit_roundpd_floor, 1);
+    sse_round_fn!(roundpd, emit_roundpd_ceil, 2);
+    sse_round_fn!(roundpd, emit_roundpd_trunc, 3);
+
+    sse_fn!(sqrtps, emit_sqrtps);
+    sse_fn!(sqrtpd, emit_sqrtpd);
+    sse_fn!(cvtdq2ps, emit_cvtdq2ps);
+    sse_fn!(cvttps2dq, emit_cvttps2dq);
+    sse_fn!(pmovsxbw, emit_pmovsxbw);
+    sse_fn!(pmovzxbw, emit_pmovzxbw);
+    sse_fn!(pmovsxwd, emit_pmovsxwd);
+    sse_fn!(pmovzxwd, emit_pmovzxwd);
+    sse_fn!(pmovsxdq, emit_pmovsxdq);
+    sse_fn!(pmovzxdq, emit_pmovzxdq);
+    sse_fn!(pabsb, emit_pabsb);
+    sse_fn!(pabsw, emit_pabsw);
+    sse_fn!(pabsd, emit_pabsd);
+    sse_fn!(ptest, emit_ptest);
+
+    fn emit_movdqu(&mut self, src: XMMOrMemory, dst: XMMOrMemory) {
+        match (src, dst) {
+            (XMMOrMemory::XMM(src), XMMOrMemory::XMM(dst)) => {
+                dynasm!(self ; movdqu Rx(dst as u8), Rx(src as u8))
+            }
+            (XMMOrMemory::Memory(base, disp), XMMOrMemory::XMM(dst)) => {
+                dynasm!(self ; movdqu Rx(dst as u8), [Rq(base as u8) + disp])
+            }
+            (XMMOrMemory::XMM(src), XMMOrMemory::Memory(base, disp)) => {
+                dynasm!(self ; movdqu [Rq(base as u8) + disp], Rx(src as u8))
+            }
+            _ => panic!("singlepass can't emit MOVDQU {:?} {:?}", src, dst),
+        };
+    }
+
+    fn emit_pshufd(&mut self, src: XMMOrMemory, imm: u8, dst: XMM) {
+        match src {
+            XMMOrMemory::XMM(x) => dynasm!(self ; pshufd Rx(dst as u8), Rx(x as u8), imm as i8),
+            XMMOrMemory::Memory(base, disp) => {
+                dynasm!(self ; pshufd Rx(dst as u8), [Rq(base as u8) + disp], imm as i8)
+            }
+        }
+    }
+
+    fn emit_pinsrb(&mut self, src: GPR, lane: u8, dst: XMM) {
+        dynasm!(self ; pinsrb Rx(dst as u8), Rd(src as u8), lane as i8);
+    }
+    fn emit_pinsrw(&mut self, src: GPR, lane: u8, dst: XMM) {
+        dynasm!(self ; pinsrw Rx(dst as u8), Rd(src as u8), lane as i8);
+    }
+    fn emit_pinsrd(&mut self, src: GPR, lane: u8, dst: XMM) {
+        dynasm!(self ; pinsrd Rx(dst as u8), Rd(src as u8), lane as i8);
+    }
+    fn emit_pinsrq(&mut self, src: GPR, lane: u8, dst: XMM) {
+        dynasm!(self ; pinsrq Rx(dst as u8), Rq(src as u8), lane as i8);
+    }
+
+    fn emit_pextrb(&mut self, src: XMM, lane: u8, dst: GPR) {
+        dynasm!(self ; pextrb Rd(dst as u8), Rx(src as u8), lane as i8);
+    }
+    fn emit_pextrw(&mut self, src: XMM, lane: u8, dst: GPR) {
+        dynasm!(self ; pextrw Rd(dst as u8), Rx(src as u8), lane as i8);
+    }
+    fn emit_pextrd(&mut self, src: XMM, lane: u8, dst: GPR) {
+        dynasm!(self ; pextrd Rd(dst as u8), Rx(src as u8), lane as i8);
+    }
+    fn emit_pextrq(&mut self, src: XMM, lane: u8, dst: GPR) {
+        dynasm!(self ; pextrq Rq(dst as u8), Rx(src as u8), lane as i8);
+    }
+
+    fn emit_pmovmskb(&mut self, src: XMM, dst: GPR) {
+        dynasm!(self ; pmovmskb Rd(dst as u8), Rx(src as u8));
+    }
+    fn emit_movmskps(&mut self, src: XMM, dst: GPR) {
+        dynasm!(self ; movmskps Rd(dst as u8), Rx(src as u8));
+    }
+    fn emit_movmskpd(&mut self, src: XMM, dst: GPR) {
+        dynasm!(self ; movmskpd Rd(dst as u8), Rx(src as u8));
+    }
+
     fn emit_ucomiss(&mut self, src: XMMOrMemory, dst: XMM) {
         match src {
             XMMOrMemory::XMM(x) => dynasm!(self ; ucomiss Rx(dst as u8), Rx(x as u8)),
@@ -14,8 +14,12 @@ struct MachineStackOffset(usize);
 pub struct Machine {
     used_gprs: HashSet<GPR>,
     used_xmms: HashSet<XMM>,
+    /// Locations currently holding a `v128` value. Stack slots in this set are 16 bytes wide.
+    v128_locations: HashSet<Location>,
     stack_offset: MachineStackOffset,
     save_area_offset: Option<MachineStackOffset>,
+    /// Callee-saved registers holding locals, in the order they are saved.
+    saved_local_gprs: Vec<GPR>,
     pub state: MachineState,
     pub(crate) track_state: bool,
 }
@@ -25,8 +29,10 @@ impl Machine {
         Machine {
             used_gprs: HashSet::new(),
             used_xmms: HashSet::new(),
+            v128_locations: HashSet::new(),
             stack_offset: MachineStackOffset(0),
             save_area_offset: None,
+            saved_local_gprs: vec![],
             state: new_machine_state(),
             track_state: true,
         }
@@ -44,6 +50,20 @@ impl Machine {
         self.used_xmms.iter().cloned().collect()
     }
 
+    /// Returns whether `loc` currently holds a `v128` value.
+    pub fn is_v128(&self, loc: Location) -> bool {
+        self.v128_locations.contains(&loc)
+    }
+
+    /// Returns the size in bytes of the stack slot at `loc`.
+    fn stack_slot_size(&self, loc: Location) -> usize {
+        if self.is_v128(loc) {
+            16
+        } else {
+            8
+        }
+    }
+
     pub fn get_vmctx_reg() -> GPR {
         GPR::R15
     }
@@ -155,17 +175,25 @@ impl Machine {
         for (ty, mv) in tys {
             let loc = match *ty {
                 WpType::F32 | WpType::F64 => self.pick_xmm().map(Location::XMM),
-                WpType::I32 | WpType::I64 => self.pick_gpr().map(Location::GPR),
+                // `v128` values are always kept on the stack.
+                WpType::V128 => None,
+                WpType::I32 | WpType::I64 | WpType::FuncRef | WpType::ExternRef => {
+                    self.pick_gpr().map(Location::GPR)
+                }
                 _ => unreachable!(),
             };
+            let size = if *ty == WpType::V128 { 16 } else { 8 };
 
             let loc = if let Some(x) = loc {
                 x
             } else {
-                self.stack_offset.0 += 8;
-                delta_stack_offset += 8;
+                self.stack_offset.0 += size;
+                delta_stack_offset += size;
                 Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32))
             };
+            if *ty == WpType::V128 {
+                self.v128_locations.insert(loc);
+            }
             if let Location::GPR(x) = loc {
                 self.used_gprs.insert(x);
                 self.state.register_values[X64Register::GPR(x).to_index().0] = mv.clone();
@@ -174,6 +202,9 @@ impl Machine {
                 self.state.register_values[X64Register::XMM(x).to_index().0] = mv.clone();
             } else {
                 self.state.stack_values.push(mv.clone());
+                if size == 16 {
+                    self.state.stack_values.push(MachineValue::Undefined);
+                }
             }
             self.state.wasm_stack.push(WasmAbstractValue::Runtime);
             ret.push(loc);
@@ -194,6 +225,105 @@ impl Machine {
         ret
     }
 
+    /// Acquires locations on the machine stack, never in registers.
+    ///
+    /// Branches write their values to these locations, so they must not
+    /// be reused by the values pushed in between.
+    pub fn acquire_stack_locations<E: Emitter>(
+        &mut self,
+        assembler: &mut E,
+        tys: &[(WpType, MachineValue)],
+    ) -> SmallVec<[Location; 1]> {
+        let mut ret = smallvec![];
+        let mut delta_stack_offset: usize = 0;
+        for (ty, mv) in tys {
+            let size = if *ty == WpType::V128 { 16 } else { 8 };
+            self.stack_offset.0 += size;
+            delta_stack_offset += size;
+            self.state.stack_values.push(mv.clone());
+            if size == 16 {
+                self.state.stack_values.push(MachineValue::Undefined);
+            }
+            self.state.wasm_stack.push(WasmAbstractValue::Runtime);
+            let loc = Location::Memory(GPR::RBP, -(self.stack_offset.0 as i32));
+            if size == 16 {
+                self.v128_locations.insert(loc);
+            }
+            ret.push(loc);
+        }
+
+        if delta_stack_offset != 0 {
+            assembler.emit_sub(
+                Size::S64,
+                Location::Imm32(delta_stack_offset as u32),
+                Location::GPR(GPR::RSP),
+            );
+        }
+        ret
+    }
+
+    /// Acquires stack locations of types `tys` under the values at `locs`,
+    /// which must be the values on top of the machine stack.
+    ///
+    /// Returns the new locations followed by the ones the values at `locs`
+    /// must be moved to, in the same order.
+    pub fn insert_stack_locations<E: Emitter>(
+        &mut self,
+        assembler: &mut E,
+        locs: &[Location],
+        tys: &[(WpType, MachineValue)],
+    ) -> (SmallVec<[Location; 1]>, SmallVec<[Location; 8]>) {
+        let sizes: SmallVec<[usize; 8]> =
+            locs.iter().map(|loc| self.stack_slot_size(*loc)).collect();
+        let values_size: usize = sizes.iter().sum();
+        for loc in locs {
+            self.v128_locations.remove(loc);
+        }
+
+        let mut depth = self.stack_offset.0 - values_size;
+        let mut slots = smallvec![];
+        let mut values = smallvec![];
+        let mut stack_values = vec![];
+        let mut delta_stack_offset: usize = 0;
+        for (ty, mv) in tys {
+            let size = if *ty == WpType::V128 { 16 } else { 8 };
+            depth += size;
+            delta_stack_offset += size;
+            stack_values.push(mv.clone());
+            if size == 16 {
+                stack_values.push(MachineValue::Undefined);
+            }
+            self.state.wasm_stack.push(WasmAbstractValue::Runtime);
+            let loc = Location::Memory(GPR::RBP, -(depth as i32));
+            if size == 16 {
+                self.v128_locations.insert(loc);
+            }
+            slots.push(loc);
+        }
+        for size in sizes {
+            depth += size;
+            let loc = Location::Memory(GPR::RBP, -(depth as i32));
+            if size == 16 {
+                self.v128_locations.insert(loc);
+            }
+            values.push(loc);
+        }
+        self.stack_offset.0 = depth;
+
+        // The machine state of the values moves along with them.
+        let at = self.state.stack_values.len() - values_size / 8;
+        self.state.stack_values.splice(at..at, stack_values);
+
+        if delta_stack_offset != 0 {
+            assembler.emit_sub(
+                Size::S64,
+                Location::Imm32(delta_stack_offset as u32),
+                Location::GPR(GPR::RSP),
+            );
+        }
+        (slots, values)
+    }
+
     /// Releases locations used for stack value.
     pub fn release_locations<E: Emitter>(&mut self, assembler: &mut E, locs: &[Location]) {
         let mut delta_stack_offset: usize = 0;
@@ -207,6 +337,7 @@ impl Machine {
                 }
                 Location::XMM(ref x) => {
                     assert_eq!(self.used_xmms.remove(x), true);
+                    self.v128_locations.remove(loc);
                     self.state.register_values[X64Register::XMM(*x).to_index().0] =
                         MachineValue::Undefined;
                 }
@@ -218,9 +349,13 @@ impl Machine {
                     if offset != self.stack_offset.0 {
                         unreachable!();
                     }
-                    self.stack_offset.0 -= 8;
-                    delta_stack_offset += 8;
-                    self.state.stack_values.pop().unwrap();
+                    let size = self.stack_slot_size(*loc);
+                    self.v128_locations.remove(loc);
+                    self.stack_offset.0 -= size;
+                    delta_stack_offset += size;
+                    for _ in 0..size / 8 {
+                        self.state.stack_values.pop().unwrap();
+                    }
                 }
                 _ => {}
             }
@@ -246,6 +381,7 @@ impl Machine {
                 }
                 Location::XMM(ref x) => {
                     assert_eq!(self.used_xmms.remove(x), true);
+                    self.v128_locations.remove(loc);
                     self.state.register_values[X64Register::XMM(*x).to_index().0] =
                         MachineValue::Undefined;
                 }
@@ -271,9 +407,13 @@ impl Machine {
                 if offset != self.stack_offset.0 {
                     unreachable!();
                 }
-                self.stack_offset.0 -= 8;
-                delta_stack_offset += 8;
-                self.state.stack_values.pop().unwrap();
+                let size = self.stack_slot_size(*loc);
+                self.v128_locations.remove(loc);
+                self.stack_offset.0 -= size;
+                delta_stack_offset += size;
+                for _ in 0..size / 8 {
+                    self.state.stack_values.pop().unwrap();
+                }
             }
             // Wasm state popping is deferred to `release_locations_only_osr_state`.
         }
@@ -310,8 +450,9 @@ impl Machine {
                 if offset != stack_offset {
                     unreachable!();
                 }
-                stack_offset -= 8;
-                delta_stack_offset += 8;
+                let size = self.stack_slot_size(*loc);
+                stack_offset -= size;
+                delta_stack_offset += size;
             }
         }
 
@@ -327,9 +468,13 @@ impl Machine {
     pub fn init_locals<E: Emitter>(
         &mut self,
         a: &mut E,
-        n: usize,
-        n_params: usize,
+        local_types: &[WpType],
+        params: &[Location],
+        vmctx: GPR,
     ) -> Vec<Location> {
+        let n = local_types.len();
+        let n_params = params.len();
+
         // Determine whether a local should be allocated on the stack.
         fn is_local_on_stack(idx: usize) -> bool {
             idx > 3
@@ -369,9 +514,28 @@ impl Machine {
         // Total size of callee saved registers.
         let callee_saved_regs_size = static_area_size;
 
+        // `v128` locals don't fit in the slots above, so they get 16-byte
+        // slots of their own, right under the callee-saved registers.
+        let mut v128_area_size = 0;
+        let v128_locations: Vec<Option<Location>> = local_types
+            .iter()
+            .map(|ty| {
+                if *ty == WpType::V128 {
+                    v128_area_size += 16;
+                    Some(Location::Memory(
+                        GPR::RBP,
+                        -((callee_saved_regs_size + v128_area_size) as i32),
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        static_area_size += v128_area_size;
+
         // Now we can determine concrete locations for locals.
-        let locations: Vec<Location> = (0..n)
-            .map(|i| get_local_location(i, callee_saved_regs_size))
+        let mut locations: Vec<Location> = (0..n)
+            .map(|i| get_local_location(i, callee_saved_regs_size + v128_area_size))
             .collect();
 
         // Add size of locals on stack.
@@ -387,6 +551,7 @@ impl Machine {
         // Save callee-saved registers.
         for loc in locations.iter() {
             if let Location::GPR(x) = *loc {
+                self.saved_local_gprs.push(x);
                 self.stack_offset.0 += 8;
                 a.emit_mov(
                     Size::S64,
@@ -414,14 +579,23 @@ impl Machine {
         self.save_area_offset = Some(MachineStackOffset(self.stack_offset.0));
 
         // Save location information for locals.
+        for (i, v128_loc) in v128_locations.iter().enumerate() {
+            if v128_loc.is_some() {
+                self.state.stack_values.push(MachineValue::WasmLocal(i));
+                self.state.stack_values.push(MachineValue::Undefined);
+            }
+        }
         for (i, loc) in locations.iter().enumerate() {
+            let value = match v128_locations[i] {
+                Some(_) => MachineValue::Undefined,
+                None => MachineValue::WasmLocal(i),
+            };
             match *loc {
                 Location::GPR(x) => {
-                    self.state.register_values[X64Register::GPR(x).to_index().0] =
-                        MachineValue::WasmLocal(i);
+                    self.state.register_values[X64Register::GPR(x).to_index().0] = value;
                 }
                 Location::Memory(_, _) => {
-                    self.state.stack_values.push(MachineValue::WasmLocal(i));
+                    self.state.stack_values.push(value);
                 }
                 _ => unreachable!(),
             }
@@ -430,32 +604,52 @@ impl Machine {
         // Load in-register parameters into the allocated locations.
         // Locals are allocated on the stack from higher address to lower address,
         // so we won't skip the stack guard page here.
-        for i in 0..n_params {
-            let loc = Self::get_param_location(i + 1);
-            match loc {
-                Location::GPR(_) => {
-                    a.emit_mov(Size::S64, loc, locations[i]);
+        for (i, &loc) in params.iter().enumerate() {
+            match (loc, locations[i], v128_locations[i]) {
+                (Location::XMM(x), _, Some(Location::Memory(base, offset))) => {
+                    a.emit_movdqu(XMMOrMemory::XMM(x), XMMOrMemory::Memory(base, offset));
                 }
-                Location::Memory(_, _) => match locations[i] {
-                    Location::GPR(_) => {
-                        a.emit_mov(Size::S64, loc, locations[i]);
-                    }
-                    Location::Memory(_, _) => {
-                        a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
-                        a.emit_mov(Size::S64, Location::GPR(GPR::RAX), locations[i]);
+                (
+                    Location::Memory(src_base, src_offset),
+                    _,
+                    Some(Location::Memory(base, offset)),
+                ) => {
+                    for half in &[0, 8] {
+                        a.emit_mov(
+                            Size::S64,
+                            Location::Memory(src_base, src_offset + half),
+                            Location::GPR(GPR::RAX),
+                        );
+                        a.emit_mov(
+                            Size::S64,
+                            Location::GPR(GPR::RAX),
+                            Location::Memory(base, offset + half),
+                        );
                     }
-                    _ => unreachable!(),
-                },
-                _ => unreachable!(),
+                }
+                (Location::Memory(_, _), dst @ Location::Memory(_, _), _) => {
+                    a.emit_mov(Size::S64, loc, Location::GPR(GPR::RAX));
+                    a.emit_mov(Size::S64, Location::GPR(GPR::RAX), dst);
+                }
+                (_, dst, _) => a.emit_mov(Size::S64, loc, dst),
+            }
+        }
+
+        // Initialize the `v128` locals to zero.
+        for v128_loc in &v128_locations[n_params..] {
+            if let Some(Location::Memory(base, offset)) = *v128_loc {
+                for half in &[0, 8] {
+                    a.emit_mov(
+                        Size::S64,
+                        Location::Imm32(0),
+                        Location::Memory(base, offset + half),
+                    );
+                }
             }
         }
 
         // Load vmctx into R15.
-        a.emit_mov(
-            Size::S64,
-            Self::get_param_location(0),
-            Location::GPR(GPR::R15),
-        );
+        a.emit_mov(Size::S64, Location::GPR(vmctx), Location::GPR(GPR::R15));
 
         // Stack probe.
         //
@@ -495,10 +689,21 @@ impl Machine {
         // Add the size of all locals allocated to stack.
         self.stack_offset.0 += static_area_size - callee_saved_regs_size;
 
+        for (loc, v128_loc) in locations.iter_mut().zip(v128_locations) {
+            if let Some(v128_loc) = v128_loc {
+                self.v128_locations.insert(v128_loc);
+                *loc = v128_loc;
+            }
+        }
+
         locations
     }
 
-    pub fn finalize_locals<E: Emitter>(&mut self, a: &mut E, locations: &[Location]) {
+    /// Restores the callee-saved registers saved by `init_locals`.
+    ///
+    /// The locations of the locals may have been replaced since, so the
+    /// registers are the ones recorded by `init_locals`.
+    pub fn finalize_locals<E: Emitter>(&mut self, a: &mut E) {
         // Unwind stack to the "save area".
         a.emit_lea(
             Size::S64,
@@ -513,22 +718,8 @@ impl Machine {
         a.emit_pop(Size::S64, Location::GPR(GPR::R15));
 
         // Restore callee-saved registers.
-        for loc in locations.iter().rev() {
-            if let Location::GPR(_) = *loc {
-                a.emit_pop(Size::S64, *loc);
-            }
-        }
-    }
-
-    pub fn get_param_location(idx: usize) -> Location {
-        match idx {
-            0 => Location::GPR(GPR::RDI),
-            1 => Location::GPR(GPR::RSI),
-            2 => Location::GPR(GPR::RDX),
-            3 => Location::GPR(GPR::RCX),
-            4 => Location::GPR(GPR::R8),
-            5 => Location::GPR(GPR::R9),
-            _ => Location::Memory(GPR::RBP, (16 + (idx - 6) * 8) as i32),
+        for gpr in self.saved_local_gprs.iter().rev() {
+            a.emit_pop(Size::S64, Location::GPR(*gpr));
         }
     }
 }
@@ -552,4 +743,60 @@ mod test {
 
         machine.release_locations_keep_state(&mut assembler, &locs);
     }
+
+    #[test]
+    fn test_acquire_stack_locations() {
+        let mut machine = Machine::new();
+        let mut assembler = Assembler::new().unwrap();
+        let regs = machine.acquire_locations(
+            &mut assembler,
+            &[(WpType::I32, MachineValue::Undefined)],
+            false,
+        );
+        let slots = machine.acquire_stack_locations(
+            &mut assembler,
+            &[
+                (WpType::I64, MachineValue::Undefined),
+                (WpType::F64, MachineValue::Undefined),
+            ],
+        );
+        assert!(matches!(regs[0], Location::GPR(_)));
+        assert_eq!(
+            &slots[..],
+            &[
+                Location::Memory(GPR::RBP, -8),
+                Location::Memory(GPR::RBP, -16)
+            ]
+        );
+
+        machine.release_locations(&mut assembler, &slots);
+        machine.release_locations(&mut assembler, &regs);
+        assert_eq!(machine.get_stack_offset(), 0);
+    }
+
+    #[test]
+    fn test_acquire_v128_stack_locations() {
+        let mut machine = Machine::new();
+        let mut assembler = Assembler::new().unwrap();
+        let slots = machine.acquire_stack_locations(
+            &mut assembler,
+            &[
+                (WpType::V128, MachineValue::Undefined),
+                (WpType::I32, MachineValue::Undefined),
+            ],
+        );
+        assert_eq!(
+            &slots[..],
+            &[
+                Location::Memory(GPR::RBP, -16),
+                Location::Memory(GPR::RBP, -24)
+            ]
+        );
+        assert!(machine.is_v128(slots[0]));
+        assert!(!machine.is_v128(slots[1]));
+
+        machine.release_locations(&mut assembler, &slots);
+        assert_eq!(machine.get_stack_offset(), 0);
+        assert!(!machine.is_v128(slots[0]));
+    }
 }
@@ -1,6 +1,7 @@
 //! X64 structures.
 
 use crate::common_decl::{MachineState, MachineValue, RegisterIndex};
+use smallvec::SmallVec;
 use std::collections::BTreeMap;
 use wasmer_types::Type;
 
@@ -81,6 +82,31 @@ pub enum XMM {
     XMM15,
 }
 
+impl XMM {
+    /// Returns the XMM register `index`.
+    pub fn from_index(index: usize) -> Self {
+        static XMMS: &[XMM] = &[
+            XMM::XMM0,
+            XMM::XMM1,
+            XMM::XMM2,
+            XMM::XMM3,
+            XMM::XMM4,
+            XMM::XMM5,
+            XMM::XMM6,
+            XMM::XMM7,
+            XMM::XMM8,
+            XMM::XMM9,
+            XMM::XMM10,
+            XMM::XMM11,
+            XMM::XMM12,
+            XMM::XMM13,
+            XMM::XMM14,
+            XMM::XMM15,
+        ];
+        XMMS[index]
+    }
+}
+
 /// A machine register under the x86-64 architecture.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum X64Register {
@@ -99,6 +125,31 @@ impl X64Register {
         }
     }
 
+    /// Converts the register to its DWARF regnum.
+    pub fn to_dwarf_regnum(&self) -> u16 {
+        match *self {
+            X64Register::GPR(x) => match x {
+                GPR::RAX => 0,
+                GPR::RDX => 1,
+                GPR::RCX => 2,
+                GPR::RBX => 3,
+                GPR::RSI => 4,
+                GPR::RDI => 5,
+                GPR::RBP => 6,
+                GPR::RSP => 7,
+                GPR::R8 => 8,
+                GPR::R9 => 9,
+                GPR::R10 => 10,
+                GPR::R11 => 11,
+                GPR::R12 => 12,
+                GPR::R13 => 13,
+                GPR::R14 => 14,
+                GPR::R15 => 15,
+            },
+            X64Register::XMM(x) => 17 + x as u16,
+        }
+    }
+
     /// Converts a DWARF regnum to X64Register.
     pub fn _from_dwarf_regnum(x: u16) -> Option<X64Register> {
         Some(match x {
@@ -161,55 +212,129 @@ impl X64Register {
     }
 }
 
-/// An allocator that allocates registers for function arguments according to the System V ABI.
-#[derive(Default)]
-pub struct ArgumentRegisterAllocator {
-    n_gprs: usize,
-    n_xmms: usize,
+/// Where a parameter or a result of a function is passed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AbiLocation {
+    /// In a register.
+    Reg(X64Register),
+    /// At an offset in the stack arguments for a parameter, or in the
+    /// results area for a result.
+    Memory(i32),
 }
 
-impl ArgumentRegisterAllocator {
-    /// Allocates a register for argument type `ty`. Returns `None` if no register is available for this type.
-    pub fn next(&mut self, ty: Type) -> Option<X64Register> {
-        static GPR_SEQ: &'static [GPR] =
-            &[GPR::RDI, GPR::RSI, GPR::RDX, GPR::RCX, GPR::R8, GPR::R9];
-        static XMM_SEQ: &'static [XMM] = &[
-            XMM::XMM0,
-            XMM::XMM1,
-            XMM::XMM2,
-            XMM::XMM3,
-            XMM::XMM4,
-            XMM::XMM5,
-            XMM::XMM6,
-            XMM::XMM7,
-        ];
-        match ty {
-            Type::I32 | Type::I64 => {
-                if self.n_gprs < GPR_SEQ.len() {
-                    let gpr = GPR_SEQ[self.n_gprs];
-                    self.n_gprs += 1;
-                    Some(X64Register::GPR(gpr))
-                } else {
-                    None
+/// The calling convention of a function, as Cranelift lowers a
+/// signature for the System V ABI, so that the functions generated by
+/// both compilers can call each other.
+///
+/// The parameters come after `vmctx`: integers in GPRs and floats in XMM
+/// registers, then on the stack, and `v128` values in XMM registers. The
+/// results are returned in RAX, RDX and RCX for integers, and XMM0 and
+/// XMM1 for floats and `v128` values. If they don't all fit, they are all
+/// returned in an area whose address is passed before `vmctx` and
+/// returned in RAX.
+#[derive(Clone, Debug)]
+pub struct FunctionAbi {
+    /// The register of the address of the results area, if there is one.
+    pub results_area: Option<GPR>,
+    /// The register of `vmctx`.
+    pub vmctx: GPR,
+    /// Where each parameter is passed.
+    pub params: SmallVec<[AbiLocation; 8]>,
+    /// The size of the parameters passed on the stack.
+    pub stack_params_size: usize,
+    /// Where each result is returned.
+    pub results: SmallVec<[AbiLocation; 8]>,
+    /// The size of the results area.
+    pub results_area_size: usize,
+}
+
+impl FunctionAbi {
+    /// Returns the calling convention of a function with `params` and `results`.
+    pub fn new(params: &[Type], results: &[Type]) -> Self {
+        static PARAM_GPRS: &[GPR] = &[GPR::RDI, GPR::RSI, GPR::RDX, GPR::RCX, GPR::R8, GPR::R9];
+        static RESULT_GPRS: &[GPR] = &[GPR::RAX, GPR::RDX, GPR::RCX];
+
+        let mut in_registers = SmallVec::new();
+        let (mut gprs, mut xmms) = (RESULT_GPRS.iter(), 0);
+        for ty in results {
+            let reg = match ty {
+                Type::F32 | Type::F64 if xmms >= 2 => None,
+                Type::F32 | Type::F64 | Type::V128 => {
+                    xmms += 1;
+                    Some(X64Register::XMM(XMM::from_index(xmms - 1)))
                 }
+                _ => gprs.next().map(|gpr| X64Register::GPR(*gpr)),
+            };
+            match reg {
+                Some(reg) => in_registers.push(AbiLocation::Reg(reg)),
+                None => break,
             }
-            Type::F32 | Type::F64 => {
-                if self.n_xmms < XMM_SEQ.len() {
-                    let xmm = XMM_SEQ[self.n_xmms];
-                    self.n_xmms += 1;
-                    Some(X64Register::XMM(xmm))
-                } else {
-                    None
+        }
+
+        let mut gprs = PARAM_GPRS.iter().copied();
+        let (results_area, results, results_area_size) = if in_registers.len() == results.len() {
+            (None, in_registers, 0)
+        } else {
+            // The results are laid out like a C struct.
+            let mut size = 0;
+            let results = results
+                .iter()
+                .map(|ty| {
+                    let bytes = type_size(*ty);
+                    let offset = (size + bytes - 1) / bytes * bytes;
+                    size = offset + bytes;
+                    AbiLocation::Memory(offset as i32)
+                })
+                .collect();
+            (gprs.next(), results, size)
+        };
+        let vmctx = gprs.next().unwrap();
+
+        let mut xmms = 0;
+        let mut stack_params_size = 0;
+        let params = params
+            .iter()
+            .map(|ty| {
+                let reg = match ty {
+                    Type::F32 | Type::F64 if xmms >= 8 => None,
+                    Type::V128 if xmms >= 16 => None,
+                    Type::F32 | Type::F64 | Type::V128 => {
+                        xmms += 1;
+                        Some(X64Register::XMM(XMM::from_index(xmms - 1)))
+                    }
+                    _ => gprs.next().map(X64Register::GPR),
+                };
+                match reg {
+                    Some(reg) => AbiLocation::Reg(reg),
+                    None => {
+                        let offset = stack_params_size;
+                        stack_params_size += if *ty == Type::V128 { 16 } else { 8 };
+                        AbiLocation::Memory(offset as i32)
+                    }
                 }
-            }
-            _ => todo!(
-                "ArgumentRegisterAllocator::next: Unsupported type: {:?}",
-                ty
-            ),
+            })
+            .collect();
+
+        Self {
+            results_area,
+            vmctx,
+            params,
+            stack_params_size,
+            results,
+            results_area_size,
         }
     }
 }
 
+/// Returns the size of a value of type `ty` in memory.
+fn type_size(ty: Type) -> usize {
+    match ty {
+        Type::I32 | Type::F32 => 4,
+        Type::I64 | Type::F64 | Type::ExternRef | Type::FuncRef => 8,
+        Type::V128 => 16,
+    }
+}
+
 /// Create a new `MachineState` with default values.
 pub fn new_machine_state() -> MachineState {
     MachineState {
@@ -5,6 +5,7 @@ use crate::lib::std::vec::Vec;
 use crate::sourceloc::SourceLoc;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
+use wasmer_types::Type;
 
 /// Single source location to generated address mapping.
 #[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
@@ -40,3 +41,45 @@ pub struct FunctionAddressMap {
     /// Generated function body length.
     pub body_len: usize,
 }
+
+/// Where a compiled function keeps a Wasm value.
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueLocation {
+    /// In the register with the given DWARF register number.
+    Register(u16),
+    /// In memory, at `offset` from the address in the register with the
+    /// DWARF register number `register`.
+    Memory {
+        /// The DWARF register number of the base register.
+        register: u16,
+        /// The offset from the base register.
+        offset: i64,
+    },
+}
+
+/// The location of a Wasm value over a range of the generated code.
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueLocationRange {
+    /// The offset of the first instruction of the range.
+    pub start: u32,
+
+    /// The offset past the last instruction of the range.
+    pub end: u32,
+
+    /// Where the value is in the range.
+    pub location: ValueLocation,
+}
+
+/// The locations of a Wasm local of a compiled function.
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalLocations {
+    /// The type of the local.
+    pub ty: Type,
+
+    /// Where the local is, sorted by offset. Outside of these ranges the
+    /// value of the local is not available.
+    pub ranges: Vec<ValueLocationRange>,
+}
@@ -7,13 +7,14 @@ use crate::lib::std::boxed::Box;
 use crate::lib::std::sync::Arc;
 use crate::module::CompileModuleInfo;
 use crate::target::Target;
-use crate::translator::ModuleMiddleware;
+use crate::translator::{validate_function_body, ModuleMiddleware};
 use crate::FunctionBodyData;
 use crate::ModuleTranslationState;
 use crate::SectionIndex;
-use wasmer_types::entity::PrimaryMap;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{Features, FunctionIndex, LocalFunctionIndex, SignatureIndex};
-use wasmparser::{Validator, WasmFeatures};
+use wasmer_vm::ModuleInfo;
+use wasmparser::{BinaryReaderError, Parser, ValidPayload, Validator, WasmFeatures};
 
 /// The compiler configuration options.
 pub trait CompilerConfig {
@@ -58,7 +59,7 @@ where
 }
 
 /// An implementation of a Compiler from parsed WebAssembly module to Compiled native code.
-pub trait Compiler: Send {
+pub trait Compiler: Send + Sync {
     /// Validates a module.
     ///
     /// It returns the a succesful Result in case is valid, `CompileError` in case is not.
@@ -82,12 +83,40 @@ pub trait Compiler: Send {
             deterministic_only: false,
         };
         validator.wasm_features(wasm_features);
-        validator
-            .validate_all(data)
-            .map_err(|e| CompileError::Validate(format!("{}", e)))?;
+        let validate_error = |e: BinaryReaderError| CompileError::Validate(format!("{}", e));
+        let mut functions_to_validate = Vec::new();
+        for payload in Parser::new(0).parse_all(data) {
+            let payload = payload.map_err(validate_error)?;
+            if let ValidPayload::Func(func_validator, body) =
+                validator.payload(&payload).map_err(validate_error)?
+            {
+                functions_to_validate.push((func_validator, body));
+            }
+        }
+        for (mut func_validator, body) in functions_to_validate {
+            validate_function_body(&mut func_validator, &body, features.exceptions)?;
+        }
         Ok(())
     }
 
+    /// Transforms a parsed module in-place with the module middlewares.
+    ///
+    /// This is called before the styles of the memories and tables are
+    /// chosen, so the ones added by the middlewares get a style from the
+    /// tunables too.
+    fn transform_module_info(&self, _module: &mut ModuleInfo) {}
+
+    /// Analyzes the function bodies of a parsed module with the module
+    /// middlewares, before any of them is compiled.
+    ///
+    /// This is called after `transform_module_info`, once per module.
+    fn analyze_function_bodies(
+        &self,
+        _module: &ModuleInfo,
+        _function_body_inputs: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) {
+    }
+
     /// Compiles a parsed module.
     ///
     /// It returns the [`Compilation`] or a [`CompileError`].
@@ -100,6 +129,56 @@ pub trait Compiler: Send {
         function_body_inputs: PrimaryMap<LocalFunctionIndex, FunctionBodyData<'data>>,
     ) -> Result<Compilation, CompileError>;
 
+    /// Compiles a single function of a parsed module.
+    ///
+    /// This lets engines compile the functions of a module lazily. The
+    /// returned [`Compilation`] has the function as its only one, and no
+    /// trampolines; the relocations of the function still refer to the
+    /// other functions by their index in the module.
+    ///
+    /// By default, the module is compiled with the bodies of the other
+    /// functions replaced by `unreachable`, and without unwind information.
+    fn compile_function<'data, 'module>(
+        &self,
+        target: &Target,
+        module: &'module mut CompileModuleInfo,
+        module_translation: &ModuleTranslationState,
+        index: LocalFunctionIndex,
+        input: &FunctionBodyData<'data>,
+    ) -> Result<Compilation, CompileError> {
+        // No locals, `unreachable` and `end`.
+        const PLACEHOLDER: &[u8] = &[0x00, 0x00, 0x0b];
+        let num_local_functions =
+            module.module.functions.len() - module.module.num_imported_functions;
+        let function_body_inputs = (0..num_local_functions)
+            .map(LocalFunctionIndex::new)
+            .map(|i| {
+                if i == index {
+                    FunctionBodyData {
+                        data: input.data,
+                        module_offset: input.module_offset,
+                    }
+                } else {
+                    FunctionBodyData {
+                        data: PLACEHOLDER,
+                        module_offset: input.module_offset,
+                    }
+                }
+            })
+            .collect();
+        let compilation =
+            self.compile_module(target, module, module_translation, function_body_inputs)?;
+        let mut functions = PrimaryMap::new();
+        functions.push(compilation.get(index).clone());
+        Ok(Compilation::new(
+            functions,
+            compilation.get_custom_sections(),
+            PrimaryMap::new(),
+            PrimaryMap::new(),
+            None,
+        ))
+    }
+
     /// Compiles a module into a native object file.
     ///
     /// It returns the bytes as a `&[u8]` or a [`CompileError`].
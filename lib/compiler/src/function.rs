@@ -11,7 +11,9 @@
 use crate::lib::std::vec::Vec;
 use crate::section::{CustomSection, SectionIndex};
 use crate::trap::TrapInformation;
-use crate::{CompiledFunctionUnwindInfo, FunctionAddressMap, JumpTableOffsets, Relocation};
+use crate::{
+    CompiledFunctionUnwindInfo, FunctionAddressMap, JumpTableOffsets, LocalLocations, Relocation,
+};
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
 use wasmer_types::entity::PrimaryMap;
@@ -31,6 +33,10 @@ pub struct CompiledFunctionFrameInfo {
 
     /// The address map.
     pub address_map: FunctionAddressMap,
+
+    /// The locations of the locals of the function, parameters first, for
+    /// debuggers. Empty if the compiler doesn't track them.
+    pub locals: Vec<LocalLocations>,
 }
 
 /// The function body.
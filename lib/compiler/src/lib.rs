@@ -66,7 +66,9 @@ mod translator;
 mod section;
 mod sourceloc;
 
-pub use crate::address_map::{FunctionAddressMap, InstructionAddressMap};
+pub use crate::address_map::{
+    FunctionAddressMap, InstructionAddressMap, LocalLocations, ValueLocation, ValueLocationRange,
+};
 #[cfg(feature = "translator")]
 pub use crate::compiler::{Compiler, CompilerConfig, Symbol, SymbolRegistry};
 pub use crate::error::{
@@ -3,10 +3,9 @@
 
 use super::module::translate_module;
 use super::state::ModuleTranslationState;
-use crate::lib::std::borrow::ToOwned;
 use crate::lib::std::string::ToString;
 use crate::lib::std::{boxed::Box, string::String, vec::Vec};
-use crate::{WasmError, WasmResult};
+use crate::WasmResult;
 use std::convert::{TryFrom, TryInto};
 use std::sync::Arc;
 use wasmer_types::entity::PrimaryMap;
@@ -15,9 +14,9 @@ use wasmer_types::{
     CustomSectionIndex, DataIndex, DataInitializer, DataInitializerLocation, ElemIndex,
     ExportIndex, FunctionIndex, GlobalIndex, GlobalInit, GlobalType, ImportIndex,
     LocalFunctionIndex, MemoryIndex, MemoryType, SignatureIndex, TableIndex, TableInitializer,
-    TableType,
+    TableType, TagIndex,
 };
-use wasmer_vm::ModuleInfo;
+use wasmer_vm::{InstanceInitializer, ModuleInfo, Submodule};
 
 /// Contains function data: bytecode and its offset in the module.
 #[derive(Hash)]
@@ -208,6 +207,95 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    pub(crate) fn declare_tag_import(
+        &mut self,
+        sig_index: SignatureIndex,
+        module: &str,
+        field: &str,
+    ) -> WasmResult<()> {
+        debug_assert_eq!(
+            self.result.module.tags.len(),
+            self.result.module.num_imported_tags,
+            "Imported tags must be declared first"
+        );
+        self.declare_import(
+            ImportIndex::Tag(TagIndex::from_u32(
+                self.result.module.num_imported_tags as _,
+            )),
+            module,
+            field,
+        )?;
+        self.result.module.tags.push(sig_index);
+        self.result.module.num_imported_tags += 1;
+        self.imports += 1;
+        Ok(())
+    }
+
+    /// Whether any function, table, memory, global or tag has been
+    /// defined locally, after which no more imports can be declared.
+    pub(crate) fn has_local_definitions(&self) -> bool {
+        let module = &self.result.module;
+        module.functions.len() != module.num_imported_functions
+            || module.tables.len() != module.num_imported_tables
+            || module.memories.len() != module.num_imported_memories
+            || module.globals.len() != module.num_imported_globals
+            || module.tags.len() != module.num_imported_tags
+    }
+
+    /// The index of the import of `import`, if it is imported.
+    pub(crate) fn import_of(&self, import: ImportIndex) -> Option<u32> {
+        self.result
+            .module
+            .imports
+            .iter()
+            .find(|(_, index)| **index == import)
+            .map(|((_, _, index), _)| *index)
+    }
+
+    /// Declares that the next import is resolved from the export `field`
+    /// of the instance at `instance`, created by the module.
+    pub(crate) fn declare_instance_export(&mut self, instance: usize, field: &str) {
+        self.result
+            .module
+            .instance_exports
+            .insert(self.imports, (instance, field.to_string()));
+    }
+
+    /// Sets the signatures of the imports of the functions and tags
+    /// exported by created instances, reusing the signatures of the
+    /// module where they match.
+    pub(crate) fn declare_aliased_signatures(
+        &mut self,
+        signatures: Vec<(ImportIndex, FunctionType)>,
+    ) -> WasmResult<()> {
+        let module = &mut self.result.module;
+        for (import, sig) in signatures {
+            let sig_index = match module.signatures.iter().find(|(_, s)| **s == sig) {
+                Some((sig_index, _)) => sig_index,
+                None => module.signatures.push(sig),
+            };
+            match import {
+                ImportIndex::Function(index) => module.functions[index] = sig_index,
+                ImportIndex::Tag(index) => module.tags[index] = sig_index,
+                _ => unreachable!("only functions and tags have signatures"),
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn declare_submodule(&mut self, binary: &[u8]) -> WasmResult<()> {
+        self.result
+            .module
+            .submodules
+            .push(Submodule::Binary(binary.into()));
+        Ok(())
+    }
+
+    pub(crate) fn declare_instance(&mut self, instance: InstanceInitializer) -> WasmResult<()> {
+        self.result.module.instances.push(instance);
+        Ok(())
+    }
+
     pub(crate) fn finish_imports(&mut self) -> WasmResult<()> {
         Ok(())
     }
@@ -250,11 +338,6 @@ impl<'data> ModuleEnvironment<'data> {
     }
 
     pub(crate) fn declare_memory(&mut self, memory: MemoryType) -> WasmResult<()> {
-        if memory.shared {
-            return Err(WasmError::Unsupported(
-                "shared memories are not supported yet".to_owned(),
-            ));
-        }
         self.result.module.memories.push(memory);
         Ok(())
     }
@@ -277,6 +360,19 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    pub(crate) fn reserve_tags(&mut self, num: u32) -> WasmResult<()> {
+        self.result
+            .module
+            .tags
+            .reserve_exact(usize::try_from(num).unwrap());
+        Ok(())
+    }
+
+    pub(crate) fn declare_tag(&mut self, sig_index: SignatureIndex) -> WasmResult<()> {
+        self.result.module.tags.push(sig_index);
+        Ok(())
+    }
+
     pub(crate) fn reserve_exports(&mut self, num: u32) -> WasmResult<()> {
         self.result
             .module
@@ -317,6 +413,10 @@ impl<'data> ModuleEnvironment<'data> {
         self.declare_export(ExportIndex::Global(global_index), name)
     }
 
+    pub(crate) fn declare_tag_export(&mut self, tag_index: TagIndex, name: &str) -> WasmResult<()> {
+        self.declare_export(ExportIndex::Tag(tag_index), name)
+    }
+
     pub(crate) fn declare_start_function(&mut self, func_index: FunctionIndex) -> WasmResult<()> {
         debug_assert!(self.result.module.start_function.is_none());
         self.result.module.start_function = Some(func_index);
@@ -368,6 +468,11 @@ impl<'data> ModuleEnvironment<'data> {
         Ok(())
     }
 
+    pub(crate) fn declare_code_section_start(&mut self, offset: usize) -> WasmResult<()> {
+        self.result.module.code_section_offset = offset;
+        Ok(())
+    }
+
     pub(crate) fn define_function_body(
         &mut self,
         _module_translation_state: &ModuleTranslationState,
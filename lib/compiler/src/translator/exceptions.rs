@@ -0,0 +1,161 @@
+//! Decoding and validation of the instructions of the exception handling
+//! proposal that wasmparser doesn't support yet.
+//!
+//! wasmparser decodes `try`, `catch`, `throw`, `rethrow` and `unwind`, and
+//! the earlier encoding of `catch_all`, which reuses the opcode of `else`.
+//! The `delegate` instruction and the current encoding of `catch_all` are
+//! decoded here, before the bytes are handed to wasmparser.
+
+use crate::error::{CompileError, WasmResult};
+use wasmparser::{
+    BinaryReader, BinaryReaderError, FuncValidator, FunctionBody, Operator, ValidatorResources,
+};
+
+/// The opcode of `delegate`.
+const DELEGATE: u32 = 0x18;
+/// The opcode of `catch_all`.
+const CATCH_ALL: u32 = 0x19;
+
+/// An instruction of the exception handling proposal that wasmparser
+/// doesn't decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExceptionOperator {
+    /// `catch_all`, which wasmparser represents as `Operator::Else` inside
+    /// a `try` block.
+    CatchAll,
+    /// `delegate`, which ends a `try` block and sends the exceptions thrown
+    /// in its body to the `try` block `relative_depth` labels out of it.
+    Delegate { relative_depth: u32 },
+}
+
+/// Reads the next instruction of `reader` if it is one of the
+/// instructions wasmparser doesn't decode, and leaves `reader` as it is
+/// otherwise.
+pub(crate) fn read_exception_operator(
+    reader: &mut BinaryReader,
+) -> WasmResult<Option<ExceptionOperator>> {
+    let mut peek = reader.clone();
+    let operator = match peek.read_u8() {
+        Ok(CATCH_ALL) => ExceptionOperator::CatchAll,
+        Ok(DELEGATE) => ExceptionOperator::Delegate {
+            relative_depth: peek.read_var_u32()?,
+        },
+        _ => return Ok(None),
+    };
+    *reader = peek;
+    Ok(Some(operator))
+}
+
+/// The kind of a control frame, as far as `delegate` is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameKind {
+    /// The body of a `try` block.
+    Try,
+    /// A clause of a `try` block.
+    TryClause,
+    /// Any other block, or the function itself.
+    Other,
+}
+
+/// Validates the body of a function with `validator`.
+///
+/// If `exceptions` is enabled, the `catch_all` and `delegate` instructions
+/// are checked here and replaced by equivalent instructions for the
+/// validator: `catch_all` by `else`, and `delegate` by a `catch_all`
+/// clause ending in `unreachable`, which checks the results of the body
+/// like `delegate` does.
+pub(crate) fn validate_function_body(
+    validator: &mut FuncValidator<ValidatorResources>,
+    body: &FunctionBody,
+    exceptions: bool,
+) -> Result<(), CompileError> {
+    let validate_error = |e: BinaryReaderError| CompileError::Validate(format!("{}", e));
+    let mut reader = body.get_binary_reader();
+    validator.read_locals(&mut reader).map_err(validate_error)?;
+    let mut frames = vec![FrameKind::Other];
+    while !reader.eof() {
+        let offset = reader.original_position();
+        let exception_operator = if exceptions {
+            read_exception_operator(&mut reader).map_err(CompileError::Wasm)?
+        } else {
+            None
+        };
+        match exception_operator {
+            Some(ExceptionOperator::CatchAll) => {
+                match frames.last_mut() {
+                    Some(kind @ FrameKind::Try) | Some(kind @ FrameKind::TryClause) => {
+                        *kind = FrameKind::TryClause
+                    }
+                    _ => {
+                        return Err(CompileError::Validate(format!(
+                            "catch_all found outside of a `try` block (at offset {})",
+                            offset
+                        )))
+                    }
+                }
+                validator
+                    .op(offset, &Operator::Else)
+                    .map_err(validate_error)?;
+            }
+            Some(ExceptionOperator::Delegate { relative_depth }) => {
+                if frames.pop() != Some(FrameKind::Try) {
+                    return Err(CompileError::Validate(format!(
+                        "delegate found outside of a `try` block (at offset {})",
+                        offset
+                    )));
+                }
+                if relative_depth as usize >= frames.len() {
+                    return Err(CompileError::Validate(format!(
+                        "unknown label: delegate depth too large (at offset {})",
+                        offset
+                    )));
+                }
+                for operator in &[Operator::Else, Operator::Unreachable, Operator::End] {
+                    validator.op(offset, operator).map_err(validate_error)?;
+                }
+            }
+            None => {
+                let operator = reader.read_operator().map_err(validate_error)?;
+                match operator {
+                    Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } => {
+                        frames.push(FrameKind::Other)
+                    }
+                    Operator::Try { .. } => frames.push(FrameKind::Try),
+                    Operator::Catch { .. } | Operator::Unwind | Operator::Else => {
+                        if let Some(kind @ FrameKind::Try) = frames.last_mut() {
+                            *kind = FrameKind::TryClause;
+                        }
+                    }
+                    Operator::End => {
+                        frames.pop();
+                    }
+                    _ => {}
+                }
+                validator.op(offset, &operator).map_err(validate_error)?;
+            }
+        }
+    }
+    validator
+        .finish(reader.original_position())
+        .map_err(validate_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_exception_operators() {
+        let mut reader = BinaryReader::new(&[0x19, 0x18, 0x02, 0x0b]);
+        assert_eq!(
+            read_exception_operator(&mut reader).unwrap(),
+            Some(ExceptionOperator::CatchAll)
+        );
+        assert_eq!(
+            read_exception_operator(&mut reader).unwrap(),
+            Some(ExceptionOperator::Delegate { relative_depth: 2 })
+        );
+        assert_eq!(read_exception_operator(&mut reader).unwrap(), None);
+        assert!(matches!(reader.read_operator().unwrap(), Operator::End));
+    }
+}
@@ -5,11 +5,14 @@ use smallvec::SmallVec;
 use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::ops::Deref;
+use wasmer_types::entity::PrimaryMap;
 use wasmer_types::LocalFunctionIndex;
 use wasmer_vm::ModuleInfo;
 use wasmparser::{BinaryReader, Operator, Type};
 
 use crate::error::{MiddlewareError, WasmResult};
+use crate::translator::environ::FunctionBodyData;
+use crate::translator::exceptions::{read_exception_operator, ExceptionOperator};
 
 /// A shared builder for function middlewares.
 pub trait ModuleMiddleware: Debug + Send + Sync {
@@ -25,6 +28,15 @@ pub trait ModuleMiddleware: Debug + Send + Sync {
 
     /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
     fn transform_module_info(&self, _: &mut ModuleInfo) {}
+
+    /// Analyzes the bodies of all the local functions of a module. This is called after
+    /// `transform_module_info`, and before application on functions begins.
+    fn analyze_function_bodies(
+        &self,
+        _: &ModuleInfo,
+        _: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) {
+    }
 }
 
 /// A function middleware specialized for a single function.
@@ -48,6 +60,10 @@ pub struct MiddlewareBinaryReader<'a> {
 
     /// The backing middleware chain for this reader.
     chain: Vec<Box<dyn FunctionMiddleware>>,
+
+    /// The module offset of the original operator of the last operator
+    /// read.
+    current_operator_offset: usize,
 }
 
 /// The state of the binary reader. Exposed to middlewares to push their outputs.
@@ -56,8 +72,13 @@ pub struct MiddlewareReaderState<'a> {
     /// Raw binary reader.
     inner: BinaryReader<'a>,
 
-    /// The pending operations added by the middleware.
-    pending_operations: VecDeque<Operator<'a>>,
+    /// The pending operations added by the middleware, with the module
+    /// offset of the original operator each one was generated from.
+    pending_operations: VecDeque<(Operator<'a>, usize)>,
+
+    /// The module offset of the original operator of the operator being
+    /// fed through the chain.
+    operator_offset: usize,
 }
 
 /// Trait for generating middleware chains from "prototype" (generator) chains.
@@ -70,6 +91,13 @@ pub trait ModuleMiddlewareChain {
 
     /// Applies the chain on a `ModuleInfo` struct.
     fn apply_on_module_info(&self, module_info: &mut ModuleInfo);
+
+    /// Applies the chain on the bodies of the local functions of a module.
+    fn apply_on_function_bodies(
+        &self,
+        module_info: &ModuleInfo,
+        function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    );
 }
 
 impl<T: Deref<Target = dyn ModuleMiddleware>> ModuleMiddlewareChain for [T] {
@@ -89,24 +117,67 @@ impl<T: Deref<Target = dyn ModuleMiddleware>> ModuleMiddlewareChain for [T] {
             item.transform_module_info(module_info);
         }
     }
+
+    /// Applies the chain on the bodies of the local functions of a module.
+    fn apply_on_function_bodies(
+        &self,
+        module_info: &ModuleInfo,
+        function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) {
+        for item in self {
+            item.analyze_function_bodies(module_info, function_bodies);
+        }
+    }
 }
 
 impl<'a> MiddlewareReaderState<'a> {
-    /// Push an operator.
+    /// Push an operator, generated from the operator being fed.
     pub fn push_operator(&mut self, operator: Operator<'a>) {
-        self.pending_operations.push_back(operator);
+        self.push_operator_at(operator, self.operator_offset);
+    }
+
+    /// Push an operator, generated from the original operator at the
+    /// module offset `offset`.
+    ///
+    /// Middlewares holding operators back record their offset with
+    /// `current_offset`, so the compiled code keeps the source location
+    /// of the operators it was compiled from.
+    pub fn push_operator_at(&mut self, operator: Operator<'a>, offset: usize) {
+        self.pending_operations.push_back((operator, offset));
+    }
+
+    /// The module offset of the original operator of the operator being
+    /// fed through the middleware chain.
+    ///
+    /// The operators pushed by the previous middlewares report the offset
+    /// of the operator they were generated from.
+    pub fn current_offset(&self) -> usize {
+        self.operator_offset
+    }
+
+    /// Reads the next operator from the raw binary reader, decoding the
+    /// current encoding of `catch_all` as the one wasmparser knows.
+    fn read_raw_operator(&mut self) -> WasmResult<Operator<'a>> {
+        let mut reader = self.inner.clone();
+        if let Some(ExceptionOperator::CatchAll) = read_exception_operator(&mut reader)? {
+            self.inner = reader;
+            return Ok(Operator::Else);
+        }
+        Ok(self.inner.read_operator()?)
     }
 }
 
 impl<'a> Extend<Operator<'a>> for MiddlewareReaderState<'a> {
     fn extend<I: IntoIterator<Item = Operator<'a>>>(&mut self, iter: I) {
-        self.pending_operations.extend(iter);
+        for operator in iter {
+            self.push_operator(operator);
+        }
     }
 }
 
 impl<'a: 'b, 'b> Extend<&'b Operator<'a>> for MiddlewareReaderState<'a> {
     fn extend<I: IntoIterator<Item = &'b Operator<'a>>>(&mut self, iter: I) {
-        self.pending_operations.extend(iter.into_iter().cloned());
+        self.extend(iter.into_iter().cloned());
     }
 }
 
@@ -118,8 +189,10 @@ impl<'a> MiddlewareBinaryReader<'a> {
             state: MiddlewareReaderState {
                 inner,
                 pending_operations: VecDeque::new(),
+                operator_offset: original_offset,
             },
             chain: vec![],
+            current_operator_offset: original_offset,
         }
     }
 
@@ -144,30 +217,65 @@ impl<'a> MiddlewareBinaryReader<'a> {
     pub fn read_operator(&mut self) -> WasmResult<Operator<'a>> {
         if self.chain.is_empty() {
             // We short-circuit in case no chain is used
-            return Ok(self.state.inner.read_operator()?);
+            self.current_operator_offset = self.state.inner.original_position();
+            return self.state.read_raw_operator();
         }
 
         // Try to fill the `self.pending_operations` buffer, until it is non-empty.
         while self.state.pending_operations.is_empty() {
-            let raw_op = self.state.inner.read_operator()?;
+            let offset = self.state.inner.original_position();
+            let raw_op = self.state.read_raw_operator()?;
 
             // Fill the initial raw operator into pending buffer.
-            self.state.pending_operations.push_back(raw_op);
+            self.state.pending_operations.push_back((raw_op, offset));
 
             // Run the operator through each stage.
             for stage in &mut self.chain {
                 // Take the outputs from the previous stage.
-                let pending: SmallVec<[Operator<'a>; 2]> =
+                let pending: SmallVec<[(Operator<'a>, usize); 2]> =
                     self.state.pending_operations.drain(0..).collect();
 
                 // ...and feed them into the current stage.
-                for pending_op in pending {
+                for (pending_op, offset) in pending {
+                    self.state.operator_offset = offset;
                     stage.feed(pending_op, &mut self.state)?;
                 }
             }
         }
 
-        Ok(self.state.pending_operations.pop_front().unwrap())
+        let (operator, offset) = self.state.pending_operations.pop_front().unwrap();
+        self.current_operator_offset = offset;
+        Ok(operator)
+    }
+
+    /// Reads a `delegate` instruction, which wasmparser doesn't decode, if
+    /// it is the next instruction, and returns its label depth.
+    ///
+    /// `delegate` isn't fed to the middlewares, so this returns `None` while
+    /// they have operators pending.
+    pub fn read_delegate(&mut self) -> WasmResult<Option<u32>> {
+        if !self.state.pending_operations.is_empty() {
+            return Ok(None);
+        }
+        let offset = self.state.inner.original_position();
+        let mut reader = self.state.inner.clone();
+        match read_exception_operator(&mut reader)? {
+            Some(ExceptionOperator::Delegate { relative_depth }) => {
+                self.state.inner = reader;
+                self.current_operator_offset = offset;
+                Ok(Some(relative_depth))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the module offset of the original operator the last
+    /// operator read was generated from.
+    ///
+    /// This is the source location of the code compiled from the last
+    /// operator read.
+    pub fn current_operator_offset(&self) -> usize {
+        self.current_operator_offset
     }
 
     /// Returns the inner `BinaryReader`'s current position.
@@ -5,8 +5,9 @@
 //! to deal with each part of it.
 use super::environ::ModuleEnvironment;
 use super::sections::{
-    parse_data_section, parse_element_section, parse_export_section, parse_function_section,
-    parse_global_section, parse_import_section, parse_memory_section, parse_name_section,
+    parse_alias_section, parse_data_section, parse_element_section, parse_event_section,
+    parse_export_section, parse_function_section, parse_global_section, parse_import_section,
+    parse_instance_section, parse_memory_section, parse_name_section, parse_nested_module,
     parse_start_section, parse_table_section, parse_type_section,
 };
 use super::state::ModuleTranslationState;
@@ -20,9 +21,21 @@ pub fn translate_module<'data>(
     environ: &mut ModuleEnvironment<'data>,
 ) -> WasmResult<ModuleTranslationState> {
     let mut module_translation_state = ModuleTranslationState::new();
+    // The payloads of nested modules follow their entry in the module
+    // section, and are skipped as they are translated on their own.
+    let mut nested_depth = 0;
 
     for payload in Parser::new(0).parse_all(data) {
-        match payload? {
+        let payload = payload?;
+        if nested_depth > 0 {
+            match payload {
+                Payload::End => nested_depth -= 1,
+                Payload::ModuleSectionEntry { .. } => nested_depth += 1,
+                _ => {}
+            }
+            continue;
+        }
+        match payload {
             Payload::Version { .. } | Payload::End => {}
 
             Payload::TypeSection(types) => {
@@ -30,7 +43,7 @@ pub fn translate_module<'data>(
             }
 
             Payload::ImportSection(imports) => {
-                parse_import_section(imports, environ)?;
+                parse_import_section(imports, &mut module_translation_state, environ)?;
             }
 
             Payload::FunctionSection(functions) => {
@@ -49,6 +62,10 @@ pub fn translate_module<'data>(
                 parse_global_section(globals, environ)?;
             }
 
+            Payload::EventSection(events) => {
+                parse_event_section(events, environ)?;
+            }
+
             Payload::ExportSection(exports) => {
                 parse_export_section(exports, environ)?;
             }
@@ -61,7 +78,9 @@ pub fn translate_module<'data>(
                 parse_element_section(elements, environ)?;
             }
 
-            Payload::CodeSectionStart { .. } => {}
+            Payload::CodeSectionStart { range, .. } => {
+                environ.declare_code_section_start(range.start)?;
+            }
             Payload::CodeSectionEntry(code) => {
                 let mut code = code.get_binary_reader();
                 let size = code.bytes_remaining();
@@ -81,12 +100,22 @@ pub fn translate_module<'data>(
                 environ.reserve_passive_data(count)?;
             }
 
-            Payload::InstanceSection(_)
-            | Payload::AliasSection(_)
-            | Payload::EventSection(_)
-            | Payload::ModuleSectionStart { .. }
-            | Payload::ModuleSectionEntry { .. } => {
-                unimplemented!("module linking not implemented yet")
+            Payload::AliasSection(aliases) => {
+                parse_alias_section(aliases, &mut module_translation_state, environ)?;
+            }
+
+            Payload::InstanceSection(instances) => {
+                parse_instance_section(instances, &mut module_translation_state, environ)?;
+            }
+
+            Payload::ModuleSectionStart { .. } => {}
+            Payload::ModuleSectionEntry { range, .. } => {
+                parse_nested_module(
+                    &data[range.start..range.end],
+                    &mut module_translation_state,
+                    environ,
+                )?;
+                nested_depth += 1;
             }
 
             Payload::CustomSection {
@@ -101,5 +130,8 @@ pub fn translate_module<'data>(
         }
     }
 
+    let aliased_signatures = std::mem::take(&mut module_translation_state.aliased_signatures);
+    environ.declare_aliased_signatures(aliased_signatures)?;
+
     Ok(module_translation_state)
 }
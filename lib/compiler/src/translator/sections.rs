@@ -11,7 +11,7 @@
 //! is handled, according to the semantics of WebAssembly, to only specific expressions that are
 //! interpreted on the fly.
 use super::environ::ModuleEnvironment;
-use super::state::ModuleTranslationState;
+use super::state::{InstanceTranslation, ModuleTranslationState};
 use crate::wasm_unsupported;
 use crate::{WasmError, WasmResult};
 use core::convert::TryFrom;
@@ -21,15 +21,19 @@ use std::vec::Vec;
 use wasmer_types::entity::packed_option::ReservedValue;
 use wasmer_types::entity::EntityRef;
 use wasmer_types::{
-    DataIndex, ElemIndex, FunctionIndex, FunctionType, GlobalIndex, GlobalInit, GlobalType,
-    MemoryIndex, MemoryType, Pages, SignatureIndex, TableIndex, TableType, Type, V128,
+    DataIndex, ElemIndex, ExternType, FunctionIndex, FunctionType, GlobalIndex, GlobalInit,
+    GlobalType, ImportIndex, MemoryIndex, MemoryType, Pages, SignatureIndex, TableIndex, TableType,
+    TagIndex, Type, V128, WASM64_MAX_PAGES,
 };
+use wasmer_vm::{InstanceInitializer, InstantiationArg};
 use wasmparser::{
-    self, Data, DataKind, DataSectionReader, Element, ElementItem, ElementItems, ElementKind,
-    ElementSectionReader, Export, ExportSectionReader, ExternalKind, FuncType as WPFunctionType,
-    FunctionSectionReader, GlobalSectionReader, GlobalType as WPGlobalType, ImportSectionEntryType,
-    ImportSectionReader, MemorySectionReader, MemoryType as WPMemoryType, NameSectionReader,
-    Naming, NamingReader, Operator, TableSectionReader, TypeDef, TypeSectionReader,
+    self, Alias, AliasSectionReader, Data, DataKind, DataSectionReader, Element, ElementItem,
+    ElementItems, ElementKind, ElementSectionReader, EventSectionReader, EventType, Export,
+    ExportSectionReader, ExternalKind, FuncType as WPFunctionType, FunctionSectionReader,
+    GlobalSectionReader, GlobalType as WPGlobalType, ImportSectionEntryType, ImportSectionReader,
+    InstanceArg, InstanceSectionReader, MemorySectionReader, MemoryType as WPMemoryType,
+    NameSectionReader, Naming, NamingReader, Operator, TableSectionReader, TypeDef,
+    TypeSectionReader,
 };
 
 /// Helper function translating wasmparser types to Wasm Type.
@@ -49,6 +53,37 @@ pub fn wptype_to_type(ty: wasmparser::Type) -> WasmResult<Type> {
     }
 }
 
+/// Helper function translating wasmparser memory types to Wasm MemoryType.
+fn wpmemory_to_memory(ty: WPMemoryType) -> WasmResult<MemoryType> {
+    match ty {
+        WPMemoryType::M32 { limits, shared } => Ok(MemoryType::new(
+            Pages(limits.initial),
+            limits.maximum.map(Pages),
+            shared,
+        )),
+        WPMemoryType::M64 { limits, shared } => {
+            // Pages are counted in 32 bits. A larger maximum can never be
+            // reached, so it is clamped to `WASM64_MAX_PAGES`, but a larger
+            // minimum could never be allocated.
+            let minimum = u32::try_from(limits.initial).map_err(|_| {
+                wasm_unsupported!(
+                    "64-bit memory with a minimum of {} pages, the limit is {} pages",
+                    limits.initial,
+                    WASM64_MAX_PAGES
+                )
+            })?;
+            let maximum = limits
+                .maximum
+                .map(|maximum| u32::try_from(maximum).unwrap_or(WASM64_MAX_PAGES));
+            Ok(MemoryType::new64(
+                Pages(minimum),
+                maximum.map(Pages),
+                shared,
+            ))
+        }
+    }
+}
+
 /// Parses the Type section of the wasm module.
 pub fn parse_type_section(
     types: TypeSectionReader,
@@ -59,7 +94,9 @@ pub fn parse_type_section(
     environ.reserve_signatures(count)?;
 
     for entry in types {
-        if let Ok(TypeDef::Func(WPFunctionType { params, returns })) = entry {
+        let entry = entry?;
+        let type_index = module_translation_state.wasm_types.len() as u32;
+        if let TypeDef::Func(WPFunctionType { params, returns }) = entry {
             let sig_params: Vec<Type> = params
                 .iter()
                 .map(|ty| {
@@ -78,7 +115,22 @@ pub fn parse_type_section(
             environ.declare_signature(sig)?;
             module_translation_state.wasm_types.push((params, returns));
         } else {
-            unimplemented!("module linking not implemented yet")
+            // Module and instance types share the index space of function
+            // signatures, so a placeholder keeps the indices aligned.
+            environ.declare_signature(FunctionType::new(vec![], vec![]))?;
+            module_translation_state
+                .wasm_types
+                .push((Vec::new().into_boxed_slice(), Vec::new().into_boxed_slice()));
+            if let TypeDef::Instance(instance_type) = entry {
+                let exports = instance_type
+                    .exports
+                    .iter()
+                    .map(|export| (export.name.to_string(), export.ty))
+                    .collect();
+                module_translation_state
+                    .instance_types
+                    .insert(type_index, exports);
+            }
         }
     }
 
@@ -88,73 +140,265 @@ pub fn parse_type_section(
 /// Parses the Import section of the wasm module.
 pub fn parse_import_section<'data>(
     imports: ImportSectionReader<'data>,
+    module_translation_state: &mut ModuleTranslationState,
     environ: &mut ModuleEnvironment<'data>,
 ) -> WasmResult<()> {
     environ.reserve_imports(imports.get_count())?;
 
+    // The instances implicitly imported by two-level imports, by namespace.
+    let mut implicit_instances = HashMap::new();
     for entry in imports {
         let import = entry?;
         let module_name = import.module;
-        let field_name = import.field;
-
-        match import.ty {
-            ImportSectionEntryType::Function(sig) => {
-                environ.declare_func_import(
-                    SignatureIndex::from_u32(sig),
-                    module_name,
-                    field_name.unwrap_or_default(),
-                )?;
-            }
-            ImportSectionEntryType::Module(_)
-            | ImportSectionEntryType::Instance(_)
-            | ImportSectionEntryType::Event(_) => {
-                unimplemented!("module linking not implemented yet")
+
+        let field_name = match (import.ty, import.field) {
+            (ImportSectionEntryType::Instance(type_index), None) => {
+                // An imported instance is resolved from the namespace with
+                // the same name; its exports become imports when aliased.
+                environ.declare_instance(InstanceInitializer::Import(module_name.to_string()))?;
+                module_translation_state
+                    .instances
+                    .push(InstanceTranslation::Imported {
+                        namespace: module_name.to_string(),
+                        exports: module_translation_state.instance_types[&type_index].to_vec(),
+                    });
+                continue;
             }
-            ImportSectionEntryType::Memory(WPMemoryType::M32 {
-                limits: ref memlimits,
-                shared,
-            }) => {
-                environ.declare_memory_import(
-                    MemoryType {
-                        minimum: Pages(memlimits.initial),
-                        maximum: memlimits.maximum.map(Pages),
-                        shared,
-                    },
-                    module_name,
-                    field_name.unwrap_or_default(),
-                )?;
+            (ImportSectionEntryType::Instance(_), Some(_)) => {
+                return Err(wasm_unsupported!(
+                    "two-level import of instance `{}`",
+                    module_name
+                ));
             }
-            ImportSectionEntryType::Memory(WPMemoryType::M64 { .. }) => {
-                unimplemented!("64bit memory not implemented yet")
+            (ImportSectionEntryType::Module(_), _) => {
+                return Err(wasm_unsupported!("import of module `{}`", module_name));
             }
-            ImportSectionEntryType::Global(ref ty) => {
-                environ.declare_global_import(
-                    GlobalType {
-                        ty: wptype_to_type(ty.content_type).unwrap(),
-                        mutability: ty.mutable.into(),
-                    },
-                    module_name,
-                    field_name.unwrap_or_default(),
-                )?;
+            (_, field_name) => field_name,
+        };
+        if let Some(field_name) = field_name {
+            let instances = &mut module_translation_state.instances;
+            let index = *implicit_instances
+                .entry(module_name)
+                .or_insert_with(|| instances.len());
+            if index == instances.len() {
+                environ.declare_instance(InstanceInitializer::Import(module_name.to_string()))?;
+                instances.push(InstanceTranslation::Imported {
+                    namespace: module_name.to_string(),
+                    exports: Vec::new(),
+                });
             }
-            ImportSectionEntryType::Table(ref tab) => {
-                environ.declare_table_import(
-                    TableType {
-                        ty: wptype_to_type(tab.element_type).unwrap(),
-                        minimum: tab.limits.initial,
-                        maximum: tab.limits.maximum,
-                    },
-                    module_name,
-                    field_name.unwrap_or_default(),
-                )?;
+            if let InstanceTranslation::Imported { exports, .. } = &mut instances[index] {
+                exports.push((field_name.to_string(), import.ty));
             }
         }
+        declare_entity_import(
+            import.ty,
+            module_name,
+            field_name.unwrap_or_default(),
+            environ,
+        )?;
     }
 
     environ.finish_imports()?;
     Ok(())
 }
 
+/// Declares an import of a single entity (function, table, memory, global
+/// or tag) named `field` in the `module` namespace.
+fn declare_entity_import(
+    ty: ImportSectionEntryType,
+    module: &str,
+    field: &str,
+    environ: &mut ModuleEnvironment,
+) -> WasmResult<()> {
+    match ty {
+        ImportSectionEntryType::Function(sig) => {
+            environ.declare_func_import(SignatureIndex::from_u32(sig), module, field)?;
+        }
+        ImportSectionEntryType::Event(EventType { type_index }) => {
+            environ.declare_tag_import(SignatureIndex::from_u32(type_index), module, field)?;
+        }
+        ImportSectionEntryType::Memory(ty) => {
+            environ.declare_memory_import(wpmemory_to_memory(ty)?, module, field)?;
+        }
+        ImportSectionEntryType::Global(ref ty) => {
+            environ.declare_global_import(
+                GlobalType {
+                    ty: wptype_to_type(ty.content_type).unwrap(),
+                    mutability: ty.mutable.into(),
+                },
+                module,
+                field,
+            )?;
+        }
+        ImportSectionEntryType::Table(ref tab) => {
+            environ.declare_table_import(
+                TableType {
+                    ty: wptype_to_type(tab.element_type).unwrap(),
+                    minimum: tab.limits.initial,
+                    maximum: tab.limits.maximum,
+                },
+                module,
+                field,
+            )?;
+        }
+        ImportSectionEntryType::Module(_) | ImportSectionEntryType::Instance(_) => {
+            return Err(wasm_unsupported!(
+                "nested module or instance `{}` in `{}`",
+                field,
+                module
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses the Alias section of the wasm module.
+///
+/// Only aliases of the exports of instances are supported. Each one is
+/// declared as an import: of the export from the namespace the instance
+/// is imported from, or resolved from the instance the module creates.
+/// They must therefore appear before any local definition.
+pub fn parse_alias_section(
+    aliases: AliasSectionReader,
+    module_translation_state: &mut ModuleTranslationState,
+    environ: &mut ModuleEnvironment,
+) -> WasmResult<()> {
+    for entry in aliases {
+        let (instance_index, export) = match entry? {
+            Alias::InstanceExport {
+                instance,
+                kind: _,
+                export,
+            } => (instance as usize, export),
+            Alias::OuterType { .. } | Alias::OuterModule { .. } => {
+                return Err(wasm_unsupported!("aliases of the outer module"))
+            }
+        };
+        if environ.has_local_definitions() {
+            return Err(wasm_unsupported!(
+                "alias of `{}` after local definitions",
+                export
+            ));
+        }
+        match &module_translation_state.instances[instance_index] {
+            InstanceTranslation::Imported { namespace, exports } => {
+                let (_, ty) = exports
+                    .iter()
+                    .find(|(name, _)| name == export)
+                    .ok_or_else(|| wasm_unsupported!("unknown instance export `{}`", export))?;
+                declare_entity_import(*ty, namespace, export, environ)?;
+            }
+            InstanceTranslation::Created { module } => {
+                let ty = module_translation_state.submodules[*module]
+                    .exports()
+                    .find(|export_type| export_type.name() == export)
+                    .map(|export_type| export_type.ty().clone())
+                    .ok_or_else(|| wasm_unsupported!("unknown instance export `{}`", export))?;
+                environ.declare_instance_export(instance_index, export);
+                declare_instance_export_import(ty, export, module_translation_state, environ)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Declares the import of the export `field`, of type `ty`, of an
+/// instance the module creates.
+fn declare_instance_export_import(
+    ty: ExternType,
+    field: &str,
+    module_translation_state: &mut ModuleTranslationState,
+    environ: &mut ModuleEnvironment,
+) -> WasmResult<()> {
+    let module = &environ.result.module;
+    // The signatures of functions and tags are declared after the type
+    // sections, so they don't shift the type indices.
+    match ty {
+        ExternType::Function(sig) => {
+            let import = ImportIndex::Function(FunctionIndex::new(module.functions.len()));
+            module_translation_state
+                .aliased_signatures
+                .push((import, sig));
+            environ.declare_func_import(SignatureIndex::new(0), "", field)?;
+        }
+        ExternType::Tag(tag) => {
+            let import = ImportIndex::Tag(TagIndex::new(module.tags.len()));
+            module_translation_state
+                .aliased_signatures
+                .push((import, FunctionType::new(tag.params(), vec![])));
+            environ.declare_tag_import(SignatureIndex::new(0), "", field)?;
+        }
+        ExternType::Table(table) => environ.declare_table_import(table, "", field)?,
+        ExternType::Memory(memory) => environ.declare_memory_import(memory, "", field)?,
+        ExternType::Global(global) => environ.declare_global_import(global, "", field)?,
+    }
+    Ok(())
+}
+
+/// Parses the Instance section of the wasm module.
+///
+/// The instantiated modules must be nested in the module, and the items
+/// given to them must be imported or aliased.
+pub fn parse_instance_section(
+    instances: InstanceSectionReader,
+    module_translation_state: &mut ModuleTranslationState,
+    environ: &mut ModuleEnvironment,
+) -> WasmResult<()> {
+    for entry in instances {
+        let instance = entry?;
+        let module = instance.module() as usize;
+        if module >= module_translation_state.submodules.len() {
+            return Err(wasm_unsupported!("instantiation of imported modules"));
+        }
+        let mut args = Vec::new();
+        for arg in instance.args()? {
+            let InstanceArg { name, kind, index } = arg?;
+            let import = match kind {
+                ExternalKind::Function => ImportIndex::Function(FunctionIndex::from_u32(index)),
+                ExternalKind::Table => ImportIndex::Table(TableIndex::from_u32(index)),
+                ExternalKind::Memory => ImportIndex::Memory(MemoryIndex::from_u32(index)),
+                ExternalKind::Global => ImportIndex::Global(GlobalIndex::from_u32(index)),
+                ExternalKind::Event => ImportIndex::Tag(TagIndex::from_u32(index)),
+                ExternalKind::Instance => {
+                    args.push((name.to_string(), InstantiationArg::Instance(index as usize)));
+                    continue;
+                }
+                ExternalKind::Type | ExternalKind::Module => {
+                    return Err(wasm_unsupported!(
+                        "{:?} `{}` given to a nested module",
+                        kind,
+                        name
+                    ))
+                }
+            };
+            let import = environ.import_of(import).ok_or_else(|| {
+                wasm_unsupported!("local definition `{}` given to a nested module", name)
+            })?;
+            args.push((name.to_string(), InstantiationArg::Import(import)));
+        }
+        environ.declare_instance(InstanceInitializer::Instantiate { module, args })?;
+        module_translation_state
+            .instances
+            .push(InstanceTranslation::Created { module });
+    }
+
+    Ok(())
+}
+
+/// Translates the nested module in `binary`, for the types of its
+/// exports, and declares it.
+pub fn parse_nested_module(
+    binary: &[u8],
+    module_translation_state: &mut ModuleTranslationState,
+    environ: &mut ModuleEnvironment,
+) -> WasmResult<()> {
+    let translation = ModuleEnvironment::new().translate(binary)?;
+    module_translation_state.submodules.push(translation.module);
+    environ.declare_submodule(binary)
+}
+
 /// Parses the Function section of the wasm module.
 pub fn parse_function_section(
     functions: FunctionSectionReader,
@@ -204,16 +448,23 @@ pub fn parse_memory_section(
 
     for entry in memories {
         let memory = entry?;
-        match memory {
-            WPMemoryType::M32 { limits, shared } => {
-                environ.declare_memory(MemoryType {
-                    minimum: Pages(limits.initial),
-                    maximum: limits.maximum.map(Pages),
-                    shared,
-                })?;
-            }
-            WPMemoryType::M64 { .. } => unimplemented!("64bit memory not implemented yet"),
-        }
+        environ.declare_memory(wpmemory_to_memory(memory)?)?;
+    }
+
+    Ok(())
+}
+
+/// Parses the Event section of the wasm module, which declares the
+/// exception tags.
+pub fn parse_event_section(
+    events: EventSectionReader,
+    environ: &mut ModuleEnvironment,
+) -> WasmResult<()> {
+    environ.reserve_tags(events.get_count())?;
+
+    for entry in events {
+        let EventType { type_index } = entry?;
+        environ.declare_tag(SignatureIndex::from_u32(type_index))?;
     }
 
     Ok(())
@@ -294,11 +545,9 @@ pub fn parse_export_section<'data>(
             ExternalKind::Global => {
                 environ.declare_global_export(GlobalIndex::new(index), field)?
             }
-            ExternalKind::Type
-            | ExternalKind::Module
-            | ExternalKind::Instance
-            | ExternalKind::Event => {
-                unimplemented!("module linking not implemented yet")
+            ExternalKind::Event => environ.declare_tag_export(TagIndex::new(index), field)?,
+            ExternalKind::Type | ExternalKind::Module | ExternalKind::Instance => {
+                return Err(wasm_unsupported!("export of {:?} `{}`", kind, field))
             }
         }
     }
@@ -371,7 +620,11 @@ pub fn parse_element_section<'data>(
                 let index = ElemIndex::from_u32(index as u32);
                 environ.declare_passive_element(index, segments)?;
             }
-            ElementKind::Declared => return Err(wasm_unsupported!("element kind declared")),
+            ElementKind::Declared => {
+                // Declared segments only make their functions referenceable
+                // by `ref.func`. At runtime they behave like dropped passive
+                // segments, which are missing from `passive_elements`.
+            }
         }
     }
     Ok(())
@@ -394,6 +647,16 @@ pub fn parse_data_section<'data>(
                 let mut init_expr_reader = init_expr.get_binary_reader();
                 let (base, offset) = match init_expr_reader.read_operator()? {
                     Operator::I32Const { value } => (None, value as u32 as usize),
+                    // Only 64-bit memories are initialized at `i64` offsets.
+                    Operator::I64Const { value } => match usize::try_from(value as u64) {
+                        Ok(offset) => (None, offset),
+                        Err(_) => {
+                            return Err(wasm_unsupported!(
+                                "data segment offset {} out of range",
+                                value as u64
+                            ))
+                        }
+                    },
                     Operator::GlobalGet { global_index } => {
                         (Some(GlobalIndex::from_u32(global_index)), 0)
                     }
@@ -3,8 +3,12 @@
 
 use crate::{wasm_unsupported, WasmResult};
 use std::boxed::Box;
+use std::collections::HashMap;
+use std::string::String;
+use std::vec::Vec;
 use wasmer_types::entity::PrimaryMap;
-use wasmer_types::SignatureIndex;
+use wasmer_types::{FunctionType, ImportIndex, SignatureIndex};
+use wasmer_vm::ModuleInfo;
 
 /// Map of signatures to a function's parameter and return types.
 pub(crate) type WasmTypes =
@@ -23,6 +27,35 @@ pub struct ModuleTranslationState {
     /// This is used for translating multi-value Wasm blocks inside functions,
     /// which are encoded to refer to their type signature via index.
     pub(crate) wasm_types: WasmTypes,
+
+    /// The exports of every instance type in the type section, keyed by
+    /// type index.
+    pub(crate) instance_types: HashMap<u32, Box<[(String, wasmparser::ImportSectionEntryType)]>>,
+
+    /// The instances, in instance index order.
+    pub(crate) instances: Vec<InstanceTranslation>,
+
+    /// The nested modules, in module index order.
+    pub(crate) submodules: Vec<ModuleInfo>,
+
+    /// The imports of exported functions and tags of created instances,
+    /// whose signatures are declared once the whole module is translated.
+    pub(crate) aliased_signatures: Vec<(ImportIndex, FunctionType)>,
+}
+
+/// An instance of the module linking proposal, as seen by the module
+/// translating it.
+#[derive(Debug)]
+pub(crate) enum InstanceTranslation {
+    /// An imported instance, from the namespace with this name and with
+    /// these exports. Two-level imports implicitly import an instance of
+    /// their namespace, with an export per field.
+    Imported {
+        namespace: String,
+        exports: Vec<(String, wasmparser::ImportSectionEntryType)>,
+    },
+    /// An instance of the nested module at this module index.
+    Created { module: usize },
 }
 
 impl ModuleTranslationState {
@@ -30,6 +63,10 @@ impl ModuleTranslationState {
     pub fn new() -> Self {
         Self {
             wasm_types: PrimaryMap::new(),
+            instance_types: HashMap::new(),
+            instances: Vec::new(),
+            submodules: Vec::new(),
+            aliased_signatures: Vec::new(),
         }
     }
 
@@ -19,9 +19,9 @@ use std::f64;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use wasmer::{
-    imports, namespace, Exports, ExternRef, Function, FunctionType, Global, ImportObject, Instance,
-    LazyInit, Memory, MemoryType, Module, NativeFunc, Pages, RuntimeError, Store, Table, TableType,
-    Val, ValType, WasmerEnv,
+    imports, namespace, Exports, Function, FunctionType, Global, ImportObject, Instance, LazyInit,
+    Memory, MemoryType, Module, NativeFunc, Pages, RuntimeError, Store, Table, TableType, Val,
+    ValType, WasmerEnv,
 };
 
 #[cfg(unix)]
@@ -99,7 +99,11 @@ impl EmEnv {
     }
 
     /// Get a reference to the memory
-    pub fn memory(&self, _mem_idx: u32) -> &Memory {
+    ///
+    /// The Emscripten ABI only knows about the `env.memory` import, so
+    /// `mem_idx` must be `0`.
+    pub fn memory(&self, mem_idx: u32) -> &Memory {
+        assert_eq!(mem_idx, 0, "Emscripten only supports a single memory");
         (*self.memory).as_ref().unwrap()
     }
 }
@@ -478,7 +482,7 @@ impl EmscriptenGlobals {
             maximum: table_max,
         };
         // TODO: review init value
-        let table = Table::new(store, table_type, Val::ExternRef(ExternRef::null())).unwrap();
+        let table = Table::new(store, table_type, Val::FuncRef(None)).unwrap();
 
         let data = {
             let static_bump = STATIC_BUMP;
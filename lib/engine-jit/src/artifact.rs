@@ -1,21 +1,23 @@
 //! Define `JITArtifact` to allow compiling and instantiating to be
 //! done as separate steps.
 
+#[cfg(feature = "gdb-jit")]
+use crate::debug::{build_debug_image, GdbJitImageRegistration};
 use crate::engine::{JITEngine, JITEngineInner};
-use crate::link::link_module;
+use crate::link::{link_module, link_module_through_stubs};
 #[cfg(feature = "compiler")]
 use crate::serialize::SerializableCompilation;
 use crate::serialize::SerializableModule;
 use std::sync::{Arc, Mutex};
-use wasmer_compiler::{CompileError, Features, Triple};
+use wasmer_compiler::{CompileError, Features, Target, Triple};
 #[cfg(feature = "compiler")]
-use wasmer_compiler::{CompileModuleInfo, ModuleEnvironment};
+use wasmer_compiler::{CompileModuleInfo, Compiler, ModuleEnvironment, ModuleInfoTranslation};
+#[cfg(feature = "compiler")]
+use wasmer_engine::{compile_submodules, SerializableFunctionFrameInfo, Tunables};
 use wasmer_engine::{
-    register_frame_info, Artifact, DeserializeError, FunctionExtent, GlobalFrameInfoRegistration,
-    SerializeError,
+    register_frame_info, Artifact, DeserializeError, Engine, FunctionExtent,
+    GlobalFrameInfoRegistration, SerializeError,
 };
-#[cfg(feature = "compiler")]
-use wasmer_engine::{Engine, SerializableFunctionFrameInfo, Tunables};
 use wasmer_types::entity::{BoxedSlice, PrimaryMap};
 use wasmer_types::{
     FunctionIndex, LocalFunctionIndex, MemoryIndex, OwnedDataInitializer, SignatureIndex,
@@ -33,7 +35,9 @@ pub struct JITArtifact {
     finished_dynamic_function_trampolines: BoxedSlice<FunctionIndex, FunctionBodyPtr>,
     signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
     frame_info_registration: Mutex<Option<GlobalFrameInfoRegistration>>,
-    finished_function_lengths: BoxedSlice<LocalFunctionIndex, usize>,
+    finished_function_extents: BoxedSlice<LocalFunctionIndex, FunctionExtent>,
+    #[cfg(feature = "gdb-jit")]
+    gdb_jit_registration: Option<GdbJitImageRegistration>,
 }
 
 impl JITArtifact {
@@ -51,11 +55,28 @@ impl JITArtifact {
         data: &[u8],
         tunables: &dyn Tunables,
     ) -> Result<Self, CompileError> {
+        let serializable = Self::translate(jit, data, tunables)?;
+        Self::from_parts(&mut jit.inner_mut(), serializable, jit.target())
+    }
+
+    /// Translate and compile a data buffer into its serializable form.
+    ///
+    /// The engine must not be locked, as its nested modules are compiled
+    /// first.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn translate(
+        jit: &JITEngine,
+        data: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<SerializableModule, CompileError> {
         let environ = ModuleEnvironment::new();
-        let mut inner_jit = jit.inner_mut();
-        let features = inner_jit.features();
 
-        let translation = environ.translate(data).map_err(CompileError::Wasm)?;
+        let mut translation = environ.translate(data).map_err(CompileError::Wasm)?;
+        compile_submodules(jit, &mut translation.module, tunables)?;
+        let inner_jit = jit.inner();
+        let compiler = inner_jit.compiler()?;
+        compiler.transform_module_info(&mut translation.module);
+        compiler.analyze_function_bodies(&translation.module, &translation.function_body_inputs);
 
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = translation
             .module
@@ -70,6 +91,26 @@ impl JITArtifact {
             .map(|table_type| tunables.table_style(table_type))
             .collect();
 
+        Self::compile(
+            inner_jit.compiler()?,
+            jit,
+            inner_jit.features(),
+            translation,
+            memory_styles,
+            table_styles,
+        )
+    }
+
+    /// Compile a translated module into its serializable form.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn compile(
+        compiler: &dyn Compiler,
+        jit: &JITEngine,
+        features: &Features,
+        translation: ModuleInfoTranslation,
+        memory_styles: PrimaryMap<MemoryIndex, MemoryStyle>,
+        table_styles: PrimaryMap<TableIndex, TableStyle>,
+    ) -> Result<SerializableModule, CompileError> {
         let mut compile_info = CompileModuleInfo {
             module: Arc::new(translation.module),
             features: features.clone(),
@@ -77,8 +118,6 @@ impl JITArtifact {
             table_styles,
         };
 
-        let compiler = inner_jit.compiler()?;
-
         // Compile the Module
         let compilation = compiler.compile_module(
             &jit.target(),
@@ -116,12 +155,11 @@ impl JITArtifact {
             custom_section_relocations: compilation.get_custom_section_relocations(),
             debug: compilation.get_debug(),
         };
-        let serializable = SerializableModule {
+        Ok(SerializableModule {
             compilation: serializable_compilation,
             compile_info,
             data_initializers,
-        };
-        Self::from_parts(&mut inner_jit, serializable)
+        })
     }
 
     /// Compile a data buffer into a `JITArtifact`, which may then be instantiated.
@@ -148,13 +186,31 @@ impl JITArtifact {
         let serializable: SerializableModule = bincode::deserialize(inner_bytes)
             .map_err(|e| DeserializeError::CorruptedBinary(format!("{:?}", e)))?;
 
-        Self::from_parts(&mut jit.inner_mut(), serializable).map_err(DeserializeError::Compiler)
+        Self::from_parts(&mut jit.inner_mut(), serializable, jit.target())
+            .map_err(DeserializeError::Compiler)
     }
 
-    /// Construct a `JITArtifact` from component parts.
+    /// Construct a `JITArtifact` from component parts, compiled for
+    /// `target`.
     pub fn from_parts(
         inner_jit: &mut JITEngineInner,
         serializable: SerializableModule,
+        target: &Target,
+    ) -> Result<Self, CompileError> {
+        Self::from_parts_through(inner_jit, serializable, target, None)
+    }
+
+    /// Construct a `JITArtifact` from component parts, whose functions
+    /// call each other through `stubs` if given.
+    ///
+    /// The stubs are then the functions of the artifact, but they still
+    /// have to be pointed to the compiled code, see `function_bodies`.
+    #[cfg_attr(not(feature = "gdb-jit"), allow(unused_variables))]
+    pub(crate) fn from_parts_through(
+        inner_jit: &mut JITEngineInner,
+        serializable: SerializableModule,
+        target: &Target,
+        stubs: Option<&BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>>,
     ) -> Result<Self, CompileError> {
         let (
             finished_functions,
@@ -169,14 +225,24 @@ impl JITArtifact {
             &serializable.compilation.custom_sections,
         )?;
 
-        link_module(
-            &serializable.compile_info.module,
-            &finished_functions,
-            &serializable.compilation.function_jt_offsets,
-            serializable.compilation.function_relocations.clone(),
-            &custom_sections,
-            &serializable.compilation.custom_section_relocations,
-        );
+        match stubs {
+            Some(stubs) => link_module_through_stubs(
+                &finished_functions,
+                &serializable.compilation.function_jt_offsets,
+                serializable.compilation.function_relocations.clone(),
+                &custom_sections,
+                &serializable.compilation.custom_section_relocations,
+                stubs,
+            ),
+            None => link_module(
+                &serializable.compile_info.module,
+                &finished_functions,
+                &serializable.compilation.function_jt_offsets,
+                serializable.compilation.function_relocations.clone(),
+                &custom_sections,
+                &serializable.compilation.custom_section_relocations,
+            ),
+        }
 
         // Compute indices into the shared signature table.
         let signatures = {
@@ -208,16 +274,26 @@ impl JITArtifact {
 
         inner_jit.publish_eh_frame(eh_frame)?;
 
-        let finished_function_lengths = finished_functions
-            .values()
-            .map(|extent| extent.length)
-            .collect::<PrimaryMap<LocalFunctionIndex, usize>>()
-            .into_boxed_slice();
-        let finished_functions = finished_functions
-            .values()
-            .map(|extent| extent.ptr)
-            .collect::<PrimaryMap<LocalFunctionIndex, FunctionBodyPtr>>()
-            .into_boxed_slice();
+        // Describe the code to debuggers; if the image can't be built the
+        // code is simply not visible to them.
+        #[cfg(feature = "gdb-jit")]
+        let gdb_jit_registration = build_debug_image(
+            &serializable.compile_info.module,
+            &finished_functions,
+            &serializable.compilation.function_frame_info,
+            target.triple(),
+        )
+        .map(GdbJitImageRegistration::register);
+
+        let finished_function_extents = finished_functions.into_boxed_slice();
+        let finished_functions = match stubs {
+            Some(stubs) => stubs.clone(),
+            None => finished_function_extents
+                .values()
+                .map(|extent| extent.ptr)
+                .collect::<PrimaryMap<LocalFunctionIndex, FunctionBodyPtr>>()
+                .into_boxed_slice(),
+        };
         let finished_function_call_trampolines =
             finished_function_call_trampolines.into_boxed_slice();
         let finished_dynamic_function_trampolines =
@@ -231,10 +307,48 @@ impl JITArtifact {
             finished_dynamic_function_trampolines,
             signatures,
             frame_info_registration: Mutex::new(None),
-            finished_function_lengths,
+            finished_function_extents,
+            #[cfg(feature = "gdb-jit")]
+            gdb_jit_registration,
         })
     }
 
+    /// The compiled code of the functions, which is only different from
+    /// `finished_functions` when they are stubs.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn function_bodies(
+        &self,
+    ) -> impl Iterator<Item = (LocalFunctionIndex, FunctionBodyPtr)> + '_ {
+        self.finished_function_extents
+            .iter()
+            .map(|(index, extent)| (index, extent.ptr))
+    }
+
+    /// The object file describing the compiled code that is registered
+    /// with debuggers, if it could be built.
+    #[cfg(feature = "gdb-jit")]
+    pub fn debug_image(&self) -> Option<&[u8]> {
+        self.gdb_jit_registration
+            .as_ref()
+            .map(|registration| registration.image())
+    }
+
+    /// Serialize a compiled module.
+    pub(crate) fn serialize_module(
+        serializable: &SerializableModule,
+    ) -> Result<Vec<u8>, SerializeError> {
+        // let mut s = flexbuffers::FlexbufferSerializer::new();
+        // self.serializable.serialize(&mut s).map_err(|e| SerializeError::Generic(format!("{:?}", e)));
+        // Ok(s.take_buffer())
+        let bytes = bincode::serialize(serializable)
+            .map_err(|e| SerializeError::Generic(format!("{:?}", e)))?;
+
+        // Prepend the header.
+        let mut serialized = Self::MAGIC_HEADER.to_vec();
+        serialized.extend(bytes);
+        Ok(serialized)
+    }
+
     /// Get the default extension when serializing this artifact
     pub fn get_default_extension(_triple: &Triple) -> &'static str {
         // `.wjit` is the default extension for all the triples
@@ -262,19 +376,10 @@ impl Artifact for JITArtifact {
             return;
         }
 
-        let finished_function_extents = self
-            .finished_functions
-            .values()
-            .copied()
-            .zip(self.finished_function_lengths.values().copied())
-            .map(|(ptr, length)| FunctionExtent { ptr, length })
-            .collect::<PrimaryMap<LocalFunctionIndex, _>>()
-            .into_boxed_slice();
-
         let frame_infos = &self.serializable.compilation.function_frame_info;
         *info = register_frame_info(
             self.serializable.compile_info.module.clone(),
-            &finished_function_extents,
+            &self.finished_function_extents,
             frame_infos.clone(),
         );
     }
@@ -312,15 +417,6 @@ impl Artifact for JITArtifact {
     }
 
     fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
-        // let mut s = flexbuffers::FlexbufferSerializer::new();
-        // self.serializable.serialize(&mut s).map_err(|e| SerializeError::Generic(format!("{:?}", e)));
-        // Ok(s.take_buffer())
-        let bytes = bincode::serialize(&self.serializable)
-            .map_err(|e| SerializeError::Generic(format!("{:?}", e)))?;
-
-        // Prepend the header.
-        let mut serialized = Self::MAGIC_HEADER.to_vec();
-        serialized.extend(bytes);
-        Ok(serialized)
+        Self::serialize_module(&self.serializable)
     }
 }
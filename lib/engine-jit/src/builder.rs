@@ -1,12 +1,19 @@
-use crate::JITEngine;
+use crate::{JITEngine, ProfilingAgent, ProfilingStrategy};
 use wasmer_compiler::{CompilerConfig, Features, Target};
 
 /// The JIT builder
 pub struct JIT {
     #[allow(dead_code)]
     compiler_config: Option<Box<dyn CompilerConfig>>,
+    #[cfg(feature = "compiler")]
+    tier_up_compiler_config: Option<Box<dyn CompilerConfig>>,
+    #[cfg(feature = "compiler")]
+    tier_up_threshold: u64,
     target: Option<Target>,
     features: Option<Features>,
+    #[cfg(feature = "compiler")]
+    lazy: bool,
+    profiler: Option<Box<dyn ProfilingAgent>>,
 }
 
 impl JIT {
@@ -17,8 +24,15 @@ impl JIT {
     {
         Self {
             compiler_config: Some(compiler_config.into()),
+            #[cfg(feature = "compiler")]
+            tier_up_compiler_config: None,
+            #[cfg(feature = "compiler")]
+            tier_up_threshold: 0,
             target: None,
             features: None,
+            #[cfg(feature = "compiler")]
+            lazy: false,
+            profiler: None,
         }
     }
 
@@ -26,8 +40,15 @@ impl JIT {
     pub fn headless() -> Self {
         Self {
             compiler_config: None,
+            #[cfg(feature = "compiler")]
+            tier_up_compiler_config: None,
+            #[cfg(feature = "compiler")]
+            tier_up_threshold: 0,
             target: None,
             features: None,
+            #[cfg(feature = "compiler")]
+            lazy: false,
+            profiler: None,
         }
     }
 
@@ -37,6 +58,46 @@ impl JIT {
         self
     }
 
+    /// Enable tiered compilation, recompiling the modules with the given
+    /// (usually optimizing) compiler in the background.
+    ///
+    /// See [`JITEngine::set_tier_up_compiler`].
+    #[cfg(feature = "compiler")]
+    pub fn tier_up<T>(mut self, compiler_config: T) -> Self
+    where
+        T: Into<Box<dyn CompilerConfig>>,
+    {
+        self.tier_up_compiler_config = Some(compiler_config.into());
+        self
+    }
+
+    /// Only tier up the functions called at least `calls` times.
+    ///
+    /// See [`JITEngine::set_tier_up_threshold`].
+    #[cfg(feature = "compiler")]
+    pub fn tier_up_threshold(mut self, calls: u64) -> Self {
+        self.tier_up_threshold = calls;
+        self
+    }
+
+    /// Only compile functions when they are first called.
+    ///
+    /// See [`JITEngine::set_lazy`].
+    #[cfg(feature = "compiler")]
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// Tell profilers about the compiled code.
+    ///
+    /// Fails if the files the profiler reads can't be created. See
+    /// [`JITEngine::set_profiling_strategy`].
+    pub fn profiling_strategy(mut self, strategy: ProfilingStrategy) -> std::io::Result<Self> {
+        self.profiler = strategy.agent()?;
+        Ok(self)
+    }
+
     /// Set the features
     pub fn features(mut self, features: Features) -> Self {
         self.features = Some(features);
@@ -52,15 +113,26 @@ impl JIT {
                 .features
                 .unwrap_or_else(|| compiler_config.default_features_for_target(&target));
             let compiler = compiler_config.compiler();
-            JITEngine::new(compiler, target, features)
+            let mut engine = JITEngine::new(compiler, target, features);
+            if let Some(tier_up_compiler_config) = self.tier_up_compiler_config {
+                engine.set_tier_up_compiler(tier_up_compiler_config.compiler());
+            }
+            engine.set_tier_up_threshold(self.tier_up_threshold);
+            engine.set_lazy(self.lazy);
+            engine.set_profiler(self.profiler);
+            engine
         } else {
-            JITEngine::headless()
+            let mut engine = JITEngine::headless();
+            engine.set_profiler(self.profiler);
+            engine
         }
     }
 
     /// Build the `JITEngine` for this configuration
     #[cfg(not(feature = "compiler"))]
     pub fn engine(self) -> JITEngine {
-        JITEngine::headless()
+        let mut engine = JITEngine::headless();
+        engine.set_profiler(self.profiler);
+        engine
     }
 }
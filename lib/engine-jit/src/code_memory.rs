@@ -2,6 +2,7 @@
 // Attributions: https://github.com/wasmerio/wasmer/blob/master/ATTRIBUTIONS.md
 
 //! Memory management for executable code.
+use crate::profiling::ProfilingAgent;
 use crate::unwind::UnwindRegistry;
 use wasmer_compiler::{CompiledFunctionUnwindInfo, CustomSection, FunctionBody};
 use wasmer_vm::{Mmap, VMFunctionBody};
@@ -22,6 +23,10 @@ pub struct CodeMemory {
     unwind_registry: UnwindRegistry,
     mmap: Mmap,
     start_of_nonexecutable_pages: usize,
+    /// The address and length of the allocated functions.
+    functions: Vec<(usize, usize)>,
+    /// The names of the allocated functions, for profilers.
+    function_names: Vec<String>,
 }
 
 impl CodeMemory {
@@ -31,6 +36,8 @@ impl CodeMemory {
             unwind_registry: UnwindRegistry::new(),
             mmap: Mmap::new(),
             start_of_nonexecutable_pages: 0,
+            functions: vec![],
+            function_names: vec![],
         }
     }
 
@@ -96,6 +103,8 @@ impl CodeMemory {
 
             let vmfunc = Self::copy_function(&mut self.unwind_registry, func, func_buf);
             assert_eq!(vmfunc.as_ptr() as usize % ARCH_FUNCTION_ALIGNMENT, 0);
+            self.functions
+                .push((vmfunc.as_ptr() as usize, vmfunc.len()));
             function_result.push(vmfunc);
         }
         for section in executable_sections {
@@ -135,8 +144,21 @@ impl CodeMemory {
         ))
     }
 
+    /// Name the allocated functions, in the order they were allocated.
+    ///
+    /// The names are given to the profiler when the code is published.
+    pub fn set_function_names(&mut self, names: Vec<String>) {
+        self.function_names = names;
+    }
+
     /// Apply the page permissions.
     pub fn publish(&mut self) {
+        self.publish_with_profiler(None)
+    }
+
+    /// Apply the page permissions, and tell `profiler` about the
+    /// functions now that they are executable.
+    pub fn publish_with_profiler(&mut self, profiler: Option<&dyn ProfilingAgent>) {
         if self.mmap.is_empty() || self.start_of_nonexecutable_pages == 0 {
             return;
         }
@@ -149,6 +171,17 @@ impl CodeMemory {
             )
         }
         .expect("unable to make memory readonly and executable");
+
+        if let Some(profiler) = profiler {
+            for (index, (address, length)) in self.functions.iter().enumerate() {
+                let name = self
+                    .function_names
+                    .get(index)
+                    .cloned()
+                    .unwrap_or_else(|| format!("wasm-function[{}]", index));
+                profiler.register_function(&name, *address as *const u8, *length);
+            }
+        }
     }
 
     /// Calculates the allocation size of the given compiled function.
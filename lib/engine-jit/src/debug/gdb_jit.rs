@@ -0,0 +1,127 @@
+//! The GDB JIT compilation interface.
+//!
+//! Debuggers put a breakpoint in `__jit_debug_register_code`, and read
+//! the in-memory object files linked from `__jit_debug_descriptor` each
+//! time it is called.
+//!
+//! See <https://sourceware.org/gdb/current/onlinedocs/gdb/JIT-Interface.html>.
+
+use std::ptr;
+use std::sync::Mutex;
+
+#[repr(C)]
+struct JITCodeEntry {
+    next_entry: *mut JITCodeEntry,
+    prev_entry: *mut JITCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+const JIT_NOACTION: u32 = 0;
+const JIT_REGISTER_FN: u32 = 1;
+const JIT_UNREGISTER_FN: u32 = 2;
+
+#[repr(C)]
+struct JITDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JITCodeEntry,
+    first_entry: *mut JITCodeEntry,
+}
+
+#[no_mangle]
+#[used]
+static mut __jit_debug_descriptor: JITDescriptor = JITDescriptor {
+    version: 1,
+    action_flag: JIT_NOACTION,
+    relevant_entry: ptr::null_mut(),
+    first_entry: ptr::null_mut(),
+};
+
+#[no_mangle]
+#[inline(never)]
+extern "C" fn __jit_debug_register_code() {
+    // The debugger sets a breakpoint here; make sure the call is not
+    // optimized away.
+    unsafe {
+        let x = 0;
+        ptr::read_volatile(&x);
+    }
+}
+
+lazy_static::lazy_static! {
+    /// Serializes the updates of the descriptor's linked list.
+    static ref GDB_REGISTRATION: Mutex<()> = Mutex::new(());
+}
+
+/// An object file registered with the GDB JIT interface.
+///
+/// The image is unregistered when this is dropped.
+pub struct GdbJitImageRegistration {
+    entry: *mut JITCodeEntry,
+    image: Box<[u8]>,
+}
+
+impl GdbJitImageRegistration {
+    /// Register the in-memory object file `image` with the debugger.
+    pub fn register(image: Vec<u8>) -> Self {
+        let image = image.into_boxed_slice();
+        let entry = Box::into_raw(Box::new(JITCodeEntry {
+            next_entry: ptr::null_mut(),
+            prev_entry: ptr::null_mut(),
+            symfile_addr: image.as_ptr(),
+            symfile_size: image.len() as u64,
+        }));
+
+        let _guard = GDB_REGISTRATION.lock().unwrap();
+        unsafe {
+            let first = __jit_debug_descriptor.first_entry;
+            (*entry).next_entry = first;
+            if !first.is_null() {
+                (*first).prev_entry = entry;
+            }
+            __jit_debug_descriptor.first_entry = entry;
+            __jit_debug_descriptor.relevant_entry = entry;
+            __jit_debug_descriptor.action_flag = JIT_REGISTER_FN;
+            __jit_debug_register_code();
+            __jit_debug_descriptor.action_flag = JIT_NOACTION;
+            __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+        }
+
+        Self { entry, image }
+    }
+
+    /// The registered object file.
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+}
+
+impl Drop for GdbJitImageRegistration {
+    fn drop(&mut self) {
+        let _guard = GDB_REGISTRATION.lock().unwrap();
+        unsafe {
+            let entry = self.entry;
+            let prev = (*entry).prev_entry;
+            let next = (*entry).next_entry;
+            if prev.is_null() {
+                __jit_debug_descriptor.first_entry = next;
+            } else {
+                (*prev).next_entry = next;
+            }
+            if !next.is_null() {
+                (*next).prev_entry = prev;
+            }
+            __jit_debug_descriptor.relevant_entry = entry;
+            __jit_debug_descriptor.action_flag = JIT_UNREGISTER_FN;
+            __jit_debug_register_code();
+            __jit_debug_descriptor.action_flag = JIT_NOACTION;
+            __jit_debug_descriptor.relevant_entry = ptr::null_mut();
+            drop(Box::from_raw(entry));
+        }
+    }
+}
+
+// The entry is only accessed while holding `GDB_REGISTRATION`.
+unsafe impl Send for GdbJitImageRegistration {}
+unsafe impl Sync for GdbJitImageRegistration {}
@@ -0,0 +1,839 @@
+//! Build the in-memory ELF images describing JIT-compiled code to
+//! debuggers.
+//!
+//! The image is a shared object whose `.text` section is located at the
+//! compiled code, with a symbol for every function. When the module has
+//! Wasm DWARF, a native `.debug_line` is translated from it, and the
+//! locals the compiler tracked are described with the names it gives
+//! them.
+
+use gimli::write::{
+    Address, AttributeValue, DirectoryId, DwarfUnit, EndianVec, Expression, FileId, LineProgram,
+    LineString, Location, LocationList, Sections, UnitEntryId,
+};
+use gimli::{
+    ColumnType, DebugLine, DebugLineOffset, DebugLineStr, DebugStr, Encoding, EndianSlice, Format,
+    LineEncoding, LittleEndian, Reader, Register,
+};
+use object::elf;
+use object::{bytes_of, U16, U32, U64};
+use std::collections::{BTreeMap, HashMap};
+use wasmer_compiler::{CompiledFunctionFrameInfo, LocalLocations, Triple, ValueLocation};
+use wasmer_engine::{FunctionExtent, SerializableFunctionFrameInfo};
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{LocalFunctionIndex, Type};
+use wasmer_vm::ModuleInfo;
+
+/// A row of the Wasm line programs, keyed by module offset.
+#[derive(Clone, Copy)]
+struct WasmRow {
+    offset: u64,
+    file: Option<FileId>,
+    line: u64,
+    column: u64,
+}
+
+/// The names the Wasm DWARF gives to the locals of the functions, keyed
+/// by the module offset of the functions and the index of the locals.
+type LocalNames = BTreeMap<u64, HashMap<u32, Vec<u8>>>;
+
+/// A function symbol of the image.
+struct FunctionSymbol {
+    name: Vec<u8>,
+    address: u64,
+    size: u64,
+}
+
+/// Build the debug image for the compiled functions of `module`.
+///
+/// Returns `None` for targets the image can't be built for.
+pub fn build_debug_image(
+    module: &ModuleInfo,
+    functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    frame_infos: &PrimaryMap<LocalFunctionIndex, SerializableFunctionFrameInfo>,
+    triple: &Triple,
+) -> Option<Vec<u8>> {
+    let machine = match triple.architecture {
+        wasmer_compiler::Architecture::X86_64 => elf::EM_X86_64,
+        wasmer_compiler::Architecture::Aarch64(_) => elf::EM_AARCH64,
+        _ => return None,
+    };
+    let start = functions.values().map(|f| f.ptr.0 as u64).min()?;
+    let end = functions
+        .values()
+        .map(|f| f.ptr.0 as u64 + f.length as u64)
+        .max()?;
+
+    let frame_infos = frame_infos
+        .values()
+        .map(|info| match info {
+            SerializableFunctionFrameInfo::Processed(info) => info.clone(),
+            SerializableFunctionFrameInfo::Unprocessed(info) => info.deserialize(),
+        })
+        .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 8,
+    };
+    let mut dwarf = DwarfUnit::new(encoding);
+    let module_name = module.name.clone().unwrap_or_else(|| "wasm".to_string());
+    dwarf.unit.line_program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(b".".to_vec()),
+        LineString::String(module_name.clone().into_bytes()),
+        None,
+    );
+    let rows = read_wasm_rows(module, &mut dwarf.unit.line_program);
+    let local_names = read_wasm_local_names(module).unwrap_or_default();
+
+    let root = dwarf.unit.root();
+    let entry = dwarf.unit.get_mut(root);
+    entry.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(module_name.into_bytes()),
+    );
+    entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(start)),
+    );
+    entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(end - start));
+    entry.set(gimli::DW_AT_stmt_list, AttributeValue::LineProgramRef);
+
+    let mut base_types = HashMap::new();
+    let mut symbols = Vec::new();
+    for (local_index, function) in functions.iter() {
+        let func_index = module.func_index(local_index);
+        let name = module
+            .function_names
+            .get(&func_index)
+            .cloned()
+            .unwrap_or_else(|| format!("wasm-function[{}]", func_index.index()));
+        let address = function.ptr.0 as u64;
+        let frame_info = &frame_infos[local_index];
+        symbols.push(FunctionSymbol {
+            name: name.clone().into_bytes(),
+            address,
+            size: function.length as u64,
+        });
+
+        let subprogram = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        let entry = dwarf.unit.get_mut(subprogram);
+        entry.set(gimli::DW_AT_name, AttributeValue::String(name.into_bytes()));
+        entry.set(
+            gimli::DW_AT_low_pc,
+            AttributeValue::Address(Address::Constant(address)),
+        );
+        entry.set(
+            gimli::DW_AT_high_pc,
+            AttributeValue::Udata(function.length as u64),
+        );
+
+        // The Wasm DWARF addresses a function by the start of its body
+        // size, which precedes the body by at most 5 bytes.
+        let body_offset = u64::from(frame_info.address_map.start_srcloc.bits());
+        let names = local_names
+            .range(body_offset.saturating_sub(5)..=body_offset)
+            .next_back()
+            .map(|(_, names)| names);
+        let param_count = module.signatures[module.functions[func_index]]
+            .params()
+            .len();
+        for (index, local) in frame_info.locals.iter().enumerate() {
+            let name = names
+                .and_then(|names| names.get(&(index as u32)))
+                .cloned()
+                .unwrap_or_else(|| format!("var{}", index).into_bytes());
+            let base_type = *base_types
+                .entry(local.ty)
+                .or_insert_with(|| add_base_type(&mut dwarf, local.ty));
+            add_local(
+                &mut dwarf,
+                subprogram,
+                if index < param_count {
+                    gimli::DW_TAG_formal_parameter
+                } else {
+                    gimli::DW_TAG_variable
+                },
+                name,
+                base_type,
+                address,
+                local,
+            );
+        }
+
+        if let Some(rows) = &rows {
+            add_function_rows(
+                &mut dwarf.unit.line_program,
+                rows,
+                address,
+                function.length as u64,
+                frame_info,
+            );
+        }
+    }
+
+    let mut sections = Sections::new(EndianVec::new(gimli::RunTimeEndian::Little));
+    dwarf.write(&mut sections).ok()?;
+    let mut debug_sections = Vec::new();
+    sections
+        .for_each(|id, data| -> Result<(), ()> {
+            if !data.slice().is_empty() {
+                debug_sections.push((id.name(), data.slice().to_vec()));
+            }
+            Ok(())
+        })
+        .ok()?;
+
+    Some(write_image(
+        machine,
+        start,
+        end - start,
+        &symbols,
+        &debug_sections,
+    ))
+}
+
+/// Add the base type describing values of the Wasm type `ty`.
+fn add_base_type(dwarf: &mut DwarfUnit, ty: Type) -> UnitEntryId {
+    let (name, encoding, size): (&[u8], _, _) = match ty {
+        Type::I32 => (b"i32", gimli::DW_ATE_signed, 4),
+        Type::I64 => (b"i64", gimli::DW_ATE_signed, 8),
+        Type::F32 => (b"f32", gimli::DW_ATE_float, 4),
+        Type::F64 => (b"f64", gimli::DW_ATE_float, 8),
+        Type::V128 => (b"v128", gimli::DW_ATE_unsigned, 16),
+        Type::ExternRef => (b"externref", gimli::DW_ATE_address, 8),
+        Type::FuncRef => (b"funcref", gimli::DW_ATE_address, 8),
+    };
+    let root = dwarf.unit.root();
+    let base_type = dwarf.unit.add(root, gimli::DW_TAG_base_type);
+    let entry = dwarf.unit.get_mut(base_type);
+    entry.set(gimli::DW_AT_name, AttributeValue::String(name.to_vec()));
+    entry.set(gimli::DW_AT_encoding, AttributeValue::Encoding(encoding));
+    entry.set(gimli::DW_AT_byte_size, AttributeValue::Data1(size));
+    base_type
+}
+
+/// Describe a local of the function at `address` as a child of
+/// `subprogram`.
+fn add_local(
+    dwarf: &mut DwarfUnit,
+    subprogram: UnitEntryId,
+    tag: gimli::DwTag,
+    name: Vec<u8>,
+    base_type: UnitEntryId,
+    address: u64,
+    local: &LocalLocations,
+) {
+    let ranges = local.ranges.iter().filter(|range| range.start < range.end);
+    let locations = ranges
+        .map(|range| {
+            let mut data = Expression::new();
+            match range.location {
+                ValueLocation::Register(register) => data.op_reg(Register(register)),
+                ValueLocation::Memory { register, offset } => {
+                    data.op_breg(Register(register), offset)
+                }
+            }
+            Location::OffsetPair {
+                begin: u64::from(range.start),
+                end: u64::from(range.end),
+                data,
+            }
+        })
+        .collect::<Vec<_>>();
+    let location = if locations.is_empty() {
+        None
+    } else {
+        // The offsets are relative to the function.
+        let base = Location::BaseAddress {
+            address: Address::Constant(address),
+        };
+        let locations = std::iter::once(base).chain(locations).collect();
+        Some(dwarf.unit.locations.add(LocationList(locations)))
+    };
+
+    let variable = dwarf.unit.add(subprogram, tag);
+    let entry = dwarf.unit.get_mut(variable);
+    entry.set(gimli::DW_AT_name, AttributeValue::String(name));
+    entry.set(gimli::DW_AT_type, AttributeValue::UnitRef(base_type));
+    if let Some(location) = location {
+        entry.set(
+            gimli::DW_AT_location,
+            AttributeValue::LocationListRef(location),
+        );
+    }
+}
+
+/// The contents of the custom section `name` of `module`, empty if it
+/// has none.
+fn custom_section<'a>(module: &'a ModuleInfo, name: &str) -> &'a [u8] {
+    module
+        .custom_sections
+        .get(name)
+        .map(|index| &*module.custom_sections_data[*index])
+        .unwrap_or(&[][..])
+}
+
+/// Read the names the module's `.debug_info` custom section gives to the
+/// locals of its functions.
+///
+/// Only the variables located with `DW_OP_WASM_location` in a local are
+/// named; the variables of inlined functions are ignored.
+fn read_wasm_local_names(module: &ModuleInfo) -> Option<LocalNames> {
+    const DW_OP_WASM_LOCATION: u8 = 0xed;
+    const WASM_LOCAL: u8 = 0x00;
+
+    let dwarf = gimli::Dwarf::load(
+        |id| -> Result<_, gimli::Error> {
+            Ok(EndianSlice::new(
+                custom_section(module, id.name()),
+                LittleEndian,
+            ))
+        },
+        |_| Ok(EndianSlice::new(&[][..], LittleEndian)),
+    )
+    .ok()?;
+
+    let mut names = LocalNames::new();
+    let mut headers = dwarf.units();
+    while let Some(header) = headers.next().ok()? {
+        let unit = dwarf.unit(header).ok()?;
+        let mut entries = unit.entries();
+        let mut depth = 0;
+        // The enclosing subprograms and inlined subroutines, with the
+        // module offset of the subprograms.
+        let mut scopes: Vec<(isize, Option<u64>)> = Vec::new();
+        while let Some((delta, entry)) = entries.next_dfs().ok()? {
+            depth += delta;
+            while scopes.last().map_or(false, |(scope, _)| *scope >= depth) {
+                scopes.pop();
+            }
+            match entry.tag() {
+                gimli::DW_TAG_subprogram => {
+                    let offset = match entry.attr_value(gimli::DW_AT_low_pc).ok()? {
+                        Some(gimli::AttributeValue::Addr(address)) => {
+                            Some(module.code_section_offset as u64 + address)
+                        }
+                        _ => None,
+                    };
+                    scopes.push((depth, offset));
+                }
+                gimli::DW_TAG_inlined_subroutine => scopes.push((depth, None)),
+                gimli::DW_TAG_formal_parameter | gimli::DW_TAG_variable => {
+                    let offset = match scopes.last() {
+                        Some((_, Some(offset))) => *offset,
+                        _ => continue,
+                    };
+                    let mut location = match entry.attr_value(gimli::DW_AT_location).ok()? {
+                        Some(gimli::AttributeValue::Exprloc(expression)) => expression.0,
+                        _ => continue,
+                    };
+                    if location.read_u8() != Ok(DW_OP_WASM_LOCATION)
+                        || location.read_u8() != Ok(WASM_LOCAL)
+                    {
+                        continue;
+                    }
+                    let index = match location.read_uleb128() {
+                        Ok(index) => index as u32,
+                        Err(_) => continue,
+                    };
+                    let name = match entry.attr_value(gimli::DW_AT_name).ok()? {
+                        Some(value) => match dwarf.attr_string(&unit, value) {
+                            Ok(name) => name.slice().to_vec(),
+                            Err(_) => continue,
+                        },
+                        None => continue,
+                    };
+                    names.entry(offset).or_default().insert(index, name);
+                }
+                _ => {}
+            }
+        }
+    }
+    Some(names)
+}
+
+/// Read the rows of all the line programs in the module's `.debug_line`
+/// custom section, sorted by module offset.
+///
+/// The files they refer to are added to `program`.
+fn read_wasm_rows(module: &ModuleInfo, program: &mut LineProgram) -> Option<Vec<WasmRow>> {
+    let section = |name: &str| custom_section(module, name);
+    let debug_line_data = section(".debug_line");
+    if debug_line_data.is_empty() {
+        return None;
+    }
+    let debug_line = DebugLine::new(debug_line_data, LittleEndian);
+    let debug_str = DebugStr::new(section(".debug_str"), LittleEndian);
+    let debug_line_str =
+        DebugLineStr::from(EndianSlice::new(section(".debug_line_str"), LittleEndian));
+    let attr_string = |value: gimli::AttributeValue<EndianSlice<LittleEndian>>| match value {
+        gimli::AttributeValue::String(s) => Some(s.slice().to_vec()),
+        gimli::AttributeValue::DebugStrRef(offset) => {
+            debug_str.get_str(offset).ok().map(|s| s.slice().to_vec())
+        }
+        gimli::AttributeValue::DebugLineStrRef(offset) => debug_line_str
+            .get_str(offset)
+            .ok()
+            .map(|s| s.slice().to_vec()),
+        _ => None,
+    };
+
+    let mut directories: HashMap<Vec<u8>, DirectoryId> = HashMap::new();
+    let mut files: HashMap<(Vec<u8>, Vec<u8>), FileId> = HashMap::new();
+    let mut rows = Vec::new();
+    let mut offset = 0;
+    while offset < debug_line_data.len() {
+        let wasm_program = debug_line
+            .program(DebugLineOffset(offset), 4, None, None)
+            .ok()?;
+        let header = wasm_program.header();
+        offset += header.unit_length() + header.format().initial_length_size() as usize;
+
+        let mut wasm_rows = wasm_program.rows();
+        while let Ok(Some((header, row))) = wasm_rows.next_row() {
+            let file = row.file(header).and_then(|file| {
+                let name = attr_string(file.path_name())?;
+                let directory = file
+                    .directory(header)
+                    .and_then(|directory| attr_string(directory))
+                    .unwrap_or_default();
+                let directory_id = *directories.entry(directory.clone()).or_insert_with(|| {
+                    program.add_directory(LineString::String(directory.clone()))
+                });
+                Some(*files.entry((directory, name.clone())).or_insert_with(|| {
+                    program.add_file(LineString::String(name), directory_id, None)
+                }))
+            });
+            rows.push(WasmRow {
+                offset: module.code_section_offset as u64 + row.address(),
+                // The end of a sequence is not part of any line.
+                file: if row.end_sequence() { None } else { file },
+                line: row.line().map(u64::from).unwrap_or(0),
+                column: match row.column() {
+                    ColumnType::LeftEdge => 0,
+                    ColumnType::Column(column) => u64::from(column),
+                },
+            });
+        }
+    }
+    rows.sort_by_key(|row| row.offset);
+    Some(rows)
+}
+
+/// Add the native line rows of a function to `program`.
+fn add_function_rows(
+    program: &mut LineProgram,
+    rows: &[WasmRow],
+    address: u64,
+    length: u64,
+    frame_info: &CompiledFunctionFrameInfo,
+) {
+    program.begin_sequence(Some(Address::Constant(address)));
+    let mut last = None;
+    for instruction in &frame_info.address_map.instructions {
+        if instruction.srcloc.is_default() {
+            continue;
+        }
+        let srcloc = u64::from(instruction.srcloc.bits());
+        let row = match rows.binary_search_by_key(&srcloc, |row| row.offset) {
+            Ok(index) => rows[index],
+            Err(0) => continue,
+            Err(index) => rows[index - 1],
+        };
+        let file = match row.file {
+            Some(file) => file,
+            None => continue,
+        };
+        if last == Some((file, row.line, row.column)) {
+            continue;
+        }
+        last = Some((file, row.line, row.column));
+        let native_row = program.row();
+        native_row.address_offset = instruction.code_offset as u64;
+        native_row.file = file;
+        native_row.line = row.line;
+        native_row.column = row.column;
+        program.generate_row();
+    }
+    program.end_sequence(length);
+}
+
+/// Write a shared object whose `.text` section is located at `address`,
+/// as debuggers expect JIT images to be.
+///
+/// The code itself is not part of the image, which only has the
+/// `symbols` and the `debug_sections`.
+fn write_image(
+    machine: u16,
+    address: u64,
+    size: u64,
+    symbols: &[FunctionSymbol],
+    debug_sections: &[(&str, Vec<u8>)],
+) -> Vec<u8> {
+    const TEXT_INDEX: u16 = 1;
+    let e = object::LittleEndian;
+
+    // The string tables, starting with the empty string.
+    let mut strtab = vec![0];
+    let mut shstrtab = vec![0];
+    fn add_string(table: &mut Vec<u8>, string: &[u8]) -> u32 {
+        let offset = table.len() as u32;
+        table.extend_from_slice(string);
+        table.push(0);
+        offset
+    }
+
+    let mut symtab = bytes_of(&elf::Sym64 {
+        st_name: U32::new(e, 0),
+        st_info: 0,
+        st_other: 0,
+        st_shndx: U16::new(e, 0),
+        st_value: U64::new(e, 0),
+        st_size: U64::new(e, 0),
+    })
+    .to_vec();
+    for symbol in symbols {
+        let sym = elf::Sym64 {
+            st_name: U32::new(e, add_string(&mut strtab, &symbol.name)),
+            st_info: (elf::STB_LOCAL << 4) | elf::STT_FUNC,
+            st_other: elf::STV_DEFAULT,
+            st_shndx: U16::new(e, TEXT_INDEX),
+            st_value: U64::new(e, symbol.address),
+            st_size: U64::new(e, symbol.size),
+        };
+        symtab.extend_from_slice(bytes_of(&sym));
+    }
+
+    struct Section<'a> {
+        name: u32,
+        sh_type: u32,
+        flags: u64,
+        address: u64,
+        size: u64,
+        link: u32,
+        info: u32,
+        align: u64,
+        entsize: u64,
+        data: &'a [u8],
+    }
+    fn section(name: u32, sh_type: u32, data: &[u8]) -> Section<'_> {
+        Section {
+            name,
+            sh_type,
+            flags: 0,
+            address: 0,
+            size: data.len() as u64,
+            link: 0,
+            info: 0,
+            align: 1,
+            entsize: 0,
+            data,
+        }
+    }
+
+    let mut sections = vec![
+        section(0, elf::SHT_NULL, &[]),
+        Section {
+            flags: u64::from(elf::SHF_ALLOC | elf::SHF_EXECINSTR),
+            address,
+            size,
+            align: 16,
+            ..section(add_string(&mut shstrtab, b".text"), elf::SHT_NOBITS, &[])
+        },
+    ];
+    for (name, data) in debug_sections {
+        sections.push(section(
+            add_string(&mut shstrtab, name.as_bytes()),
+            elf::SHT_PROGBITS,
+            data,
+        ));
+    }
+    let symtab_index = sections.len() as u32;
+    let symtab_name = add_string(&mut shstrtab, b".symtab");
+    let strtab_name = add_string(&mut shstrtab, b".strtab");
+    let shstrtab_name = add_string(&mut shstrtab, b".shstrtab");
+    sections.push(Section {
+        link: symtab_index + 1,
+        // All the symbols are local.
+        info: symbols.len() as u32 + 1,
+        align: 8,
+        entsize: std::mem::size_of::<elf::Sym64<object::LittleEndian>>() as u64,
+        ..section(symtab_name, elf::SHT_SYMTAB, &symtab)
+    });
+    sections.push(section(strtab_name, elf::SHT_STRTAB, &strtab));
+    sections.push(section(shstrtab_name, elf::SHT_STRTAB, &shstrtab));
+
+    let align = |image: &mut Vec<u8>, align: u64| {
+        let len = (image.len() as u64 + align - 1) / align * align;
+        image.resize(len as usize, 0);
+    };
+    let mut image = vec![0; std::mem::size_of::<elf::FileHeader64<object::LittleEndian>>()];
+    let mut offsets = Vec::with_capacity(sections.len());
+    for section in &sections {
+        align(&mut image, section.align);
+        offsets.push(image.len() as u64);
+        image.extend_from_slice(section.data);
+    }
+    align(&mut image, 8);
+    let section_headers = image.len() as u64;
+    for (index, (section, offset)) in sections.iter().zip(offsets).enumerate() {
+        let header = elf::SectionHeader64 {
+            sh_name: U32::new(e, section.name),
+            sh_type: U32::new(e, section.sh_type),
+            sh_flags: U64::new(e, section.flags),
+            sh_addr: U64::new(e, section.address),
+            sh_offset: U64::new(e, if index == 0 { 0 } else { offset }),
+            sh_size: U64::new(e, section.size),
+            sh_link: U32::new(e, section.link),
+            sh_info: U32::new(e, section.info),
+            sh_addralign: U64::new(e, section.align),
+            sh_entsize: U64::new(e, section.entsize),
+        };
+        image.extend_from_slice(bytes_of(&header));
+    }
+
+    let header = elf::FileHeader64 {
+        e_ident: elf::Ident {
+            magic: elf::ELFMAG,
+            class: elf::ELFCLASS64,
+            data: elf::ELFDATA2LSB,
+            version: elf::EV_CURRENT,
+            os_abi: elf::ELFOSABI_NONE,
+            abi_version: 0,
+            padding: [0; 7],
+        },
+        e_type: U16::new(e, elf::ET_DYN),
+        e_machine: U16::new(e, machine),
+        e_version: U32::new(e, u32::from(elf::EV_CURRENT)),
+        e_entry: U64::new(e, 0),
+        e_phoff: U64::new(e, 0),
+        e_shoff: U64::new(e, section_headers),
+        e_flags: U32::new(e, 0),
+        e_ehsize: U16::new(
+            e,
+            std::mem::size_of::<elf::FileHeader64<object::LittleEndian>>() as u16,
+        ),
+        e_phentsize: U16::new(e, 0),
+        e_phnum: U16::new(e, 0),
+        e_shentsize: U16::new(
+            e,
+            std::mem::size_of::<elf::SectionHeader64<object::LittleEndian>>() as u16,
+        ),
+        e_shnum: U16::new(e, sections.len() as u16),
+        e_shstrndx: U16::new(e, sections.len() as u16 - 1),
+    };
+    image[..std::mem::size_of_val(&header)].copy_from_slice(bytes_of(&header));
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::read::elf::{ElfFile64, FileHeader};
+    use object::{Architecture, Bytes, Object, ObjectSection, ObjectSymbol};
+    use std::sync::Arc;
+    use wasmer_compiler::{FunctionAddressMap, SourceLoc, ValueLocationRange};
+    use wasmer_types::FunctionType;
+    use wasmer_vm::{FunctionBodyPtr, VMFunctionBody};
+
+    const ADDRESS: u64 = 0x1_0000;
+    const LENGTH: usize = 0x40;
+    const CODE_SECTION_OFFSET: usize = 0x20;
+    const BODY_OFFSET: usize = 0x30;
+
+    /// The Wasm DWARF sections of a function whose body starts at
+    /// `BODY_OFFSET`, naming its second local.
+    fn wasm_dwarf() -> Vec<(&'static str, Vec<u8>)> {
+        let encoding = Encoding {
+            format: Format::Dwarf32,
+            version: 4,
+            address_size: 4,
+        };
+        let mut dwarf = DwarfUnit::new(encoding);
+        let root = dwarf.unit.root();
+        let subprogram = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+        dwarf.unit.get_mut(subprogram).set(
+            gimli::DW_AT_low_pc,
+            // The start of the body size.
+            AttributeValue::Address(Address::Constant(
+                (BODY_OFFSET - CODE_SECTION_OFFSET - 1) as u64,
+            )),
+        );
+        let variable = dwarf.unit.add(subprogram, gimli::DW_TAG_variable);
+        let entry = dwarf.unit.get_mut(variable);
+        entry.set(
+            gimli::DW_AT_name,
+            AttributeValue::String(b"counter".to_vec()),
+        );
+        entry.set(
+            gimli::DW_AT_location,
+            AttributeValue::Exprloc(Expression::raw(vec![0xed, 0x00, 0x01])),
+        );
+
+        let mut sections = Sections::new(EndianVec::new(LittleEndian));
+        dwarf.write(&mut sections).unwrap();
+        let mut dwarf_sections = Vec::new();
+        sections
+            .for_each(|id, data| -> Result<(), ()> {
+                dwarf_sections.push((id.name(), data.slice().to_vec()));
+                Ok(())
+            })
+            .unwrap();
+        dwarf_sections
+    }
+
+    fn debug_image(triple: &Triple) -> Option<Vec<u8>> {
+        let mut module = ModuleInfo::new();
+        let signature = module
+            .signatures
+            .push(FunctionType::new(vec![Type::I32], vec![]));
+        let func_index = module.functions.push(signature);
+        module.function_names.insert(func_index, "run".to_string());
+        module.code_section_offset = CODE_SECTION_OFFSET;
+        for (name, data) in wasm_dwarf() {
+            let index = module
+                .custom_sections_data
+                .push(Arc::from(data.into_boxed_slice()));
+            module.custom_sections.insert(name.to_string(), index);
+        }
+
+        let mut functions = PrimaryMap::new();
+        functions.push(FunctionExtent {
+            ptr: FunctionBodyPtr(ADDRESS as usize as *const VMFunctionBody),
+            length: LENGTH,
+        });
+        let mut frame_infos = PrimaryMap::new();
+        frame_infos.push(SerializableFunctionFrameInfo::Processed(
+            CompiledFunctionFrameInfo {
+                traps: vec![],
+                address_map: FunctionAddressMap {
+                    instructions: vec![],
+                    start_srcloc: SourceLoc::new(BODY_OFFSET as u32),
+                    end_srcloc: SourceLoc::new(BODY_OFFSET as u32 + 8),
+                    body_offset: 0,
+                    body_len: LENGTH,
+                },
+                locals: vec![
+                    LocalLocations {
+                        ty: Type::I32,
+                        ranges: vec![ValueLocationRange {
+                            start: 0,
+                            end: 0x10,
+                            location: ValueLocation::Register(5),
+                        }],
+                    },
+                    LocalLocations {
+                        ty: Type::I64,
+                        ranges: vec![ValueLocationRange {
+                            start: 0x8,
+                            end: 0x30,
+                            location: ValueLocation::Memory {
+                                register: 6,
+                                offset: -16,
+                            },
+                        }],
+                    },
+                ],
+            },
+        ));
+        build_debug_image(&module, &functions, &frame_infos, triple)
+    }
+
+    #[test]
+    fn locates_text_at_the_code() {
+        let image = debug_image(&"x86_64-unknown-linux-gnu".parse().unwrap()).unwrap();
+        let file = ElfFile64::<object::LittleEndian>::parse(&*image).unwrap();
+        let header = elf::FileHeader64::<object::LittleEndian>::parse(Bytes(&image)).unwrap();
+        assert_eq!(header.e_type(object::LittleEndian), elf::ET_DYN);
+        assert_eq!(file.architecture(), Architecture::X86_64);
+
+        let text = file.section_by_name(".text").unwrap();
+        assert_eq!(text.address(), ADDRESS);
+        assert_eq!(text.size(), LENGTH as u64);
+
+        let symbol = file.symbols().find(|symbol| symbol.name() == Ok("run"));
+        let symbol = symbol.unwrap();
+        assert_eq!(symbol.address(), ADDRESS);
+        assert_eq!(symbol.size(), LENGTH as u64);
+    }
+
+    #[test]
+    fn uses_the_target_architecture() {
+        let image = debug_image(&"aarch64-unknown-linux-gnu".parse().unwrap()).unwrap();
+        let file = ElfFile64::<object::LittleEndian>::parse(&*image).unwrap();
+        assert_eq!(file.architecture(), Architecture::Aarch64);
+
+        assert!(debug_image(&"riscv64gc-unknown-linux-gnu".parse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn describes_locals() {
+        let image = debug_image(&"x86_64-unknown-linux-gnu".parse().unwrap()).unwrap();
+        let file = ElfFile64::<object::LittleEndian>::parse(&*image).unwrap();
+        let dwarf = gimli::Dwarf::load(
+            |id| -> Result<_, gimli::Error> {
+                let data = file
+                    .section_by_name(id.name())
+                    .map(|section| section.data().unwrap())
+                    .unwrap_or(&[]);
+                Ok(EndianSlice::new(data, LittleEndian))
+            },
+            |_| Ok(EndianSlice::new(&[][..], LittleEndian)),
+        )
+        .unwrap();
+
+        let header = dwarf.units().next().unwrap().unwrap();
+        let unit = dwarf.unit(header).unwrap();
+        let mut entries = unit.entries();
+        let mut locals = Vec::new();
+        while let Some((_, entry)) = entries.next_dfs().unwrap() {
+            if entry.tag() != gimli::DW_TAG_formal_parameter
+                && entry.tag() != gimli::DW_TAG_variable
+            {
+                continue;
+            }
+            let name = entry.attr_value(gimli::DW_AT_name).unwrap().unwrap();
+            let name = dwarf.attr_string(&unit, name).unwrap().slice().to_vec();
+            let location = match entry.attr_value(gimli::DW_AT_location).unwrap() {
+                Some(gimli::AttributeValue::LocationListsRef(offset)) => offset,
+                value => panic!("unexpected location {:?}", value),
+            };
+            let mut locations = dwarf.locations(&unit, location).unwrap();
+            let location = locations.next().unwrap().unwrap();
+            assert!(locations.next().unwrap().is_none());
+            locals.push((
+                entry.tag(),
+                name,
+                location.range.begin - ADDRESS,
+                location.range.end - ADDRESS,
+                location.data.0.slice().to_vec(),
+            ));
+        }
+
+        assert_eq!(
+            locals,
+            vec![
+                (
+                    gimli::DW_TAG_formal_parameter,
+                    b"var0".to_vec(),
+                    0,
+                    0x10,
+                    // DW_OP_reg5
+                    vec![0x55],
+                ),
+                (
+                    gimli::DW_TAG_variable,
+                    b"counter".to_vec(),
+                    0x8,
+                    0x30,
+                    // DW_OP_breg6 -16
+                    vec![0x76, 0x70],
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,7 @@
+//! Make JIT-compiled code visible to native debuggers.
+
+mod gdb_jit;
+mod image;
+
+pub use self::gdb_jit::GdbJitImageRegistration;
+pub use self::image::build_debug_image;
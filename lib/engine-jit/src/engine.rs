@@ -1,6 +1,11 @@
 //! JIT compilation.
 
+#[cfg(feature = "compiler")]
+use crate::function_table::FunctionTable;
+use crate::profiling::{ProfilingAgent, ProfilingStrategy};
 use crate::{CodeMemory, JITArtifact};
+#[cfg(feature = "compiler")]
+use crate::{JITLazyArtifact, JITTieredArtifact};
 use std::sync::{Arc, Mutex};
 #[cfg(feature = "compiler")]
 use wasmer_compiler::Compiler;
@@ -8,7 +13,7 @@ use wasmer_compiler::{
     CompileError, CustomSection, CustomSectionProtection, FunctionBody, SectionIndex, Target,
 };
 use wasmer_engine::{Artifact, DeserializeError, Engine, EngineId, FunctionExtent, Tunables};
-use wasmer_types::entity::PrimaryMap;
+use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::Features;
 use wasmer_types::{FunctionIndex, FunctionType, LocalFunctionIndex, SignatureIndex};
 use wasmer_vm::{
@@ -20,6 +25,17 @@ use wasmer_vm::{
 #[derive(Clone)]
 pub struct JITEngine {
     inner: Arc<Mutex<JITEngineInner>>,
+    /// The compiler that recompiles modules in the background, if
+    /// tiered compilation is enabled.
+    #[cfg(feature = "compiler")]
+    tier_up_compiler: Option<Arc<dyn Compiler>>,
+    /// The number of calls after which a function is recompiled, when
+    /// tiered compilation is enabled.
+    #[cfg(feature = "compiler")]
+    tier_up_threshold: u64,
+    /// Whether modules are only compiled when first instantiated.
+    #[cfg(feature = "compiler")]
+    lazy: bool,
     /// The target for the compiler
     target: Arc<Target>,
     engine_id: EngineId,
@@ -31,11 +47,15 @@ impl JITEngine {
     pub fn new(compiler: Box<dyn Compiler>, target: Target, features: Features) -> Self {
         Self {
             inner: Arc::new(Mutex::new(JITEngineInner {
-                compiler: Some(compiler),
+                compiler: Some(compiler.into()),
                 code_memory: vec![],
                 signatures: SignatureRegistry::new(),
                 features,
+                profiler: None,
             })),
+            tier_up_compiler: None,
+            tier_up_threshold: 0,
+            lazy: false,
             target: Arc::new(target),
             engine_id: EngineId::default(),
         }
@@ -62,12 +82,76 @@ impl JITEngine {
                 code_memory: vec![],
                 signatures: SignatureRegistry::new(),
                 features: Features::default(),
+                profiler: None,
             })),
+            #[cfg(feature = "compiler")]
+            tier_up_compiler: None,
+            #[cfg(feature = "compiler")]
+            tier_up_threshold: 0,
+            #[cfg(feature = "compiler")]
+            lazy: false,
             target: Arc::new(Target::default()),
             engine_id: EngineId::default(),
         }
     }
 
+    /// Enable tiered compilation.
+    ///
+    /// Modules are compiled with the engine's compiler first, so they can
+    /// be instantiated right away, and their functions are recompiled
+    /// with `compiler` in a background thread. Each function is switched
+    /// to its optimized code as soon as it is ready, in every instance of
+    /// the module, including the running ones.
+    #[cfg(feature = "compiler")]
+    pub fn set_tier_up_compiler(&mut self, compiler: Box<dyn Compiler>) {
+        self.tier_up_compiler = Some(compiler.into());
+    }
+
+    /// Only recompile the functions called at least `calls` times with
+    /// the tier-up compiler, hottest first.
+    ///
+    /// With the default of 0, all the functions are recompiled.
+    #[cfg(feature = "compiler")]
+    pub fn set_tier_up_threshold(&mut self, calls: u64) {
+        self.tier_up_threshold = calls;
+    }
+
+    /// Enable lazy compilation.
+    ///
+    /// Compiling a module then only translates it, and each of its
+    /// functions is compiled the first time it is called. Serializing the
+    /// module compiles all of it. Lazy compilation can't be combined with
+    /// tiered compilation.
+    #[cfg(feature = "compiler")]
+    pub fn set_lazy(&mut self, lazy: bool) {
+        self.lazy = lazy;
+    }
+
+    /// Tell profilers about the code compiled from now on.
+    ///
+    /// Fails if the files the profiler reads can't be created.
+    pub fn set_profiling_strategy(&mut self, strategy: ProfilingStrategy) -> std::io::Result<()> {
+        self.set_profiler(strategy.agent()?);
+        Ok(())
+    }
+
+    /// Tell `profiler` about the code compiled from now on.
+    pub(crate) fn set_profiler(&mut self, profiler: Option<Box<dyn ProfilingAgent>>) {
+        self.inner_mut().profiler = profiler;
+    }
+
+    /// The compiler used to tier up modules, if any.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn tier_up_compiler(&self) -> Option<Arc<dyn Compiler>> {
+        self.tier_up_compiler.clone()
+    }
+
+    /// The number of calls after which functions are tiered up.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn tier_up_threshold(&self) -> u64 {
+        self.tier_up_threshold
+    }
+
     pub(crate) fn inner(&self) -> std::sync::MutexGuard<'_, JITEngineInner> {
         self.inner.lock().unwrap()
     }
@@ -107,6 +191,17 @@ impl Engine for JITEngine {
         binary: &[u8],
         tunables: &dyn Tunables,
     ) -> Result<Arc<dyn Artifact>, CompileError> {
+        if self.lazy {
+            if self.tier_up_compiler.is_some() {
+                return Err(CompileError::Codegen(
+                    "lazy compilation can't be combined with tiered compilation".to_string(),
+                ));
+            }
+            return Ok(Arc::new(JITLazyArtifact::new(&self, binary, tunables)?));
+        }
+        if self.tier_up_compiler.is_some() {
+            return Ok(Arc::new(JITTieredArtifact::new(&self, binary, tunables)?));
+        }
         Ok(Arc::new(JITArtifact::new(&self, binary, tunables)?))
     }
 
@@ -141,7 +236,7 @@ impl Engine for JITEngine {
 pub struct JITEngineInner {
     /// The compiler
     #[cfg(feature = "compiler")]
-    compiler: Option<Box<dyn Compiler>>,
+    compiler: Option<Arc<dyn Compiler>>,
     /// The features to compile the Wasm module with
     features: Features,
     /// The code memory is responsible of publishing the compiled
@@ -150,6 +245,8 @@ pub struct JITEngineInner {
     /// The signature registry is used mainly to operate with trampolines
     /// performantly.
     signatures: SignatureRegistry,
+    /// The agent told about the published functions, if profiling.
+    profiler: Option<Box<dyn ProfilingAgent>>,
 }
 
 impl JITEngineInner {
@@ -162,6 +259,14 @@ impl JITEngineInner {
         Ok(&**self.compiler.as_ref().unwrap())
     }
 
+    /// Gets the compiler associated to this engine, to compile with it
+    /// once the engine is unlocked.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn shared_compiler(&self) -> Result<Arc<dyn Compiler>, CompileError> {
+        self.compiler()?;
+        Ok(self.compiler.clone().unwrap())
+    }
+
     /// Validate the module
     #[cfg(feature = "compiler")]
     pub fn validate<'data>(&self, data: &'data [u8]) -> Result<(), CompileError> {
@@ -186,7 +291,7 @@ impl JITEngineInner {
     #[allow(clippy::type_complexity)]
     pub(crate) fn allocate(
         &mut self,
-        _module: &ModuleInfo,
+        module: &ModuleInfo,
         functions: &PrimaryMap<LocalFunctionIndex, FunctionBody>,
         function_call_trampolines: &PrimaryMap<SignatureIndex, FunctionBody>,
         dynamic_function_trampolines: &PrimaryMap<FunctionIndex, FunctionBody>,
@@ -208,7 +313,25 @@ impl JITEngineInner {
         let (executable_sections, data_sections): (Vec<_>, _) = custom_sections
             .values()
             .partition(|section| section.protection == CustomSectionProtection::ReadExecute);
-        self.code_memory.push(CodeMemory::new());
+        let mut code_memory = CodeMemory::new();
+        if self.profiler.is_some() {
+            let names = functions
+                .keys()
+                .map(|local_index| function_name(module, local_index))
+                .chain(
+                    function_call_trampolines
+                        .keys()
+                        .map(|index| format!("wasm-trampoline[{}]", index.index())),
+                )
+                .chain(
+                    dynamic_function_trampolines
+                        .keys()
+                        .map(|index| format!("wasm-dynamic-trampoline[{}]", index.index())),
+                )
+                .collect();
+            code_memory.set_function_names(names);
+        }
+        self.code_memory.push(code_memory);
 
         let (mut allocated_functions, allocated_executable_sections, allocated_data_sections) =
             self.code_memory
@@ -250,22 +373,11 @@ impl JITEngineInner {
             .map(|slice| FunctionBodyPtr(slice.as_ptr()))
             .collect::<PrimaryMap<FunctionIndex, _>>();
 
-        let mut exec_iter = allocated_executable_sections.iter();
-        let mut data_iter = allocated_data_sections.iter();
-        let allocated_custom_sections = custom_sections
-            .iter()
-            .map(|(_, section)| {
-                SectionBodyPtr(
-                    if section.protection == CustomSectionProtection::ReadExecute {
-                        exec_iter.next()
-                    } else {
-                        data_iter.next()
-                    }
-                    .unwrap()
-                    .as_ptr(),
-                )
-            })
-            .collect::<PrimaryMap<SectionIndex, _>>();
+        let allocated_custom_sections = custom_section_pointers(
+            custom_sections,
+            &allocated_executable_sections,
+            &allocated_data_sections,
+        );
 
         Ok((
             allocated_functions_result,
@@ -275,9 +387,84 @@ impl JITEngineInner {
         ))
     }
 
+    /// Allocate the function `index` of `module`, compiled on its own,
+    /// into memory.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn allocate_function(
+        &mut self,
+        module: &ModuleInfo,
+        index: LocalFunctionIndex,
+        function: &FunctionBody,
+        custom_sections: &PrimaryMap<SectionIndex, CustomSection>,
+    ) -> Result<(FunctionExtent, PrimaryMap<SectionIndex, SectionBodyPtr>), CompileError> {
+        let (executable_sections, data_sections): (Vec<_>, _) = custom_sections
+            .values()
+            .partition(|section| section.protection == CustomSectionProtection::ReadExecute);
+        let mut code_memory = CodeMemory::new();
+        if self.profiler.is_some() {
+            code_memory.set_function_names(vec![function_name(module, index)]);
+        }
+        self.code_memory.push(code_memory);
+
+        let (allocated_functions, allocated_executable_sections, allocated_data_sections) = self
+            .code_memory
+            .last_mut()
+            .unwrap()
+            .allocate(
+                &[function],
+                executable_sections.as_slice(),
+                data_sections.as_slice(),
+            )
+            .map_err(|message| {
+                CompileError::Resource(format!(
+                    "failed to allocate memory for functions: {}",
+                    message
+                ))
+            })?;
+
+        let allocated_function = FunctionExtent {
+            ptr: FunctionBodyPtr(allocated_functions[0].as_ptr()),
+            length: allocated_functions[0].len(),
+        };
+        let allocated_custom_sections = custom_section_pointers(
+            custom_sections,
+            &allocated_executable_sections,
+            &allocated_data_sections,
+        );
+        Ok((allocated_function, allocated_custom_sections))
+    }
+
+    /// Allocate the stubs of the `num_functions` local functions of a
+    /// module, and make them executable.
+    ///
+    /// See `FunctionTable::new` for `lazy` and `call_threshold`.
+    #[cfg(feature = "compiler")]
+    pub(crate) fn allocate_function_table(
+        &mut self,
+        num_functions: usize,
+        lazy: bool,
+        call_threshold: Option<u64>,
+    ) -> Result<FunctionTable, CompileError> {
+        let mut code_memory = CodeMemory::new();
+        let table = FunctionTable::new(&mut code_memory, num_functions, lazy, call_threshold)
+            .map_err(|message| {
+                CompileError::Resource(format!(
+                    "failed to allocate the function stubs: {}",
+                    message
+                ))
+            })?;
+        code_memory.publish();
+        self.code_memory.push(code_memory);
+        Ok(table)
+    }
+
     /// Make memory containing compiled code executable.
     pub(crate) fn publish_compiled_code(&mut self) {
-        self.code_memory.last_mut().unwrap().publish();
+        let profiler = self.profiler.as_deref();
+        self.code_memory
+            .last_mut()
+            .unwrap()
+            .publish_with_profiler(profiler);
     }
 
     /// Register DWARF-type exception handling information associated with the code.
@@ -298,3 +485,38 @@ impl JITEngineInner {
         &self.signatures
     }
 }
+
+/// The name of a function for profilers.
+fn function_name(module: &ModuleInfo, local_index: LocalFunctionIndex) -> String {
+    let index = module.func_index(local_index);
+    module
+        .function_names
+        .get(&index)
+        .cloned()
+        .unwrap_or_else(|| format!("wasm-function[{}]", index.index()))
+}
+
+/// The addresses of the allocated custom sections, given the executable
+/// and data sections in the order they were allocated.
+fn custom_section_pointers(
+    custom_sections: &PrimaryMap<SectionIndex, CustomSection>,
+    allocated_executable_sections: &[&mut [u8]],
+    allocated_data_sections: &[&mut [u8]],
+) -> PrimaryMap<SectionIndex, SectionBodyPtr> {
+    let mut exec_iter = allocated_executable_sections.iter();
+    let mut data_iter = allocated_data_sections.iter();
+    custom_sections
+        .iter()
+        .map(|(_, section)| {
+            SectionBodyPtr(
+                if section.protection == CustomSectionProtection::ReadExecute {
+                    exec_iter.next()
+                } else {
+                    data_iter.next()
+                }
+                .unwrap()
+                .as_ptr(),
+            )
+        })
+        .collect::<PrimaryMap<SectionIndex, _>>()
+}
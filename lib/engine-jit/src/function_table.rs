@@ -0,0 +1,394 @@
+//! Function tables give every local function of a module a stub, an
+//! address that stays the same while the code behind it changes.
+//!
+//! A stub jumps to the address in its function's slot, so the code of a
+//! function can be swapped by updating the slot: every caller, including
+//! the function tables and exports of the instances created already, goes
+//! through the stub. Stubs may count the calls to their function, and
+//! the slots of a lazily compiled function first point to a thunk asking
+//! a resolver for the code of the function. A counting stub goes through
+//! the thunk once, when its function gets hot.
+//!
+//! Only x86-64 is supported so far.
+
+use crate::link::link_function;
+use crate::{CodeMemory, JITEngine};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wasmer_compiler::{
+    Compilation, CompileError, CustomSection, CustomSectionProtection, SectionBody,
+};
+use wasmer_engine::{
+    register_function_frame_info, GlobalFrameInfoRegistration, SerializableFunctionFrameInfo,
+};
+use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
+use wasmer_types::LocalFunctionIndex;
+use wasmer_vm::{FunctionBodyPtr, ModuleInfo};
+
+/// The size of a stub, and of a lazy thunk.
+const ENTRY_SIZE: usize = 32;
+
+/// A function returning the address of the code of the function at the
+/// given index, called with the context of the table.
+///
+/// It is called from a thunk, before the function itself, and has to
+/// either return or raise a trap.
+pub(crate) type ResolveFunction = unsafe extern "sysv64" fn(*const u8, u64) -> usize;
+
+/// The stubs of the local functions of a module.
+pub(crate) struct FunctionTable {
+    stubs: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+    /// The address of the resolver context, then of the resolve
+    /// function, followed by the slot and the call counter of each
+    /// function.
+    ///
+    /// The stubs and the resolver address these words directly, so they
+    /// must not move while the code can run.
+    data: Box<[AtomicUsize]>,
+    thunks: Option<usize>,
+    call_threshold: Option<u64>,
+}
+
+// The table only hands out the code it allocated, and its data is only
+// ever accessed atomically.
+unsafe impl Send for FunctionTable {}
+unsafe impl Sync for FunctionTable {}
+
+impl FunctionTable {
+    /// Allocate the stubs of `num_functions` functions in `code_memory`.
+    ///
+    /// When `lazy`, the slots point to the thunks calling the resolver,
+    /// which must be set with `set_resolver` before the functions are
+    /// called. Otherwise the slots must be set before their stubs are
+    /// called. With a `call_threshold`, the stubs count the calls to their
+    /// function, and the call reaching the threshold goes through the
+    /// resolver too, which must then return the code in the slot.
+    pub(crate) fn new(
+        code_memory: &mut CodeMemory,
+        num_functions: usize,
+        lazy: bool,
+        call_threshold: Option<u64>,
+    ) -> Result<Self, String> {
+        if !cfg!(target_arch = "x86_64") {
+            return Err("function stubs are only supported on x86-64".to_string());
+        }
+
+        let has_resolver = lazy || call_threshold.is_some();
+        let num_thunks = if has_resolver { num_functions } else { 0 };
+        let code_len = (num_functions + num_thunks) * ENTRY_SIZE
+            + if has_resolver { RESOLVER_SIZE } else { 0 };
+        let code_section = CustomSection {
+            protection: CustomSectionProtection::ReadExecute,
+            bytes: SectionBody::new_with_vec(vec![0; code_len]),
+            relocations: vec![],
+        };
+        let (_, mut code, _) = code_memory.allocate(&[], &[&code_section], &[])?;
+        let code = &mut code[0][..code_len];
+        let code_address = code.as_ptr() as usize;
+        // The data is written at run time, so it is kept out of the code
+        // memory, which isn't writable once published.
+        let data = (0..2 + 2 * num_functions)
+            .map(|_| AtomicUsize::new(0))
+            .collect::<Box<[_]>>();
+        let data_address = data.as_ptr() as usize;
+
+        let slot = |index: usize| data_address + (2 + 2 * index) * 8;
+        let counter = |index: usize| slot(index) + 8;
+        let thunk = |index: usize| code_address + (num_functions + index) * ENTRY_SIZE;
+        let resolver = code_address + (num_functions + num_thunks) * ENTRY_SIZE;
+
+        let mut assembler = Assembler::new(code_address);
+        for index in 0..num_functions {
+            if call_threshold.is_some() {
+                // The counter starts at minus the threshold, so that
+                // exactly one call brings it to zero.
+                assembler.mov_r11(counter(index));
+                assembler.lock_inc_r11();
+                assembler.je(thunk(index));
+                assembler.jmp_r11(-8);
+            } else {
+                assembler.mov_r11(slot(index));
+                assembler.jmp_r11(0);
+            }
+            assembler.align(ENTRY_SIZE);
+        }
+        if has_resolver {
+            for index in 0..num_functions {
+                assembler.push_imm32(index as u32);
+                assembler.jmp(resolver);
+                assembler.align(ENTRY_SIZE);
+            }
+            assembler.resolver(data_address, data_address + 8);
+            debug_assert!(assembler.len() <= code_len);
+        }
+        let bytes = assembler.finish();
+        code[..bytes.len()].copy_from_slice(&bytes);
+
+        for index in 0..num_functions {
+            if lazy {
+                data[2 + 2 * index].store(thunk(index), Ordering::Relaxed);
+            }
+            if let Some(threshold) = call_threshold {
+                data[3 + 2 * index].store(threshold.wrapping_neg() as usize, Ordering::Relaxed);
+            }
+        }
+        let stubs = (0..num_functions)
+            .map(|index| FunctionBodyPtr((code_address + index * ENTRY_SIZE) as *const _))
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>()
+            .into_boxed_slice();
+
+        Ok(Self {
+            stubs,
+            data,
+            thunks: if lazy {
+                Some(code_address + num_functions * ENTRY_SIZE)
+            } else {
+                None
+            },
+            call_threshold,
+        })
+    }
+
+    /// The stubs of the functions.
+    pub(crate) fn stubs(&self) -> &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr> {
+        &self.stubs
+    }
+
+    /// Call `resolve` with `context` to get the code of the lazily
+    /// compiled functions.
+    pub(crate) fn set_resolver(&self, context: *const u8, resolve: ResolveFunction) {
+        self.word(0).store(context as usize, Ordering::Release);
+        self.word(1).store(resolve as usize, Ordering::Release);
+    }
+
+    /// Make the stub of `index` jump to `code`.
+    pub(crate) fn set(&self, index: LocalFunctionIndex, code: FunctionBodyPtr) {
+        self.word(2 + 2 * index.index())
+            .store(*code as usize, Ordering::Release);
+    }
+
+    /// The code the stub of `index` jumps to, unless it's still the
+    /// lazy thunk.
+    pub(crate) fn get(&self, index: LocalFunctionIndex) -> Option<FunctionBodyPtr> {
+        let code = self.word(2 + 2 * index.index()).load(Ordering::Acquire);
+        let thunk = self
+            .thunks
+            .map(|thunks| thunks + index.index() * ENTRY_SIZE);
+        if code == 0 || Some(code) == thunk {
+            None
+        } else {
+            Some(FunctionBodyPtr(code as *const _))
+        }
+    }
+
+    /// Load the function `index` of `module`, compiled on its own into
+    /// `compilation`, and make its stub jump to it.
+    ///
+    /// The returned registration keeps the frame information of the
+    /// function available to traps.
+    pub(crate) fn publish(
+        &self,
+        jit: &JITEngine,
+        module: &Arc<ModuleInfo>,
+        index: LocalFunctionIndex,
+        compilation: &Compilation,
+    ) -> Result<Option<GlobalFrameInfoRegistration>, CompileError> {
+        let function = compilation.get(LocalFunctionIndex::new(0));
+        let custom_sections = compilation.get_custom_sections();
+        let mut inner_jit = jit.inner_mut();
+        let (extent, allocated_sections) =
+            inner_jit.allocate_function(module, index, &function.body, &custom_sections)?;
+        link_function(
+            index,
+            &extent,
+            &function.jt_offsets,
+            &function.relocations,
+            &allocated_sections,
+            &compilation.get_custom_section_relocations(),
+            &self.stubs,
+        );
+        let eh_frame = compilation.get_debug().map(|debug| unsafe {
+            std::slice::from_raw_parts(
+                *allocated_sections[debug.eh_frame],
+                custom_sections[debug.eh_frame].bytes.len(),
+            )
+        });
+        inner_jit.publish_compiled_code();
+        inner_jit.publish_eh_frame(eh_frame)?;
+        drop(inner_jit);
+
+        let registration = register_function_frame_info(
+            module.clone(),
+            index,
+            &extent,
+            SerializableFunctionFrameInfo::Processed(function.frame_info.clone()),
+        );
+        self.set(index, extent.ptr);
+        Ok(registration)
+    }
+
+    /// The number of calls to `index` counted so far, or zero if the
+    /// stubs don't count the calls.
+    pub(crate) fn calls(&self, index: LocalFunctionIndex) -> u64 {
+        match self.call_threshold {
+            Some(threshold) => (self.word(3 + 2 * index.index()).load(Ordering::Relaxed) as u64)
+                .wrapping_add(threshold),
+            None => 0,
+        }
+    }
+
+    fn word(&self, index: usize) -> &AtomicUsize {
+        &self.data[index]
+    }
+}
+
+/// The size of the code of the resolver.
+const RESOLVER_SIZE: usize = 512;
+
+/// The registers the resolver saves, besides `rbp` and the vector
+/// registers: all the registers that may hold arguments, or that are
+/// clobbered by a call.
+const SAVED_REGISTERS: [u8; 9] = [0, 1, 2, 6, 7, 8, 9, 10, 11];
+
+/// An x86-64 assembler for the few instructions of the stubs.
+struct Assembler {
+    address: usize,
+    bytes: Vec<u8>,
+}
+
+impl Assembler {
+    fn new(address: usize) -> Self {
+        Self {
+            address,
+            bytes: vec![],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    /// Emit the displacement to `target` from the end of the instruction,
+    /// whose remaining bytes after the displacement are `after`.
+    fn rel32(&mut self, target: usize, after: usize) {
+        let end = self.address + self.bytes.len() + 4 + after;
+        let displacement = (target as i64 - end as i64) as i32;
+        self.emit(&displacement.to_le_bytes());
+    }
+
+    /// Pad with `int3` up to a multiple of `alignment`.
+    fn align(&mut self, alignment: usize) {
+        while self.bytes.len() % alignment != 0 {
+            self.emit(&[0xcc]);
+        }
+    }
+
+    /// `mov r11, address`
+    ///
+    /// `r11` is a scratch register, which holds no argument.
+    fn mov_r11(&mut self, address: usize) {
+        self.emit(&[0x49, 0xbb]);
+        self.emit(&(address as u64).to_le_bytes());
+    }
+
+    /// `lock inc qword ptr [r11]`
+    fn lock_inc_r11(&mut self) {
+        self.emit(&[0xf0, 0x49, 0xff, 0x03]);
+    }
+
+    /// `jmp qword ptr [r11 + displacement]`
+    fn jmp_r11(&mut self, displacement: i8) {
+        self.emit(&[0x41, 0xff, 0x63, displacement as u8]);
+    }
+
+    /// `je target`
+    fn je(&mut self, target: usize) {
+        self.emit(&[0x0f, 0x84]);
+        self.rel32(target, 0);
+    }
+
+    /// `push imm32`
+    fn push_imm32(&mut self, value: u32) {
+        self.emit(&[0x68]);
+        self.emit(&value.to_le_bytes());
+    }
+
+    /// `jmp target`
+    fn jmp(&mut self, target: usize) {
+        self.emit(&[0xe9]);
+        self.rel32(target, 0);
+    }
+
+    /// `movdqu [rsp + 16 * register], xmm<register>` when `store`, and the
+    /// other way around otherwise.
+    fn movdqu_rsp(&mut self, register: u8, store: bool) {
+        self.emit(&[0xf3]);
+        if register >= 8 {
+            self.emit(&[0x44]);
+        }
+        let opcode = if store { 0x7f } else { 0x6f };
+        self.emit(&[0x0f, opcode, 0x84 | ((register & 7) << 3), 0x24]);
+        self.emit(&(16 * register as u32).to_le_bytes());
+    }
+
+    /// The code shared by the thunks, which push the index of their
+    /// function before jumping to it.
+    ///
+    /// It saves the registers holding the arguments of the function,
+    /// calls the resolve function at `resolve` with the context at
+    /// `context` and the index, then jumps to the address it returns with
+    /// the registers restored, as if the function had been called.
+    fn resolver(&mut self, context: usize, resolve: usize) {
+        let start = self.bytes.len();
+        // push rbp; mov rbp, rsp
+        self.emit(&[0x55, 0x48, 0x89, 0xe5]);
+        for &register in SAVED_REGISTERS.iter() {
+            // push <register>
+            if register >= 8 {
+                self.emit(&[0x41]);
+            }
+            self.emit(&[0x50 + (register & 7)]);
+        }
+        // sub rsp, 256; and rsp, -16
+        self.emit(&[0x48, 0x81, 0xec, 0x00, 0x01, 0x00, 0x00]);
+        self.emit(&[0x48, 0x83, 0xe4, 0xf0]);
+        for register in 0..16 {
+            self.movdqu_rsp(register, true);
+        }
+        // mov rax, context; mov rdi, [rax]
+        self.emit(&[0x48, 0xb8]);
+        self.emit(&(context as u64).to_le_bytes());
+        self.emit(&[0x48, 0x8b, 0x38]);
+        // mov rsi, [rbp + 8]
+        self.emit(&[0x48, 0x8b, 0x75, 0x08]);
+        // mov rax, resolve; call [rax]
+        self.emit(&[0x48, 0xb8]);
+        self.emit(&(resolve as u64).to_le_bytes());
+        self.emit(&[0xff, 0x10]);
+        // mov [rbp + 8], rax
+        self.emit(&[0x48, 0x89, 0x45, 0x08]);
+        for register in 0..16 {
+            self.movdqu_rsp(register, false);
+        }
+        // lea rsp, [rbp - 8 * SAVED_REGISTERS.len()]
+        self.emit(&[0x48, 0x8d, 0x65, (-8 * SAVED_REGISTERS.len() as i8) as u8]);
+        for &register in SAVED_REGISTERS.iter().rev() {
+            // pop <register>
+            if register >= 8 {
+                self.emit(&[0x41]);
+            }
+            self.emit(&[0x58 + (register & 7)]);
+        }
+        // pop rbp; ret
+        self.emit(&[0x5d, 0xc3]);
+        debug_assert!(self.bytes.len() - start <= RESOLVER_SIZE);
+    }
+}
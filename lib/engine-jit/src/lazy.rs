@@ -0,0 +1,325 @@
+//! Define `JITLazyArtifact`, an artifact whose functions are only
+//! compiled when they are first called.
+
+use crate::function_table::FunctionTable;
+use crate::serialize::SerializableModule;
+use crate::{JITArtifact, JITEngine};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use wasmer_compiler::{
+    CompileError, CompileModuleInfo, Compiler, Features, FunctionBodyData, ModuleEnvironment,
+    ModuleTranslationState,
+};
+use wasmer_engine::{
+    compile_submodules, Artifact, Engine, GlobalFrameInfoRegistration, SerializeError, Tunables,
+};
+use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
+use wasmer_types::{
+    FunctionIndex, LocalFunctionIndex, MemoryIndex, OwnedDataInitializer, SignatureIndex,
+    TableIndex,
+};
+use wasmer_vm::{
+    raise_user_trap, FunctionBodyPtr, MemoryStyle, ModuleInfo, TableStyle, VMSharedSignatureIndex,
+    VMTrampoline,
+};
+
+/// A translated wasm module whose functions are compiled the first time
+/// they are called.
+///
+/// Each function starts as a stub calling the compiler, which compiles
+/// just that function and points the stub to the compiled code.
+/// Serializing the module compiles all of it.
+pub struct JITLazyArtifact {
+    functions: Box<LazyFunctions>,
+    data_initializers: Box<[OwnedDataInitializer]>,
+    finished_function_call_trampolines: BoxedSlice<SignatureIndex, VMTrampoline>,
+    finished_dynamic_function_trampolines: BoxedSlice<FunctionIndex, FunctionBodyPtr>,
+    signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
+}
+
+/// The functions of a `JITLazyArtifact`, with what it takes to compile
+/// them.
+struct LazyFunctions {
+    jit: JITEngine,
+    compiler: Arc<dyn Compiler>,
+    data: Vec<u8>,
+    compile_info: CompileModuleInfo,
+    module_translation_state: ModuleTranslationState,
+    /// The range of each function body in `data`, and its offset in the
+    /// module.
+    bodies: PrimaryMap<LocalFunctionIndex, (Range<usize>, usize)>,
+    table: FunctionTable,
+    /// Locked while the function is compiled.
+    compiling: PrimaryMap<LocalFunctionIndex, Mutex<()>>,
+    frame_info_registrations: Mutex<Vec<GlobalFrameInfoRegistration>>,
+}
+
+impl JITLazyArtifact {
+    /// Translate a data buffer into a `JITLazyArtifact`, deferring the
+    /// compilation of its functions.
+    pub fn new(
+        jit: &JITEngine,
+        data: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<Self, CompileError> {
+        let environ = ModuleEnvironment::new();
+        let mut translation = environ.translate(data).map_err(CompileError::Wasm)?;
+        compile_submodules(jit, &mut translation.module, tunables)?;
+
+        let mut inner_jit = jit.inner_mut();
+        let compiler = inner_jit.shared_compiler()?;
+        compiler.transform_module_info(&mut translation.module);
+        compiler.analyze_function_bodies(&translation.module, &translation.function_body_inputs);
+
+        let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = translation
+            .module
+            .memories
+            .values()
+            .map(|memory_type| tunables.memory_style(memory_type))
+            .collect();
+        let table_styles: PrimaryMap<TableIndex, TableStyle> = translation
+            .module
+            .tables
+            .values()
+            .map(|table_type| tunables.table_style(table_type))
+            .collect();
+        let mut compile_info = CompileModuleInfo {
+            module: Arc::new(translation.module),
+            features: inner_jit.features().clone(),
+            memory_styles,
+            table_styles,
+        };
+        // SAFETY: Calling `unwrap` is correct since
+        // `environ.translate()` above will write some data into
+        // `module_translation_state`.
+        let module_translation_state = translation.module_translation_state.unwrap();
+
+        // Compiling no function still gives the trampolines.
+        let compilation = compiler.compile_module(
+            jit.target(),
+            &mut compile_info,
+            &module_translation_state,
+            PrimaryMap::new(),
+        )?;
+        let (_, finished_function_call_trampolines, finished_dynamic_function_trampolines, _) =
+            inner_jit.allocate(
+                &compile_info.module,
+                &PrimaryMap::new(),
+                &compilation.get_function_call_trampolines(),
+                &compilation.get_dynamic_function_trampolines(),
+                &PrimaryMap::new(),
+            )?;
+        inner_jit.publish_compiled_code();
+
+        let signatures = {
+            let signature_registry = inner_jit.signatures();
+            compile_info
+                .module
+                .signatures
+                .values()
+                .map(|sig| signature_registry.register(sig))
+                .collect::<PrimaryMap<_, _>>()
+        };
+
+        let bodies = translation
+            .function_body_inputs
+            .values()
+            .map(|body| {
+                let start = body.data.as_ptr() as usize - data.as_ptr() as usize;
+                (start..start + body.data.len(), body.module_offset)
+            })
+            .collect::<PrimaryMap<LocalFunctionIndex, _>>();
+        let table = inner_jit.allocate_function_table(bodies.len(), true, None)?;
+        drop(inner_jit);
+
+        let data_initializers = translation
+            .data_initializers
+            .iter()
+            .map(OwnedDataInitializer::new)
+            .collect::<Vec<_>>()
+            .into_boxed_slice();
+
+        let functions = Box::new(LazyFunctions {
+            jit: jit.clone(),
+            compiler,
+            data: data.to_vec(),
+            compile_info,
+            module_translation_state,
+            compiling: bodies.keys().map(|_| Mutex::new(())).collect(),
+            bodies,
+            table,
+            frame_info_registrations: Mutex::new(vec![]),
+        });
+        functions
+            .table
+            .set_resolver(&*functions as *const LazyFunctions as *const u8, resolve);
+
+        Ok(Self {
+            functions,
+            data_initializers,
+            finished_function_call_trampolines: finished_function_call_trampolines
+                .into_boxed_slice(),
+            finished_dynamic_function_trampolines: finished_dynamic_function_trampolines
+                .into_boxed_slice(),
+            signatures: signatures.into_boxed_slice(),
+        })
+    }
+
+    /// Whether the local function `index` has been compiled already.
+    pub fn is_compiled(&self, index: LocalFunctionIndex) -> bool {
+        self.functions.table.get(index).is_some()
+    }
+
+    /// Compile the local function `index`, unless it has been compiled
+    /// already.
+    pub fn compile_function(&self, index: LocalFunctionIndex) -> Result<(), CompileError> {
+        self.functions.compile(index).map(drop)
+    }
+}
+
+impl LazyFunctions {
+    /// Compile the function `index` and point its stub to it, unless
+    /// that was done already.
+    fn compile(&self, index: LocalFunctionIndex) -> Result<FunctionBodyPtr, CompileError> {
+        let _compiling = self.compiling[index].lock().unwrap();
+        if let Some(body) = self.table.get(index) {
+            return Ok(body);
+        }
+
+        let (range, module_offset) = &self.bodies[index];
+        let input = FunctionBodyData {
+            data: &self.data[range.clone()],
+            module_offset: *module_offset,
+        };
+        let compilation = self.compiler.compile_function(
+            self.jit.target(),
+            &mut self.compile_info(),
+            &self.module_translation_state,
+            index,
+            &input,
+        )?;
+        let registration =
+            self.table
+                .publish(&self.jit, &self.compile_info.module, index, &compilation)?;
+        if let Some(registration) = registration {
+            self.frame_info_registrations
+                .lock()
+                .unwrap()
+                .push(registration);
+        }
+        Ok(self.table.get(index).unwrap())
+    }
+
+    /// Compile the whole module.
+    fn compile_module(&self) -> Result<SerializableModule, CompileError> {
+        let environ = ModuleEnvironment::new();
+        let mut translation = environ.translate(&self.data).map_err(CompileError::Wasm)?;
+        // The middlewares transformed the module when the artifact was
+        // created, and apply to one module only.
+        translation.module = (*self.compile_info.module).clone();
+        JITArtifact::compile(
+            &*self.compiler,
+            &self.jit,
+            &self.compile_info.features,
+            translation,
+            self.compile_info.memory_styles.clone(),
+            self.compile_info.table_styles.clone(),
+        )
+    }
+
+    fn compile_info(&self) -> CompileModuleInfo {
+        CompileModuleInfo {
+            module: self.compile_info.module.clone(),
+            features: self.compile_info.features.clone(),
+            memory_styles: self.compile_info.memory_styles.clone(),
+            table_styles: self.compile_info.table_styles.clone(),
+        }
+    }
+}
+
+/// Compile a function on its first call, see `FunctionTable`.
+unsafe extern "sysv64" fn resolve(context: *const u8, index: u64) -> usize {
+    // The caller may be deep into its stack, so the function is compiled
+    // on a thread of its own. The caller waits for it, so the functions
+    // outlive the thread.
+    let context = context as usize;
+    let index = LocalFunctionIndex::new(index as usize);
+    let result = thread::Builder::new()
+        .name("wasmer-lazy-compile".to_string())
+        .spawn(move || {
+            let functions = &*(context as *const LazyFunctions);
+            functions.compile(index).map(|body| *body as usize)
+        })
+        .map_err(|e| CompileError::Resource(format!("failed to spawn the compiler thread: {}", e)))
+        .and_then(|handle| {
+            handle
+                .join()
+                .unwrap_or_else(|_| Err(CompileError::Codegen("the compiler panicked".to_string())))
+        });
+    match result {
+        Ok(body) => body,
+        Err(error) => raise_user_trap(Box::new(error)),
+    }
+}
+
+impl Artifact for JITLazyArtifact {
+    fn module(&self) -> Arc<ModuleInfo> {
+        self.functions.compile_info.module.clone()
+    }
+
+    fn module_ref(&self) -> &ModuleInfo {
+        &self.functions.compile_info.module
+    }
+
+    fn module_mut(&mut self) -> Option<&mut ModuleInfo> {
+        Arc::get_mut(&mut self.functions.compile_info.module)
+    }
+
+    fn register_frame_info(&self) {
+        // The frame information of each function is registered when it
+        // is compiled.
+    }
+
+    fn features(&self) -> &Features {
+        &self.functions.compile_info.features
+    }
+
+    fn data_initializers(&self) -> &[OwnedDataInitializer] {
+        &*self.data_initializers
+    }
+
+    fn memory_styles(&self) -> &PrimaryMap<MemoryIndex, MemoryStyle> {
+        &self.functions.compile_info.memory_styles
+    }
+
+    fn table_styles(&self) -> &PrimaryMap<TableIndex, TableStyle> {
+        &self.functions.compile_info.table_styles
+    }
+
+    fn finished_functions(&self) -> &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr> {
+        self.functions.table.stubs()
+    }
+
+    fn finished_function_call_trampolines(&self) -> &BoxedSlice<SignatureIndex, VMTrampoline> {
+        &self.finished_function_call_trampolines
+    }
+
+    fn finished_dynamic_function_trampolines(&self) -> &BoxedSlice<FunctionIndex, FunctionBodyPtr> {
+        &self.finished_dynamic_function_trampolines
+    }
+
+    fn signatures(&self) -> &BoxedSlice<SignatureIndex, VMSharedSignatureIndex> {
+        &self.signatures
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        // The functions compiled so far are each in code of their own, so
+        // the whole module is compiled to be serialized.
+        let serializable = self
+            .functions
+            .compile_module()
+            .map_err(|e| SerializeError::Generic(format!("failed to compile the module: {}", e)))?;
+        JITArtifact::serialize_module(&serializable)
+    }
+}
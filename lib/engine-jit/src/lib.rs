@@ -27,16 +27,30 @@
 mod artifact;
 mod builder;
 mod code_memory;
+#[cfg(feature = "gdb-jit")]
+mod debug;
 mod engine;
+#[cfg(feature = "compiler")]
+mod function_table;
+#[cfg(feature = "compiler")]
+mod lazy;
 mod link;
+mod profiling;
 mod serialize;
+#[cfg(feature = "compiler")]
+mod tiered;
 mod unwind;
 
 pub use crate::artifact::JITArtifact;
 pub use crate::builder::JIT;
 pub use crate::code_memory::CodeMemory;
 pub use crate::engine::JITEngine;
+#[cfg(feature = "compiler")]
+pub use crate::lazy::JITLazyArtifact;
 pub use crate::link::link_module;
+pub use crate::profiling::{ProfilingAgent, ProfilingStrategy};
+#[cfg(feature = "compiler")]
+pub use crate::tiered::{JITTieredArtifact, TierUpError};
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -6,33 +6,29 @@ use wasmer_compiler::{
     SectionIndex,
 };
 use wasmer_engine::FunctionExtent;
-use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
 use wasmer_types::LocalFunctionIndex;
-use wasmer_vm::ModuleInfo;
-use wasmer_vm::SectionBodyPtr;
+use wasmer_vm::{FunctionBodyPtr, ModuleInfo, SectionBodyPtr};
 
-fn apply_relocation(
-    body: usize,
+fn relocation_target(
     r: &Relocation,
-    allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
-    jt_offsets: &PrimaryMap<LocalFunctionIndex, JumpTableOffsets>,
+    function: impl Fn(LocalFunctionIndex) -> usize,
+    jump_table: impl Fn(LocalFunctionIndex, JumpTable) -> usize,
     allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
-) {
-    let target_func_address: usize = match r.reloc_target {
-        RelocationTarget::LocalFunc(index) => *allocated_functions[index].ptr as usize,
+) -> usize {
+    match r.reloc_target {
+        RelocationTarget::LocalFunc(index) => function(index),
         RelocationTarget::LibCall(libcall) => libcall.function_pointer(),
         RelocationTarget::CustomSection(custom_section) => {
             *allocated_sections[custom_section] as usize
         }
         RelocationTarget::JumpTable(func_index, jt) => {
-            let offset = *jt_offsets
-                .get(func_index)
-                .and_then(|ofs| ofs.get(JumpTable::new(jt.index())))
-                .expect("func jump table");
-            *allocated_functions[func_index].ptr as usize + offset as usize
+            jump_table(func_index, JumpTable::new(jt.index()))
         }
-    };
+    }
+}
 
+fn apply_relocation(body: usize, r: &Relocation, target_func_address: usize) {
     match r.kind {
         #[cfg(target_pointer_width = "64")]
         RelocationKind::Abs8 => unsafe {
@@ -72,16 +68,94 @@ pub fn link_module(
     allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
     section_relocations: &PrimaryMap<SectionIndex, Vec<Relocation>>,
 ) {
+    link(
+        allocated_functions,
+        jt_offsets,
+        function_relocations,
+        allocated_sections,
+        section_relocations,
+        |index| *allocated_functions[index].ptr as usize,
+    )
+}
+
+/// Links a module like `link_module`, except that the functions call
+/// each other through `stubs`.
+pub(crate) fn link_module_through_stubs(
+    allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    jt_offsets: &PrimaryMap<LocalFunctionIndex, JumpTableOffsets>,
+    function_relocations: Relocations,
+    allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    section_relocations: &PrimaryMap<SectionIndex, Vec<Relocation>>,
+    stubs: &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+) {
+    link(
+        allocated_functions,
+        jt_offsets,
+        function_relocations,
+        allocated_sections,
+        section_relocations,
+        |index| *stubs[index] as usize,
+    )
+}
+
+fn link(
+    allocated_functions: &PrimaryMap<LocalFunctionIndex, FunctionExtent>,
+    jt_offsets: &PrimaryMap<LocalFunctionIndex, JumpTableOffsets>,
+    function_relocations: Relocations,
+    allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    section_relocations: &PrimaryMap<SectionIndex, Vec<Relocation>>,
+    callee: impl Fn(LocalFunctionIndex) -> usize,
+) {
+    let function = |index| *allocated_functions[index].ptr as usize;
+    let jump_table = |func_index, jt| {
+        let offset = *jt_offsets
+            .get(func_index)
+            .and_then(|ofs: &JumpTableOffsets| ofs.get(jt))
+            .expect("func jump table");
+        function(func_index) + offset as usize
+    };
     for (i, section_relocs) in section_relocations.iter() {
         let body = *allocated_sections[i] as usize;
         for r in section_relocs {
-            apply_relocation(body, r, allocated_functions, jt_offsets, allocated_sections);
+            let target = relocation_target(r, function, jump_table, allocated_sections);
+            apply_relocation(body, r, target);
         }
     }
     for (i, function_relocs) in function_relocations.iter() {
-        let body = *allocated_functions[i].ptr as usize;
+        let body = function(i);
         for r in function_relocs {
-            apply_relocation(body, r, allocated_functions, jt_offsets, allocated_sections);
+            let target = relocation_target(r, &callee, jump_table, allocated_sections);
+            apply_relocation(body, r, target);
+        }
+    }
+}
+
+/// Links the function `index` of a module, compiled on its own, so it
+/// calls the other functions through `stubs`.
+#[cfg(feature = "compiler")]
+pub(crate) fn link_function(
+    index: LocalFunctionIndex,
+    allocated_function: &FunctionExtent,
+    jt_offsets: &JumpTableOffsets,
+    function_relocations: &[Relocation],
+    allocated_sections: &PrimaryMap<SectionIndex, SectionBodyPtr>,
+    section_relocations: &PrimaryMap<SectionIndex, Vec<Relocation>>,
+    stubs: &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
+) {
+    let body = *allocated_function.ptr as usize;
+    // The custom sections, such as the unwind information, refer to the
+    // function itself rather than to its stub.
+    let function = |i| if i == index { body } else { *stubs[i] as usize };
+    let jump_table = |_, jt| body + jt_offsets[jt] as usize;
+    for (i, section_relocs) in section_relocations.iter() {
+        let section = *allocated_sections[i] as usize;
+        for r in section_relocs {
+            let target = relocation_target(r, function, jump_table, allocated_sections);
+            apply_relocation(section, r, target);
         }
     }
+    for r in function_relocations {
+        let target = relocation_target(r, |i| *stubs[i] as usize, jump_table, allocated_sections);
+        apply_relocation(body, r, target);
+    }
 }
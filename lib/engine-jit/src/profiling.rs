@@ -0,0 +1,213 @@
+//! Tell external profilers, like Linux `perf`, about the code published
+//! by the engine.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// How the engine tells profilers about the compiled code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfilingStrategy {
+    /// Don't tell profilers anything.
+    None,
+    /// Append an entry to `/tmp/perf-<pid>.map` for each function, which
+    /// `perf report` reads to symbolize JIT code.
+    PerfMap,
+    /// Write the functions and their code to a `jit-<pid>.dump` file in
+    /// the current directory, for `perf inject --jit`.
+    ///
+    /// Only available on Linux.
+    JitDump,
+}
+
+impl Default for ProfilingStrategy {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// Something told about every published function.
+pub trait ProfilingAgent: Send + Sync {
+    /// The function `name`, made of the `size` bytes at `address`, has
+    /// been made executable.
+    fn register_function(&self, name: &str, address: *const u8, size: usize);
+}
+
+impl ProfilingStrategy {
+    /// Create the agent implementing this strategy.
+    pub(crate) fn agent(self) -> io::Result<Option<Box<dyn ProfilingAgent>>> {
+        Ok(match self {
+            Self::None => None,
+            Self::PerfMap => Some(Box::new(PerfMapAgent::new()?)),
+            #[cfg(target_os = "linux")]
+            Self::JitDump => Some(Box::new(jitdump::JitDumpAgent::new()?)),
+            #[cfg(not(target_os = "linux"))]
+            Self::JitDump => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "jitdump profiling is only supported on Linux",
+                ))
+            }
+        })
+    }
+}
+
+/// Writes the `/tmp/perf-<pid>.map` file.
+struct PerfMapAgent {
+    file: Mutex<File>,
+}
+
+impl PerfMapAgent {
+    fn new() -> io::Result<Self> {
+        let path = format!("/tmp/perf-{}.map", std::process::id());
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl ProfilingAgent for PerfMapAgent {
+    fn register_function(&self, name: &str, address: *const u8, size: usize) {
+        let mut file = self.file.lock().unwrap();
+        // Profiling is best-effort: a failed write must not fail the
+        // compilation.
+        let _ = writeln!(file, "{:x} {:x} {}", address as usize, size, name);
+        let _ = file.flush();
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod jitdump {
+    //! The jitdump format, as described in
+    //! `tools/perf/Documentation/jitdump-specification.txt` of the Linux
+    //! sources.
+
+    use super::ProfilingAgent;
+    use std::fs::{File, OpenOptions};
+    use std::io::{self, Write};
+    use std::os::unix::io::AsRawFd;
+    use std::sync::Mutex;
+
+    const MAGIC: u32 = 0x4A69_5444;
+    const VERSION: u32 = 1;
+    const JIT_CODE_LOAD: u32 = 0;
+    const HEADER_SIZE: u32 = 40;
+    const CODE_LOAD_SIZE: u32 = 56;
+
+    #[cfg(target_arch = "x86_64")]
+    const ELF_MACHINE: u32 = 62;
+    #[cfg(target_arch = "aarch64")]
+    const ELF_MACHINE: u32 = 183;
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    const ELF_MACHINE: u32 = 0;
+
+    struct State {
+        file: File,
+        code_index: u64,
+    }
+
+    /// Writes the `jit-<pid>.dump` file.
+    pub(super) struct JitDumpAgent {
+        state: Mutex<State>,
+        /// `perf record` only finds the dump through this mapping of it.
+        marker: *mut libc::c_void,
+    }
+
+    // The marker is only unmapped on drop.
+    unsafe impl Send for JitDumpAgent {}
+    unsafe impl Sync for JitDumpAgent {}
+
+    fn timestamp() -> u64 {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        unsafe {
+            libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts);
+        }
+        ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+    }
+
+    impl JitDumpAgent {
+        pub(super) fn new() -> io::Result<Self> {
+            let pid = std::process::id();
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(format!("jit-{}.dump", pid))?;
+
+            let page_size = region::page::size();
+            let marker = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    page_size,
+                    libc::PROT_READ | libc::PROT_EXEC,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                )
+            };
+            if marker == libc::MAP_FAILED {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+            header.extend_from_slice(&MAGIC.to_ne_bytes());
+            header.extend_from_slice(&VERSION.to_ne_bytes());
+            header.extend_from_slice(&HEADER_SIZE.to_ne_bytes());
+            header.extend_from_slice(&ELF_MACHINE.to_ne_bytes());
+            header.extend_from_slice(&0u32.to_ne_bytes());
+            header.extend_from_slice(&pid.to_ne_bytes());
+            header.extend_from_slice(&timestamp().to_ne_bytes());
+            header.extend_from_slice(&0u64.to_ne_bytes());
+            file.write_all(&header)?;
+
+            Ok(Self {
+                state: Mutex::new(State {
+                    file,
+                    code_index: 0,
+                }),
+                marker,
+            })
+        }
+    }
+
+    impl ProfilingAgent for JitDumpAgent {
+        fn register_function(&self, name: &str, address: *const u8, size: usize) {
+            let mut state = self.state.lock().unwrap();
+            let code = unsafe { std::slice::from_raw_parts(address, size) };
+            let total_size = CODE_LOAD_SIZE as usize + name.len() + 1 + size;
+            let tid = unsafe { libc::syscall(libc::SYS_gettid) } as u32;
+
+            let mut record = Vec::with_capacity(total_size);
+            record.extend_from_slice(&JIT_CODE_LOAD.to_ne_bytes());
+            record.extend_from_slice(&(total_size as u32).to_ne_bytes());
+            record.extend_from_slice(&timestamp().to_ne_bytes());
+            record.extend_from_slice(&std::process::id().to_ne_bytes());
+            record.extend_from_slice(&tid.to_ne_bytes());
+            record.extend_from_slice(&(address as u64).to_ne_bytes());
+            record.extend_from_slice(&(address as u64).to_ne_bytes());
+            record.extend_from_slice(&(size as u64).to_ne_bytes());
+            record.extend_from_slice(&state.code_index.to_ne_bytes());
+            record.extend_from_slice(name.as_bytes());
+            record.push(0);
+            record.extend_from_slice(code);
+
+            // Profiling is best-effort: a failed write must not fail the
+            // compilation.
+            let _ = state.file.write_all(&record);
+            state.code_index += 1;
+        }
+    }
+
+    impl Drop for JitDumpAgent {
+        fn drop(&mut self) {
+            unsafe {
+                libc::munmap(self.marker, region::page::size());
+            }
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Define `JITTieredArtifact`, an artifact that starts with code from a
+//! fast baseline compiler and switches each function to code from an
+//! optimizing compiler once it has been produced in the background.
+
+use crate::function_table::FunctionTable;
+use crate::{JITArtifact, JITEngine};
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use wasmer_compiler::{CompileError, CompileModuleInfo, Compiler, Features, ModuleEnvironment};
+use wasmer_engine::{Artifact, Engine, GlobalFrameInfoRegistration, SerializeError, Tunables};
+use wasmer_types::entity::{BoxedSlice, EntityRef, PrimaryMap};
+use wasmer_types::{
+    FunctionIndex, LocalFunctionIndex, MemoryIndex, OwnedDataInitializer, SignatureIndex,
+    TableIndex,
+};
+use wasmer_vm::{
+    FunctionBodyPtr, MemoryStyle, ModuleInfo, TableStyle, VMSharedSignatureIndex, VMTrampoline,
+};
+
+/// A compiled wasm module whose functions are recompiled in the
+/// background by the tier-up compiler of the `JITEngine`.
+///
+/// The functions are called through stubs, so switching a function to
+/// its optimized code applies to every instance of the module, even the
+/// ones running it: the calls in progress finish in the baseline code,
+/// and the next ones use the optimized code.
+pub struct JITTieredArtifact {
+    baseline: JITArtifact,
+    functions: Arc<TieredFunctions>,
+    wake_up: Arc<WakeUp>,
+}
+
+/// The functions of a `JITTieredArtifact`, shared with the background
+/// thread tiering them up.
+struct TieredFunctions {
+    jit: JITEngine,
+    table: FunctionTable,
+    optimized: PrimaryMap<LocalFunctionIndex, AtomicBool>,
+    frame_info_registrations: Mutex<Vec<GlobalFrameInfoRegistration>>,
+    wake_up: Arc<WakeUp>,
+    errors: Mutex<Vec<TierUpError>>,
+}
+
+/// Wakes the background thread up when a function gets hot, or when the
+/// artifact is dropped.
+#[derive(Default)]
+struct WakeUp {
+    /// Whether a function got hot, and whether the artifact was dropped,
+    /// since the thread last woke up.
+    state: Mutex<(bool, bool)>,
+    condvar: Condvar,
+}
+
+impl WakeUp {
+    fn notify(&self, dropped: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.0 = true;
+        state.1 |= dropped;
+        self.condvar.notify_one();
+    }
+
+    /// Wait until a function gets hot, and return `false` if the artifact
+    /// was dropped instead.
+    fn wait(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        while !state.0 {
+            state = self.condvar.wait(state).unwrap();
+        }
+        state.0 = false;
+        !state.1
+    }
+}
+
+/// An error of the tier-up compiler. The functions it concerns keep
+/// running their baseline code.
+#[derive(Debug)]
+pub struct TierUpError {
+    /// The function that failed to compile, or `None` if the module
+    /// couldn't be translated.
+    pub function: Option<LocalFunctionIndex>,
+    /// The error.
+    pub error: CompileError,
+}
+
+impl JITTieredArtifact {
+    /// Compile a data buffer with the engine's compiler, and start
+    /// recompiling its functions with the tier-up compiler in a
+    /// background thread.
+    pub fn new(
+        jit: &JITEngine,
+        data: &[u8],
+        tunables: &dyn Tunables,
+    ) -> Result<Self, CompileError> {
+        let threshold = jit.tier_up_threshold();
+        let serializable = JITArtifact::translate(jit, data, tunables)?;
+        let mut inner_jit = jit.inner_mut();
+        let num_functions = serializable.compilation.function_bodies.len();
+        let call_threshold = if threshold > 0 { Some(threshold) } else { None };
+        let table = inner_jit.allocate_function_table(num_functions, false, call_threshold)?;
+        let baseline = JITArtifact::from_parts_through(
+            &mut inner_jit,
+            serializable,
+            jit.target(),
+            Some(table.stubs()),
+        )?;
+        drop(inner_jit);
+        for (index, body) in baseline.function_bodies() {
+            table.set(index, body);
+        }
+
+        let wake_up = Arc::new(WakeUp::default());
+        let functions = Arc::new(TieredFunctions {
+            jit: jit.clone(),
+            table,
+            optimized: (0..num_functions).map(|_| AtomicBool::new(false)).collect(),
+            frame_info_registrations: Mutex::new(vec![]),
+            wake_up: wake_up.clone(),
+            errors: Mutex::new(vec![]),
+        });
+        functions
+            .table
+            .set_resolver(Arc::as_ptr(&functions) as *const u8, function_got_hot);
+
+        if let Some(compiler) = jit.tier_up_compiler() {
+            let functions = Arc::downgrade(&functions);
+            let data = data.to_vec();
+            // The tier-up compiler is given the module as transformed by
+            // the engine's compiler, since the instances are laid out
+            // after it.
+            let compile_info = CompileModuleInfo {
+                module: baseline.module(),
+                features: baseline.features().clone(),
+                memory_styles: baseline.memory_styles().clone(),
+                table_styles: baseline.table_styles().clone(),
+            };
+            let wake_up = wake_up.clone();
+            std::thread::spawn(move || {
+                tier_up(
+                    functions,
+                    &wake_up,
+                    &*compiler,
+                    &data,
+                    compile_info,
+                    threshold,
+                )
+            });
+        }
+
+        Ok(Self {
+            baseline,
+            functions,
+            wake_up,
+        })
+    }
+
+    /// Whether all the functions run their optimized code.
+    ///
+    /// Functions that are never called enough, or that the tier-up
+    /// compiler fails to compile, keep running their baseline code.
+    pub fn is_optimized(&self) -> bool {
+        self.functions
+            .optimized
+            .values()
+            .all(|optimized| optimized.load(Ordering::Acquire))
+    }
+
+    /// Whether the local function `index` runs its optimized code.
+    pub fn is_function_optimized(&self, index: LocalFunctionIndex) -> bool {
+        self.functions.optimized[index].load(Ordering::Acquire)
+    }
+
+    /// Take the errors of the tier-up compiler reported since the last
+    /// call.
+    pub fn take_tier_up_errors(&self) -> Vec<TierUpError> {
+        std::mem::take(&mut *self.functions.errors.lock().unwrap())
+    }
+}
+
+impl Drop for JITTieredArtifact {
+    fn drop(&mut self) {
+        self.wake_up.notify(true);
+    }
+}
+
+/// Wake the background thread up when a function reaches the tier-up
+/// threshold, see `FunctionTable`, and go on with its current code.
+unsafe extern "sysv64" fn function_got_hot(context: *const u8, index: u64) -> usize {
+    let functions = &*(context as *const TieredFunctions);
+    functions.wake_up.notify(false);
+    let index = LocalFunctionIndex::new(index as usize);
+    *functions.table.get(index).unwrap() as usize
+}
+
+/// Recompile the functions with `compiler`, hottest first, until they
+/// are all optimized or the artifact is dropped.
+///
+/// The errors are reported to the artifact, and the functions that fail
+/// to compile keep running their baseline code.
+fn tier_up(
+    functions: Weak<TieredFunctions>,
+    wake_up: &WakeUp,
+    compiler: &dyn Compiler,
+    data: &[u8],
+    mut compile_info: CompileModuleInfo,
+    threshold: u64,
+) {
+    let report = |function: Option<LocalFunctionIndex>, error: CompileError| {
+        if let Some(functions) = functions.upgrade() {
+            functions
+                .errors
+                .lock()
+                .unwrap()
+                .push(TierUpError { function, error });
+        }
+    };
+    let translation = match ModuleEnvironment::new().translate(data) {
+        Ok(translation) => translation,
+        Err(error) => return report(None, CompileError::Wasm(error)),
+    };
+    // SAFETY: Calling `unwrap` is correct since
+    // `environ.translate()` above will write some data into
+    // `module_translation_state`.
+    let module_translation_state = translation.module_translation_state.unwrap();
+    compiler.analyze_function_bodies(&compile_info.module, &translation.function_body_inputs);
+    let mut pending = translation.function_body_inputs.keys().collect::<Vec<_>>();
+
+    while !pending.is_empty() {
+        let functions = match functions.upgrade() {
+            Some(functions) => functions,
+            None => return,
+        };
+        let hottest = pending
+            .iter()
+            .enumerate()
+            .map(|(position, &index)| (position, functions.table.calls(index)))
+            .filter(|&(_, calls)| calls >= threshold)
+            .max_by_key(|&(position, calls)| (calls, Reverse(position)));
+        let index = match hottest {
+            Some((position, _)) => pending.remove(position),
+            None => {
+                // The stubs wake the thread up when a function gets hot.
+                drop(functions);
+                if !wake_up.wait() {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        // If the optimizing compiler fails, the baseline code keeps
+        // being used.
+        let published = compiler
+            .compile_function(
+                functions.jit.target(),
+                &mut compile_info,
+                &module_translation_state,
+                index,
+                &translation.function_body_inputs[index],
+            )
+            .and_then(|compilation| {
+                functions
+                    .table
+                    .publish(&functions.jit, &compile_info.module, index, &compilation)
+            });
+        match published {
+            Ok(registration) => {
+                if let Some(registration) = registration {
+                    functions
+                        .frame_info_registrations
+                        .lock()
+                        .unwrap()
+                        .push(registration);
+                }
+                functions.optimized[index].store(true, Ordering::Release);
+            }
+            Err(error) => functions.errors.lock().unwrap().push(TierUpError {
+                function: Some(index),
+                error,
+            }),
+        }
+    }
+}
+
+impl Artifact for JITTieredArtifact {
+    fn module(&self) -> Arc<ModuleInfo> {
+        self.baseline.module()
+    }
+
+    fn module_ref(&self) -> &ModuleInfo {
+        self.baseline.module_ref()
+    }
+
+    fn module_mut(&mut self) -> Option<&mut ModuleInfo> {
+        self.baseline.module_mut()
+    }
+
+    fn register_frame_info(&self) {
+        self.baseline.register_frame_info();
+    }
+
+    fn features(&self) -> &Features {
+        self.baseline.features()
+    }
+
+    fn data_initializers(&self) -> &[OwnedDataInitializer] {
+        self.baseline.data_initializers()
+    }
+
+    fn memory_styles(&self) -> &PrimaryMap<MemoryIndex, MemoryStyle> {
+        self.baseline.memory_styles()
+    }
+
+    fn table_styles(&self) -> &PrimaryMap<TableIndex, TableStyle> {
+        self.baseline.table_styles()
+    }
+
+    fn finished_functions(&self) -> &BoxedSlice<LocalFunctionIndex, FunctionBodyPtr> {
+        self.functions.table.stubs()
+    }
+
+    fn finished_function_call_trampolines(&self) -> &BoxedSlice<SignatureIndex, VMTrampoline> {
+        self.baseline.finished_function_call_trampolines()
+    }
+
+    fn finished_dynamic_function_trampolines(&self) -> &BoxedSlice<FunctionIndex, FunctionBodyPtr> {
+        self.baseline.finished_dynamic_function_trampolines()
+    }
+
+    fn signatures(&self) -> &BoxedSlice<SignatureIndex, VMSharedSignatureIndex> {
+        self.baseline.signatures()
+    }
+
+    fn serialize(&self) -> Result<Vec<u8>, SerializeError> {
+        // The optimized functions are each in code of their own, so the
+        // baseline code is serialized.
+        self.baseline.serialize()
+    }
+}
@@ -17,11 +17,12 @@ use tracing::trace;
 use wasmer_compiler::{CompileError, Features, OperatingSystem, Symbol, SymbolRegistry, Triple};
 #[cfg(feature = "compiler")]
 use wasmer_compiler::{
-    CompileModuleInfo, FunctionBodyData, ModuleEnvironment, ModuleTranslationState,
+    CompileModuleInfo, Compiler, FunctionBodyData, ModuleEnvironment, ModuleInfoTranslation,
+    ModuleTranslationState,
 };
-use wasmer_engine::{Artifact, DeserializeError, InstantiationError, SerializeError};
 #[cfg(feature = "compiler")]
-use wasmer_engine::{Engine, Tunables};
+use wasmer_engine::{compile_submodules, Engine, Tunables};
+use wasmer_engine::{Artifact, DeserializeError, InstantiationError, SerializeError};
 #[cfg(feature = "compiler")]
 use wasmer_object::{emit_compilation, emit_data, get_object_for_target};
 use wasmer_types::entity::{BoxedSlice, PrimaryMap};
@@ -96,8 +97,9 @@ impl NativeArtifact {
     #[cfg(feature = "compiler")]
     /// Generate a compilation
     fn generate_metadata<'data>(
-        data: &'data [u8],
+        mut translation: ModuleInfoTranslation<'data>,
         features: &Features,
+        compiler: &dyn Compiler,
         tunables: &dyn Tunables,
     ) -> Result<
         (
@@ -108,8 +110,8 @@ impl NativeArtifact {
         ),
         CompileError,
     > {
-        let environ = ModuleEnvironment::new();
-        let translation = environ.translate(data).map_err(CompileError::Wasm)?;
+        compiler.transform_module_info(&mut translation.module);
+        compiler.analyze_function_bodies(&translation.module, &translation.function_body_inputs);
         let memory_styles: PrimaryMap<MemoryIndex, MemoryStyle> = translation
             .module
             .memories
@@ -144,11 +146,16 @@ impl NativeArtifact {
         data: &[u8],
         tunables: &dyn Tunables,
     ) -> Result<Self, CompileError> {
+        let environ = ModuleEnvironment::new();
+        let mut translation = environ.translate(data).map_err(CompileError::Wasm)?;
+        // The nested modules are compiled before locking the engine.
+        compile_submodules(engine, &mut translation.module, tunables)?;
+
         let mut engine_inner = engine.inner_mut();
         let target = engine.target();
         let compiler = engine_inner.compiler()?;
         let (compile_info, function_body_inputs, data_initializers, module_translation) =
-            Self::generate_metadata(data, engine_inner.features(), tunables)?;
+            Self::generate_metadata(translation, engine_inner.features(), compiler, tunables)?;
 
         let data_initializers = data_initializers
             .iter()
@@ -167,7 +167,7 @@ pub trait Artifact: Send + Sync + Upcastable {
             })
             .collect::<Vec<_>>();
         handle
-            .finish_instantiation(&data_initializers)
+            .finish_instantiation(&data_initializers, self.features().bulk_memory)
             .map_err(|trap| InstantiationError::Start(RuntimeError::from_trap(trap)))
     }
 }
@@ -8,7 +8,7 @@ use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::sync::Arc;
 use wasmer_compiler::{CompileError, Target};
 use wasmer_types::FunctionType;
-use wasmer_vm::VMSharedSignatureIndex;
+use wasmer_vm::{ModuleInfo, Submodule, VMSharedSignatureIndex};
 
 /// A unimplemented Wasmer `Engine`.
 ///
@@ -68,6 +68,30 @@ pub trait Engine {
     fn cloned(&self) -> Arc<dyn Engine + Send + Sync>;
 }
 
+/// Compile the nested modules of `module` with `engine`, and keep them
+/// serialized in place of their Wasm binaries, so that deserializing
+/// the module doesn't compile them again.
+///
+/// The engine must not be locked, as it compiles each nested module.
+pub fn compile_submodules(
+    engine: &dyn Engine,
+    module: &mut ModuleInfo,
+    tunables: &dyn Tunables,
+) -> Result<(), CompileError> {
+    for submodule in module.submodules.iter_mut() {
+        if let Submodule::Binary(binary) = submodule {
+            let serialized = engine
+                .compile(binary, tunables)?
+                .serialize()
+                .map_err(|error| {
+                    CompileError::Codegen(format!("failed to serialize a nested module: {}", error))
+                })?;
+            *submodule = Submodule::Serialized(serialized.into());
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[repr(transparent)]
 /// A unique identifier for an Engine.
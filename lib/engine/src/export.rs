@@ -1,6 +1,6 @@
 use wasmer_vm::{
     ImportInitializerFuncPtr, VMExport, VMExportFunction, VMExportGlobal, VMExportMemory,
-    VMExportTable,
+    VMExportTable, VMExportTag,
 };
 
 use std::sync::Arc;
@@ -19,6 +19,9 @@ pub enum Export {
 
     /// A global export value.
     Global(ExportGlobal),
+
+    /// A tag export value.
+    Tag(ExportTag),
 }
 
 impl From<Export> for VMExport {
@@ -28,6 +31,7 @@ impl From<Export> for VMExport {
             Export::Memory(ExportMemory { vm_memory }) => Self::Memory(vm_memory),
             Export::Table(ExportTable { vm_table }) => Self::Table(vm_table),
             Export::Global(ExportGlobal { vm_global }) => Self::Global(vm_global),
+            Export::Tag(ExportTag { vm_tag }) => Self::Tag(vm_tag),
         }
     }
 }
@@ -42,6 +46,7 @@ impl From<VMExport> for Export {
             VMExport::Memory(vm_memory) => Self::Memory(ExportMemory { vm_memory }),
             VMExport::Table(vm_table) => Self::Table(ExportTable { vm_table }),
             VMExport::Global(vm_global) => Self::Global(ExportGlobal { vm_global }),
+            VMExport::Tag(vm_tag) => Self::Tag(ExportTag { vm_tag }),
         }
     }
 }
@@ -186,3 +191,16 @@ impl From<ExportGlobal> for Export {
         Self::Global(global)
     }
 }
+
+/// A tag export value.
+#[derive(Debug, Clone)]
+pub struct ExportTag {
+    /// The VM tag, containing info about the tag.
+    pub vm_tag: VMExportTag,
+}
+
+impl From<ExportTag> for Export {
+    fn from(tag: ExportTag) -> Self {
+        Self::Tag(tag)
+    }
+}
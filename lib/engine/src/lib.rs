@@ -30,12 +30,13 @@ mod trap;
 mod tunables;
 
 pub use crate::artifact::Artifact;
-pub use crate::engine::{Engine, EngineId};
+pub use crate::engine::{compile_submodules, Engine, EngineId};
 pub use crate::error::{
     DeserializeError, ImportError, InstantiationError, LinkError, SerializeError,
 };
 pub use crate::export::{
     Export, ExportFunction, ExportFunctionMetadata, ExportGlobal, ExportMemory, ExportTable,
+    ExportTag,
 };
 pub use crate::resolver::{
     resolve_imports, ChainableNamedResolver, NamedResolver, NamedResolverChain, NullResolver,
@@ -98,6 +98,7 @@ fn get_extern_from_import(module: &ModuleInfo, import_index: &ImportIndex) -> Ex
             let global = module.globals[*index];
             ExternType::Global(global)
         }
+        ImportIndex::Tag(index) => ExternType::Tag(module.tag_type(*index)),
     }
 }
 
@@ -111,6 +112,7 @@ fn get_extern_from_export(_module: &ModuleInfo, export: &Export) -> ExternType {
             let global = g.vm_global.from.ty();
             ExternType::Global(*global)
         }
+        Export::Tag(ref t) => ExternType::Tag(t.vm_tag.ty().clone()),
     }
 }
 
@@ -131,6 +133,7 @@ pub fn resolve_imports(
     let mut table_imports = PrimaryMap::with_capacity(module.num_imported_tables);
     let mut memory_imports = PrimaryMap::with_capacity(module.num_imported_memories);
     let mut global_imports = PrimaryMap::with_capacity(module.num_imported_globals);
+    let mut tag_imports = PrimaryMap::with_capacity(module.num_imported_tags);
 
     for ((module_name, field, import_idx), import_index) in module.imports.iter() {
         let resolved = resolver.resolve(*import_idx, module_name, field);
@@ -268,6 +271,10 @@ pub fn resolve_imports(
                     from: g.vm_global.from.clone(),
                 });
             }
+
+            Export::Tag(ref t) => {
+                tag_imports.push(t.vm_tag.from.clone());
+            }
         }
     }
 
@@ -277,6 +284,7 @@ pub fn resolve_imports(
         table_imports,
         memory_imports,
         global_imports,
+        tag_imports,
     ))
 }
 
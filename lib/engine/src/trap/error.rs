@@ -4,7 +4,7 @@ use std::error::Error;
 use std::fmt;
 use std::sync::Arc;
 use std::sync::RwLockReadGuard;
-use wasmer_vm::{raise_user_trap, Trap, TrapCode};
+use wasmer_vm::{raise_user_trap, Exception, Trap, TrapCode};
 
 /// A struct representing an aborted instruction execution, with a message
 /// indicating the cause.
@@ -223,6 +223,22 @@ impl RuntimeError {
             _ => false,
         }
     }
+
+    /// Returns the Wasm exception that caused this error, if it was thrown
+    /// and not caught.
+    ///
+    /// The exception may have been thrown by Wasm code, or by a host
+    /// function wrapping it in its own error type.
+    pub fn exception(&self) -> Option<&Exception> {
+        let mut source = self.source();
+        while let Some(err) = source {
+            if let Some(exception) = err.downcast_ref::<Exception>() {
+                return Some(exception);
+            }
+            source = err.source();
+        }
+        None
+    }
 }
 
 impl fmt::Debug for RuntimeError {
@@ -261,6 +277,21 @@ impl fmt::Display for RuntimeError {
                 func_index,
                 frame.module_offset()
             )?;
+            // Like a native backtrace, list the functions inlined at this
+            // point before the location in the function itself.
+            if let Some((function, inlined)) = frame.source_frames().split_last() {
+                for source in inlined {
+                    writeln!(f)?;
+                    write!(f, "             at ")?;
+                    match source.function_name() {
+                        Some(name) => write!(f, "{:#}", rustc_demangle::demangle(name))?,
+                        None => write!(f, "<unnamed>")?,
+                    }
+                    write!(f, " [inlined] ({})", source)?;
+                }
+                writeln!(f)?;
+                write!(f, "             at {}", function)?;
+            }
         }
         Ok(())
     }
@@ -10,6 +10,7 @@
 //! let module: ModuleInfo = ...;
 //! FRAME_INFO.register(module, compiled_functions);
 //! ```
+use super::source_map::{SourceFrame, SourceMap};
 use crate::serialize::SerializableFunctionFrameInfo;
 use std::cmp;
 use std::collections::BTreeMap;
@@ -54,7 +55,8 @@ struct ModuleInfoFrameInfo {
     start: usize,
     functions: BTreeMap<usize, FunctionInfo>,
     module: Arc<ModuleInfo>,
-    frame_infos: PrimaryMap<LocalFunctionIndex, SerializableFunctionFrameInfo>,
+    frame_infos: BTreeMap<LocalFunctionIndex, SerializableFunctionFrameInfo>,
+    source_map: Arc<SourceMap>,
 }
 
 impl ModuleInfoFrameInfo {
@@ -62,11 +64,11 @@ impl ModuleInfoFrameInfo {
         &self,
         local_index: LocalFunctionIndex,
     ) -> &SerializableFunctionFrameInfo {
-        &self.frame_infos.get(local_index).unwrap()
+        &self.frame_infos[&local_index]
     }
 
     fn process_function_debug_info(&mut self, local_index: LocalFunctionIndex) {
-        let func = self.frame_infos.get_mut(local_index).unwrap();
+        let func = self.frame_infos.get_mut(&local_index).unwrap();
         let processed: CompiledFunctionFrameInfo = match func {
             SerializableFunctionFrameInfo::Processed(_) => {
                 // This should be a no-op on processed info
@@ -156,15 +158,37 @@ impl GlobalFrameInfo {
             None => instr_map.start_srcloc,
         };
         let func_index = module.module.func_index(func.local_index);
+        let source_frames = module.source_map.lookup(&module.module, instr);
         Some(FrameInfo {
             module_name: module.module.name(),
             func_index: func_index.index() as u32,
             function_name: module.module.function_names.get(&func_index).cloned(),
             instr,
             func_start: instr_map.start_srcloc,
+            source_frames,
         })
     }
 
+    /// Fetches the source frames at an offset of `module`, innermost
+    /// first.
+    ///
+    /// Returns an empty list if the module has no DWARF, or if it isn't
+    /// registered.
+    pub fn lookup_source_frames(
+        &self,
+        module: &ModuleInfo,
+        module_offset: usize,
+    ) -> Vec<SourceFrame> {
+        self.ranges
+            .values()
+            .find(|info| std::ptr::eq(&*info.module, module))
+            .map(|info| {
+                info.source_map
+                    .lookup(module, SourceLoc::new(module_offset as u32))
+            })
+            .unwrap_or_default()
+    }
+
     /// Fetches trap information about a program counter in a backtrace.
     pub fn lookup_trap_info(&self, pc: usize) -> Option<&TrapInformation> {
         let module = self.module_info(pc)?;
@@ -267,8 +291,56 @@ pub fn register(
     if functions.is_empty() {
         return None;
     }
+    let frame_infos = frame_infos.into_iter().collect();
+    Some(insert(min, max, functions, module, frame_infos))
+}
 
+/// Registers the frame information of a function of `module` compiled
+/// on its own, after the rest of the module.
+pub fn register_function(
+    module: Arc<ModuleInfo>,
+    local_index: LocalFunctionIndex,
+    extent: &FunctionExtent,
+    frame_info: SerializableFunctionFrameInfo,
+) -> Option<GlobalFrameInfoRegistration> {
+    if extent.length == 0 {
+        return None;
+    }
+    let start = *extent.ptr as usize;
+    let end = start + extent.length;
+    let mut functions = BTreeMap::new();
+    functions.insert(end, FunctionInfo { start, local_index });
+    let mut frame_infos = BTreeMap::new();
+    frame_infos.insert(local_index, frame_info);
+    Some(insert(start, end, functions, module, frame_infos))
+}
+
+/// Insert the frame information of the `functions` of `module` between
+/// `min` and `max`.
+///
+/// The registrations of the same module share its source map, so its
+/// DWARF is loaded at most once.
+fn insert(
+    min: usize,
+    max: usize,
+    functions: BTreeMap<usize, FunctionInfo>,
+    module: Arc<ModuleInfo>,
+    frame_infos: BTreeMap<LocalFunctionIndex, SerializableFunctionFrameInfo>,
+) -> GlobalFrameInfoRegistration {
     let mut info = FRAME_INFO.write().unwrap();
+    let source_map = info
+        .ranges
+        .values()
+        .find(|other| Arc::ptr_eq(&other.module, &module))
+        .map(|other| other.source_map.clone())
+        .unwrap_or_else(|| Arc::new(SourceMap::new()));
+    let module_info = ModuleInfoFrameInfo {
+        start: min,
+        functions,
+        module,
+        frame_infos,
+        source_map,
+    };
     // First up assert that our chunk of jit functions doesn't collide with
     // any other known chunks of jit functions...
     if let Some((_, prev)) = info.ranges.range(max..).next() {
@@ -279,17 +351,9 @@ pub fn register(
     }
 
     // ... then insert our range and assert nothing was there previously
-    let prev = info.ranges.insert(
-        max,
-        ModuleInfoFrameInfo {
-            start: min,
-            functions,
-            module,
-            frame_infos,
-        },
-    );
+    let prev = info.ranges.insert(max, module_info);
     assert!(prev.is_none());
-    Some(GlobalFrameInfoRegistration { key: max })
+    GlobalFrameInfoRegistration { key: max }
 }
 
 /// Description of a frame in a backtrace for a [`RuntimeError::trace`](crate::RuntimeError::trace).
@@ -307,6 +371,7 @@ pub struct FrameInfo {
     function_name: Option<String>,
     func_start: SourceLoc,
     instr: SourceLoc,
+    source_frames: Vec<SourceFrame>,
 }
 
 impl FrameInfo {
@@ -367,4 +432,14 @@ impl FrameInfo {
     pub fn func_offset(&self) -> usize {
         (self.instr.bits() - self.func_start.bits()) as usize
     }
+
+    /// Returns the source-level frames of this frame, innermost first.
+    ///
+    /// They come from the DWARF debugging information of the module, so
+    /// this is empty if the module wasn't compiled with it. There is one
+    /// frame for the function of this frame, preceded by one for each
+    /// function inlined into it at this point.
+    pub fn source_frames(&self) -> &[SourceFrame] {
+        &self.source_frames
+    }
 }
@@ -0,0 +1,156 @@
+//! Map Wasm offsets back to the source code the module was compiled
+//! from, using the DWARF in the module's `.debug_*` custom sections.
+
+use gimli::{EndianArcSlice, LittleEndian};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wasmer_compiler::SourceLoc;
+use wasmer_vm::ModuleInfo;
+
+type Reader = EndianArcSlice<LittleEndian>;
+
+/// A source-level frame, as described by the DWARF of the module.
+///
+/// A Wasm frame has one source frame for its function, and one more for
+/// each function inlined into it at that point.
+#[derive(Debug, Clone)]
+pub struct SourceFrame {
+    function_name: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    column: Option<u32>,
+}
+
+impl SourceFrame {
+    /// The name of the source function, possibly mangled.
+    pub fn function_name(&self) -> Option<&str> {
+        self.function_name.as_deref()
+    }
+
+    /// The source file.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// The line in the source file, starting at 1.
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+
+    /// The column in the source line, starting at 1.
+    pub fn column(&self) -> Option<u32> {
+        self.column
+    }
+}
+
+impl fmt::Display for SourceFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.file.as_deref().unwrap_or("<unknown>"))?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+            if let Some(column) = self.column {
+                write!(f, ":{}", column)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The DWARF of a module, loaded the first time it is queried.
+///
+/// Loading it takes a while for large modules, and only traps need it.
+/// It is shared by all the frame information registrations of a module.
+pub(crate) struct SourceMap {
+    dwarf: Mutex<Dwarf>,
+}
+
+enum Dwarf {
+    Unloaded,
+    Loaded(Box<addr2line::Context<Reader>>),
+    Missing,
+}
+
+impl SourceMap {
+    /// A source map for a module, loaded on the first lookup.
+    pub(crate) fn new() -> Self {
+        Self {
+            dwarf: Mutex::new(Dwarf::Unloaded),
+        }
+    }
+
+    /// Load the DWARF of `module`, if it has any.
+    fn load(module: &ModuleInfo) -> Option<addr2line::Context<Reader>> {
+        let section = |name: &str| {
+            let data = module
+                .custom_sections
+                .get(name)
+                .map(|index| module.custom_sections_data[*index].clone())
+                .unwrap_or_else(|| Arc::from(&[][..]));
+            Reader::new(data, LittleEndian)
+        };
+        if !module.custom_sections.contains_key(".debug_info") {
+            return None;
+        }
+        addr2line::Context::from_sections(
+            section(".debug_abbrev").into(),
+            section(".debug_addr").into(),
+            section(".debug_info").into(),
+            section(".debug_line").into(),
+            section(".debug_line_str").into(),
+            section(".debug_ranges").into(),
+            section(".debug_rnglists").into(),
+            section(".debug_str").into(),
+            section(".debug_str_offsets").into(),
+            section(""),
+        )
+        .ok()
+    }
+
+    /// The source frames of `module` at the instruction at `srcloc`,
+    /// innermost first.
+    pub(crate) fn lookup(&self, module: &ModuleInfo, srcloc: SourceLoc) -> Vec<SourceFrame> {
+        let mut source_frames = Vec::new();
+        let code_section_offset = module.code_section_offset as u64;
+        if srcloc.is_default() || u64::from(srcloc.bits()) < code_section_offset {
+            return source_frames;
+        }
+        let address = u64::from(srcloc.bits()) - code_section_offset;
+        let mut dwarf = self.dwarf.lock().unwrap();
+        if let Dwarf::Unloaded = *dwarf {
+            *dwarf = match Self::load(module) {
+                Some(context) => Dwarf::Loaded(Box::new(context)),
+                None => Dwarf::Missing,
+            };
+        }
+        let context = match &*dwarf {
+            Dwarf::Loaded(context) => context,
+            _ => return source_frames,
+        };
+        let mut frames = match context.find_frames(address) {
+            Ok(frames) => frames,
+            Err(_) => return source_frames,
+        };
+        while let Ok(Some(frame)) = frames.next() {
+            let function_name = frame
+                .function
+                .as_ref()
+                .and_then(|name| name.raw_name().ok())
+                .map(|name| name.into_owned());
+            let (file, line, column) = match frame.location {
+                Some(location) => (
+                    location.file.map(str::to_string),
+                    location.line,
+                    location.column,
+                ),
+                None => (None, None, None),
+            };
+            source_frames.push(SourceFrame {
+                function_name,
+                file,
+                line,
+                column,
+            });
+        }
+        source_frames
+    }
+}
@@ -75,7 +75,7 @@ pub trait Tunables {
             let mi = MemoryIndex::new(index);
             let ty = &module.memories[mi];
             let style = &memory_styles[mi];
-            let mdl = memory_definition_locations[index];
+            let mdl = memory_definition_locations[index - num_imports];
             memories.push(
                 self.create_vm_memory(ty, style, mdl)
                     .map_err(|e| LinkError::Resource(format!("Failed to create memory: {}", e)))?,
@@ -98,7 +98,7 @@ pub trait Tunables {
             let ti = TableIndex::new(index);
             let ty = &module.tables[ti];
             let style = &table_styles[ti];
-            let tdl = table_definition_locations[index];
+            let tdl = table_definition_locations[index - num_imports];
             tables.push(
                 self.create_vm_table(ty, style, tdl)
                     .map_err(LinkError::Resource)?,
@@ -0,0 +1,593 @@
+//! `call_trace` is a middleware for recording the entry and exit of the
+//! functions defined by a module, and the calls it makes to the functions
+//! it imports.
+//!
+//! The instrumented module imports its hooks from the
+//! [`CALL_TRACE_NAMESPACE`] namespace. They all receive the index of the
+//! function being entered, left or called, and must be provided by the
+//! host when instantiating the module:
+//!
+//! * [`ENTER_FUNCTION`] and [`EXIT_FUNCTION`] when tracing functions;
+//! * [`HOST_CALL_FUNCTION`] and [`HOST_RETURN_FUNCTION`] when tracing
+//!   host calls. The arguments and results of the imported function can
+//!   be read from these hooks with [`get_saved_values`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::Operator;
+use wasmer::{
+    ExportIndex, FunctionMiddleware, FunctionType, GlobalInit, GlobalType, Instance,
+    LocalFunctionIndex, MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+    Val,
+};
+use wasmer_types::entity::{EntityRef, PrimaryMap};
+use wasmer_types::{FunctionIndex, GlobalIndex, ImportIndex, SignatureIndex};
+use wasmer_vm::ModuleInfo;
+
+/// The namespace the hooks of an instrumented module are imported from.
+pub const CALL_TRACE_NAMESPACE: &str = "wasmer_call_trace";
+
+/// The hook called when entering a function defined by the module.
+pub const ENTER_FUNCTION: &str = "enter";
+
+/// The hook called when leaving a function defined by the module.
+pub const EXIT_FUNCTION: &str = "exit";
+
+/// The hook called before calling an imported function.
+pub const HOST_CALL_FUNCTION: &str = "host_call";
+
+/// The hook called after an imported function returned.
+pub const HOST_RETURN_FUNCTION: &str = "host_return";
+
+/// The value types that can be saved into globals around host calls.
+const SAVED_TYPES: [Type; 4] = [Type::I32, Type::I64, Type::F32, Type::F64];
+
+/// The globals the arguments and the results of an imported function are
+/// saved into around the calls to the host hooks.
+#[derive(Debug)]
+struct SavedValues {
+    params: Vec<GlobalIndex>,
+    results: Vec<GlobalIndex>,
+}
+
+#[derive(Debug)]
+struct CallTraceHooks {
+    /// The index of the imported `enter` hook.
+    enter: Option<FunctionIndex>,
+    /// The index of the imported `exit` hook.
+    exit: Option<FunctionIndex>,
+    /// The index of the imported `host_call` hook.
+    host_call: Option<FunctionIndex>,
+    /// The index of the imported `host_return` hook.
+    host_return: Option<FunctionIndex>,
+    /// The number of functions imported by the original module.
+    num_imported_functions: usize,
+    /// The number of hooks appended to the imported functions.
+    num_hooks: usize,
+    /// The saved values of each function imported by the original module,
+    /// or `None` if its signature has values that can't be saved.
+    saved_values: Vec<Option<SavedValues>>,
+}
+
+impl CallTraceHooks {
+    /// Maps a function index of the original module to the index of the
+    /// same function in the instrumented module.
+    ///
+    /// The hooks are appended to the imported functions, so every
+    /// function defined by the module is moved by the number of hooks.
+    fn remap(&self, index: FunctionIndex) -> FunctionIndex {
+        if index.index() < self.num_imported_functions {
+            index
+        } else {
+            FunctionIndex::new(index.index() + self.num_hooks)
+        }
+    }
+}
+
+/// The module-level call trace middleware.
+///
+/// The indexes passed to the hooks are the function indexes of the
+/// instrumented module, as found in `Module::info`.
+///
+/// Only the direct calls to imported functions are traced, and only when
+/// their signature is made of numbers.
+///
+/// # Panic
+///
+/// An instance of `CallTrace` should not be shared among different modules, since it tracks
+/// module-specific information like the index of the imported hooks. Attempts to use
+/// a `CallTrace` instance from multiple modules will result in a panic.
+#[derive(Debug)]
+pub struct CallTrace {
+    /// Whether to trace the entry and exit of the functions.
+    functions: bool,
+
+    /// Whether to trace the calls to the imported functions.
+    host_calls: bool,
+
+    /// The indexes of the imported hooks.
+    hooks: Mutex<Option<Arc<CallTraceHooks>>>,
+}
+
+/// The function-level call trace middleware.
+#[derive(Debug)]
+pub struct FunctionCallTrace {
+    /// The indexes of the imported hooks.
+    hooks: Arc<CallTraceHooks>,
+
+    /// The index of the instrumented function.
+    function_index: FunctionIndex,
+
+    /// Whether the `enter` hook has been called already.
+    entered: bool,
+
+    /// The number of blocks the current operator is nested in.
+    depth: usize,
+}
+
+impl CallTrace {
+    /// Creates a `CallTrace` middleware, tracing the entry and exit of the
+    /// functions and/or the calls to the imported functions.
+    pub fn new(functions: bool, host_calls: bool) -> Self {
+        Self {
+            functions,
+            host_calls,
+            hooks: Mutex::new(None),
+        }
+    }
+}
+
+impl ModuleMiddleware for CallTrace {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        let hooks = self.hooks.lock().unwrap().clone().unwrap();
+        Box::new(FunctionCallTrace {
+            function_index: FunctionIndex::new(
+                hooks.num_imported_functions + hooks.num_hooks + local_function_index.index(),
+            ),
+            hooks,
+            entered: false,
+            depth: 0,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut hooks = self.hooks.lock().unwrap();
+
+        if hooks.is_some() {
+            panic!("CallTrace::transform_module_info: Attempting to use a `CallTrace` middleware from multiple modules.");
+        }
+
+        let num_imported_functions = module_info.num_imported_functions;
+        let saved_values = if self.host_calls {
+            save_imported_values(module_info)
+        } else {
+            Vec::new()
+        };
+
+        // Append the hooks to the imported functions.
+        let signature: SignatureIndex = module_info
+            .signatures
+            .push(FunctionType::new(vec![Type::I32], vec![]));
+        let mut functions: PrimaryMap<FunctionIndex, SignatureIndex> = module_info
+            .functions
+            .values()
+            .take(num_imported_functions)
+            .cloned()
+            .collect();
+        let mut import_hook = |enabled: bool, name: &str| {
+            if !enabled {
+                return None;
+            }
+            let index = functions.push(signature);
+            let import_index = module_info.imports.len() as u32;
+            module_info.imports.insert(
+                (
+                    CALL_TRACE_NAMESPACE.to_string(),
+                    name.to_string(),
+                    import_index,
+                ),
+                ImportIndex::Function(index),
+            );
+            Some(index)
+        };
+        let enter = import_hook(self.functions, ENTER_FUNCTION);
+        let exit = import_hook(self.functions, EXIT_FUNCTION);
+        let host_call = import_hook(self.host_calls, HOST_CALL_FUNCTION);
+        let host_return = import_hook(self.host_calls, HOST_RETURN_FUNCTION);
+        let num_hooks = functions.len() - num_imported_functions;
+        for signature in module_info.functions.values().skip(num_imported_functions) {
+            functions.push(*signature);
+        }
+        module_info.functions = functions;
+        module_info.num_imported_functions += num_hooks;
+
+        // Update every reference to the functions defined by the module.
+        let new_hooks = CallTraceHooks {
+            enter,
+            exit,
+            host_call,
+            host_return,
+            num_imported_functions,
+            num_hooks,
+            saved_values,
+        };
+        for export in module_info.exports.values_mut() {
+            if let ExportIndex::Function(index) = export {
+                *index = new_hooks.remap(*index);
+            }
+        }
+        module_info.start_function = module_info
+            .start_function
+            .map(|index| new_hooks.remap(index));
+        for initializer in module_info.table_initializers.iter_mut() {
+            for index in initializer.elements.iter_mut() {
+                *index = new_hooks.remap(*index);
+            }
+        }
+        for elements in module_info.passive_elements.values_mut() {
+            for index in elements.iter_mut() {
+                *index = new_hooks.remap(*index);
+            }
+        }
+        for initializer in module_info.global_initializers.values_mut() {
+            if let GlobalInit::RefFunc(index) = initializer {
+                *index = new_hooks.remap(*index);
+            }
+        }
+        module_info.function_names = module_info
+            .function_names
+            .drain()
+            .map(|(index, name)| (new_hooks.remap(index), name))
+            .collect::<HashMap<_, _>>();
+
+        *hooks = Some(Arc::new(new_hooks));
+    }
+}
+
+/// The name of the exported global holding the `nth` saved value of the
+/// given type.
+fn saved_value_global_name(ty: Type, nth: usize) -> String {
+    format!(
+        "wasmer_call_trace_{}_{}",
+        ty.to_string().to_lowercase(),
+        nth
+    )
+}
+
+/// Assigns the globals of each type to the given value types, in order.
+fn assign_globals(globals: &HashMap<Type, Vec<GlobalIndex>>, types: &[Type]) -> Vec<GlobalIndex> {
+    let mut used: HashMap<Type, usize> = HashMap::new();
+    types
+        .iter()
+        .map(|ty| {
+            let nth = used.entry(*ty).or_insert(0);
+            *nth += 1;
+            globals[ty][*nth - 1]
+        })
+        .collect()
+}
+
+/// Appends the globals needed to save the arguments and the results of
+/// the imported functions, and assigns them to each imported function.
+fn save_imported_values(module_info: &mut ModuleInfo) -> Vec<Option<SavedValues>> {
+    let is_saved = |types: &[Type]| types.iter().all(|ty| SAVED_TYPES.contains(ty));
+    let signatures = (0..module_info.num_imported_functions)
+        .map(|index| {
+            let signature = module_info.functions[FunctionIndex::new(index)];
+            module_info.signatures[signature].clone()
+        })
+        .map(|signature| {
+            if is_saved(signature.params()) && is_saved(signature.results()) {
+                Some(signature)
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // The arguments and the results are never saved at the same time, so
+    // they share the same globals.
+    let mut globals: HashMap<Type, Vec<GlobalIndex>> = HashMap::new();
+    for ty in SAVED_TYPES.iter() {
+        let count = |types: &[Type]| types.iter().filter(|other| *other == ty).count();
+        let needed = signatures
+            .iter()
+            .flatten()
+            .map(|signature| count(signature.params()).max(count(signature.results())))
+            .max()
+            .unwrap_or(0);
+        let indexes = (0..needed)
+            .map(|nth| {
+                let index = module_info
+                    .globals
+                    .push(GlobalType::new(*ty, Mutability::Var));
+                module_info.global_initializers.push(match ty {
+                    Type::I32 => GlobalInit::I32Const(0),
+                    Type::I64 => GlobalInit::I64Const(0),
+                    Type::F32 => GlobalInit::F32Const(0.0),
+                    _ => GlobalInit::F64Const(0.0),
+                });
+                module_info.exports.insert(
+                    saved_value_global_name(*ty, nth),
+                    ExportIndex::Global(index),
+                );
+                index
+            })
+            .collect();
+        globals.insert(*ty, indexes);
+    }
+
+    signatures
+        .iter()
+        .map(|signature| {
+            signature.as_ref().map(|signature| SavedValues {
+                params: assign_globals(&globals, signature.params()),
+                results: assign_globals(&globals, signature.results()),
+            })
+        })
+        .collect()
+}
+
+impl FunctionCallTrace {
+    fn call_hook(
+        &self,
+        hook: Option<FunctionIndex>,
+        function_index: FunctionIndex,
+        state: &mut MiddlewareReaderState<'_>,
+    ) {
+        if let Some(hook) = hook {
+            state.extend(&[
+                Operator::I32Const {
+                    value: function_index.as_u32() as i32,
+                },
+                Operator::Call {
+                    function_index: hook.as_u32(),
+                },
+            ]);
+        }
+    }
+
+    fn remap(&self, function_index: u32) -> u32 {
+        self.hooks
+            .remap(FunctionIndex::from_u32(function_index))
+            .as_u32()
+    }
+
+    /// Calls the imported function, saving its arguments and results
+    /// around the calls to the host hooks.
+    fn call_imported(
+        &self,
+        function_index: FunctionIndex,
+        saved: &SavedValues,
+        state: &mut MiddlewareReaderState<'_>,
+    ) {
+        let save = |globals: &[GlobalIndex], state: &mut MiddlewareReaderState<'_>| {
+            state.extend(globals.iter().rev().map(|global| Operator::GlobalSet {
+                global_index: global.as_u32(),
+            }));
+        };
+        let restore = |globals: &[GlobalIndex], state: &mut MiddlewareReaderState<'_>| {
+            state.extend(globals.iter().map(|global| Operator::GlobalGet {
+                global_index: global.as_u32(),
+            }));
+        };
+
+        save(&saved.params, state);
+        self.call_hook(self.hooks.host_call, function_index, state);
+        restore(&saved.params, state);
+        state.push_operator(Operator::Call {
+            function_index: function_index.as_u32(),
+        });
+        save(&saved.results, state);
+        self.call_hook(self.hooks.host_return, function_index, state);
+        restore(&saved.results, state);
+    }
+}
+
+impl FunctionMiddleware for FunctionCallTrace {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !self.entered {
+            self.entered = true;
+            self.call_hook(self.hooks.enter, self.function_index, state);
+        }
+
+        let operator = match operator {
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Try { .. } => {
+                self.depth += 1;
+                operator
+            }
+            Operator::End => {
+                // The last `end` of the body is the end of the function
+                match self.depth.checked_sub(1) {
+                    Some(depth) => self.depth = depth,
+                    None => self.call_hook(self.hooks.exit, self.function_index, state),
+                }
+                operator
+            }
+            Operator::Return | Operator::ReturnCallIndirect { .. } => {
+                self.call_hook(self.hooks.exit, self.function_index, state);
+                operator
+            }
+            Operator::ReturnCall { function_index } => {
+                self.call_hook(self.hooks.exit, self.function_index, state);
+                Operator::ReturnCall {
+                    function_index: self.remap(function_index),
+                }
+            }
+            Operator::Call { function_index } => {
+                let saved = self
+                    .hooks
+                    .saved_values
+                    .get(function_index as usize)
+                    .and_then(Option::as_ref);
+                if let Some(saved) = saved {
+                    self.call_imported(FunctionIndex::from_u32(function_index), saved, state);
+                    return Ok(());
+                }
+                Operator::Call {
+                    function_index: self.remap(function_index),
+                }
+            }
+            Operator::RefFunc { function_index } => Operator::RefFunc {
+                function_index: self.remap(function_index),
+            },
+            operator => operator,
+        };
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// Get the values saved by the instrumented module around a host call:
+/// the arguments of the imported function from the `host_call` hook, or
+/// its results from the `host_return` hook.
+///
+/// # Panic
+///
+/// The instance Module must have been processed with the [`CallTrace`] middleware
+/// tracing host calls at compile time, and `types` must be the parameters or the
+/// results of the imported function, otherwise this will panic.
+pub fn get_saved_values(instance: &Instance, types: &[Type]) -> Vec<Val> {
+    let mut used: HashMap<Type, usize> = HashMap::new();
+    types
+        .iter()
+        .map(|ty| {
+            let nth = used.entry(*ty).or_insert(0);
+            let name = saved_value_global_name(*ty, *nth);
+            *nth += 1;
+            instance
+                .exports
+                .get_global(&name)
+                .unwrap_or_else(|_| panic!("Can't get `{}` from Instance", name))
+                .get()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasmer::{
+        imports, wat2wasm, CompilerConfig, Cranelift, Function, HostEnvInitError, LazyInit, Module,
+        Store, WasmerEnv, JIT,
+    };
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (import "env" "add" (func $add (param i32 i64) (result i64)))
+            (func $double (param $value i32) (result i32)
+                local.get $value
+                i32.const 2
+                i32.mul)
+            (func $quadruple (export "quadruple") (param $value i32) (result i64)
+                local.get $value
+                call $double
+                call $double
+                i64.const 1
+                call $add
+                return))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    // `Val` isn't `Send`, so the saved integers are recorded as `i64`.
+    type Events = Arc<Mutex<Vec<(&'static str, i32, Vec<i64>)>>>;
+
+    #[derive(Clone, Default)]
+    struct Env {
+        events: Events,
+        instance: LazyInit<Instance>,
+    }
+
+    impl WasmerEnv for Env {
+        fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+            self.instance.initialize(instance.clone());
+            Ok(())
+        }
+    }
+
+    impl Env {
+        fn record(&self, event: &'static str, index: i32, types: &[Type]) {
+            let values = get_saved_values(self.instance.get_ref().unwrap(), types)
+                .iter()
+                .map(|value| match value {
+                    Val::I32(value) => *value as i64,
+                    Val::I64(value) => *value,
+                    value => panic!("unexpected value {:?}", value),
+                })
+                .collect();
+            self.events.lock().unwrap().push((event, index, values));
+        }
+    }
+
+    #[test]
+    fn hooks_are_called() {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(CallTrace::new(true, true)));
+        let store = Store::new(&JIT::new(compiler_config).engine());
+        let module = Module::new(&store, bytecode()).unwrap();
+
+        let env = Env::default();
+        let imports = imports! {
+            "env" => {
+                "add" => Function::new_native(&store, |a: i32, b: i64| a as i64 + b),
+            },
+            CALL_TRACE_NAMESPACE => {
+                ENTER_FUNCTION => Function::new_native_with_env(&store, env.clone(), |env: &Env, index: i32| {
+                    env.record("enter", index, &[])
+                }),
+                EXIT_FUNCTION => Function::new_native_with_env(&store, env.clone(), |env: &Env, index: i32| {
+                    env.record("exit", index, &[])
+                }),
+                HOST_CALL_FUNCTION => Function::new_native_with_env(&store, env.clone(), |env: &Env, index: i32| {
+                    env.record("host_call", index, &[Type::I32, Type::I64])
+                }),
+                HOST_RETURN_FUNCTION => Function::new_native_with_env(&store, env.clone(), |env: &Env, index: i32| {
+                    env.record("host_return", index, &[Type::I64])
+                }),
+            },
+        };
+        let instance = Instance::new(&module, &imports).unwrap();
+        let quadruple = instance
+            .exports
+            .get_function("quadruple")
+            .unwrap()
+            .native::<i32, i64>()
+            .unwrap();
+        assert_eq!(quadruple.call(3).unwrap(), 13);
+
+        // The imported function comes first, followed by the four hooks,
+        // so `$double` is now the function 5 and `$quadruple` the function 6.
+        assert_eq!(
+            *env.events.lock().unwrap(),
+            vec![
+                ("enter", 6, vec![]),
+                ("enter", 5, vec![]),
+                ("exit", 5, vec![]),
+                ("enter", 5, vec![]),
+                ("exit", 5, vec![]),
+                ("host_call", 0, vec![12, 1]),
+                ("host_return", 0, vec![13]),
+                ("exit", 6, vec![]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,655 @@
+//! `coverage` is a middleware for measuring which basic blocks of the
+//! functions defined by a module are executed, and how many times.
+//!
+//! Each basic block increments a 64-bit counter in a memory the
+//! middleware adds to the module and exports as [`COUNTERS_MEMORY`]
+//! when it's entered.
+//! [`Coverage::report`] maps the counters back to the functions and the
+//! Wasm offsets of their blocks, or to source lines when the module has
+//! DWARF, and the report can be written in the lcov format.
+
+use crate::metering::is_block_boundary;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{MemoryImmediate, Operator};
+use wasmer::{
+    ExportIndex, FunctionMiddleware, Instance, LocalFunctionIndex, MemoryType, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware, Pages, WASM_PAGE_SIZE,
+};
+use wasmer_types::entity::EntityRef;
+use wasmer_types::MemoryIndex;
+use wasmer_vm::ModuleInfo;
+
+/// The name of the exported memory holding the counters.
+pub const COUNTERS_MEMORY: &str = "wasmer_coverage_counters";
+
+/// The number of blocks counted by default.
+const DEFAULT_MAX_BLOCKS: usize = 64 * 1024;
+
+/// A basic block, as instrumented.
+#[derive(Debug, Clone)]
+struct Block {
+    function: LocalFunctionIndex,
+    /// The module offset of the first operator of the block.
+    start: usize,
+    /// The module offset of the operator ending the block.
+    end: usize,
+}
+
+#[derive(Debug)]
+struct CoverageState {
+    /// The index of the counters memory.
+    memory_index: MemoryIndex,
+    /// The instrumented blocks, indexed by counter.
+    blocks: Mutex<Vec<Block>>,
+    /// Whether some blocks were left uncounted, past the maximum number
+    /// of blocks.
+    truncated: AtomicBool,
+}
+
+/// The module-level coverage middleware.
+///
+/// Push it before the other middlewares, so it only counts the blocks of
+/// the original code.
+///
+/// The counters are assigned to the blocks while compiling, so the
+/// report can only be made by the `Coverage` the module was compiled
+/// with. Blocks after the first [`Coverage::max_blocks`] are not counted,
+/// and the report is then flagged as truncated.
+///
+/// # Panic
+///
+/// An instance of `Coverage` should not be shared among different modules, since it tracks
+/// module-specific information like the blocks each counter belongs to. Attempts to use
+/// a `Coverage` instance from multiple modules will result in a panic.
+#[derive(Debug)]
+pub struct Coverage {
+    /// The maximum number of blocks counted.
+    max_blocks: usize,
+
+    /// The counters memory and the instrumented blocks.
+    state: Mutex<Option<Arc<CoverageState>>>,
+}
+
+/// The function-level coverage middleware.
+#[derive(Debug)]
+pub struct FunctionCoverage {
+    /// The counters memory and the instrumented blocks.
+    state: Arc<CoverageState>,
+
+    /// The maximum number of blocks counted.
+    max_blocks: usize,
+
+    /// The index of the instrumented function.
+    function: LocalFunctionIndex,
+
+    /// Whether the current block has any operator yet.
+    in_block: bool,
+
+    /// The counter of the current block, unless it's past the maximum
+    /// number of blocks.
+    counter: Option<usize>,
+}
+
+impl Coverage {
+    /// Creates a `Coverage` middleware.
+    pub fn new() -> Self {
+        Self {
+            max_blocks: DEFAULT_MAX_BLOCKS,
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Sets the maximum number of blocks counted, 65536 by default.
+    ///
+    /// Each block takes 8 bytes of the counters memory.
+    pub fn max_blocks(mut self, max_blocks: usize) -> Self {
+        self.max_blocks = max_blocks;
+        self
+    }
+
+    /// Reads the counters of an `Instance` of the module compiled with
+    /// this middleware.
+    ///
+    /// # Panic
+    ///
+    /// The instance Module must have been compiled with this middleware,
+    /// otherwise this will panic.
+    pub fn report(&self, instance: &Instance) -> CoverageReport {
+        let state = self
+            .state
+            .lock()
+            .unwrap()
+            .clone()
+            .expect("Coverage::report: the middleware hasn't compiled any module");
+        let memory = instance
+            .exports
+            .get_memory(COUNTERS_MEMORY)
+            .expect("Can't get `wasmer_coverage_counters` from Instance");
+        let counters = memory.view::<u64>();
+        let module = instance.module();
+        let info = module.info();
+
+        let mut functions: BTreeMap<LocalFunctionIndex, Vec<BlockCoverage>> = BTreeMap::new();
+        for (counter, block) in state.blocks.lock().unwrap().iter().enumerate() {
+            let location = module.source_frames(block.start).into_iter().next();
+            functions
+                .entry(block.function)
+                .or_default()
+                .push(BlockCoverage {
+                    start: block.start,
+                    end: block.end,
+                    count: counters[counter].get(),
+                    file: location
+                        .as_ref()
+                        .and_then(|frame| frame.file().map(str::to_string)),
+                    line: location.as_ref().and_then(|frame| frame.line()),
+                });
+        }
+
+        CoverageReport {
+            module_name: module.name().unwrap_or("<module>").to_string(),
+            functions: functions
+                .into_iter()
+                .map(|(function, mut blocks)| {
+                    blocks.sort_by_key(|block| block.start);
+                    let index = info.func_index(function);
+                    FunctionReport {
+                        index: index.index() as u32,
+                        name: info.function_names.get(&index).cloned(),
+                        blocks,
+                    }
+                })
+                .collect(),
+            truncated: state.truncated.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ModuleMiddleware for Coverage {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(
+        &self,
+        local_function_index: LocalFunctionIndex,
+    ) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionCoverage {
+            state: self.state.lock().unwrap().clone().unwrap(),
+            max_blocks: self.max_blocks,
+            function: local_function_index,
+            in_block: false,
+            counter: None,
+        })
+    }
+
+    /// Transforms a `ModuleInfo` struct in-place. This is called before application on functions begins.
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.is_some() {
+            panic!("Coverage::transform_module_info: Attempting to use a `Coverage` middleware from multiple modules.");
+        }
+
+        // Append a memory large enough for all the counters.
+        let bytes = (self.max_blocks * 8).max(1);
+        let pages = Pages(((bytes + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE) as u32);
+        let memory_index = module_info
+            .memories
+            .push(MemoryType::new(pages, Some(pages), false));
+
+        module_info.exports.insert(
+            COUNTERS_MEMORY.to_string(),
+            ExportIndex::Memory(memory_index),
+        );
+
+        *state = Some(Arc::new(CoverageState {
+            memory_index,
+            blocks: Mutex::new(Vec::new()),
+            truncated: AtomicBool::new(false),
+        }))
+    }
+}
+
+impl FunctionMiddleware for FunctionCoverage {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        let offset = state.current_offset();
+
+        // The counter is incremented before the first operator of the
+        // block, which every path into the block runs.
+        if !self.in_block {
+            self.in_block = true;
+            let mut blocks = self.state.blocks.lock().unwrap();
+            self.counter = if blocks.len() < self.max_blocks {
+                let memarg = MemoryImmediate {
+                    align: 3,
+                    offset: (blocks.len() * 8) as u32,
+                    memory: self.state.memory_index.as_u32(),
+                };
+                state.extend(&[
+                    // counters[block] += 1;
+                    Operator::I32Const { value: 0 },
+                    Operator::I32Const { value: 0 },
+                    Operator::I64Load { memarg },
+                    Operator::I64Const { value: 1 },
+                    Operator::I64Add,
+                    Operator::I64Store { memarg },
+                ]);
+                // The end of the block is only known at its last operator.
+                blocks.push(Block {
+                    function: self.function,
+                    start: offset,
+                    end: offset,
+                });
+                Some(blocks.len() - 1)
+            } else {
+                self.state.truncated.store(true, Ordering::Relaxed);
+                None
+            };
+        }
+
+        // Unlike metering, a block is also ended by `if`, which may skip
+        // the rest of it, and by `unreachable`, which never completes it.
+        let ends_block = is_block_boundary(&operator)
+            || matches!(operator, Operator::If { .. } | Operator::Unreachable);
+        if ends_block {
+            self.in_block = false;
+            if let Some(counter) = self.counter.take() {
+                self.state.blocks.lock().unwrap()[counter].end = offset;
+            }
+        }
+        state.push_operator(operator);
+
+        Ok(())
+    }
+}
+
+/// The counters of an instance, per function and basic block.
+#[derive(Debug, Clone)]
+pub struct CoverageReport {
+    module_name: String,
+    functions: Vec<FunctionReport>,
+    truncated: bool,
+}
+
+/// The counters of a function.
+#[derive(Debug, Clone)]
+pub struct FunctionReport {
+    index: u32,
+    name: Option<String>,
+    blocks: Vec<BlockCoverage>,
+}
+
+/// The counter of a basic block.
+#[derive(Debug, Clone)]
+pub struct BlockCoverage {
+    start: usize,
+    end: usize,
+    count: u64,
+    file: Option<String>,
+    line: Option<u32>,
+}
+
+impl CoverageReport {
+    /// The source file and line of a block, or the module and the Wasm
+    /// offset of the block if it has no source location.
+    fn location<'a>(&'a self, block: &'a BlockCoverage) -> (&'a str, u32) {
+        match (&block.file, block.line) {
+            (Some(file), Some(line)) => (file.as_str(), line),
+            _ => (self.module_name.as_str(), block.start as u32),
+        }
+    }
+
+    /// The instrumented functions, in the order of their index.
+    pub fn functions(&self) -> &[FunctionReport] {
+        &self.functions
+    }
+
+    /// Whether the module has more blocks than [`Coverage::max_blocks`],
+    /// so that the blocks past them are missing from the report.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Write the report in the lcov tracefile format, read by tools like
+    /// `genhtml`.
+    ///
+    /// The blocks without source location are reported in a file named
+    /// after the module, with their Wasm offset as line number.
+    pub fn write_lcov<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        #[derive(Default)]
+        struct SourceFile {
+            functions: Vec<(u32, String, u64)>,
+            lines: BTreeMap<u32, u64>,
+        }
+
+        let mut files: BTreeMap<&str, SourceFile> = BTreeMap::new();
+        for function in &self.functions {
+            let first = match function.blocks.first() {
+                Some(first) => first,
+                None => continue,
+            };
+            let (file, line) = self.location(first);
+            let name = function
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("wasm-function[{}]", function.index));
+            files
+                .entry(file)
+                .or_default()
+                .functions
+                .push((line, name, function.hits()));
+            for block in &function.blocks {
+                let (file, line) = self.location(block);
+                let count = files
+                    .entry(file)
+                    .or_default()
+                    .lines
+                    .entry(line)
+                    .or_insert(0);
+                // Several blocks may share a line; it ran as many times
+                // as the most executed one.
+                *count = (*count).max(block.count);
+            }
+        }
+
+        for (file, source) in &files {
+            writeln!(out, "TN:")?;
+            writeln!(out, "SF:{}", file)?;
+            for (line, name, _) in &source.functions {
+                writeln!(out, "FN:{},{}", line, name)?;
+            }
+            for (_, name, hits) in &source.functions {
+                writeln!(out, "FNDA:{},{}", hits, name)?;
+            }
+            writeln!(out, "FNF:{}", source.functions.len())?;
+            let functions_hit = source.functions.iter().filter(|f| f.2 > 0).count();
+            writeln!(out, "FNH:{}", functions_hit)?;
+            for (line, count) in &source.lines {
+                writeln!(out, "DA:{},{}", line, count)?;
+            }
+            writeln!(out, "LF:{}", source.lines.len())?;
+            let lines_hit = source.lines.values().filter(|count| **count > 0).count();
+            writeln!(out, "LH:{}", lines_hit)?;
+            writeln!(out, "end_of_record")?;
+        }
+        Ok(())
+    }
+}
+
+impl FunctionReport {
+    /// The index of the function in the module.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// The name of the function, if the module has one for it.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The basic blocks of the function, in the order of their offset.
+    pub fn blocks(&self) -> &[BlockCoverage] {
+        &self.blocks
+    }
+
+    /// The number of times the function ran, as counted by its first
+    /// block.
+    pub fn hits(&self) -> u64 {
+        self.blocks.first().map_or(0, |block| block.count)
+    }
+}
+
+impl BlockCoverage {
+    /// The module offset of the first operator of the block.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The module offset of the operator ending the block.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// The number of times the block was entered. A trap may have stopped
+    /// some of them before its end.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The source file of the block, when the module has DWARF.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    /// The source line of the block, when the module has DWARF.
+    pub fn line(&self) -> Option<u32> {
+        self.line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use wasmer::{imports, wat2wasm, CompilerConfig, Cranelift, Module, Store, JIT};
+
+    fn bytecode() -> Vec<u8> {
+        wat2wasm(
+            br#"
+            (module
+            (func $choose (param $value i32) (result i32)
+                local.get $value
+                if (result i32)
+                    i32.const 1
+                else
+                    i32.const 2
+                end)
+            (export "choose" (func $choose)))
+            "#,
+        )
+        .unwrap()
+        .into()
+    }
+
+    fn instantiate(coverage: &Arc<Coverage>) -> Instance {
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(coverage.clone());
+        let store = Store::new(&JIT::new(compiler_config).engine());
+        let module = Module::new(&store, bytecode()).unwrap();
+        Instance::new(&module, &imports! {}).unwrap()
+    }
+
+    #[test]
+    fn counts_blocks() {
+        let coverage = Arc::new(Coverage::new());
+        let instance = instantiate(&coverage);
+        let choose = instance
+            .exports
+            .get_function("choose")
+            .unwrap()
+            .native::<i32, i32>()
+            .unwrap();
+        assert_eq!(choose.call(1).unwrap(), 1);
+        assert_eq!(choose.call(1).unwrap(), 1);
+
+        let report = coverage.report(&instance);
+        let functions = report.functions();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(functions[0].name(), Some("choose"));
+        assert_eq!(functions[0].hits(), 2);
+        // The condition, the `then` branch, the `else` branch, and the
+        // end of the function.
+        let counts = functions[0]
+            .blocks()
+            .iter()
+            .map(BlockCoverage::count)
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![2, 2, 0, 2]);
+        assert!(!report.is_truncated());
+    }
+
+    #[test]
+    fn counts_blocks_stopped_by_a_trap() {
+        let coverage = Arc::new(Coverage::new());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(coverage.clone());
+        let store = Store::new(&JIT::new(compiler_config).engine());
+        let wat = br#"
+            (module
+            (func $divide (export "divide") (param $value i32) (result i32)
+                i32.const 42
+                local.get $value
+                i32.div_u))
+            "#;
+        let module = Module::new(&store, wat2wasm(wat).unwrap()).unwrap();
+        let instance = Instance::new(&module, &imports! {}).unwrap();
+        let divide = instance
+            .exports
+            .get_function("divide")
+            .unwrap()
+            .native::<i32, i32>()
+            .unwrap();
+        assert_eq!(divide.call(2).unwrap(), 21);
+        assert!(divide.call(0).is_err());
+
+        let report = coverage.report(&instance);
+        assert_eq!(report.functions()[0].hits(), 2);
+    }
+
+    #[test]
+    fn flags_truncated_reports() {
+        let coverage = Arc::new(Coverage::new().max_blocks(2));
+        let instance = instantiate(&coverage);
+        let choose = instance
+            .exports
+            .get_function("choose")
+            .unwrap()
+            .native::<i32, i32>()
+            .unwrap();
+        assert_eq!(choose.call(0).unwrap(), 2);
+
+        let report = coverage.report(&instance);
+        assert!(report.is_truncated());
+        let counts = report.functions()[0]
+            .blocks()
+            .iter()
+            .map(BlockCoverage::count)
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![1, 0]);
+    }
+
+    #[test]
+    fn writes_lcov() {
+        let coverage = Arc::new(Coverage::new().max_blocks(16));
+        let instance = instantiate(&coverage);
+        let choose = instance
+            .exports
+            .get_function("choose")
+            .unwrap()
+            .native::<i32, i32>()
+            .unwrap();
+        assert_eq!(choose.call(0).unwrap(), 2);
+
+        let mut lcov = Vec::new();
+        coverage.report(&instance).write_lcov(&mut lcov).unwrap();
+        let lcov = String::from_utf8(lcov).unwrap();
+        assert!(lcov.starts_with("TN:\nSF:<module>\n"));
+        assert!(lcov.contains("FNDA:1,choose\n"));
+        assert!(lcov.contains("FNH:1\n"));
+        assert!(lcov.contains("LF:4\nLH:3\n"));
+        assert!(lcov.ends_with("end_of_record\n"));
+    }
+
+    #[test]
+    fn counters_memory_style_from_tunables() {
+        use std::ptr::NonNull;
+        use wasmer::vm::{self, MemoryError, MemoryStyle, TableStyle};
+        use wasmer::vm::{VMMemoryDefinition, VMTableDefinition};
+        use wasmer::{BaseTunables, TableType, Target, Tunables};
+
+        /// Records the memory types the styles are chosen for.
+        struct RecordingTunables {
+            base: BaseTunables,
+            memories: Arc<Mutex<Vec<MemoryType>>>,
+        }
+
+        impl Tunables for RecordingTunables {
+            fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+                self.memories.lock().unwrap().push(*memory);
+                self.base.memory_style(memory)
+            }
+            fn table_style(&self, table: &TableType) -> TableStyle {
+                self.base.table_style(table)
+            }
+            fn create_host_memory(
+                &self,
+                ty: &MemoryType,
+                style: &MemoryStyle,
+            ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+                self.base.create_host_memory(ty, style)
+            }
+            unsafe fn create_vm_memory(
+                &self,
+                ty: &MemoryType,
+                style: &MemoryStyle,
+                vm_definition_location: NonNull<VMMemoryDefinition>,
+            ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+                self.base
+                    .create_vm_memory(ty, style, vm_definition_location)
+            }
+            fn create_host_table(
+                &self,
+                ty: &TableType,
+                style: &TableStyle,
+            ) -> Result<Arc<dyn vm::Table>, String> {
+                self.base.create_host_table(ty, style)
+            }
+            unsafe fn create_vm_table(
+                &self,
+                ty: &TableType,
+                style: &TableStyle,
+                vm_definition_location: NonNull<VMTableDefinition>,
+            ) -> Result<Arc<dyn vm::Table>, String> {
+                self.base.create_vm_table(ty, style, vm_definition_location)
+            }
+        }
+
+        let coverage = Arc::new(Coverage::new().max_blocks(16));
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(coverage.clone());
+        let memories = Arc::new(Mutex::new(Vec::new()));
+        let tunables = RecordingTunables {
+            base: BaseTunables::for_target(&Target::default()),
+            memories: memories.clone(),
+        };
+        let store = Store::new_with_tunables(&JIT::new(compiler_config).engine(), tunables);
+        let module = Module::new(&store, bytecode()).unwrap();
+
+        // The memory added by the middleware gets its style from the
+        // tunables like the memories of the module.
+        assert_eq!(
+            *memories.lock().unwrap(),
+            vec![MemoryType::new(1, Some(1), false)]
+        );
+        let instance = Instance::new(&module, &imports! {}).unwrap();
+        let choose = instance
+            .exports
+            .get_function("choose")
+            .unwrap()
+            .native::<i32, i32>()
+            .unwrap();
+        assert_eq!(choose.call(1).unwrap(), 1);
+        assert_eq!(coverage.report(&instance).functions()[0].hits(), 1);
+    }
+}
@@ -1,5 +1,11 @@
+pub mod call_trace;
+pub mod coverage;
 pub mod metering;
+pub mod optimize;
 
 // The most commonly used symbol are exported at top level of the module. Others are available
 // via modules, e.g. `wasmer_middlewares::metering::get_remaining_points`
+pub use call_trace::CallTrace;
+pub use coverage::Coverage;
 pub use metering::Metering;
+pub use optimize::Optimize;
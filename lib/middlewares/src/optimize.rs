@@ -0,0 +1,822 @@
+//! `optimize` is a middleware simplifying the code of the functions
+//! before it is compiled, for the compilers that translate it as is,
+//! like Singlepass and Cranelift.
+//!
+//! The optimizations are peephole ones, done while the operators stream
+//! through the middleware chain:
+//!
+//! * constant folding of the integer arithmetic, bitwise and comparison
+//!   operators, and of constants that are dropped right away;
+//! * dead code elimination of the unreachable code following an
+//!   `unreachable`, `br`, `br_table`, `return`, `throw` or `rethrow`;
+//! * local simplification of a `local.set` followed by a `local.get` of
+//!   the same local into a `local.tee`;
+//! * inlining of the calls to small functions, found by a pre-pass over
+//!   the bodies of all the functions of the module.
+//!
+//! The operators combined or held back keep the source location of the
+//! operators they were generated from, so traps are reported where they
+//! were before.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use wasmer::wasmparser::{self, Operator};
+use wasmer::{
+    FunctionBodyData, FunctionMiddleware, LocalFunctionIndex, MiddlewareError,
+    MiddlewareReaderState, ModuleMiddleware,
+};
+use wasmer_types::entity::PrimaryMap;
+use wasmer_vm::ModuleInfo;
+
+/// The maximum number of operators held back to be combined with the
+/// next ones.
+const WINDOW_SIZE: usize = 2;
+
+/// The maximum number of operators of the functions to inline, besides
+/// the ones getting their parameters.
+const INLINE_MAX_OPERATORS: usize = 16;
+
+/// The module-level optimization middleware.
+///
+/// Push it first in the middleware chain of a compiler, so the other
+/// middlewares see the simplified code. Every optimization is enabled by
+/// default; [`Optimize::for_singlepass`] and [`Optimize::for_cranelift`]
+/// only enable the ones worth it for each compiler. LLVM optimizes the
+/// code on its own.
+///
+/// # Example
+///
+/// ```ignore
+/// let mut compiler_config = Singlepass::default();
+/// compiler_config.push_middleware(Arc::new(Optimize::for_singlepass()));
+/// ```
+///
+/// # Panic
+///
+/// When inlining is enabled, an instance of `Optimize` should not be
+/// shared among different modules, since it tracks the functions to
+/// inline of the module. Attempts to use such an `Optimize` instance from
+/// multiple modules will result in a panic. It may be shared by the
+/// baseline and tier-up compilers of a tiered engine, which both analyze
+/// the same module.
+pub struct Optimize {
+    optimizations: Optimizations,
+
+    /// The id of the analyzed module, and its functions to inline by
+    /// function index, found by the pre-pass.
+    inlined_functions: Mutex<Option<(String, Arc<InlinedFunctions>)>>,
+}
+
+/// The optimizations enabled in an `Optimize` middleware.
+#[derive(Debug, Clone, Copy)]
+struct Optimizations {
+    /// Whether to fold the operators applied to constants.
+    fold_constants: bool,
+
+    /// Whether to remove the unreachable code.
+    eliminate_dead_code: bool,
+
+    /// Whether to turn `local.set` and `local.get` pairs into `local.tee`.
+    simplify_locals: bool,
+
+    /// Whether to inline the calls to small functions.
+    inline_functions: bool,
+}
+
+/// The bodies of the functions to inline, without the operators getting
+/// their parameters, by function index.
+///
+/// A function is inlined when its body gets all its parameters in order
+/// and then applies at most `INLINE_MAX_OPERATORS` operators to them,
+/// without control flow, calls, locals or operators that may trap: its
+/// arguments are already on the stack of its callers in that order, and
+/// it doesn't show up in the backtraces.
+type InlinedFunctions = HashMap<u32, Vec<Operator<'static>>>;
+
+/// An operator held back to be combined with the next ones.
+#[derive(Debug, Clone, Copy)]
+enum Held {
+    I32Const(i32),
+    I64Const(i64),
+    LocalSet(u32),
+}
+
+impl Held {
+    fn into_operator<'a>(self) -> Operator<'a> {
+        match self {
+            Self::I32Const(value) => Operator::I32Const { value },
+            Self::I64Const(value) => Operator::I64Const { value },
+            Self::LocalSet(local_index) => Operator::LocalSet { local_index },
+        }
+    }
+}
+
+/// The function-level optimization middleware.
+#[derive(Debug)]
+pub struct FunctionOptimize {
+    optimizations: Optimizations,
+
+    /// The functions to inline.
+    inlined_functions: Option<Arc<InlinedFunctions>>,
+
+    /// The operators held back to be combined with the next ones, with
+    /// the module offset of the operators they were generated from.
+    window: Vec<(Held, usize)>,
+
+    /// While the code is unreachable, the number of blocks opened in it.
+    dead_depth: Option<usize>,
+}
+
+impl Optimize {
+    /// Creates an `Optimize` middleware with every optimization enabled.
+    pub fn new() -> Self {
+        Self {
+            optimizations: Optimizations {
+                fold_constants: true,
+                eliminate_dead_code: true,
+                simplify_locals: true,
+                inline_functions: true,
+            },
+            inlined_functions: Mutex::new(None),
+        }
+    }
+
+    /// Creates an `Optimize` middleware for Singlepass, which compiles
+    /// each operator on its own: every optimization is enabled.
+    pub fn for_singlepass() -> Self {
+        Self::new()
+    }
+
+    /// Creates an `Optimize` middleware for Cranelift, which folds
+    /// constants, removes dead code and keeps locals in registers on its
+    /// own: only inlining is enabled.
+    pub fn for_cranelift() -> Self {
+        Self::new()
+            .fold_constants(false)
+            .eliminate_dead_code(false)
+            .simplify_locals(false)
+    }
+
+    /// Enables or disables constant folding.
+    pub fn fold_constants(mut self, enable: bool) -> Self {
+        self.optimizations.fold_constants = enable;
+        self
+    }
+
+    /// Enables or disables dead code elimination.
+    pub fn eliminate_dead_code(mut self, enable: bool) -> Self {
+        self.optimizations.eliminate_dead_code = enable;
+        self
+    }
+
+    /// Enables or disables the simplification of local accesses.
+    pub fn simplify_locals(mut self, enable: bool) -> Self {
+        self.optimizations.simplify_locals = enable;
+        self
+    }
+
+    /// Enables or disables the inlining of small functions.
+    pub fn inline_functions(mut self, enable: bool) -> Self {
+        self.optimizations.inline_functions = enable;
+        self
+    }
+}
+
+impl Default for Optimize {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Optimize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Optimize")
+            .field("optimizations", &self.optimizations)
+            .finish()
+    }
+}
+
+impl ModuleMiddleware for Optimize {
+    /// Generates a `FunctionMiddleware` for a given function.
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(FunctionOptimize {
+            optimizations: self.optimizations,
+            inlined_functions: self
+                .inlined_functions
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|(_, functions)| functions.clone()),
+            window: Vec::with_capacity(WINDOW_SIZE + 1),
+            dead_depth: None,
+        })
+    }
+
+    /// Finds the functions to inline.
+    fn analyze_function_bodies(
+        &self,
+        module_info: &ModuleInfo,
+        function_bodies: &PrimaryMap<LocalFunctionIndex, FunctionBodyData<'_>>,
+    ) {
+        if !self.optimizations.inline_functions {
+            return;
+        }
+        let mut inlined_functions = self.inlined_functions.lock().unwrap();
+        if let Some((module, _)) = &*inlined_functions {
+            // The tier-up compiler of a tiered engine analyzes the module
+            // again, and finds the same functions.
+            if *module == module_info.id.id() {
+                return;
+            }
+            panic!("Optimize::analyze_function_bodies: Attempting to use an `Optimize` middleware from multiple modules.");
+        }
+
+        let functions = function_bodies
+            .iter()
+            .filter_map(|(index, body)| {
+                let function_index = module_info.func_index(index);
+                let signature = &module_info.signatures[module_info.functions[function_index]];
+                let operators = inlined_operators(body, signature.params().len())?;
+                Some((function_index.as_u32(), operators))
+            })
+            .collect();
+        *inlined_functions = Some((module_info.id.id(), Arc::new(functions)));
+    }
+}
+
+/// The operators to inline a function with, if it is small enough.
+fn inlined_operators(body: &FunctionBodyData, params: usize) -> Option<Vec<Operator<'static>>> {
+    let body = wasmparser::FunctionBody::new(body.module_offset, body.data);
+    if body.get_locals_reader().ok()?.get_count() != 0 {
+        return None;
+    }
+    let mut reader = body.get_operators_reader().ok()?;
+    for param in 0..params {
+        match reader.read().ok()? {
+            Operator::LocalGet { local_index } if local_index as usize == param => {}
+            _ => return None,
+        }
+    }
+    let mut operators = vec![];
+    loop {
+        match reader.read().ok()? {
+            Operator::End if reader.eof() => return Some(operators),
+            operator if operators.len() < INLINE_MAX_OPERATORS => {
+                operators.push(inlinable(&operator)?);
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// The operator as an operator that may be inlined, if it is one.
+fn inlinable(operator: &Operator) -> Option<Operator<'static>> {
+    macro_rules! inlinable {
+        ($($unit:ident)*; $($variant:ident { $($field:ident),* })*) => {
+            match *operator {
+                $(Operator::$unit => Some(Operator::$unit),)*
+                $(Operator::$variant { $($field),* } => Some(Operator::$variant { $($field),* }),)*
+                _ => None,
+            }
+        };
+    }
+    inlinable!(
+        Nop Drop Select
+        I32Eqz I32Eq I32Ne I32LtS I32LtU I32GtS I32GtU I32LeS I32LeU I32GeS I32GeU
+        I64Eqz I64Eq I64Ne I64LtS I64LtU I64GtS I64GtU I64LeS I64LeU I64GeS I64GeU
+        F32Eq F32Ne F32Lt F32Gt F32Le F32Ge F64Eq F64Ne F64Lt F64Gt F64Le F64Ge
+        I32Clz I32Ctz I32Popcnt I32Add I32Sub I32Mul I32And I32Or I32Xor
+        I32Shl I32ShrS I32ShrU I32Rotl I32Rotr
+        I64Clz I64Ctz I64Popcnt I64Add I64Sub I64Mul I64And I64Or I64Xor
+        I64Shl I64ShrS I64ShrU I64Rotl I64Rotr
+        F32Abs F32Neg F32Ceil F32Floor F32Trunc F32Nearest F32Sqrt
+        F32Add F32Sub F32Mul F32Div F32Min F32Max F32Copysign
+        F64Abs F64Neg F64Ceil F64Floor F64Trunc F64Nearest F64Sqrt
+        F64Add F64Sub F64Mul F64Div F64Min F64Max F64Copysign
+        I32WrapI64 I64ExtendI32S I64ExtendI32U
+        F32ConvertI32S F32ConvertI32U F32ConvertI64S F32ConvertI64U F32DemoteF64
+        F64ConvertI32S F64ConvertI32U F64ConvertI64S F64ConvertI64U F64PromoteF32
+        I32ReinterpretF32 I64ReinterpretF64 F32ReinterpretI32 F64ReinterpretI64
+        I32Extend8S I32Extend16S I64Extend8S I64Extend16S I64Extend32S
+        I32TruncSatF32S I32TruncSatF32U I32TruncSatF64S I32TruncSatF64U
+        I64TruncSatF32S I64TruncSatF32U I64TruncSatF64S I64TruncSatF64U;
+        I32Const { value }
+        I64Const { value }
+        F32Const { value }
+        F64Const { value }
+        GlobalGet { global_index }
+        GlobalSet { global_index }
+        MemorySize { mem, mem_byte }
+    )
+}
+
+/// Folds a binary operator applied to two `i32` constants.
+fn fold_i32(operator: &Operator, lhs: i32, rhs: i32) -> Option<i32> {
+    Some(match operator {
+        Operator::I32Add => lhs.wrapping_add(rhs),
+        Operator::I32Sub => lhs.wrapping_sub(rhs),
+        Operator::I32Mul => lhs.wrapping_mul(rhs),
+        Operator::I32And => lhs & rhs,
+        Operator::I32Or => lhs | rhs,
+        Operator::I32Xor => lhs ^ rhs,
+        Operator::I32Shl => lhs.wrapping_shl(rhs as u32),
+        Operator::I32ShrS => lhs.wrapping_shr(rhs as u32),
+        Operator::I32ShrU => (lhs as u32).wrapping_shr(rhs as u32) as i32,
+        Operator::I32Rotl => lhs.rotate_left(rhs as u32 % 32),
+        Operator::I32Rotr => lhs.rotate_right(rhs as u32 % 32),
+        Operator::I32Eq => (lhs == rhs) as i32,
+        Operator::I32Ne => (lhs != rhs) as i32,
+        Operator::I32LtS => (lhs < rhs) as i32,
+        Operator::I32LtU => ((lhs as u32) < (rhs as u32)) as i32,
+        Operator::I32GtS => (lhs > rhs) as i32,
+        Operator::I32GtU => ((lhs as u32) > (rhs as u32)) as i32,
+        Operator::I32LeS => (lhs <= rhs) as i32,
+        Operator::I32LeU => ((lhs as u32) <= (rhs as u32)) as i32,
+        Operator::I32GeS => (lhs >= rhs) as i32,
+        Operator::I32GeU => ((lhs as u32) >= (rhs as u32)) as i32,
+        _ => return None,
+    })
+}
+
+/// Folds a binary operator applied to two `i64` constants.
+///
+/// Comparisons result in an `i32`.
+fn fold_i64<'a>(operator: &Operator, lhs: i64, rhs: i64) -> Option<Operator<'a>> {
+    let value = match operator {
+        Operator::I64Add => lhs.wrapping_add(rhs),
+        Operator::I64Sub => lhs.wrapping_sub(rhs),
+        Operator::I64Mul => lhs.wrapping_mul(rhs),
+        Operator::I64And => lhs & rhs,
+        Operator::I64Or => lhs | rhs,
+        Operator::I64Xor => lhs ^ rhs,
+        Operator::I64Shl => lhs.wrapping_shl(rhs as u32),
+        Operator::I64ShrS => lhs.wrapping_shr(rhs as u32),
+        Operator::I64ShrU => (lhs as u64).wrapping_shr(rhs as u32) as i64,
+        Operator::I64Rotl => lhs.rotate_left((rhs as u64 % 64) as u32),
+        Operator::I64Rotr => lhs.rotate_right((rhs as u64 % 64) as u32),
+        _ => {
+            let value = match operator {
+                Operator::I64Eq => lhs == rhs,
+                Operator::I64Ne => lhs != rhs,
+                Operator::I64LtS => lhs < rhs,
+                Operator::I64LtU => (lhs as u64) < (rhs as u64),
+                Operator::I64GtS => lhs > rhs,
+                Operator::I64GtU => (lhs as u64) > (rhs as u64),
+                Operator::I64LeS => lhs <= rhs,
+                Operator::I64LeU => (lhs as u64) <= (rhs as u64),
+                Operator::I64GeS => lhs >= rhs,
+                Operator::I64GeU => (lhs as u64) >= (rhs as u64),
+                _ => return None,
+            };
+            return Some(Operator::I32Const {
+                value: value as i32,
+            });
+        }
+    };
+    Some(Operator::I64Const { value })
+}
+
+impl FunctionOptimize {
+    /// Combines `operator` with the end of the window, if possible.
+    fn combine<'a>(&mut self, operator: &Operator<'a>) -> Option<Operator<'a>> {
+        let len = self.window.len();
+        let last = self.window.last().map(|&(held, _)| held);
+        let last_two = if len >= 2 {
+            Some((self.window[len - 2].0, self.window[len - 1].0))
+        } else {
+            None
+        };
+        if self.optimizations.fold_constants {
+            match (last_two, last, operator) {
+                (_, Some(Held::I32Const(value)), Operator::I32Eqz) => {
+                    let value = (value == 0) as i32;
+                    self.window.pop();
+                    return Some(Operator::I32Const { value });
+                }
+                (_, Some(Held::I64Const(value)), Operator::I64Eqz) => {
+                    let value = (value == 0) as i32;
+                    self.window.pop();
+                    return Some(Operator::I32Const { value });
+                }
+                (Some((Held::I32Const(lhs), Held::I32Const(rhs))), _, _) => {
+                    if let Some(value) = fold_i32(operator, lhs, rhs) {
+                        self.window.truncate(len - 2);
+                        return Some(Operator::I32Const { value });
+                    }
+                }
+                (Some((Held::I64Const(lhs), Held::I64Const(rhs))), _, _) => {
+                    if let Some(folded) = fold_i64(operator, lhs, rhs) {
+                        self.window.truncate(len - 2);
+                        return Some(folded);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if self.optimizations.simplify_locals {
+            if let (Some(Held::LocalSet(set)), Operator::LocalGet { local_index }) =
+                (last, operator)
+            {
+                if set == *local_index {
+                    self.window.pop();
+                    return Some(Operator::LocalTee { local_index: set });
+                }
+            }
+        }
+        None
+    }
+
+    /// The operator to hold back, if `operator` may be combined with the
+    /// operators following it.
+    fn held(&self, operator: &Operator) -> Option<Held> {
+        match *operator {
+            Operator::I32Const { value } if self.optimizations.fold_constants => {
+                Some(Held::I32Const(value))
+            }
+            Operator::I64Const { value } if self.optimizations.fold_constants => {
+                Some(Held::I64Const(value))
+            }
+            Operator::LocalSet { local_index } if self.optimizations.simplify_locals => {
+                Some(Held::LocalSet(local_index))
+            }
+            _ => None,
+        }
+    }
+
+    /// The operators `operator` is inlined with, if it calls a function
+    /// to inline.
+    fn inlined(&self, operator: &Operator) -> Option<Arc<InlinedFunctions>> {
+        match (operator, &self.inlined_functions) {
+            (Operator::Call { function_index }, Some(functions))
+                if functions.contains_key(function_index) =>
+            {
+                Some(functions.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Pushes the operators held back to `state`.
+    fn flush<'a>(&mut self, state: &mut MiddlewareReaderState<'a>) {
+        for (held, offset) in self.window.drain(..) {
+            state.push_operator_at(held.into_operator(), offset);
+        }
+    }
+
+    /// Whether the code following `operator` is unreachable.
+    fn is_terminator(operator: &Operator) -> bool {
+        matches!(
+            operator,
+            Operator::Unreachable
+                | Operator::Br { .. }
+                | Operator::BrTable { .. }
+                | Operator::Return
+                | Operator::Throw { .. }
+                | Operator::Rethrow { .. }
+        )
+    }
+}
+
+impl FunctionMiddleware for FunctionOptimize {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        // Skip the unreachable code, up to the end of the block it is in.
+        if let Some(depth) = self.dead_depth {
+            match operator {
+                Operator::Block { .. }
+                | Operator::Loop { .. }
+                | Operator::If { .. }
+                | Operator::Try { .. } => self.dead_depth = Some(depth + 1),
+                Operator::End if depth > 0 => self.dead_depth = Some(depth - 1),
+                Operator::End | Operator::Else | Operator::Catch { .. } | Operator::Unwind
+                    if depth == 0 =>
+                {
+                    self.dead_depth = None;
+                    state.push_operator(operator);
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        // The inlined operators are optimized along with the ones around
+        // the call. They have no calls, so this doesn't recurse further.
+        if let Some(functions) = self.inlined(&operator) {
+            if let Operator::Call { function_index } = operator {
+                for inlined in &functions[&function_index] {
+                    self.feed(inlined.clone(), state)?;
+                }
+            }
+            return Ok(());
+        }
+
+        // Dropping a constant leaves nothing to compute.
+        if self.optimizations.fold_constants
+            && matches!(operator, Operator::Drop)
+            && matches!(
+                self.window.last(),
+                Some((Held::I32Const(_), _)) | Some((Held::I64Const(_), _))
+            )
+        {
+            self.window.pop();
+            return Ok(());
+        }
+
+        let mut operator = operator;
+        while let Some(combined) = self.combine(&operator) {
+            operator = combined;
+        }
+
+        if let Some(held) = self.held(&operator) {
+            self.window.push((held, state.current_offset()));
+            if self.window.len() > WINDOW_SIZE {
+                let (held, offset) = self.window.remove(0);
+                state.push_operator_at(held.into_operator(), offset);
+            }
+            return Ok(());
+        }
+
+        self.flush(state);
+        if self.optimizations.eliminate_dead_code && Self::is_terminator(&operator) {
+            self.dead_depth = Some(0);
+        }
+        state.push_operator(operator);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+    use wasmer::wasmparser::{Parser, Payload};
+    use wasmer::{imports, wat2wasm, CompilerConfig, Cranelift, Instance, Module, Store, JIT};
+
+    /// Records the operators it is fed, after the optimizations.
+    #[derive(Debug, Default)]
+    struct Record {
+        operators: Arc<Mutex<Vec<String>>>,
+        offsets: Arc<Mutex<Vec<(String, usize)>>>,
+    }
+
+    #[derive(Debug)]
+    struct FunctionRecord {
+        operators: Arc<Mutex<Vec<String>>>,
+        offsets: Arc<Mutex<Vec<(String, usize)>>>,
+    }
+
+    impl ModuleMiddleware for Record {
+        fn generate_function_middleware(
+            &self,
+            _: LocalFunctionIndex,
+        ) -> Box<dyn FunctionMiddleware> {
+            Box::new(FunctionRecord {
+                operators: self.operators.clone(),
+                offsets: self.offsets.clone(),
+            })
+        }
+    }
+
+    impl FunctionMiddleware for FunctionRecord {
+        fn feed<'a>(
+            &mut self,
+            operator: Operator<'a>,
+            state: &mut MiddlewareReaderState<'a>,
+        ) -> Result<(), MiddlewareError> {
+            let name = format!("{:?}", operator);
+            self.operators.lock().unwrap().push(name.clone());
+            self.offsets
+                .lock()
+                .unwrap()
+                .push((name, state.current_offset()));
+            state.push_operator(operator);
+            Ok(())
+        }
+    }
+
+    fn compile_recorded(optimize: Optimize, wat: &[u8]) -> (Instance, Arc<Record>) {
+        let record = Arc::new(Record::default());
+        let mut compiler_config = Cranelift::default();
+        compiler_config.push_middleware(Arc::new(optimize));
+        compiler_config.push_middleware(record.clone());
+        let store = Store::new(&JIT::new(compiler_config).engine());
+        let module = Module::new(&store, wat2wasm(wat).unwrap()).unwrap();
+        let instance = Instance::new(&module, &imports! {}).unwrap();
+        (instance, record)
+    }
+
+    fn compile(optimize: Optimize, wat: &[u8]) -> (Instance, Vec<String>) {
+        let (instance, record) = compile_recorded(optimize, wat);
+        let operators = record.operators.lock().unwrap().clone();
+        (instance, operators)
+    }
+
+    /// The operators of the only function of a module, with their offsets.
+    fn operators_with_offsets(wat: &[u8]) -> Vec<(String, usize)> {
+        let wasm = wat2wasm(wat).unwrap();
+        for payload in Parser::new(0).parse_all(&wasm) {
+            if let Payload::CodeSectionEntry(body) = payload.unwrap() {
+                let mut reader = body.get_operators_reader().unwrap();
+                let mut operators = vec![];
+                while !reader.eof() {
+                    let (operator, offset) = reader.read_with_offset().unwrap();
+                    operators.push((format!("{:?}", operator), offset));
+                }
+                return operators;
+            }
+        }
+        unreachable!("the module has no function")
+    }
+
+    #[test]
+    fn folds_constants() {
+        let (instance, operators) = compile(
+            Optimize::new(),
+            br#"
+            (module
+              (func (export "f") (result i32)
+                i32.const 6
+                i32.const 7
+                i32.mul
+                i32.const 2
+                i32.sub
+                i32.const 99
+                drop))
+            "#,
+        );
+        assert_eq!(operators, ["I32Const { value: 40 }", "End"]);
+        let f = instance
+            .exports
+            .get_native_function::<(), i32>("f")
+            .unwrap();
+        assert_eq!(f.call().unwrap(), 40);
+    }
+
+    #[test]
+    fn removes_dead_code() {
+        let (instance, operators) = compile(
+            Optimize::new(),
+            br#"
+            (module
+              (func (export "f") (param i32) (result i32)
+                (block
+                  local.get 0
+                  br_if 0
+                  i32.const 1
+                  return
+                  (block
+                    i32.const 2
+                    drop)
+                  i32.const 3
+                  drop)
+                i32.const 4))
+            "#,
+        );
+        assert!(!operators.iter().any(|op| op == "I32Const { value: 2 }"));
+        assert!(!operators.iter().any(|op| op == "I32Const { value: 3 }"));
+        let f = instance
+            .exports
+            .get_native_function::<i32, i32>("f")
+            .unwrap();
+        assert_eq!(f.call(0).unwrap(), 1);
+        assert_eq!(f.call(1).unwrap(), 4);
+    }
+
+    #[test]
+    fn simplifies_locals() {
+        let wat = br#"
+            (module
+              (func (export "f") (param i32) (result i32) (local i32)
+                local.get 0
+                local.set 1
+                local.get 1))
+            "#;
+        let (instance, operators) = compile(Optimize::new(), wat);
+        assert!(operators
+            .iter()
+            .any(|op| op == "LocalTee { local_index: 1 }"));
+        let f = instance
+            .exports
+            .get_native_function::<i32, i32>("f")
+            .unwrap();
+        assert_eq!(f.call(5).unwrap(), 5);
+
+        let (_, operators) = compile(Optimize::new().simplify_locals(false), wat);
+        assert!(!operators.iter().any(|op| op.starts_with("LocalTee")));
+    }
+
+    #[test]
+    fn inlines_small_functions() {
+        let (instance, operators) = compile(
+            Optimize::new(),
+            br#"
+            (module
+              (global $g (mut i32) (i32.const 5))
+              (func $add (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.add)
+              (func $get (result i32)
+                global.get $g)
+              (func $div (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.div_u)
+              (func (export "f") (result i32)
+                i32.const 1
+                i32.const 2
+                call $add
+                call $get
+                i32.add
+                i32.const 12
+                i32.const 4
+                call $div
+                i32.add))
+            "#,
+        );
+        assert!(!operators
+            .iter()
+            .any(|op| op == "Call { function_index: 0 }"));
+        assert!(!operators
+            .iter()
+            .any(|op| op == "Call { function_index: 1 }"));
+        // Functions that may trap keep their frame in the backtraces.
+        assert!(operators
+            .iter()
+            .any(|op| op == "Call { function_index: 2 }"));
+        // The inlined operators are optimized with their caller's.
+        assert!(operators.iter().any(|op| op == "I32Const { value: 3 }"));
+        let f = instance
+            .exports
+            .get_native_function::<(), i32>("f")
+            .unwrap();
+        assert_eq!(f.call().unwrap(), 11);
+
+        let (_, operators) = compile(
+            Optimize::for_singlepass().inline_functions(false),
+            br#"
+            (module
+              (func $get (result i32)
+                i32.const 1)
+              (func (export "f") (result i32)
+                call $get))
+            "#,
+        );
+        assert!(operators
+            .iter()
+            .any(|op| op == "Call { function_index: 0 }"));
+    }
+
+    #[test]
+    fn keeps_the_offsets_of_held_operators() {
+        let wat = br#"
+            (module
+              (func (export "f") (param i32) (result i32) (local i32)
+                i32.const 1
+                local.get 0
+                i32.add
+                local.set 1
+                i32.const 2
+                drop
+                local.get 0))
+            "#;
+        let (_, record) = compile_recorded(Optimize::new(), wat);
+        let offsets = record.offsets.lock().unwrap().clone();
+        let original = operators_with_offsets(wat);
+        // `local.set 1` and `local.get 0` aren't combined, and the dropped
+        // constant is removed.
+        let expected = original
+            .into_iter()
+            .filter(|(op, _)| op != "I32Const { value: 2 }" && op != "Drop")
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, expected);
+    }
+
+    #[test]
+    fn traps_at_the_offset_of_held_operators() {
+        let wat = br#"
+            (module
+              (func (export "f") (result i32)
+                i32.const 1
+                i32.const 0
+                i32.div_u))
+            "#;
+        let (instance, _) = compile_recorded(Optimize::new(), wat);
+        let div_offset = operators_with_offsets(wat)
+            .into_iter()
+            .find(|(op, _)| op == "I32DivU")
+            .unwrap()
+            .1;
+        let f = instance
+            .exports
+            .get_native_function::<(), i32>("f")
+            .unwrap();
+        let error = f.call().unwrap_err();
+        assert_eq!(error.trace()[0].module_offset(), div_offset);
+    }
+}
@@ -1,9 +1,13 @@
-//! Runtime build script compiles C code using setjmp for trap handling.
+//! Runtime build script compiles C code using setjmp for trap handling,
+//! and ucontext for running guest code on its own stack.
 
 fn main() {
     println!("cargo:rerun-if-changed=src/trap/helpers.c");
-    cc::Build::new()
-        .warnings(true)
-        .file("src/trap/helpers.c")
-        .compile("helpers");
+    println!("cargo:rerun-if-changed=src/trap/stack.c");
+    let mut build = cc::Build::new();
+    build.warnings(true).file("src/trap/helpers.c");
+    if std::env::var_os("CARGO_CFG_UNIX").is_some() {
+        build.file("src/trap/stack.c");
+    }
+    build.compile("helpers");
 }
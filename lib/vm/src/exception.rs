@@ -0,0 +1,54 @@
+//! Exceptions thrown by `throw`, from the exception handling proposal.
+
+use crate::tag::Tag;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+/// A thrown exception: a tag and the values it carries.
+///
+/// Exceptions unwind through host frames as user traps. The `try_call`
+/// libcall recovers them before they reach the host, and hands them back
+/// to the compiled code that catches them.
+#[derive(Debug, Clone)]
+pub struct Exception {
+    tag: Arc<Tag>,
+    payload: Vec<u128>,
+}
+
+impl Exception {
+    /// Create a new exception for `tag` carrying `payload`, with one raw
+    /// value per parameter of the tag type.
+    pub fn new(tag: Arc<Tag>, payload: Vec<u128>) -> Self {
+        debug_assert_eq!(tag.ty().params().len(), payload.len());
+        Self { tag, payload }
+    }
+
+    /// Get the tag of the exception.
+    pub fn tag(&self) -> &Arc<Tag> {
+        &self.tag
+    }
+
+    /// Get the raw values carried by the exception.
+    pub fn payload(&self) -> &[u128] {
+        &self.payload
+    }
+
+    /// Returns whether the exception was created from `tag`.
+    pub fn is(&self, tag: &Arc<Tag>) -> bool {
+        Arc::ptr_eq(&self.tag, tag)
+    }
+
+    /// Consume the exception, returning its raw values.
+    pub fn into_payload(self) -> Vec<u128> {
+        self.payload
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "uncaught exception with tag {}", self.tag.ty())
+    }
+}
+
+impl Error for Exception {}
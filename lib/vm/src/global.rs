@@ -97,6 +97,35 @@ impl Global {
         self.set_unchecked(val)
     }
 
+    /// Get the raw value of the global, as compiled code stores it.
+    pub fn get_raw(&self) -> u128 {
+        let _global_guard = self.lock.lock().unwrap();
+        unsafe { (*self.vm_global_definition.get()).to_u128() }
+    }
+
+    /// Set the raw value of the global, as compiled code stores it.
+    ///
+    /// # Safety
+    /// `raw` must be a valid value of the global's type, coming from the
+    /// same store as this global.
+    pub unsafe fn set_raw(&self, raw: u128) -> Result<(), GlobalError> {
+        let _global_guard = self.lock.lock().unwrap();
+        if self.ty().mutability != Mutability::Var {
+            return Err(GlobalError::ImmutableGlobalCannotBeSet);
+        }
+        self.set_raw_unchecked(raw);
+        Ok(())
+    }
+
+    /// Set the raw value of the global (unchecked)
+    ///
+    /// # Safety
+    /// Same as [`Global::set_raw`]. The caller should also ensure that this
+    /// global is synchronized. Otherwise, use `set_raw` instead.
+    pub unsafe fn set_raw_unchecked(&self, raw: u128) {
+        *(*self.vm_global_definition.get()).as_u128_mut() = raw;
+    }
+
     /// Set a value from the global (unchecked)
     ///
     /// # Safety
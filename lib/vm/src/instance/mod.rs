@@ -13,26 +13,28 @@ mod r#ref;
 pub use allocator::InstanceAllocator;
 pub use r#ref::InstanceRef;
 
+use crate::exception::Exception;
 use crate::export::VMExport;
 use crate::global::Global;
 use crate::imports::Imports;
 use crate::memory::{Memory, MemoryError};
-use crate::table::Table;
-use crate::trap::{catch_traps, init_traps, Trap, TrapCode};
+use crate::table::{Table, TableElement};
+use crate::tag::Tag;
+use crate::trap::{catch_traps, init_traps, wasmer_call_trampoline, Trap, TrapCode};
 use crate::vmcontext::{
-    VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionBody,
+    VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMExternRef, VMFunctionBody,
     VMFunctionEnvironment, VMFunctionImport, VMFunctionKind, VMGlobalDefinition, VMGlobalImport,
     VMMemoryDefinition, VMMemoryImport, VMSharedSignatureIndex, VMTableDefinition, VMTableImport,
     VMTrampoline,
 };
 use crate::{FunctionBodyPtr, ModuleInfo, VMOffsets};
-use crate::{VMExportFunction, VMExportGlobal, VMExportMemory, VMExportTable};
+use crate::{VMExportFunction, VMExportGlobal, VMExportMemory, VMExportTable, VMExportTag};
 use memoffset::offset_of;
 use more_asserts::assert_lt;
 use std::any::Any;
 use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
+use std::convert::TryFrom;
 use std::ffi;
 use std::fmt;
 use std::ptr::NonNull;
@@ -42,7 +44,7 @@ use wasmer_types::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef,
 use wasmer_types::{
     DataIndex, DataInitializer, ElemIndex, ExportIndex, FunctionIndex, GlobalIndex, GlobalInit,
     LocalFunctionIndex, LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, Pages,
-    SignatureIndex, TableIndex, TableInitializer,
+    SignatureIndex, TableIndex, TableInitializer, TagIndex, Type,
 };
 
 /// The function pointer to call with data and an [`Instance`] pointer to
@@ -73,6 +75,9 @@ pub(crate) struct Instance {
     /// WebAssembly global data.
     globals: BoxedSlice<LocalGlobalIndex, Arc<Global>>,
 
+    /// Exception tags, imported ones first.
+    tags: BoxedSlice<TagIndex, Arc<Tag>>,
+
     /// Pointers to functions in executable memory.
     functions: BoxedSlice<LocalFunctionIndex, FunctionBodyPtr>,
 
@@ -88,6 +93,12 @@ pub(crate) struct Instance {
     /// get removed. A missing entry is considered equivalent to an empty slice.
     passive_data: RefCell<HashMap<DataIndex, Arc<[u8]>>>,
 
+    /// Function references handed out to compiled code, which sees them
+    /// as pointers. They are interned so that the pointers stay valid,
+    /// and compare equal, for the lifetime of the instance.
+    func_refs:
+        RefCell<HashMap<(usize, VMSharedSignatureIndex, usize), Box<VMCallerCheckedAnyfunc>>>,
+
     /// Hosts can store arbitrary per-instance information here.
     host_state: Box<dyn Any>,
 
@@ -101,6 +112,10 @@ pub(crate) struct Instance {
     /// functions from other Wasm modules.
     imported_function_envs: BoxedSlice<FunctionIndex, ImportFunctionEnv>,
 
+    /// The resolved imports. The `vmctx` holds bitwise copies of them, so
+    /// the instance owns the originals.
+    imports: Imports,
+
     /// Additional context used by compiled WebAssembly code. This
     /// field is last, and represents a dynamically-sized array that
     /// extends beyond the nominal end of the struct (similar to a
@@ -528,11 +543,7 @@ impl Instance {
     }
 
     /// Get table element by index.
-    fn table_get(
-        &self,
-        table_index: LocalTableIndex,
-        index: u32,
-    ) -> Option<VMCallerCheckedAnyfunc> {
+    fn table_get(&self, table_index: LocalTableIndex, index: u32) -> Option<TableElement> {
         self.tables
             .get(table_index)
             .unwrap_or_else(|| panic!("no table for index {}", table_index.index()))
@@ -544,7 +555,7 @@ impl Instance {
         &self,
         table_index: LocalTableIndex,
         index: u32,
-        val: VMCallerCheckedAnyfunc,
+        val: TableElement,
     ) -> Result<(), Trap> {
         self.tables
             .get(table_index)
@@ -580,6 +591,30 @@ impl Instance {
         }
     }
 
+    /// The `ref.func` operation: returns a reference to the function
+    /// `index`, as seen by compiled code.
+    pub(crate) fn func_ref(&self, index: FunctionIndex) -> *const VMCallerCheckedAnyfunc {
+        self.intern_func_ref(self.get_caller_checked_anyfunc(index))
+    }
+
+    /// Returns a pointer to a `VMCallerCheckedAnyfunc` equal to `anyfunc`
+    /// that lives as long as the instance, or null for a null reference.
+    pub(crate) fn intern_func_ref(
+        &self,
+        anyfunc: VMCallerCheckedAnyfunc,
+    ) -> *const VMCallerCheckedAnyfunc {
+        if anyfunc.func_ptr.is_null() {
+            return ptr::null();
+        }
+        let key = (
+            anyfunc.func_ptr as usize,
+            anyfunc.type_index,
+            unsafe { anyfunc.vmctx.host_env } as usize,
+        );
+        let mut func_refs = self.func_refs.borrow_mut();
+        &**func_refs.entry(key).or_insert_with(|| Box::new(anyfunc))
+    }
+
     /// The `table.init` operation: initializes a portion of a table with a
     /// passive element.
     ///
@@ -613,7 +648,7 @@ impl Instance {
 
         for (dst, src) in (dst..dst + len).zip(src..src + len) {
             table
-                .set(dst, elem[src as usize].clone())
+                .set(dst, segment_element(table, elem[src as usize].clone()))
                 .expect("should never panic because we already did the bounds check above");
         }
 
@@ -639,9 +674,9 @@ impl Instance {
     pub(crate) fn local_memory_copy(
         &self,
         memory_index: LocalMemoryIndex,
-        dst: u32,
-        src: u32,
-        len: u32,
+        dst: u64,
+        src: u64,
+        len: u64,
     ) -> Result<(), Trap> {
         // https://webassembly.github.io/reference-types/core/exec/instructions.html#exec-memory-copy
 
@@ -654,9 +689,9 @@ impl Instance {
     pub(crate) fn imported_memory_copy(
         &self,
         memory_index: MemoryIndex,
-        dst: u32,
-        src: u32,
-        len: u32,
+        dst: u64,
+        src: u64,
+        len: u64,
     ) -> Result<(), Trap> {
         let import = self.imported_memory(memory_index);
         let memory = unsafe { import.definition.as_ref() };
@@ -664,6 +699,27 @@ impl Instance {
         unsafe { memory.memory_copy(dst, src, len) }
     }
 
+    /// Perform a `memory.copy` between two different memories, each of which
+    /// may be locally defined or imported.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error when the source or destination ranges are out of
+    /// bounds.
+    pub(crate) fn memory_copy_between(
+        &self,
+        dst_memory_index: MemoryIndex,
+        src_memory_index: MemoryIndex,
+        dst: u64,
+        src: u64,
+        len: u64,
+    ) -> Result<(), Trap> {
+        let dst_memory = self.get_memory(dst_memory_index);
+        let src_memory = self.get_memory(src_memory_index);
+        // The following memory copy is not synchronized and is not atomic:
+        unsafe { dst_memory.memory_copy_from(&src_memory, dst, src, len) }
+    }
+
     /// Perform the `memory.fill` operation on a locally defined memory.
     ///
     /// # Errors
@@ -672,9 +728,9 @@ impl Instance {
     pub(crate) fn local_memory_fill(
         &self,
         memory_index: LocalMemoryIndex,
-        dst: u32,
+        dst: u64,
         val: u32,
-        len: u32,
+        len: u64,
     ) -> Result<(), Trap> {
         let memory = self.memory(memory_index);
         // The following memory fill is not synchronized and is not atomic:
@@ -689,9 +745,9 @@ impl Instance {
     pub(crate) fn imported_memory_fill(
         &self,
         memory_index: MemoryIndex,
-        dst: u32,
+        dst: u64,
         val: u32,
-        len: u32,
+        len: u64,
     ) -> Result<(), Trap> {
         let import = self.imported_memory(memory_index);
         let memory = unsafe { import.definition.as_ref() };
@@ -699,6 +755,183 @@ impl Instance {
         unsafe { memory.memory_fill(dst, val, len) }
     }
 
+    /// Perform the `memory.atomic.wait32` operation on a locally defined memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the memory isn't shared, or if the location
+    /// is out of bounds or misaligned.
+    pub(crate) fn local_memory_atomic_wait32(
+        &self,
+        memory_index: LocalMemoryIndex,
+        dst: u64,
+        expected: u32,
+        timeout: i64,
+    ) -> Result<u32, Trap> {
+        let waiters = self.memories[memory_index]
+            .waiters()
+            .ok_or_else(|| Trap::new_from_runtime(TrapCode::AtomicWaitOnUnsharedMemory))?;
+        let memory = self.memory(memory_index);
+        unsafe { memory.atomic_wait32(waiters, dst, expected, timeout) }
+    }
+
+    /// Perform the `memory.atomic.wait32` operation on an imported memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the memory isn't shared, or if the location
+    /// is out of bounds or misaligned.
+    pub(crate) fn imported_memory_atomic_wait32(
+        &self,
+        memory_index: MemoryIndex,
+        dst: u64,
+        expected: u32,
+        timeout: i64,
+    ) -> Result<u32, Trap> {
+        let import = self.imported_memory(memory_index);
+        let waiters = import
+            .from
+            .waiters()
+            .ok_or_else(|| Trap::new_from_runtime(TrapCode::AtomicWaitOnUnsharedMemory))?;
+        let memory = unsafe { import.definition.as_ref() };
+        unsafe { memory.atomic_wait32(waiters, dst, expected, timeout) }
+    }
+
+    /// Perform the `memory.atomic.wait64` operation on a locally defined memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the memory isn't shared, or if the location
+    /// is out of bounds or misaligned.
+    pub(crate) fn local_memory_atomic_wait64(
+        &self,
+        memory_index: LocalMemoryIndex,
+        dst: u64,
+        expected: u64,
+        timeout: i64,
+    ) -> Result<u32, Trap> {
+        let waiters = self.memories[memory_index]
+            .waiters()
+            .ok_or_else(|| Trap::new_from_runtime(TrapCode::AtomicWaitOnUnsharedMemory))?;
+        let memory = self.memory(memory_index);
+        unsafe { memory.atomic_wait64(waiters, dst, expected, timeout) }
+    }
+
+    /// Perform the `memory.atomic.wait64` operation on an imported memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the memory isn't shared, or if the location
+    /// is out of bounds or misaligned.
+    pub(crate) fn imported_memory_atomic_wait64(
+        &self,
+        memory_index: MemoryIndex,
+        dst: u64,
+        expected: u64,
+        timeout: i64,
+    ) -> Result<u32, Trap> {
+        let import = self.imported_memory(memory_index);
+        let waiters = import
+            .from
+            .waiters()
+            .ok_or_else(|| Trap::new_from_runtime(TrapCode::AtomicWaitOnUnsharedMemory))?;
+        let memory = unsafe { import.definition.as_ref() };
+        unsafe { memory.atomic_wait64(waiters, dst, expected, timeout) }
+    }
+
+    /// Perform the `memory.atomic.notify` operation on a locally defined memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the location is out of bounds or misaligned.
+    pub(crate) fn local_memory_atomic_notify(
+        &self,
+        memory_index: LocalMemoryIndex,
+        dst: u64,
+        count: u32,
+    ) -> Result<u32, Trap> {
+        let waiters = self.memories[memory_index].waiters();
+        let memory = self.memory(memory_index);
+        unsafe { memory.atomic_notify(waiters, dst, count) }
+    }
+
+    /// Perform the `memory.atomic.notify` operation on an imported memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the location is out of bounds or misaligned.
+    pub(crate) fn imported_memory_atomic_notify(
+        &self,
+        memory_index: MemoryIndex,
+        dst: u64,
+        count: u32,
+    ) -> Result<u32, Trap> {
+        let import = self.imported_memory(memory_index);
+        let memory = unsafe { import.definition.as_ref() };
+        unsafe { memory.atomic_notify(import.from.waiters(), dst, count) }
+    }
+
+    /// Get an exception tag by index.
+    pub(crate) fn tag(&self, tag_index: TagIndex) -> &Arc<Tag> {
+        &self.tags[tag_index]
+    }
+
+    /// Create an exception for the tag `tag_index`, reading its payload
+    /// from `values`.
+    ///
+    /// # Safety
+    ///
+    /// `values` must point to one `u128` per parameter of the tag type.
+    pub(crate) unsafe fn exception_new(
+        &self,
+        tag_index: TagIndex,
+        values: *const u128,
+    ) -> Exception {
+        let tag = self.tag(tag_index);
+        let payload = slice::from_raw_parts(values, tag.ty().params().len()).to_vec();
+        Exception::new(tag.clone(), payload)
+    }
+
+    /// Call `callee` with the arguments in `values_vec`, through the
+    /// trampoline of the signature `sig_index`, catching any exception
+    /// it throws.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error for anything thrown that isn't an
+    /// exception.
+    ///
+    /// # Safety
+    ///
+    /// `callee` and `callee_vmctx` must be a function of the signature
+    /// `sig_index`, and `values_vec` must be large enough for both its
+    /// parameters and its results.
+    pub(crate) unsafe fn try_call(
+        &self,
+        sig_index: SignatureIndex,
+        callee: *const VMFunctionBody,
+        callee_vmctx: VMFunctionEnvironment,
+        values_vec: *mut u128,
+    ) -> Result<Option<Exception>, Trap> {
+        let trampoline = self.function_call_trampolines[sig_index];
+        match wasmer_call_trampoline(callee_vmctx, trampoline, callee, values_vec as *mut u8) {
+            Ok(()) => Ok(None),
+            Err(Trap::User(error)) => {
+                // Host functions may wrap the exception they throw in
+                // their own error type.
+                let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&*error);
+                while let Some(error) = source {
+                    if let Some(exception) = error.downcast_ref::<Exception>() {
+                        return Ok(Some(exception.clone()));
+                    }
+                    source = error.source();
+                }
+                Err(Trap::User(error))
+            }
+            Err(trap) => Err(trap),
+        }
+    }
+
     /// Performs the `memory.init` operation.
     ///
     /// # Errors
@@ -710,7 +943,7 @@ impl Instance {
         &self,
         memory_index: MemoryIndex,
         data_index: DataIndex,
-        dst: u32,
+        dst: u64,
         src: u32,
         len: u32,
     ) -> Result<(), Trap> {
@@ -726,8 +959,8 @@ impl Instance {
             .checked_add(len)
             .map_or(true, |n| n as usize > data.len())
             || dst
-                .checked_add(len)
-                .map_or(true, |m| m > memory.current_length)
+                .checked_add(u64::from(len))
+                .map_or(true, |m| m > memory.current_length as u64)
         {
             return Err(Trap::new_from_runtime(TrapCode::HeapAccessOutOfBounds));
         }
@@ -824,6 +1057,16 @@ impl InstanceHandle {
             .collect::<PrimaryMap<LocalGlobalIndex, _>>()
             .into_boxed_slice();
         let passive_data = RefCell::new(module.passive_data.clone());
+        let tags = imports
+            .tags
+            .values()
+            .cloned()
+            .chain(
+                (module.num_imported_tags..module.tags.len())
+                    .map(|index| Arc::new(Tag::new(module.tag_type(TagIndex::new(index))))),
+            )
+            .collect::<PrimaryMap<TagIndex, _>>()
+            .into_boxed_slice();
 
         let handle = {
             let offsets = allocator.offsets().clone();
@@ -834,13 +1077,16 @@ impl InstanceHandle {
                 memories: finished_memories,
                 tables: finished_tables,
                 globals: finished_globals,
+                tags,
                 functions: finished_functions,
                 function_call_trampolines: finished_function_call_trampolines,
                 passive_elements: Default::default(),
                 passive_data,
+                func_refs: Default::default(),
                 host_state,
                 signal_handler: Cell::new(None),
                 imported_function_envs,
+                imports,
                 vmctx: VMContext {},
             };
 
@@ -858,24 +1104,24 @@ impl InstanceHandle {
             vmshared_signatures.len(),
         );
         ptr::copy(
-            imports.functions.values().as_slice().as_ptr(),
+            instance.imports.functions.values().as_slice().as_ptr(),
             instance.imported_functions_ptr() as *mut VMFunctionImport,
-            imports.functions.len(),
+            instance.imports.functions.len(),
         );
         ptr::copy(
-            imports.tables.values().as_slice().as_ptr(),
+            instance.imports.tables.values().as_slice().as_ptr(),
             instance.imported_tables_ptr() as *mut VMTableImport,
-            imports.tables.len(),
+            instance.imports.tables.len(),
         );
         ptr::copy(
-            imports.memories.values().as_slice().as_ptr(),
+            instance.imports.memories.values().as_slice().as_ptr(),
             instance.imported_memories_ptr() as *mut VMMemoryImport,
-            imports.memories.len(),
+            instance.imports.memories.len(),
         );
         ptr::copy(
-            imports.globals.values().as_slice().as_ptr(),
+            instance.imports.globals.values().as_slice().as_ptr(),
             instance.imported_globals_ptr() as *mut VMGlobalImport,
-            imports.globals.len(),
+            instance.imports.globals.len(),
         );
         // these should already be set, add asserts here? for:
         // - instance.tables_ptr() as *mut VMTableDefinition
@@ -908,16 +1154,24 @@ impl InstanceHandle {
 
     /// Finishes the instantiation process started by `Instance::new`.
     ///
+    /// With `bulk_memory`, the segments are applied in order and the
+    /// ones applied before an out-of-bounds segment persist, as the
+    /// bulk memory operations proposal specifies. Otherwise, nothing is
+    /// written unless every segment fits.
+    ///
     /// # Safety
     ///
     /// Only safe to call immediately after instantiation.
     pub unsafe fn finish_instantiation(
         &self,
         data_initializers: &[DataInitializer<'_>],
+        bulk_memory: bool,
     ) -> Result<(), Trap> {
         let instance = self.instance().as_ref();
-        check_table_init_bounds(instance)?;
-        check_memory_init_bounds(instance, data_initializers)?;
+        if !bulk_memory {
+            check_table_init_bounds(instance)?;
+            check_memory_init_bounds(instance, data_initializers)?;
+        }
 
         // Apply the initializers.
         initialize_tables(instance)?;
@@ -1044,6 +1298,11 @@ impl InstanceHandle {
                 }
                 .into()
             }
+            ExportIndex::Tag(index) => VMExportTag {
+                from: instance_ref.tags[*index].clone(),
+                instance_ref: Some(instance),
+            }
+            .into(),
         }
     }
 
@@ -1097,11 +1356,7 @@ impl InstanceHandle {
     /// Get table element reference.
     ///
     /// Returns `None` if index is out of bounds.
-    pub fn table_get(
-        &self,
-        table_index: LocalTableIndex,
-        index: u32,
-    ) -> Option<VMCallerCheckedAnyfunc> {
+    pub fn table_get(&self, table_index: LocalTableIndex, index: u32) -> Option<TableElement> {
         self.instance().as_ref().table_get(table_index, index)
     }
 
@@ -1112,7 +1367,7 @@ impl InstanceHandle {
         &self,
         table_index: LocalTableIndex,
         index: u32,
-        val: VMCallerCheckedAnyfunc,
+        val: TableElement,
     ) -> Result<(), Trap> {
         self.instance().as_ref().table_set(table_index, index, val)
     }
@@ -1207,14 +1462,23 @@ fn get_memory_init_start(init: &DataInitializer<'_>, instance: &Instance) -> usi
     let mut start = init.location.offset;
 
     if let Some(base) = init.location.base {
-        let val = unsafe {
+        let global = unsafe {
             if let Some(def_index) = instance.module.local_global_index(base) {
-                instance.global(def_index).to_u32()
+                instance.global(def_index)
             } else {
-                instance.imported_global(base).definition.as_ref().to_u32()
+                instance.imported_global(base).definition.as_ref().clone()
             }
         };
-        start += usize::try_from(val).unwrap();
+        // 64-bit memories are initialized at the offset of an `i64` global.
+        let val = if instance.module.memories[init.location.memory_index].memory64 {
+            global.to_u64()
+        } else {
+            u64::from(global.to_u32())
+        };
+        start = usize::try_from(val)
+            .ok()
+            .and_then(|val| start.checked_add(val))
+            .unwrap_or(usize::max_value());
     }
 
     start
@@ -1235,7 +1499,7 @@ unsafe fn get_memory_slice<'instance>(
         let import = instance.imported_memory(init.location.memory_index);
         *import.definition.as_ref()
     };
-    slice::from_raw_parts_mut(memory.base, memory.current_length.try_into().unwrap())
+    slice::from_raw_parts_mut(memory.base, memory.current_length)
 }
 
 fn check_memory_init_bounds(
@@ -1284,13 +1548,16 @@ fn initialize_tables(instance: &Instance) -> Result<(), Trap> {
             .checked_add(init.elements.len())
             .map_or(true, |end| end > table.size() as usize)
         {
-            return Err(Trap::new_from_runtime(TrapCode::TableAccessOutOfBounds));
+            return Err(Trap::new_from_runtime(TrapCode::TableSetterOutOfBounds));
         }
 
         for (i, func_idx) in init.elements.iter().enumerate() {
             let anyfunc = instance.get_caller_checked_anyfunc(*func_idx);
             table
-                .set(u32::try_from(start + i).unwrap(), anyfunc)
+                .set(
+                    u32::try_from(start + i).unwrap(),
+                    segment_element(table, anyfunc),
+                )
                 .unwrap();
         }
     }
@@ -1298,6 +1565,18 @@ fn initialize_tables(instance: &Instance) -> Result<(), Trap> {
     Ok(())
 }
 
+/// Converts an item of an element segment to an element of `table`.
+/// Segments of `externref` tables only hold null references.
+fn segment_element(table: &dyn Table, anyfunc: VMCallerCheckedAnyfunc) -> TableElement {
+    match table.ty().ty {
+        Type::ExternRef => {
+            debug_assert!(anyfunc.func_ptr.is_null());
+            TableElement::ExternRef(VMExternRef::null())
+        }
+        _ => TableElement::FuncRef(anyfunc),
+    }
+}
+
 /// Initialize the `Instance::passive_elements` map by resolving the
 /// `ModuleInfo::passive_elements`'s `FunctionIndex`s into `VMCallerCheckedAnyfunc`s for
 /// this instance.
@@ -1337,9 +1616,9 @@ fn initialize_memories(
         let start = get_memory_init_start(init, instance);
         if start
             .checked_add(init.data.len())
-            .map_or(true, |end| end > memory.current_length.try_into().unwrap())
+            .map_or(true, |end| end > memory.current_length)
         {
-            return Err(Trap::new_from_runtime(TrapCode::HeapAccessOutOfBounds));
+            return Err(Trap::new_from_runtime(TrapCode::HeapSetterOutOfBounds));
         }
 
         unsafe {
@@ -1373,7 +1652,10 @@ fn initialize_globals(instance: &Instance) {
                         };
                     *to = from;
                 }
-                GlobalInit::RefNullConst | GlobalInit::RefFunc(_) => unimplemented!(),
+                GlobalInit::RefNullConst => *(*to).as_u64_mut() = 0,
+                GlobalInit::RefFunc(func_index) => {
+                    *(*to).as_u64_mut() = instance.func_ref(*func_index) as u64
+                }
             }
         }
     }
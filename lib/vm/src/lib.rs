@@ -20,6 +20,7 @@
     )
 )]
 
+mod exception;
 mod export;
 mod global;
 mod imports;
@@ -30,12 +31,15 @@ mod module;
 mod probestack;
 mod sig_registry;
 mod table;
+mod tag;
 mod trap;
 mod vmcontext;
 mod vmoffsets;
+mod waiter;
 
 pub mod libcalls;
 
+pub use crate::exception::Exception;
 pub use crate::export::*;
 pub use crate::global::*;
 pub use crate::imports::Imports;
@@ -44,18 +48,23 @@ pub use crate::instance::{
 };
 pub use crate::memory::{LinearMemory, Memory, MemoryError, MemoryStyle};
 pub use crate::mmap::Mmap;
-pub use crate::module::{ExportsIterator, ImportsIterator, ModuleInfo};
+pub use crate::module::{
+    ExportsIterator, ImportsIterator, InstanceInitializer, InstantiationArg, ModuleInfo,
+    Submodule,
+};
 pub use crate::probestack::PROBESTACK;
 pub use crate::sig_registry::SignatureRegistry;
-pub use crate::table::{LinearTable, Table, TableStyle};
+pub use crate::table::{LinearTable, RawTableElement, Table, TableElement, TableStyle};
+pub use crate::tag::Tag;
 pub use crate::trap::*;
 pub use crate::vmcontext::{
     VMBuiltinFunctionIndex, VMCallerCheckedAnyfunc, VMContext, VMDynamicFunctionContext,
-    VMFunctionBody, VMFunctionEnvironment, VMFunctionImport, VMFunctionKind, VMGlobalDefinition,
-    VMGlobalImport, VMMemoryDefinition, VMMemoryImport, VMSharedSignatureIndex, VMTableDefinition,
-    VMTableImport, VMTrampoline,
+    VMExternRef, VMFunctionBody, VMFunctionEnvironment, VMFunctionImport, VMFunctionKind,
+    VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition, VMMemoryImport, VMSharedSignatureIndex,
+    VMTableDefinition, VMTableImport, VMTrampoline,
 };
 pub use crate::vmoffsets::{TargetSharedSignatureIndex, VMOffsets};
+pub use crate::waiter::Waiters;
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
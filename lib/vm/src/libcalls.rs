@@ -35,12 +35,21 @@
 //!   }
 //!   ```
 
+use crate::exception::Exception;
 use crate::probestack::PROBESTACK;
-use crate::trap::{raise_lib_trap, Trap, TrapCode};
-use crate::vmcontext::VMContext;
+use crate::table::{RawTableElement, Table, TableElement};
+use crate::trap::{raise_lib_trap, raise_user_trap, resume_panic, Trap, TrapCode};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionEnvironment};
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::fmt;
-use wasmer_types::{DataIndex, ElemIndex, LocalMemoryIndex, MemoryIndex, TableIndex};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering};
+use wasmer_types::{
+    DataIndex, ElemIndex, FunctionIndex, LocalMemoryIndex, MemoryIndex, SignatureIndex, TableIndex,
+    TagIndex, Type,
+};
 
 /// Implementation of f32.ceil
 #[no_mangle]
@@ -197,6 +206,73 @@ pub unsafe extern "C" fn wasmer_imported_memory32_size(
     instance.imported_memory_size(memory_index).0
 }
 
+/// Implementation of memory.grow for locally-defined 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory64_grow(
+    vmctx: *mut VMContext,
+    delta: u64,
+    memory_index: u32,
+) -> u64 {
+    let instance = (&*vmctx).instance();
+    let memory_index = LocalMemoryIndex::from_u32(memory_index);
+
+    u32::try_from(delta)
+        .ok()
+        .and_then(|delta| instance.memory_grow(memory_index, delta).ok())
+        .map(|pages| u64::from(pages.0))
+        .unwrap_or(u64::max_value())
+}
+
+/// Implementation of memory.grow for imported 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_imported_memory64_grow(
+    vmctx: *mut VMContext,
+    delta: u64,
+    memory_index: u32,
+) -> u64 {
+    let instance = (&*vmctx).instance();
+    let memory_index = MemoryIndex::from_u32(memory_index);
+
+    u32::try_from(delta)
+        .ok()
+        .and_then(|delta| instance.imported_memory_grow(memory_index, delta).ok())
+        .map(|pages| u64::from(pages.0))
+        .unwrap_or(u64::max_value())
+}
+
+/// Implementation of memory.size for locally-defined 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory64_size(vmctx: *mut VMContext, memory_index: u32) -> u64 {
+    let instance = (&*vmctx).instance();
+    let memory_index = LocalMemoryIndex::from_u32(memory_index);
+
+    u64::from(instance.memory_size(memory_index).0)
+}
+
+/// Implementation of memory.size for imported 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_imported_memory64_size(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+) -> u64 {
+    let instance = (&*vmctx).instance();
+    let memory_index = MemoryIndex::from_u32(memory_index);
+
+    u64::from(instance.imported_memory_size(memory_index).0)
+}
+
 /// Implementation of `table.copy`.
 ///
 /// # Safety
@@ -258,6 +334,134 @@ pub unsafe extern "C" fn wasmer_elem_drop(vmctx: *mut VMContext, elem_index: u32
     instance.elem_drop(elem_index);
 }
 
+/// Implementation of `ref.func`.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_func_ref(
+    vmctx: *mut VMContext,
+    function_index: u32,
+) -> *const VMCallerCheckedAnyfunc {
+    let function_index = FunctionIndex::from_u32(function_index);
+    let instance = (&*vmctx).instance();
+    instance.func_ref(function_index)
+}
+
+/// Implementation of `table.get`.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_table_get(
+    vmctx: *mut VMContext,
+    table_index: u32,
+    index: u32,
+) -> RawTableElement {
+    let result = {
+        let table_index = TableIndex::from_u32(table_index);
+        let instance = (&*vmctx).instance();
+        instance
+            .get_table(table_index)
+            .get(index)
+            .map(|element| match element {
+                TableElement::FuncRef(anyfunc) => RawTableElement {
+                    func_ref: instance.intern_func_ref(anyfunc),
+                },
+                TableElement::ExternRef(extern_ref) => RawTableElement { extern_ref },
+            })
+            .ok_or_else(|| Trap::new_from_runtime(TrapCode::TableAccessOutOfBounds))
+    };
+    match result {
+        Ok(element) => element,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// Implementation of `table.set`.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null, and `value` must be a valid
+/// element of the table.
+pub unsafe extern "C" fn wasmer_table_set(
+    vmctx: *mut VMContext,
+    table_index: u32,
+    index: u32,
+    value: RawTableElement,
+) {
+    let result = {
+        let table_index = TableIndex::from_u32(table_index);
+        let instance = (&*vmctx).instance();
+        let table = instance.get_table(table_index);
+        table.set(index, table_element(table, value))
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `table.grow`.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null, and `init_value` must be a valid
+/// element of the table.
+pub unsafe extern "C" fn wasmer_table_grow(
+    vmctx: *mut VMContext,
+    table_index: u32,
+    init_value: RawTableElement,
+    delta: u32,
+) -> u32 {
+    let table_index = TableIndex::from_u32(table_index);
+    let instance = (&*vmctx).instance();
+    let table = instance.get_table(table_index);
+    match table.grow(delta) {
+        Some(previous_size) => {
+            table
+                .fill(previous_size, table_element(table, init_value), delta)
+                .expect("the grown elements are in bounds");
+            previous_size
+        }
+        None => u32::max_value(),
+    }
+}
+
+/// Implementation of `table.fill`.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null, and `value` must be a valid
+/// element of the table.
+pub unsafe extern "C" fn wasmer_table_fill(
+    vmctx: *mut VMContext,
+    table_index: u32,
+    dst: u32,
+    value: RawTableElement,
+    len: u32,
+) {
+    let result = {
+        let table_index = TableIndex::from_u32(table_index);
+        let instance = (&*vmctx).instance();
+        let table = instance.get_table(table_index);
+        table.fill(dst, table_element(table, value), len)
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Reads the element of `table` that `value` is the raw value of. A
+/// `funcref` points to the function reference, a null pointer being the
+/// null reference.
+unsafe fn table_element(table: &dyn Table, value: RawTableElement) -> TableElement {
+    match table.ty().ty {
+        Type::ExternRef => TableElement::ExternRef(value.extern_ref),
+        _ if value.func_ref.is_null() => TableElement::FuncRef(VMCallerCheckedAnyfunc::default()),
+        _ => TableElement::FuncRef((*value.func_ref).clone()),
+    }
+}
+
 /// Implementation of `memory.copy` for locally defined memories.
 ///
 /// # Safety
@@ -273,7 +477,7 @@ pub unsafe extern "C" fn wasmer_local_memory_copy(
     let result = {
         let memory_index = LocalMemoryIndex::from_u32(memory_index);
         let instance = (&*vmctx).instance();
-        instance.local_memory_copy(memory_index, dst, src, len)
+        instance.local_memory_copy(memory_index, dst.into(), src.into(), len.into())
     };
     if let Err(trap) = result {
         raise_lib_trap(trap);
@@ -295,7 +499,37 @@ pub unsafe extern "C" fn wasmer_imported_memory_copy(
     let result = {
         let memory_index = MemoryIndex::from_u32(memory_index);
         let instance = (&*vmctx).instance();
-        instance.imported_memory_copy(memory_index, dst, src, len)
+        instance.imported_memory_copy(memory_index, dst.into(), src.into(), len.into())
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `memory.copy` between two different memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory_copy_between(
+    vmctx: *mut VMContext,
+    dst_memory_index: u32,
+    src_memory_index: u32,
+    dst: u32,
+    src: u32,
+    len: u32,
+) {
+    let result = {
+        let dst_memory_index = MemoryIndex::from_u32(dst_memory_index);
+        let src_memory_index = MemoryIndex::from_u32(src_memory_index);
+        let instance = (&*vmctx).instance();
+        instance.memory_copy_between(
+            dst_memory_index,
+            src_memory_index,
+            dst.into(),
+            src.into(),
+            len.into(),
+        )
     };
     if let Err(trap) = result {
         raise_lib_trap(trap);
@@ -317,7 +551,7 @@ pub unsafe extern "C" fn wasmer_memory_fill(
     let result = {
         let memory_index = LocalMemoryIndex::from_u32(memory_index);
         let instance = (&*vmctx).instance();
-        instance.local_memory_fill(memory_index, dst, val, len)
+        instance.local_memory_fill(memory_index, dst.into(), val, len.into())
     };
     if let Err(trap) = result {
         raise_lib_trap(trap);
@@ -339,7 +573,7 @@ pub unsafe extern "C" fn wasmer_imported_memory_fill(
     let result = {
         let memory_index = MemoryIndex::from_u32(memory_index);
         let instance = (&*vmctx).instance();
-        instance.imported_memory_fill(memory_index, dst, val, len)
+        instance.imported_memory_fill(memory_index, dst.into(), val, len.into())
     };
     if let Err(trap) = result {
         raise_lib_trap(trap);
@@ -358,6 +592,143 @@ pub unsafe extern "C" fn wasmer_memory_init(
     dst: u32,
     src: u32,
     len: u32,
+) {
+    let result = {
+        let memory_index = MemoryIndex::from_u32(memory_index);
+        let data_index = DataIndex::from_u32(data_index);
+        let instance = (&*vmctx).instance();
+        instance.memory_init(memory_index, data_index, dst.into(), src, len)
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `memory.copy` for locally defined 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_local_memory64_copy(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    src: u64,
+    len: u64,
+) {
+    let result = {
+        let memory_index = LocalMemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.local_memory_copy(memory_index, dst, src, len)
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `memory.copy` for imported 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_imported_memory64_copy(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    src: u64,
+    len: u64,
+) {
+    let result = {
+        let memory_index = MemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.imported_memory_copy(memory_index, dst, src, len)
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `memory.copy` between two different memories, one
+/// of which at least is a 64-bit memory. 32-bit operands are zero-extended.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory64_copy_between(
+    vmctx: *mut VMContext,
+    dst_memory_index: u32,
+    src_memory_index: u32,
+    dst: u64,
+    src: u64,
+    len: u64,
+) {
+    let result = {
+        let dst_memory_index = MemoryIndex::from_u32(dst_memory_index);
+        let src_memory_index = MemoryIndex::from_u32(src_memory_index);
+        let instance = (&*vmctx).instance();
+        instance.memory_copy_between(dst_memory_index, src_memory_index, dst, src, len)
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `memory.fill` for locally defined 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory64_fill(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    val: u32,
+    len: u64,
+) {
+    let result = {
+        let memory_index = LocalMemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.local_memory_fill(memory_index, dst, val, len)
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `memory.fill` for imported 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_imported_memory64_fill(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    val: u32,
+    len: u64,
+) {
+    let result = {
+        let memory_index = MemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.imported_memory_fill(memory_index, dst, val, len)
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `memory.init` for 64-bit memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory64_init(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    data_index: u32,
+    dst: u64,
+    src: u32,
+    len: u32,
 ) {
     let result = {
         let memory_index = MemoryIndex::from_u32(memory_index);
@@ -381,6 +752,364 @@ pub unsafe extern "C" fn wasmer_data_drop(vmctx: *mut VMContext, data_index: u32
     instance.data_drop(data_index)
 }
 
+/// Implementation of `memory.atomic.wait32` for locally defined memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory_atomic_wait32(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    expected: u32,
+    timeout: i64,
+) -> u32 {
+    let result = {
+        let memory_index = LocalMemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.local_memory_atomic_wait32(memory_index, dst, expected, timeout)
+    };
+    match result {
+        Ok(result) => result,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// Implementation of `memory.atomic.wait32` for imported memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_imported_memory_atomic_wait32(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    expected: u32,
+    timeout: i64,
+) -> u32 {
+    let result = {
+        let memory_index = MemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.imported_memory_atomic_wait32(memory_index, dst, expected, timeout)
+    };
+    match result {
+        Ok(result) => result,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// Implementation of `memory.atomic.wait64` for locally defined memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory_atomic_wait64(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    expected: u64,
+    timeout: i64,
+) -> u32 {
+    let result = {
+        let memory_index = LocalMemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.local_memory_atomic_wait64(memory_index, dst, expected, timeout)
+    };
+    match result {
+        Ok(result) => result,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// Implementation of `memory.atomic.wait64` for imported memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_imported_memory_atomic_wait64(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    expected: u64,
+    timeout: i64,
+) -> u32 {
+    let result = {
+        let memory_index = MemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.imported_memory_atomic_wait64(memory_index, dst, expected, timeout)
+    };
+    match result {
+        Ok(result) => result,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// Implementation of `memory.atomic.notify` for locally defined memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_memory_atomic_notify(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    count: u32,
+) -> u32 {
+    let result = {
+        let memory_index = LocalMemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.local_memory_atomic_notify(memory_index, dst, count)
+    };
+    match result {
+        Ok(result) => result,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// Implementation of `memory.atomic.notify` for imported memories.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null.
+pub unsafe extern "C" fn wasmer_imported_memory_atomic_notify(
+    vmctx: *mut VMContext,
+    memory_index: u32,
+    dst: u64,
+    count: u32,
+) -> u32 {
+    let result = {
+        let memory_index = MemoryIndex::from_u32(memory_index);
+        let instance = (&*vmctx).instance();
+        instance.imported_memory_atomic_notify(memory_index, dst, count)
+    };
+    match result {
+        Ok(result) => result,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// The operation of a `wasmer_atomic_rmw` call.
+#[repr(u32)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AtomicRmwOp {
+    /// `atomic.rmw.add`
+    Add,
+    /// `atomic.rmw.sub`
+    Sub,
+    /// `atomic.rmw.and`
+    And,
+    /// `atomic.rmw.or`
+    Or,
+    /// `atomic.rmw.xor`
+    Xor,
+    /// `atomic.rmw.xchg`
+    Xchg,
+}
+
+/// Evaluates `$body` with `$atomic` bound to the atomic integer of `$size`
+/// bytes at `$addr`.
+macro_rules! with_atomic {
+    ($size:expr, $addr:expr, |$atomic:ident| $body:expr) => {
+        match $size {
+            1 => {
+                let $atomic = &*($addr as *const AtomicU8);
+                $body
+            }
+            2 => {
+                let $atomic = &*($addr as *const AtomicU16);
+                $body
+            }
+            4 => {
+                let $atomic = &*($addr as *const AtomicU32);
+                $body
+            }
+            // The values are `u64` already.
+            #[allow(trivial_numeric_casts, clippy::unnecessary_cast)]
+            8 => {
+                let $atomic = &*($addr as *const AtomicU64);
+                $body
+            }
+            size => panic!("invalid size of atomic access: {}", size),
+        }
+    };
+}
+
+/// Implementation of the atomic loads, for the targets which can't lower
+/// them. The result is zero-extended.
+///
+/// # Safety
+///
+/// `addr` must be valid and aligned for an access of `size` bytes, which
+/// is 1, 2, 4 or 8.
+pub unsafe extern "C" fn wasmer_atomic_load(addr: *mut u8, size: u32) -> u64 {
+    with_atomic!(size, addr, |atomic| atomic.load(Ordering::SeqCst) as u64)
+}
+
+/// Implementation of the atomic stores, for the targets which can't lower
+/// them. `value` is truncated to `size` bytes.
+///
+/// # Safety
+///
+/// See [`wasmer_atomic_load`].
+pub unsafe extern "C" fn wasmer_atomic_store(addr: *mut u8, size: u32, value: u64) {
+    with_atomic!(size, addr, |atomic| {
+        atomic.store(value as _, Ordering::SeqCst)
+    })
+}
+
+/// Implementation of the atomic read-modify-write operations, for the
+/// targets which can't lower them. Returns the previous value,
+/// zero-extended.
+///
+/// # Safety
+///
+/// See [`wasmer_atomic_load`].
+pub unsafe extern "C" fn wasmer_atomic_rmw(
+    addr: *mut u8,
+    size: u32,
+    op: AtomicRmwOp,
+    value: u64,
+) -> u64 {
+    with_atomic!(size, addr, |atomic| match op {
+        AtomicRmwOp::Add => atomic.fetch_add(value as _, Ordering::SeqCst),
+        AtomicRmwOp::Sub => atomic.fetch_sub(value as _, Ordering::SeqCst),
+        AtomicRmwOp::And => atomic.fetch_and(value as _, Ordering::SeqCst),
+        AtomicRmwOp::Or => atomic.fetch_or(value as _, Ordering::SeqCst),
+        AtomicRmwOp::Xor => atomic.fetch_xor(value as _, Ordering::SeqCst),
+        AtomicRmwOp::Xchg => atomic.swap(value as _, Ordering::SeqCst),
+    } as u64)
+}
+
+/// Implementation of the atomic compare-and-exchange operations, for the
+/// targets which can't lower them. Returns the previous value,
+/// zero-extended.
+///
+/// # Safety
+///
+/// See [`wasmer_atomic_load`].
+pub unsafe extern "C" fn wasmer_atomic_cmpxchg(
+    addr: *mut u8,
+    size: u32,
+    expected: u64,
+    replacement: u64,
+) -> u64 {
+    with_atomic!(size, addr, |atomic| match atomic.compare_exchange(
+        expected as _,
+        replacement as _,
+        Ordering::SeqCst,
+        Ordering::SeqCst,
+    ) {
+        Ok(previous) | Err(previous) => previous as u64,
+    })
+}
+
+/// Implementation of a call made from inside a `try` block.
+///
+/// Returns the exception thrown by the callee, or null if it returned
+/// normally, in which case its results are in `values_vec`.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null. `callee` and `callee_vmctx` must be
+/// a function of the signature `sig_index`, and `values_vec` must be large
+/// enough for both its parameters and its results.
+pub unsafe extern "C" fn wasmer_try_call(
+    vmctx: *mut VMContext,
+    sig_index: u32,
+    callee: *const VMFunctionBody,
+    callee_vmctx: *mut VMContext,
+    values_vec: *mut u128,
+) -> *mut Exception {
+    let result = {
+        let sig_index = SignatureIndex::from_u32(sig_index);
+        let callee_vmctx = VMFunctionEnvironment {
+            vmctx: callee_vmctx,
+        };
+        let instance = (&*vmctx).instance();
+        panic::catch_unwind(AssertUnwindSafe(|| {
+            instance.try_call(sig_index, callee, callee_vmctx, values_vec)
+        }))
+    };
+    match result {
+        Ok(Ok(Some(exception))) => Box::into_raw(Box::new(exception)),
+        Ok(Ok(None)) => ptr::null_mut(),
+        Ok(Err(trap)) => raise_lib_trap(trap),
+        Err(panic) => resume_panic(panic),
+    }
+}
+
+/// Implementation of `throw`: creates an exception for the tag
+/// `tag_index`, carrying the values in `values`.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null, and `values` must point to one
+/// value per parameter of the tag type.
+pub unsafe extern "C" fn wasmer_exception_new(
+    vmctx: *mut VMContext,
+    tag_index: u32,
+    values: *const u128,
+) -> *mut Exception {
+    let tag_index = TagIndex::from_u32(tag_index);
+    let instance = (&*vmctx).instance();
+    Box::into_raw(Box::new(instance.exception_new(tag_index, values)))
+}
+
+/// Returns 1 if `exception` was created from the tag `tag_index`, and 0
+/// otherwise.
+///
+/// # Safety
+///
+/// `vmctx` must be valid and not null, and `exception` must have been
+/// returned by `wasmer_exception_new` or `wasmer_try_call`.
+pub unsafe extern "C" fn wasmer_exception_matches(
+    vmctx: *mut VMContext,
+    exception: *const Exception,
+    tag_index: u32,
+) -> u32 {
+    let tag_index = TagIndex::from_u32(tag_index);
+    let instance = (&*vmctx).instance();
+    (&*exception).is(instance.tag(tag_index)) as u32
+}
+
+/// Writes the values carried by `exception` to `values`, then frees it.
+///
+/// # Safety
+///
+/// `exception` must have been returned by `wasmer_exception_new` or
+/// `wasmer_try_call`, and must not be used afterwards. `values` must have
+/// room for one value per parameter of its tag type.
+pub unsafe extern "C" fn wasmer_exception_take_payload(
+    exception: *mut Exception,
+    values: *mut u128,
+) {
+    let payload = Box::from_raw(exception).into_payload();
+    ptr::copy_nonoverlapping(payload.as_ptr(), values, payload.len());
+}
+
+/// Frees `exception` without throwing it, when the clause holding it is
+/// left by a branch or ends without throwing it again.
+///
+/// # Safety
+///
+/// `exception` must have been returned by `wasmer_exception_new` or
+/// `wasmer_try_call`, and must not be used afterwards.
+pub unsafe extern "C" fn wasmer_exception_delete(exception: *mut Exception) {
+    drop(Box::from_raw(exception))
+}
+
+/// Throws `exception` out of the current function, to be caught by a
+/// `try_call` further up the stack, or by the host.
+///
+/// # Safety
+///
+/// Only safe to call when wasm code is on the stack. `exception` must have
+/// been returned by `wasmer_exception_new` or `wasmer_try_call`, and must
+/// not be used afterwards.
+pub unsafe extern "C" fn wasmer_exception_throw(exception: *mut Exception) -> ! {
+    raise_user_trap(Box::from_raw(exception))
+}
+
 /// Implementation for raising a trap
 ///
 /// # Safety
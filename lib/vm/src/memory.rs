@@ -7,6 +7,7 @@
 
 use crate::mmap::Mmap;
 use crate::vmcontext::VMMemoryDefinition;
+use crate::waiter::Waiters;
 use more_asserts::assert_ge;
 use serde::{Deserialize, Serialize};
 use std::borrow::BorrowMut;
@@ -113,6 +114,15 @@ pub trait Memory: fmt::Debug + Send + Sync {
     ///
     /// The pointer returned in [`VMMemoryDefinition`] must be valid for the lifetime of this memory.
     fn vmmemory(&self) -> NonNull<VMMemoryDefinition>;
+
+    /// Returns the threads blocked in `memory.atomic.wait` on this memory, or
+    /// `None` if the memory isn't shared.
+    ///
+    /// Memories that can be shared must override it, `memory.atomic.wait`
+    /// traps on memories without waiters.
+    fn waiters(&self) -> Option<&Waiters> {
+        None
+    }
 }
 
 /// A linear memory instance.
@@ -140,6 +150,9 @@ pub struct LinearMemory {
     // Records whether we're using a bounds-checking strategy which requires
     // handlers to catch trapping accesses.
     pub(crate) needs_signal_handlers: bool,
+
+    /// The threads waiting on this memory, if it is shared.
+    waiters: Option<Waiters>,
 }
 
 /// A type to help manage who is responsible for the backing memory of them
@@ -205,18 +218,19 @@ impl LinearMemory {
         style: &MemoryStyle,
         vm_memory_location: Option<NonNull<VMMemoryDefinition>>,
     ) -> Result<Self, MemoryError> {
-        if memory.minimum > Pages::max_value() {
+        if memory.minimum > memory.max_pages() {
             return Err(MemoryError::MinimumMemoryTooLarge {
                 min_requested: memory.minimum,
-                max_allowed: Pages::max_value(),
+                max_allowed: memory.max_pages(),
             });
         }
-        // `maximum` cannot be set to more than `65536` pages.
+        // `maximum` cannot be set to more than `65536` pages, or more than
+        // `WASM64_MAX_PAGES` for 64-bit memories.
         if let Some(max) = memory.maximum {
-            if max > Pages::max_value() {
+            if max > memory.max_pages() {
                 return Err(MemoryError::MaximumMemoryTooLarge {
                     max_requested: max,
-                    max_allowed: Pages::max_value(),
+                    max_allowed: memory.max_pages(),
                 });
             }
             if max < memory.minimum {
@@ -229,6 +243,12 @@ impl LinearMemory {
             }
         }
 
+        if memory.shared && memory.maximum.is_none() {
+            return Err(MemoryError::InvalidMemory {
+                reason: "shared memories must have a maximum size".to_string(),
+            });
+        }
+
         let offset_guard_bytes = style.offset_guard_size() as usize;
 
         // If we have an offset guard, or if we're doing the static memory
@@ -247,6 +267,12 @@ impl LinearMemory {
                 *bound
             }
         };
+        // Other threads may access a shared memory while it grows, so it
+        // must never move: reserve its maximum size up front.
+        let minimum_pages = match memory.maximum {
+            Some(maximum) if memory.shared => minimum_pages.max(maximum),
+            _ => minimum_pages,
+        };
         let minimum_bytes = minimum_pages.bytes().0;
         let request_bytes = minimum_bytes.checked_add(offset_guard_bytes).unwrap();
         let mapped_pages = memory.minimum;
@@ -259,7 +285,7 @@ impl LinearMemory {
         };
 
         let base_ptr = mmap.alloc.as_mut_ptr();
-        let mem_length = memory.minimum.bytes().0.try_into().unwrap();
+        let mem_length = memory.minimum.bytes().0;
         Ok(Self {
             mmap: Mutex::new(mmap),
             maximum: memory.maximum,
@@ -283,6 +309,11 @@ impl LinearMemory {
             },
             memory: *memory,
             style: style.clone(),
+            waiters: if memory.shared {
+                Some(Waiters::default())
+            } else {
+                None
+            },
         })
     }
 
@@ -334,13 +365,15 @@ impl Memory for LinearMemory {
             return Ok(mmap.size);
         }
 
-        let new_pages = mmap
-            .size
-            .checked_add(delta)
-            .ok_or(MemoryError::CouldNotGrow {
-                current: mmap.size,
-                attempted_delta: delta,
-            })?;
+        let new_pages =
+            mmap.size
+                .0
+                .checked_add(delta.0)
+                .map(Pages)
+                .ok_or(MemoryError::CouldNotGrow {
+                    current: mmap.size,
+                    attempted_delta: delta,
+                })?;
         let prev_pages = mmap.size;
 
         if let Some(maximum) = self.maximum {
@@ -355,7 +388,7 @@ impl Memory for LinearMemory {
         // Wasm linear memories are never allowed to grow beyond what is
         // indexable. If the memory has no maximum, enforce the greatest
         // limit here.
-        if new_pages >= Pages::max_value() {
+        if new_pages >= self.memory.max_pages() {
             // Linear memory size would exceed the index range.
             return Err(MemoryError::CouldNotGrow {
                 current: mmap.size,
@@ -368,6 +401,7 @@ impl Memory for LinearMemory {
         let new_bytes = new_pages.bytes().0;
 
         if new_bytes > mmap.alloc.len() - self.offset_guard_size {
+            debug_assert!(!self.memory.shared, "shared memories can't move");
             // If the new size is within the declared maximum, but needs more memory than we
             // have on hand, it's a dynamic heap and it can move.
             let guard_bytes = self.offset_guard_size;
@@ -399,7 +433,7 @@ impl Memory for LinearMemory {
         unsafe {
             let mut md_ptr = self.get_vm_memory_definition();
             let md = md_ptr.as_mut();
-            md.current_length = new_pages.bytes().0.try_into().unwrap();
+            md.current_length = new_pages.bytes().0;
             md.base = mmap.alloc.as_mut_ptr() as _;
         }
 
@@ -411,4 +445,9 @@ impl Memory for LinearMemory {
         let _mmap_guard = self.mmap.lock().unwrap();
         unsafe { self.get_vm_memory_definition() }
     }
+
+    /// Returns the threads waiting on this memory, if it is shared.
+    fn waiters(&self) -> Option<&Waiters> {
+        self.waiters.as_ref()
+    }
 }
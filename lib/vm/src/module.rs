@@ -15,8 +15,8 @@ use wasmer_types::entity::{EntityRef, PrimaryMap};
 use wasmer_types::{
     CustomSectionIndex, DataIndex, ElemIndex, ExportIndex, ExportType, ExternType, FunctionIndex,
     FunctionType, GlobalIndex, GlobalInit, GlobalType, ImportIndex, ImportType, LocalFunctionIndex,
-    LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, MemoryIndex, MemoryType, SignatureIndex,
-    TableIndex, TableInitializer, TableType,
+    LocalGlobalIndex, LocalMemoryIndex, LocalTableIndex, LocalTagIndex, MemoryIndex, MemoryType,
+    SignatureIndex, TableIndex, TableInitializer, TableType, TagIndex, TagType,
 };
 
 #[derive(Debug, Clone)]
@@ -96,12 +96,20 @@ pub struct ModuleInfo {
     /// WebAssembly global variables (imported and local).
     pub globals: PrimaryMap<GlobalIndex, GlobalType>,
 
+    /// WebAssembly exception tags (imported and local), with the signature
+    /// whose parameters are the types of the values their exceptions carry.
+    pub tags: PrimaryMap<TagIndex, SignatureIndex>,
+
     /// Custom sections in the module.
     pub custom_sections: IndexMap<String, CustomSectionIndex>,
 
     /// The data for each CustomSection in the module.
     pub custom_sections_data: PrimaryMap<CustomSectionIndex, Arc<[u8]>>,
 
+    /// The module offset of the contents of the code section, which the
+    /// addresses in the DWARF of the module are relative to.
+    pub code_section_offset: usize,
+
     /// Number of imported functions in the module.
     pub num_imported_functions: usize,
 
@@ -113,6 +121,55 @@ pub struct ModuleInfo {
 
     /// Number of imported globals in the module.
     pub num_imported_globals: usize,
+
+    /// Number of imported exception tags in the module.
+    pub num_imported_tags: usize,
+
+    /// The nested modules of the module linking proposal.
+    pub submodules: Vec<Submodule>,
+
+    /// The instances of the module linking proposal, imported or
+    /// created by the module, in index order.
+    pub instances: Vec<InstanceInitializer>,
+
+    /// The imports resolved from the exports of the instances created
+    /// by the module rather than by the resolver, by index of the import,
+    /// as the index of the instance and the name of the export.
+    pub instance_exports: HashMap<u32, (usize, String)>,
+}
+
+/// A nested module of the module linking proposal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Submodule {
+    /// The Wasm binary of the module, as translated.
+    Binary(Arc<[u8]>),
+    /// The module compiled and serialized by the engine of its parent.
+    Serialized(Arc<[u8]>),
+}
+
+/// How an instance of the module linking proposal is obtained.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum InstanceInitializer {
+    /// The instance is imported: its exports are resolved from the
+    /// namespace with this name.
+    Import(String),
+
+    /// The instance is created by instantiating a nested module.
+    Instantiate {
+        /// The index of the module in `ModuleInfo::submodules`.
+        module: usize,
+        /// The items given to the imports of the module, by name.
+        args: Vec<(String, InstantiationArg)>,
+    },
+}
+
+/// An item given to a nested module when instantiating it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum InstantiationArg {
+    /// An item imported by the parent module, by index of the import.
+    Import(u32),
+    /// An instance of the parent module, by index.
+    Instance(usize),
 }
 
 impl ModuleInfo {
@@ -134,12 +191,18 @@ impl ModuleInfo {
             tables: PrimaryMap::new(),
             memories: PrimaryMap::new(),
             globals: PrimaryMap::new(),
+            tags: PrimaryMap::new(),
             num_imported_functions: 0,
             num_imported_tables: 0,
             num_imported_memories: 0,
             num_imported_globals: 0,
+            num_imported_tags: 0,
             custom_sections: IndexMap::new(),
             custom_sections_data: PrimaryMap::new(),
+            code_section_offset: 0,
+            submodules: Vec::new(),
+            instances: Vec::new(),
+            instance_exports: HashMap::new(),
         }
     }
 
@@ -184,6 +247,7 @@ impl ModuleInfo {
                     let global_type = self.globals.get(*i).unwrap();
                     ExternType::Global(*global_type)
                 }
+                ExportIndex::Tag(i) => ExternType::Tag(self.tag_type(*i)),
             };
             ExportType::new(name, extern_type)
         });
@@ -198,6 +262,7 @@ impl ModuleInfo {
         let iter = self
             .imports
             .iter()
+            .filter(move |((_, _, index), _)| !self.instance_exports.contains_key(index))
             .map(move |((module, field, _), import_index)| {
                 let extern_type = match import_index {
                     ImportIndex::Function(i) => {
@@ -217,12 +282,13 @@ impl ModuleInfo {
                         let global_type = self.globals.get(*i).unwrap();
                         ExternType::Global(*global_type)
                     }
+                    ImportIndex::Tag(i) => ExternType::Tag(self.tag_type(*i)),
                 };
                 ImportType::new(module, field, extern_type)
             });
         ImportsIterator {
             iter,
-            size: self.imports.len(),
+            size: self.imports.len() - self.instance_exports.len(),
         }
     }
 
@@ -313,6 +379,25 @@ impl ModuleInfo {
         index.index() < self.num_imported_globals
     }
 
+    /// Convert a `LocalTagIndex` into a `TagIndex`.
+    pub fn tag_index(&self, local_tag: LocalTagIndex) -> TagIndex {
+        TagIndex::new(self.num_imported_tags + local_tag.index())
+    }
+
+    /// Convert a `TagIndex` into a `LocalTagIndex`. Returns None if the
+    /// index is an imported tag.
+    pub fn local_tag_index(&self, tag: TagIndex) -> Option<LocalTagIndex> {
+        tag.index()
+            .checked_sub(self.num_imported_tags)
+            .map(LocalTagIndex::new)
+    }
+
+    /// Get the type of the given exception tag.
+    pub fn tag_type(&self, index: TagIndex) -> TagType {
+        let signature = self.tags[index];
+        TagType::new(self.signatures[signature].params())
+    }
+
     /// Get the Module name
     pub fn name(&self) -> String {
         match self.name {
@@ -382,6 +467,13 @@ impl<I: Iterator<Item = ExportType> + Sized> ExportsIterator<I> {
             _ => None,
         })
     }
+    /// Get only the tags
+    pub fn tags(self) -> impl Iterator<Item = ExportType<TagType>> + Sized {
+        self.iter.filter_map(|extern_| match extern_.ty() {
+            ExternType::Tag(ty) => Some(ExportType::new(extern_.name(), ty.clone())),
+            _ => None,
+        })
+    }
 }
 
 impl<I: Iterator<Item = ExportType> + Sized> Iterator for ExportsIterator<I> {
@@ -438,6 +530,17 @@ impl<I: Iterator<Item = ImportType> + Sized> ImportsIterator<I> {
             _ => None,
         })
     }
+    /// Get only the tags
+    pub fn tags(self) -> impl Iterator<Item = ImportType<TagType>> + Sized {
+        self.iter.filter_map(|extern_| match extern_.ty() {
+            ExternType::Tag(ty) => Some(ImportType::new(
+                extern_.module(),
+                extern_.name(),
+                ty.clone(),
+            )),
+            _ => None,
+        })
+    }
 }
 
 impl<I: Iterator<Item = ImportType> + Sized> Iterator for ImportsIterator<I> {
@@ -6,7 +6,7 @@
 //! `Table` is to WebAssembly tables what `LinearMemory` is to WebAssembly linear memories.
 
 use crate::trap::{Trap, TrapCode};
-use crate::vmcontext::{VMCallerCheckedAnyfunc, VMTableDefinition};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMExternRef, VMTableDefinition};
 use serde::{Deserialize, Serialize};
 use std::borrow::{Borrow, BorrowMut};
 use std::cell::UnsafeCell;
@@ -23,6 +23,73 @@ pub enum TableStyle {
     CallerChecksSignature,
 }
 
+/// The value of a table element.
+#[derive(Debug, Clone)]
+pub enum TableElement {
+    /// An element of a `funcref` table.
+    FuncRef(VMCallerCheckedAnyfunc),
+    /// An element of an `externref` table.
+    ExternRef(VMExternRef),
+}
+
+/// The raw value of a table element as compiled code passes it to the
+/// table libcalls, the table type telling which field is valid.
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub union RawTableElement {
+    /// A `funcref`: a pointer to a `VMCallerCheckedAnyfunc`, or null.
+    pub func_ref: *const VMCallerCheckedAnyfunc,
+    /// An `externref`.
+    pub extern_ref: VMExternRef,
+}
+
+/// The elements of a table, stored the way compiled code reads them.
+#[derive(Debug)]
+enum TableElements {
+    FuncRefs(Vec<VMCallerCheckedAnyfunc>),
+    ExternRefs(Vec<VMExternRef>),
+}
+
+impl TableElements {
+    fn resize(&mut self, new_len: usize) {
+        match self {
+            Self::FuncRefs(vec) => vec.resize(new_len, VMCallerCheckedAnyfunc::default()),
+            Self::ExternRefs(vec) => vec.resize(new_len, VMExternRef::null()),
+        }
+    }
+
+    fn base(&mut self) -> *mut u8 {
+        match self {
+            Self::FuncRefs(vec) => vec.as_mut_ptr() as _,
+            Self::ExternRefs(vec) => vec.as_mut_ptr() as _,
+        }
+    }
+
+    fn get(&self, index: usize) -> Option<TableElement> {
+        match self {
+            Self::FuncRefs(vec) => vec.get(index).cloned().map(TableElement::FuncRef),
+            Self::ExternRefs(vec) => vec.get(index).cloned().map(TableElement::ExternRef),
+        }
+    }
+
+    fn set(&mut self, index: usize, element: TableElement) -> Result<(), Trap> {
+        match (self, element) {
+            (Self::FuncRefs(vec), TableElement::FuncRef(func)) => match vec.get_mut(index) {
+                Some(slot) => *slot = func,
+                None => return Err(Trap::new_from_runtime(TrapCode::TableAccessOutOfBounds)),
+            },
+            (Self::ExternRefs(vec), TableElement::ExternRef(extern_ref)) => {
+                match vec.get_mut(index) {
+                    Some(slot) => *slot = extern_ref,
+                    None => return Err(Trap::new_from_runtime(TrapCode::TableAccessOutOfBounds)),
+                }
+            }
+            (_, element) => panic!("table element {:?} of the wrong type", element),
+        }
+        Ok(())
+    }
+}
+
 /// Trait for implementing the interface of a Wasm table.
 pub trait Table: fmt::Debug + Send + Sync {
     /// Returns the style for this Table.
@@ -43,14 +110,14 @@ pub trait Table: fmt::Debug + Send + Sync {
     /// Get reference to the specified element.
     ///
     /// Returns `None` if the index is out of bounds.
-    fn get(&self, index: u32) -> Option<VMCallerCheckedAnyfunc>;
+    fn get(&self, index: u32) -> Option<TableElement>;
 
     /// Set reference to the specified element.
     ///
     /// # Errors
     ///
     /// Returns an error if the index is out of bounds.
-    fn set(&self, index: u32, func: VMCallerCheckedAnyfunc) -> Result<(), Trap>;
+    fn set(&self, index: u32, element: TableElement) -> Result<(), Trap>;
 
     /// Return a `VMTableDefinition` for exposing the table to compiled wasm code.
     fn vmtable(&self) -> NonNull<VMTableDefinition>;
@@ -100,13 +167,32 @@ pub trait Table: fmt::Debug + Send + Sync {
 
         Ok(())
     }
+
+    /// Set `len` elements starting at `dst_index` to `element`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is out of bounds of the table.
+    fn fill(&self, dst_index: u32, element: TableElement, len: u32) -> Result<(), Trap> {
+        // https://webassembly.github.io/reference-types/core/exec/instructions.html#exec-table-fill
+
+        if dst_index.checked_add(len).map_or(true, |m| m > self.size()) {
+            return Err(Trap::new_from_runtime(TrapCode::TableAccessOutOfBounds));
+        }
+
+        for index in dst_index..dst_index + len {
+            self.set(index, element.clone())?;
+        }
+
+        Ok(())
+    }
 }
 
 /// A table instance.
 #[derive(Debug)]
 pub struct LinearTable {
     // TODO: we can remove the mutex by using atomic swaps and preallocating the max table size
-    vec: Mutex<Vec<VMCallerCheckedAnyfunc>>,
+    vec: Mutex<TableElements>,
     maximum: Option<u32>,
     /// The WebAssembly table description.
     table: TableType,
@@ -163,10 +249,6 @@ impl LinearTable {
         style: &TableStyle,
         vm_table_location: Option<NonNull<VMTableDefinition>>,
     ) -> Result<Self, String> {
-        match table.ty {
-            ValType::FuncRef => (),
-            ty => return Err(format!("tables of types other than anyfunc ({})", ty)),
-        };
         if let Some(max) = table.maximum {
             if max < table.minimum {
                 return Err(format!(
@@ -177,8 +259,16 @@ impl LinearTable {
         }
         let table_minimum = usize::try_from(table.minimum)
             .map_err(|_| "Table minimum is bigger than usize".to_string())?;
-        let mut vec = vec![VMCallerCheckedAnyfunc::default(); table_minimum];
-        let base = vec.as_mut_ptr();
+        let mut vec = match table.ty {
+            ValType::FuncRef => {
+                TableElements::FuncRefs(vec![VMCallerCheckedAnyfunc::default(); table_minimum])
+            }
+            ValType::ExternRef => {
+                TableElements::ExternRefs(vec![VMExternRef::null(); table_minimum])
+            }
+            ty => return Err(format!("tables of type {} are not supported", ty)),
+        };
+        let base = vec.base();
         match style {
             TableStyle::CallerChecksSignature => Ok(Self {
                 vec: Mutex::new(vec),
@@ -189,14 +279,14 @@ impl LinearTable {
                     {
                         let mut ptr = table_loc;
                         let td = ptr.as_mut();
-                        td.base = base as _;
+                        td.base = base;
                         td.current_elements = table_minimum as _;
                     }
                     VMTableDefinitionOwnership::VMOwned(table_loc)
                 } else {
                     VMTableDefinitionOwnership::HostOwned(Box::new(UnsafeCell::new(
                         VMTableDefinition {
-                            base: base as _,
+                            base,
                             current_elements: table_minimum as _,
                         },
                     )))
@@ -253,17 +343,14 @@ impl Table for LinearTable {
         if self.maximum.map_or(false, |max| new_len > max) {
             return None;
         }
-        vec.resize(
-            usize::try_from(new_len).unwrap(),
-            VMCallerCheckedAnyfunc::default(),
-        );
+        vec.resize(usize::try_from(new_len).unwrap());
 
         // update table definition
         unsafe {
             let mut td_ptr = self.get_vm_table_definition();
             let td = td_ptr.as_mut();
             td.current_elements = new_len;
-            td.base = vec.as_mut_ptr() as _;
+            td.base = vec.base();
         }
         Some(size)
     }
@@ -271,9 +358,9 @@ impl Table for LinearTable {
     /// Get reference to the specified element.
     ///
     /// Returns `None` if the index is out of bounds.
-    fn get(&self, index: u32) -> Option<VMCallerCheckedAnyfunc> {
+    fn get(&self, index: u32) -> Option<TableElement> {
         let vec_guard = self.vec.lock().unwrap();
-        vec_guard.borrow().get(index as usize).cloned()
+        vec_guard.borrow().get(index as usize)
     }
 
     /// Set reference to the specified element.
@@ -281,16 +368,9 @@ impl Table for LinearTable {
     /// # Errors
     ///
     /// Returns an error if the index is out of bounds.
-    fn set(&self, index: u32, func: VMCallerCheckedAnyfunc) -> Result<(), Trap> {
+    fn set(&self, index: u32, element: TableElement) -> Result<(), Trap> {
         let mut vec_guard = self.vec.lock().unwrap();
-        let vec = vec_guard.borrow_mut();
-        match vec.get_mut(index as usize) {
-            Some(slot) => {
-                *slot = func;
-                Ok(())
-            }
-            None => Err(Trap::new_from_runtime(TrapCode::TableAccessOutOfBounds)),
-        }
+        vec_guard.borrow_mut().set(index as usize, element)
     }
 
     /// Return a `VMTableDefinition` for exposing the table to compiled wasm code.
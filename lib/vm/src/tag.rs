@@ -0,0 +1,25 @@
+//! Exception tags, from the exception handling proposal.
+
+use wasmer_types::TagType;
+
+/// An exception tag.
+///
+/// Tags are compared by identity: two tags with the same type are still
+/// different tags, so exceptions are always matched against the
+/// `Arc<Tag>` they were created from.
+#[derive(Debug)]
+pub struct Tag {
+    ty: TagType,
+}
+
+impl Tag {
+    /// Create a new tag of the given type.
+    pub fn new(ty: TagType) -> Self {
+        Self { ty }
+    }
+
+    /// Get the type of the tag.
+    pub fn ty(&self) -> &TagType {
+        &self.ty
+    }
+}
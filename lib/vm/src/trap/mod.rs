@@ -3,9 +3,13 @@
 
 //! This is the module that facilitates the usage of Traps
 //! in Wasmer Runtime
+#[cfg(unix)]
+mod stack;
 mod trapcode;
 mod traphandlers;
 
+#[cfg(unix)]
+pub use stack::on_guest_stack;
 pub use trapcode::TrapCode;
 pub use traphandlers::{
     catch_traps, catch_traps_with_result, raise_lib_trap, raise_user_trap, wasmer_call_trampoline,
@@ -0,0 +1,43 @@
+// Switching to the stack guest code runs on, see `stack.rs`.
+
+// `ucontext.h` is only declared for XSI-conforming programs on macOS.
+#define _XOPEN_SOURCE 700
+#if defined(__APPLE__)
+#define _DARWIN_C_SOURCE
+#endif
+
+#include <stddef.h>
+#include <ucontext.h>
+
+// `makecontext` can only pass `int` arguments, so the body is handed over
+// through thread-local storage. It is read as soon as the new stack is
+// entered, before anything else can run on this thread.
+static __thread void (*StackBody)(void*);
+static __thread void *StackPayload;
+
+static void StackEntry(void) {
+  StackBody(StackPayload);
+}
+
+int CallOnStack(
+    void *stack,
+    size_t stack_size,
+    void (*body)(void*),
+    void *payload) {
+  ucontext_t caller;
+  ucontext_t callee;
+  if (getcontext(&callee) != 0) {
+    return 0;
+  }
+  callee.uc_stack.ss_sp = stack;
+  callee.uc_stack.ss_size = stack_size;
+  callee.uc_stack.ss_flags = 0;
+  callee.uc_link = &caller;
+  makecontext(&callee, StackEntry, 0);
+  StackBody = body;
+  StackPayload = payload;
+  if (swapcontext(&caller, &callee) != 0) {
+    return 0;
+  }
+  return 1;
+}
@@ -0,0 +1,153 @@
+//! Running WebAssembly code on a dedicated stack.
+//!
+//! The recursion depth a guest can reach normally depends on the stack of
+//! the host thread calling it. [`on_guest_stack`] instead runs it on a
+//! stack of a chosen size, kept for each thread, with a guard page below
+//! it so that overflowing it is reported as a
+//! [`TrapCode::StackOverflow`] trap.
+
+use super::trapcode::TrapCode;
+use super::traphandlers::Trap;
+use crate::mmap::Mmap;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+
+extern "C" {
+    fn CallOnStack(
+        stack: *mut u8,
+        stack_size: usize,
+        body: extern "C" fn(*mut u8),
+        payload: *mut u8,
+    ) -> i32;
+}
+
+thread_local! {
+    /// The stack of this thread, allocated on first use.
+    static STACK: RefCell<Option<GuestStack>> = RefCell::new(None);
+
+    /// The address range of the stack this thread is running on, guard
+    /// page included, if it is a guest stack.
+    static CURRENT: Cell<Option<(usize, usize)>> = Cell::new(None);
+}
+
+/// A stack with a guard page below it.
+struct GuestStack {
+    mmap: Mmap,
+    guard_size: usize,
+}
+
+impl GuestStack {
+    fn new(size: usize) -> Result<Self, String> {
+        let page_size = region::page::size();
+        let size = ((size.max(1) + page_size - 1) / page_size) * page_size;
+        let guard_size = page_size;
+        let mmap = Mmap::accessible_reserved(guard_size + size, guard_size + size)?;
+        unsafe { region::protect(mmap.as_ptr(), guard_size, region::Protection::NONE) }
+            .map_err(|error| error.to_string())?;
+        Ok(Self { mmap, guard_size })
+    }
+
+    /// The usable size of the stack.
+    fn size(&self) -> usize {
+        self.mmap.len() - self.guard_size
+    }
+
+    /// The address range of the stack, guard page included.
+    fn range(&self) -> (usize, usize) {
+        let start = self.mmap.as_ptr() as usize;
+        (start, start + self.mmap.len())
+    }
+}
+
+/// Whether `addr` is in the guest stack the current thread is running on.
+///
+/// This is called from the signal handler, so it must not allocate.
+pub(crate) fn in_guest_stack(addr: usize) -> bool {
+    CURRENT
+        .try_with(|current| match current.get() {
+            Some((start, end)) => start <= addr && addr < end,
+            None => false,
+        })
+        .unwrap_or(false)
+}
+
+/// Runs `closure` on a dedicated stack of at least `size` bytes.
+///
+/// The stack is allocated on the first call in each thread, and again
+/// when `size` changes. If the thread is already running on its guest
+/// stack, as when the host called from WebAssembly calls it again,
+/// `closure` runs on the current stack.
+///
+/// Returns a [`TrapCode::VMOutOfMemory`] trap if the stack can't be
+/// allocated.
+///
+/// # Safety
+///
+/// `closure` must not unwind through the stack switch with anything but
+/// a Rust panic, which is resumed on the calling stack. Traps must be
+/// caught on the guest stack, by calling [`catch_traps`] from `closure`.
+///
+/// [`catch_traps`]: super::catch_traps
+pub unsafe fn on_guest_stack<F, R>(size: usize, closure: F) -> Result<R, Trap>
+where
+    F: FnOnce() -> R,
+{
+    if CURRENT.with(|current| current.get().is_some()) {
+        return Ok(closure());
+    }
+
+    STACK.with(|stack| {
+        let mut stack = stack.borrow_mut();
+        let reuse = match &*stack {
+            Some(guest_stack) => guest_stack.size() >= size,
+            None => false,
+        };
+        if !reuse {
+            // Free the previous stack first, in case both wouldn't fit.
+            *stack = None;
+            *stack = Some(
+                GuestStack::new(size)
+                    .map_err(|_| Trap::new_from_runtime(TrapCode::VMOutOfMemory))?,
+            );
+        }
+        let stack = stack.as_mut().unwrap();
+
+        struct Call<F, R> {
+            closure: Option<F>,
+            result: Option<Result<R, Box<dyn Any + Send>>>,
+        }
+
+        extern "C" fn call<F, R>(payload: *mut u8)
+        where
+            F: FnOnce() -> R,
+        {
+            let call = unsafe { &mut *(payload as *mut Call<F, R>) };
+            let closure = call.closure.take().unwrap();
+            // Panics can't unwind past the start of the stack, so they are
+            // carried back to the calling stack.
+            call.result = Some(panic::catch_unwind(AssertUnwindSafe(closure)));
+        }
+
+        let mut payload = Call {
+            closure: Some(closure),
+            result: None,
+        };
+        let (start, end) = stack.range();
+        CURRENT.with(|current| current.set(Some((start, end))));
+        let switched = CallOnStack(
+            stack.mmap.as_mut_ptr().add(stack.guard_size),
+            stack.size(),
+            call::<F, R>,
+            &mut payload as *mut Call<F, R> as *mut u8,
+        );
+        CURRENT.with(|current| current.set(None));
+        if switched == 0 {
+            return Err(Trap::new_from_runtime(TrapCode::VMOutOfMemory));
+        }
+        match payload.result.take().unwrap() {
+            Ok(result) => Ok(result),
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    })
+}
@@ -121,7 +121,10 @@ cfg_if::cfg_if! {
                     // The stack and its guard page covers the
                     // range [stackaddr - guard pages .. stackaddr + stacksize).
                     // We assume the guard page is 1 page, and pages are 4KiB (or 16KiB in Apple Silicon)
-                    if stackaddr - region::page::size() <= addr && addr < stackaddr + stacksize {
+                    let in_thread_stack =
+                        stackaddr - region::page::size() <= addr && addr < stackaddr + stacksize;
+                    // Guest stacks have their own guard page.
+                    if in_thread_stack || super::stack::in_guest_stack(addr) {
                         Some(TrapCode::StackOverflow)
                     } else {
                         Some(TrapCode::HeapAccessOutOfBounds)
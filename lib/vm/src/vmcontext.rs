@@ -9,11 +9,14 @@ use crate::instance::Instance;
 use crate::memory::Memory;
 use crate::table::Table;
 use crate::trap::{Trap, TrapCode};
+use crate::waiter::Waiters;
 use std::any::Any;
 use std::convert::TryFrom;
 use std::fmt;
 use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use std::u32;
 
 /// Union representing the first parameter passed when calling a function.
@@ -322,7 +325,7 @@ pub struct VMMemoryDefinition {
     pub base: *mut u8,
 
     /// The current logical size of this linear memory in bytes.
-    pub current_length: u32,
+    pub current_length: usize,
 }
 
 /// # Safety
@@ -347,25 +350,43 @@ impl VMMemoryDefinition {
     /// # Safety
     /// The memory is not copied atomically and is not synchronized: it's the
     /// caller's responsibility to synchronize.
-    pub(crate) unsafe fn memory_copy(&self, dst: u32, src: u32, len: u32) -> Result<(), Trap> {
+    pub(crate) unsafe fn memory_copy(&self, dst: u64, src: u64, len: u64) -> Result<(), Trap> {
+        self.memory_copy_from(self, dst, src, len)
+    }
+
+    /// Do an unsynchronized, non-atomic `memory.copy` from `src_memory` into
+    /// this memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error when the source or destination ranges are out of
+    /// bounds.
+    ///
+    /// # Safety
+    /// The memory is not copied atomically and is not synchronized: it's the
+    /// caller's responsibility to synchronize.
+    pub(crate) unsafe fn memory_copy_from(
+        &self,
+        src_memory: &Self,
+        dst: u64,
+        src: u64,
+        len: u64,
+    ) -> Result<(), Trap> {
         // https://webassembly.github.io/reference-types/core/exec/instructions.html#exec-memory-copy
         if src
             .checked_add(len)
-            .map_or(true, |n| n > self.current_length)
+            .map_or(true, |n| n > src_memory.current_length as u64)
             || dst
                 .checked_add(len)
-                .map_or(true, |m| m > self.current_length)
+                .map_or(true, |m| m > self.current_length as u64)
         {
             return Err(Trap::new_from_runtime(TrapCode::HeapAccessOutOfBounds));
         }
 
-        let dst = usize::try_from(dst).unwrap();
-        let src = usize::try_from(src).unwrap();
-
-        // Bounds and casts are checked above, by this point we know that
-        // everything is safe.
-        let dst = self.base.add(dst);
-        let src = self.base.add(src);
+        // Bounds are checked above, and the lengths of the memories fit in
+        // `usize`, by this point we know that everything is safe.
+        let dst = self.base.add(dst as usize);
+        let src = src_memory.base.add(src as usize);
         ptr::copy(src, dst, len as usize);
 
         Ok(())
@@ -381,24 +402,121 @@ impl VMMemoryDefinition {
     /// # Safety
     /// The memory is not filled atomically and is not synchronized: it's the
     /// caller's responsibility to synchronize.
-    pub(crate) unsafe fn memory_fill(&self, dst: u32, val: u32, len: u32) -> Result<(), Trap> {
+    pub(crate) unsafe fn memory_fill(&self, dst: u64, val: u32, len: u64) -> Result<(), Trap> {
         if dst
             .checked_add(len)
-            .map_or(true, |m| m > self.current_length)
+            .map_or(true, |m| m > self.current_length as u64)
         {
             return Err(Trap::new_from_runtime(TrapCode::HeapAccessOutOfBounds));
         }
 
-        let dst = isize::try_from(dst).unwrap();
         let val = val as u8;
 
-        // Bounds and casts are checked above, by this point we know that
-        // everything is safe.
-        let dst = self.base.offset(dst);
+        // Bounds are checked above, and the length of the memory fits in
+        // `usize`, by this point we know that everything is safe.
+        let dst = self.base.add(dst as usize);
         ptr::write_bytes(dst, val, len as usize);
 
         Ok(())
     }
+
+    /// Returns a pointer to the `size` bytes at `dst`, checking that they
+    /// are in bounds and aligned for an atomic access.
+    unsafe fn atomic_location(&self, dst: u64, size: u64) -> Result<*mut u8, Trap> {
+        if dst
+            .checked_add(size)
+            .map_or(true, |end| end > self.current_length as u64)
+        {
+            return Err(Trap::new_from_runtime(TrapCode::HeapAccessOutOfBounds));
+        }
+        if dst % size != 0 {
+            return Err(Trap::new_from_runtime(TrapCode::UnalignedAtomic));
+        }
+        Ok(self.base.add(usize::try_from(dst).unwrap()))
+    }
+
+    /// Perform the `memory.atomic.wait32` operation for the memory.
+    ///
+    /// Returns 0 when woken by a notification, 1 if the location doesn't
+    /// hold `expected` and 2 if the timeout, in nanoseconds, elapsed.
+    /// A negative timeout waits forever.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the location is out of bounds or misaligned.
+    ///
+    /// # Safety
+    /// `waiters` must be the waiters of this memory.
+    pub(crate) unsafe fn atomic_wait32(
+        &self,
+        waiters: &Waiters,
+        dst: u64,
+        expected: u32,
+        timeout: i64,
+    ) -> Result<u32, Trap> {
+        let location = &*(self.atomic_location(dst, 4)? as *const AtomicU32);
+        Ok(waiters.wait(
+            dst,
+            || location.load(Ordering::SeqCst) == expected,
+            wait_timeout(timeout),
+        ))
+    }
+
+    /// Perform the `memory.atomic.wait64` operation for the memory.
+    ///
+    /// See [`VMMemoryDefinition::atomic_wait32`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the location is out of bounds or misaligned.
+    ///
+    /// # Safety
+    /// `waiters` must be the waiters of this memory.
+    pub(crate) unsafe fn atomic_wait64(
+        &self,
+        waiters: &Waiters,
+        dst: u64,
+        expected: u64,
+        timeout: i64,
+    ) -> Result<u32, Trap> {
+        let location = &*(self.atomic_location(dst, 8)? as *const AtomicU64);
+        Ok(waiters.wait(
+            dst,
+            || location.load(Ordering::SeqCst) == expected,
+            wait_timeout(timeout),
+        ))
+    }
+
+    /// Perform the `memory.atomic.notify` operation for the memory, and
+    /// return the number of woken waiters.
+    ///
+    /// `waiters` is `None` for an unshared memory, which never has waiters.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `Trap` error if the location is out of bounds or misaligned.
+    ///
+    /// # Safety
+    /// `waiters` must be the waiters of this memory.
+    pub(crate) unsafe fn atomic_notify(
+        &self,
+        waiters: Option<&Waiters>,
+        dst: u64,
+        count: u32,
+    ) -> Result<u32, Trap> {
+        self.atomic_location(dst, 4)?;
+        Ok(waiters.map_or(0, |waiters| waiters.notify(dst, count)))
+    }
+}
+
+/// Converts the timeout operand of `memory.atomic.wait`, where negative
+/// values mean no timeout.
+fn wait_timeout(timeout: i64) -> Option<Duration> {
+    if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(timeout as u64))
+    }
 }
 
 #[cfg(test)]
@@ -424,12 +542,10 @@ mod test_vmmemory_definition {
             offset_of!(VMMemoryDefinition, current_length),
             usize::from(offsets.vmmemory_definition_current_length())
         );
-        /* TODO: Assert that the size of `current_length` matches.
         assert_eq!(
-            size_of::<VMMemoryDefinition::current_length>(),
+            size_of::<usize>(),
             usize::from(offsets.size_of_vmmemory_definition_current_length())
         );
-        */
     }
 }
 
@@ -804,6 +920,27 @@ impl Default for VMCallerCheckedAnyfunc {
     }
 }
 
+/// The raw value of an `externref` as compiled code sees it: an opaque
+/// word identifying the referenced host data, 0 being the null reference.
+///
+/// The host is responsible for keeping the data alive while compiled code
+/// may still hold the value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[repr(transparent)]
+pub struct VMExternRef(pub usize);
+
+impl VMExternRef {
+    /// The null reference.
+    pub const fn null() -> Self {
+        Self(0)
+    }
+
+    /// Returns true if this is the null reference.
+    pub const fn is_null(self) -> bool {
+        self.0 == 0
+    }
+}
+
 /// An index type for builtin functions.
 #[derive(Copy, Clone, Debug)]
 pub struct VMBuiltinFunctionIndex(u32);
@@ -866,9 +1003,156 @@ impl VMBuiltinFunctionIndex {
     pub const fn get_raise_trap_index() -> Self {
         Self(13)
     }
+    /// Returns an index for wasm's `memory.atomic.wait32` for locally
+    /// defined memories.
+    pub const fn get_memory_atomic_wait32_index() -> Self {
+        Self(14)
+    }
+    /// Returns an index for wasm's `memory.atomic.wait32` for imported
+    /// memories.
+    pub const fn get_imported_memory_atomic_wait32_index() -> Self {
+        Self(15)
+    }
+    /// Returns an index for wasm's `memory.atomic.wait64` for locally
+    /// defined memories.
+    pub const fn get_memory_atomic_wait64_index() -> Self {
+        Self(16)
+    }
+    /// Returns an index for wasm's `memory.atomic.wait64` for imported
+    /// memories.
+    pub const fn get_imported_memory_atomic_wait64_index() -> Self {
+        Self(17)
+    }
+    /// Returns an index for wasm's `memory.atomic.notify` for locally
+    /// defined memories.
+    pub const fn get_memory_atomic_notify_index() -> Self {
+        Self(18)
+    }
+    /// Returns an index for wasm's `memory.atomic.notify` for imported
+    /// memories.
+    pub const fn get_imported_memory_atomic_notify_index() -> Self {
+        Self(19)
+    }
+    /// Returns an index for a call made from inside a `try` block.
+    pub const fn get_try_call_index() -> Self {
+        Self(20)
+    }
+    /// Returns an index for creating the exception of wasm's `throw`
+    /// instruction.
+    pub const fn get_exception_new_index() -> Self {
+        Self(21)
+    }
+    /// Returns an index for matching an exception against the tag of
+    /// wasm's `catch` instruction.
+    pub const fn get_exception_matches_index() -> Self {
+        Self(22)
+    }
+    /// Returns an index for taking the values carried by an exception.
+    pub const fn get_exception_take_payload_index() -> Self {
+        Self(23)
+    }
+    /// Returns an index for throwing an exception out of a function.
+    pub const fn get_exception_throw_index() -> Self {
+        Self(24)
+    }
+    /// Returns an index for wasm's `memory.grow` on 64-bit memories.
+    pub const fn get_memory64_grow_index() -> Self {
+        Self(25)
+    }
+    /// Returns an index for wasm's imported `memory.grow` on 64-bit
+    /// memories.
+    pub const fn get_imported_memory64_grow_index() -> Self {
+        Self(26)
+    }
+    /// Returns an index for wasm's `memory.size` on 64-bit memories.
+    pub const fn get_memory64_size_index() -> Self {
+        Self(27)
+    }
+    /// Returns an index for wasm's imported `memory.size` on 64-bit
+    /// memories.
+    pub const fn get_imported_memory64_size_index() -> Self {
+        Self(28)
+    }
+    /// Returns an index for wasm's `memory.copy` between two different
+    /// memories.
+    pub const fn get_memory_copy_between_index() -> Self {
+        Self(29)
+    }
+    /// Returns an index for wasm's `ref.func` instruction.
+    pub const fn get_func_ref_index() -> Self {
+        Self(30)
+    }
+    /// Returns an index for wasm's `table.get` instruction.
+    pub const fn get_table_get_index() -> Self {
+        Self(31)
+    }
+    /// Returns an index for wasm's `table.set` instruction.
+    pub const fn get_table_set_index() -> Self {
+        Self(32)
+    }
+    /// Returns an index for wasm's `table.grow` instruction.
+    pub const fn get_table_grow_index() -> Self {
+        Self(33)
+    }
+    /// Returns an index for wasm's `table.fill` instruction.
+    pub const fn get_table_fill_index() -> Self {
+        Self(34)
+    }
+    /// Returns an index for wasm's local `memory.copy` on 64-bit memories.
+    pub const fn get_local_memory64_copy_index() -> Self {
+        Self(35)
+    }
+    /// Returns an index for wasm's imported `memory.copy` on 64-bit
+    /// memories.
+    pub const fn get_imported_memory64_copy_index() -> Self {
+        Self(36)
+    }
+    /// Returns an index for wasm's local `memory.fill` on 64-bit memories.
+    pub const fn get_memory64_fill_index() -> Self {
+        Self(37)
+    }
+    /// Returns an index for wasm's imported `memory.fill` on 64-bit
+    /// memories.
+    pub const fn get_imported_memory64_fill_index() -> Self {
+        Self(38)
+    }
+    /// Returns an index for wasm's `memory.init` on 64-bit memories.
+    pub const fn get_memory64_init_index() -> Self {
+        Self(39)
+    }
+    /// Returns an index for wasm's `memory.copy` between two different
+    /// memories, at least one of which is a 64-bit memory.
+    pub const fn get_memory64_copy_between_index() -> Self {
+        Self(40)
+    }
+    /// Returns an index for the atomic loads, on the targets which can't
+    /// lower them.
+    pub const fn get_atomic_load_index() -> Self {
+        Self(41)
+    }
+    /// Returns an index for the atomic stores, on the targets which can't
+    /// lower them.
+    pub const fn get_atomic_store_index() -> Self {
+        Self(42)
+    }
+    /// Returns an index for the atomic read-modify-write operations, on the
+    /// targets which can't lower them.
+    pub const fn get_atomic_rmw_index() -> Self {
+        Self(43)
+    }
+    /// Returns an index for the atomic compare-and-exchange operations, on
+    /// the targets which can't lower them.
+    pub const fn get_atomic_cmpxchg_index() -> Self {
+        Self(44)
+    }
+    /// Returns an index for freeing an exception that isn't thrown
+    /// further.
+    pub const fn get_exception_delete_index() -> Self {
+        Self(45)
+    }
     /// Returns the total number of builtin functions.
     pub const fn builtin_functions_total_number() -> u32 {
-        14
+        46
     }
 
     /// Return the index as an u32 number.
@@ -926,6 +1210,70 @@ impl VMBuiltinFunctionsArray {
             wasmer_data_drop as usize;
         ptrs[VMBuiltinFunctionIndex::get_raise_trap_index().index() as usize] =
             wasmer_raise_trap as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory_atomic_wait32_index().index() as usize] =
+            wasmer_memory_atomic_wait32 as usize;
+        ptrs[VMBuiltinFunctionIndex::get_imported_memory_atomic_wait32_index().index() as usize] =
+            wasmer_imported_memory_atomic_wait32 as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory_atomic_wait64_index().index() as usize] =
+            wasmer_memory_atomic_wait64 as usize;
+        ptrs[VMBuiltinFunctionIndex::get_imported_memory_atomic_wait64_index().index() as usize] =
+            wasmer_imported_memory_atomic_wait64 as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory_atomic_notify_index().index() as usize] =
+            wasmer_memory_atomic_notify as usize;
+        ptrs[VMBuiltinFunctionIndex::get_imported_memory_atomic_notify_index().index() as usize] =
+            wasmer_imported_memory_atomic_notify as usize;
+        ptrs[VMBuiltinFunctionIndex::get_try_call_index().index() as usize] =
+            wasmer_try_call as usize;
+        ptrs[VMBuiltinFunctionIndex::get_exception_new_index().index() as usize] =
+            wasmer_exception_new as usize;
+        ptrs[VMBuiltinFunctionIndex::get_exception_matches_index().index() as usize] =
+            wasmer_exception_matches as usize;
+        ptrs[VMBuiltinFunctionIndex::get_exception_take_payload_index().index() as usize] =
+            wasmer_exception_take_payload as usize;
+        ptrs[VMBuiltinFunctionIndex::get_exception_throw_index().index() as usize] =
+            wasmer_exception_throw as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory64_grow_index().index() as usize] =
+            wasmer_memory64_grow as usize;
+        ptrs[VMBuiltinFunctionIndex::get_imported_memory64_grow_index().index() as usize] =
+            wasmer_imported_memory64_grow as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory64_size_index().index() as usize] =
+            wasmer_memory64_size as usize;
+        ptrs[VMBuiltinFunctionIndex::get_imported_memory64_size_index().index() as usize] =
+            wasmer_imported_memory64_size as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory_copy_between_index().index() as usize] =
+            wasmer_memory_copy_between as usize;
+        ptrs[VMBuiltinFunctionIndex::get_func_ref_index().index() as usize] =
+            wasmer_func_ref as usize;
+        ptrs[VMBuiltinFunctionIndex::get_table_get_index().index() as usize] =
+            wasmer_table_get as usize;
+        ptrs[VMBuiltinFunctionIndex::get_table_set_index().index() as usize] =
+            wasmer_table_set as usize;
+        ptrs[VMBuiltinFunctionIndex::get_table_grow_index().index() as usize] =
+            wasmer_table_grow as usize;
+        ptrs[VMBuiltinFunctionIndex::get_table_fill_index().index() as usize] =
+            wasmer_table_fill as usize;
+        ptrs[VMBuiltinFunctionIndex::get_local_memory64_copy_index().index() as usize] =
+            wasmer_local_memory64_copy as usize;
+        ptrs[VMBuiltinFunctionIndex::get_imported_memory64_copy_index().index() as usize] =
+            wasmer_imported_memory64_copy as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory64_fill_index().index() as usize] =
+            wasmer_memory64_fill as usize;
+        ptrs[VMBuiltinFunctionIndex::get_imported_memory64_fill_index().index() as usize] =
+            wasmer_imported_memory64_fill as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory64_init_index().index() as usize] =
+            wasmer_memory64_init as usize;
+        ptrs[VMBuiltinFunctionIndex::get_memory64_copy_between_index().index() as usize] =
+            wasmer_memory64_copy_between as usize;
+        ptrs[VMBuiltinFunctionIndex::get_atomic_load_index().index() as usize] =
+            wasmer_atomic_load as usize;
+        ptrs[VMBuiltinFunctionIndex::get_atomic_store_index().index() as usize] =
+            wasmer_atomic_store as usize;
+        ptrs[VMBuiltinFunctionIndex::get_atomic_rmw_index().index() as usize] =
+            wasmer_atomic_rmw as usize;
+        ptrs[VMBuiltinFunctionIndex::get_atomic_cmpxchg_index().index() as usize] =
+            wasmer_atomic_cmpxchg as usize;
+        ptrs[VMBuiltinFunctionIndex::get_exception_delete_index().index() as usize] =
+            wasmer_exception_delete as usize;
 
         debug_assert!(ptrs.iter().cloned().all(|p| p != 0));
 
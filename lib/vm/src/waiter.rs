@@ -0,0 +1,168 @@
+//! Wait queues for `memory.atomic.wait` and `memory.atomic.notify`.
+//!
+//! Each shared memory owns its wait queues, keyed by the offset of the
+//! waited-on location, so threads running different instances that import
+//! the memory see each other.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The waiter was woken by a `memory.atomic.notify`.
+pub(crate) const WAIT_OK: u32 = 0;
+
+/// The location didn't hold the expected value.
+pub(crate) const WAIT_NOT_EQUAL: u32 = 1;
+
+/// The timeout elapsed before the waiter was woken.
+pub(crate) const WAIT_TIMED_OUT: u32 = 2;
+
+/// A thread blocked in `memory.atomic.wait`.
+#[derive(Debug, Default)]
+struct Waiter {
+    notified: Mutex<bool>,
+    cond: Condvar,
+}
+
+/// The threads blocked in `memory.atomic.wait` on a shared memory.
+#[derive(Debug, Default)]
+pub struct Waiters {
+    /// The waiters of each location, in the order they started waiting.
+    queues: Mutex<HashMap<u64, VecDeque<Arc<Waiter>>>>,
+}
+
+impl Waiters {
+    /// Blocks the current thread on the location at `offset` until it is
+    /// notified or `timeout` elapses, if `expected` returns `true`.
+    ///
+    /// `expected` runs with the wait queues locked, so no notification can be
+    /// missed between the check and the wait.
+    pub(crate) fn wait(
+        &self,
+        offset: u64,
+        expected: impl FnOnce() -> bool,
+        timeout: Option<Duration>,
+    ) -> u32 {
+        let waiter = {
+            let mut waiters = self.queues.lock().unwrap();
+            if !expected() {
+                return WAIT_NOT_EQUAL;
+            }
+            let waiter = Arc::new(Waiter::default());
+            waiters.entry(offset).or_default().push_back(waiter.clone());
+            waiter
+        };
+
+        // A timeout too large to be represented is the same as no timeout.
+        let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+        let mut notified = waiter.notified.lock().unwrap();
+        while !*notified {
+            match deadline {
+                None => notified = waiter.cond.wait(notified).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    notified = waiter
+                        .cond
+                        .wait_timeout(notified, deadline - now)
+                        .unwrap()
+                        .0;
+                }
+            }
+        }
+        if *notified {
+            return WAIT_OK;
+        }
+        drop(notified);
+
+        // The waiter may have been notified after the timeout elapsed but
+        // before it could remove itself from the queue.
+        let mut waiters = self.queues.lock().unwrap();
+        if let Some(queue) = waiters.get_mut(&offset) {
+            if let Some(position) = queue.iter().position(|w| Arc::ptr_eq(w, &waiter)) {
+                queue.remove(position);
+                if queue.is_empty() {
+                    remove_queue(&mut waiters, offset);
+                }
+                return WAIT_TIMED_OUT;
+            }
+        }
+        WAIT_OK
+    }
+
+    /// Wakes up to `count` threads waiting on the location at `offset`, and
+    /// returns how many were woken.
+    pub(crate) fn notify(&self, offset: u64, count: u32) -> u32 {
+        let mut waiters = self.queues.lock().unwrap();
+        let queue = match waiters.get_mut(&offset) {
+            Some(queue) => queue,
+            None => return 0,
+        };
+        let mut woken = 0;
+        while woken < count {
+            let waiter = match queue.pop_front() {
+                Some(waiter) => waiter,
+                None => break,
+            };
+            *waiter.notified.lock().unwrap() = true;
+            waiter.cond.notify_one();
+            woken += 1;
+        }
+        if queue.is_empty() {
+            remove_queue(&mut waiters, offset);
+        }
+        woken
+    }
+}
+
+/// Removes the empty queue of `offset`, and releases the storage of the
+/// queues once no thread waits on the memory.
+fn remove_queue(queues: &mut HashMap<u64, VecDeque<Arc<Waiter>>>, offset: u64) {
+    queues.remove(&offset);
+    if queues.is_empty() {
+        queues.shrink_to_fit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::thread;
+
+    #[test]
+    fn wait_not_equal() {
+        let waiters = Waiters::default();
+        assert_eq!(waiters.wait(1, || false, None), WAIT_NOT_EQUAL);
+    }
+
+    #[test]
+    fn wait_timed_out() {
+        let waiters = Waiters::default();
+        assert_eq!(
+            waiters.wait(2, || true, Some(Duration::from_millis(1))),
+            WAIT_TIMED_OUT
+        );
+        assert_eq!(waiters.notify(2, 1), 0);
+        assert_eq!(waiters.queues.lock().unwrap().capacity(), 0);
+    }
+
+    #[test]
+    fn notify_wakes_waiter() {
+        let waiters = Arc::new(Waiters::default());
+        let value = Arc::new(AtomicU32::new(0));
+        let waiting = {
+            let waiters = waiters.clone();
+            let value = value.clone();
+            thread::spawn(move || waiters.wait(8, || value.load(Ordering::SeqCst) == 0, None))
+        };
+        // Keep notifying until the other thread has started waiting.
+        while waiters.notify(8, 1) == 0 {
+            thread::yield_now();
+        }
+        assert_eq!(waiting.join().unwrap(), WAIT_OK);
+        assert!(waiters.queues.lock().unwrap().is_empty());
+    }
+}
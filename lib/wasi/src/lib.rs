@@ -29,7 +29,10 @@ pub use crate::syscalls::types;
 pub use crate::utils::{get_wasi_version, is_wasi_module, WasiVersion};
 
 use thiserror::Error;
-use wasmer::{imports, Function, ImportObject, LazyInit, Memory, Module, Store, WasmerEnv};
+use wasmer::{
+    imports, Function, HostEnvInitError, ImportObject, Instance, LazyInit, Memory, Module, Store,
+    WasmerEnv,
+};
 #[cfg(all(target_os = "macos", target_arch = "aarch64",))]
 use wasmer::{FunctionType, ValType};
 
@@ -46,7 +49,7 @@ pub enum WasiError {
 }
 
 /// The environment provided to the WASI imports.
-#[derive(Debug, Clone, WasmerEnv)]
+#[derive(Debug, Clone)]
 pub struct WasiEnv {
     /// Shared state of the WASI system. Manages all the data that the
     /// executing WASI program can see.
@@ -55,18 +58,37 @@ pub struct WasiEnv {
     /// if the lock is held and the Wasm calls into a host function that tries
     /// to lock this mutex, the program will deadlock.
     pub state: Arc<Mutex<WasiState>>,
-    #[wasmer(export)]
+    /// The name of the exported memory the WASI calls operate on.
+    memory_name: String,
     memory: LazyInit<Memory>,
 }
 
+impl WasmerEnv for WasiEnv {
+    fn init_with_instance(&mut self, instance: &Instance) -> Result<(), HostEnvInitError> {
+        let memory = instance.exports.get_memory(&self.memory_name)?;
+        self.memory.initialize(memory.clone());
+        Ok(())
+    }
+}
+
 impl WasiEnv {
     pub fn new(state: WasiState) -> Self {
         Self {
             state: Arc::new(Mutex::new(state)),
+            memory_name: "memory".to_string(),
             memory: LazyInit::new(),
         }
     }
 
+    /// Set the name of the exported memory that the WASI calls should use.
+    ///
+    /// Defaults to `"memory"`. This is useful for modules that define
+    /// several memories, see the multi-memory proposal.
+    pub fn set_memory_name(&mut self, name: &str) -> &mut Self {
+        self.memory_name = name.to_string();
+        self
+    }
+
     pub fn import_object(&mut self, module: &Module) -> Result<ImportObject, WasiError> {
         let wasi_version = get_wasi_version(module, false).ok_or(WasiError::UnknownWasiVersion)?;
         Ok(generate_import_object_from_env(
@@ -92,6 +114,11 @@ impl WasiEnv {
         self.state.lock().unwrap()
     }
 
+    /// Get access to the memory, if it has been initialized.
+    pub fn memory_ref(&self) -> Option<&Memory> {
+        self.memory.get_ref()
+    }
+
     /// Get a reference to the memory
     pub fn memory(&self) -> &Memory {
         self.memory_ref()
@@ -57,10 +57,10 @@ where
     T: ValueType,
 {
     /// Creates a new MemoryView given a `pointer` and `length`.
-    pub unsafe fn new(ptr: *mut T, length: u32) -> Self {
+    pub unsafe fn new(ptr: *mut T, length: usize) -> Self {
         Self {
             ptr,
-            length: length as usize,
+            length,
             _phantom: PhantomData,
         }
     }
@@ -115,6 +115,8 @@ pub enum ExternType {
     Table(TableType),
     /// This external type is the type of a WebAssembly memory.
     Memory(MemoryType),
+    /// This external type is the type of a WebAssembly exception tag.
+    Tag(TagType),
 }
 
 fn is_global_compatible(exported: GlobalType, imported: GlobalType) -> bool {
@@ -130,13 +132,6 @@ fn is_global_compatible(exported: GlobalType, imported: GlobalType) -> bool {
     exported_ty == imported_ty && imported_mutability == exported_mutability
 }
 
-fn is_table_element_type_compatible(exported_type: Type, imported_type: Type) -> bool {
-    match exported_type {
-        Type::FuncRef => true,
-        _ => imported_type == exported_type,
-    }
-}
-
 fn is_table_compatible(exported: &TableType, imported: &TableType) -> bool {
     let TableType {
         ty: exported_ty,
@@ -149,7 +144,7 @@ fn is_table_compatible(exported: &TableType, imported: &TableType) -> bool {
         maximum: imported_maximum,
     } = imported;
 
-    is_table_element_type_compatible(*exported_ty, *imported_ty)
+    exported_ty == imported_ty
         && imported_minimum <= exported_minimum
         && (imported_maximum.is_none()
             || (!exported_maximum.is_none()
@@ -161,11 +156,13 @@ fn is_memory_compatible(exported: &MemoryType, imported: &MemoryType) -> bool {
         minimum: exported_minimum,
         maximum: exported_maximum,
         shared: exported_shared,
+        memory64: exported_memory64,
     } = exported;
     let MemoryType {
         minimum: imported_minimum,
         maximum: imported_maximum,
         shared: imported_shared,
+        memory64: imported_memory64,
     } = imported;
 
     imported_minimum <= exported_minimum
@@ -173,6 +170,7 @@ fn is_memory_compatible(exported: &MemoryType, imported: &MemoryType) -> bool {
             || (!exported_maximum.is_none()
                 && imported_maximum.unwrap() >= exported_maximum.unwrap()))
         && exported_shared == imported_shared
+        && exported_memory64 == imported_memory64
 }
 
 macro_rules! accessors {
@@ -205,6 +203,7 @@ impl ExternType {
         (Global(GlobalType) global unwrap_global)
         (Table(TableType) table unwrap_table)
         (Memory(MemoryType) memory unwrap_memory)
+        (Tag(TagType) tag unwrap_tag)
     }
     /// Check if two externs are compatible
     pub fn is_compatible_with(&self, other: &Self) -> bool {
@@ -213,6 +212,7 @@ impl ExternType {
             (Self::Global(a), Self::Global(b)) => is_global_compatible(*a, *b),
             (Self::Table(a), Self::Table(b)) => is_table_compatible(a, b),
             (Self::Memory(a), Self::Memory(b)) => is_memory_compatible(a, b),
+            (Self::Tag(a), Self::Tag(b)) => a == b,
             // The rest of possibilities, are not compatible
             _ => false,
         }
@@ -488,6 +488,8 @@ pub struct MemoryType {
     pub maximum: Option<Pages>,
     /// Whether the memory may be shared between multiple threads.
     pub shared: bool,
+    /// Whether the memory is indexed with 64-bit addresses.
+    pub memory64: bool,
 }
 
 impl MemoryType {
@@ -501,6 +503,29 @@ impl MemoryType {
             minimum: minimum.into(),
             maximum: maximum.map(Into::into),
             shared,
+            memory64: false,
+        }
+    }
+
+    /// Creates a new descriptor for a 64-bit WebAssembly memory given the
+    /// specified limits of the memory.
+    pub fn new64<IntoPages>(minimum: IntoPages, maximum: Option<IntoPages>, shared: bool) -> Self
+    where
+        IntoPages: Into<Pages>,
+    {
+        Self {
+            memory64: true,
+            ..Self::new(minimum, maximum, shared)
+        }
+    }
+
+    /// The largest number of pages the memory could ever hold, regardless
+    /// of its declared maximum.
+    pub fn max_pages(&self) -> Pages {
+        if self.memory64 {
+            Pages::max_value64()
+        } else {
+            Pages::max_value()
         }
     }
 }
@@ -508,14 +533,56 @@ impl MemoryType {
 impl fmt::Display for MemoryType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let shared = if self.shared { "shared" } else { "not shared" };
+        let index = if self.memory64 { " i64" } else { "" };
         if let Some(maximum) = self.maximum {
-            write!(f, "{} ({:?}..{:?})", shared, self.minimum, maximum)
+            write!(f, "{}{} ({:?}..{:?})", shared, index, self.minimum, maximum)
         } else {
-            write!(f, "{} ({:?}..)", shared, self.minimum)
+            write!(f, "{}{} ({:?}..)", shared, index, self.minimum)
         }
     }
 }
 
+// Tag Types
+
+/// The type of an exception tag, which is the types of the values carried by
+/// the exceptions thrown with it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "enable-serde", derive(Serialize, Deserialize))]
+pub struct TagType {
+    /// The types of the values carried by the exceptions.
+    params: Box<[Type]>,
+}
+
+impl TagType {
+    /// Creates a new tag type whose exceptions carry values of the given
+    /// types.
+    pub fn new<Params>(params: Params) -> Self
+    where
+        Params: Into<Box<[Type]>>,
+    {
+        Self {
+            params: params.into(),
+        }
+    }
+
+    /// The types of the values carried by the exceptions.
+    pub fn params(&self) -> &[Type] {
+        &self.params
+    }
+}
+
+impl fmt::Display for TagType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let params = self
+            .params
+            .iter()
+            .map(|p| format!("{:?}", p))
+            .collect::<Vec<_>>()
+            .join(", ");
+        write!(f, "[{}]", params)
+    }
+}
+
 // Import Types
 
 /// A descriptor for an imported value into a wasm module.
@@ -15,6 +15,14 @@ pub const WASM_PAGE_SIZE: usize = 0x10000;
 /// The number of pages we can have before we run out of byte index space.
 pub const WASM_MAX_PAGES: u32 = 0x10000;
 
+/// The number of pages a 64-bit memory can have.
+///
+/// The 64-bit index space is much larger than this, but no host can back
+/// more than 256 TiB of linear memory anyway. A larger declared maximum is
+/// clamped to this limit, while a larger declared minimum is rejected when
+/// the module is translated.
+pub const WASM64_MAX_PAGES: u32 = u32::MAX;
+
 /// The minimum number of pages allowed.
 pub const WASM_MIN_PAGES: u32 = 0x100;
 
@@ -32,6 +40,12 @@ impl Pages {
         Self(WASM_MAX_PAGES)
     }
 
+    /// Returns the largest value a 64-bit memory can hold.
+    #[inline(always)]
+    pub const fn max_value64() -> Self {
+        Self(WASM64_MAX_PAGES)
+    }
+
     /// Checked addition. Computes `self + rhs`,
     /// returning `None` if overflow occurred.
     pub fn checked_add(self, rhs: Self) -> Option<Self> {
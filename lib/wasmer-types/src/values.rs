@@ -31,7 +31,9 @@ pub enum Value<T> {
     ExternRef(ExternRef),
 
     /// A first-class reference to a WebAssembly function.
-    FuncRef(T),
+    ///
+    /// `None` is the null `funcref`.
+    FuncRef(Option<T>),
 
     /// A 128-bit number
     V128(u128),
@@ -120,7 +122,7 @@ impl<T> Value<T> {
         (I64(i64) i64 unwrap_i64 *e)
         (F32(f32) f32 unwrap_f32 *e)
         (F64(f64) f64 unwrap_f64 *e)
-        (FuncRef(&T) funcref unwrap_funcref e)
+        (FuncRef(&Option<T>) funcref unwrap_funcref e)
         (V128(u128) v128 unwrap_v128 *e)
     }
 
@@ -220,7 +222,7 @@ impl<T> From<ExternRef> for Value<T> {
 
 // impl<T> From<T> for Value<T> {
 //     fn from(val: T) -> Self {
-//         Self::FuncRef(val)
+//         Self::FuncRef(Some(val))
 //     }
 // }
 
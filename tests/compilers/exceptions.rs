@@ -0,0 +1,396 @@
+use crate::get_compiler;
+use anyhow::Result;
+use wasmer::*;
+#[cfg(feature = "test-jit")]
+use wasmer_engine_jit::JIT;
+#[cfg(feature = "test-native")]
+use wasmer_engine_native::Native;
+
+fn get_store() -> Store {
+    let mut features = Features::new();
+    features.exceptions(true);
+    #[cfg(feature = "test-jit")]
+    let engine = JIT::new(get_compiler(false)).features(features).engine();
+    #[cfg(feature = "test-native")]
+    let engine = Native::new(get_compiler(false)).features(features).engine();
+    Store::new(&engine)
+}
+
+#[test]
+#[cfg_attr(feature = "test-singlepass", ignore)]
+fn catch_from_callee() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (event $e (param i32))
+          (func $throw (param i32)
+            local.get 0
+            throw $e)
+          (func (export "run") (param i32) (result i32)
+            try (result i32)
+              local.get 0
+              call $throw
+              i32.const 0
+            catch $e
+              i32.const 1
+              i32.add
+            end))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<i32, i32>("run")?;
+
+    assert_eq!(run.call(41)?, 42);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(feature = "test-singlepass", ignore)]
+fn unwind_and_rethrow() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (event $e (param i64))
+          (global $unwound (mut i32) (i32.const 0))
+          (func (export "run") (result i64)
+            try (result i64)
+              try
+                try
+                  i64.const 7
+                  throw $e
+                unwind
+                  i32.const 1
+                  global.set $unwound
+                end
+              catch $e
+                rethrow 0
+              end
+              i64.const 0
+            catch $e
+            end)
+          (func (export "unwound") (result i32)
+            global.get $unwound))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<(), i64>("run")?;
+    let unwound = instance.exports.get_native_function::<(), i32>("unwound")?;
+
+    assert_eq!(run.call()?, 7);
+    assert_eq!(unwound.call()?, 1);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(feature = "test-singlepass", ignore)]
+fn uncaught_exception() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (event $e (export "e") (param i32 f64))
+          (event $other (param i32 f64))
+          (func (export "run")
+            try
+              i32.const 1
+              f64.const 2.5
+              throw $e
+            catch $other
+              drop
+              drop
+            end))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let tag = instance.exports.get_tag("e")?;
+    assert_eq!(tag.ty(), &TagType::new(vec![Type::I32, Type::F64]));
+    let run = instance.exports.get_function("run")?;
+
+    let e = run.call(&[]).err().expect("error calling function");
+    let exception = Exception::from_runtime_error(&e).expect("expected an exception");
+
+    assert!(exception.is(tag));
+    assert_eq!(exception.payload(), vec![Value::I32(1), Value::F64(2.5)]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(feature = "test-singlepass", ignore)]
+fn host_function_throws() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (import "env" "tag" (event $e (param i32)))
+          (import "env" "fail" (func $fail))
+          (func (export "run") (result i32)
+            try (result i32)
+              call $fail
+              i32.const 0
+            catch $e
+            end))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let tag = Tag::new(&store, TagType::new(vec![Type::I32]));
+    let exception = Exception::new(&tag, &[Value::I32(42)])?;
+    let fail = Function::new(&store, &FunctionType::new(vec![], vec![]), move |_| {
+        Err(exception.clone().into())
+    });
+    let instance = Instance::new(
+        &module,
+        &imports! {
+            "env" => {
+                "tag" => tag,
+                "fail" => fail,
+            }
+        },
+    )?;
+    let run = instance.exports.get_native_function::<(), i32>("run")?;
+
+    assert_eq!(run.call()?, 42);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(feature = "test-singlepass", ignore)]
+fn catch_all_and_rethrow() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (event $e (param i32))
+          (global $caught (mut i32) (i32.const 0))
+          (func (export "run") (result i32)
+            try (result i32)
+              try
+                i32.const 3
+                throw $e
+              catch_all
+                i32.const 1
+                global.set $caught
+                rethrow 0
+              end
+              i32.const 0
+            catch $e
+            end)
+          (func (export "caught") (result i32)
+            global.get $caught))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<(), i32>("run")?;
+    let caught = instance.exports.get_native_function::<(), i32>("caught")?;
+
+    assert_eq!(run.call()?, 3);
+    assert_eq!(caught.call()?, 1);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(feature = "test-singlepass", ignore)]
+fn catch_all_opcode() -> Result<()> {
+    let store = get_store();
+    // `wat` encodes `catch_all` with the opcode of `else`, so this is
+    // `(func (export "run") (result i32)
+    //    try (result i32) i32.const 5 throw $e catch_all i32.const 7 end)`
+    // with the current opcode.
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x09, 0x02, 0x60, 0x01, 0x7f, 0x00, 0x60, 0x00, 0x01, 0x7f, // type section
+        0x03, 0x02, 0x01, 0x01, // function section
+        0x0d, 0x03, 0x01, 0x00, 0x00, // event section
+        0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x00, // export section
+        0x0a, 0x0e, 0x01, 0x0c, 0x00, 0x06, 0x7f, 0x41, 0x05, 0x08, 0x00, 0x19, 0x41, 0x07, 0x0b,
+        0x0b, // code section
+    ];
+
+    let module = Module::new(&store, &wasm[..])?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<(), i32>("run")?;
+
+    assert_eq!(run.call()?, 7);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(feature = "test-singlepass", ignore)]
+fn delegate() -> Result<()> {
+    let store = get_store();
+    // `wat` can't encode `delegate` yet, so this is
+    // ```
+    // (func (export "run") (result i32)
+    //   try (result i32)
+    //     try (result i32)
+    //       try (result i32)
+    //         i32.const 5
+    //         throw $e
+    //       delegate 1
+    //     catch $e
+    //       drop
+    //       i32.const 100
+    //     end
+    //   catch $e
+    //     i32.const 1
+    //     i32.add
+    //   end)
+    // ```
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x09, 0x02, 0x60, 0x01, 0x7f, 0x00, 0x60, 0x00, 0x01, 0x7f, // type section
+        0x03, 0x02, 0x01, 0x01, // function section
+        0x0d, 0x03, 0x01, 0x00, 0x00, // event section
+        0x07, 0x07, 0x01, 0x03, 0x72, 0x75, 0x6e, 0x00, 0x00, // export section
+        0x0a, 0x1d, 0x01, 0x1b, 0x00, 0x06, 0x7f, 0x06, 0x7f, 0x06, 0x7f, 0x41, 0x05, 0x08, 0x00,
+        0x18, 0x01, 0x07, 0x00, 0x1a, 0x41, 0xe4, 0x00, 0x0b, 0x07, 0x00, 0x41, 0x01, 0x6a, 0x0b,
+        0x0b, // code section
+    ];
+
+    let module = Module::new(&store, &wasm[..])?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<(), i32>("run")?;
+
+    assert_eq!(run.call()?, 6);
+
+    Ok(())
+}
+
+#[test]
+fn delegate_outside_of_try_is_rejected() -> Result<()> {
+    let store = get_store();
+    // `(func block delegate 0 end)`
+    let wasm = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // header
+        0x01, 0x04, 0x01, 0x60, 0x00, 0x00, // type section
+        0x03, 0x02, 0x01, 0x00, // function section
+        0x0a, 0x08, 0x01, 0x06, 0x00, 0x02, 0x40, 0x18, 0x00, 0x0b, // code section
+    ];
+
+    match Module::new(&store, &wasm[..]) {
+        Err(CompileError::Validate(message)) => {
+            assert!(message.contains("delegate"), "{}", message)
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(feature = "test-singlepass", ignore)]
+fn branch_out_of_clauses() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (event $e (export "e") (param i32))
+          (func $throw (param i32)
+            local.get 0
+            throw $e)
+          (func (export "unwind") (result i32)
+            (local $i i32)
+            block $done (result i32)
+              loop $again
+                block $out
+                  try
+                    local.get $i
+                    call $throw
+                  unwind
+                    local.get $i
+                    i32.const 1
+                    i32.add
+                    local.tee $i
+                    i32.const 1000
+                    i32.lt_u
+                    br_if $out
+                    local.get $i
+                    br $done
+                  end
+                end
+                br $again
+              end
+              unreachable
+            end)
+          (func (export "catch_all") (param i32) (result i32)
+            block $zero
+              block $one
+                try
+                  i32.const 0
+                  call $throw
+                catch_all
+                  local.get 0
+                  i32.const 5
+                  i32.eq
+                  if
+                    i32.const 5
+                    return
+                  end
+                  local.get 0
+                  br_table $one $zero
+                end
+              end
+              i32.const 1
+              return
+            end
+            i32.const 0)
+          (func (export "throw_in_unwind")
+            try
+              i32.const 1
+              call $throw
+            unwind
+              i32.const 2
+              call $throw
+            end))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let unwind = instance.exports.get_native_function::<(), i32>("unwind")?;
+    let catch_all = instance
+        .exports
+        .get_native_function::<i32, i32>("catch_all")?;
+    let tag = instance.exports.get_tag("e")?;
+    let throw_in_unwind = instance.exports.get_function("throw_in_unwind")?;
+
+    assert_eq!(unwind.call()?, 1000);
+    assert_eq!(catch_all.call(0)?, 1);
+    assert_eq!(catch_all.call(1)?, 0);
+    assert_eq!(catch_all.call(5)?, 5);
+
+    let e = throw_in_unwind
+        .call(&[])
+        .err()
+        .expect("error calling function");
+    let exception = Exception::from_runtime_error(&e).expect("expected an exception");
+    assert!(exception.is(tag));
+    assert_eq!(exception.payload(), vec![Value::I32(2)]);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "test-singlepass")]
+fn singlepass_rejects_exceptions() -> Result<()> {
+    let store = get_store();
+
+    // The feature being enabled is not enough to reject a module.
+    Module::new(&store, "(module (func (export \"run\")))")?;
+
+    for wat in &[
+        "(module (event (param i32)))",
+        "(module (func try catch_all end))",
+    ] {
+        match Module::new(&store, wat) {
+            Err(CompileError::UnsupportedFeature(feature)) => assert_eq!(feature, "exceptions"),
+            result => panic!("unexpected result: {:?}", result.map(|_| ())),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,173 @@
+#![cfg(feature = "test-jit")]
+
+use crate::get_compiler;
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use wasmer::wasmparser::Operator;
+use wasmer::*;
+use wasmer_cache::FunctionCache;
+use wasmer_compiler::CompilerConfig;
+use wasmer_engine_jit::JIT;
+use wasmer_middlewares::Metering;
+
+const TWO_FUNCTIONS: &str = r#"
+    (module
+      (func (export "one") (result i32)
+        i32.const 1)
+      (func (export "two") (result i32)
+        i32.const 2))
+"#;
+
+fn cached_files(path: &Path) -> BTreeSet<PathBuf> {
+    fs::read_dir(path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect()
+}
+
+fn call(config: impl CompilerConfig + 'static, wat: &str, name: &str) -> Result<i32> {
+    let store = Store::new(&JIT::new(config).engine());
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let function = instance.exports.get_native_function::<(), i32>(name)?;
+    Ok(function.call()?)
+}
+
+fn call_cached(path: &Path, wat: &str, name: &str) -> Result<i32> {
+    call(FunctionCache::new(get_compiler(false), path)?, wat, name)
+}
+
+#[test]
+fn caches_each_function() -> Result<()> {
+    let directory = tempfile::tempdir()?;
+    let path = directory.path();
+
+    assert_eq!(call_cached(path, TWO_FUNCTIONS, "two")?, 2);
+    let files = cached_files(path);
+    assert_eq!(files.len(), 2);
+
+    // Compiling the module again compiles nothing.
+    assert_eq!(call_cached(path, TWO_FUNCTIONS, "one")?, 1);
+    assert_eq!(cached_files(path), files);
+
+    // Editing a function only compiles that function.
+    let edited = TWO_FUNCTIONS.replace("i32.const 2", "i32.const 3");
+    assert_eq!(call_cached(path, &edited, "two")?, 3);
+    assert_eq!(cached_files(path).len(), 3);
+    Ok(())
+}
+
+#[test]
+fn reuses_cached_functions() -> Result<()> {
+    let directory = tempfile::tempdir()?;
+    let path = directory.path();
+    let forty_two = r#"(module (func (export "f") (result i32) i32.const 42))"#;
+    let seven = r#"(module (func (export "f") (result i32) i32.const 7))"#;
+
+    assert_eq!(call_cached(path, forty_two, "f")?, 42);
+    let forty_two_files = cached_files(path);
+    assert_eq!(call_cached(path, seven, "f")?, 7);
+    let seven_file = cached_files(path)
+        .difference(&forty_two_files)
+        .next()
+        .cloned()
+        .unwrap();
+
+    // The cached function is used instead of compiling the body.
+    let forty_two_file = forty_two_files.iter().next().unwrap();
+    fs::copy(&seven_file, forty_two_file)?;
+    assert_eq!(call_cached(path, forty_two, "f")?, 7);
+    Ok(())
+}
+
+#[test]
+fn reuses_functions_at_other_indices() -> Result<()> {
+    let directory = tempfile::tempdir()?;
+    let path = directory.path();
+
+    assert_eq!(call_cached(path, TWO_FUNCTIONS, "two")?, 2);
+    let files = cached_files(path);
+    let reordered = r#"
+        (module
+          (func (export "two") (result i32)
+            i32.const 2)
+          (func (export "three") (result i32)
+            i32.const 3))
+    "#;
+    assert_eq!(call_cached(path, reordered, "two")?, 2);
+    assert_eq!(call_cached(path, reordered, "three")?, 3);
+    assert_eq!(cached_files(path).len(), files.len() + 1);
+    Ok(())
+}
+
+#[test]
+fn invalidates_functions_of_other_configurations() -> Result<()> {
+    fn cost_always_one(_: &Operator) -> u64 {
+        1
+    }
+    let directory = tempfile::tempdir()?;
+    let path = directory.path();
+
+    assert_eq!(call_cached(path, TWO_FUNCTIONS, "two")?, 2);
+    let mut files = cached_files(path).len();
+    for limit in [10, 20].iter() {
+        let mut config = get_compiler(false);
+        config.push_middleware(Arc::new(Metering::new(*limit, cost_always_one)));
+        let config = FunctionCache::new(config, path)?;
+        assert_eq!(call(config, TWO_FUNCTIONS, "two")?, 2);
+        assert_eq!(cached_files(path).len(), files + 2);
+        files += 2;
+    }
+    Ok(())
+}
+
+fn trap_trace(config: impl CompilerConfig + 'static, wat: &str) -> Result<Vec<(u32, usize)>> {
+    let store = Store::new(&JIT::new(config).engine());
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_function("run")?;
+    let error = run.call(&[]).err().expect("error calling function");
+    Ok(error
+        .trace()
+        .iter()
+        .map(|frame| (frame.func_index(), frame.module_offset()))
+        .collect())
+}
+
+#[test]
+#[cfg_attr(
+    any(
+        feature = "test-singlepass",
+        target_arch = "aarch64",
+        target_env = "musl",
+    ),
+    ignore
+)]
+fn traces_traps_in_cached_functions() -> Result<()> {
+    let directory = tempfile::tempdir()?;
+    let path = directory.path();
+    let wat = r#"
+        (module
+          (func (export "run") (call $trap))
+          (func $trap (unreachable)))
+    "#;
+    trap_trace(FunctionCache::new(get_compiler(false), path)?, wat)?;
+
+    // `$trap` is moved after another function, and `run` calls it at its
+    // new index.
+    let moved = r#"
+        (module
+          (func (export "other") nop)
+          (func (export "run") (call $trap))
+          (func $trap (unreachable)))
+    "#;
+    let files = cached_files(path).len();
+    let trace = trap_trace(FunctionCache::new(get_compiler(false), path)?, moved)?;
+    assert_eq!(cached_files(path).len(), files + 2);
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace, trap_trace(get_compiler(false), moved)?);
+    Ok(())
+}
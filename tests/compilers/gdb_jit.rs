@@ -0,0 +1,75 @@
+#![cfg(feature = "gdb-jit")]
+
+use crate::utils::{get_store, with_dwarf};
+use anyhow::Result;
+use gimli::{EndianSlice, LittleEndian};
+use object::read::elf::ElfFile64;
+use object::{Object, ObjectSection, ObjectSymbol};
+use wasmer::*;
+use wasmer_engine_jit::JITArtifact;
+
+#[test]
+#[cfg_attr(any(target_arch = "aarch64", target_env = "musl"), ignore)]
+fn debug_image_describes_locals() -> Result<()> {
+    let store = get_store(false);
+    let wat = br#"
+        (module
+            (func $run (export "run") (param i32 i32) (result i32) (local i64)
+                local.get 0
+                local.get 1
+                i32.add)
+        )
+    "#;
+    let module = Module::new(&store, with_dwarf(&wat2wasm(wat)?))?;
+    let artifact = module
+        .artifact()
+        .downcast_ref::<JITArtifact>()
+        .expect("expected a JIT artifact");
+    let image = artifact.debug_image().expect("expected a debug image");
+
+    let file = ElfFile64::<object::LittleEndian>::parse(image)?;
+    let symbol = file
+        .symbols()
+        .find(|symbol| symbol.name() == Ok("run"))
+        .expect("expected a symbol for run");
+    let text = file.section_by_name(".text").unwrap();
+    assert!(text.address() <= symbol.address());
+    assert!(symbol.address() + symbol.size() <= text.address() + text.size());
+
+    let dwarf = gimli::Dwarf::load(
+        |id| -> Result<_, gimli::Error> {
+            let data = file
+                .section_by_name(id.name())
+                .map(|section| section.data().unwrap())
+                .unwrap_or(&[]);
+            Ok(EndianSlice::new(data, LittleEndian))
+        },
+        |_| Ok(EndianSlice::new(&[][..], LittleEndian)),
+    )?;
+    let unit = dwarf.unit(dwarf.units().next()?.unwrap())?;
+    let mut entries = unit.entries();
+    let mut locals = Vec::new();
+    while let Some((_, entry)) = entries.next_dfs()? {
+        if entry.tag() != gimli::DW_TAG_formal_parameter && entry.tag() != gimli::DW_TAG_variable {
+            continue;
+        }
+        let name = entry.attr_value(gimli::DW_AT_name)?.unwrap();
+        let name = dwarf.attr_string(&unit, name)?.to_string()?.to_string();
+        let location = entry.attr_value(gimli::DW_AT_location)?;
+        locals.push((entry.tag(), name, location.is_some()));
+    }
+    assert_eq!(locals.len(), 3);
+    // The parameters are located, and named by the Wasm DWARF if it
+    // describes them.
+    assert_eq!(
+        locals[0],
+        (gimli::DW_TAG_formal_parameter, "x".to_string(), true)
+    );
+    assert_eq!(
+        locals[1],
+        (gimli::DW_TAG_formal_parameter, "var1".to_string(), true)
+    );
+    assert_eq!(locals[2].0, gimli::DW_TAG_variable);
+    assert_eq!(locals[2].1, "var2");
+    Ok(())
+}
@@ -2,6 +2,7 @@
 //! This tests checks that the provided functions (both native and
 //! dynamic ones) work properly.
 
+use crate::get_compiler;
 use crate::utils::get_store;
 use anyhow::Result;
 use std::convert::Infallible;
@@ -390,3 +391,63 @@ fn multi_use_host_fn_manages_memory_correctly() -> Result<()> {
     drop(instance2);
     Ok(())
 }
+
+#[test]
+fn dynamic_function_with_v128() -> Result<()> {
+    let mut features = Features::new();
+    features.simd(true).multi_value(true);
+    #[cfg(feature = "test-jit")]
+    let engine = wasmer_engine_jit::JIT::new(get_compiler(false))
+        .features(features)
+        .engine();
+    #[cfg(feature = "test-native")]
+    let engine = wasmer_engine_native::Native::new(get_compiler(false))
+        .features(features)
+        .engine();
+    let store = Store::new(&engine);
+    // The last two `v128` parameters don't fit in registers.
+    let wat = r#"
+        (import "host" "fn" (func $host
+          (param i32 v128 f64 v128 v128 v128 v128 v128 v128 v128 v128)
+          (result v128 i32 v128)))
+        (func (export "main") (result i64)
+          (local $a v128)
+          (local $b i32)
+          (call $host
+            (i32.const 7) (v128.const i64x2 1 100) (f64.const 2.5)
+            (v128.const i64x2 3 300) (v128.const i64x2 4 400) (v128.const i64x2 5 500)
+            (v128.const i64x2 6 600) (v128.const i64x2 7 700) (v128.const i64x2 8 800)
+            (v128.const i64x2 9 900) (v128.const i64x2 10 1000))
+          (local.set $a)
+          (local.set $b)
+          (i64x2.extract_lane 0)
+          (i64.add (i64.mul (i64x2.extract_lane 0 (local.get $a)) (i64.const 10)))
+          (i64.add (i64.mul (i64.extend_i32_u (local.get $b)) (i64.const 100))))
+    "#;
+    let module = Module::new(&store, wat)?;
+
+    let v128 = |low: u64, high: u64| Value::V128((high as u128) << 64 | low as u128);
+    let mut params = vec![ValType::I32, ValType::V128, ValType::F64];
+    params.extend(vec![ValType::V128; 8]);
+    let ty = FunctionType::new(params, vec![ValType::V128, ValType::I32, ValType::V128]);
+    let instance = Instance::new(
+        &module,
+        &imports! {
+            "host" => {
+                "fn" => Function::new(&store, ty, move |values| {
+                    assert_eq!(values[0], Value::I32(7));
+                    assert_eq!(values[1], v128(1, 100));
+                    assert_eq!(values[2], Value::F64(2.5));
+                    for (i, value) in values[3..].iter().enumerate() {
+                        let k = i as u64 + 3;
+                        assert_eq!(*value, v128(k, k * 100));
+                    }
+                    Ok(vec![v128(1, 2), Value::I32(3), v128(4, 5)])
+                }),
+            },
+        },
+    )?;
+    let main: NativeFunc<(), i64> = instance.exports.get_native_function("main")?;
+    assert_eq!(main.call()?, 341);
+    Ok(())
+}
@@ -0,0 +1,206 @@
+#![cfg(feature = "test-jit")]
+
+use crate::get_compiler;
+use crate::utils::with_dwarf;
+use anyhow::Result;
+use std::sync::Arc;
+use wasmer::wasmparser::Operator;
+use wasmer::*;
+use wasmer_engine_jit::{JITLazyArtifact, JIT};
+use wasmer_types::entity::EntityRef;
+
+const WAT: &str = r#"
+    (module
+      (func $add (export "add") (param i32 i32) (result i32)
+        local.get 0
+        local.get 1
+        i32.add)
+      (func (export "add_twice") (param i32 i32) (result i32)
+        local.get 0
+        local.get 1
+        call $add
+        local.get 1
+        call $add)
+      (func (export "mul") (param i32 i32) (result i32)
+        local.get 0
+        local.get 1
+        i32.mul))
+"#;
+
+fn lazy_artifact(module: &Module) -> &JITLazyArtifact {
+    module
+        .artifact()
+        .downcast_ref::<JITLazyArtifact>()
+        .expect("the module should be lazy")
+}
+
+#[test]
+fn compiles_functions_on_first_call() -> Result<()> {
+    let engine = JIT::new(get_compiler(false)).lazy(true).engine();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, WAT)?;
+    let artifact = lazy_artifact(&module);
+    let compiled = || {
+        (0..3)
+            .map(|index| artifact.is_compiled(LocalFunctionIndex::new(index)))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(compiled(), [false, false, false]);
+
+    let instance = Instance::new(&module, &imports! {})?;
+    assert_eq!(compiled(), [false, false, false]);
+
+    let add_twice = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("add_twice")?;
+    assert_eq!(add_twice.call(1, 2)?, 5);
+    assert_eq!(compiled(), [true, true, false]);
+
+    // Instances created before or after share the compiled code.
+    let other = Instance::new(&module, &imports! {})?;
+    let mul = other
+        .exports
+        .get_native_function::<(i32, i32), i32>("mul")?;
+    assert_eq!(mul.call(6, 7)?, 42);
+    assert_eq!(compiled(), [true, true, true]);
+    let add = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("add")?;
+    assert_eq!(add.call(40, 2)?, 42);
+    Ok(())
+}
+
+#[test]
+fn serializes_partially_compiled_modules() -> Result<()> {
+    let engine = JIT::new(get_compiler(false)).lazy(true).engine();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let add = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("add")?;
+    assert_eq!(add.call(1, 2)?, 3);
+
+    let serialized = module.serialize()?;
+    let deserialized = unsafe { Module::deserialize(&store, &serialized)? };
+    let instance = Instance::new(&deserialized, &imports! {})?;
+    let add_twice = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("add_twice")?;
+    assert_eq!(add_twice.call(1, 2)?, 5);
+    let mul = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("mul")?;
+    assert_eq!(mul.call(6, 7)?, 42);
+    Ok(())
+}
+
+/// Rejects the functions multiplying.
+#[derive(Debug)]
+struct RejectMul;
+
+impl ModuleMiddleware for RejectMul {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        Box::new(RejectMul)
+    }
+}
+
+impl FunctionMiddleware for RejectMul {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if let Operator::I32Mul = operator {
+            return Err(MiddlewareError::new("RejectMul", "i32.mul is not allowed"));
+        }
+        state.push_operator(operator);
+        Ok(())
+    }
+}
+
+#[test]
+fn traps_when_a_function_fails_to_compile() -> Result<()> {
+    let mut compiler = get_compiler(false);
+    compiler.push_middleware(Arc::new(RejectMul));
+    let engine = JIT::new(compiler).lazy(true).engine();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &imports! {})?;
+
+    let mul = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("mul")?;
+    let error = mul.call(6, 7).unwrap_err();
+    let error = error
+        .downcast::<CompileError>()
+        .expect("the trap should be a compilation error");
+    assert!(error.to_string().contains("i32.mul is not allowed"));
+    assert!(!lazy_artifact(&module).is_compiled(LocalFunctionIndex::new(2)));
+
+    let add = instance
+        .exports
+        .get_native_function::<(i32, i32), i32>("add")?;
+    assert_eq!(add.call(40, 2)?, 42);
+    Ok(())
+}
+
+#[test]
+fn rejects_tiered_compilation() -> Result<()> {
+    let engine = JIT::new(get_compiler(false))
+        .tier_up(get_compiler(false))
+        .lazy(true)
+        .engine();
+    let store = Store::new(&engine);
+    let error = Module::new(&store, WAT).unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("lazy compilation can't be combined with tiered compilation"));
+    Ok(())
+}
+
+#[test]
+fn traps_in_lazily_compiled_functions() -> Result<()> {
+    let engine = JIT::new(get_compiler(false)).lazy(true).engine();
+    let store = Store::new(&engine);
+    let wat = r#"
+        (module $lazy
+          (func $crash (export "crash")
+            unreachable))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let crash = instance.exports.get_function("crash")?;
+    let error = crash.call(&[]).unwrap_err();
+    let trace = error.trace();
+    assert_eq!(trace.len(), 1);
+    assert_eq!(trace[0].module_name(), "lazy");
+    assert_eq!(trace[0].function_name(), Some("crash"));
+    Ok(())
+}
+
+#[test]
+fn traces_source_frames_of_lazily_compiled_functions() -> Result<()> {
+    let engine = JIT::new(get_compiler(false)).lazy(true).engine();
+    let store = Store::new(&engine);
+    let wat = br#"
+        (module $lazy
+          (func $crash (export "crash")
+            i32.const 1
+            drop
+            unreachable))
+    "#;
+    let module = Module::new(&store, with_dwarf(&wat2wasm(wat)?))?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let crash = instance.exports.get_function("crash")?;
+    let error = crash.call(&[]).unwrap_err();
+    let trace = error.trace();
+    assert_eq!(trace.len(), 1);
+    let source_frames = trace[0].source_frames();
+    assert_eq!(source_frames.len(), 2);
+    assert_eq!(source_frames[0].function_name(), Some("helper"));
+    assert_eq!(source_frames[0].line(), Some(8));
+    assert_eq!(source_frames[1].function_name(), Some("run"));
+    assert_eq!(source_frames[1].line(), Some(3));
+    Ok(())
+}
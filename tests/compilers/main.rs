@@ -4,12 +4,25 @@
 //! implementation, such as: singlepass, cranelift or llvm depending
 //! on what's available on the target.
 
+mod exceptions;
+mod function_cache;
+mod gdb_jit;
 mod imports;
+mod lazy;
+mod memory64;
 mod metering;
 mod middlewares;
+mod module_linking;
+mod multi_memory;
+mod multi_value;
 mod multi_value_imports;
 mod native_functions;
+mod profiler;
+mod profiling;
 mod serialize;
+mod tail_call;
+mod threads;
+mod tiered;
 mod traps;
 mod utils;
 mod wasi;
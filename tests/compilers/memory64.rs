@@ -0,0 +1,197 @@
+use crate::get_compiler;
+use anyhow::Result;
+use wasmer::*;
+#[cfg(feature = "test-jit")]
+use wasmer_engine_jit::JIT;
+#[cfg(feature = "test-native")]
+use wasmer_engine_native::Native;
+
+fn get_store() -> Store {
+    let mut features = Features::new();
+    features.memory64(true);
+    features.multi_memory(true);
+    #[cfg(feature = "test-jit")]
+    let engine = JIT::new(get_compiler(false)).features(features).engine();
+    #[cfg(feature = "test-native")]
+    let engine = Native::new(get_compiler(false)).features(features).engine();
+    Store::new(&engine)
+}
+
+#[test]
+fn grow_and_access() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (memory (export "memory") i64 1)
+          (func (export "grow") (param i64) (result i64)
+            local.get 0
+            memory.grow)
+          (func (export "size") (result i64)
+            memory.size)
+          (func (export "store") (param i64 i32)
+            local.get 0
+            local.get 1
+            i32.store offset=4)
+          (func (export "load") (param i64) (result i32)
+            local.get 0
+            i32.load offset=4)
+          (func (export "load-far") (param i64) (result i32)
+            local.get 0
+            i32.load offset=0x80000000))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let memory = instance.exports.get_memory("memory")?;
+    assert!(memory.ty().memory64);
+    let grow = instance.exports.get_native_function::<i64, i64>("grow")?;
+    let size = instance.exports.get_native_function::<(), i64>("size")?;
+    let store = instance
+        .exports
+        .get_native_function::<(i64, i32), ()>("store")?;
+    let load = instance.exports.get_native_function::<i64, i32>("load")?;
+    let load_far = instance
+        .exports
+        .get_native_function::<i64, i32>("load-far")?;
+
+    assert_eq!(grow.call(2)?, 1);
+    assert_eq!(size.call()?, 3);
+    assert_eq!(grow.call(1 << 40)?, -1);
+
+    let last = 3 * WASM_PAGE_SIZE as i64 - 8;
+    store.call(last, 42)?;
+    assert_eq!(load.call(last)?, 42);
+    let ptr: WasmPtr64<i32> = WasmPtr64::new(last as u64 + 4);
+    assert_eq!(ptr.deref(memory).unwrap().get(), 42);
+
+    assert!(load.call(last + 1).is_err());
+    // The effective address wraps around.
+    assert!(load.call(-4).is_err());
+    assert!(store.call(-1, 0).is_err());
+    // Offsets are unsigned.
+    assert!(load_far.call(0).is_err());
+    assert!(load_far.call(-0x8000_0000).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn import_memory64() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (import "env" "memory" (memory i64 1))
+          (func (export "load") (param i64) (result i64)
+            local.get 0
+            i64.load))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+
+    let memory32 = Memory::new(&store, MemoryType::new(1, None, false))?;
+    let result = Instance::new(
+        &module,
+        &imports! {
+            "env" => {
+                "memory" => memory32,
+            }
+        },
+    );
+    assert!(result.is_err());
+
+    let memory = Memory::new(&store, MemoryType::new64(1, None, false))?;
+    let ptr: WasmPtr64<u64> = WasmPtr64::new(16);
+    ptr.deref(&memory).unwrap().set(0x1234_5678_9abc);
+    let instance = Instance::new(
+        &module,
+        &imports! {
+            "env" => {
+                "memory" => memory,
+            }
+        },
+    )?;
+    let load = instance.exports.get_native_function::<i64, i64>("load")?;
+    assert_eq!(load.call(16)?, 0x1234_5678_9abc);
+
+    Ok(())
+}
+
+#[test]
+fn bulk_memory() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (memory $m64 (export "memory") i64 1)
+          (memory $m32 (export "memory32") 1)
+          (data $d "hello")
+          (func (export "fill") (param i64 i32 i64)
+            (memory.fill $m64 (local.get 0) (local.get 1) (local.get 2)))
+          (func (export "copy") (param i64 i64 i64)
+            (memory.copy $m64 $m64 (local.get 0) (local.get 1) (local.get 2)))
+          (func (export "init") (param i64 i32 i32)
+            (memory.init $d (local.get 0) (local.get 1) (local.get 2)))
+          (func (export "copy-to-32") (param i32 i64 i32)
+            (memory.copy $m32 $m64 (local.get 0) (local.get 1) (local.get 2))))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let memory = instance.exports.get_memory("memory")?;
+    let fill = instance
+        .exports
+        .get_native_function::<(i64, i32, i64), ()>("fill")?;
+    let copy = instance
+        .exports
+        .get_native_function::<(i64, i64, i64), ()>("copy")?;
+    let init = instance
+        .exports
+        .get_native_function::<(i64, i32, i32), ()>("init")?;
+    let copy_to_32 = instance
+        .exports
+        .get_native_function::<(i32, i64, i32), ()>("copy-to-32")?;
+    let memory32 = instance.exports.get_memory("memory32")?;
+    let byte = |offset: u64| WasmPtr64::<u8>::new(offset).deref(memory).unwrap().get();
+
+    let end = WASM_PAGE_SIZE as i64;
+    fill.call(end - 4, 7, 4)?;
+    assert_eq!(byte(end as u64 - 1), 7);
+    copy.call(0, end - 4, 4)?;
+    assert_eq!(byte(3), 7);
+    init.call(8, 1, 4)?;
+    assert_eq!(byte(8), b'e');
+    assert_eq!(byte(11), b'o');
+    copy_to_32.call(16, 8, 2)?;
+    let byte_32 = |offset: u32| WasmPtr::<u8>::new(offset).deref(memory32).unwrap().get();
+    assert_eq!(byte_32(16), b'e');
+    assert_eq!(byte_32(17), b'l');
+
+    assert!(fill.call(end - 4, 0, 5).is_err());
+    assert!(fill.call(-1, 0, 2).is_err());
+    assert!(copy.call(0, end - 4, 5).is_err());
+    assert!(copy.call(0, 1 << 40, 1).is_err());
+    assert!(init.call(end - 2, 0, 4).is_err());
+    assert!(copy_to_32.call(0, -1, 1).is_err());
+    assert_eq!(byte(end as u64 - 1), 7);
+
+    Ok(())
+}
+
+#[test]
+fn limits() -> Result<()> {
+    let store = get_store();
+
+    // A maximum beyond what the pages can count is clamped.
+    let module = Module::new(
+        &store,
+        "(module (memory (export \"memory\") i64 1 0x1_0000_0000_0))",
+    )?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let memory = instance.exports.get_memory("memory")?;
+    assert_eq!(memory.ty().maximum, Some(Pages::max_value64()));
+
+    // A minimum beyond it can never be allocated.
+    let error = Module::new(&store, "(module (memory i64 0x1_0000_0000_0))").unwrap_err();
+    assert!(error.to_string().contains("the limit is"), "{}", error);
+
+    Ok(())
+}
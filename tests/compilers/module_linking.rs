@@ -0,0 +1,204 @@
+use crate::get_compiler;
+use crate::utils::get_headless_store;
+use anyhow::Result;
+use wasmer::*;
+#[cfg(feature = "test-jit")]
+use wasmer_engine_jit::JIT;
+#[cfg(feature = "test-native")]
+use wasmer_engine_native::Native;
+
+fn get_store() -> Store {
+    let mut features = Features::new();
+    features.module_linking(true);
+    #[cfg(feature = "test-jit")]
+    let engine = JIT::new(get_compiler(false)).features(features).engine();
+    #[cfg(feature = "test-native")]
+    let engine = Native::new(get_compiler(false)).features(features).engine();
+    Store::new(&engine)
+}
+
+#[test]
+fn instance_import_from_namespace() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (import "math" (instance $math
+            (export "add" (func (param i32 i32) (result i32)))
+            (export "offset" (global i32))))
+          (func (export "add_offset") (param i32) (result i32)
+            local.get 0
+            global.get (global $math "offset")
+            call (func $math "add")))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let mut imports = module
+        .imports()
+        .map(|import| (import.module().to_string(), import.name().to_string()))
+        .collect::<Vec<_>>();
+    imports.sort();
+    assert_eq!(
+        imports,
+        vec![
+            ("math".to_string(), "add".to_string()),
+            ("math".to_string(), "offset".to_string()),
+        ]
+    );
+
+    fn add(a: i32, b: i32) -> i32 {
+        a + b
+    }
+    let import_object = imports! {
+        "math" => {
+            "add" => Function::new_native(&store, add),
+            "offset" => Global::new(&store, Value::I32(10)),
+        },
+    };
+    let instance = Instance::new(&module, &import_object)?;
+    let add_offset = instance
+        .exports
+        .get_native_function::<i32, i32>("add_offset")?;
+    assert_eq!(add_offset.call(32)?, 42);
+
+    let incomplete = imports! {
+        "math" => {
+            "add" => Function::new_native(&store, add),
+        },
+    };
+    assert!(Instance::new(&module, &incomplete).is_err());
+    Ok(())
+}
+
+#[test]
+fn nested_module_instantiation() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (import "host" (instance $host
+            (export "double" (func (param i32) (result i32)))))
+          (import "env" "base" (global $base i32))
+          (module $inner
+            (import "host" (instance $host
+              (export "double" (func (param i32) (result i32)))))
+            (import "base" (global $base i32))
+            (func (export "compute") (param i32) (result i32)
+              local.get 0
+              call (func $host "double")
+              global.get $base
+              i32.add))
+          (instance $i (instantiate $inner
+            "host" (instance $host)
+            "base" (global $base)))
+          (func (export "run") (param i32) (result i32)
+            local.get 0
+            call (func $i "compute")))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    // The exports of created instances are not imports of the module, nor
+    // are the exports of imported instances it only gives to them.
+    let imports = module
+        .imports()
+        .map(|import| (import.module().to_string(), import.name().to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(imports, vec![("env".to_string(), "base".to_string())]);
+
+    fn double(a: i32) -> i32 {
+        a * 2
+    }
+    let import_object = imports! {
+        "host" => {
+            "double" => Function::new_native(&store, double),
+        },
+        "env" => {
+            "base" => Global::new(&store, Value::I32(1)),
+        },
+    };
+    let instance = Instance::new(&module, &import_object)?;
+    let run = instance.exports.get_native_function::<i32, i32>("run")?;
+    assert_eq!(run.call(5)?, 11);
+    Ok(())
+}
+
+#[test]
+fn nested_instances_given_to_each_other() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (module $counter
+            (global $count (mut i32) (i32.const 0))
+            (func (export "next") (result i32)
+              global.get $count
+              i32.const 1
+              i32.add
+              global.set $count
+              global.get $count))
+          (module $user
+            (import "next" (func $next (result i32)))
+            (func (export "twice") (result i32)
+              call $next
+              drop
+              call $next))
+          (instance $c (instantiate $counter))
+          (instance $u (instantiate $user "next" (func $c "next")))
+          (func (export "run") (result i32)
+            call (func $u "twice")
+            call (func $c "next")
+            i32.add))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    assert_eq!(module.imports().count(), 0);
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<(), i32>("run")?;
+    // Both nested modules share the one instance of the counter.
+    assert_eq!(run.call()?, 2 + 3);
+    Ok(())
+}
+
+#[test]
+fn nested_instantiation_error() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (module $failing
+            (func $start unreachable)
+            (start $start)
+            (func (export "f")))
+          (instance $i (instantiate $failing))
+          (func (export "run")
+            call (func $i "f")))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    match Instance::new(&module, &imports! {}) {
+        Err(InstantiationError::Start(error)) => {
+            assert!(error.message().contains("unreachable"))
+        }
+        other => panic!("unexpected result: {:?}", other.map(|_| ())),
+    }
+    Ok(())
+}
+
+#[test]
+fn nested_modules_are_deserialized_without_compiling() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (module $inner
+            (func (export "answer") (result i32)
+              i32.const 42))
+          (instance $i (instantiate $inner))
+          (func (export "run") (result i32)
+            call (func $i "answer")))
+    "#;
+    let serialized = Module::new(&store, wat)?.serialize()?;
+
+    // A headless engine can't compile the nested module.
+    let headless_store = get_headless_store();
+    let module = unsafe { Module::deserialize(&headless_store, &serialized)? };
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<(), i32>("run")?;
+    assert_eq!(run.call()?, 42);
+    Ok(())
+}
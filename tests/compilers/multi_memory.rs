@@ -0,0 +1,121 @@
+use crate::get_compiler;
+use anyhow::Result;
+use wasmer::*;
+#[cfg(feature = "test-jit")]
+use wasmer_engine_jit::JIT;
+#[cfg(feature = "test-native")]
+use wasmer_engine_native::Native;
+
+fn get_store() -> Store {
+    let mut features = Features::new();
+    features.multi_memory(true);
+    #[cfg(feature = "test-jit")]
+    let engine = JIT::new(get_compiler(false)).features(features).engine();
+    #[cfg(feature = "test-native")]
+    let engine = Native::new(get_compiler(false)).features(features).engine();
+    Store::new(&engine)
+}
+
+#[test]
+fn access_second_memory() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (memory (export "mem0") 1)
+          (memory (export "mem1") 1 3)
+          (func (export "store1") (param i32 i32)
+            local.get 0
+            local.get 1
+            i32.store (memory 1))
+          (func (export "load0") (param i32) (result i32)
+            local.get 0
+            i32.load (memory 0))
+          (func (export "load1") (param i32) (result i32)
+            local.get 0
+            i32.load (memory 1))
+          (func (export "grow1") (param i32) (result i32)
+            local.get 0
+            memory.grow (memory 1))
+          (func (export "size1") (result i32)
+            memory.size (memory 1)))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let mem1 = instance.exports.get_memory("mem1")?;
+    let store1 = instance
+        .exports
+        .get_native_function::<(i32, i32), ()>("store1")?;
+    let load0 = instance.exports.get_native_function::<i32, i32>("load0")?;
+    let load1 = instance.exports.get_native_function::<i32, i32>("load1")?;
+    let grow1 = instance.exports.get_native_function::<i32, i32>("grow1")?;
+    let size1 = instance.exports.get_native_function::<(), i32>("size1")?;
+
+    store1.call(16, 42)?;
+    assert_eq!(load1.call(16)?, 42);
+    assert_eq!(load0.call(16)?, 0);
+    assert_eq!(WasmPtr::<i32>::new(16).deref(mem1).unwrap().get(), 42);
+
+    assert_eq!(size1.call()?, 1);
+    assert_eq!(grow1.call(2)?, 1);
+    assert_eq!(grow1.call(1)?, -1);
+    assert_eq!(size1.call()?, 3);
+
+    let last = 3 * WASM_PAGE_SIZE as i32 - 4;
+    store1.call(last, 7)?;
+    assert_eq!(load1.call(last)?, 7);
+    assert!(load0.call(last).is_err());
+    Ok(())
+}
+
+#[test]
+fn copy_between_memories() -> Result<()> {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (memory $m0 (import "env" "memory") 1)
+          (memory $m1 (export "memory") 1)
+          (data (memory $m1) (i32.const 0) "\01\02\03\04")
+          (func (export "copy_to_import") (param i32 i32 i32)
+            local.get 0
+            local.get 1
+            local.get 2
+            memory.copy $m0 $m1)
+          (func (export "copy_from_import") (param i32 i32 i32)
+            local.get 0
+            local.get 1
+            local.get 2
+            memory.copy $m1 $m0))
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let imported = Memory::new(&store, MemoryType::new(1, None, false))?;
+    let import_object = imports! {
+        "env" => {
+            "memory" => imported.clone(),
+        },
+    };
+    let instance = Instance::new(&module, &import_object)?;
+    let local = instance.exports.get_memory("memory")?;
+    let copy_to_import = instance
+        .exports
+        .get_native_function::<(i32, i32, i32), ()>("copy_to_import")?;
+    let copy_from_import = instance
+        .exports
+        .get_native_function::<(i32, i32, i32), ()>("copy_from_import")?;
+
+    copy_to_import.call(100, 0, 4)?;
+    let view = imported.view::<u8>();
+    let copied: Vec<u8> = view[100..104].iter().map(|cell| cell.get()).collect();
+    assert_eq!(copied, vec![1, 2, 3, 4]);
+
+    copy_from_import.call(8, 100, 4)?;
+    let view = local.view::<u8>();
+    let copied: Vec<u8> = view[8..12].iter().map(|cell| cell.get()).collect();
+    assert_eq!(copied, vec![1, 2, 3, 4]);
+
+    assert!(copy_to_import
+        .call(WASM_PAGE_SIZE as i32 - 2, 0, 4)
+        .is_err());
+    Ok(())
+}
@@ -0,0 +1,163 @@
+//! Calls of multi-value functions defined in Wasm, through `call_indirect`
+//! and from the host, including between functions generated by different
+//! compilers.
+
+use crate::utils::get_store;
+use anyhow::Result;
+use wasmer::*;
+
+const WAT: &str = r#"
+    (module
+      (type $swap (func (param i32 i64) (result i64 i32 f64)))
+      (type $many (func (param i32) (result i32 i64 f32 f64 i32 i64 f32 f64)))
+      (import "env" "host_swap" (func $host_swap (type $swap)))
+      (table 3 funcref)
+      (elem (i32.const 0) $swap $host_swap $many)
+      (func $swap (export "swap") (type $swap)
+        local.get 1
+        local.get 0
+        local.get 0
+        f64.convert_i32_s)
+      (func $many (export "many") (type $many)
+        local.get 0
+        local.get 0
+        i64.extend_i32_s
+        local.get 0
+        f32.convert_i32_s
+        local.get 0
+        f64.convert_i32_s
+        local.get 0
+        i32.const 1
+        i32.add
+        local.get 0
+        i64.extend_i32_s
+        i64.const 1
+        i64.add
+        local.get 0
+        f32.convert_i32_s
+        f32.const 0.5
+        f32.add
+        local.get 0
+        f64.convert_i32_s
+        f64.const 0.5
+        f64.add)
+      (func (export "call_swap") (param i32 i32 i64) (result i64 i32 f64)
+        local.get 1
+        local.get 2
+        local.get 0
+        call_indirect (type $swap))
+      (func (export "call_many") (param i32) (result i32 i64 f32 f64 i32 i64 f32 f64)
+        local.get 0
+        i32.const 2
+        call_indirect (type $many)))
+"#;
+
+type Many = (i32, i64, f32, f64, i32, i64, f32, f64);
+
+fn instantiate(store: &Store) -> Result<(Module, Instance)> {
+    let module = Module::new(store, WAT)?;
+    // Native host functions can't return multiple values yet.
+    let host_swap_type = FunctionType::new(
+        vec![Type::I32, Type::I64],
+        vec![Type::I64, Type::I32, Type::F64],
+    );
+    let host_swap = Function::new(store, &host_swap_type, |args| {
+        let a = args[0].unwrap_i32();
+        Ok(vec![args[1].clone(), Val::I32(a), Val::F64(f64::from(a))])
+    });
+    let import_object = imports! {
+        "env" => {
+            "host_swap" => host_swap,
+        },
+    };
+    let instance = Instance::new(&module, &import_object)?;
+    Ok((module, instance))
+}
+
+fn check_calls(instance: &Instance) -> Result<()> {
+    let expected_many: Many = (7, 7, 7.0, 7.0, 8, 8, 7.5, 7.5);
+
+    // From the host, through the native and the dynamic APIs.
+    let swap = instance
+        .exports
+        .get_native_function::<(i32, i64), (i64, i32, f64)>("swap")?;
+    assert_eq!(swap.call(3, 4)?, (4, 3, 3.0));
+    let many = instance.exports.get_native_function::<i32, Many>("many")?;
+    assert_eq!(many.call(7)?, expected_many);
+    let results = instance
+        .exports
+        .get_function("swap")?
+        .call(&[Val::I32(5), Val::I64(6)])?;
+    assert_eq!(
+        results.to_vec(),
+        vec![Val::I64(6), Val::I32(5), Val::F64(5.0)]
+    );
+
+    // Through `call_indirect`, to Wasm and host functions.
+    let call_swap = instance
+        .exports
+        .get_native_function::<(i32, i32, i64), (i64, i32, f64)>("call_swap")?;
+    assert_eq!(call_swap.call(0, -1, 2)?, (2, -1, -1.0));
+    assert_eq!(call_swap.call(1, -1, 2)?, (2, -1, -1.0));
+    let call_many = instance
+        .exports
+        .get_native_function::<i32, Many>("call_many")?;
+    assert_eq!(call_many.call(7)?, expected_many);
+    Ok(())
+}
+
+#[test]
+fn multi_value_calls() -> Result<()> {
+    let store = get_store(false);
+    let (_module, instance) = instantiate(&store)?;
+    check_calls(&instance)
+}
+
+/// Singlepass functions calling multi-value functions tiered up to
+/// Cranelift, and the other way around.
+#[cfg(all(feature = "singlepass", feature = "cranelift", feature = "test-jit"))]
+#[test]
+fn multi_value_calls_between_compilers() -> Result<()> {
+    use std::time::{Duration, Instant};
+    use wasmer_engine_jit::{JITTieredArtifact, JIT};
+    use wasmer_types::entity::EntityRef;
+
+    let engine = JIT::new(wasmer_compiler_singlepass::Singlepass::new())
+        .tier_up(wasmer_compiler_cranelift::Cranelift::new())
+        .tier_up_threshold(100)
+        .engine();
+    let store = Store::new(&engine);
+    let (module, instance) = instantiate(&store)?;
+    let artifact = module
+        .artifact()
+        .downcast_ref::<JITTieredArtifact>()
+        .expect("the module should be tiered");
+    let wait_until_optimized = |index: usize| {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while !artifact.is_function_optimized(LocalFunctionIndex::new(index)) {
+            assert!(Instant::now() < deadline, "the function never tiered up");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    };
+
+    // `call_swap` reaches the host function only, so it tiers up to
+    // Cranelift while `swap` stays in Singlepass.
+    let call_swap = instance
+        .exports
+        .get_native_function::<(i32, i32, i64), (i64, i32, f64)>("call_swap")?;
+    for _ in 0..200 {
+        call_swap.call(1, 1, 2)?;
+    }
+    wait_until_optimized(2);
+    assert!(!artifact.is_function_optimized(LocalFunctionIndex::new(0)));
+    check_calls(&instance)?;
+
+    // `many` tiers up to Cranelift while `call_many` stays in Singlepass.
+    let many = instance.exports.get_native_function::<i32, Many>("many")?;
+    for _ in 0..200 {
+        many.call(1)?;
+    }
+    wait_until_optimized(1);
+    assert!(!artifact.is_function_optimized(LocalFunctionIndex::new(3)));
+    check_calls(&instance)
+}
@@ -63,7 +63,6 @@ macro_rules! mvr_test {
             }
 
             #[test]
-            #[cfg_attr(feature="test-singlepass", ignore)]
             fn dynamic() -> anyhow::Result<()> {
                 let store = get_store(false);
                 let module = get_module(&store)?;
@@ -0,0 +1,50 @@
+#![cfg(unix)]
+
+use crate::utils::get_store;
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use wasmer::*;
+
+#[test]
+fn samples_guest_functions() -> Result<()> {
+    let store = get_store(false);
+    let wat = r#"
+        (module
+          (func $spin (param i32)
+            (loop $continue
+              local.get 0
+              i32.const 1
+              i32.sub
+              local.tee 0
+              br_if $continue))
+          (func (export "run") (param i32)
+            local.get 0
+            call $spin))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<i32, ()>("run")?;
+
+    let profiler = Profiler::start(Duration::from_millis(1))?;
+    let deadline = Instant::now() + Duration::from_millis(500);
+    while Instant::now() < deadline {
+        run.call(1_000_000)?;
+    }
+    let profile = profiler.stop();
+
+    assert!(profile.self_samples().get("spin").copied().unwrap_or(0) > 0);
+    let mut folded = Vec::new();
+    profile.write_folded(&mut folded)?;
+    let folded = String::from_utf8(folded)?;
+    assert!(folded.lines().any(|line| line.contains("spin ")));
+
+    let mut pprof = Vec::new();
+    profile.write_pprof(&mut pprof)?;
+    // The first field is a sample type, and the string table, the last
+    // field, starts with the empty string and holds the function names.
+    assert_eq!(pprof[0], 1 << 3 | 2);
+    let contains = |bytes: &[u8]| pprof.windows(bytes.len()).any(|window| window == bytes);
+    assert!(contains(&[6 << 3 | 2, 0]));
+    assert!(contains(&[6 << 3 | 2, 4, b's', b'p', b'i', b'n']));
+    Ok(())
+}
@@ -0,0 +1,27 @@
+#![cfg(all(feature = "test-jit", target_os = "linux"))]
+
+use crate::get_compiler;
+use anyhow::Result;
+use wasmer::*;
+use wasmer_engine_jit::{ProfilingStrategy, JIT};
+
+#[test]
+fn perf_map_names_guest_functions() -> Result<()> {
+    let engine = JIT::new(get_compiler(false))
+        .profiling_strategy(ProfilingStrategy::PerfMap)?
+        .engine();
+    let store = Store::new(&engine);
+    let wat = r#"
+        (module
+          (func $profiled_guest_function (export "run"))
+          (func (export "anonymous")))
+    "#;
+    let _module = Module::new(&store, wat)?;
+
+    let map = std::fs::read_to_string(format!("/tmp/perf-{}.map", std::process::id()))?;
+    assert!(map
+        .lines()
+        .any(|line| line.ends_with(" profiled_guest_function")));
+    assert!(map.lines().any(|line| line.ends_with(" wasm-function[1]")));
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use crate::get_compiler;
+use wasmer::*;
+#[cfg(feature = "test-jit")]
+use wasmer_engine_jit::JIT;
+#[cfg(feature = "test-native")]
+use wasmer_engine_native::Native;
+
+fn get_store() -> Store {
+    let mut features = Features::new();
+    features.tail_call(true);
+    #[cfg(feature = "test-jit")]
+    let engine = JIT::new(get_compiler(false)).features(features).engine();
+    #[cfg(feature = "test-native")]
+    let engine = Native::new(get_compiler(false)).features(features).engine();
+    Store::new(&engine)
+}
+
+#[test]
+fn tail_call_to_other_function_is_unsupported() {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (func $callee (param i32) (result i32)
+            local.get 0)
+          (func (export "caller") (param i32) (result i32)
+            (return_call $callee (local.get 0))))
+    "#;
+
+    assert!(
+        Module::new(&store, wat).is_err(),
+        "a tail call to another function must not compile"
+    );
+}
+
+#[test]
+fn tail_call_indirect_is_unsupported() {
+    let store = get_store();
+    let wat = r#"
+        (module
+          (type $t (func (param i32) (result i32)))
+          (table funcref (elem $callee))
+          (func $callee (param i32) (result i32)
+            local.get 0)
+          (func (export "caller") (param i32) (result i32)
+            (return_call_indirect (type $t) (local.get 0) (i32.const 0))))
+    "#;
+
+    assert!(
+        Module::new(&store, wat).is_err(),
+        "an indirect tail call must not compile"
+    );
+}
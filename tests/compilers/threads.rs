@@ -0,0 +1,134 @@
+use crate::get_compiler;
+use anyhow::Result;
+use wasmer::*;
+#[cfg(feature = "test-jit")]
+use wasmer_engine_jit::JIT;
+#[cfg(feature = "test-native")]
+use wasmer_engine_native::Native;
+
+fn get_store() -> Store {
+    let mut features = Features::new();
+    features.threads(true);
+    #[cfg(feature = "test-jit")]
+    let engine = JIT::new(get_compiler(false)).features(features).engine();
+    #[cfg(feature = "test-native")]
+    let engine = Native::new(get_compiler(false)).features(features).engine();
+    Store::new(&engine)
+}
+
+const WAT: &str = r#"
+    (module
+      (import "env" "memory" (memory 1 1 shared))
+      (func (export "store") (param i32 i32)
+        (i32.store offset=4 (local.get 0) (local.get 1)))
+      (func (export "wait") (param i32 i64) (result i32)
+        (memory.atomic.wait32 (i32.const 0) (local.get 0) (local.get 1)))
+      (func (export "notify") (result i32)
+        (memory.atomic.notify (i32.const 0) (i32.const 1))))
+"#;
+
+fn spawn<T, F>(module: &Module, memory: &Memory, entry: F) -> GuestThread<T>
+where
+    F: FnOnce(&Instance) -> Result<T, RuntimeError> + Send + 'static,
+    T: Send + 'static,
+{
+    let memory = memory.clone();
+    module.spawn_thread(
+        move || {
+            imports! {
+                "env" => {
+                    "memory" => memory,
+                },
+            }
+        },
+        entry,
+    )
+}
+
+fn get_function<Args, Rets>(
+    instance: &Instance,
+    name: &str,
+) -> Result<NativeFunc<Args, Rets>, RuntimeError>
+where
+    Args: WasmTypeList,
+    Rets: WasmTypeList,
+{
+    instance
+        .exports
+        .get_native_function(name)
+        .map_err(|error| RuntimeError::new(error.to_string()))
+}
+
+#[test]
+fn threads_share_memory() -> Result<()> {
+    let store = get_store();
+    let module = Module::new(&store, WAT)?;
+    let memory = Memory::new(&store, MemoryType::new(1, Some(1), true))?;
+
+    // Each thread writes its own slot of the memory.
+    let threads = (0..4)
+        .map(|i| {
+            spawn(&module, &memory, move |instance| {
+                get_function::<(i32, i32), ()>(instance, "store")?.call(i * 4, i + 1)
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join()?;
+    }
+
+    let slots: WasmPtr<u32, Array> = WasmPtr::new(4);
+    let values = slots
+        .deref(&memory, 0, 4)
+        .unwrap()
+        .iter()
+        .map(|cell| cell.get())
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec![1, 2, 3, 4]);
+    Ok(())
+}
+
+#[test]
+fn threads_wait_and_notify() -> Result<()> {
+    let store = get_store();
+    let module = Module::new(&store, WAT)?;
+    let memory = Memory::new(&store, MemoryType::new(1, Some(1), true))?;
+
+    // The value doesn't match, so the wait returns 1 right away, and with
+    // nobody notifying it times out with 2.
+    let thread = spawn(&module, &memory, |instance| {
+        let wait = get_function::<(i32, i64), i32>(instance, "wait")?;
+        Ok((wait.call(1, -1)?, wait.call(0, 1_000_000)?))
+    });
+    assert_eq!(thread.join()?, (1, 2));
+
+    // Notify until the waiter is woken up.
+    let waiter = spawn(&module, &memory, |instance| {
+        get_function::<(i32, i64), i32>(instance, "wait")?.call(0, -1)
+    });
+    let import_object = imports! {
+        "env" => {
+            "memory" => memory.clone(),
+        },
+    };
+    let instance = Instance::new(&module, &import_object)?;
+    let notify = instance.exports.get_native_function::<(), i32>("notify")?;
+    while notify.call()? == 0 {
+        std::thread::yield_now();
+    }
+    assert_eq!(waiter.join()?, 0);
+    Ok(())
+}
+
+#[test]
+fn thread_instantiation_error() -> Result<()> {
+    let store = get_store();
+    let module = Module::new(&store, WAT)?;
+
+    let thread = module.spawn_thread(|| imports! {}, |_| Ok(()));
+    match thread.join() {
+        Err(ThreadError::Instantiation(_)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+    Ok(())
+}
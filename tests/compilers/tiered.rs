@@ -0,0 +1,179 @@
+#![cfg(feature = "test-jit")]
+
+use crate::get_compiler;
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wasmer::*;
+use wasmer_compiler::CompilerConfig;
+use wasmer_engine_jit::{JITTieredArtifact, JIT};
+use wasmer_middlewares::Optimize;
+use wasmer_types::entity::EntityRef;
+
+const FIB: &str = r#"
+    (module
+      (func $fib (export "fib") (param i32) (result i32)
+        local.get 0
+        i32.const 2
+        i32.lt_u
+        if (result i32)
+          local.get 0
+        else
+          local.get 0
+          i32.const 1
+          i32.sub
+          call $fib
+          local.get 0
+          i32.const 2
+          i32.sub
+          call $fib
+          i32.add
+        end)
+      (func (export "cold") (result i32)
+        i32.const 42))
+"#;
+
+fn tiered_artifact(module: &Module) -> &JITTieredArtifact {
+    module
+        .artifact()
+        .downcast_ref::<JITTieredArtifact>()
+        .expect("the module should be tiered")
+}
+
+fn wait_until(mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(60);
+    while !condition() {
+        assert!(Instant::now() < deadline, "the module never tiered up");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn tier_up_keeps_behaviour(
+    baseline: impl CompilerConfig + 'static,
+    optimizing: impl CompilerConfig + 'static,
+) -> Result<()> {
+    let engine = JIT::new(baseline).tier_up(optimizing).engine();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, FIB)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let fib = instance.exports.get_native_function::<i32, i32>("fib")?;
+    assert_eq!(fib.call(20)?, 6765);
+
+    let artifact = tiered_artifact(&module);
+    wait_until(|| artifact.is_optimized());
+
+    // The instances created before tiering up run the optimized code.
+    assert_eq!(fib.call(20)?, 6765);
+    let cold = instance.exports.get_native_function::<(), i32>("cold")?;
+    assert_eq!(cold.call()?, 42);
+    let other = Instance::new(&module, &imports! {})?;
+    let fib = other.exports.get_native_function::<i32, i32>("fib")?;
+    assert_eq!(fib.call(10)?, 55);
+    Ok(())
+}
+
+#[test]
+fn tier_up_with_the_same_compiler() -> Result<()> {
+    tier_up_keeps_behaviour(get_compiler(false), get_compiler(false))
+}
+
+#[cfg(all(feature = "singlepass", feature = "cranelift"))]
+#[test]
+fn tier_up_from_singlepass_to_cranelift() -> Result<()> {
+    tier_up_keeps_behaviour(
+        wasmer_compiler_singlepass::Singlepass::new(),
+        wasmer_compiler_cranelift::Cranelift::new(),
+    )
+}
+
+#[test]
+fn tier_up_hot_functions() -> Result<()> {
+    let engine = JIT::new(get_compiler(false))
+        .tier_up(get_compiler(false))
+        .tier_up_threshold(100)
+        .engine();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, FIB)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let artifact = tiered_artifact(&module);
+    let fib_index = LocalFunctionIndex::new(0);
+    let cold_index = LocalFunctionIndex::new(1);
+
+    let cold = instance.exports.get_native_function::<(), i32>("cold")?;
+    assert_eq!(cold.call()?, 42);
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!artifact.is_function_optimized(fib_index));
+
+    let fib = instance.exports.get_native_function::<i32, i32>("fib")?;
+    assert_eq!(fib.call(15)?, 610);
+    wait_until(|| artifact.is_function_optimized(fib_index));
+    assert_eq!(fib.call(15)?, 610);
+    assert!(!artifact.is_function_optimized(cold_index));
+    assert!(!artifact.is_optimized());
+    Ok(())
+}
+
+#[cfg(all(feature = "singlepass", feature = "cranelift"))]
+#[test]
+fn tier_up_errors_are_reported() -> Result<()> {
+    let mut features = Features::new();
+    features.exceptions(true);
+    let engine = JIT::new(wasmer_compiler_cranelift::Cranelift::new())
+        .tier_up(wasmer_compiler_singlepass::Singlepass::new())
+        .features(features)
+        .engine();
+    let store = Store::new(&engine);
+    let wat = r#"
+        (module
+          (func (export "run") (result i32)
+            try (result i32)
+              i32.const 42
+            catch_all
+              i32.const 0
+            end))
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run = instance.exports.get_native_function::<(), i32>("run")?;
+    assert_eq!(run.call()?, 42);
+
+    // Singlepass doesn't support exceptions, so the function keeps its
+    // baseline code.
+    let artifact = tiered_artifact(&module);
+    let mut errors = vec![];
+    wait_until(|| {
+        errors.extend(artifact.take_tier_up_errors());
+        !errors.is_empty()
+    });
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].function, Some(LocalFunctionIndex::new(0)));
+    match &errors[0].error {
+        CompileError::UnsupportedFeature(feature) => assert_eq!(feature, "exceptions"),
+        error => panic!("unexpected error: {}", error),
+    }
+    assert!(!artifact.is_optimized());
+    assert_eq!(run.call()?, 42);
+    Ok(())
+}
+
+#[test]
+fn tier_up_with_an_optimize_middleware_shared_by_both_compilers() -> Result<()> {
+    // Both compilers analyze the module for the functions to inline.
+    let optimize = Arc::new(Optimize::new());
+    let mut baseline = get_compiler(false);
+    baseline.push_middleware(optimize.clone());
+    let mut optimizing = get_compiler(false);
+    optimizing.push_middleware(optimize);
+    let engine = JIT::new(baseline).tier_up(optimizing).engine();
+    let store = Store::new(&engine);
+    let module = Module::new(&store, FIB)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let fib = instance.exports.get_native_function::<i32, i32>("fib")?;
+    assert_eq!(fib.call(20)?, 6765);
+
+    let artifact = tiered_artifact(&module);
+    wait_until(|| artifact.is_optimized());
+    assert!(artifact.take_tier_up_errors().is_empty());
+    assert_eq!(fib.call(20)?, 6765);
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::utils::get_store;
+use crate::utils::{get_store, with_dwarf};
 use anyhow::Result;
 use std::panic::{self, AssertUnwindSafe};
 use wasmer::*;
@@ -164,6 +164,72 @@ fn test_trap_stack_overflow() -> Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg(unix)]
+#[cfg_attr(
+    any(
+        feature = "test-singlepass",
+        feature = "test-native",
+        target_arch = "aarch64",
+        target_env = "musl",
+    ),
+    ignore
+)]
+fn test_trap_guest_stack_overflow() -> Result<()> {
+    let store = get_store(false);
+    store.set_guest_stack_size(Some(64 * 1024))?;
+    let wat = r#"
+        (module $rec_mod
+            (func $run (export "run") (call $run))
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func.call(&[]).err().expect("error calling function");
+    assert!(e.message().contains("call stack exhausted"));
+
+    // The guest stack is still usable after overflowing it.
+    let e = run_func.call(&[]).err().expect("error calling function");
+    assert!(e.message().contains("call stack exhausted"));
+
+    Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+#[cfg_attr(feature = "test-native", ignore)]
+fn test_guest_stack_deep_recursion() -> Result<()> {
+    let store = get_store(false);
+    store.set_guest_stack_size(Some(256 * 1024 * 1024))?;
+    assert_eq!(store.guest_stack_size(), Some(256 * 1024 * 1024));
+    let wat = r#"
+        (module
+            (func $rec (export "rec") (param i32) (result i32)
+                (if (result i32) (i32.eqz (local.get 0))
+                    (then (i32.const 0))
+                    (else
+                        (i32.add
+                            (call $rec (i32.sub (local.get 0) (i32.const 1)))
+                            (i32.const 1)))))
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let rec = instance.exports.get_native_function::<i32, i32>("rec")?;
+
+    // Deeper than the 8MiB main thread stack would allow.
+    assert_eq!(rec.call(1_000_000)?, 1_000_000);
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(
     any(
@@ -206,6 +272,83 @@ RuntimeError: unreachable
     Ok(())
 }
 
+#[test]
+#[cfg_attr(
+    any(feature = "test-native", target_arch = "aarch64", target_env = "musl",),
+    ignore
+)]
+fn trap_source_frames() -> Result<()> {
+    let store = get_store(false);
+    let wat = br#"
+        (module $m
+            (func (export "run") i32.const 1 drop unreachable)
+        )
+    "#;
+
+    let module = Module::new(&store, with_dwarf(&wat2wasm(wat)?))?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func.call(&[]).err().expect("error calling function");
+    let trace = e.trace();
+    assert_eq!(trace.len(), 1);
+    let source_frames = trace[0].source_frames();
+    assert_eq!(source_frames.len(), 2);
+    assert_eq!(source_frames[0].function_name(), Some("helper"));
+    assert_eq!(source_frames[0].file(), Some("/work/src/lib.rs"));
+    assert_eq!(source_frames[0].line(), Some(8));
+    assert_eq!(source_frames[0].column(), Some(5));
+    assert_eq!(source_frames[1].function_name(), Some("run"));
+    assert_eq!(source_frames[1].file(), Some("/work/src/lib.rs"));
+    assert_eq!(source_frames[1].line(), Some(3));
+    assert_eq!(source_frames[1].column(), Some(5));
+    assert_eq!(
+        module.source_frames(trace[0].module_offset()).len(),
+        source_frames.len()
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(
+    any(
+        feature = "test-llvm",
+        feature = "test-native",
+        target_arch = "aarch64",
+        target_env = "musl",
+    ),
+    ignore
+)]
+fn trap_display_source_frames() -> Result<()> {
+    let store = get_store(false);
+    let wat = br#"
+        (module $m
+            (func (export "run") i32.const 1 drop unreachable)
+        )
+    "#;
+
+    let module = Module::new(&store, with_dwarf(&wat2wasm(wat)?))?;
+    let instance = Instance::new(&module, &imports! {})?;
+    let run_func = instance
+        .exports
+        .get_function("run")
+        .expect("expected function export");
+
+    let e = run_func.call(&[]).err().expect("error calling function");
+    assert_eq!(
+        e.to_string(),
+        "\
+RuntimeError: unreachable
+    at <unnamed> (m[0]:0x23)
+             at helper [inlined] (/work/src/lib.rs:8:5)
+             at /work/src/lib.rs:3:5"
+    );
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(
     any(
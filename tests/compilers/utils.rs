@@ -1,3 +1,4 @@
+use std::fmt::Debug;
 use std::sync::Arc;
 use wasmer::{ModuleMiddleware, Store};
 use wasmer_compiler::CompilerConfig;
@@ -7,7 +8,7 @@ use wasmer_engine_jit::JIT;
 #[cfg(feature = "test-native")]
 use wasmer_engine_native::Native;
 
-pub fn get_compiler(canonicalize_nans: bool) -> impl CompilerConfig {
+pub fn get_compiler(canonicalize_nans: bool) -> impl CompilerConfig + Debug {
     cfg_if::cfg_if! {
         if #[cfg(any(
             all(feature = "test-llvm", any(feature = "test-cranelift", feature = "test-singlepass")),
@@ -73,3 +74,164 @@ pub fn get_headless_store() -> Store {
 pub fn get_headless_store() -> Store {
     Store::new(&Native::headless().engine())
 }
+
+/// Appends to the module `wasm` the DWARF of a Rust crate, describing its
+/// first function as `run` in `/work/src/lib.rs`, into which `helper`
+/// was inlined at line 3: the instruction before the `end` of `run` comes
+/// from line 8 of `helper`. The first local of `run` is the variable `x`.
+pub fn with_dwarf(wasm: &[u8]) -> Vec<u8> {
+    use gimli::write::{
+        Address, AttributeValue, DwarfUnit, EndianVec, Expression, LineProgram, LineString,
+        Sections,
+    };
+    use gimli::{Encoding, Format, LineEncoding, LittleEndian};
+    use wasmer::wasmparser::{Parser, Payload};
+
+    let mut code_section_offset = 0;
+    let mut body = 0..0;
+    let mut inlined = 0;
+    for payload in Parser::new(0).parse_all(wasm) {
+        match payload.unwrap() {
+            Payload::CodeSectionStart { range, .. } => code_section_offset = range.start,
+            Payload::CodeSectionEntry(function) => {
+                let range = function.range();
+                body = range.start..range.end;
+                let operators = function
+                    .get_operators_reader()
+                    .unwrap()
+                    .into_iter_with_offsets()
+                    .collect::<Result<Vec<_>, _>>()
+                    .unwrap();
+                inlined = operators[operators.len() - 2].1;
+                break;
+            }
+            _ => {}
+        }
+    }
+    let address = |offset: usize| (offset - code_section_offset) as u64;
+
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 4,
+    };
+    let mut dwarf = DwarfUnit::new(encoding);
+    let mut program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(b"/work".to_vec()),
+        LineString::String(b"src/lib.rs".to_vec()),
+        None,
+    );
+    let directory = program.add_directory(LineString::String(b"src".to_vec()));
+    let file = program.add_file(LineString::String(b"lib.rs".to_vec()), directory, None);
+    program.begin_sequence(Some(Address::Constant(address(body.start))));
+    program.row().file = file;
+    program.row().line = 2;
+    program.row().column = 1;
+    program.generate_row();
+    program.row().address_offset = address(inlined) - address(body.start);
+    program.row().line = 8;
+    program.row().column = 5;
+    program.generate_row();
+    program.end_sequence(address(body.end) - address(body.start));
+    dwarf.unit.line_program = program;
+
+    let root = dwarf.unit.root();
+    let entry = dwarf.unit.get_mut(root);
+    entry.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(b"src/lib.rs".to_vec()),
+    );
+    entry.set(
+        gimli::DW_AT_comp_dir,
+        AttributeValue::String(b"/work".to_vec()),
+    );
+    entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(address(body.start))),
+    );
+    entry.set(
+        gimli::DW_AT_high_pc,
+        AttributeValue::Udata(address(body.end) - address(body.start)),
+    );
+    let helper = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+    let entry = dwarf.unit.get_mut(helper);
+    entry.set(
+        gimli::DW_AT_name,
+        AttributeValue::String(b"helper".to_vec()),
+    );
+    entry.set(
+        gimli::DW_AT_inline,
+        AttributeValue::Inline(gimli::DW_INL_inlined),
+    );
+    let run = dwarf.unit.add(root, gimli::DW_TAG_subprogram);
+    let entry = dwarf.unit.get_mut(run);
+    entry.set(gimli::DW_AT_name, AttributeValue::String(b"run".to_vec()));
+    entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(address(body.start))),
+    );
+    entry.set(
+        gimli::DW_AT_high_pc,
+        AttributeValue::Udata(address(body.end) - address(body.start)),
+    );
+    let x = dwarf.unit.add(run, gimli::DW_TAG_variable);
+    let entry = dwarf.unit.get_mut(x);
+    entry.set(gimli::DW_AT_name, AttributeValue::String(b"x".to_vec()));
+    // DW_OP_WASM_location of the local 0.
+    entry.set(
+        gimli::DW_AT_location,
+        AttributeValue::Exprloc(Expression::raw(vec![0xed, 0x00, 0x00])),
+    );
+    let inlined_helper = dwarf.unit.add(run, gimli::DW_TAG_inlined_subroutine);
+    let entry = dwarf.unit.get_mut(inlined_helper);
+    entry.set(
+        gimli::DW_AT_abstract_origin,
+        AttributeValue::UnitRef(helper),
+    );
+    entry.set(
+        gimli::DW_AT_low_pc,
+        AttributeValue::Address(Address::Constant(address(inlined))),
+    );
+    entry.set(gimli::DW_AT_high_pc, AttributeValue::Udata(1));
+    entry.set(
+        gimli::DW_AT_call_file,
+        AttributeValue::FileIndex(Some(file)),
+    );
+    entry.set(gimli::DW_AT_call_line, AttributeValue::Udata(3));
+    entry.set(gimli::DW_AT_call_column, AttributeValue::Udata(5));
+
+    let mut sections = Sections::new(EndianVec::new(LittleEndian));
+    dwarf.write(&mut sections).unwrap();
+    let mut wasm = wasm.to_vec();
+    sections
+        .for_each(|id, section| {
+            let data = section.slice();
+            if !data.is_empty() {
+                let name = id.name();
+                let mut contents = leb128(name.len());
+                contents.extend_from_slice(name.as_bytes());
+                contents.extend_from_slice(data);
+                wasm.push(0);
+                wasm.extend(leb128(contents.len()));
+                wasm.extend(contents);
+            }
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+    wasm
+}
+
+fn leb128(mut value: usize) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
@@ -0,0 +1,153 @@
+//! Tests of the `wasmer inspect` command.
+
+use anyhow::{bail, Context};
+use std::path::Path;
+use std::process::Command;
+use wasmer_integration_tests_cli::*;
+
+const INSPECT_TEST_WAT: &str = r#"
+(module $inspected
+  (import "env" "log" (func $log (param i32)))
+  (import "env" "memory" (memory 1 2))
+  (table 2 funcref)
+  (global $counter (mut i32) (i32.const 0))
+  (func $start)
+  (func $add (export "add") (param $a i32) (param $b i32) (result i32 i32)
+    (local $sum i32)
+    local.get $a
+    local.get $b
+    i32.add
+    local.tee $sum
+    call $log
+    local.get $sum
+    global.get $counter)
+  (start $start)
+  (export "counter" (global $counter))
+  (elem (i32.const 0) $start $add)
+  (data (i32.const 16) "hello")
+  (data "passive"))
+"#;
+
+/// Runs `wasmer inspect` with the given format and extra flags on the file
+/// at `path`.
+fn run_inspect(path: &Path, format: &str, flags: &[&str]) -> anyhow::Result<String> {
+    let output = Command::new(get_wasmer_path())
+        .arg("inspect")
+        .arg(path)
+        .arg("--format")
+        .arg(format)
+        .args(flags)
+        .output()?;
+
+    if !output.status.success() {
+        bail!(
+            "wasmer inspect failed with: stdout: {}\n\nstderr: {}",
+            std::str::from_utf8(&output.stdout)
+                .expect("stdout is not utf8! need to handle arbitrary bytes"),
+            std::str::from_utf8(&output.stderr)
+                .expect("stderr is not utf8! need to handle arbitrary bytes")
+        );
+    }
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[test]
+fn inspect_json_works() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat_path = temp_dir.path().join("inspected.wat");
+    std::fs::write(&wat_path, INSPECT_TEST_WAT)?;
+
+    let output = run_inspect(&wat_path, "json", &[]).context("Failed to inspect the module")?;
+    let report: serde_json::Value = serde_json::from_str(&output)?;
+
+    assert_eq!(report["type"], "wat");
+    assert_eq!(report["size"], INSPECT_TEST_WAT.len());
+
+    let imports = &report["imports"];
+    assert_eq!(imports["functions"][0]["module"], "env");
+    assert_eq!(imports["functions"][0]["name"], "log");
+    assert_eq!(imports["memories"][0]["name"], "memory");
+    let exports = &report["exports"];
+    assert_eq!(exports["functions"][0]["name"], "add");
+    assert_eq!(exports["functions"][0]["module"], serde_json::Value::Null);
+    assert_eq!(exports["globals"][0]["name"], "counter");
+
+    assert_eq!(report["start_function"], 1);
+    let memory = &report["memories"][0];
+    assert_eq!(memory["imported"], true);
+    assert_eq!(memory["limits"]["minimum"], 1);
+    assert_eq!(memory["limits"]["maximum"], 2);
+    assert_eq!(memory["shared"], false);
+    let table = &report["tables"][0];
+    assert_eq!(table["imported"], false);
+    assert_eq!(table["limits"]["minimum"], 2);
+    assert_eq!(table["limits"]["maximum"], serde_json::Value::Null);
+
+    let data_segments = report["data_segments"].as_array().unwrap();
+    assert_eq!(data_segments.len(), 2);
+    assert_eq!(data_segments[0]["memory_index"], 0);
+    assert_eq!(data_segments[0]["size"], 5);
+    assert_eq!(data_segments[1]["passive"], true);
+    assert_eq!(data_segments[1]["size"], 7);
+    assert_eq!(report["element_segments"][0]["table_index"], 0);
+    assert_eq!(report["element_segments"][0]["size"], 2);
+
+    let names = &report["name_section"];
+    assert_eq!(names["module"], "inspected");
+    assert_eq!(names["functions_with_local_names"], 1);
+    let functions = report["functions"].as_array().unwrap();
+    assert_eq!(functions.len(), 2);
+    assert_eq!(functions[1]["index"], 2);
+    assert_eq!(functions[1]["name"], "add");
+    assert_eq!(functions[1]["locals"], 1);
+    assert!(report["custom_sections"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|section| section["name"] == "name"));
+    assert_eq!(
+        report["features"],
+        serde_json::json!(["bulk-memory", "multi-value"])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn inspect_detects_atomic_and_simd_operators() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat_path = temp_dir.path().join("features.wat");
+    std::fs::write(
+        &wat_path,
+        r#"
+(module
+  (memory 1 1 shared)
+  (func (param i32) (result i32)
+    local.get 0
+    i32.const 1
+    i32.atomic.rmw.add)
+  (func (param v128) (result v128)
+    local.get 0
+    local.get 0
+    f64x2.mul))
+"#,
+    )?;
+
+    let output = run_inspect(&wat_path, "json", &["--enable-simd", "--enable-threads"])
+        .context("Failed to inspect the module")?;
+    let report: serde_json::Value = serde_json::from_str(&output)?;
+    assert_eq!(report["features"], serde_json::json!(["simd", "threads"]));
+
+    Ok(())
+}
+
+#[test]
+fn inspect_rejects_unknown_format() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat_path = temp_dir.path().join("inspected.wat");
+    std::fs::write(&wat_path, INSPECT_TEST_WAT)?;
+
+    assert!(run_inspect(&wat_path, "yaml", &[]).is_err());
+
+    Ok(())
+}
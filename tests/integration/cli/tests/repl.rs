@@ -0,0 +1,114 @@
+//! Tests of the `wasmer repl` command.
+
+use anyhow::bail;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use wasmer_integration_tests_cli::*;
+
+const REPL_TEST_WAT: &str = r#"
+(module
+  (memory (export "memory") 1 2)
+  (global (export "counter") (mut i32) (i32.const 7))
+  (func (export "add") (param i32 i32) (result i32)
+    local.get 0
+    local.get 1
+    i32.add))
+"#;
+
+/// Runs `wasmer repl` on the file at `path`, feeding it the given commands
+/// through stdin, and returns its stdout and stderr.
+fn run_repl(path: &Path, commands: &str) -> anyhow::Result<(String, String)> {
+    let mut child = Command::new(get_wasmer_path())
+        .arg("repl")
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(commands.as_bytes())?;
+    let output = child.wait_with_output()?;
+
+    let stdout = String::from_utf8(output.stdout)?;
+    let stderr = String::from_utf8(output.stderr)?;
+    if !output.status.success() {
+        bail!(
+            "wasmer repl failed with: stdout: {}\n\nstderr: {}",
+            stdout,
+            stderr
+        );
+    }
+    Ok((stdout, stderr))
+}
+
+#[test]
+fn repl_runs_commands() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat_path = temp_dir.path().join("repl.wat");
+    std::fs::write(&wat_path, REPL_TEST_WAT)?;
+
+    let (stdout, stderr) = run_repl(
+        &wat_path,
+        "exports
+call add 2 3
+get counter
+set counter 42
+get counter
+poke memory 0x10 0x48 0x69
+hexdump memory 0x10 2
+grow memory 1
+reset
+get counter
+hexdump memory 0x10 2
+",
+    )?;
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        vec![
+            "memory \"memory\": not shared (1 pages..2 pages)",
+            "global \"counter\": I32 (mutable)",
+            "function \"add\": [I32, I32] -> [I32]",
+            "5",
+            "7",
+            "42",
+            "00000010  48 69                                            |Hi|",
+            "1 -> 2 pages",
+            "7",
+            "00000010  00 00                                            |..|",
+        ]
+    );
+    assert_eq!(stderr, "");
+
+    Ok(())
+}
+
+#[test]
+fn repl_reports_errors() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat_path = temp_dir.path().join("repl.wat");
+    std::fs::write(&wat_path, REPL_TEST_WAT)?;
+
+    let (stdout, stderr) = run_repl(
+        &wat_path,
+        "call add 1
+get
+hexdump memory 0xfff0 0x20
+poke memory 0x10 0x100
+grow memory 2
+call add 1 2
+",
+    )?;
+
+    // The REPL keeps going after each error.
+    assert_eq!(stdout, "3\n");
+    let errors = stderr.lines().collect::<Vec<_>>();
+    assert_eq!(errors.len(), 5);
+    assert!(errors[0].contains("Function expected 2 arguments, but received 1"));
+    assert!(errors[1].contains("Wrong arguments for `get`"));
+    assert!(errors[2].contains("The range 0xfff0..0x10010 is out of the memory bounds"));
+    assert!(errors[3].contains("`0x100` doesn't fit into a byte"));
+    assert!(errors[4].contains("The memory could not grow"));
+
+    Ok(())
+}
@@ -23,11 +23,14 @@ pub fn spectest_importobject(store: &Store) -> ImportObject {
     let global_f64 = Global::new(store, Val::F64(f64::from_bits(0x4084_d000_0000_0000)));
 
     let ty = TableType::new(ValType::FuncRef, 10, Some(20));
-    let table = Table::new(store, ty, Val::ExternRef(ExternRef::Null)).unwrap();
+    let table = Table::new(store, ty, Val::FuncRef(None)).unwrap();
 
     let ty = MemoryType::new(1, Some(2), false);
     let memory = Memory::new(store, ty).unwrap();
 
+    let ty = MemoryType::new(1, Some(2), true);
+    let shared_memory = Memory::new(store, ty).unwrap();
+
     imports! {
         "spectest" => {
             "print" => print,
@@ -43,6 +46,7 @@ pub fn spectest_importobject(store: &Store) -> ImportObject {
             "global_f64" => global_f64,
             "table" => table,
             "memory" => memory,
+            "shared_memory" => shared_memory,
         },
     }
 }
@@ -401,6 +401,9 @@ impl Wast {
             F32Const(x) => Val::F32(f32::from_bits(x.bits)),
             F64Const(x) => Val::F64(f64::from_bits(x.bits)),
             V128Const(x) => Val::V128(u128::from_le_bytes(x.to_le_bytes())),
+            RefNull(wast::HeapType::Func) => Val::FuncRef(None),
+            RefNull(wast::HeapType::Extern) => Val::ExternRef(ExternRef::null()),
+            RefExtern(x) => Val::ExternRef(ExternRef::new(*x)),
             other => bail!("couldn't convert {:?} to a runtime value", other),
         })
     }
@@ -464,6 +467,16 @@ fn val_matches(actual: &Val, expected: &wast::AssertExpression) -> Result<bool>
         (Val::F32(a), wast::AssertExpression::F32(b)) => f32_matches(*a, b),
         (Val::F64(a), wast::AssertExpression::F64(b)) => f64_matches(*a, b),
         (Val::V128(a), wast::AssertExpression::V128(b)) => v128_matches(*a, b),
+        (Val::FuncRef(a), wast::AssertExpression::RefNull(None))
+        | (Val::FuncRef(a), wast::AssertExpression::RefNull(Some(wast::HeapType::Func))) => {
+            a.is_none()
+        }
+        (Val::FuncRef(a), wast::AssertExpression::RefFunc(_)) => a.is_some(),
+        (Val::ExternRef(a), wast::AssertExpression::RefNull(None))
+        | (Val::ExternRef(a), wast::AssertExpression::RefNull(Some(wast::HeapType::Extern))) => {
+            a.is_null()
+        }
+        (Val::ExternRef(a), wast::AssertExpression::RefExtern(b)) => a.downcast::<u32>() == Some(b),
         _ => bail!(
             "don't know how to compare {:?} and {:?} yet",
             actual,
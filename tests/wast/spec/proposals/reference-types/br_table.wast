@@ -1243,71 +1243,41 @@
     )
   )
 
-  (func (export "meet-anyref") (param i32) (param anyref) (result anyref)
-    (block $l1 (result anyref)
-      (block $l2 (result anyref)
+  (func (export "meet-externref") (param i32) (param externref) (result externref)
+    (block $l1 (result externref)
+      (block $l2 (result externref)
         (br_table $l1 $l2 $l1 (local.get 1) (local.get 0))
       )
     )
   )
 
-  (func (export "meet-funcref-1") (param i32) (result anyref)
-    (block $l1 (result anyref)
+  (func (export "meet-funcref-1") (param i32) (result funcref)
+    (block $l1 (result funcref)
       (block $l2 (result funcref)
         (br_table $l1 $l1 $l2 (table.get 0 (i32.const 0)) (local.get 0))
       )
     )
   )
-  (func (export "meet-funcref-2") (param i32) (result anyref)
-    (block $l1 (result anyref)
+  (func (export "meet-funcref-2") (param i32) (result funcref)
+    (block $l1 (result funcref)
       (block $l2 (result funcref)
         (br_table $l2 $l2 $l1 (table.get 0 (i32.const 0)) (local.get 0))
       )
     )
   )
-  (func (export "meet-funcref-3") (param i32) (result anyref)
-    (block $l1 (result anyref)
+  (func (export "meet-funcref-3") (param i32) (result funcref)
+    (block $l1 (result funcref)
       (block $l2 (result funcref)
         (br_table $l2 $l1 $l2 (table.get 0 (i32.const 0)) (local.get 0))
       )
     )
   )
-  (func (export "meet-funcref-4") (param i32) (result anyref)
-    (block $l1 (result anyref)
-      (block $l2 (result funcref)
-        (br_table $l1 $l2 $l1 (table.get 0 (i32.const 0)) (local.get 0))
-      )
-    )
-  )
-
-  (func (export "meet-nullref") (param i32) (result funcref)
+  (func (export "meet-funcref-4") (param i32) (result funcref)
     (block $l1 (result funcref)
-      (block $l2 (result nullref)
-        (br_table $l1 $l2 $l1 (ref.null) (local.get 0))
-      )
-    )
-  )
-
-  (func (export "meet-multi-ref") (param i32) (result anyref)
-    (block $l1 (result anyref)
       (block $l2 (result funcref)
-        (block $l3 (result nullref)
-          (br_table $l3 $l2 $l1 (ref.null) (local.get 0))
-        )
-      )
-    )
-  )
-
-  (func (export "meet-bottom")
-    (block (result f64)
-      (block (result f32)
-        (unreachable)
-        (br_table 0 1 1 (i32.const 1))
+        (br_table $l1 $l2 $l1 (table.get 0 (i32.const 0)) (local.get 0))
       )
-      (drop)
-      (f64.const 0)
     )
-    (drop)
   )
 )
 
@@ -1492,9 +1462,9 @@
 
 (assert_return (invoke "nested-br_table-loop-block" (i32.const 1)) (i32.const 3))
 
-(assert_return (invoke "meet-anyref" (i32.const 0) (ref.host 1)) (ref.host 1))
-(assert_return (invoke "meet-anyref" (i32.const 1) (ref.host 1)) (ref.host 1))
-(assert_return (invoke "meet-anyref" (i32.const 2) (ref.host 1)) (ref.host 1))
+(assert_return (invoke "meet-externref" (i32.const 0) (ref.extern 1)) (ref.extern 1))
+(assert_return (invoke "meet-externref" (i32.const 1) (ref.extern 1)) (ref.extern 1))
+(assert_return (invoke "meet-externref" (i32.const 2) (ref.extern 1)) (ref.extern 1))
 
 (assert_return (invoke "meet-funcref-1" (i32.const 0)) (ref.func))
 (assert_return (invoke "meet-funcref-1" (i32.const 1)) (ref.func))
@@ -1637,14 +1607,14 @@
 )
 
 (assert_invalid
-  (module (func $meet-bottom (param i32) (result anyref)
-    (block $l1 (result anyref)
+  (module (func $meet-bottom (param i32) (result externref)
+    (block $l1 (result externref)
       (drop
         (block $l2 (result i32)
-          (br_table $l2 $l1 $l2 (ref.null) (local.get 0))
+          (br_table $l2 $l1 $l2 (ref.null extern) (local.get 0))
         )
       )
-      (ref.null)
+      (ref.null extern)
     )
   ))
   "type mismatch"
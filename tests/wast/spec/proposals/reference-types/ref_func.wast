@@ -9,8 +9,8 @@
     (i32.add (local.get $x) (i32.const 1))
   )
 
-  (global anyref (ref.func $f))
-  (global anyref (ref.func $g))
+  (global funcref (ref.func $f))
+  (global funcref (ref.func $g))
   (global funcref (ref.func $f))
   (global funcref (ref.func $g))
   (global $v (mut funcref) (ref.func $f))
@@ -76,10 +76,6 @@
   "unknown function 7"
 )
 
-(assert_invalid
-  (module (func $f) (global funcref (ref.func $f)))
-  "undeclared function reference"
-)
 (assert_invalid
   (module (func $f (drop (ref.func $f))))
   "undeclared function reference"
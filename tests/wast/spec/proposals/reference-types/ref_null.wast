@@ -1,13 +1,10 @@
 (module
-  (func (export "anyref") (result anyref) (ref.null))
-  (func (export "funcref") (result funcref) (ref.null))
-  (func (export "nullref") (result nullref) (ref.null))
+  (func (export "externref") (result externref) (ref.null extern))
+  (func (export "funcref") (result funcref) (ref.null func))
 
-  (global anyref (ref.null))
-  (global funcref (ref.null))
-  (global nullref (ref.null))
+  (global externref (ref.null extern))
+  (global funcref (ref.null func))
 )
 
-(assert_return (invoke "anyref") (ref.null))
-(assert_return (invoke "funcref") (ref.null))
-(assert_return (invoke "nullref") (ref.null))
+(assert_return (invoke "externref") (ref.null extern))
+(assert_return (invoke "funcref") (ref.null func))
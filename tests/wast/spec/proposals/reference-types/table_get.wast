@@ -1,15 +1,15 @@
 (module
-  (table $t2 2 anyref)
+  (table $t2 2 externref)
   (table $t3 3 funcref)
   (elem (table $t3) (i32.const 1) func $dummy)
   (func $dummy)
 
-  (func (export "init") (param $r anyref)
+  (func (export "init") (param $r externref)
     (table.set $t2 (i32.const 1) (local.get $r))
     (table.set $t3 (i32.const 2) (table.get $t3 (i32.const 1)))
   )
 
-  (func (export "get-anyref") (param $i i32) (result anyref)
+  (func (export "get-externref") (param $i i32) (result externref)
     (table.get $t2 (local.get $i))
   )
   (func $f3 (export "get-funcref") (param $i i32) (result funcref)
@@ -21,18 +21,18 @@
   )
 )
 
-(invoke "init" (ref.host 1))
+(invoke "init" (ref.extern 1))
 
-(assert_return (invoke "get-anyref" (i32.const 0)) (ref.null))
-(assert_return (invoke "get-anyref" (i32.const 1)) (ref.host 1))
+(assert_return (invoke "get-externref" (i32.const 0)) (ref.null extern))
+(assert_return (invoke "get-externref" (i32.const 1)) (ref.extern 1))
 
-(assert_return (invoke "get-funcref" (i32.const 0)) (ref.null))
+(assert_return (invoke "get-funcref" (i32.const 0)) (ref.null func))
 (assert_return (invoke "is_null-funcref" (i32.const 1)) (i32.const 0))
 (assert_return (invoke "is_null-funcref" (i32.const 2)) (i32.const 0))
 
-(assert_trap (invoke "get-anyref" (i32.const 2)) "out of bounds")
+(assert_trap (invoke "get-externref" (i32.const 2)) "out of bounds")
 (assert_trap (invoke "get-funcref" (i32.const 3)) "out of bounds")
-(assert_trap (invoke "get-anyref" (i32.const -1)) "out of bounds")
+(assert_trap (invoke "get-externref" (i32.const -1)) "out of bounds")
 (assert_trap (invoke "get-funcref" (i32.const -1)) "out of bounds")
 
 
@@ -40,8 +40,8 @@
 
 (assert_invalid
   (module
-    (table $t 10 anyref)
-    (func $type-index-empty-vs-i32 (result anyref)
+    (table $t 10 externref)
+    (func $type-index-empty-vs-i32 (result externref)
       (table.get $t)
     )
   )
@@ -49,8 +49,8 @@
 )
 (assert_invalid
   (module
-    (table $t 10 anyref)
-    (func $type-index-f32-vs-i32 (result anyref)
+    (table $t 10 externref)
+    (func $type-index-f32-vs-i32 (result externref)
       (table.get $t (f32.const 1))
     )
   )
@@ -59,8 +59,8 @@
 
 (assert_invalid
   (module
-    (table $t 10 anyref)
-    (func $type-result-anyref-vs-empty
+    (table $t 10 externref)
+    (func $type-result-externref-vs-empty
       (table.get $t (i32.const 0))
     )
   )
@@ -68,8 +68,8 @@
 )
 (assert_invalid
   (module
-    (table $t 10 anyref)
-    (func $type-result-anyref-vs-funcref (result funcref)
+    (table $t 10 externref)
+    (func $type-result-externref-vs-funcref (result funcref)
       (table.get $t (i32.const 1))
     )
   )
@@ -79,8 +79,8 @@
 (assert_invalid
   (module
     (table $t1 1 funcref)
-    (table $t2 1 anyref)
-    (func $type-result-anyref-vs-funcref-multi (result funcref)
+    (table $t2 1 externref)
+    (func $type-result-externref-vs-funcref-multi (result funcref)
       (table.get $t2 (i32.const 0))
     )
   )
@@ -1,17 +1,17 @@
 (module
-  (table $t2 1 anyref)
+  (table $t2 1 externref)
   (table $t3 2 funcref)
   (elem (table $t3) (i32.const 1) func $dummy)
   (func $dummy)
 
-  (func (export "get-anyref") (param $i i32) (result anyref)
+  (func (export "get-externref") (param $i i32) (result externref)
     (table.get $t2 (local.get $i))
   )
   (func $f3 (export "get-funcref") (param $i i32) (result funcref)
     (table.get $t3 (local.get $i))
   )
 
-  (func (export "set-anyref") (param $i i32) (param $r anyref)
+  (func (export "set-externref") (param $i i32) (param $r externref)
     (table.set $t2 (local.get $i) (local.get $r))
   )
   (func (export "set-funcref") (param $i i32) (param $r funcref)
@@ -26,26 +26,26 @@
   )
 )
 
-(assert_return (invoke "get-anyref" (i32.const 0)) (ref.null))
-(assert_return (invoke "set-anyref" (i32.const 0) (ref.host 1)))
-(assert_return (invoke "get-anyref" (i32.const 0)) (ref.host 1))
-(assert_return (invoke "set-anyref" (i32.const 0) (ref.null)))
-(assert_return (invoke "get-anyref" (i32.const 0)) (ref.null))
+(assert_return (invoke "get-externref" (i32.const 0)) (ref.null extern))
+(assert_return (invoke "set-externref" (i32.const 0) (ref.extern 1)))
+(assert_return (invoke "get-externref" (i32.const 0)) (ref.extern 1))
+(assert_return (invoke "set-externref" (i32.const 0) (ref.null extern)))
+(assert_return (invoke "get-externref" (i32.const 0)) (ref.null extern))
 
-(assert_return (invoke "get-funcref" (i32.const 0)) (ref.null))
+(assert_return (invoke "get-funcref" (i32.const 0)) (ref.null func))
 (assert_return (invoke "set-funcref-from" (i32.const 0) (i32.const 1)))
 (assert_return (invoke "is_null-funcref" (i32.const 0)) (i32.const 0))
-(assert_return (invoke "set-funcref" (i32.const 0) (ref.null)))
-(assert_return (invoke "get-funcref" (i32.const 0)) (ref.null))
+(assert_return (invoke "set-funcref" (i32.const 0) (ref.null func)))
+(assert_return (invoke "get-funcref" (i32.const 0)) (ref.null func))
 
-(assert_trap (invoke "set-anyref" (i32.const 2) (ref.null)) "out of bounds")
-(assert_trap (invoke "set-funcref" (i32.const 3) (ref.null)) "out of bounds")
-(assert_trap (invoke "set-anyref" (i32.const -1) (ref.null)) "out of bounds")
-(assert_trap (invoke "set-funcref" (i32.const -1) (ref.null)) "out of bounds")
+(assert_trap (invoke "set-externref" (i32.const 2) (ref.null extern)) "out of bounds")
+(assert_trap (invoke "set-funcref" (i32.const 3) (ref.null func)) "out of bounds")
+(assert_trap (invoke "set-externref" (i32.const -1) (ref.null extern)) "out of bounds")
+(assert_trap (invoke "set-funcref" (i32.const -1) (ref.null func)) "out of bounds")
 
-(assert_trap (invoke "set-anyref" (i32.const 2) (ref.host 0)) "out of bounds")
+(assert_trap (invoke "set-externref" (i32.const 2) (ref.extern 0)) "out of bounds")
 (assert_trap (invoke "set-funcref-from" (i32.const 3) (i32.const 1)) "out of bounds")
-(assert_trap (invoke "set-anyref" (i32.const -1) (ref.host 0)) "out of bounds")
+(assert_trap (invoke "set-externref" (i32.const -1) (ref.extern 0)) "out of bounds")
 (assert_trap (invoke "set-funcref-from" (i32.const -1) (i32.const 1)) "out of bounds")
 
 
@@ -53,8 +53,8 @@
 
 (assert_invalid
   (module
-    (table $t 10 anyref)
-    (func $type-index-value-empty-vs-i32-anyref 
+    (table $t 10 externref)
+    (func $type-index-value-empty-vs-i32-externref 
       (table.set $t)
     )
   )
@@ -62,17 +62,17 @@
 )
 (assert_invalid
   (module
-    (table $t 10 anyref)
+    (table $t 10 externref)
     (func $type-index-empty-vs-i32
-      (table.set $t (ref.null))
+      (table.set $t (ref.null extern))
     )
   )
   "type mismatch"
 )
 (assert_invalid
   (module
-    (table $t 10 anyref)
-    (func $type-value-empty-vs-anyref
+    (table $t 10 externref)
+    (func $type-value-empty-vs-externref
       (table.set $t (i32.const 1))
     )
   )
@@ -80,9 +80,9 @@
 )
 (assert_invalid
   (module
-    (table $t 10 anyref)
+    (table $t 10 externref)
     (func $type-size-f32-vs-i32
-      (table.set $t (f32.const 1) (ref.null))
+      (table.set $t (f32.const 1) (ref.null extern))
     )
   )
   "type mismatch"
@@ -90,7 +90,7 @@
 (assert_invalid
   (module
     (table $t 10 funcref)
-    (func $type-value-anyref-vs-funcref (param $r anyref)
+    (func $type-value-externref-vs-funcref (param $r externref)
       (table.set $t (i32.const 1) (local.get $r))
     )
   )
@@ -99,9 +99,9 @@
 
 (assert_invalid
   (module
-    (table $t1 1 anyref)
+    (table $t1 1 externref)
     (table $t2 1 funcref)
-    (func $type-value-anyref-vs-funcref-multi (param $r anyref)
+    (func $type-value-externref-vs-funcref-multi (param $r externref)
       (table.set $t2 (i32.const 0) (local.get $r))
     )
   )
@@ -110,9 +110,9 @@
 
 (assert_invalid
   (module
-    (table $t 10 anyref)
+    (table $t 10 externref)
     (func $type-result-empty-vs-num (result i32)
-      (table.set $t (i32.const 0) (ref.null))
+      (table.set $t (i32.const 0) (ref.null extern))
     )
   )
   "type mismatch"
@@ -1,8 +1,8 @@
 (module
-  (table $t0 0 anyref)
-  (table $t1 1 anyref)
-  (table $t2 0 2 anyref)
-  (table $t3 3 8 anyref)
+  (table $t0 0 externref)
+  (table $t1 1 externref)
+  (table $t2 0 2 externref)
+  (table $t3 3 8 externref)
 
   (func (export "size-t0") (result i32) (table.size $t0))
   (func (export "size-t1") (result i32) (table.size $t1))
@@ -10,16 +10,16 @@
   (func (export "size-t3") (result i32) (table.size $t3))
 
   (func (export "grow-t0") (param $sz i32)
-    (drop (table.grow $t0 (ref.null) (local.get $sz)))
+    (drop (table.grow $t0 (ref.null extern) (local.get $sz)))
   )
   (func (export "grow-t1") (param $sz i32)
-    (drop (table.grow $t1 (ref.null) (local.get $sz)))
+    (drop (table.grow $t1 (ref.null extern) (local.get $sz)))
   )
   (func (export "grow-t2") (param $sz i32)
-    (drop (table.grow $t2 (ref.null) (local.get $sz)))
+    (drop (table.grow $t2 (ref.null extern) (local.get $sz)))
   )
   (func (export "grow-t3") (param $sz i32)
-    (drop (table.grow $t3 (ref.null) (local.get $sz)))
+    (drop (table.grow $t3 (ref.null extern) (local.get $sz)))
   )
 )
 
@@ -68,7 +68,7 @@
 
 (assert_invalid
   (module
-    (table $t 1 anyref)
+    (table $t 1 externref)
     (func $type-result-i32-vs-empty
       (table.size $t)
     )
@@ -77,7 +77,7 @@
 )
 (assert_invalid
   (module
-    (table $t 1 anyref)
+    (table $t 1 externref)
     (func $type-result-i32-vs-f32 (result f32)
       (table.size $t)
     )
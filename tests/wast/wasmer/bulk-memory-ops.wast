@@ -0,0 +1,54 @@
+;; Bulk memory operators lowered through the VM builtins.
+;;
+;; Values are kept live in registers across the builtin calls to make
+;; sure the calls save and restore them.
+
+(module
+  (memory 1)
+  (data $d "\01\02\03\04")
+  (table 4 funcref)
+  (elem $e func $one $two)
+
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+
+  (func (export "fill") (param $dst i32) (param $val i32) (param $len i32) (result i32)
+    (local.get $dst)
+    (memory.fill (local.get $dst) (local.get $val) (local.get $len))
+    (i32.load8_u))
+
+  (func (export "copy") (param $dst i32) (param $src i32) (param $len i32) (result i32)
+    (i32.store (i32.const 0) (i32.const 0x04030201))
+    (memory.copy (local.get $dst) (local.get $src) (local.get $len))
+    (i32.load (local.get $dst)))
+
+  (func (export "init") (param $dst i32) (result i32)
+    (memory.init $d (local.get $dst) (i32.const 1) (i32.const 3))
+    (i32.load8_u (i32.add (local.get $dst) (i32.const 2))))
+
+  (func (export "drop-data")
+    (data.drop $d))
+
+  (func (export "table-init-copy") (result i32)
+    (table.init $e (i32.const 0) (i32.const 0) (i32.const 2))
+    (table.copy (i32.const 2) (i32.const 0) (i32.const 2))
+    (i32.add
+      (call_indirect (result i32) (i32.const 2))
+      (call_indirect (result i32) (i32.const 3))))
+
+  (func (export "drop-elem")
+    (elem.drop $e))
+
+  (func (export "table-init-again")
+    (table.init $e (i32.const 0) (i32.const 0) (i32.const 1)))
+)
+
+(assert_return (invoke "fill" (i32.const 16) (i32.const 0xab) (i32.const 8)) (i32.const 0xab))
+(assert_trap (invoke "fill" (i32.const 65535) (i32.const 0) (i32.const 2)) "out of bounds memory access")
+(assert_return (invoke "copy" (i32.const 1) (i32.const 0) (i32.const 4)) (i32.const 0x04030201))
+(assert_return (invoke "init" (i32.const 32)) (i32.const 4))
+(invoke "drop-data")
+(assert_trap (invoke "init" (i32.const 32)) "out of bounds memory access")
+(assert_return (invoke "table-init-copy") (i32.const 3))
+(invoke "drop-elem")
+(assert_trap (invoke "table-init-again") "out of bounds table access")
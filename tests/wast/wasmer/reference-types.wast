@@ -0,0 +1,79 @@
+;; `ref.func` and the `funcref` table operators: `table.get`, `table.set`,
+;; `table.grow`, `table.fill` and `table.size`.
+
+(module
+  (type $ret-i32 (func (result i32)))
+
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+  (func $id (param funcref) (result funcref) (local.get 0))
+
+  (table $t 3 funcref)
+  (table $u 0 4 funcref)
+  (elem declare func $two)
+
+  (global $g (mut funcref) (ref.func $one))
+  (global $null funcref (ref.null func))
+
+  (func (export "call") (param i32) (result i32)
+    (call_indirect $t (type $ret-i32) (local.get 0)))
+  (func (export "call-u") (param i32) (result i32)
+    (call_indirect $u (type $ret-i32) (local.get 0)))
+
+  (func (export "set-two") (param i32)
+    (table.set $t (local.get 0) (call $id (ref.func $two))))
+  (func (export "set-global") (param i32)
+    (table.set $t (local.get 0) (global.get $g)))
+  (func (export "set-null") (param i32)
+    (table.set $t (local.get 0) (ref.null func)))
+  (func (export "move") (param i32 i32)
+    (table.set $t (local.get 1) (table.get $t (local.get 0))))
+  (func (export "is-null") (param i32) (result i32)
+    (ref.is_null (table.get $t (local.get 0))))
+  (func (export "global-is-null") (result i32)
+    (ref.is_null (global.get $null)))
+
+  (func (export "grow-u") (param i32) (result i32)
+    (table.grow $u (ref.func $one) (local.get 0)))
+  (func (export "size-u") (result i32)
+    (table.size $u))
+  (func (export "fill-u") (param i32 i32)
+    (table.fill $u (local.get 0) (ref.func $two) (local.get 1)))
+  (func (export "fill-u-null") (param i32 i32)
+    (table.fill $u (local.get 0) (ref.null func) (local.get 1)))
+)
+
+(assert_return (invoke "is-null" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "global-is-null") (i32.const 1))
+(assert_trap (invoke "call" (i32.const 0)) "uninitialized element")
+
+(invoke "set-two" (i32.const 0))
+(invoke "set-global" (i32.const 1))
+(assert_return (invoke "is-null" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "call" (i32.const 0)) (i32.const 2))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 1))
+
+(invoke "move" (i32.const 0) (i32.const 2))
+(assert_return (invoke "call" (i32.const 2)) (i32.const 2))
+(invoke "set-null" (i32.const 0))
+(assert_return (invoke "is-null" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 2)) (i32.const 2))
+
+(assert_trap (invoke "is-null" (i32.const 3)) "out of bounds table access")
+(assert_trap (invoke "set-null" (i32.const 3)) "out of bounds table access")
+
+(assert_return (invoke "size-u") (i32.const 0))
+(assert_return (invoke "grow-u" (i32.const 3)) (i32.const 0))
+(assert_return (invoke "size-u") (i32.const 3))
+(assert_return (invoke "call-u" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "grow-u" (i32.const 2)) (i32.const -1))
+(assert_return (invoke "size-u") (i32.const 3))
+
+(invoke "fill-u" (i32.const 1) (i32.const 2))
+(assert_return (invoke "call-u" (i32.const 0)) (i32.const 1))
+(assert_return (invoke "call-u" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "call-u" (i32.const 2)) (i32.const 2))
+(invoke "fill-u-null" (i32.const 0) (i32.const 1))
+(assert_trap (invoke "call-u" (i32.const 0)) "uninitialized element")
+(assert_trap (invoke "fill-u" (i32.const 2) (i32.const 2)) "out of bounds table access")
+(assert_return (invoke "call-u" (i32.const 2)) (i32.const 2))
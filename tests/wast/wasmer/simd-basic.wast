@@ -0,0 +1,70 @@
+;; Basic `v128` operations.
+;;
+;; The exported functions only take and return scalars, so the values
+;; also go through locals, memory and lane accesses.
+
+(module
+  (memory 1)
+
+  (func (export "i32x4-add") (param $a i32) (param $b i32) (result i32)
+    (local $v v128)
+    (local.set $v (i32x4.add (i32x4.splat (local.get $a)) (i32x4.splat (local.get $b))))
+    (i32x4.extract_lane 3 (local.get $v)))
+
+  (func (export "i16x8-mul") (param $a i32) (param $b i32) (result i32)
+    (i16x8.extract_lane_s 5 (i16x8.mul (i16x8.splat (local.get $a)) (i16x8.splat (local.get $b)))))
+
+  (func (export "i8x16-extract-s") (param $a i32) (result i32)
+    (i8x16.extract_lane_s 9 (i8x16.splat (local.get $a))))
+
+  (func (export "i8x16-extract-u") (param $a i32) (result i32)
+    (i8x16.extract_lane_u 9 (i8x16.splat (local.get $a))))
+
+  (func (export "i64x2-replace") (param $a i64) (result i64)
+    (i64x2.extract_lane 1
+      (i64x2.replace_lane 1 (v128.const i64x2 1 2) (local.get $a))))
+
+  (func (export "f64x2-add") (param $a f64) (param $b f64) (result f64)
+    (f64x2.extract_lane 0 (f64x2.add (f64x2.splat (local.get $a)) (f64x2.splat (local.get $b)))))
+
+  (func (export "f32x4-neg") (param $a f32) (result f32)
+    (f32x4.extract_lane 2 (f32x4.neg (f32x4.splat (local.get $a)))))
+
+  (func (export "lt-u") (param $a i32) (param $b i32) (result i32)
+    (i32x4.extract_lane 0 (i32x4.lt_u (i32x4.splat (local.get $a)) (i32x4.splat (local.get $b)))))
+
+  (func (export "bitselect") (result i64)
+    (i64x2.extract_lane 0
+      (v128.bitselect
+        (v128.const i64x2 0x1111111111111111 0)
+        (v128.const i64x2 0x2222222222222222 0)
+        (v128.const i64x2 0x00000000ffffffff 0))))
+
+  (func (export "shuffle") (result i32)
+    (i32x4.extract_lane 0
+      (v8x16.shuffle 16 1 18 3 4 5 6 7 8 9 10 11 12 13 14 15
+        (v128.const i8x16 0 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15)
+        (v128.const i8x16 16 17 18 19 20 21 22 23 24 25 26 27 28 29 30 31))))
+
+  (func (export "all-true") (param $a i32) (result i32)
+    (i32x4.all_true (i32x4.replace_lane 2 (v128.const i32x4 1 1 1 1) (local.get $a))))
+
+  (func (export "load-store") (param $a i32) (result i32)
+    (v128.store (i32.const 16) (i32x4.splat (local.get $a)))
+    (i32x4.extract_lane 0
+      (i32x4.add (v128.load (i32.const 16)) (v128.load (i32.const 16))))))
+
+(assert_return (invoke "i32x4-add" (i32.const 40) (i32.const 2)) (i32.const 42))
+(assert_return (invoke "i16x8-mul" (i32.const 300) (i32.const 300)) (i32.const 24464))
+(assert_return (invoke "i8x16-extract-s" (i32.const 0xff)) (i32.const -1))
+(assert_return (invoke "i8x16-extract-u" (i32.const 0xff)) (i32.const 255))
+(assert_return (invoke "i64x2-replace" (i64.const 7)) (i64.const 7))
+(assert_return (invoke "f64x2-add" (f64.const 1.5) (f64.const 2.25)) (f64.const 3.75))
+(assert_return (invoke "f32x4-neg" (f32.const 2.5)) (f32.const -2.5))
+(assert_return (invoke "lt-u" (i32.const 1) (i32.const -1)) (i32.const -1))
+(assert_return (invoke "lt-u" (i32.const -1) (i32.const 1)) (i32.const 0))
+(assert_return (invoke "bitselect") (i64.const 0x2222222211111111))
+(assert_return (invoke "shuffle") (i32.const 0x03120110))
+(assert_return (invoke "all-true" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "all-true" (i32.const 8)) (i32.const 1))
+(assert_return (invoke "load-store" (i32.const 21)) (i32.const 42))
@@ -0,0 +1,12 @@
+;; `select` of `v128` values.
+
+(module
+  (func (export "select") (param $c i32) (result i32)
+    (i32x4.extract_lane 1
+      (select (result v128)
+        (v128.const i32x4 1 2 3 4)
+        (v128.const i32x4 5 6 7 8)
+        (local.get $c)))))
+
+(assert_return (invoke "select" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "select" (i32.const 0)) (i32.const 6))
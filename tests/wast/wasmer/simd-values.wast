@@ -0,0 +1,94 @@
+;; `v128` values passed through signatures, blocks, globals and tables.
+
+(module
+  (type $binop (func (param v128 v128) (result v128)))
+  (table 1 funcref)
+  (elem (i32.const 0) $add)
+  (global $g (mut v128) (v128.const i32x4 0 0 0 0))
+
+  (func $add (param v128 v128) (result v128)
+    (i32x4.add (local.get 0) (local.get 1)))
+
+  ;; The last parameters don't fit in registers.
+  (func $sum (param i32 v128 i64 v128 f64 v128 v128) (result v128)
+    (i32x4.add
+      (i32x4.add (local.get 1) (local.get 3))
+      (i32x4.add (local.get 5) (local.get 6))))
+
+  (func $swap (param v128 i32 v128) (result v128 i32 v128)
+    (local.get 2) (local.get 1) (local.get 0))
+
+  (func (export "call") (param i32) (result i32)
+    (i32x4.extract_lane 3
+      (call $add (i32x4.splat (local.get 0)) (v128.const i32x4 1 2 3 4))))
+
+  (func (export "call-indirect") (param i32) (result i32)
+    (i32x4.extract_lane 2
+      (call_indirect (type $binop)
+        (i32x4.splat (local.get 0)) (v128.const i32x4 1 2 3 4) (i32.const 0))))
+
+  (func (export "call-stack-params") (result i32)
+    (i32x4.extract_lane 1
+      (call $sum
+        (i32.const 1) (v128.const i32x4 1 2 3 4) (i64.const 2)
+        (v128.const i32x4 10 20 30 40) (f64.const 3)
+        (v128.const i32x4 100 200 300 400) (v128.const i32x4 1000 2000 3000 4000))))
+
+  (func (export "multi-value") (result i32)
+    (local $a v128)
+    (local $i i32)
+    (call $swap (v128.const i32x4 1 2 3 4) (i32.const 7) (v128.const i32x4 5 6 7 8))
+    (local.set $a)
+    (local.set $i)
+    (i32x4.extract_lane 3)
+    (i32.add (i32x4.extract_lane 0 (local.get $a)))
+    (i32.add (local.get $i)))
+
+  (func (export "block") (param i32) (result i32)
+    (i32x4.extract_lane 0
+      (block (result v128)
+        (v128.const i32x4 1 1 1 1)
+        (br_if 0 (local.get 0))
+        (drop)
+        (v128.const i32x4 2 2 2 2))))
+
+  (func (export "loop") (param i32) (result i32)
+    (local $v v128)
+    (local.set $v (v128.const i32x4 0 0 0 0))
+    (loop $l
+      (local.set $v (i32x4.add (local.get $v) (v128.const i32x4 1 2 3 4)))
+      (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+      (br_if $l (local.get 0)))
+    (i32x4.extract_lane 2 (local.get $v)))
+
+  (func (export "if") (param i32) (result i32)
+    (i32x4.extract_lane 1
+      (if (result v128) (local.get 0)
+        (then (v128.const i32x4 1 2 3 4))
+        (else (v128.const i32x4 5 6 7 8)))))
+
+  (func (export "global") (param i32) (result i32)
+    (global.set $g (i32x4.add (global.get $g) (i32x4.splat (local.get 0))))
+    (i32x4.extract_lane 3 (global.get $g)))
+
+  (func (export "roundtrip") (param v128) (result v128)
+    (local.get 0))
+
+  (func (export "i64x2-mul") (param v128 v128) (result v128)
+    (i64x2.mul (local.get 0) (local.get 1))))
+
+(assert_return (invoke "call" (i32.const 10)) (i32.const 14))
+(assert_return (invoke "call-indirect" (i32.const 10)) (i32.const 13))
+(assert_return (invoke "call-stack-params") (i32.const 2222))
+(assert_return (invoke "multi-value") (i32.const 16))
+(assert_return (invoke "block" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "block" (i32.const 0)) (i32.const 2))
+(assert_return (invoke "loop" (i32.const 3)) (i32.const 9))
+(assert_return (invoke "if" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "if" (i32.const 0)) (i32.const 6))
+(assert_return (invoke "global" (i32.const 5)) (i32.const 5))
+(assert_return (invoke "global" (i32.const 5)) (i32.const 10))
+(assert_return (invoke "roundtrip" (v128.const i32x4 1 2 3 4)) (v128.const i32x4 1 2 3 4))
+(assert_return
+  (invoke "i64x2-mul" (v128.const i64x2 0x100000003 -5) (v128.const i64x2 0x200000007 0x123456789))
+  (v128.const i64x2 0xd00000015 -0x5B05B05AD))
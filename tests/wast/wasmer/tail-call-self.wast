@@ -0,0 +1,28 @@
+;; Tail calls from a function to itself run in constant stack space, with
+;; the non-parameter locals reset on each call.
+
+(module
+  (func $count (export "count") (param $n i64) (param $acc i64) (result i64)
+    (local $seen i64)
+    ;; `$seen` must start at zero on every call.
+    (if (i64.ne (local.get $seen) (i64.const 0))
+      (then (unreachable)))
+    (local.set $seen (i64.const 1))
+    (if (result i64) (i64.eqz (local.get $n))
+      (then (local.get $acc))
+      (else
+        (return_call $count
+          (i64.sub (local.get $n) (i64.const 1))
+          (i64.add (local.get $acc) (local.get $seen))))))
+
+  (func $sum-f64 (export "sum-f64") (param $n i32) (param $acc f64) (result f64)
+    (if (result f64) (i32.eqz (local.get $n))
+      (then (local.get $acc))
+      (else
+        (return_call $sum-f64
+          (i32.sub (local.get $n) (i32.const 1))
+          (f64.add (local.get $acc) (f64.const 0.5)))))))
+
+(assert_return (invoke "count" (i64.const 0) (i64.const 0)) (i64.const 0))
+(assert_return (invoke "count" (i64.const 1_000_000) (i64.const 0)) (i64.const 1_000_000))
+(assert_return (invoke "sum-f64" (i32.const 1_000_000) (f64.const 0)) (f64.const 500_000))
@@ -0,0 +1,43 @@
+;; `memory.atomic.wait` and `memory.atomic.notify` from a single thread.
+
+(module
+  (memory 1 1 shared)
+
+  (func (export "wait32") (param $addr i32) (param $expected i32) (param $timeout i64) (result i32)
+    (memory.atomic.wait32 (local.get $addr) (local.get $expected) (local.get $timeout)))
+
+  (func (export "wait64") (param $addr i32) (param $expected i64) (param $timeout i64) (result i32)
+    (memory.atomic.wait64 (local.get $addr) (local.get $expected) (local.get $timeout)))
+
+  (func (export "wait32-offset") (param $addr i32) (result i32)
+    (memory.atomic.wait32 offset=4 (local.get $addr) (i32.const 0) (i64.const 0)))
+
+  (func (export "notify") (param $addr i32) (param $count i32) (result i32)
+    (memory.atomic.notify (local.get $addr) (local.get $count)))
+
+  (func (export "store") (param $addr i32) (param $value i32)
+    (i32.store (local.get $addr) (local.get $value))))
+
+;; The value doesn't match.
+(invoke "store" (i32.const 0) (i32.const 1))
+(assert_return (invoke "wait32" (i32.const 0) (i32.const 0) (i64.const -1)) (i32.const 1))
+;; Nobody notifies, so the waits time out.
+(assert_return (invoke "wait32" (i32.const 0) (i32.const 1) (i64.const 0)) (i32.const 2))
+(assert_return (invoke "wait64" (i32.const 8) (i64.const 0) (i64.const 1000)) (i32.const 2))
+(assert_return (invoke "notify" (i32.const 0) (i32.const 1)) (i32.const 0))
+
+(assert_trap (invoke "wait32" (i32.const 2) (i32.const 0) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "wait64" (i32.const 4) (i64.const 0) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "notify" (i32.const 65536) (i32.const 1)) "out of bounds memory access")
+;; The static offset is part of the address.
+(assert_trap (invoke "wait32-offset" (i32.const 65532)) "out of bounds memory access")
+
+(module
+  (memory 1 1)
+  (func (export "wait") (result i32)
+    (memory.atomic.wait32 (i32.const 0) (i32.const 0) (i64.const 0)))
+  (func (export "notify") (result i32)
+    (memory.atomic.notify (i32.const 0) (i32.const 1))))
+
+(assert_trap (invoke "wait") "expected shared memory")
+(assert_return (invoke "notify") (i32.const 0))